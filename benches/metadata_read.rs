@@ -0,0 +1,163 @@
+//! Benchmarks loading the metadata of files of a few sizes, and counts the allocations it takes.
+//!
+//! Run with `cargo bench --bench metadata_read`. The allocation counts are printed first, then how
+//! long opening takes with the tensors stored as tables and as columns.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    io::Cursor,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Instant,
+};
+
+use criterion::{criterion_group, BenchmarkId, Criterion};
+use tempfile::NamedTempFile;
+use tensorbuffers::{
+    Tensor, TensorBuffers, TensorBuffersRead, TensorBuffersReader, TensorBuffersWrite,
+    TensorBuffersWriter, WriterOptions,
+};
+use tokio::runtime::Runtime;
+
+/// Counts the allocations of the process, and the bytes they request.
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(new_size, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Numbers of tensors in the files, from a small model to one whose metadata is larger than the
+/// first read of the end of the file.
+const TENSOR_COUNTS: [usize; 3] = [10, 500, 5000];
+
+/// Number of opens `report_open_times` averages.
+const OPENS: u32 = 20;
+
+/// Writes a file with `tensors` small tensors, named like the layers of a model.
+fn write_file(runtime: &Runtime, tensors: usize) -> Vec<u8> {
+    write_file_with(runtime, tensors, WriterOptions::new())
+}
+
+/// Writes a file with `tensors` small tensors as `options` say.
+fn write_file_with(runtime: &Runtime, tensors: usize, options: WriterOptions) -> Vec<u8> {
+    let names = (0..tensors).map(|i| format!("layers.{}.weight", i)).collect::<Vec<_>>();
+    let data = [0.5f32; 16];
+    let tensors = names.iter().map(|name| Tensor::new(name, &data, vec![4, 4])).collect();
+    let mut bytes = Cursor::new(Vec::new());
+    let mut writer = TensorBuffersWriter::with_options(&mut bytes, options);
+    runtime.block_on(writer.write(tensors, vec![])).unwrap();
+    bytes.into_inner()
+}
+
+/// Returns the allocations `f` makes, and the bytes they request.
+fn count_allocations(f: impl FnOnce()) -> (usize, usize) {
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let bytes = ALLOCATED_BYTES.load(Ordering::Relaxed);
+    f();
+    (
+        ALLOCATIONS.load(Ordering::Relaxed) - allocations,
+        ALLOCATED_BYTES.load(Ordering::Relaxed) - bytes,
+    )
+}
+
+/// Prints the allocations one `TensorBuffers::open` and metadata load take, and those of reading
+/// the metadata again with a reader that already read it.
+fn report_allocations(runtime: &Runtime) {
+    for tensors in TENSOR_COUNTS {
+        let bytes = write_file(runtime, tensors);
+        let tmp = NamedTempFile::new().unwrap();
+        std::fs::write(tmp.path(), &bytes).unwrap();
+        let url = format!("file://{}", tmp.path().display());
+        let (allocations, allocated) = count_allocations(|| {
+            runtime.block_on(async {
+                let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+                tensor_buffers.features().await.unwrap();
+            })
+        });
+        println!("open/{}: {} allocations, {} bytes", tensors, allocations, allocated);
+
+        let file = runtime.block_on(tokio::fs::File::open(tmp.path())).unwrap();
+        let mut reader = TensorBuffersReader::new(file);
+        let mut metadata = Vec::new();
+        let mut read = || {
+            runtime.block_on(async {
+                metadata.resize(reader.get_metadata_size().await.unwrap(), 0);
+                reader.read_metadata(&mut metadata).await.unwrap();
+            })
+        };
+        read();
+        let (allocations, allocated) = count_allocations(read);
+        println!("read_metadata/{}: {} allocations, {} bytes", tensors, allocations, allocated);
+    }
+}
+
+/// Prints how long opening a file and loading its metadata takes on average, with the tensors
+/// stored as tables and as columns, and the size of the file.
+fn report_open_times(runtime: &Runtime) {
+    for tensors in TENSOR_COUNTS.into_iter().chain([100_000]) {
+        for columnar in [false, true] {
+            let options = WriterOptions::new().with_columnar_metadata(columnar);
+            let tmp = NamedTempFile::new().unwrap();
+            std::fs::write(tmp.path(), write_file_with(runtime, tensors, options)).unwrap();
+            let url = format!("file://{}", tmp.path().display());
+            let start = Instant::now();
+            for _ in 0..OPENS {
+                runtime.block_on(async {
+                    let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+                    tensor_buffers.tensor_count().await.unwrap();
+                });
+            }
+            let layout = if columnar { "columns" } else { "tables" };
+            let size = std::fs::metadata(tmp.path()).unwrap().len();
+            let elapsed = start.elapsed() / OPENS;
+            println!("open/{}/{}: {:?}, file of {} bytes", tensors, layout, elapsed, size);
+        }
+    }
+}
+
+fn bench_read_metadata(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    report_allocations(&runtime);
+    report_open_times(&runtime);
+
+    let mut group = c.benchmark_group("read_metadata");
+    for tensors in TENSOR_COUNTS {
+        let tmp = NamedTempFile::new().unwrap();
+        std::fs::write(tmp.path(), write_file(&runtime, tensors)).unwrap();
+        group.bench_function(BenchmarkId::from_parameter(tensors), |b| {
+            // One reader is reused across iterations, like the handles of an open file.
+            let file = runtime.block_on(tokio::fs::File::open(tmp.path())).unwrap();
+            let mut reader = TensorBuffersReader::new(file);
+            let mut metadata = Vec::new();
+            b.iter(|| {
+                runtime.block_on(async {
+                    metadata.resize(reader.get_metadata_size().await.unwrap(), 0);
+                    reader.read_metadata(&mut metadata).await.unwrap();
+                })
+            });
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_read_metadata);
+criterion::criterion_main!(benches);
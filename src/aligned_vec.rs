@@ -0,0 +1,141 @@
+use std::{
+    alloc::{self, Layout},
+    fmt,
+    mem::align_of,
+    ptr::{self, NonNull},
+    slice,
+};
+
+use bytemuck::{cast_slice, Pod};
+
+use crate::Result;
+
+/// Default alignment of tensor data loaded from a file, which suits AVX-512 and GPU uploads.
+pub(crate) const DEFAULT_ALIGNMENT: usize = 64;
+
+/// A fixed-size buffer whose start is aligned to a chosen power of two, which `Vec` can't
+/// guarantee beyond the alignment of `T`.
+pub(crate) struct AlignedVec<T> {
+    ptr: NonNull<T>,
+    len: usize,
+    align: usize,
+}
+
+// SAFETY: `AlignedVec` owns its elements like `Vec` does.
+unsafe impl<T: Send> Send for AlignedVec<T> {}
+// SAFETY: shared access only hands out `&[T]`.
+unsafe impl<T: Sync> Sync for AlignedVec<T> {}
+
+impl<T> AlignedVec<T> {
+    /// Returns the layout of the allocation, or `None` if nothing is allocated.
+    fn layout(len: usize, align: usize) -> Result<Option<Layout>> {
+        let layout = Layout::array::<T>(len)?.align_to(align)?;
+        Ok((layout.size() > 0).then_some(layout))
+    }
+
+    pub fn as_slice(&self) -> &[T] {
+        // SAFETY: `ptr` points to `len` initialized elements, or is dangling and aligned for an
+        // empty buffer.
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        // SAFETY: as in `as_slice`, and `&mut self` guarantees exclusive access.
+        unsafe { slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<T: Clone> AlignedVec<T> {
+    /// Creates a buffer of `len` copies of `value`.
+    ///
+    /// # Arguments
+    /// * `value` - The initial value of every element.
+    /// * `len` - The number of elements.
+    /// * `align` - The alignment in bytes, a power of two. The alignment of `T` is used if it is
+    ///   larger.
+    pub fn from_elem(value: T, len: usize, align: usize) -> Result<Self> {
+        if !align.is_power_of_two() {
+            return Err(format!("Alignment {} is not a power of two", align).into());
+        }
+        let align = align.max(align_of::<T>());
+        let ptr = match Self::layout(len, align)? {
+            Some(layout) => {
+                // SAFETY: the layout has a non-zero size.
+                let ptr = unsafe { alloc::alloc(layout) } as *mut T;
+                let Some(ptr) = NonNull::new(ptr) else {
+                    alloc::handle_alloc_error(layout);
+                };
+                for i in 0..len {
+                    // SAFETY: `i` is within the allocation, which holds `len` elements.
+                    unsafe { ptr::write(ptr.as_ptr().add(i), value.clone()) };
+                }
+                ptr
+            }
+            // An aligned dangling pointer is valid for empty slices and zero-sized types.
+            None => NonNull::new(align as *mut T).expect("alignment is non-zero"),
+        };
+        Ok(AlignedVec { ptr, len, align })
+    }
+}
+
+impl<T> Drop for AlignedVec<T> {
+    fn drop(&mut self) {
+        // SAFETY: the elements are initialized and dropped only here.
+        unsafe { ptr::drop_in_place(self.as_mut_slice()) };
+        if let Ok(Some(layout)) = Self::layout(self.len, self.align) {
+            // SAFETY: the buffer was allocated with this layout in `from_elem`.
+            unsafe { alloc::dealloc(self.ptr.as_ptr() as *mut u8, layout) };
+        }
+    }
+}
+
+impl<T: Clone> Clone for AlignedVec<T> {
+    fn clone(&self) -> Self {
+        let Some(first) = self.as_slice().first() else {
+            return AlignedVec { ptr: self.ptr, len: 0, align: self.align };
+        };
+        let mut clone = AlignedVec::from_elem(first.clone(), self.len, self.align)
+            .expect("the layout was valid for the original");
+        clone.as_mut_slice().clone_from_slice(self.as_slice());
+        clone
+    }
+}
+
+impl<T: Pod> AsRef<[u8]> for AlignedVec<T> {
+    fn as_ref(&self) -> &[u8] {
+        cast_slice(self.as_slice())
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for AlignedVec<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.as_slice().fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aligned_vec() {
+        for align in [1, 64, 4096] {
+            let mut data = AlignedVec::from_elem(0u16, 3, align).unwrap();
+            assert_eq!(data.as_slice().as_ptr() as usize % align, 0);
+            data.as_mut_slice()[1] = 7;
+            let clone = data.clone();
+            assert_eq!(clone.as_slice(), [0, 7, 0]);
+            assert_eq!(clone.as_slice().as_ptr() as usize % align, 0);
+        }
+        let empty = AlignedVec::from_elem(0f64, 0, 64).unwrap();
+        assert_eq!(empty.as_slice().as_ptr() as usize % 64, 0);
+        let clone = empty.clone();
+        drop(empty);
+        assert!(clone.as_slice().is_empty());
+        // The alignment of the element type is a lower bound.
+        let data = AlignedVec::from_elem(0u64, 1, 1).unwrap();
+        assert_eq!(data.as_slice().as_ptr() as usize % align_of::<u64>(), 0);
+        let error = AlignedVec::from_elem(0u8, 1, 48).unwrap_err();
+        assert_eq!(error.to_string(), "Alignment 48 is not a power of two");
+    }
+}
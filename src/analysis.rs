@@ -0,0 +1,340 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{
+    codec,
+    generated::tensor_buffers::{Compression, TensorMetadata},
+    read_mode::tensor_problem,
+    Result, TensorBuffers,
+};
+
+/// Size of the blocks `analyze` checks for zeros. Only whole blocks count, or the tail of a
+/// tensor, so scattered zero bytes aren't reported.
+const ZERO_BLOCK_SIZE: usize = 4096;
+
+/// How much of an uncompressed tensor `analyze` compresses to estimate how well all of it would.
+const COMPRESSION_SAMPLE_SIZE: usize = 1 << 20;
+
+/// Zstandard level of the compression estimates, the writer's default.
+const COMPRESSION_ESTIMATE_LEVEL: i32 = 3;
+
+/// Tensors whose data is identical, see [`TensorBuffers::analyze`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DuplicateGroup {
+    /// The xxHash64 of the tensors' data before compression.
+    pub hash: u64,
+    /// Bytes of each tensor's data before compression.
+    pub data_size: u64,
+    /// The tensors, in id order.
+    pub tensors: Vec<String>,
+    /// Bytes of the file holding copies of the data beyond the first, which deduplicating the
+    /// tensors would save.
+    pub wasted_bytes: u64,
+}
+
+/// A run of zeros in a tensor's data, see [`TensorBuffers::analyze`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ZeroRegion {
+    /// The tensor.
+    pub tensor: String,
+    /// Where the zeros start in the tensor's data before compression.
+    pub offset: u64,
+    /// Number of zero bytes.
+    pub size: u64,
+}
+
+/// How much smaller an uncompressed tensor would be stored with Zstandard, see
+/// [`TensorBuffers::analyze`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CompressionEstimate {
+    /// The tensor.
+    pub tensor: String,
+    /// Bytes the tensor's data takes in the file.
+    pub stored_size: u64,
+    /// Bytes the data would take compressed, extrapolated from its first MiB.
+    pub estimated_size: u64,
+}
+
+/// What [`TensorBuffers::analyze`] found.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AnalysisReport {
+    /// Number of tensors analyzed.
+    pub tensors: usize,
+    /// Bytes of the file holding tensor data, counting data that tensors share once.
+    pub stored_size: u64,
+    /// Tensors with identical data stored more than once, most wasteful first.
+    pub duplicates: Vec<DuplicateGroup>,
+    /// Bytes the duplicate groups waste.
+    pub duplicate_bytes: u64,
+    /// Runs of zeros of at least 4 KiB, and tensors whose data is all zeros, in id order.
+    pub zero_regions: Vec<ZeroRegion>,
+    /// Bytes the zero regions span before compression.
+    pub zero_bytes: u64,
+    /// Estimates for the tensors stored uncompressed, most savings first.
+    pub compression_estimates: Vec<CompressionEstimate>,
+    /// Bytes compressing the uncompressed tensors would save by the estimates.
+    pub compressible_bytes: u64,
+    /// Bytes of the file between tensors' data, left by alignment.
+    pub padding_bytes: u64,
+}
+
+impl TensorBuffers {
+    /// Reads every tensor's data once to find what repacking the file could save: tensors with
+    /// identical data stored more than once, runs of zeros, uncompressed tensors that would
+    /// compress well, and the padding alignment leaves between tensors. Data shared by aliases
+    /// or deduplicated tensors is read once, and each tensor's data is held in memory, within
+    /// the memory budget, while it is analyzed. Data stored in other files is analyzed too, but
+    /// doesn't count towards this file's sizes.
+    ///
+    /// # Returns
+    /// The statistics of the file. Errors are returned for files whose data can't be read.
+    pub async fn analyze(&self) -> Result<AnalysisReport> {
+        self.check_data_reads()?;
+        let mut report = AnalysisReport::default();
+        // The tensors of each content hash, and the stored regions holding that content.
+        let mut contents = BTreeMap::<(u64, u64), (Vec<String>, HashMap<Region, u64>)>::new();
+        // Each stored region's content hash, so shared data is read once.
+        let mut hashes = HashMap::<Region, u64>::new();
+        let mut local_regions = Vec::new();
+        // Unreadable tensors only remain in lenient mode, where loads skip them too.
+        let tensors = self.tensor_tables().await?.into_iter().flatten();
+        for tensor in tensors.filter(|tensor| tensor_problem(tensor).is_none()) {
+            report.tensors += 1;
+            let name = tensor.name().to_string();
+            let stored_size = codec::stored_size(&tensor);
+            let region = Region {
+                location: tensor.external().map(|external| external.location().to_string()),
+                offset: tensor.data_offset() as u64,
+                size: stored_size,
+            };
+            let hash = match hashes.get(&region) {
+                Some(&hash) => hash,
+                None => {
+                    let data = self.analyzed_data(&tensor).await?;
+                    let hash = xxhash_rust::xxh64::xxh64(&data, 0);
+                    report.zero_regions.extend(zero_regions(&name, &data));
+                    if tensor.compression() == Compression::None && !data.is_empty() {
+                        report.compression_estimates.push(estimate_compression(&name, &data)?);
+                    }
+                    if region.location.is_none() {
+                        local_regions.push((region.offset, stored_size as u64));
+                    }
+                    hashes.insert(region.clone(), hash);
+                    hash
+                }
+            };
+            let (names, regions) = contents.entry((hash, tensor.data_size() as u64)).or_default();
+            names.push(name);
+            regions.insert(region, stored_size as u64);
+        }
+
+        for ((hash, data_size), (tensors, regions)) in contents {
+            if regions.len() < 2 {
+                continue;
+            }
+            // Keeping the smallest copy saves the most.
+            let sizes = regions.values();
+            let wasted_bytes = sizes.clone().sum::<u64>() - sizes.min().copied().unwrap_or(0);
+            report.duplicates.push(DuplicateGroup { hash, data_size, tensors, wasted_bytes });
+        }
+        report.duplicates.sort_by(|a, b| b.wasted_bytes.cmp(&a.wasted_bytes));
+        report.duplicate_bytes = report.duplicates.iter().map(|group| group.wasted_bytes).sum();
+        report.zero_bytes = report.zero_regions.iter().map(|region| region.size).sum();
+        report
+            .compression_estimates
+            .retain(|estimate| estimate.estimated_size < estimate.stored_size);
+        report.compression_estimates.sort_by(|a, b| {
+            (b.stored_size - b.estimated_size).cmp(&(a.stored_size - a.estimated_size))
+        });
+        report.compressible_bytes = report
+            .compression_estimates
+            .iter()
+            .map(|estimate| estimate.stored_size - estimate.estimated_size)
+            .sum();
+
+        local_regions.sort_unstable();
+        let mut end = None;
+        for (offset, size) in local_regions {
+            report.stored_size += size;
+            if let Some(end) = end {
+                report.padding_bytes += offset.saturating_sub(end);
+            }
+            end = Some(end.map_or(offset + size, |end: u64| end.max(offset + size)));
+        }
+        Ok(report)
+    }
+
+    /// Reads a tensor's data, decompressed.
+    async fn analyzed_data(&self, tensor: &TensorMetadata<'_>) -> Result<Vec<u8>> {
+        let (readers, offset) = self.locate_data(tensor).await?;
+        let stored = self.read_bytes_from(&readers, offset, codec::stored_size(tensor)).await?;
+        if tensor.compression() == Compression::None {
+            return Ok(stored);
+        }
+        let dictionary = self.compression_dictionary(tensor).await?;
+        let codec = codec::custom_codec_id(tensor)?;
+        let mut data = vec![0; tensor.data_size() as usize];
+        codec::decompress(&stored, tensor.compression(), dictionary, codec, &mut data)?;
+        Ok(data)
+    }
+}
+
+/// Where a tensor's data is stored.
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct Region {
+    /// The file holding the data, or `None` for this file.
+    location: Option<String>,
+    offset: u64,
+    size: usize,
+}
+
+/// Returns the runs of whole zero blocks in the data of the tensor `name`. A zero tail shorter
+/// than a block counts when it joins a run or is all of the data.
+fn zero_regions(name: &str, data: &[u8]) -> Vec<ZeroRegion> {
+    let mut regions = Vec::new();
+    let mut run: Option<(usize, usize)> = None;
+    for (i, block) in data.chunks(ZERO_BLOCK_SIZE).enumerate() {
+        let start = i * ZERO_BLOCK_SIZE;
+        let whole = block.len() == ZERO_BLOCK_SIZE || start == 0 || run.is_some();
+        if whole && block.iter().all(|&byte| byte == 0) {
+            let (_, end) = run.get_or_insert((start, start));
+            *end = start + block.len();
+            continue;
+        }
+        regions.extend(run.take());
+    }
+    regions.extend(run);
+    regions
+        .into_iter()
+        .map(|(start, end)| ZeroRegion {
+            tensor: name.to_string(),
+            offset: start as u64,
+            size: (end - start) as u64,
+        })
+        .collect()
+}
+
+/// Estimates how small the data of the tensor `name` would be compressed with Zstandard, from
+/// how well its first MiB compresses.
+fn estimate_compression(name: &str, data: &[u8]) -> Result<CompressionEstimate> {
+    let sample = &data[..data.len().min(COMPRESSION_SAMPLE_SIZE)];
+    let compressed = codec::compress(sample, Compression::Zstd, COMPRESSION_ESTIMATE_LEVEL)?;
+    let estimated_size =
+        (compressed.len() as u128 * data.len() as u128 / sample.len() as u128) as u64;
+    Ok(CompressionEstimate {
+        tensor: name.to_string(),
+        stored_size: data.len() as u64,
+        estimated_size,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::NamedTempFile;
+    use tokio::fs::File;
+
+    use super::*;
+    use crate::{OpenOptions, Tensor, TensorBuffersWrite, TensorBuffersWriter, WriterOptions};
+
+    #[test]
+    fn test_zero_regions() {
+        let mut data = vec![1u8; 5 * ZERO_BLOCK_SIZE + 10];
+        data[ZERO_BLOCK_SIZE..3 * ZERO_BLOCK_SIZE].fill(0);
+        data[4 * ZERO_BLOCK_SIZE + 1..].fill(0);
+        let regions = zero_regions("x", &data);
+        let regions = regions.iter().map(|region| (region.offset, region.size)).collect::<Vec<_>>();
+        let block = ZERO_BLOCK_SIZE as u64;
+        assert_eq!(regions, [(block, 2 * block)]);
+
+        data[4 * ZERO_BLOCK_SIZE] = 0;
+        let regions = zero_regions("x", &data);
+        assert_eq!(regions.len(), 2);
+        assert_eq!((regions[1].offset, regions[1].size), (4 * block, block + 10));
+        assert_eq!(zero_regions("x", &[0; 8]).len(), 1);
+        assert!(zero_regions("x", &[0, 1]).is_empty());
+        assert!(zero_regions("x", &[]).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_analyze() {
+        let weights = (0..4096).map(|i| (i % 7) as f32).collect::<Vec<_>>();
+        let noise = (0..1024u32)
+            .map(|i| f32::from_bits(i.wrapping_mul(2654435761) >> 2 | 0x3f00_0000))
+            .collect::<Vec<_>>();
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        let options = WriterOptions::new().with_alignment(64);
+        let mut writer = TensorBuffersWriter::with_options(&mut file, options);
+        writer.add_alias("a", "a_alias");
+        writer
+            .write(
+                vec![
+                    Tensor::new("scale", &[0.5f32], vec![1]),
+                    Tensor::new("a", &weights, vec![4096]),
+                    Tensor::new("b", &weights, vec![64, 64]),
+                    Tensor::new("noise", &noise, vec![1024]),
+                    Tensor::new("zeros", &vec![0.0f32; 2048], vec![2048]),
+                ],
+                vec![],
+            )
+            .await
+            .unwrap();
+
+        let url = format!("file://{}", tmp.path().display());
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        let report = tensor_buffers.analyze().await.unwrap();
+        assert_eq!(report.tensors, 6);
+        assert_eq!(report.stored_size, 2 * 16384 + 4096 + 8192 + 4);
+
+        // The alias shares the data of `a`, so only the copy in `b` is wasted.
+        assert_eq!(report.duplicates.len(), 1);
+        let mut tensors = report.duplicates[0].tensors.clone();
+        tensors.sort();
+        assert_eq!(tensors, ["a", "a_alias", "b"]);
+        assert_eq!(report.duplicates[0].wasted_bytes, 16384);
+        assert_eq!(report.duplicate_bytes, 16384);
+
+        assert_eq!(report.zero_regions, [ZeroRegion {
+            tensor: "zeros".to_string(),
+            offset: 0,
+            size: 8192
+        }]);
+        assert_eq!(report.zero_bytes, 8192);
+
+        // The repetitive and zero tensors compress, the noise barely does.
+        let estimated =
+            report.compression_estimates.iter().map(|e| e.tensor.as_str()).collect::<Vec<_>>();
+        assert!(estimated.contains(&"a") && estimated.contains(&"zeros"));
+        assert!(report.compressible_bytes > 2 * 16384);
+        // Alignment pads the data of `scale` to 64 bytes.
+        assert_eq!(report.padding_bytes, 60);
+
+        // Compressed duplicates are found by their data, not how it is stored.
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        let options = WriterOptions::new().with_compression(Compression::Zstd, 3);
+        let mut writer = TensorBuffersWriter::with_options(&mut file, options);
+        writer
+            .write(
+                vec![
+                    Tensor::new("a", &weights, vec![4096]),
+                    Tensor::new("b", &weights, vec![4096]),
+                ],
+                vec![],
+            )
+            .await
+            .unwrap();
+        let url = format!("file://{}", tmp.path().display());
+        let report = TensorBuffers::open(&url).await.unwrap().analyze().await.unwrap();
+        assert_eq!(report.duplicates.len(), 1);
+        assert_eq!(report.duplicates[0].data_size, 16384);
+        assert!(report.duplicates[0].wasted_bytes < 16384);
+        assert!(report.compression_estimates.is_empty());
+        let options = OpenOptions::new().with_metadata_only(true);
+        let metadata_only = TensorBuffers::open_with(&url, options).await.unwrap();
+        assert!(metadata_only.analyze().await.is_err());
+    }
+}
@@ -0,0 +1,192 @@
+use half::f16;
+
+use crate::{simd, DataType, Result};
+
+/// Size of a Q4_K block's packed sub-block scales and mins.
+const Q4K_SCALES_SIZE: usize = 12;
+
+/// Converts the blocks in `data` to f32 values, one per element of `out`.
+///
+/// # Returns
+/// An error if `data_type` isn't block-quantized or `data` doesn't hold the blocks of `out`.
+pub(crate) fn dequantize_blocks(data_type: DataType, data: &[u8], out: &mut [f32]) -> Result<()> {
+    if !data_type.is_block_quantized() {
+        return Err(format!("{:?} is not a block-quantized data type", data_type).into());
+    }
+    let (block_size, block_bytes) = (data_type.block_size(), data_type.size());
+    if out.len() % block_size != 0 || data.len() != out.len() / block_size * block_bytes {
+        return Err(format!(
+            "{} bytes don't hold {} elements of {:?} blocks",
+            data.len(),
+            out.len(),
+            data_type
+        )
+        .into());
+    }
+    let blocks = data.chunks_exact(block_bytes).zip(out.chunks_exact_mut(block_size));
+    match data_type {
+        DataType::Q8_0 => blocks.for_each(|(block, out)| dequantize_q8_0(block, out)),
+        _ => blocks.for_each(|(block, out)| dequantize_q4_k(block, out)),
+    }
+    Ok(())
+}
+
+/// Reads the f16 at the start of `bytes`.
+fn read_f16(bytes: &[u8]) -> f32 {
+    f16::from_le_bytes([bytes[0], bytes[1]]).to_f32()
+}
+
+/// Dequantizes a Q8_0 block: `scale * value` for each of its 32 signed bytes.
+fn dequantize_q8_0(block: &[u8], out: &mut [f32]) {
+    let scale = read_f16(block);
+    simd::dequantize_i8(bytemuck::cast_slice(&block[2..]), 0, scale, out);
+}
+
+/// Returns the 6-bit scale and min of sub-block `j` of a Q4_K block, packed in `scales`: those of
+/// the first four sub-blocks in the low bits of the first eight bytes, and those of the last four
+/// split between the last four bytes and the high bits of the first eight.
+fn q4_k_scale_min(j: usize, scales: &[u8]) -> (u8, u8) {
+    if j < 4 {
+        (scales[j] & 63, scales[j + 4] & 63)
+    } else {
+        let scale = (scales[j + 4] & 0x0f) | ((scales[j - 4] >> 6) << 4);
+        let min = (scales[j + 4] >> 4) | ((scales[j] >> 6) << 4);
+        (scale, min)
+    }
+}
+
+/// Dequantizes a Q4_K block: each 32-value sub-block is `scale * value - min`, with its scale and
+/// min the block's f16 scale and min times the sub-block's 6-bit ones. Each 32 bytes of values
+/// hold two sub-blocks, the first in the low nibbles.
+fn dequantize_q4_k(block: &[u8], out: &mut [f32]) {
+    let (scale, min) = (read_f16(block), read_f16(&block[2..]));
+    let scales = &block[4..4 + Q4K_SCALES_SIZE];
+    let values = &block[4 + Q4K_SCALES_SIZE..];
+    for (j, (values, out)) in values.chunks_exact(32).zip(out.chunks_exact_mut(64)).enumerate() {
+        let (low_scale, low_min) = q4_k_scale_min(2 * j, scales);
+        let (high_scale, high_min) = q4_k_scale_min(2 * j + 1, scales);
+        let (low_scale, low_min) = (scale * low_scale as f32, min * low_min as f32);
+        let (high_scale, high_min) = (scale * high_scale as f32, min * high_min as f32);
+        let (low, high) = out.split_at_mut(32);
+        for ((&byte, low), high) in values.iter().zip(low).zip(high) {
+            *low = low_scale * (byte & 0x0f) as f32 - low_min;
+            *high = high_scale * (byte >> 4) as f32 - high_min;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "async")]
+    use tempfile::NamedTempFile;
+    #[cfg(feature = "async")]
+    use tokio::fs::File;
+
+    use super::*;
+    use crate::Tensor;
+    #[cfg(feature = "async")]
+    use crate::{
+        utils::hash_key, TensorBuffers, TensorBuffersWrite, TensorBuffersWriter, WriterOptions,
+    };
+
+    /// Builds a Q8_0 block of `scale` and `values`.
+    fn q8_0_block(scale: f32, values: impl Fn(usize) -> i8) -> Vec<u8> {
+        let mut block = f16::from_f32(scale).to_le_bytes().to_vec();
+        block.extend((0..32).map(|i| values(i) as u8));
+        block
+    }
+
+    /// Packs the 6-bit scales and mins of a Q4_K block's sub-blocks.
+    fn pack_scales(scales: [u8; 8], mins: [u8; 8]) -> [u8; 12] {
+        let mut packed = [0; 12];
+        for j in 0..4 {
+            packed[j] = scales[j] | ((scales[j + 4] >> 4) << 6);
+            packed[j + 4] = mins[j] | ((mins[j + 4] >> 4) << 6);
+            packed[j + 8] = (scales[j + 4] & 0x0f) | ((mins[j + 4] & 0x0f) << 4);
+        }
+        packed
+    }
+
+    #[test]
+    fn test_dequantize_q8_0() {
+        let data = [q8_0_block(0.5, |i| i as i8 - 16), q8_0_block(-2.0, |i| i as i8)].concat();
+        let tensor = Tensor::from_blocks("w", DataType::Q8_0, data.clone(), vec![2, 32]).unwrap();
+        assert_eq!(tensor.data_elements(), 64);
+        let values = tensor.dequantize_blocks().unwrap();
+        assert_eq!(values.shape(), [2, 32]);
+        assert_eq!(values.data()[..3], [-8.0, -7.5, -7.0]);
+        assert_eq!(values.data()[32..35], [0.0, -2.0, -4.0]);
+
+        let error = Tensor::from_blocks("w", DataType::Q8_0, data.clone(), vec![4, 16]);
+        assert_eq!(
+            error.unwrap_err().to_string(),
+            "Tensor w has rows of 16 elements, not a whole number of 32-element Q8_0 blocks"
+        );
+        assert!(Tensor::from_blocks("w", DataType::Q8_0, data.clone(), vec![96]).is_err());
+        assert!(Tensor::from_blocks("w", DataType::UInt8, data, vec![68]).is_err());
+        let plain = Tensor::from_vec("w", vec![1u8; 4], vec![4]);
+        assert!(plain.dequantize_blocks().is_err());
+    }
+
+    #[test]
+    fn test_dequantize_q4_k() {
+        // Scales and mins above 15 exercise the high bits packed apart from the rest.
+        let scales = [1, 2, 3, 4, 17, 33, 48, 63];
+        let mins = [0, 1, 2, 3, 16, 31, 40, 62];
+        let mut block =
+            [f16::from_f32(0.5).to_le_bytes(), f16::from_f32(0.25).to_le_bytes()].concat();
+        block.extend(pack_scales(scales, mins));
+        block.extend((0..128).map(|i| (i % 16) as u8 | (15 - i % 16) << 4));
+        assert_eq!(block.len(), DataType::Q4K.size());
+        for j in 0..8 {
+            assert_eq!(q4_k_scale_min(j, &pack_scales(scales, mins)), (scales[j], mins[j]));
+        }
+
+        let tensor = Tensor::from_blocks("w", DataType::Q4K, block.clone(), vec![256]).unwrap();
+        let values = tensor.dequantize_blocks().unwrap();
+        for (i, &value) in values.data().iter().enumerate() {
+            let (j, l) = (i / 32, i % 32);
+            // Even sub-blocks hold the low nibbles of their 32 bytes, odd ones the high nibbles.
+            let byte = block[16 + (j / 2) * 32 + l];
+            let nibble = if j % 2 == 0 { byte & 0x0f } else { byte >> 4 };
+            let expected = 0.5 * scales[j] as f32 * nibble as f32 - 0.25 * mins[j] as f32;
+            assert_eq!(value, expected, "element {}", i);
+        }
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn test_block_quantized_round_trip() {
+        let data = (0..4).flat_map(|row| q8_0_block(0.25, move |i| (i + row) as i8)).collect();
+        let tensor = Tensor::from_blocks("w", DataType::Q8_0, data, vec![2, 64]).unwrap();
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        let mut writer = TensorBuffersWriter::new(&mut file);
+        writer.write(vec![tensor.clone()], vec![]).await.unwrap();
+
+        let url = format!("file://{}", tmp.path().display());
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        assert_eq!(tensor_buffers.features().await.unwrap(), ["block_quantization"]);
+        let described = tensor_buffers.get_tensor_metadata(hash_key("w")).await.unwrap();
+        assert_eq!((described.data_type, described.data_size), (DataType::Q8_0, 136));
+        // The blocks load as they were written.
+        let read = tensor_buffers.get_tensor_data_by_name::<u8>("w").await.unwrap();
+        assert_eq!((read.shape(), read.data_type()), (&[2, 64][..], DataType::Q8_0));
+        assert_eq!(read.data(), tensor.data());
+        let values = tensor_buffers.get_tensor_dequantized("w").await.unwrap();
+        assert_eq!(values.data(), tensor.dequantize_blocks().unwrap().data());
+        assert_eq!(values.data()[64..67], [0.5, 0.75, 1.0]);
+        assert!(tensor_buffers.get_tensor_data_by_name::<f32>("w").await.is_err());
+
+        let options = WriterOptions::new().with_format_version("1.3.0");
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        let error = TensorBuffersWriter::with_options(&mut bytes, options)
+            .write(vec![tensor], vec![])
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Format version 1.3.0 does not support block-quantized data types, needed by tensor w"
+        );
+    }
+}
@@ -0,0 +1,125 @@
+#[cfg(feature = "async")]
+use crate::{
+    footer::{Footer, MAX_FOOTER_SIZE},
+    TensorBuffers,
+};
+use crate::{Result, TensorId};
+
+/// Filter bits per tensor, which with `HASHES` hashes gives about 1% false positives.
+const BITS_PER_TENSOR: usize = 10;
+
+/// Number of bits set per tensor.
+const HASHES: u32 = 7;
+
+/// Most hashes a filter read from a file may use, which bounds the work of a lookup.
+const MAX_HASHES: u32 = 32;
+
+#[cfg(feature = "async")]
+/// Bytes read from the end of the file to find the filter, which usually covers the whole filter
+/// so it takes a single read.
+const TAIL_SIZE: u64 = 64 << 10;
+
+/// A bloom filter over tensor ids, stored in the footer so readers can rule out missing tensors
+/// without loading the metadata.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct BloomFilter {
+    bits: Vec<u8>,
+    hashes: u32,
+}
+
+impl BloomFilter {
+    /// Creates a filter sized for the given tensors, holding all of them.
+    pub fn new(ids: &[TensorId]) -> Self {
+        let mut filter = BloomFilter {
+            bits: vec![0; (ids.len() * BITS_PER_TENSOR).div_ceil(8)],
+            hashes: HASHES,
+        };
+        for &id in ids {
+            for bit in filter.positions(id).collect::<Vec<_>>() {
+                filter.bits[bit / 8] |= 1 << (bit % 8);
+            }
+        }
+        filter
+    }
+
+    /// Creates a filter from its stored bits and number of hashes.
+    pub fn from_parts(bits: Vec<u8>, hashes: u32) -> Result<Self> {
+        if hashes == 0 || hashes > MAX_HASHES {
+            return Err(format!("Invalid number of bloom filter hashes {}", hashes).into());
+        }
+        Ok(BloomFilter { bits, hashes })
+    }
+
+    pub fn bits(&self) -> &[u8] {
+        &self.bits
+    }
+
+    pub fn hashes(&self) -> u32 {
+        self.hashes
+    }
+
+    /// Returns whether the filter may hold `id`. Ids it doesn't hold are certainly missing.
+    pub fn may_contain(&self, id: TensorId) -> bool {
+        // An empty filter comes from a file without tensors.
+        !self.bits.is_empty()
+            && self.positions(id).all(|bit| self.bits[bit / 8] & (1 << (bit % 8)) != 0)
+    }
+
+    /// Returns the bits of `id`, derived by double hashing the halves of its xxHash64. The filter
+    /// must not be empty.
+    fn positions(&self, id: TensorId) -> impl Iterator<Item = usize> + '_ {
+        let hash = xxhash_rust::xxh64::xxh64(&id.to_le_bytes(), 0);
+        let (first, second) = (hash & 0xffff_ffff, hash >> 32);
+        let size = self.bits.len() as u64 * 8;
+        (0..self.hashes as u64)
+            .map(move |i| (first.wrapping_add(i.wrapping_mul(second)) % size) as usize)
+    }
+}
+
+#[cfg(feature = "async")]
+/// Reads the file's bloom filter from its footer.
+///
+/// # Returns
+/// `None` if the file has no bloom filter.
+pub(crate) async fn read_bloom_filter(
+    tensor_buffers: &TensorBuffers,
+) -> Result<Option<BloomFilter>> {
+    let file_size = tensor_buffers.file_size().await?;
+    let tail_size = file_size.min(TAIL_SIZE) as usize;
+    let tail = tensor_buffers.read_bytes(file_size - tail_size as u64, tail_size).await?;
+    let footer = Footer::parse(&tail[tail_size.saturating_sub(MAX_FOOTER_SIZE)..], file_size)?;
+    let (Some(layout), Some(offset)) = (footer.filter, footer.filter_offset(file_size)) else {
+        return Ok(None);
+    };
+    let tail_start = file_size - tail_size as u64;
+    let bits = match offset.checked_sub(tail_start) {
+        Some(start) => tail[start as usize..start as usize + layout.size].to_vec(),
+        None => tensor_buffers.read_bytes(offset, layout.size).await?,
+    };
+    if crc32c::crc32c(&bits) != layout.checksum {
+        return Err("Bloom filter checksum mismatch".into());
+    }
+    Ok(Some(BloomFilter::from_parts(bits, layout.hashes)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::hash_key;
+
+    #[test]
+    fn test_bloom_filter() {
+        let ids = (0..1000).map(|i| hash_key(&format!("tensor.{}", i))).collect::<Vec<_>>();
+        let filter = BloomFilter::new(&ids);
+        assert_eq!(filter.bits().len(), 1250);
+        assert!(ids.iter().all(|&id| filter.may_contain(id)));
+        let false_positives =
+            (0..10_000).filter(|i| filter.may_contain(hash_key(&format!("missing.{}", i)))).count();
+        assert!(false_positives < 300, "{} false positives", false_positives);
+
+        assert!(!BloomFilter::new(&[]).may_contain(ids[0]));
+        let copy = BloomFilter::from_parts(filter.bits().to_vec(), filter.hashes()).unwrap();
+        assert_eq!(copy, filter);
+        assert!(BloomFilter::from_parts(vec![0; 8], 0).is_err());
+    }
+}
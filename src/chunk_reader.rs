@@ -0,0 +1,76 @@
+use std::{
+    io,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use bytes::{Buf, Bytes};
+use futures::Stream;
+use tokio::io::{AsyncRead, ReadBuf};
+
+/// Size of the chunks a tensor reader reads at a time.
+pub(crate) const READER_CHUNK_SIZE: usize = 64 << 10;
+
+/// Reads the bytes of a stream of chunks, polling the stream for the next chunk only once the
+/// current one has been read.
+pub(crate) struct ChunkReader<S> {
+    chunks: Pin<Box<S>>,
+    chunk: Bytes,
+    done: bool,
+}
+
+impl<S> ChunkReader<S>
+where
+    S: Stream<Item = io::Result<Bytes>>,
+{
+    pub fn new(chunks: S) -> Self {
+        ChunkReader { chunks: Box::pin(chunks), chunk: Bytes::new(), done: false }
+    }
+}
+
+impl<S> AsyncRead for ChunkReader<S>
+where
+    S: Stream<Item = io::Result<Bytes>>,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        while self.chunk.is_empty() && !self.done {
+            match ready!(self.chunks.as_mut().poll_next(cx)) {
+                Some(chunk) => self.chunk = chunk?,
+                None => self.done = true,
+            }
+        }
+        let len = self.chunk.len().min(buf.remaining());
+        buf.put_slice(&self.chunk[..len]);
+        self.chunk.advance(len);
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::stream;
+    use tokio::io::AsyncReadExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_chunk_reader() {
+        let chunks = ["ab", "", "cde", "f"].map(|chunk| Ok(Bytes::from(chunk)));
+        let mut reader = ChunkReader::new(stream::iter(chunks));
+        let mut buf = [0; 2];
+        assert_eq!(reader.read(&mut buf).await.unwrap(), 2);
+        assert_eq!(&buf, b"ab");
+        let mut rest = String::new();
+        reader.read_to_string(&mut rest).await.unwrap();
+        assert_eq!(rest, "cdef");
+
+        let chunks = vec![Ok(Bytes::from("ab")), Err(io::Error::other("broken"))];
+        let mut reader = ChunkReader::new(stream::iter(chunks));
+        let error = reader.read_to_end(&mut Vec::new()).await.unwrap_err();
+        assert_eq!(error.to_string(), "broken");
+    }
+}
@@ -0,0 +1,470 @@
+#[cfg(feature = "async")]
+use std::borrow::Cow;
+
+#[cfg(feature = "async")]
+use zstd::dict::EncoderDictionary;
+
+use crate::{
+    codec_registry::registered_codec,
+    generated::tensor_buffers::{ChecksumAlgorithm, Compression, TensorMetadata},
+    Result,
+};
+
+/// Where and how a tensor's data is stored in the file.
+#[cfg(feature = "async")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct StoredData {
+    pub offset: u64,
+    /// Size of the data in the file, which is smaller than the tensor's data if compressed.
+    pub size: usize,
+    pub compression: Compression,
+    pub checksum_algorithm: ChecksumAlgorithm,
+    pub checksum: u64,
+    /// The id of the registered codec the data is encoded with, for `Compression::Custom`.
+    pub codec: Option<&'static str>,
+}
+
+#[cfg(all(test, feature = "async"))]
+impl StoredData {
+    /// Describes `size` uncompressed bytes at `offset`, without a checksum.
+    pub fn raw(offset: u64, size: usize) -> Self {
+        StoredData {
+            offset,
+            size,
+            compression: Compression::None,
+            checksum_algorithm: ChecksumAlgorithm::None,
+            checksum: 0,
+            codec: None,
+        }
+    }
+}
+
+/// Returns how many bytes of the file hold the tensor's data.
+pub(crate) fn stored_size(metadata: &TensorMetadata) -> usize {
+    match metadata.compression() {
+        Compression::None => metadata.data_size() as usize,
+        _ => metadata.stored_size() as usize,
+    }
+}
+
+/// Returns the id of the custom codec a tensor's data is encoded with, as registered in this
+/// process, or `None` if it uses a built-in compression.
+///
+/// # Returns
+/// An error if the tensor uses a custom codec that isn't registered or names none.
+#[cfg(feature = "async")]
+pub(crate) fn custom_codec_id(metadata: &TensorMetadata) -> Result<Option<&'static str>> {
+    if metadata.compression() != Compression::Custom {
+        return Ok(None);
+    }
+    let id = metadata
+        .codec()
+        .ok_or_else(|| format!("Tensor {} uses a custom codec but names none", metadata.name()))?;
+    Ok(Some(registered_codec(id)?.id()))
+}
+
+/// A Zstandard dictionary trained on a file's tensor data, which compresses small tensors that
+/// barely compress on their own.
+#[cfg(feature = "async")]
+pub(crate) struct CompressionDictionary {
+    bytes: Vec<u8>,
+    /// The dictionary digested for the compression level, shared by every tensor.
+    encoder: EncoderDictionary<'static>,
+}
+
+#[cfg(feature = "async")]
+impl CompressionDictionary {
+    /// Trains a dictionary of at most `max_size` bytes on `samples`.
+    ///
+    /// # Returns
+    /// `None` if Zstandard can't train a dictionary from them, e.g. because there are too few.
+    pub fn train(samples: &[&[u8]], max_size: usize, level: i32) -> Option<Self> {
+        let bytes = zstd::dict::from_samples(samples, max_size).ok()?;
+        let encoder = EncoderDictionary::copy(&bytes, level);
+        Some(CompressionDictionary { bytes, encoder })
+    }
+
+    /// Returns the dictionary as stored in the metadata.
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+/// Compresses `data` with `compression`. Returns the data unchanged for `Compression::None`.
+///
+/// # Arguments
+/// * `data` - The bytes to compress.
+/// * `compression` - The codec.
+/// * `level` - The compression level. Only used by Zstandard.
+#[cfg(feature = "async")]
+pub(crate) fn compress(data: &[u8], compression: Compression, level: i32) -> Result<Cow<'_, [u8]>> {
+    match compression {
+        Compression::None => Ok(Cow::Borrowed(data)),
+        Compression::Zstd => Ok(Cow::Owned(zstd::bulk::compress(data, level)?)),
+        Compression::Lz4 => Ok(Cow::Owned(lz4_flex::block::compress(data))),
+        _ => Err(format!("Unsupported compression {:?}", compression).into()),
+    }
+}
+
+/// Compresses `data` into a Zstandard frame with `dictionary`, for `Compression::ZstdDictionary`.
+#[cfg(feature = "async")]
+pub(crate) fn compress_with_dictionary(
+    data: &[u8],
+    dictionary: &CompressionDictionary,
+) -> Result<Vec<u8>> {
+    let mut compressor = zstd::bulk::Compressor::with_prepared_dictionary(&dictionary.encoder)?;
+    Ok(compressor.compress(data)?)
+}
+
+/// Decompresses `stored` into `out`, which must be exactly the size of the original data.
+///
+/// # Arguments
+/// * `dictionary` - The file's compression dictionary, needed for `Compression::ZstdDictionary`.
+/// * `codec` - The id of the custom codec, needed for `Compression::Custom`.
+pub(crate) fn decompress(
+    stored: &[u8],
+    compression: Compression,
+    dictionary: Option<&[u8]>,
+    codec: Option<&str>,
+    out: &mut [u8],
+) -> Result<()> {
+    let written = match compression {
+        Compression::None => {
+            if stored.len() != out.len() {
+                return Err("Stored data size does not match the tensor size".into());
+            }
+            out.copy_from_slice(stored);
+            stored.len()
+        }
+        Compression::Zstd => zstd::bulk::decompress_to_buffer(stored, out)?,
+        Compression::Lz4 => lz4_flex::block::decompress_into(stored, out)?,
+        Compression::ZstdDictionary => {
+            let dictionary = dictionary.ok_or("Tensor needs a compression dictionary")?;
+            zstd::bulk::Decompressor::with_dictionary(dictionary)?
+                .decompress_to_buffer(stored, out)?
+        }
+        Compression::Custom => {
+            let codec = codec.ok_or("Tensor data uses a custom codec but names none")?;
+            registered_codec(codec)?.decode(stored, out)?;
+            out.len()
+        }
+        _ => return Err(format!("Unsupported compression {:?}", compression).into()),
+    };
+    if written != out.len() {
+        return Err(format!(
+            "Decompressed {} bytes, but the tensor holds {} bytes",
+            written,
+            out.len()
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Most bytes an LZ4 block decompresses to per stored byte.
+#[cfg(feature = "async")]
+const LZ4_MAX_EXPANSION: usize = 255;
+
+/// Checks that `stored` decompresses to exactly `size` bytes without allocating `size` bytes up
+/// front, for sizes read from metadata that may be damaged. Zstandard data is decompressed in
+/// chunks that are dropped; LZ4 data is only decompressed if its stored bytes can expand to `size`,
+/// and data of custom codecs only if `size` is at most `max_size`.
+///
+/// # Arguments
+/// * `dictionary` - The file's compression dictionary, needed for `Compression::ZstdDictionary`.
+/// * `codec` - The id of the custom codec, needed for `Compression::Custom`.
+#[cfg(feature = "async")]
+pub(crate) fn check_decompressed_size(
+    stored: &[u8],
+    compression: Compression,
+    dictionary: Option<&[u8]>,
+    codec: Option<&str>,
+    size: usize,
+    max_size: usize,
+) -> Result<()> {
+    use std::io::Read;
+
+    let written = match compression {
+        Compression::None if stored.len() != size => {
+            return Err("Stored data size does not match the tensor size".into());
+        }
+        Compression::None => return Ok(()),
+        Compression::Zstd => std::io::copy(
+            &mut zstd::stream::read::Decoder::with_buffer(stored)?.take(size as u64 + 1),
+            &mut std::io::sink(),
+        )?,
+        Compression::ZstdDictionary => {
+            let dictionary = dictionary.ok_or("Tensor needs a compression dictionary")?;
+            std::io::copy(
+                &mut zstd::stream::read::Decoder::with_dictionary(stored, dictionary)?
+                    .take(size as u64 + 1),
+                &mut std::io::sink(),
+            )?
+        }
+        Compression::Lz4 if size > stored.len().saturating_mul(LZ4_MAX_EXPANSION) => {
+            return Err(format!(
+                "{} stored bytes can't decompress to the {} bytes the tensor holds",
+                stored.len(),
+                size
+            )
+            .into());
+        }
+        Compression::Custom if size > max_size => {
+            return Err(format!(
+                "Tensor holds {} bytes, over the {} bytes custom codecs are checked up to",
+                size, max_size
+            )
+            .into());
+        }
+        _ => return decompress(stored, compression, dictionary, codec, &mut vec![0; size]),
+    };
+    if written != size as u64 {
+        return Err(match written > size as u64 {
+            true => format!("Decompressed data exceeds the {} bytes the tensor holds", size),
+            false => format!("Decompressed {} bytes, but the tensor holds {} bytes", written, size),
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// Decompresses a tensor's stored data as it arrives in chunks, so the whole compressed data never
+/// needs to be held next to the tensor. Only Zstandard frames can be decompressed this way; LZ4
+/// blocks need all of their data at once.
+#[cfg(feature = "async")]
+pub(crate) struct StreamingDecompressor {
+    decoder: zstd::stream::raw::Decoder<'static>,
+    /// Bytes of the tensor decompressed so far.
+    written: usize,
+    /// Whether the last chunk ended the frame.
+    frame_done: bool,
+}
+
+#[cfg(feature = "async")]
+impl StreamingDecompressor {
+    /// Creates a decompressor for data stored with `compression`.
+    ///
+    /// # Returns
+    /// `None` for codecs that can't be decompressed in chunks, or for uncompressed data.
+    pub fn new(compression: Compression, dictionary: Option<&[u8]>) -> Result<Option<Self>> {
+        let decoder = match compression {
+            Compression::Zstd => zstd::stream::raw::Decoder::new()?,
+            Compression::ZstdDictionary => {
+                let dictionary = dictionary.ok_or("Tensor needs a compression dictionary")?;
+                zstd::stream::raw::Decoder::with_dictionary(dictionary)?
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(StreamingDecompressor { decoder, written: 0, frame_done: false }))
+    }
+
+    /// Decompresses the next chunk of stored data into `out` after the data decompressed so far.
+    /// `out` must be the same buffer, exactly the size of the original data, on every call.
+    pub fn decompress(&mut self, mut chunk: &[u8], out: &mut [u8]) -> Result<()> {
+        use zstd::stream::raw::Operation;
+
+        while !chunk.is_empty() {
+            let status = self.decoder.run_on_buffers(chunk, &mut out[self.written..])?;
+            if status.bytes_read == 0 && status.bytes_written == 0 {
+                return Err(format!(
+                    "Decompressed data exceeds the {} bytes the tensor holds",
+                    out.len()
+                )
+                .into());
+            }
+            chunk = &chunk[status.bytes_read..];
+            self.written += status.bytes_written;
+            self.frame_done = status.remaining == 0;
+        }
+        Ok(())
+    }
+
+    /// Checks that the stored data decompressed to exactly `out`.
+    pub fn finish(&self, out: &[u8]) -> Result<()> {
+        if !self.frame_done || self.written != out.len() {
+            return Err(format!(
+                "Decompressed {} bytes, but the tensor holds {} bytes",
+                self.written,
+                out.len()
+            )
+            .into());
+        }
+        Ok(())
+    }
+}
+
+/// Computes the checksum of `data`. Returns 0 for `ChecksumAlgorithm::None`.
+pub(crate) fn checksum(data: &[u8], algorithm: ChecksumAlgorithm) -> Result<u64> {
+    match algorithm {
+        ChecksumAlgorithm::None => Ok(0),
+        ChecksumAlgorithm::Crc32c => Ok(crc32c::crc32c(data) as u64),
+        ChecksumAlgorithm::XxHash64 => Ok(xxhash_rust::xxh64::xxh64(data, 0)),
+        _ => Err(format!("Unsupported checksum algorithm {:?}", algorithm).into()),
+    }
+}
+
+/// Computes a checksum over data that arrives in pieces.
+#[cfg(feature = "async")]
+pub(crate) enum Checksummer {
+    None,
+    Crc32c(u32),
+    XxHash64(Box<xxhash_rust::xxh64::Xxh64>),
+}
+
+#[cfg(feature = "async")]
+impl Checksummer {
+    pub fn new(algorithm: ChecksumAlgorithm) -> Result<Self> {
+        match algorithm {
+            ChecksumAlgorithm::None => Ok(Checksummer::None),
+            ChecksumAlgorithm::Crc32c => Ok(Checksummer::Crc32c(0)),
+            ChecksumAlgorithm::XxHash64 => {
+                Ok(Checksummer::XxHash64(Box::new(xxhash_rust::xxh64::Xxh64::new(0))))
+            }
+            _ => Err(format!("Unsupported checksum algorithm {:?}", algorithm).into()),
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            Checksummer::None => {}
+            Checksummer::Crc32c(crc) => *crc = crc32c::crc32c_append(*crc, data),
+            Checksummer::XxHash64(hasher) => hasher.update(data),
+        }
+    }
+
+    /// Returns the checksum of all data so far, as `checksum` computes it.
+    pub fn finish(&self) -> u64 {
+        match self {
+            Checksummer::None => 0,
+            Checksummer::Crc32c(crc) => *crc as u64,
+            Checksummer::XxHash64(hasher) => hasher.digest(),
+        }
+    }
+}
+
+/// Checks the stored bytes of a tensor against the checksum in its metadata, if it has one.
+pub(crate) fn verify_checksum(metadata: &TensorMetadata, stored: &[u8]) -> Result<()> {
+    let algorithm = metadata.checksum_algorithm();
+    if algorithm == ChecksumAlgorithm::None {
+        return Ok(());
+    }
+    check_checksum(metadata, checksum(stored, algorithm)?)
+}
+
+/// Checks a checksum computed over the stored bytes of a tensor against its metadata.
+pub(crate) fn check_checksum(metadata: &TensorMetadata, actual: u64) -> Result<()> {
+    let algorithm = metadata.checksum_algorithm();
+    if algorithm != ChecksumAlgorithm::None && actual != metadata.checksum() {
+        return Err(format!(
+            "{:?} checksum mismatch for tensor {}: expected {:#x}, found {:#x}",
+            algorithm,
+            metadata.name(),
+            metadata.checksum(),
+            actual
+        )
+        .into());
+    }
+    Ok(())
+}
+
+#[cfg(all(test, feature = "async"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compression_round_trip() {
+        let data = (0..4096).map(|i| (i % 7) as u8).collect::<Vec<_>>();
+        for compression in [Compression::None, Compression::Zstd, Compression::Lz4] {
+            let stored = compress(&data, compression, 3).unwrap();
+            if compression != Compression::None {
+                assert!(stored.len() < data.len());
+            }
+            let mut out = vec![0; data.len()];
+            decompress(&stored, compression, None, None, &mut out).unwrap();
+            assert_eq!(out, data);
+            let mut longer = vec![0; data.len() + 1];
+            assert!(decompress(&stored, compression, None, None, &mut longer).is_err());
+        }
+    }
+
+    #[test]
+    fn test_compression_dictionary() {
+        // Small records that share structure but are too short to compress on their own.
+        let records = (0..200u32)
+            .map(|i| format!("{{\"layer\": {}, \"norm\": \"rms\", \"eps\": 1e-6}}", i).into_bytes())
+            .collect::<Vec<_>>();
+        let samples = records.iter().map(Vec::as_slice).collect::<Vec<_>>();
+        let dictionary = CompressionDictionary::train(&samples, 4096, 3).unwrap();
+        let record = &records[123];
+        let stored = compress_with_dictionary(record, &dictionary).unwrap();
+        assert!(stored.len() < compress(record, Compression::Zstd, 3).unwrap().len());
+
+        let mut out = vec![0; record.len()];
+        decompress(&stored, Compression::ZstdDictionary, Some(dictionary.bytes()), None, &mut out)
+            .unwrap();
+        assert_eq!(&out, record);
+        assert!(decompress(&stored, Compression::ZstdDictionary, None, None, &mut out).is_err());
+        assert!(CompressionDictionary::train(&samples[..2], 4096, 3).is_none());
+    }
+
+    #[test]
+    fn test_streaming_decompressor() {
+        let data = (0..100_000u32).flat_map(|i| (i % 251).to_le_bytes()).collect::<Vec<_>>();
+        let stored = compress(&data, Compression::Zstd, 3).unwrap();
+        for chunk_size in [1, 7, 4096, stored.len()] {
+            let mut decompressor =
+                StreamingDecompressor::new(Compression::Zstd, None).unwrap().unwrap();
+            let mut out = vec![0; data.len()];
+            for chunk in stored.chunks(chunk_size) {
+                decompressor.decompress(chunk, &mut out).unwrap();
+            }
+            decompressor.finish(&out).unwrap();
+            assert!(out == data);
+        }
+
+        // Truncated data, and data that decompresses to more than the tensor holds, fail.
+        let mut decompressor =
+            StreamingDecompressor::new(Compression::Zstd, None).unwrap().unwrap();
+        let mut out = vec![0; data.len()];
+        decompressor.decompress(&stored[..stored.len() / 2], &mut out).unwrap();
+        assert!(decompressor.finish(&out).is_err());
+        let mut decompressor =
+            StreamingDecompressor::new(Compression::Zstd, None).unwrap().unwrap();
+        let mut out = vec![0; data.len() - 1];
+        assert!(decompressor.decompress(&stored, &mut out).is_err());
+        assert!(StreamingDecompressor::new(Compression::Lz4, None).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_check_decompressed_size() {
+        let data = vec![7u8; 1 << 20];
+        for compression in [Compression::None, Compression::Zstd, Compression::Lz4] {
+            let stored = compress(&data, compression, 3).unwrap();
+            let check = |size| {
+                check_decompressed_size(&stored, compression, None, None, size, 1 << 20).is_ok()
+            };
+            assert!(check(data.len()));
+            assert!(!check(data.len() - 1));
+            assert!(!check(data.len() + 1));
+            // A size far beyond what the data can hold fails without allocating it.
+            assert!(!check(usize::MAX >> 1));
+        }
+        let error =
+            check_decompressed_size(&[], Compression::Custom, None, Some("x"), 1 << 40, 1 << 20);
+        assert!(error.unwrap_err().to_string().contains("over the 1048576 bytes"));
+    }
+
+    #[test]
+    fn test_checksum() {
+        assert_eq!(checksum(b"123456789", ChecksumAlgorithm::Crc32c).unwrap(), 0xe3069283);
+        assert_eq!(checksum(b"", ChecksumAlgorithm::XxHash64).unwrap(), 0xef46db3751d8e999);
+        assert_eq!(checksum(b"data", ChecksumAlgorithm::None).unwrap(), 0);
+
+        let data = (0..1000).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+        for algorithm in [ChecksumAlgorithm::Crc32c, ChecksumAlgorithm::XxHash64] {
+            let mut checksummer = Checksummer::new(algorithm).unwrap();
+            data.chunks(7).for_each(|chunk| checksummer.update(chunk));
+            assert_eq!(checksummer.finish(), checksum(&data, algorithm).unwrap());
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, RwLock},
+};
+
+use crate::Result;
+
+/// A codec for tensor data beyond the built-in compressions, e.g. a proprietary compressor or an
+/// encryption scheme, stored as `Compression::Custom` with the codec's id in each tensor's
+/// metadata.
+///
+/// Register it with [`register_codec`] in every process that writes or reads files using it, then
+/// write with `WriterOptions::with_custom_codec`. Readers look the codec up by the id in the
+/// metadata and fail to load the tensor if it isn't registered.
+///
+/// ```
+/// use tensorbuffers::{register_codec, CustomCodec};
+///
+/// /// Flips every bit, as a stand-in for a real cipher.
+/// struct Invert;
+///
+/// impl CustomCodec for Invert {
+///     fn id(&self) -> &'static str {
+///         "example.invert"
+///     }
+///
+///     fn encode(&self, data: &[u8]) -> tensorbuffers::Result<Vec<u8>> {
+///         Ok(data.iter().map(|byte| !byte).collect())
+///     }
+///
+///     fn decode(&self, stored: &[u8], out: &mut [u8]) -> tensorbuffers::Result<()> {
+///         if stored.len() != out.len() {
+///             return Err("Stored data size does not match the tensor size".into());
+///         }
+///         out.iter_mut().zip(stored).for_each(|(out, byte)| *out = !byte);
+///         Ok(())
+///     }
+/// }
+///
+/// register_codec(Invert);
+/// ```
+pub trait CustomCodec: Send + Sync {
+    /// Returns the id recorded in the metadata of tensors stored with the codec. Ids should be
+    /// namespaced, e.g. `"acme.cipher-v2"`, so codecs from different sources don't clash.
+    fn id(&self) -> &'static str;
+
+    /// Encodes a tensor's data into the bytes stored in the file, which may be larger than it.
+    fn encode(&self, data: &[u8]) -> Result<Vec<u8>>;
+
+    /// Decodes stored bytes into `out`, which is exactly the size of the tensor's data. Fails if
+    /// the bytes don't decode to exactly that size.
+    fn decode(&self, stored: &[u8], out: &mut [u8]) -> Result<()>;
+}
+
+/// The codecs registered in this process, by id.
+static CODECS: RwLock<BTreeMap<&'static str, Arc<dyn CustomCodec>>> = RwLock::new(BTreeMap::new());
+
+/// Registers `codec` for every writer and reader in the process, replacing any codec registered
+/// with the same id.
+pub fn register_codec(codec: impl CustomCodec + 'static) {
+    let codec: Arc<dyn CustomCodec> = Arc::new(codec);
+    CODECS.write().unwrap().insert(codec.id(), codec);
+}
+
+/// Removes the codec registered with `id`, returning whether there was one.
+pub fn unregister_codec(id: &str) -> bool {
+    CODECS.write().unwrap().remove(id).is_some()
+}
+
+/// Returns the ids of the registered codecs in order.
+pub fn registered_codecs() -> Vec<&'static str> {
+    CODECS.read().unwrap().keys().copied().collect()
+}
+
+/// Returns the codec registered with `id`.
+pub(crate) fn registered_codec(id: &str) -> Result<Arc<dyn CustomCodec>> {
+    match CODECS.read().unwrap().get(id) {
+        Some(codec) => Ok(codec.clone()),
+        None => Err(format!("Codec {} isn't registered", id).into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "async")]
+    use tempfile::NamedTempFile;
+    #[cfg(feature = "async")]
+    use tokio::fs::File;
+
+    use super::*;
+    #[cfg(feature = "async")]
+    use crate::{
+        Compression, Tensor, TensorBuffers, TensorBuffersWrite, TensorBuffersWriter, WriterOptions,
+    };
+
+    struct Reverse;
+
+    impl CustomCodec for Reverse {
+        fn id(&self) -> &'static str {
+            "test.registry-reverse"
+        }
+
+        fn encode(&self, data: &[u8]) -> Result<Vec<u8>> {
+            Ok(data.iter().rev().copied().collect())
+        }
+
+        fn decode(&self, stored: &[u8], out: &mut [u8]) -> Result<()> {
+            out.copy_from_slice(stored);
+            out.reverse();
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_codec_registry() {
+        assert!(registered_codec("test.registry-reverse").is_err());
+        register_codec(Reverse);
+        assert!(registered_codecs().contains(&"test.registry-reverse"));
+        let codec = registered_codec("test.registry-reverse").unwrap();
+        let stored = codec.encode(b"abc").unwrap();
+        let mut out = [0; 3];
+        codec.decode(&stored, &mut out).unwrap();
+        assert_eq!(&out, b"abc");
+
+        assert!(unregister_codec("test.registry-reverse"));
+        assert!(!unregister_codec("test.registry-reverse"));
+        assert_eq!(
+            registered_codec("test.registry-reverse").err().unwrap().to_string(),
+            "Codec test.registry-reverse isn't registered"
+        );
+    }
+
+    /// Prepends a tag byte and flips every bit, so the stored data is larger than the tensor's.
+    #[cfg(feature = "async")]
+    struct Tagged;
+
+    #[cfg(feature = "async")]
+    impl CustomCodec for Tagged {
+        fn id(&self) -> &'static str {
+            "test.tagged"
+        }
+
+        fn encode(&self, data: &[u8]) -> Result<Vec<u8>> {
+            Ok(std::iter::once(0x7a).chain(data.iter().map(|byte| !byte)).collect())
+        }
+
+        fn decode(&self, stored: &[u8], out: &mut [u8]) -> Result<()> {
+            match stored.split_first() {
+                Some((0x7a, data)) if data.len() == out.len() => {
+                    out.iter_mut().zip(data).for_each(|(out, byte)| *out = !byte);
+                    Ok(())
+                }
+                _ => Err("Not tagged data".into()),
+            }
+        }
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn test_custom_codec_round_trip() {
+        register_codec(Tagged);
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        let options = WriterOptions::new()
+            .with_custom_codec("test.tagged")
+            .with_checksum(crate::ChecksumAlgorithm::Crc32c)
+            .with_name_index(true);
+        let mut writer = TensorBuffersWriter::with_options(&mut file, options);
+        let data = (0..64).map(|i| i as f32).collect::<Vec<_>>();
+        writer.write(vec![Tensor::new("w", &data, vec![8, 8])], vec![]).await.unwrap();
+
+        let url = format!("file://{}", tmp.path().display());
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        assert_eq!(tensor_buffers.features().await.unwrap(), ["compression", "custom_codec"]);
+        let described = tensor_buffers.filter_tensors(|_| true).await.unwrap();
+        assert_eq!(described[0].compression, Compression::Custom);
+        assert_eq!(described[0].codec.as_deref(), Some("test.tagged"));
+        assert_eq!(described[0].stored_size, 257);
+        let tensor = tensor_buffers.get_tensor_data_by_name::<f32>("w").await.unwrap();
+        assert_eq!(tensor.data(), data);
+
+        // Readers without the codec fail to load the tensor, naming it.
+        unregister_codec("test.tagged");
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        let error = tensor_buffers.get_tensor_data_by_name::<f32>("w").await.unwrap_err();
+        assert_eq!(error.to_string(), "Codec test.tagged isn't registered");
+    }
+}
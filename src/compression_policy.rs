@@ -0,0 +1,167 @@
+use std::collections::BTreeMap;
+
+#[cfg(feature = "async")]
+use crate::Result;
+use crate::{Compression, DataType};
+
+/// Which tensors a writer compresses, so it doesn't spend CPU on data that won't shrink, e.g.
+/// small tensors or fp16 and quantized weights.
+///
+/// Tensors the policy doesn't exempt are compressed with the codec and level of the
+/// `WriterOptions`. Overrides take precedence over the other rules.
+///
+/// ```
+/// use tensorbuffers::{Compression, CompressionPolicy, DataType};
+///
+/// let policy = CompressionPolicy::default()
+///     .with_min_size(1 << 20)
+///     .with_skipped_data_type(DataType::Float16)
+///     .with_override("embeddings.*", Compression::Zstd, 19)
+///     .with_override("lm_head.weight", Compression::None, 0);
+/// assert_eq!(policy.min_size, 1 << 20);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CompressionPolicy {
+    /// Tensors with less data than this, in bytes, are stored uncompressed.
+    pub min_size: usize,
+    /// Data types stored uncompressed.
+    pub skipped_data_types: Vec<DataType>,
+    /// Codec and level by tensor name. A name ending in `*` matches every tensor whose name starts
+    /// with the rest; an exact name wins over patterns, and longer patterns over shorter ones.
+    pub overrides: BTreeMap<String, (Compression, i32)>,
+}
+
+impl CompressionPolicy {
+    /// Sets the smallest tensor, in bytes, that is compressed.
+    pub fn with_min_size(mut self, bytes: usize) -> Self {
+        self.min_size = bytes;
+        self
+    }
+
+    /// Stores tensors of `data_type` uncompressed.
+    pub fn with_skipped_data_type(mut self, data_type: DataType) -> Self {
+        if !self.skipped_data_types.contains(&data_type) {
+            self.skipped_data_types.push(data_type);
+        }
+        self
+    }
+
+    /// Compresses the tensors `name` matches with `compression` at `level`, whatever the other
+    /// rules say. `Compression::None` stores them uncompressed.
+    pub fn with_override(mut self, name: &str, compression: Compression, level: i32) -> Self {
+        self.overrides.insert(name.to_string(), (compression, level));
+        self
+    }
+
+    /// Returns the override for the tensor `name`, if any.
+    fn find_override(&self, name: &str) -> Option<(Compression, i32)> {
+        if let Some(&codec) = self.overrides.get(name) {
+            return Some(codec);
+        }
+        self.overrides
+            .iter()
+            .filter_map(|(pattern, &codec)| {
+                let prefix = pattern.strip_suffix('*')?;
+                name.starts_with(prefix).then_some((prefix.len(), codec))
+            })
+            .max_by_key(|(len, _)| *len)
+            .map(|(_, codec)| codec)
+    }
+
+    /// Chooses how to compress a tensor.
+    ///
+    /// # Arguments
+    /// * `name` - The tensor's name.
+    /// * `data_type` - Its element type.
+    /// * `size` - The size of its data in bytes.
+    /// * `default` - The codec and level of the writer options.
+    ///
+    /// # Returns
+    /// The codec and level to compress the tensor with.
+    pub fn choose(
+        &self,
+        name: &str,
+        data_type: DataType,
+        size: usize,
+        default: (Compression, i32),
+    ) -> (Compression, i32) {
+        if let Some(codec) = self.find_override(name) {
+            return codec;
+        }
+        if size < self.min_size || self.skipped_data_types.contains(&data_type) {
+            return (Compression::None, 0);
+        }
+        default
+    }
+
+    /// Returns whether any override compresses tensors.
+    #[cfg(feature = "async")]
+    pub(crate) fn overrides_compress(&self) -> bool {
+        self.overrides.values().any(|(compression, _)| *compression != Compression::None)
+    }
+
+    /// Returns whether any override uses `compression`.
+    #[cfg(feature = "async")]
+    pub(crate) fn overrides_with(&self, compression: Compression) -> bool {
+        self.overrides
+            .values()
+            .any(|(override_compression, _)| *override_compression == compression)
+    }
+
+    /// Checks that the overrides name codecs and levels the writer supports.
+    #[cfg(feature = "async")]
+    pub(crate) fn validate(&self) -> Result<()> {
+        for (name, &(compression, level)) in &self.overrides {
+            if compression.variant_name().is_none() || compression == Compression::ZstdDictionary {
+                return Err(
+                    format!("Unsupported compression {:?} for {}", compression, name).into()
+                );
+            }
+            if compression == Compression::Zstd && !zstd::compression_level_range().contains(&level)
+            {
+                return Err(format!("Invalid zstd compression level {} for {}", level, name).into());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "async"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_choose() {
+        let zstd = (Compression::Zstd, 3);
+        let policy = CompressionPolicy::default()
+            .with_min_size(1024)
+            .with_skipped_data_type(DataType::Float16)
+            .with_override("embed.*", Compression::Lz4, 0)
+            .with_override("embed.pos.*", Compression::None, 0)
+            .with_override("embed.pos.table", Compression::Zstd, 19);
+        assert_eq!(policy.choose("w", DataType::Float32, 4096, zstd), zstd);
+        assert_eq!(policy.choose("w", DataType::Float32, 512, zstd).0, Compression::None);
+        assert_eq!(policy.choose("w", DataType::Float16, 4096, zstd).0, Compression::None);
+        // Overrides apply whatever the size and data type.
+        assert_eq!(policy.choose("embed.tok", DataType::Float16, 16, zstd).0, Compression::Lz4);
+        assert_eq!(
+            policy.choose("embed.pos.x", DataType::Float32, 4096, zstd).0,
+            Compression::None
+        );
+        assert_eq!(
+            policy.choose("embed.pos.table", DataType::Float32, 16, zstd),
+            (Compression::Zstd, 19)
+        );
+        assert!(policy.overrides_compress());
+        assert!(policy.validate().is_ok());
+
+        let policy = CompressionPolicy::default().with_override("w", Compression::Zstd, 100);
+        assert_eq!(
+            policy.validate().unwrap_err().to_string(),
+            "Invalid zstd compression level 100 for w"
+        );
+        let policy =
+            CompressionPolicy::default().with_override("w", Compression::ZstdDictionary, 3);
+        assert!(policy.validate().is_err());
+    }
+}
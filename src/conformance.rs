@@ -0,0 +1,374 @@
+use std::path::{Path, PathBuf};
+
+use futures::SinkExt;
+
+use crate::{
+    f16, utils::hash_key, ChecksumAlgorithm, Compression, Operation, Result, Tensor, TensorAny,
+    TensorBuffers, TensorBuffersSink, TensorBuffersWrite, TensorBuffersWriter, TensorOperation,
+    WriterOptions,
+};
+
+/// File extension of the fixture files.
+const FIXTURE_EXTENSION: &str = "tb";
+
+/// A canonical file of the conformance suite: the tensors and operations it holds and the options
+/// it is written with.
+///
+/// Third-party readers can check that they decode the files written by [`generate_fixtures`] to
+/// these tensors, and third-party writers can produce files with the same contents and check them
+/// with [`check_fixtures`].
+#[derive(Debug, Clone)]
+pub struct Fixture {
+    name: &'static str,
+    description: &'static str,
+    tensors: Vec<TensorAny>,
+    operations: Vec<TensorOperation>,
+    options: WriterOptions,
+}
+
+impl Fixture {
+    /// Returns the fixture's name, which is also its file name without the extension.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns what the fixture covers.
+    pub fn description(&self) -> &'static str {
+        self.description
+    }
+
+    pub fn tensors(&self) -> &[TensorAny] {
+        &self.tensors
+    }
+
+    pub fn operations(&self) -> &[TensorOperation] {
+        &self.operations
+    }
+
+    /// Returns the options this crate writes the fixture with. Other writers may lay the file out
+    /// differently as long as it holds the same tensors and operations.
+    pub fn options(&self) -> &WriterOptions {
+        &self.options
+    }
+
+    /// Encodes the fixture as a TensorBuffers file.
+    pub async fn encode(&self) -> Result<Vec<u8>> {
+        // Only the writer can lay out deduplicated data or a leading copy of the metadata, or train
+        // a compression dictionary, and it takes tensors of a single data type.
+        if self.options.dedup()
+            || self.options.leading_metadata()
+            || self.options.compression_dictionary() > 0
+        {
+            let tensors = self
+                .tensors
+                .iter()
+                .map(|tensor| match tensor {
+                    TensorAny::Float32(tensor) => Ok(tensor.clone()),
+                    _ => Err(format!("Fixture {} mixes data types", self.name)),
+                })
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+            let mut bytes = std::io::Cursor::new(Vec::new());
+            let mut writer = TensorBuffersWriter::with_options(&mut bytes, self.options.clone());
+            writer.write(tensors, self.operations.clone()).await?;
+            return Ok(bytes.into_inner());
+        }
+        let mut sink = TensorBuffersSink::new(Vec::new(), self.options.clone())?;
+        for operation in &self.operations {
+            sink.add_operation(operation.clone());
+        }
+        for tensor in &self.tensors {
+            sink.send(tensor.clone()).await?;
+        }
+        sink.close().await?;
+        Ok(sink.into_inner())
+    }
+
+    /// Checks that the file at `url` holds exactly the fixture's tensors and operations.
+    ///
+    /// # Returns
+    /// Every difference found, empty if the file conforms. Errors are returned for files that
+    /// can't be opened at all.
+    pub async fn check(&self, url: &str) -> Result<Vec<String>> {
+        let tensor_buffers = TensorBuffers::open(url).await?;
+        let mut problems = Vec::new();
+        for expected in &self.tensors {
+            match read_tensor(&tensor_buffers, expected).await {
+                Ok(tensor) => problems.extend(compare_tensors(expected, &tensor)),
+                Err(error) => problems.push(format!("Tensor {}: {}", expected.name(), error)),
+            }
+        }
+        let tensors = tensor_buffers.tensor_count().await?;
+        if tensors != self.tensors.len() {
+            problems.push(format!("Expected {} tensors, found {}", self.tensors.len(), tensors));
+        }
+
+        let mut operations = tensor_buffers.get_tensor_operations().await?;
+        operations.sort_by_key(|op| op.id());
+        let mut expected = self.operations.clone();
+        expected.sort_by_key(|op| op.id());
+        if operations.len() != expected.len() {
+            problems.push(format!(
+                "Expected {} operations, found {}",
+                expected.len(),
+                operations.len()
+            ));
+        }
+        for (expected, op) in expected.iter().zip(&operations) {
+            if !same_operation(expected, op) {
+                problems.push(format!(
+                    "Operation {}: expected {:?}, found {:?}",
+                    expected.id(),
+                    expected,
+                    op
+                ));
+            }
+        }
+        Ok(problems.into_iter().map(|problem| format!("{}: {}", self.name, problem)).collect())
+    }
+
+    fn path(&self, dir: &Path) -> PathBuf {
+        dir.join(self.name).with_extension(FIXTURE_EXTENSION)
+    }
+}
+
+/// Reads the stored tensor matching `expected` by id, whatever its stored data type.
+async fn read_tensor(tensor_buffers: &TensorBuffers, expected: &TensorAny) -> Result<TensorAny> {
+    let metadata = tensor_buffers.stored_metadata(expected.id()).await?;
+    tensor_buffers.read_tensor_any(metadata).await
+}
+
+fn compare_tensors(expected: &TensorAny, found: &TensorAny) -> Option<String> {
+    let problem = if found.name() != expected.name() {
+        format!("found the name {:?}", found.name())
+    } else if found.data_type() != expected.data_type() {
+        format!("expected {:?} data, found {:?}", expected.data_type(), found.data_type())
+    } else if found.shape() != expected.shape() {
+        format!("expected shape {:?}, found {:?}", expected.shape(), found.shape())
+    } else if found.data_bytes() != expected.data_bytes() {
+        "data differs".to_string()
+    } else {
+        return None;
+    };
+    Some(format!("Tensor {}: {}", expected.name(), problem))
+}
+
+fn same_operation(expected: &TensorOperation, found: &TensorOperation) -> bool {
+    expected.id() == found.id()
+        && expected.operation() == found.operation()
+        && expected.input_operations() == found.input_operations()
+        && expected.output() == found.output()
+        && expected.name() == found.name()
+}
+
+/// One tensor of every data type, holding its extreme values and, for floats, signed zeros,
+/// infinities and NaN.
+fn data_type_tensors() -> Vec<TensorAny> {
+    let float =
+        [0.0, -0.0, 1.5, -2.25, f32::INFINITY, f32::NEG_INFINITY, f32::NAN, f32::MIN_POSITIVE];
+    let uint32 = vec![0u32, 1, 424242, 1 << 31, u32::MAX - 1, u32::MAX];
+    let uint64 = vec![0u64, 1, 42, 1 << 63, u64::MAX - 1, u64::MAX];
+    let float16 = float.iter().map(|&x| f16::from_f32(x)).collect();
+    let float64 = float.iter().map(|&x| f64::from(x)).collect();
+    vec![
+        Tensor::from_vec("int8", vec![i8::MIN, -1, 0, 1, 42, i8::MAX], vec![2, 3]).into(),
+        Tensor::from_vec("int16", vec![i16::MIN, -1, 0, 1, 4242, i16::MAX], vec![3, 2]).into(),
+        Tensor::from_vec("int32", vec![i32::MIN, -1, 0, 1, 424242, i32::MAX], vec![6]).into(),
+        Tensor::from_vec("int64", vec![i64::MIN, -1, 0, 1, 42, i64::MAX], vec![1, 2, 3]).into(),
+        Tensor::from_vec("uint8", vec![0u8, 1, 127, 128, 200, u8::MAX], vec![2, 3]).into(),
+        Tensor::from_vec("uint16", vec![0u16, 1, 4242, 32768, 60000, u16::MAX], vec![2, 3]).into(),
+        Tensor::from_vec("uint32", uint32, vec![2, 3]).into(),
+        Tensor::from_vec("uint64", uint64, vec![2, 3]).into(),
+        Tensor::from_vec("float16", float16, vec![2, 4]).into(),
+        Tensor::from_vec("float32", float.to_vec(), vec![4, 2]).into(),
+        Tensor::from_vec("float64", float64, vec![8]).into(),
+    ]
+}
+
+/// Float32 tensors large and repetitive enough to be compressed.
+fn weight_tensors() -> Vec<Tensor<'static, f32>> {
+    let weight =
+        Tensor::from_fn("encoder.weight", vec![16, 16], |index| (index[0] % 4) as f32 * 0.5);
+    let bias = Tensor::from_fn("encoder.bias", vec![16], |index| index[0] as f32);
+    let tied = Tensor::from_vec("decoder.weight", weight.data().to_vec(), vec![16, 16]);
+    vec![weight, bias, tied]
+}
+
+/// Small norms that share their values, enough of them to train a compression dictionary on.
+fn norm_tensors() -> Vec<Tensor<'static, f32>> {
+    (0..64)
+        .map(|layer| {
+            let name = format!("layers.{}.norm", layer);
+            Tensor::from_fn(&name, vec![32], |index| 1.0 + ((index[0] * 7 + layer % 3) % 13) as f32)
+        })
+        .collect()
+}
+
+/// Returns the canonical fixtures, in a stable order.
+pub fn fixtures() -> Vec<Fixture> {
+    let weights = weight_tensors();
+    let operations = vec![
+        TensorOperation::new(1, Operation::None, vec![], weights[0].id()).with_name("weight"),
+        TensorOperation::new(2, Operation::None, vec![], weights[1].id()).with_name("bias"),
+        TensorOperation::new(3, Operation::None, vec![], weights[2].id()),
+        TensorOperation::new(4, Operation::Add, vec![1, 3], hash_key("sum")).with_name("sum"),
+        TensorOperation::new(5, Operation::ReLU, vec![4], hash_key("activation")),
+    ];
+    let any = |tensors: &[Tensor<'static, f32>]| {
+        tensors.iter().cloned().map(TensorAny::from).collect::<Vec<_>>()
+    };
+    vec![
+        Fixture {
+            name: "data_types",
+            description: "One tensor of every data type with its extreme values",
+            tensors: data_type_tensors(),
+            operations: vec![],
+            options: WriterOptions::new(),
+        },
+        Fixture {
+            name: "empty",
+            description: "A file without tensors or operations",
+            tensors: vec![],
+            operations: vec![],
+            options: WriterOptions::new(),
+        },
+        Fixture {
+            name: "scalars_and_empty_tensors",
+            description: "Rank-0 tensors and tensors with a zero dimension",
+            tensors: vec![
+                Tensor::scalar("scalar", 3.5f32).into(),
+                Tensor::scalar("step", 1234i64).into(),
+                Tensor::<f32>::zeros("empty", vec![0]).into(),
+                Tensor::<u8>::zeros("empty_rows", vec![0, 4]).into(),
+            ],
+            operations: vec![],
+            options: WriterOptions::new(),
+        },
+        Fixture {
+            name: "big_shape",
+            description: "An empty tensor with a dimension beyond u32, stored in shape64",
+            tensors: vec![Tensor::<f32>::zeros("wide", vec![0, 5_000_000_000]).into()],
+            operations: vec![],
+            options: WriterOptions::new(),
+        },
+        Fixture {
+            name: "operations",
+            description: "Tensors with a graph of named and unnamed operations",
+            tensors: any(&weights),
+            operations,
+            options: WriterOptions::new(),
+        },
+        Fixture {
+            name: "zstd_crc32c",
+            description: "Zstd-compressed data with CRC32C checksums, aligned to 64 bytes",
+            tensors: any(&weights),
+            operations: vec![],
+            options: WriterOptions::new()
+                .with_alignment(64)
+                .with_compression(Compression::Zstd, 3)
+                .with_checksum(ChecksumAlgorithm::Crc32c),
+        },
+        Fixture {
+            name: "lz4_xxhash64",
+            description: "LZ4-compressed data with xxHash64 checksums",
+            tensors: any(&weights),
+            operations: vec![],
+            options: WriterOptions::new()
+                .with_compression(Compression::Lz4, 0)
+                .with_checksum(ChecksumAlgorithm::XxHash64),
+        },
+        Fixture {
+            name: "indexed",
+            description: "A name index, a bloom filter and compressed metadata",
+            tensors: any(&weights),
+            operations: vec![],
+            options: WriterOptions::new()
+                .with_name_index(true)
+                .with_bloom_filter(true)
+                .with_compressed_metadata(true),
+        },
+        Fixture {
+            name: "dedup_leading_metadata",
+            description: "Deduplicated data and a leading copy of the metadata",
+            tensors: any(&weights),
+            operations: vec![],
+            options: WriterOptions::new().with_dedup(true).with_leading_metadata(true),
+        },
+        Fixture {
+            name: "zstd_dictionary",
+            description: "Tensors compressed with a shared Zstandard dictionary",
+            tensors: any(&norm_tensors()),
+            operations: vec![],
+            options: WriterOptions::new()
+                .with_compression(Compression::Zstd, 3)
+                .with_compression_dictionary(2 << 10),
+        },
+        Fixture {
+            name: "version_1_0_0",
+            description: "The oldest format version, with its 8-byte footer",
+            tensors: any(&weights),
+            operations: vec![],
+            options: WriterOptions::new().with_format_version("1.0.0"),
+        },
+    ]
+}
+
+/// Writes every fixture into `dir` as `<name>.tb`, replacing existing files.
+///
+/// # Returns
+/// The paths of the files written, in fixture order.
+pub async fn generate_fixtures(dir: impl AsRef<Path>) -> Result<Vec<PathBuf>> {
+    tokio::fs::create_dir_all(dir.as_ref()).await?;
+    let mut paths = Vec::new();
+    for fixture in fixtures() {
+        let path = fixture.path(dir.as_ref());
+        tokio::fs::write(&path, fixture.encode().await?).await?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+/// Checks the fixture files in `dir`, e.g. written by another implementation of the format,
+/// against the canonical fixtures.
+///
+/// # Returns
+/// Every difference found, each prefixed with its fixture's name, or an empty list if all files
+/// conform. Missing or unreadable files are reported as differences.
+pub async fn check_fixtures(dir: impl AsRef<Path>) -> Result<Vec<String>> {
+    let mut problems = Vec::new();
+    for fixture in fixtures() {
+        let path = fixture.path(dir.as_ref());
+        let url = format!("file://{}", path.display());
+        match fixture.check(&url).await {
+            Ok(found) => problems.extend(found),
+            Err(error) => problems.push(format!("{}: {}", fixture.name, error)),
+        }
+    }
+    Ok(problems)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_conformance_fixtures() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = generate_fixtures(dir.path()).await.unwrap();
+        assert_eq!(paths.len(), fixtures().len());
+        assert!(check_fixtures(dir.path()).await.unwrap().is_empty());
+        let url = format!("file://{}", dir.path().join("zstd_dictionary.tb").display());
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        assert!(tensor_buffers.features().await.unwrap().contains(&"zstd_dictionary"));
+
+        // A file with different data, and a missing file, are both reported.
+        let fixtures = fixtures();
+        let mut changed = fixtures[0].clone();
+        changed.tensors[0] = Tensor::from_vec("int8", vec![0i8; 6], vec![2, 3]).into();
+        std::fs::write(&paths[0], changed.encode().await.unwrap()).unwrap();
+        std::fs::remove_file(&paths[1]).unwrap();
+        let problems = check_fixtures(dir.path()).await.unwrap();
+        assert_eq!(problems.len(), 2, "{:?}", problems);
+        assert_eq!(problems[0], "data_types: Tensor int8: data differs");
+        assert!(problems[1].starts_with("empty: "));
+    }
+}
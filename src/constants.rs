@@ -1,4 +1,47 @@
 pub const MAGIC_BYTES: &'static [u8] = b"TBS1";
 // / Magic bytes to identify the TensorBuffers file format.
-pub const VERSION: &'static str = "1.0.0";
+pub const VERSION: &'static str = "1.4.0";
 // / Version of the TensorBuffers file format.
+pub const SUPPORTED_VERSIONS: &[&str] = &["1.0.0", "1.1.0", "1.2.0", "1.3.0", "1.4.0"];
+// / Format versions that can be written. 1.1.0 added compression and checksums, 1.2.0 the metadata
+// / checksum in the footer, 1.3.0 the optional bloom filter in the footer and 1.4.0 the fixed
+// / 64-byte footer.
+pub type FormatVersion = (u16, u16, u16);
+// / A format version as (major, minor, patch), ordered so writers can compare it to the minimums
+// / below.
+#[cfg(feature = "async")]
+pub const MIN_VERSION_FOR_COMPRESSION: FormatVersion = (1, 1, 0);
+// / First format version with compression and checksums.
+#[cfg(feature = "async")]
+pub const MIN_VERSION_FOR_BLOOM_FILTER: FormatVersion = (1, 3, 0);
+// / First format version with the optional bloom filter in the footer.
+#[cfg(feature = "async")]
+pub const MIN_VERSION_FOR_COMPRESSED_METADATA: FormatVersion = (1, 4, 0);
+// / First format version whose fixed footer can flag compressed metadata.
+#[cfg(feature = "async")]
+pub const MIN_VERSION_FOR_FEATURES: FormatVersion = (1, 4, 0);
+// / First format version whose readers refuse files listing format features they lack. Additions
+// / advertised in the metadata's feature list, such as columnar metadata, need it.
+pub const CHECKSUM_FOOTER_MAGIC_BYTES: &[u8] = b"TBSC";
+// / Trailing magic bytes of files whose footer holds a CRC32C of the metadata.
+#[cfg(feature = "async")]
+pub const NAME_INDEX_MAGIC_BYTES: &[u8] = b"TBSX";
+// / Magic bytes that end the optional name index section right before the metadata.
+pub const BLOOM_FOOTER_MAGIC_BYTES: &[u8] = b"TBSB";
+// / Trailing magic bytes of files whose footer holds a bloom filter over tensor ids.
+pub const FIXED_FOOTER_MAGIC_BYTES: &[u8] = b"TBSF";
+// / Trailing magic bytes of files with the fixed 64-byte footer.
+#[cfg(feature = "fec")]
+pub const ERASURE_MAGIC_BYTES: &[u8] = b"TBSE";
+// / Trailing magic bytes of the parity shards of files written with erasure coding.
+pub const PARITY_MAGIC_BYTES: &[u8] = b"TBSR";
+// / Trailing magic bytes of files ending with a Reed-Solomon parity section.
+#[cfg(feature = "async")]
+pub const LEADING_METADATA_MAGIC_BYTES: &[u8] = b"TBSH";
+// / Magic bytes that start the optional copy of the metadata right after the leading magic bytes.
+#[cfg(feature = "async")]
+pub const MAX_NAME_LENGTH: usize = 1024;
+// / Longest tensor name, in bytes, that writers accept.
+#[cfg(feature = "async")]
+pub const NAMESPACE_SEPARATOR: char = '.';
+// / Separates the namespaces of a tensor name, as in `encoder.layers.0.weight`.
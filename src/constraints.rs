@@ -0,0 +1,379 @@
+#[cfg(any(feature = "async", feature = "mmap"))]
+use flatbuffers::{FlatBufferBuilder, WIPOffset};
+
+#[cfg(feature = "async")]
+use crate::{
+    generated::tensor_buffers::TensorMetadata, slice_file::stored_offset, utils::hash_key,
+    TensorBuffers, TensorInfo,
+};
+#[cfg(any(feature = "async", feature = "mmap"))]
+use crate::{
+    generated::tensor_buffers::{ConstraintKind, ConstraintMetadata, ConstraintMetadataArgs},
+    Result,
+};
+
+/// A relationship between tensors that a file declares, so readers can check that it holds, e.g.
+/// that the LM head is still tied to the input embeddings after a conversion.
+///
+/// ```
+/// use tensorbuffers::TensorConstraint;
+///
+/// let tied = TensorConstraint::Tied(vec!["embed.weight".into(), "lm_head.weight".into()]);
+/// let dims = vec![("embed.weight".to_string(), 1), ("norm.weight".to_string(), 0)];
+/// let hidden = TensorConstraint::SameDim(dims);
+/// assert_eq!(tied.tensors().count(), 2);
+/// assert_eq!(hidden.tensors().collect::<Vec<_>>(), ["embed.weight", "norm.weight"]);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TensorConstraint {
+    /// The two tensors hold equal data: the same data type, shape and values.
+    Equal(String, String),
+    /// The given dimension of each tensor has the same size.
+    SameDim(Vec<(String, usize)>),
+    /// The tensors share their weights, so they hold equal data.
+    Tied(Vec<String>),
+}
+
+impl TensorConstraint {
+    /// Returns the names of the constrained tensors, in the order they were given.
+    pub fn tensors(&self) -> impl Iterator<Item = &str> {
+        let names: Box<dyn Iterator<Item = &String>> = match self {
+            TensorConstraint::Equal(first, second) => Box::new([first, second].into_iter()),
+            TensorConstraint::SameDim(dims) => Box::new(dims.iter().map(|(name, _)| name)),
+            TensorConstraint::Tied(names) => Box::new(names.iter()),
+        };
+        names.map(String::as_str)
+    }
+
+    /// Checks that the constraint relates at least two tensors and its axes fit the format.
+    #[cfg(feature = "async")]
+    fn validate(&self) -> Result<()> {
+        if self.tensors().count() < 2 {
+            return Err(format!("Constraint {:?} must name at least two tensors", self).into());
+        }
+        if let TensorConstraint::SameDim(dims) = self {
+            if let Some((name, axis)) = dims.iter().find(|(_, axis)| u32::try_from(*axis).is_err())
+            {
+                return Err(format!("Axis {} of tensor {} is out of range", axis, name).into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads a constraint from its table.
+    ///
+    /// # Returns
+    /// An error if the constraint is of a kind this version doesn't know or its axes don't match
+    /// its tensors.
+    #[cfg(any(feature = "async", feature = "mmap"))]
+    pub(crate) fn from_metadata(metadata: &ConstraintMetadata) -> Result<Self> {
+        let names = metadata.tensors().into_iter().flatten().map(str::to_string);
+        let names = names.collect::<Vec<_>>();
+        let constraint = match metadata.kind() {
+            ConstraintKind::Equal => match <[String; 2]>::try_from(names) {
+                Ok([first, second]) => TensorConstraint::Equal(first, second),
+                Err(names) => {
+                    return Err(format!(
+                        "An Equal constraint names {} tensors, not two",
+                        names.len()
+                    )
+                    .into());
+                }
+            },
+            ConstraintKind::SameDim => {
+                let axes = metadata.axes().into_iter().flatten().collect::<Vec<_>>();
+                if axes.len() != names.len() {
+                    return Err(format!(
+                        "A SameDim constraint names {} tensors but {} axes",
+                        names.len(),
+                        axes.len()
+                    )
+                    .into());
+                }
+                TensorConstraint::SameDim(
+                    names.into_iter().zip(axes.into_iter().map(|axis| axis as usize)).collect(),
+                )
+            }
+            ConstraintKind::Tied => TensorConstraint::Tied(names),
+            kind => return Err(format!("Unknown constraint kind {}", kind.0).into()),
+        };
+        Ok(constraint)
+    }
+
+    /// Builds the table describing the constraint.
+    #[cfg(any(feature = "async", feature = "mmap"))]
+    pub(crate) fn build_table<'a>(
+        &self,
+        builder: &mut FlatBufferBuilder<'a>,
+    ) -> WIPOffset<ConstraintMetadata<'a>> {
+        let names = self.tensors().map(|name| builder.create_string(name)).collect::<Vec<_>>();
+        let tensors = Some(builder.create_vector(&names));
+        let (kind, axes) = match self {
+            TensorConstraint::Equal(..) => (ConstraintKind::Equal, None),
+            TensorConstraint::SameDim(dims) => {
+                let axes = dims.iter().map(|&(_, axis)| axis as u32).collect::<Vec<_>>();
+                (ConstraintKind::SameDim, Some(builder.create_vector(&axes)))
+            }
+            TensorConstraint::Tied(_) => (ConstraintKind::Tied, None),
+        };
+        ConstraintMetadata::create(builder, &ConstraintMetadataArgs { kind, tensors, axes })
+    }
+}
+
+/// Checks that each constraint is well formed and names only tensors for which `is_tensor`
+/// returns true.
+#[cfg(feature = "async")]
+pub(crate) fn check_constraints(
+    constraints: &[TensorConstraint],
+    is_tensor: impl Fn(&str) -> bool,
+) -> Result<()> {
+    for constraint in constraints {
+        constraint.validate()?;
+        if let Some(name) = constraint.tensors().find(|name| !is_tensor(name)) {
+            return Err(format!("Constraint refers to {}, which is not a tensor", name).into());
+        }
+    }
+    Ok(())
+}
+
+/// What [`TensorBuffers::check_constraints`] found.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ConstraintReport {
+    /// Number of constraints that hold.
+    pub satisfied: usize,
+    /// Constraints that don't hold, with the problem found.
+    pub violated: Vec<(TensorConstraint, String)>,
+}
+
+impl ConstraintReport {
+    /// Returns whether every constraint holds.
+    pub fn is_ok(&self) -> bool {
+        self.violated.is_empty()
+    }
+}
+
+#[cfg(feature = "async")]
+impl TensorBuffers {
+    /// Returns the relationships between tensors that the file declares, as added with
+    /// `TensorBuffersWriter::add_constraint`.
+    ///
+    /// # Returns
+    /// An error if a constraint is of a kind this version doesn't know or is malformed.
+    pub async fn constraints(&self) -> Result<Vec<TensorConstraint>> {
+        let metadata_root = self.get_metadata_root().await?;
+        let constraints = metadata_root.constraints().into_iter().flatten();
+        constraints.map(|constraint| TensorConstraint::from_metadata(&constraint)).collect()
+    }
+
+    /// Checks the constraints the file declares against its tensors. Shapes are checked from the
+    /// metadata alone. Equal and tied tensors are compared by their data unless they share it,
+    /// loading two tensors at a time.
+    ///
+    /// # Returns
+    /// Which constraints hold and which don't. Errors are returned for unreadable constraints and
+    /// files that can't be read, not for constraints that don't hold.
+    pub async fn check_constraints(&self) -> Result<ConstraintReport> {
+        let mut report = ConstraintReport::default();
+        for constraint in self.constraints().await? {
+            match self.constraint_problem(&constraint).await? {
+                Some(problem) => report.violated.push((constraint, problem)),
+                None => report.satisfied += 1,
+            }
+        }
+        Ok(report)
+    }
+
+    /// Returns why `constraint` doesn't hold, or `None` if it does.
+    async fn constraint_problem(&self, constraint: &TensorConstraint) -> Result<Option<String>> {
+        let mut tensors = Vec::new();
+        for name in constraint.tensors() {
+            match self.find_tensor_metadata(hash_key(name)).await? {
+                Some(metadata) if metadata.name() == name => tensors.push(metadata),
+                _ => return Ok(Some(format!("Tensor {} is missing", name))),
+            }
+        }
+        let TensorConstraint::SameDim(dims) = constraint else {
+            for other in &tensors[1..] {
+                if let Some(problem) = self.data_difference(&tensors[0], other).await? {
+                    return Ok(Some(problem));
+                }
+            }
+            return Ok(None);
+        };
+        let mut sizes = Vec::with_capacity(dims.len());
+        for ((name, axis), metadata) in dims.iter().zip(&tensors) {
+            let shape = TensorInfo::from_metadata(metadata)?.shape;
+            match shape.get(*axis) {
+                Some(&size) => sizes.push(size),
+                None => {
+                    return Ok(Some(format!(
+                        "Tensor {} has {} dimensions, so no dimension {}",
+                        name,
+                        shape.len(),
+                        axis
+                    )));
+                }
+            }
+        }
+        let problem = (1..dims.len()).find(|&i| sizes[i] != sizes[0]).map(|i| {
+            format!(
+                "Dimension {} of {} has size {}, but dimension {} of {} has size {}",
+                dims[0].1, dims[0].0, sizes[0], dims[i].1, dims[i].0, sizes[i]
+            )
+        });
+        Ok(problem)
+    }
+
+    /// Returns how the data of two tensors differs, or `None` if it is equal. Tensors that share
+    /// their stored data are equal without reading it.
+    async fn data_difference(
+        &self,
+        first: &TensorMetadata<'_>,
+        second: &TensorMetadata<'_>,
+    ) -> Result<Option<String>> {
+        let (first_info, second_info) =
+            (TensorInfo::from_metadata(first)?, TensorInfo::from_metadata(second)?);
+        let names = (first.name(), second.name());
+        if first_info.data_type != second_info.data_type {
+            return Ok(Some(format!(
+                "Tensors {} and {} are of data types {:?} and {:?}",
+                names.0, names.1, first_info.data_type, second_info.data_type
+            )));
+        }
+        if first_info.shape != second_info.shape {
+            return Ok(Some(format!(
+                "Tensors {} and {} have shapes {:?} and {:?}",
+                names.0, names.1, first_info.shape, second_info.shape
+            )));
+        }
+        let shared = first_info.location == second_info.location
+            && stored_offset(first) == stored_offset(second)
+            && first_info.stored_size == second_info.stored_size
+            && first_info.compression == second_info.compression;
+        if shared {
+            return Ok(None);
+        }
+        if self.read_quantization(first).await? != self.read_quantization(second).await? {
+            return Ok(Some(format!(
+                "Tensors {} and {} are quantized differently",
+                names.0, names.1
+            )));
+        }
+        let first_data = self.read_tensor_any(*first).await?;
+        let second_data = self.read_tensor_any(*second).await?;
+        Ok((first_data.data_bytes() != second_data.data_bytes())
+            .then(|| format!("Tensors {} and {} hold different data", names.0, names.1)))
+    }
+}
+
+#[cfg(all(test, feature = "async"))]
+mod tests {
+    use futures::SinkExt;
+    use tempfile::NamedTempFile;
+    use tokio::fs::File;
+
+    use super::*;
+    use crate::{
+        Tensor, TensorBuffers, TensorBuffersSink, TensorBuffersWrite, TensorBuffersWriter,
+        WriterOptions,
+    };
+
+    fn dim(name: &str, axis: usize) -> (String, usize) {
+        (name.to_string(), axis)
+    }
+
+    #[tokio::test]
+    async fn test_constraints() {
+        let embed = Tensor::new("embed.weight", &[1.0f32, 2.0, 3.0, 4.0, 5.0, 6.0], vec![3, 2]);
+        let head = Tensor::new("lm_head.weight", &[1.0f32, 2.0, 3.0, 4.0, 5.0, 6.0], vec![3, 2]);
+        let norm = Tensor::new("norm.weight", &[1.0f32, 1.0], vec![2]);
+        let constraints = vec![
+            TensorConstraint::Tied(vec!["embed.weight".into(), "lm_head.weight".into()]),
+            TensorConstraint::Equal("embed.weight".into(), "tied.weight".into()),
+            TensorConstraint::SameDim(vec![dim("embed.weight", 1), dim("norm.weight", 0)]),
+        ];
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        let mut writer = TensorBuffersWriter::new(&mut file);
+        writer.add_alias("embed.weight", "tied.weight");
+        for constraint in &constraints {
+            writer.add_constraint(constraint.clone());
+        }
+        writer.write(vec![embed.clone(), head, norm], vec![]).await.unwrap();
+
+        let url = format!("file://{}", tmp.path().display());
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        assert_eq!(tensor_buffers.constraints().await.unwrap(), constraints);
+        let report = tensor_buffers.check_constraints().await.unwrap();
+        assert_eq!(report, ConstraintReport { satisfied: 3, violated: vec![] });
+
+        // A conversion that unties the head, changes the hidden size and drops a tensor.
+        let broken = vec![
+            TensorConstraint::Tied(vec!["embed.weight".into(), "lm_head.weight".into()]),
+            TensorConstraint::SameDim(vec![dim("embed.weight", 1), dim("norm.weight", 0)]),
+            TensorConstraint::SameDim(vec![dim("norm.weight", 0), dim("embed.weight", 2)]),
+            TensorConstraint::Equal("embed.weight".into(), "norm.weight".into()),
+            TensorConstraint::Tied(vec!["embed.weight".into(), "gone.weight".into()]),
+        ];
+        let file = File::create(tmp.path()).await.unwrap();
+        let mut sink = TensorBuffersSink::new(file, WriterOptions::new()).unwrap();
+        for constraint in &broken {
+            sink.add_constraint(constraint.clone());
+        }
+        sink.send(embed.into()).await.unwrap();
+        let head = Tensor::new("lm_head.weight", &[1.0f32, 2.0, 3.0, 4.0, 5.0, 7.0], vec![3, 2]);
+        sink.send(head.into()).await.unwrap();
+        sink.send(Tensor::new("norm.weight", &[1.0f32; 3], vec![3]).into()).await.unwrap();
+        // Constraints naming tensors that weren't written fail the write.
+        assert_eq!(
+            sink.close().await.unwrap_err().to_string(),
+            "Constraint refers to gone.weight, which is not a tensor"
+        );
+
+        let file = File::create(tmp.path()).await.unwrap();
+        let mut sink = TensorBuffersSink::new(file, WriterOptions::new()).unwrap();
+        for constraint in &broken[..4] {
+            sink.add_constraint(constraint.clone());
+        }
+        let embed = Tensor::new("embed.weight", &[1.0f32, 2.0, 3.0, 4.0, 5.0, 6.0], vec![3, 2]);
+        sink.send(embed.into()).await.unwrap();
+        let head = Tensor::new("lm_head.weight", &[1.0f32, 2.0, 3.0, 4.0, 5.0, 7.0], vec![3, 2]);
+        sink.send(head.into()).await.unwrap();
+        sink.send(Tensor::new("norm.weight", &[1.0f32; 3], vec![3]).into()).await.unwrap();
+        sink.close().await.unwrap();
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        let report = tensor_buffers.check_constraints().await.unwrap();
+        assert!(!report.is_ok());
+        assert_eq!(report.satisfied, 0);
+        let problems = report.violated.iter().map(|(_, problem)| problem).collect::<Vec<_>>();
+        assert_eq!(problems, [
+            "Tensors embed.weight and lm_head.weight hold different data",
+            "Dimension 1 of embed.weight has size 2, but dimension 0 of norm.weight has size 3",
+            "Tensor embed.weight has 2 dimensions, so no dimension 2",
+            "Tensors embed.weight and norm.weight have shapes [3, 2] and [3]",
+        ]);
+        assert_eq!(report.violated[0].0, broken[0]);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_constraints() {
+        let tensor = Tensor::new("w", &[1.0f32; 4], vec![4]);
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        let mut writer = TensorBuffersWriter::new(&mut file);
+        writer.add_constraint(TensorConstraint::Tied(vec!["w".into()]));
+        let error = writer.write(vec![tensor.clone()], vec![]).await.unwrap_err();
+        assert_eq!(error.to_string(), "Constraint Tied([\"w\"]) must name at least two tensors");
+
+        writer.add_constraint(TensorConstraint::Equal("w".into(), "v".into()));
+        let error = writer.write(vec![tensor.clone()], vec![]).await.unwrap_err();
+        assert_eq!(error.to_string(), "Constraint refers to v, which is not a tensor");
+
+        // Constraints apply to the next write only.
+        writer.write(vec![tensor], vec![]).await.unwrap();
+        let url = format!("file://{}", tmp.path().display());
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        assert!(tensor_buffers.constraints().await.unwrap().is_empty());
+    }
+}
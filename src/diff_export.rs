@@ -0,0 +1,205 @@
+use bytemuck::Pod;
+use tokio::io::{AsyncSeek, AsyncWrite};
+use tracing::{info, instrument};
+
+use crate::{
+    codec,
+    external_data::ExternalTensor,
+    generated::tensor_buffers::{ChecksumAlgorithm, Compression, TensorMetadata},
+    read_mode::tensor_problem,
+    tensor::stored_shape,
+    Num, Result, Tensor, TensorBuffers, TensorBuffersWrite, TensorBuffersWriter, TensorOperation,
+};
+
+/// What [`TensorBuffersWriter::write_diff`] wrote.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DiffReport {
+    /// Tensors new or changed since the base file, whose data was written.
+    pub written: Vec<String>,
+    /// Tensors unchanged since the base file, which refer to its data.
+    pub referenced: Vec<String>,
+}
+
+impl<W> TensorBuffersWriter<W>
+where
+    W: AsyncWrite + AsyncSeek + Unpin + Send,
+{
+    /// Writes a file holding only the tensors that changed since `base`, e.g. to publish a
+    /// fine-tuned model without copying the weights it shares with its base model. Unchanged
+    /// tensors are written as references to their data in the base file, which readers load from
+    /// it transparently, so the base file must stay at `base_location`.
+    ///
+    /// A tensor is unchanged if the base file has a tensor of the same name, data type and shape
+    /// whose data matches. Data stored uncompressed with a checksum is compared by checksum;
+    /// other data is loaded from the base file and compared byte for byte. Tensors of the base
+    /// file that are not in `tensors` are left out.
+    ///
+    /// # Arguments
+    /// * `base` - The file to compare with.
+    /// * `base_location` - Where readers find the base file: a URL, or a path relative to the
+    ///   file being written.
+    /// * `tensors` - Every tensor of the new file.
+    /// * `operations` - The operations of the new file.
+    ///
+    /// # Returns
+    /// Which tensors were written and which refer to the base file. Fails if unchanged tensors
+    /// are found and the format version is before 1.4.0, or the writer would train a compression
+    /// dictionary while unchanged tensors use the base file's.
+    #[instrument(skip_all, fields(tensors = tensors.len()))]
+    pub async fn write_diff<T>(
+        &mut self,
+        base: &TensorBuffers,
+        base_location: &str,
+        tensors: Vec<Tensor<'_, T>>,
+        operations: Vec<TensorOperation>,
+    ) -> Result<DiffReport>
+    where
+        T: Pod + Num,
+    {
+        let dictionary = base.get_metadata_root().await?.compression_dictionary();
+        let dictionary = dictionary.map(|dictionary| dictionary.bytes());
+        let mut report = DiffReport::default();
+        let mut changed = Vec::new();
+        for tensor in tensors {
+            let base_tensor = base.find_tensor_metadata(tensor.id()).await?;
+            match base_tensor {
+                Some(base_tensor) if unchanged(base, &base_tensor, &tensor).await? => {
+                    report.referenced.push(tensor.name().to_string());
+                    let quantization = base.read_quantization(&base_tensor).await?;
+                    let external =
+                        ExternalTensor::from_metadata(&base_tensor, base_location, quantization)?;
+                    self.add_external_tensor(external, dictionary)?;
+                }
+                _ => {
+                    report.written.push(tensor.name().to_string());
+                    changed.push(tensor);
+                }
+            }
+        }
+        self.write(changed, operations).await?;
+        info!(
+            written = report.written.len(),
+            referenced = report.referenced.len(),
+            "Wrote differential file"
+        );
+        Ok(report)
+    }
+}
+
+/// Returns whether `tensor` holds the same data as `base_tensor`, a tensor of the base file.
+async fn unchanged<T>(
+    base: &TensorBuffers,
+    base_tensor: &TensorMetadata<'_>,
+    tensor: &Tensor<'_, T>,
+) -> Result<bool>
+where
+    T: Pod + Num,
+{
+    if tensor_problem(base_tensor).is_some()
+        || base_tensor.data_type() != T::data_type().into()
+        || stored_shape(base_tensor)? != tensor.shape()
+    {
+        return Ok(false);
+    }
+    let data = bytemuck::cast_slice::<T, u8>(tensor.data());
+    let algorithm = base_tensor.checksum_algorithm();
+    if base_tensor.compression() == Compression::None && algorithm != ChecksumAlgorithm::None {
+        return Ok(codec::checksum(data, algorithm)? == base_tensor.checksum());
+    }
+    let base_data = base.get_tensor_data_by_id::<T>(base_tensor.id()).await?;
+    Ok(bytemuck::cast_slice::<T, u8>(base_data.data()) == data)
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+    use tokio::fs::File;
+
+    use super::*;
+    use crate::{ChecksumAlgorithm, WriterOptions};
+
+    #[tokio::test]
+    async fn test_write_diff() {
+        let dir = TempDir::new().unwrap();
+        let weights = (0..4).map(|i| vec![i as f32; 1024]).collect::<Vec<_>>();
+        let names = ["w0", "w1", "w2", "w3"];
+        let base_tensors = names
+            .iter()
+            .zip(&weights)
+            .map(|(name, data)| Tensor::new(name, data, vec![data.len()]))
+            .collect::<Vec<_>>();
+        let mut file = File::create(dir.path().join("base.tb")).await.unwrap();
+        let options = WriterOptions::new().with_checksum(ChecksumAlgorithm::XxHash64);
+        let mut writer = TensorBuffersWriter::with_options(&mut file, options.clone());
+        writer.write(base_tensors.clone(), vec![]).await.unwrap();
+        let base_url = format!("file://{}", dir.path().join("base.tb").display());
+        let base = TensorBuffers::open(&base_url).await.unwrap();
+
+        // Fine-tune one tensor, add one and drop one.
+        let tuned = vec![9.0f32; 1024];
+        let mut tensors = base_tensors[..3].to_vec();
+        tensors[1] = Tensor::new("w1", &tuned, vec![1024]);
+        tensors.push(Tensor::new("head", &[1.0f32, 2.0], vec![2]));
+        let mut file = File::create(dir.path().join("diff.tb")).await.unwrap();
+        let mut writer = TensorBuffersWriter::with_options(&mut file, options.clone());
+        let report = writer.write_diff(&base, "base.tb", tensors, vec![]).await.unwrap();
+        assert_eq!(report.written, ["w1", "head"]);
+        assert_eq!(report.referenced, ["w0", "w2"]);
+        let diff_size = file.metadata().await.unwrap().len();
+        assert!(diff_size < 4096 + 1024);
+
+        // Readers load referenced tensors from the base file.
+        let url = format!("file://{}", dir.path().join("diff.tb").display());
+        let diff = TensorBuffers::open(&url).await.unwrap();
+        assert!(diff.features().await.unwrap().contains(&"external_data"));
+        assert!(!diff.contains_tensor("w3").await.unwrap());
+        for (name, data) in [("w0", &weights[0]), ("w1", &tuned), ("w2", &weights[2])] {
+            let tensor = diff.get_tensor_data_by_name::<f32>(name).await.unwrap();
+            assert_eq!(tensor.data(), &data[..]);
+        }
+        let loaded = diff.get_tensors_data_by_name::<f32>(&["w2", "head", "w0"]).await.unwrap();
+        assert_eq!(loaded[0].data(), &weights[2][..]);
+        assert_eq!(loaded[1].data(), [1.0, 2.0]);
+        assert!(diff.verify_data(None).await.unwrap().is_ok());
+
+        // A diff of a diff still points at the file holding the data.
+        let mut file = File::create(dir.path().join("diff2.tb")).await.unwrap();
+        let mut writer = TensorBuffersWriter::with_options(&mut file, options);
+        let tensors = vec![Tensor::new("w0", &weights[0], vec![1024])];
+        let report = writer.write_diff(&diff, "diff.tb", tensors, vec![]).await.unwrap();
+        assert_eq!(report.referenced, ["w0"]);
+        let url = format!("file://{}", dir.path().join("diff2.tb").display());
+        let diff2 = TensorBuffers::open(&url).await.unwrap();
+        let w0 = diff2.stored_metadata(crate::utils::hash_key("w0")).await.unwrap();
+        assert_eq!(w0.external().unwrap().location(), "base.tb");
+        let tensor = diff2.get_tensor_data_by_name::<f32>("w0").await.unwrap();
+        assert_eq!(tensor.data(), &weights[0][..]);
+    }
+
+    #[tokio::test]
+    async fn test_write_diff_compressed_base() {
+        let dir = TempDir::new().unwrap();
+        let data = (0..4096).map(|i| (i % 7) as f32).collect::<Vec<_>>();
+        let tensors = vec![Tensor::new("w", &data, vec![data.len()])];
+        let mut file = File::create(dir.path().join("base.tb")).await.unwrap();
+        let options = WriterOptions::new().with_compression(Compression::Zstd, 3);
+        let mut writer = TensorBuffersWriter::with_options(&mut file, options.clone());
+        writer.write(tensors.clone(), vec![]).await.unwrap();
+        let base_url = format!("file://{}", dir.path().join("base.tb").display());
+        let base = TensorBuffers::open(&base_url).await.unwrap();
+
+        // Compressed data is compared after loading it.
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        let mut writer = TensorBuffersWriter::with_options(&mut bytes, options);
+        let report = writer.write_diff(&base, "base.tb", tensors, vec![]).await.unwrap();
+        assert_eq!(report.referenced, ["w"]);
+
+        let mut writer = TensorBuffersWriter::with_options(
+            &mut bytes,
+            WriterOptions::new().with_format_version("1.3.0"),
+        );
+        let tensors = vec![Tensor::new("w", &data, vec![data.len()])];
+        let err = writer.write_diff(&base, "base.tb", tensors, vec![]).await.unwrap_err();
+        assert_eq!(err.to_string(), "Format version 1.3.0 does not support external data");
+    }
+}
@@ -0,0 +1,250 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+use tokio::{
+    fs::{self, File, OpenOptions as FileOptions},
+    io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt},
+};
+use tracing::{info, instrument};
+
+use crate::{tensor_buffers_file::RemoteFile, utils::loggable_url, OpenOptions, Result};
+
+/// Size of the ranges a download fetches and records as complete.
+const DOWNLOAD_CHUNK_SIZE: u64 = 8 << 20;
+
+/// First line of the file recording a download's progress.
+const STATE_HEADER: &str = "tensorbuffers-download 1";
+
+/// What [`download`] did.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DownloadReport {
+    /// Size of the file in bytes.
+    pub bytes: u64,
+    /// Bytes fetched by this call.
+    pub bytes_downloaded: u64,
+    /// Bytes an earlier, interrupted call had already fetched.
+    pub bytes_resumed: u64,
+}
+
+/// Copies a remote file to `path`, resuming an interrupted earlier copy instead of starting over,
+/// so a 40 GB transfer that fails near the end only fetches what is missing.
+///
+/// The file is fetched in 8 MiB ranges into `{path}.part`, and each range is recorded in
+/// `{path}.part.state` once it is written. A later call picks up the recorded ranges if the
+/// server reports the same size and ETag as before, and starts over otherwise; without an ETag
+/// only the size is compared. Once every range is fetched, the part file is renamed to `path`
+/// and the state file removed.
+///
+/// # Arguments
+/// * `url` - The remote file.
+/// * `path` - Where to store it. Replaced if it exists.
+/// * `options` - The HTTP client, retries, timeouts and rate limiter to fetch it with.
+///
+/// # Returns
+/// How many bytes were fetched now and how many by earlier calls.
+pub async fn download(
+    url: &str,
+    path: impl AsRef<Path>,
+    options: &OpenOptions,
+) -> Result<DownloadReport> {
+    download_in_chunks(url, path.as_ref(), options, DOWNLOAD_CHUNK_SIZE).await
+}
+
+#[instrument(skip(path, options), fields(url = loggable_url(url)))]
+async fn download_in_chunks(
+    url: &str,
+    path: &Path,
+    options: &OpenOptions,
+    chunk_size: u64,
+) -> Result<DownloadReport> {
+    let mut remote = RemoteFile::open_with(url, options).await?;
+    let size = remote.file_size();
+    let header = state_header(size, remote.etag());
+    let part_path = with_suffix(path, ".part");
+    let state_path = with_suffix(path, ".part.state");
+
+    let done = match fs::read_to_string(&state_path).await {
+        Ok(state) if state.starts_with(&header) && file_size(&part_path).await == Some(size) => {
+            completed_ranges(&state[header.len()..])
+        }
+        _ => {
+            let part = File::create(&part_path).await?;
+            part.set_len(size).await?;
+            fs::write(&state_path, &header).await?;
+            HashSet::new()
+        }
+    };
+
+    let mut report = DownloadReport { bytes: size, ..DownloadReport::default() };
+    let mut part = FileOptions::new().write(true).open(&part_path).await?;
+    let mut state = FileOptions::new().append(true).open(&state_path).await?;
+    let mut buf = Vec::new();
+    for start in (0..size).step_by(chunk_size as usize) {
+        let end = (start + chunk_size).min(size);
+        if done.contains(&(start, end)) {
+            report.bytes_resumed += end - start;
+            continue;
+        }
+        buf.resize((end - start) as usize, 0);
+        remote.seek(std::io::SeekFrom::Start(start)).await?;
+        remote.read_exact(&mut buf).await?;
+        part.seek(std::io::SeekFrom::Start(start)).await?;
+        part.write_all(&buf).await?;
+        // The range is only recorded once its bytes are on disk.
+        part.sync_data().await?;
+        state.write_all(format!("{} {}\n", start, end).as_bytes()).await?;
+        state.flush().await?;
+        report.bytes_downloaded += end - start;
+    }
+    part.sync_all().await?;
+    drop(part);
+    fs::rename(&part_path, path).await?;
+    fs::remove_file(&state_path).await?;
+    info!(
+        bytes = report.bytes,
+        downloaded = report.bytes_downloaded,
+        resumed = report.bytes_resumed,
+        "Downloaded file"
+    );
+    Ok(report)
+}
+
+/// Returns the lines a state file starts with, which must match for a download to resume.
+fn state_header(size: u64, etag: Option<&str>) -> String {
+    format!("{}\nsize {}\netag {}\n", STATE_HEADER, size, etag.unwrap_or("-"))
+}
+
+/// Parses the ranges recorded after the header of a state file. A line cut short by an
+/// interruption is ignored, or parses as a range that matches no chunk.
+fn completed_ranges(lines: &str) -> HashSet<(u64, u64)> {
+    lines
+        .lines()
+        .filter_map(|line| {
+            let (start, end) = line.split_once(' ')?;
+            Some((start.parse().ok()?, end.parse().ok()?))
+        })
+        .collect()
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    path.into()
+}
+
+async fn file_size(path: &Path) -> Option<u64> {
+    Some(fs::metadata(path).await.ok()?.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    };
+
+    use tempfile::TempDir;
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    /// Serves `data` with range requests until `fail_after` of them were answered, then fails the
+    /// rest. Returns the URL and the number of range requests answered.
+    async fn serve(
+        data: Arc<Vec<u8>>,
+        etag: Arc<Mutex<String>>,
+        fail_after: Arc<AtomicUsize>,
+    ) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/model.tb", listener.local_addr().unwrap());
+        let served = Arc::new(AtomicUsize::new(0));
+        let counter = served.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = vec![0; 4096];
+                let len = stream.read(&mut request).await.unwrap();
+                let request = String::from_utf8_lossy(&request[..len]).to_lowercase();
+                let etag = etag.lock().unwrap().clone();
+                let range = request.lines().find_map(|line| line.strip_prefix("range: bytes="));
+                let response = match range.and_then(|range| range.trim().split_once('-')) {
+                    None => format!(
+                        "HTTP/1.1 200 OK\r\nConnection: close\r\nETag: {}\r\n\
+                         Content-Length: {}\r\n\r\n",
+                        etag,
+                        data.len()
+                    )
+                    .into_bytes(),
+                    Some(_)
+                        if counter.load(Ordering::SeqCst) >= fail_after.load(Ordering::SeqCst) =>
+                    {
+                        b"HTTP/1.1 404 Not Found\r\nConnection: close\r\nContent-Length: 0\r\n\r\n"
+                            .to_vec()
+                    }
+                    Some((start, end)) => {
+                        counter.fetch_add(1, Ordering::SeqCst);
+                        let (start, end) = (start.parse().unwrap(), end.parse::<usize>().unwrap());
+                        let body = &data[start..=end];
+                        let mut response = format!(
+                            "HTTP/1.1 206 Partial Content\r\nConnection: close\r\n\
+                             Content-Length: {}\r\n\r\n",
+                            body.len()
+                        )
+                        .into_bytes();
+                        response.extend_from_slice(body);
+                        response
+                    }
+                };
+                let _ = stream.write_all(&response).await;
+            }
+        });
+        (url, served)
+    }
+
+    #[tokio::test]
+    async fn test_download_resumes() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("model.tb");
+        let data = Arc::new((0..10_000u32).map(|i| (i % 251) as u8).collect::<Vec<_>>());
+        let etag = Arc::new(Mutex::new("\"v1\"".to_string()));
+        let fail_after = Arc::new(AtomicUsize::new(3));
+        let (url, served) = serve(data.clone(), etag.clone(), fail_after.clone()).await;
+        let options = OpenOptions::new().with_max_retries(0);
+
+        // The first attempt is cut off after three of the ten ranges.
+        assert!(download_in_chunks(&url, &path, &options, 1000).await.is_err());
+        assert!(!path.exists());
+        assert_eq!(served.load(Ordering::SeqCst), 3);
+
+        // The next one fetches only the rest.
+        fail_after.store(usize::MAX, Ordering::SeqCst);
+        let report = download_in_chunks(&url, &path, &options, 1000).await.unwrap();
+        assert_eq!(report, DownloadReport {
+            bytes: 10_000,
+            bytes_downloaded: 7000,
+            bytes_resumed: 3000
+        });
+        assert_eq!(served.load(Ordering::SeqCst), 10);
+        assert_eq!(std::fs::read(&path).unwrap(), *data);
+        assert!(!with_suffix(&path, ".part").exists());
+        assert!(!with_suffix(&path, ".part.state").exists());
+
+        // A file that changed since the interruption is fetched from the start.
+        fail_after.store(12, Ordering::SeqCst);
+        assert!(download_in_chunks(&url, &path, &options, 1000).await.is_err());
+        *etag.lock().unwrap() = "\"v2\"".to_string();
+        fail_after.store(usize::MAX, Ordering::SeqCst);
+        let report = download_in_chunks(&url, &path, &options, 1000).await.unwrap();
+        assert_eq!((report.bytes_downloaded, report.bytes_resumed), (10_000, 0));
+        assert_eq!(std::fs::read(&path).unwrap(), *data);
+    }
+
+    #[test]
+    fn test_completed_ranges() {
+        let ranges = completed_ranges("0 10\n10 20\n20");
+        assert_eq!(ranges, HashSet::from([(0, 10), (10, 20)]));
+        assert!(completed_ranges("").is_empty());
+    }
+}
@@ -0,0 +1,562 @@
+use std::{
+    cmp::Reverse,
+    mem::size_of_val,
+    path::{Path, PathBuf},
+};
+
+use bytemuck::Pod;
+use reed_solomon_erasure::galois_8::ReedSolomon;
+use tokio::{
+    fs::{self, File},
+    io::{AsyncReadExt, AsyncWriteExt},
+};
+use tracing::{info, instrument, warn};
+
+use crate::{
+    constants::ERASURE_MAGIC_BYTES, Num, Result, Tensor, TensorBuffers, TensorBuffersWrite,
+    TensorBuffersWriter, TensorOperation, WriterOptions,
+};
+
+/// Bytes of every shard encoded at once.
+const CHUNK_SIZE: u64 = 1 << 20;
+
+/// Size of the end of a parity shard after its manifest: the manifest's size and CRC32C and the
+/// magic bytes.
+const MANIFEST_FOOTER_SIZE: usize = 12;
+
+/// How `write_sharded` splits tensors into files.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShardOptions {
+    data_shards: u16,
+    parity_shards: u16,
+    writer_options: WriterOptions,
+}
+
+impl ShardOptions {
+    /// Creates options for `data_shards` files of tensors and `parity_shards` parity files, which
+    /// together can't exceed 256. Any `parity_shards` of the files can be lost.
+    pub fn new(data_shards: u16, parity_shards: u16) -> Self {
+        ShardOptions { data_shards, parity_shards, writer_options: WriterOptions::default() }
+    }
+
+    /// Sets the options the index and the data shards are written with.
+    pub fn with_writer_options(mut self, options: WriterOptions) -> Self {
+        self.writer_options = options;
+        self
+    }
+}
+
+/// What `reconstruct_shards` found and restored.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ShardReport {
+    /// The files that were lost or damaged and have been written again.
+    pub restored: Vec<PathBuf>,
+    /// Number of data and parity shards that were intact.
+    pub intact: usize,
+}
+
+/// What a parity shard records about the set it belongs to, so any parity shard can tell which
+/// files are intact and restore them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct ShardManifest {
+    /// Which parity shard holds the manifest.
+    parity_index: u16,
+    data_shards: u16,
+    parity_shards: u16,
+    /// Size of the largest data shard, which every parity shard's parity bytes have.
+    shard_size: u64,
+    /// The size and CRC32C of each data shard, then of each parity shard's parity bytes.
+    files: Vec<(u64, u32)>,
+    /// The index file, which is small enough to keep whole.
+    index: Vec<u8>,
+}
+
+impl ShardManifest {
+    /// Encodes the manifest followed by its size, CRC32C and the magic bytes.
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(16 + self.files.len() * 12 + 4 + self.index.len());
+        for value in [self.parity_index, self.data_shards, self.parity_shards, 0] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.extend_from_slice(&self.shard_size.to_le_bytes());
+        for (size, checksum) in &self.files {
+            bytes.extend_from_slice(&size.to_le_bytes());
+            bytes.extend_from_slice(&checksum.to_le_bytes());
+        }
+        bytes.extend_from_slice(&(self.index.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&self.index);
+        let checksum = crc32c::crc32c(&bytes);
+        bytes.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&checksum.to_le_bytes());
+        bytes.extend_from_slice(ERASURE_MAGIC_BYTES);
+        bytes
+    }
+
+    /// Parses a manifest encoded by `encode`, without its footer.
+    fn parse(bytes: &[u8]) -> Option<Self> {
+        let u16_at = |offset: usize| {
+            Some(u16::from_le_bytes(bytes.get(offset..offset + 2)?.try_into().ok()?))
+        };
+        let u32_at = |offset: usize| {
+            Some(u32::from_le_bytes(bytes.get(offset..offset + 4)?.try_into().ok()?))
+        };
+        let u64_at = |offset: usize| {
+            Some(u64::from_le_bytes(bytes.get(offset..offset + 8)?.try_into().ok()?))
+        };
+        let (data_shards, parity_shards) = (u16_at(2)?, u16_at(4)?);
+        let files = (0..data_shards as usize + parity_shards as usize)
+            .map(|i| Some((u64_at(16 + i * 12)?, u32_at(24 + i * 12)?)))
+            .collect::<Option<Vec<_>>>()?;
+        let index_offset = 16 + files.len() * 12;
+        let index_size = u32_at(index_offset)? as usize;
+        let index = bytes.get(index_offset + 4..)?;
+        (index.len() == index_size).then(|| ShardManifest {
+            parity_index: u16_at(0).unwrap(),
+            data_shards,
+            parity_shards,
+            shard_size: u64_at(8).unwrap(),
+            files,
+            index: index.to_vec(),
+        })
+    }
+
+    fn shards(&self) -> usize {
+        self.files.len()
+    }
+}
+
+/// Writes tensors across files with k+m erasure coding, for models spread over unreliable nodes:
+/// `data_shards` TensorBuffers files share the tensors, and `parity_shards` parity files let
+/// `reconstruct_shards` restore any `parity_shards` of them that are lost. Each data shard is a
+/// file of its own, and the file at `path` is an index that refers to their tensors, so readers
+/// open it like any other file while the shards are next to it.
+///
+/// Next to `model.tb`, the data shards are `model-00001-of-00004.tb` and so on, and the parity
+/// shards `model-00001-of-00002.parity`. Tensors are spread so the data shards are about the
+/// same size, and the operations are written to the index.
+///
+/// # Arguments
+/// * `path` - Where the index is written.
+/// * `tensors` - The tensors to write.
+/// * `operations` - The operations to write.
+/// * `options` - The number of data and parity shards and the writer options.
+///
+/// # Returns
+/// An error if the number of shards is invalid or a file can't be written.
+#[instrument(skip_all, fields(path = %path.as_ref().display()))]
+pub async fn write_sharded<T>(
+    path: impl AsRef<Path>,
+    tensors: Vec<Tensor<'_, T>>,
+    operations: Vec<TensorOperation>,
+    options: ShardOptions,
+) -> Result<()>
+where
+    T: Pod + Num,
+{
+    let path = path.as_ref();
+    let codec = reed_solomon(options.data_shards, options.parity_shards)?;
+    let data_shards = options.data_shards as usize;
+
+    // Largest tensors first, each to the smallest shard so far.
+    let mut order = (0..tensors.len()).collect::<Vec<_>>();
+    order.sort_by_key(|&i| Reverse(size_of_val(tensors[i].data())));
+    let mut sizes = vec![0; data_shards];
+    let mut assigned = vec![0; tensors.len()];
+    for i in order {
+        let shard = (0..data_shards).min_by_key(|&shard| sizes[shard]).unwrap();
+        sizes[shard] += size_of_val(tensors[i].data());
+        assigned[i] = shard;
+    }
+    let mut shards = (0..data_shards).map(|_| Vec::new()).collect::<Vec<_>>();
+    for (tensor, shard) in tensors.into_iter().zip(assigned) {
+        shards[shard].push(tensor);
+    }
+
+    let mut index_file = File::create(path).await?;
+    let mut index =
+        TensorBuffersWriter::with_options(&mut index_file, options.writer_options.clone());
+    for (i, tensors) in shards.into_iter().enumerate() {
+        let shard_path = data_shard_path(path, i, data_shards);
+        let names = tensors.iter().map(|tensor| tensor.name().to_string()).collect::<Vec<_>>();
+        let mut file = File::create(&shard_path).await?;
+        let mut writer =
+            TensorBuffersWriter::with_options(&mut file, options.writer_options.clone());
+        writer.write(tensors, vec![]).await?;
+        file.sync_all().await?;
+
+        let shard = TensorBuffers::open(&format!("file://{}", shard_path.display())).await?;
+        let names = names.iter().map(String::as_str).collect::<Vec<_>>();
+        index.add_external(&shard, &file_name(&shard_path), &names).await?;
+    }
+    index.write(Vec::<Tensor<T>>::new(), operations).await?;
+    index_file.sync_all().await?;
+
+    encode_parity(path, &codec).await?;
+    info!(data_shards, parity_shards = options.parity_shards, "Wrote sharded tensors");
+    Ok(())
+}
+
+/// Checks the shards of a set written by `write_sharded` and writes the lost or damaged ones
+/// again from the others, data and parity shards alike, as well as the index. Any parity shard
+/// knows the size and checksum of every file, so a damaged file counts as lost.
+///
+/// # Arguments
+/// * `path` - Where the index is, or was.
+///
+/// # Returns
+/// The restored files, or an error if no parity shard is intact or more shards are lost than
+/// there are parity shards.
+#[instrument(skip_all, fields(path = %path.as_ref().display()))]
+pub async fn reconstruct_shards(path: impl AsRef<Path>) -> Result<ShardReport> {
+    let path = path.as_ref();
+    let manifest = find_manifest(path)
+        .await?
+        .ok_or_else(|| format!("No intact parity shard of {} found", path.display()))?;
+    let data_shards = manifest.data_shards as usize;
+    let paths = (0..data_shards)
+        .map(|i| data_shard_path(path, i, data_shards))
+        .chain(
+            (0..manifest.parity_shards as usize)
+                .map(|j| parity_shard_path(path, j, manifest.parity_shards as usize)),
+        )
+        .collect::<Vec<_>>();
+    let mut lost = Vec::new();
+    for (i, shard_path) in paths.iter().enumerate() {
+        let (size, checksum) = manifest.files[i];
+        let intact = match i < data_shards {
+            true => file_checksum(shard_path, size, true).await == Some(checksum),
+            false => {
+                file_checksum(shard_path, size, false).await == Some(checksum)
+                    && read_manifest(shard_path).await.ok().flatten().is_some()
+            }
+        };
+        if !intact {
+            warn!(shard = %shard_path.display(), "Shard is lost or damaged");
+            lost.push(i);
+        }
+    }
+    if lost.len() > manifest.parity_shards as usize {
+        return Err(format!(
+            "{} of the {} shards of {} are lost or damaged, more than its {} parity shards can \
+             restore",
+            lost.len(),
+            manifest.shards(),
+            path.display(),
+            manifest.parity_shards
+        )
+        .into());
+    }
+
+    let mut report = ShardReport { intact: manifest.shards() - lost.len(), ..Default::default() };
+    if !lost.is_empty() {
+        restore(&manifest, &paths, &lost).await?;
+        report.restored.extend(lost.iter().map(|&i| paths[i].clone()));
+    }
+    if fs::read(path).await.ok().as_ref() != Some(&manifest.index) {
+        fs::write(path, &manifest.index).await?;
+        report.restored.insert(0, path.to_path_buf());
+    }
+    info!(restored = report.restored.len(), "Reconstructed shards");
+    Ok(report)
+}
+
+fn reed_solomon(data_shards: u16, parity_shards: u16) -> Result<ReedSolomon> {
+    ReedSolomon::new(data_shards as usize, parity_shards as usize)
+        .map_err(|error| format!("Invalid number of shards: {:?}", error).into())
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().unwrap_or_default().to_string_lossy().into_owned()
+}
+
+/// Returns the name of a shard file next to the index: its stem, the shard's number out of
+/// `count`, and `extension`.
+fn shard_path(index: &Path, shard: usize, count: usize, extension: &str) -> PathBuf {
+    let stem = index.file_stem().unwrap_or_default().to_string_lossy();
+    index.with_file_name(format!("{}-{:05}-of-{:05}.{}", stem, shard + 1, count, extension))
+}
+
+fn data_shard_path(index: &Path, shard: usize, data_shards: usize) -> PathBuf {
+    shard_path(index, shard, data_shards, "tb")
+}
+
+fn parity_shard_path(index: &Path, shard: usize, parity_shards: usize) -> PathBuf {
+    shard_path(index, shard, parity_shards, "parity")
+}
+
+/// Reads the next `size` bytes of a shard, the rest of it if it is shorter, into a buffer of
+/// `size` bytes padded with zeros.
+///
+/// # Returns
+/// The buffer and the number of bytes read.
+async fn read_chunk(file: &mut File, size: u64, remaining: u64) -> Result<(Vec<u8>, usize)> {
+    let mut chunk = vec![0; size as usize];
+    let available = remaining.min(size) as usize;
+    file.read_exact(&mut chunk[..available]).await?;
+    Ok((chunk, available))
+}
+
+/// Computes and writes the parity shards of the data shards of the index at `path`, each
+/// followed by the manifest of the set.
+async fn encode_parity(path: &Path, codec: &ReedSolomon) -> Result<()> {
+    let (data_shards, parity_shards) = (codec.data_shard_count(), codec.parity_shard_count());
+    let mut data_files = Vec::with_capacity(data_shards);
+    let mut files = Vec::with_capacity(codec.total_shard_count());
+    for i in 0..data_shards {
+        let file = File::open(data_shard_path(path, i, data_shards)).await?;
+        files.push((file.metadata().await?.len(), 0));
+        data_files.push(file);
+    }
+    let shard_size = files.iter().map(|(size, _)| *size).max().unwrap_or(0);
+    let mut parity_files = Vec::with_capacity(parity_shards);
+    for j in 0..parity_shards {
+        parity_files.push(File::create(parity_shard_path(path, j, parity_shards)).await?);
+        files.push((shard_size, 0));
+    }
+
+    let mut offset = 0;
+    while offset < shard_size {
+        let size = CHUNK_SIZE.min(shard_size - offset);
+        let mut shards = Vec::with_capacity(files.len());
+        for (file, (file_size, checksum)) in data_files.iter_mut().zip(&mut files) {
+            let (chunk, available) =
+                read_chunk(file, size, file_size.saturating_sub(offset)).await?;
+            *checksum = crc32c::crc32c_append(*checksum, &chunk[..available]);
+            shards.push(chunk);
+        }
+        shards.extend((0..parity_shards).map(|_| vec![0; size as usize]));
+        codec.encode(&mut shards).map_err(|error| format!("{:?}", error))?;
+        for (j, file) in parity_files.iter_mut().enumerate() {
+            let parity = &shards[data_shards + j];
+            let checksum = &mut files[data_shards + j].1;
+            *checksum = crc32c::crc32c_append(*checksum, parity);
+            file.write_all(parity).await?;
+        }
+        offset += size;
+    }
+
+    let mut manifest = ShardManifest {
+        parity_index: 0,
+        data_shards: data_shards as u16,
+        parity_shards: parity_shards as u16,
+        shard_size,
+        files,
+        index: fs::read(path).await?,
+    };
+    for (j, mut file) in parity_files.into_iter().enumerate() {
+        manifest.parity_index = j as u16;
+        file.write_all(&manifest.encode()).await?;
+        file.sync_all().await?;
+    }
+    Ok(())
+}
+
+/// Writes the `lost` shards again from the others.
+async fn restore(manifest: &ShardManifest, paths: &[PathBuf], lost: &[usize]) -> Result<()> {
+    let codec = reed_solomon(manifest.data_shards, manifest.parity_shards)?;
+    let mut files = Vec::with_capacity(paths.len());
+    for (i, shard_path) in paths.iter().enumerate() {
+        files.push(match lost.contains(&i) {
+            true => File::create(shard_path).await?,
+            false => File::open(shard_path).await?,
+        });
+    }
+    let mut offset = 0;
+    while offset < manifest.shard_size {
+        let size = CHUNK_SIZE.min(manifest.shard_size - offset);
+        let mut shards = Vec::with_capacity(files.len());
+        for (i, file) in files.iter_mut().enumerate() {
+            let remaining = manifest.files[i].0.saturating_sub(offset);
+            shards.push(match lost.contains(&i) {
+                true => (vec![0; size as usize], false),
+                false => (read_chunk(file, size, remaining).await?.0, true),
+            });
+        }
+        codec.reconstruct(&mut shards).map_err(|error| format!("{:?}", error))?;
+        for &i in lost {
+            let available = manifest.files[i].0.saturating_sub(offset).min(size) as usize;
+            files[i].write_all(&shards[i].0[..available]).await?;
+        }
+        offset += size;
+    }
+    for &i in lost {
+        if i >= manifest.data_shards as usize {
+            let parity_index = (i - manifest.data_shards as usize) as u16;
+            let manifest = ShardManifest { parity_index, ..manifest.clone() };
+            files[i].write_all(&manifest.encode()).await?;
+        }
+        files[i].sync_all().await?;
+    }
+    Ok(())
+}
+
+/// Returns the CRC32C of the first `size` bytes of a file, or `None` if it can't be read or has a
+/// different size. With `exact` unset, the file may be longer.
+async fn file_checksum(path: &Path, size: u64, exact: bool) -> Option<u32> {
+    let mut file = File::open(path).await.ok()?;
+    let file_size = file.metadata().await.ok()?.len();
+    if file_size < size || (exact && file_size != size) {
+        return None;
+    }
+    let mut checksum = 0;
+    let mut offset = 0;
+    while offset < size {
+        let (chunk, available) =
+            read_chunk(&mut file, CHUNK_SIZE.min(size - offset), size - offset).await.ok()?;
+        checksum = crc32c::crc32c_append(checksum, &chunk[..available]);
+        offset += available as u64;
+    }
+    Some(checksum)
+}
+
+/// Reads the manifest at the end of a parity shard.
+///
+/// # Returns
+/// `None` if the file doesn't end with an intact manifest.
+async fn read_manifest(path: &Path) -> Result<Option<ShardManifest>> {
+    let bytes = fs::read(path).await?;
+    let Some(footer) = bytes.len().checked_sub(MANIFEST_FOOTER_SIZE).map(|end| &bytes[end..])
+    else {
+        return Ok(None);
+    };
+    let size = u32::from_le_bytes(footer[..4].try_into().unwrap()) as usize;
+    let checksum = u32::from_le_bytes(footer[4..8].try_into().unwrap());
+    let Some(start) = (bytes.len() - MANIFEST_FOOTER_SIZE).checked_sub(size) else {
+        return Ok(None);
+    };
+    let manifest = &bytes[start..bytes.len() - MANIFEST_FOOTER_SIZE];
+    if &footer[8..] != ERASURE_MAGIC_BYTES || crc32c::crc32c(manifest) != checksum {
+        return Ok(None);
+    }
+    Ok(ShardManifest::parse(manifest))
+}
+
+/// Finds an intact manifest among the parity shards next to the index at `path`.
+async fn find_manifest(path: &Path) -> Result<Option<ShardManifest>> {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let prefix = format!("{}-", stem);
+    let directory = path.parent().filter(|parent| !parent.as_os_str().is_empty());
+    let mut entries = fs::read_dir(directory.unwrap_or_else(|| Path::new("."))).await?;
+    let mut candidates = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with(&prefix) && name.ends_with(".parity") {
+            candidates.push(entry.path());
+        }
+    }
+    candidates.sort();
+    for candidate in candidates {
+        if let Ok(Some(manifest)) = read_manifest(&candidate).await {
+            let parity_shards = manifest.parity_shards as usize;
+            if parity_shard_path(path, manifest.parity_index as usize, parity_shards) == candidate {
+                return Ok(Some(manifest));
+            }
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::{utils::hash_key, Compression, Operation};
+
+    async fn read_all(path: &Path) -> Vec<Vec<f32>> {
+        let tensor_buffers =
+            TensorBuffers::open(&format!("file://{}", path.display())).await.unwrap();
+        let mut values = Vec::new();
+        for name in ["a", "b", "c", "d", "e"] {
+            values.push(
+                tensor_buffers.get_tensor_data_by_name::<f32>(name).await.unwrap().data().to_vec(),
+            );
+        }
+        values
+    }
+
+    #[tokio::test]
+    async fn test_reconstruct_shards() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("model.tb");
+        let data = (0..5000).map(|i| i as f32 * 0.25).collect::<Vec<_>>();
+        let tensors = vec![
+            Tensor::new("a", &data[..3000], vec![3000]),
+            Tensor::new("b", &data[..1500], vec![1500]),
+            Tensor::new("c", &data[..1400], vec![1400]),
+            Tensor::new("d", &data[..10], vec![10]),
+            Tensor::new("e", &data, vec![50, 100]),
+        ];
+        let operations = vec![
+            crate::TensorOperation::new(1, Operation::None, vec![], hash_key("a")),
+            crate::TensorOperation::new(2, Operation::Exp, vec![1], hash_key("a_exp")),
+        ];
+        let writer_options = WriterOptions::new().with_compression(Compression::Zstd, 3);
+        let options = ShardOptions::new(3, 2).with_writer_options(writer_options);
+        write_sharded(&path, tensors, operations, options).await.unwrap();
+        let expected = read_all(&path).await;
+        assert_eq!(expected[4], data);
+        let shards = ["00001-of-00003.tb", "00002-of-00003.tb", "00003-of-00003.tb"];
+        let parity = ["00001-of-00002.parity", "00002-of-00002.parity"];
+        let paths =
+            shards.iter().chain(&parity).map(|name| dir.path().join(format!("model-{}", name)));
+        let paths = paths.collect::<Vec<_>>();
+        let originals = paths.iter().map(|path| std::fs::read(path).unwrap()).collect::<Vec<_>>();
+        let index = std::fs::read(&path).unwrap();
+        // Each data shard is a file of its own.
+        let shard = TensorBuffers::open(&format!("file://{}", paths[0].display())).await.unwrap();
+        assert_eq!(shard.get_tensor_data_by_name::<f32>("e").await.unwrap().data(), data);
+
+        let report = reconstruct_shards(&path).await.unwrap();
+        assert_eq!(report, ShardReport { restored: vec![], intact: 5 });
+
+        // Any two files can be lost or damaged, the index aside.
+        std::fs::remove_file(&paths[0]).unwrap();
+        let mut damaged = originals[2].clone();
+        damaged[100] ^= 1;
+        std::fs::write(&paths[2], damaged).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let report = reconstruct_shards(&path).await.unwrap();
+        assert_eq!(report.restored, [path.clone(), paths[0].clone(), paths[2].clone()]);
+        assert_eq!(report.intact, 3);
+        for (path, original) in paths.iter().zip(&originals) {
+            assert_eq!(&std::fs::read(path).unwrap(), original);
+        }
+        assert_eq!(std::fs::read(&path).unwrap(), index);
+        assert_eq!(read_all(&path).await, expected);
+
+        // Parity shards are restored too.
+        std::fs::remove_file(&paths[1]).unwrap();
+        std::fs::remove_file(&paths[3]).unwrap();
+        assert_eq!(reconstruct_shards(&path).await.unwrap().restored, [
+            paths[1].clone(),
+            paths[3].clone()
+        ]);
+        assert_eq!(std::fs::read(&paths[3]).unwrap(), originals[3]);
+
+        // Three lost shards are too many.
+        for path in &paths[..3] {
+            std::fs::remove_file(path).unwrap();
+        }
+        let error = reconstruct_shards(&path).await.unwrap_err();
+        assert!(error.to_string().starts_with("3 of the 5 shards of"));
+        for path in &paths[3..] {
+            std::fs::remove_file(path).unwrap();
+        }
+        let error = reconstruct_shards(&path).await.unwrap_err();
+        assert!(error.to_string().starts_with("No intact parity shard of"));
+    }
+
+    #[tokio::test]
+    async fn test_write_sharded_errors() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("model.tb");
+        let tensors = vec![Tensor::new("a", &[1.0f32], vec![1])];
+        for options in
+            [ShardOptions::new(0, 1), ShardOptions::new(2, 0), ShardOptions::new(200, 60)]
+        {
+            assert!(write_sharded(&path, tensors.clone(), vec![], options).await.is_err());
+        }
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,727 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use bytemuck::Pod;
+use fnv::{FnvHashMap, FnvHashSet};
+use futures::{
+    channel::oneshot,
+    stream::{self, FuturesUnordered, StreamExt},
+};
+use rayon::{ThreadPool, ThreadPoolBuilder};
+
+use crate::{
+    kernels::{self, ArithmeticOp},
+    num_trait::Float,
+    utils::hash_key,
+    value_cache::ValueCache,
+    Operation, Result, TensorBuffers, TensorGraph, TensorId, TensorOperation, TensorOperationId,
+    TensorValue,
+};
+
+/// Number of source tensors fetched ahead of the operations that need them.
+const DEFAULT_PREFETCH_DEPTH: usize = 4;
+
+/// Slope used by `Operation::LeakyReLU` for negative inputs.
+const LEAKY_RELU_ALPHA: f64 = 0.01;
+
+/// Evaluates the operation graph stored in a TensorBuffers file on the CPU.
+///
+/// `Operation::None` nodes are sources: their output tensor is loaded from the file the first
+/// time it is needed. Every other operation consumes the results of its `input_operations`.
+///
+/// Each operation starts on a rayon pool as soon as all of its inputs are available, so
+/// independent operations run in parallel. Meanwhile the calling task keeps fetching source
+/// tensors ahead of need, ordered by when they are first consumed, so reading weights from a
+/// (possibly remote) file overlaps with compute instead of preceding it.
+///
+/// With a cache enabled, results are kept across runs so that later runs sharing a prefix of
+/// the graph neither recompute nor re-read it.
+pub struct Executor<'a> {
+    tensor_buffers: &'a TensorBuffers,
+    pool: Option<ThreadPool>,
+    cache: Option<Mutex<ValueCache>>,
+    prefetch_depth: usize,
+}
+
+impl<'a> Executor<'a> {
+    pub fn new(tensor_buffers: &'a TensorBuffers) -> Self {
+        Executor { tensor_buffers, pool: None, cache: None, prefetch_depth: DEFAULT_PREFETCH_DEPTH }
+    }
+
+    /// Limits execution to a dedicated pool of `threads` threads instead of the global rayon pool.
+    pub fn with_threads(mut self, threads: usize) -> Result<Self> {
+        self.pool = Some(ThreadPoolBuilder::new().num_threads(threads).build()?);
+        Ok(self)
+    }
+
+    /// Sets how many source tensors may be fetched ahead of the operations that need them.
+    /// Larger values hide more latency when reading from a remote file.
+    pub fn with_prefetch_depth(mut self, prefetch_depth: usize) -> Self {
+        self.prefetch_depth = prefetch_depth;
+        self
+    }
+
+    /// Caches source tensors and intermediate results across runs, keeping at most
+    /// `budget_bytes` of tensor data and evicting the least recently used results first.
+    pub fn with_cache(mut self, budget_bytes: usize) -> Self {
+        self.cache = Some(Mutex::new(ValueCache::new(budget_bytes)));
+        self
+    }
+
+    /// Returns the number of bytes of tensor data held by the cache.
+    pub fn cached_bytes(&self) -> usize {
+        match &self.cache {
+            Some(cache) => cache.lock().unwrap().used(),
+            None => 0,
+        }
+    }
+
+    /// Drops every cached result.
+    pub fn clear_cache(&self) {
+        if let Some(cache) = &self.cache {
+            cache.lock().unwrap().clear();
+        }
+    }
+
+    fn cached<T: Float + Pod>(&self, operation_id: TensorOperationId) -> Option<TensorValue<T>> {
+        self.cache.as_ref()?.lock().unwrap().get(operation_id)
+    }
+
+    fn cache<T: Float + Pod>(&self, operation_id: TensorOperationId, value: &TensorValue<T>) {
+        if let Some(cache) = &self.cache {
+            cache.lock().unwrap().insert(operation_id, value.clone());
+        }
+    }
+
+    /// Computes the named output tensors.
+    ///
+    /// Only the operations the requested outputs depend on are evaluated, and only their
+    /// source tensors are read from the file. Cached results stop the traversal, so nothing
+    /// behind them is evaluated or read.
+    ///
+    /// # Arguments
+    /// * `output_names` - Names of the tensors to compute; each must be the output of an operation.
+    ///
+    /// # Returns
+    /// Returns the computed tensors keyed by name.
+    pub async fn run<T>(&self, output_names: &[&str]) -> Result<HashMap<String, TensorValue<T>>>
+    where
+        T: Float + Pod,
+    {
+        let graph = self.tensor_buffers.graph().await?;
+        let targets = resolve_outputs(&graph, output_names)?;
+        let mut values: FnvHashMap<TensorOperationId, Arc<TensorValue<T>>> = FnvHashMap::default();
+        let mut required = FnvHashSet::default();
+        let mut stack = targets.iter().map(|&(_, operation_id)| operation_id).collect::<Vec<_>>();
+        while let Some(operation_id) = stack.pop() {
+            if required.contains(&operation_id) || values.contains_key(&operation_id) {
+                continue;
+            }
+            if let Some(value) = self.cached::<T>(operation_id) {
+                values.insert(operation_id, Arc::new(value));
+                continue;
+            }
+            required.insert(operation_id);
+            stack.extend(graph.dependencies_of(operation_id)?.iter().map(|op| op.id()));
+        }
+        let ordered = graph
+            .operations_in_topological_order()?
+            .into_iter()
+            .filter(|op| required.contains(&op.id()))
+            .collect::<Vec<_>>();
+
+        // Every operation waits for the number of inputs not yet available; when a value
+        // arrives, its consumers are decremented and started once nothing is missing.
+        let mut missing: FnvHashMap<TensorOperationId, usize> = FnvHashMap::default();
+        let mut consumers: FnvHashMap<TensorOperationId, Vec<&TensorOperation>> =
+            FnvHashMap::default();
+        let mut ready = Vec::new();
+        for &op in ordered.iter().filter(|op| *op.operation() != Operation::None) {
+            let unavailable = op
+                .input_operations()
+                .iter()
+                .filter(|id| !values.contains_key(id))
+                .collect::<Vec<_>>();
+            for &id in &unavailable {
+                consumers.entry(*id).or_default().push(op);
+            }
+            if unavailable.is_empty() {
+                ready.push(op);
+            } else {
+                missing.insert(op.id(), unavailable.len());
+            }
+        }
+
+        // Built in a loop, as a closure mapping the operations would be held across awaits, and
+        // its type isn't `Send` for every borrow of them.
+        let mut fetches = Vec::new();
+        for op in prefetch_order(&ordered) {
+            fetches.push(self.fetch::<T>(op));
+        }
+        let mut fetches = stream::iter(fetches).buffered(self.prefetch_depth.max(1));
+        let mut running = FuturesUnordered::new();
+        let mut remaining = ordered.len();
+        loop {
+            for op in ready.drain(..) {
+                let inputs = op.input_operations().iter().map(|id| values[id].clone()).collect();
+                let result = self.spawn_evaluate(op.clone(), inputs);
+                running.push(async move { (op, result.await) });
+            }
+            if remaining == 0 {
+                break;
+            }
+            let (op, value) = tokio::select! {
+                Some(fetched) = fetches.next() => fetched?,
+                Some((op, result)) = running.next() => {
+                    (op, result.map_err(|_| "Evaluation was cancelled")??)
+                }
+                else => return Err("Execution stalled before all operations ran".into()),
+            };
+            remaining -= 1;
+            self.cache(op.id(), &value);
+            values.insert(op.id(), Arc::new(value));
+            for consumer in consumers.remove(&op.id()).unwrap_or_default() {
+                let count = missing.get_mut(&consumer.id()).unwrap();
+                *count -= 1;
+                if *count == 0 {
+                    ready.push(consumer);
+                }
+            }
+        }
+
+        Ok(targets
+            .into_iter()
+            .map(|(name, operation_id)| (name.to_string(), values[&operation_id].as_ref().clone()))
+            .collect())
+    }
+
+    /// Loads the tensor a source operation outputs.
+    async fn fetch<'g, T: Float + Pod>(
+        &self,
+        op: &'g TensorOperation,
+    ) -> Result<(&'g TensorOperation, TensorValue<T>)> {
+        let tensor = self.tensor_buffers.get_tensor_data_by_id::<T>(*op.output()).await?;
+        Ok((op, TensorValue::from(tensor)))
+    }
+
+    /// Evaluates `op` on the rayon pool, leaving the calling task free to keep fetching.
+    /// Errors come back as the strings `evaluate_inputs` returns.
+    fn spawn_evaluate<T: Float + Pod>(
+        &self,
+        op: TensorOperation,
+        inputs: Vec<Arc<TensorValue<T>>>,
+    ) -> oneshot::Receiver<std::result::Result<TensorValue<T>, String>> {
+        let (sender, receiver) = oneshot::channel();
+        let task = move || {
+            let inputs = inputs.iter().map(Arc::as_ref).collect::<Vec<_>>();
+            let _ = sender.send(evaluate_inputs(&op, &inputs));
+        };
+        match &self.pool {
+            Some(pool) => pool.spawn(task),
+            None => rayon::spawn(task),
+        }
+        receiver
+    }
+}
+
+pub(crate) fn evaluate<T: Float + Pod>(
+    op: &TensorOperation,
+    values: &FnvHashMap<TensorOperationId, TensorValue<T>>,
+) -> std::result::Result<TensorValue<T>, String> {
+    let inputs = op.input_operations().iter().map(|id| &values[id]).collect::<Vec<_>>();
+    evaluate_inputs(op, &inputs)
+}
+
+fn evaluate_inputs<T: Float + Pod>(
+    op: &TensorOperation,
+    inputs: &[&TensorValue<T>],
+) -> std::result::Result<TensorValue<T>, String> {
+    compute(*op.operation(), inputs)
+        .map_err(|e| format!("Operation {} ({:?}) failed: {}", op.id(), op.operation(), e))
+}
+
+/// Orders the sources in `ordered` by the wave of their first consumer, so the tensors
+/// needed soonest are fetched first.
+fn prefetch_order<'g>(ordered: &[&'g TensorOperation]) -> Vec<&'g TensorOperation> {
+    let sources: FnvHashMap<TensorOperationId, &TensorOperation> = ordered
+        .iter()
+        .filter(|op| *op.operation() == Operation::None)
+        .map(|op| (op.id(), *op))
+        .collect();
+    let mut scheduled = FnvHashSet::default();
+    let mut order = Vec::with_capacity(sources.len());
+    for wave in waves(ordered) {
+        for op in wave {
+            for id in op.input_operations() {
+                if let Some(source) = sources.get(id).filter(|_| scheduled.insert(*id)) {
+                    order.push(*source);
+                }
+            }
+        }
+    }
+    // Sources that are requested directly have no consumer.
+    order.extend(
+        ordered.iter().filter(|op| sources.contains_key(&op.id()) && scheduled.insert(op.id())),
+    );
+    order
+}
+
+/// Groups topologically ordered operations into waves; every operation only depends on
+/// operations from earlier waves, so the operations of one wave can run concurrently.
+/// Inputs outside `ordered` are treated as already available.
+fn waves<'g>(ordered: &[&'g TensorOperation]) -> Vec<Vec<&'g TensorOperation>> {
+    let mut levels: FnvHashMap<TensorOperationId, usize> = FnvHashMap::default();
+    let mut waves: Vec<Vec<&TensorOperation>> = Vec::new();
+    for &op in ordered {
+        let level = op
+            .input_operations()
+            .iter()
+            .filter_map(|id| levels.get(id).map(|level| level + 1))
+            .max()
+            .unwrap_or_default();
+        levels.insert(op.id(), level);
+        if waves.len() <= level {
+            waves.resize_with(level + 1, Vec::new);
+        }
+        waves[level].push(op);
+    }
+    waves
+}
+
+/// Maps output tensor names to the operations producing them.
+pub(crate) fn resolve_outputs<'n>(
+    graph: &TensorGraph,
+    output_names: &[&'n str],
+) -> Result<Vec<(&'n str, TensorOperationId)>> {
+    let producers: FnvHashMap<TensorId, TensorOperationId> =
+        graph.operations().iter().map(|op| (*op.output(), op.id())).collect();
+    let mut targets = Vec::with_capacity(output_names.len());
+    for &name in output_names {
+        let operation_id = producers
+            .get(&hash_key(name))
+            .ok_or_else(|| format!("No operation produces tensor '{}'", name))?;
+        targets.push((name, *operation_id));
+    }
+    Ok(targets)
+}
+
+/// Collects the given operations and everything they transitively depend on.
+pub(crate) fn required_operations(
+    graph: &TensorGraph,
+    targets: impl Iterator<Item = TensorOperationId>,
+) -> Result<FnvHashSet<TensorOperationId>> {
+    let mut required = FnvHashSet::default();
+    let mut stack = targets.collect::<Vec<_>>();
+    while let Some(operation_id) = stack.pop() {
+        if required.insert(operation_id) {
+            stack.extend(graph.dependencies_of(operation_id)?.iter().map(|op| op.id()));
+        }
+    }
+    Ok(required)
+}
+
+fn expect_inputs<T>(inputs: &[&TensorValue<T>], expected: usize) -> Result<()> {
+    if inputs.len() != expected {
+        return Err(format!("expected {} inputs, found {}", expected, inputs.len()).into());
+    }
+    Ok(())
+}
+
+fn map<T, F>(input: &TensorValue<T>, f: F) -> TensorValue<T>
+where
+    T: Copy + Send + Sync,
+    F: Fn(T) -> T + Sync,
+{
+    TensorValue { shape: input.shape.clone(), data: kernels::unary(&input.data, f) }
+}
+
+fn zip<T, F>(lhs: &TensorValue<T>, rhs: &TensorValue<T>, f: F) -> Result<TensorValue<T>>
+where
+    T: Copy,
+    F: Fn(T, T) -> T,
+{
+    let (data, shape) = kernels::binary(&lhs.data, &lhs.shape, &rhs.data, &rhs.shape, f)?;
+    Ok(TensorValue { shape, data })
+}
+
+fn arithmetic<T: Float + Pod>(
+    op: ArithmeticOp,
+    lhs: &TensorValue<T>,
+    rhs: &TensorValue<T>,
+) -> Result<TensorValue<T>> {
+    let (data, shape) = kernels::arithmetic(op, &lhs.data, &lhs.shape, &rhs.data, &rhs.shape)?;
+    Ok(TensorValue { shape, data })
+}
+
+fn scalar<T>(value: T) -> TensorValue<T> {
+    TensorValue { shape: Vec::new(), data: vec![value] }
+}
+
+fn mean<T: Float>(data: &[T]) -> T {
+    kernels::sum(data) / T::from_f64(data.len() as f64)
+}
+
+fn matrix_dims<T>(value: &TensorValue<T>) -> Result<(usize, usize)> {
+    match value.shape[..] {
+        [rows, cols] => Ok((rows, cols)),
+        _ => Err(format!("expected a 2-D tensor, found shape {:?}", value.shape).into()),
+    }
+}
+
+fn matmul<T: Float + Pod>(lhs: &TensorValue<T>, rhs: &TensorValue<T>) -> Result<TensorValue<T>> {
+    let (m, k) = matrix_dims(lhs)?;
+    let (rhs_k, n) = matrix_dims(rhs)?;
+    if k != rhs_k {
+        return Err(format!("cannot multiply shapes {:?} and {:?}", lhs.shape, rhs.shape).into());
+    }
+    Ok(TensorValue { shape: vec![m, n], data: kernels::matmul(&lhs.data, &rhs.data, m, k, n) })
+}
+
+fn concat<T: Copy>(inputs: &[&TensorValue<T>]) -> Result<TensorValue<T>> {
+    let first = inputs.first().ok_or("expected at least one input")?;
+    let inner = first.shape.get(1..).ok_or("cannot concatenate rank-0 tensors")?;
+    let mut rows = 0;
+    let mut data = Vec::new();
+    for input in inputs {
+        if input.shape.get(1..) != Some(inner) {
+            return Err(format!(
+                "cannot concatenate shapes {:?} and {:?} along axis 0",
+                first.shape, input.shape
+            )
+            .into());
+        }
+        rows += input.shape[0];
+        data.extend_from_slice(&input.data);
+    }
+    let mut shape = vec![rows];
+    shape.extend_from_slice(inner);
+    Ok(TensorValue { shape, data })
+}
+
+pub(crate) fn compute<T: Float + Pod>(
+    operation: Operation,
+    inputs: &[&TensorValue<T>],
+) -> Result<TensorValue<T>> {
+    match operation {
+        Operation::Add | Operation::Sub | Operation::Mul | Operation::Div => {
+            expect_inputs(inputs, 2)?;
+            let op = match operation {
+                Operation::Add => ArithmeticOp::Add,
+                Operation::Sub => ArithmeticOp::Sub,
+                Operation::Mul => ArithmeticOp::Mul,
+                _ => ArithmeticOp::Div,
+            };
+            arithmetic(op, inputs[0], inputs[1])
+        }
+        Operation::Pow => {
+            expect_inputs(inputs, 2)?;
+            zip(inputs[0], inputs[1], T::powf)
+        }
+        Operation::Sqr => {
+            expect_inputs(inputs, 1)?;
+            Ok(map(inputs[0], |x| x * x))
+        }
+        Operation::Sqrt => {
+            expect_inputs(inputs, 1)?;
+            Ok(map(inputs[0], T::sqrt))
+        }
+        Operation::Sigmoid => {
+            expect_inputs(inputs, 1)?;
+            Ok(map(inputs[0], |x| T::one() / (T::one() + (-x).exp())))
+        }
+        Operation::Tanh => {
+            expect_inputs(inputs, 1)?;
+            Ok(map(inputs[0], T::tanh))
+        }
+        Operation::ReLU => {
+            expect_inputs(inputs, 1)?;
+            Ok(map(inputs[0], |x| if x > T::zero() { x } else { T::zero() }))
+        }
+        Operation::LeakyReLU => {
+            expect_inputs(inputs, 1)?;
+            let alpha = T::from_f64(LEAKY_RELU_ALPHA);
+            Ok(map(inputs[0], |x| if x > T::zero() { x } else { alpha * x }))
+        }
+        Operation::Softplus => {
+            expect_inputs(inputs, 1)?;
+            Ok(map(inputs[0], |x| (T::one() + x.exp()).ln()))
+        }
+        Operation::Log => {
+            expect_inputs(inputs, 1)?;
+            Ok(map(inputs[0], T::ln))
+        }
+        Operation::Exp => {
+            expect_inputs(inputs, 1)?;
+            Ok(map(inputs[0], T::exp))
+        }
+        Operation::Abs => {
+            expect_inputs(inputs, 1)?;
+            Ok(map(inputs[0], T::abs))
+        }
+        Operation::Dropout => {
+            // Dropout is the identity at inference time.
+            expect_inputs(inputs, 1)?;
+            Ok(inputs[0].clone())
+        }
+        Operation::Flatten => {
+            expect_inputs(inputs, 1)?;
+            Ok(TensorValue { shape: vec![inputs[0].data.len()], data: inputs[0].data.clone() })
+        }
+        Operation::Sum => {
+            expect_inputs(inputs, 1)?;
+            Ok(scalar(kernels::sum(&inputs[0].data)))
+        }
+        Operation::Mean => {
+            expect_inputs(inputs, 1)?;
+            Ok(scalar(mean(&inputs[0].data)))
+        }
+        Operation::Argmax => {
+            expect_inputs(inputs, 1)?;
+            let index =
+                kernels::argmax(&inputs[0].data).ok_or("cannot take argmax of empty tensor")?;
+            Ok(scalar(T::from_f64(index as f64)))
+        }
+        Operation::Softmax => {
+            expect_inputs(inputs, 1)?;
+            let row_len = inputs[0].shape.last().copied().unwrap_or(1);
+            Ok(TensorValue {
+                shape: inputs[0].shape.clone(),
+                data: kernels::softmax(&inputs[0].data, row_len),
+            })
+        }
+        Operation::MatMul => {
+            expect_inputs(inputs, 2)?;
+            matmul(inputs[0], inputs[1])
+        }
+        Operation::FC => {
+            // Fully connected layer: inputs are [x, weights] or [x, weights, bias].
+            if inputs.len() != 2 && inputs.len() != 3 {
+                return Err(format!("expected 2 or 3 inputs, found {}", inputs.len()).into());
+            }
+            let product = matmul(inputs[0], inputs[1])?;
+            match inputs.get(2) {
+                Some(bias) => arithmetic(ArithmeticOp::Add, &product, bias),
+                None => Ok(product),
+            }
+        }
+        Operation::Transpose => {
+            expect_inputs(inputs, 1)?;
+            let (rows, cols) = matrix_dims(inputs[0])?;
+            Ok(TensorValue {
+                shape: vec![cols, rows],
+                data: kernels::transpose(&inputs[0].data, rows, cols),
+            })
+        }
+        Operation::Concat => concat(inputs),
+        Operation::MSELoss | Operation::L1Loss => {
+            expect_inputs(inputs, 2)?;
+            let f: fn(T, T) -> T = match operation {
+                Operation::MSELoss => |a, b| (a - b) * (a - b),
+                _ => |a, b| (a - b).abs(),
+            };
+            let errors = zip(inputs[0], inputs[1], f)?;
+            Ok(scalar(mean(&errors.data)))
+        }
+        Operation::CrossEntropyLoss => {
+            // Inputs are predicted probabilities and target distributions of the same shape;
+            // the loss is averaged over rows.
+            expect_inputs(inputs, 2)?;
+            if inputs[0].shape != inputs[1].shape {
+                return Err(format!(
+                    "prediction shape {:?} does not match target shape {:?}",
+                    inputs[0].shape, inputs[1].shape
+                )
+                .into());
+            }
+            let terms = zip(inputs[0], inputs[1], |p, t| t * p.ln())?;
+            let rows = inputs[0].data.len() / inputs[0].shape.last().copied().unwrap_or(1).max(1);
+            Ok(scalar(-kernels::sum(&terms.data) / T::from_f64(rows.max(1) as f64)))
+        }
+        _ => Err(format!("operation {:?} is not supported by the executor", operation).into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::NamedTempFile;
+    use tokio::fs::File;
+
+    use super::*;
+    use crate::{Tensor, TensorBuffersWrite, TensorBuffersWriter};
+
+    async fn write_model(path: &std::path::Path) {
+        let x = Tensor::new("x", &[1.0f32, 2.0, 3.0, 4.0], vec![2, 2]);
+        let w = Tensor::new("w", &[1.0f32, 0.0, 0.0, 1.0], vec![2, 2]);
+        let b = Tensor::new("b", &[0.5f32, -0.5], vec![2]);
+        let operations = vec![
+            TensorOperation::new(1, Operation::None, vec![], x.id()),
+            TensorOperation::new(2, Operation::None, vec![], w.id()),
+            TensorOperation::new(3, Operation::None, vec![], b.id()),
+            TensorOperation::new(4, Operation::MatMul, vec![1, 2], hash_key("xw")),
+            TensorOperation::new(5, Operation::Add, vec![4, 3], hash_key("logits")),
+            TensorOperation::new(6, Operation::Softmax, vec![5], hash_key("probs")),
+            TensorOperation::new(7, Operation::Conv2D, vec![1], hash_key("conv")),
+        ];
+        let mut file = File::create(path).await.unwrap();
+        let mut writer = TensorBuffersWriter::new(&mut file);
+        writer.write(vec![x, w, b], operations).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_executor_run() {
+        let tmp = NamedTempFile::new().unwrap();
+        write_model(tmp.path()).await;
+        let url = format!("file://{}", tmp.path().display());
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        let executor = Executor::new(&tensor_buffers);
+
+        let outputs = executor.run::<f32>(&["logits", "probs"]).await.unwrap();
+        let logits = &outputs["logits"];
+        assert_eq!(logits.shape(), &[2, 2]);
+        assert_eq!(logits.data(), &[1.5, 1.5, 3.5, 3.5]);
+        let probs = &outputs["probs"];
+        assert_eq!(probs.shape(), &[2, 2]);
+        assert_eq!(probs.data(), &[0.5, 0.5, 0.5, 0.5]);
+    }
+
+    #[tokio::test]
+    async fn test_executor_with_threads() {
+        let tmp = NamedTempFile::new().unwrap();
+        write_model(tmp.path()).await;
+        let url = format!("file://{}", tmp.path().display());
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        let executor = Executor::new(&tensor_buffers).with_threads(2).unwrap();
+
+        let outputs = executor.run::<f32>(&["xw", "probs"]).await.unwrap();
+        assert_eq!(outputs["xw"].data(), &[1.0, 2.0, 3.0, 4.0]);
+        assert_eq!(outputs["probs"].data(), &[0.5, 0.5, 0.5, 0.5]);
+    }
+
+    #[tokio::test]
+    async fn test_executor_with_cache() {
+        let tmp = NamedTempFile::new().unwrap();
+        write_model(tmp.path()).await;
+        let url = format!("file://{}", tmp.path().display());
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        let executor = Executor::new(&tensor_buffers).with_cache(1024);
+
+        let logits = executor.run::<f32>(&["logits"]).await.unwrap();
+        // x, w, b, xw and logits.
+        assert_eq!(executor.cached_bytes(), 4 * (4 + 4 + 2 + 4 + 4));
+        let outputs = executor.run::<f32>(&["probs", "logits"]).await.unwrap();
+        assert_eq!(outputs["logits"], logits["logits"]);
+        assert_eq!(outputs["probs"].data(), &[0.5, 0.5, 0.5, 0.5]);
+        assert_eq!(executor.cached_bytes(), 4 * (4 + 4 + 2 + 4 + 4 + 4));
+
+        executor.clear_cache();
+        assert_eq!(executor.cached_bytes(), 0);
+
+        // A budget smaller than every result caches nothing but still runs.
+        let executor = Executor::new(&tensor_buffers).with_cache(4);
+        let outputs = executor.run::<f32>(&["probs"]).await.unwrap();
+        assert_eq!(outputs["probs"].data(), &[0.5, 0.5, 0.5, 0.5]);
+        assert_eq!(executor.cached_bytes(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_executor_with_prefetch_depth() {
+        let tmp = NamedTempFile::new().unwrap();
+        write_model(tmp.path()).await;
+        let url = format!("file://{}", tmp.path().display());
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+
+        for prefetch_depth in [0, 1, 8] {
+            let executor = Executor::new(&tensor_buffers).with_prefetch_depth(prefetch_depth);
+            let outputs = executor.run::<f32>(&["probs", "w"]).await.unwrap();
+            assert_eq!(outputs["probs"].data(), &[0.5, 0.5, 0.5, 0.5]);
+            assert_eq!(outputs["w"].data(), &[1.0, 0.0, 0.0, 1.0]);
+        }
+    }
+
+    #[test]
+    fn test_prefetch_order() {
+        let operations = vec![
+            TensorOperation::new(1, Operation::None, vec![], 10),
+            TensorOperation::new(2, Operation::None, vec![], 20),
+            TensorOperation::new(3, Operation::None, vec![], 30),
+            TensorOperation::new(4, Operation::None, vec![], 40),
+            TensorOperation::new(5, Operation::Exp, vec![1], 50),
+            TensorOperation::new(6, Operation::Exp, vec![5], 60),
+            TensorOperation::new(7, Operation::Add, vec![6, 3], 70),
+            TensorOperation::new(8, Operation::Add, vec![2, 1], 80),
+        ];
+        let ordered = operations.iter().collect::<Vec<_>>();
+        let ids = prefetch_order(&ordered).iter().map(|op| op.id()).collect::<Vec<_>>();
+        // 3 is only needed by the third wave and 4 is requested directly.
+        assert_eq!(ids, vec![1, 2, 3, 4]);
+        let reordered = [&operations[2], &operations[0], &operations[3], &operations[1]]
+            .into_iter()
+            .chain(&operations[4..])
+            .collect::<Vec<_>>();
+        let ids = prefetch_order(&reordered).iter().map(|op| op.id()).collect::<Vec<_>>();
+        assert_eq!(ids, vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_waves() {
+        let operations = vec![
+            TensorOperation::new(1, Operation::None, vec![], 10),
+            TensorOperation::new(2, Operation::None, vec![], 20),
+            TensorOperation::new(3, Operation::Exp, vec![1], 30),
+            TensorOperation::new(4, Operation::Exp, vec![2], 40),
+            TensorOperation::new(5, Operation::Add, vec![3, 4], 50),
+            TensorOperation::new(6, Operation::Add, vec![1, 5], 60),
+        ];
+        let ordered = operations.iter().collect::<Vec<_>>();
+        let ids = waves(&ordered)
+            .iter()
+            .map(|wave| wave.iter().map(|op| op.id()).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec![vec![1, 2], vec![3, 4], vec![5], vec![6]]);
+    }
+
+    #[tokio::test]
+    async fn test_executor_errors() {
+        let tmp = NamedTempFile::new().unwrap();
+        write_model(tmp.path()).await;
+        let url = format!("file://{}", tmp.path().display());
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        let executor = Executor::new(&tensor_buffers);
+
+        assert!(executor.run::<f32>(&["conv"]).await.is_err());
+        assert!(executor.run::<f32>(&["missing"]).await.is_err());
+        assert!(executor.run::<f64>(&["logits"]).await.is_err());
+    }
+
+    #[test]
+    fn test_compute_operations() {
+        let a = TensorValue::new(vec![1.0f64, -2.0, 3.0, -4.0], vec![2, 2]).unwrap();
+        let b = TensorValue::new(vec![1.0f64, 1.0, 1.0, 1.0], vec![2, 2]).unwrap();
+
+        let relu = compute(Operation::ReLU, &[&a]).unwrap();
+        assert_eq!(relu.data(), &[1.0, 0.0, 3.0, 0.0]);
+        let transposed = compute(Operation::Transpose, &[&a]).unwrap();
+        assert_eq!(transposed.data(), &[1.0, 3.0, -2.0, -4.0]);
+        let concat = compute(Operation::Concat, &[&a, &b]).unwrap();
+        assert_eq!(concat.shape(), &[4, 2]);
+        let l1 = compute(Operation::L1Loss, &[&a, &b]).unwrap();
+        assert_eq!(l1.shape(), &[] as &[usize]);
+        assert_eq!(l1.data(), &[2.5]);
+        let argmax = compute(Operation::Argmax, &[&a]).unwrap();
+        assert_eq!(argmax.data(), &[2.0]);
+        assert!(compute(Operation::Add, &[&a]).is_err());
+        assert!(TensorValue::new(vec![1.0f32], vec![2]).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_tensor_value_serde() {
+        let value = TensorValue::new(vec![1.0f32, 2.0], vec![2]).unwrap();
+        let json = serde_json::to_string(&value).unwrap();
+        assert_eq!(json, r#"{"shape":[2],"data":[1.0,2.0]}"#);
+        assert_eq!(serde_json::from_str::<TensorValue<f32>>(&json).unwrap(), value);
+        assert!(serde_json::from_str::<TensorValue<f32>>(r#"{"shape":[3],"data":[1.0]}"#).is_err());
+    }
+}
@@ -0,0 +1,297 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    sync::{Arc, Mutex, RwLock},
+};
+
+use crate::{generated::tensor_buffers as generated, num_trait::DataType, Result};
+
+/// An experimental element type, e.g. an MXFP or posit format, that isn't yet a built-in
+/// `DataType`. Tensors of the type are written and read as the raw bytes of their elements, with
+/// the type's id and element size recorded in their metadata, so they round-trip through files
+/// whether or not the reader knows the type.
+///
+/// Create tensors of the type with `Tensor::from_extension`. Register an [`ExtensionDecoder`] to
+/// convert them to f32 with `Tensor::decode_extension`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ExtensionType {
+    id: &'static str,
+    element_size: usize,
+}
+
+impl ExtensionType {
+    /// Creates an extension type.
+    ///
+    /// # Arguments
+    /// * `id` - The type's identifier. Ids should be namespaced, e.g. `"ocp.mxfp8-e4m3"`, so
+    ///   types from different sources don't clash.
+    /// * `element_size` - The size of one element in bytes.
+    pub fn new(id: &str, element_size: usize) -> Self {
+        ExtensionType { id: intern(id), element_size }
+    }
+
+    /// Returns the type's identifier.
+    pub fn id(&self) -> &'static str {
+        self.id
+    }
+
+    /// Returns the size of one element in bytes.
+    pub fn element_size(&self) -> usize {
+        self.element_size
+    }
+}
+
+impl fmt::Debug for ExtensionType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.id, self.element_size)
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct SerializedExtensionType {
+    id: String,
+    element_size: usize,
+}
+
+// Derived deserialization would borrow the id from the input, so it is read as an owned string.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for ExtensionType {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        let extension = SerializedExtensionType::deserialize(deserializer)?;
+        Ok(ExtensionType::new(&extension.id, extension.element_size))
+    }
+}
+
+/// The ids of every extension type created in this process, so types stay `Copy` like the
+/// built-in data types. Files name few distinct types, so the set stays small.
+static IDS: Mutex<BTreeSet<&'static str>> = Mutex::new(BTreeSet::new());
+
+fn intern(id: &str) -> &'static str {
+    let mut ids = IDS.lock().unwrap();
+    match ids.get(id) {
+        Some(id) => id,
+        None => {
+            let id: &'static str = Box::leak(id.to_string().into_boxed_str());
+            ids.insert(id);
+            id
+        }
+    }
+}
+
+/// Converts the elements of an [`ExtensionType`] to f32, the hook that lets experimental formats
+/// be used before readers support them natively.
+///
+/// Register it with [`register_extension`] in every process that decodes tensors of the type.
+///
+/// ```
+/// use tensorbuffers::{register_extension, ExtensionDecoder, ExtensionType};
+///
+/// /// Bytes holding a value in sixteenths, as a stand-in for a real format.
+/// struct Sixteenths;
+///
+/// impl ExtensionDecoder for Sixteenths {
+///     fn extension_type(&self) -> ExtensionType {
+///         ExtensionType::new("example.sixteenths", 1)
+///     }
+///
+///     fn decode(&self, data: &[u8], out: &mut [f32]) -> tensorbuffers::Result<()> {
+///         out.iter_mut().zip(data).for_each(|(out, &byte)| *out = byte as f32 / 16.0);
+///         Ok(())
+///     }
+/// }
+///
+/// register_extension(Sixteenths);
+/// ```
+pub trait ExtensionDecoder: Send + Sync {
+    /// Returns the type the decoder converts.
+    fn extension_type(&self) -> ExtensionType;
+
+    /// Decodes `data`, the bytes of a tensor's elements, into `out`, which holds one value per
+    /// element.
+    fn decode(&self, data: &[u8], out: &mut [f32]) -> Result<()>;
+}
+
+/// The decoders registered in this process, by the id of their type.
+static DECODERS: RwLock<BTreeMap<&'static str, Arc<dyn ExtensionDecoder>>> =
+    RwLock::new(BTreeMap::new());
+
+/// Registers `decoder` for every tensor in the process, replacing any decoder registered for a
+/// type with the same id.
+pub fn register_extension(decoder: impl ExtensionDecoder + 'static) {
+    let decoder: Arc<dyn ExtensionDecoder> = Arc::new(decoder);
+    DECODERS.write().unwrap().insert(decoder.extension_type().id(), decoder);
+}
+
+/// Removes the decoder registered for the type with `id`, returning whether there was one.
+pub fn unregister_extension(id: &str) -> bool {
+    DECODERS.write().unwrap().remove(id).is_some()
+}
+
+/// Returns the types with registered decoders, in id order.
+pub fn registered_extensions() -> Vec<ExtensionType> {
+    DECODERS.read().unwrap().values().map(|decoder| decoder.extension_type()).collect()
+}
+
+/// Returns the decoder registered for `extension`.
+///
+/// # Returns
+/// An error if no decoder is registered for the type's id, or the registered one decodes elements
+/// of another size.
+pub(crate) fn registered_extension(extension: ExtensionType) -> Result<Arc<dyn ExtensionDecoder>> {
+    let decoder = match DECODERS.read().unwrap().get(extension.id()) {
+        Some(decoder) => decoder.clone(),
+        None => return Err(format!("Extension type {} isn't registered", extension.id()).into()),
+    };
+    let registered = decoder.extension_type();
+    if registered.element_size() != extension.element_size() {
+        return Err(format!(
+            "Extension type {} is registered with {}-byte elements, but the tensor has {}-byte \
+             elements",
+            extension.id(),
+            registered.element_size(),
+            extension.element_size()
+        )
+        .into());
+    }
+    Ok(decoder)
+}
+
+/// Returns the data type of a tensor from its metadata, including the extension type it names.
+pub(crate) fn data_type_of(metadata: &generated::TensorMetadata) -> Result<DataType> {
+    if metadata.data_type() != generated::DataType::Extension {
+        return Ok(DataType::try_from(metadata.data_type())?);
+    }
+    let Some(extension) = metadata.extension() else {
+        return Err(format!("Tensor {} has no extension type", metadata.name()).into());
+    };
+    if extension.element_size() == 0 {
+        return Err(format!(
+            "Tensor {} has extension type {} with empty elements",
+            metadata.name(),
+            extension.id()
+        )
+        .into());
+    }
+    Ok(DataType::Extension(ExtensionType::new(extension.id(), extension.element_size() as usize)))
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "async")]
+    use tempfile::NamedTempFile;
+    #[cfg(feature = "async")]
+    use tokio::fs::File;
+
+    use super::*;
+    #[cfg(feature = "async")]
+    use crate::{Tensor, TensorBuffers, TensorBuffersWrite, TensorBuffersWriter, WriterOptions};
+
+    /// Posit-like 16-bit elements, decoded here as halves for simplicity.
+    struct Halves;
+
+    impl ExtensionDecoder for Halves {
+        fn extension_type(&self) -> ExtensionType {
+            ExtensionType::new("test.halves", 2)
+        }
+
+        fn decode(&self, data: &[u8], out: &mut [f32]) -> Result<()> {
+            for (out, bytes) in out.iter_mut().zip(data.chunks_exact(2)) {
+                *out = half::f16::from_le_bytes([bytes[0], bytes[1]]).to_f32();
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_extension_registry() {
+        let halves = ExtensionType::new("test.halves", 2);
+        assert_eq!(halves, Halves.extension_type());
+        assert!(registered_extension(halves).is_err());
+        register_extension(Halves);
+        assert!(registered_extensions().contains(&halves));
+        assert!(registered_extension(halves).is_ok());
+        let error = registered_extension(ExtensionType::new("test.halves", 4)).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Extension type test.halves is registered with 2-byte elements, but the tensor has \
+             4-byte elements"
+        );
+
+        assert!(unregister_extension("test.halves"));
+        assert!(!unregister_extension("test.halves"));
+        assert_eq!(
+            registered_extension(halves).err().unwrap().to_string(),
+            "Extension type test.halves isn't registered"
+        );
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn test_extension_round_trip() {
+        let mxfp = ExtensionType::new("test.mxfp8", 1);
+        let data = (0..12).collect::<Vec<u8>>();
+        let tensor = Tensor::from_extension("w", mxfp, data.clone(), vec![3, 4]).unwrap();
+        assert_eq!(tensor.data_type(), DataType::Extension(mxfp));
+        let fives = ExtensionType::new("test.fives", 5);
+        let error = Tensor::from_extension("w", fives, data, vec![2]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Tensor w has 12 bytes, not a whole number of 5-byte test.fives elements"
+        );
+
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        let mut writer = TensorBuffersWriter::new(&mut file);
+        writer.write(vec![tensor.clone()], vec![]).await.unwrap();
+        let options = WriterOptions::new().with_format_version("1.3.0");
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        let error = TensorBuffersWriter::with_options(&mut bytes, options)
+            .write(vec![tensor], vec![])
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Format version 1.3.0 does not support extension data types, needed by tensor w"
+        );
+
+        // Readers that don't know the type still get the bytes back.
+        let url = format!("file://{}", tmp.path().display());
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        assert_eq!(tensor_buffers.features().await.unwrap(), ["extension_dtype"]);
+        let described = tensor_buffers.filter_tensors(|_| true).await.unwrap();
+        assert_eq!(described[0].data_type, DataType::Extension(mxfp));
+        let read = tensor_buffers.get_tensor_data_by_name::<u8>("w").await.unwrap();
+        assert_eq!((read.shape(), read.data_type()), (&[3, 4][..], DataType::Extension(mxfp)));
+        assert_eq!(read.data(), (0..12).collect::<Vec<u8>>());
+        assert!(tensor_buffers.get_tensor_data_by_name::<f32>("w").await.is_err());
+
+        // Registered decoders convert them.
+        assert_eq!(
+            read.decode_extension().unwrap_err().to_string(),
+            "Extension type test.mxfp8 isn't registered"
+        );
+        register_extension(Quarters);
+        let decoded = read.decode_extension().unwrap();
+        assert_eq!(decoded.shape(), [3, 4]);
+        assert_eq!(decoded.data()[..3], [0.0, 0.25, 0.5]);
+    }
+
+    #[cfg(feature = "async")]
+    struct Quarters;
+
+    #[cfg(feature = "async")]
+    impl ExtensionDecoder for Quarters {
+        fn extension_type(&self) -> ExtensionType {
+            ExtensionType::new("test.mxfp8", 1)
+        }
+
+        fn decode(&self, data: &[u8], out: &mut [f32]) -> Result<()> {
+            out.iter_mut().zip(data).for_each(|(out, &byte)| *out = byte as f32 / 4.0);
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,263 @@
+use std::{collections::HashMap, sync::Arc};
+
+use tokio::{
+    io::{AsyncSeek, AsyncWrite},
+    sync::Mutex,
+};
+
+use crate::{
+    codec::{self, StoredData},
+    extension_type::data_type_of,
+    generated::tensor_buffers::{Compression, TensorMetadata},
+    num_trait::DataType,
+    open_options::OpenOptions,
+    reader_pool::ReaderPool,
+    stats::ReadCounters,
+    tensor::stored_shape,
+    tensor_buffers_file::TensorBuffersFile,
+    utils::hash_key,
+    QuantizationParams, Result, TensorBuffers, TensorBuffersWriter, TensorId,
+};
+
+/// A tensor whose metadata is written to a file while its data stays in another file.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct ExternalTensor {
+    pub id: TensorId,
+    pub name: String,
+    pub data_type: DataType,
+    pub shape: Vec<usize>,
+    pub data_size: usize,
+    /// Where and how the data is stored in the other file.
+    pub stored: StoredData,
+    /// The URL of the other file, or its path relative to the file being written.
+    pub location: String,
+    /// How the tensor's values were quantized, if they were.
+    pub quantization: Option<QuantizationParams>,
+}
+
+impl ExternalTensor {
+    /// Describes the data of `metadata`, a tensor of the file at `location` quantized as
+    /// `quantization` says, as seen from a file that refers to it. Tensors that are themselves
+    /// stored elsewhere keep pointing at the file holding their data.
+    pub fn from_metadata(
+        metadata: &TensorMetadata,
+        location: &str,
+        quantization: Option<QuantizationParams>,
+    ) -> Result<Self> {
+        let (location, offset) = match metadata.external() {
+            Some(external) => {
+                (resolve_location(location, external.location()), external.data_offset())
+            }
+            None => (location.to_string(), metadata.data_offset() as u64),
+        };
+        Ok(ExternalTensor {
+            id: metadata.id(),
+            name: metadata.name().to_string(),
+            data_type: data_type_of(metadata)?,
+            shape: stored_shape(metadata)?,
+            data_size: metadata.data_size() as usize,
+            stored: StoredData {
+                offset,
+                size: codec::stored_size(metadata),
+                compression: metadata.compression(),
+                checksum_algorithm: metadata.checksum_algorithm(),
+                checksum: metadata.checksum(),
+                codec: codec::custom_codec_id(metadata)?,
+            },
+            location,
+            quantization,
+        })
+    }
+
+    /// Returns whether reading the data needs the dictionary of the file it is stored in.
+    pub fn needs_dictionary(&self) -> bool {
+        self.stored.compression == Compression::ZstdDictionary
+    }
+}
+
+impl<W> TensorBuffersWriter<W>
+where
+    W: AsyncWrite + AsyncSeek + Unpin,
+{
+    /// Adds tensors of another file to the next `write` as references to their data there, so
+    /// thin files can stitch together shared base weights and small deltas without copying the
+    /// weights. Readers load the tensors from the other file transparently, so it must stay at
+    /// `location`. Tensors that `source` itself refers to elsewhere keep referring to the file
+    /// holding their data.
+    ///
+    /// # Arguments
+    /// * `source` - The file holding the tensors.
+    /// * `location` - Where readers find `source`: a URL, or a path relative to the file being
+    ///   written.
+    /// * `tensor_names` - The tensors to refer to.
+    ///
+    /// # Returns
+    /// An error if `source` lacks a tensor or the tensors need different compression
+    /// dictionaries. The write fails if it has a tensor of the same name or a format version
+    /// before 1.4.0.
+    pub async fn add_external(
+        &mut self,
+        source: &TensorBuffers,
+        location: &str,
+        tensor_names: &[&str],
+    ) -> Result<()> {
+        let dictionary = source.get_metadata_root().await?.compression_dictionary();
+        let dictionary = dictionary.map(|dictionary| dictionary.bytes());
+        for name in tensor_names {
+            let metadata = source.stored_metadata(hash_key(name)).await?;
+            // Scales stored in `source` outside its metadata are copied into the file written.
+            let quantization = source.read_quantization(&metadata).await?;
+            self.add_external_tensor(
+                ExternalTensor::from_metadata(&metadata, location, quantization)?,
+                dictionary,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Resolves the location of an external file against the URL or path of the file referring to
+/// it. URLs are kept as they are, and relative paths are taken from the referring file's
+/// directory.
+pub(crate) fn resolve_location(base: &str, location: &str) -> String {
+    if location.contains("://") {
+        return location.to_string();
+    }
+    if let Some(path) = location.strip_prefix('/') {
+        // An absolute path, on the referring file's host if it has one.
+        return match base.split_once("://") {
+            Some(("file", _)) => format!("file:///{}", path),
+            Some((scheme, rest)) => {
+                format!("{}://{}/{}", scheme, rest.split('/').next().unwrap_or_default(), path)
+            }
+            None => location.to_string(),
+        };
+    }
+    match base.rfind('/') {
+        Some(end) => format!("{}/{}", &base[..end], location),
+        None => location.to_string(),
+    }
+}
+
+/// Handles to the files that a file's external tensors are stored in, opened on first use.
+#[derive(Default)]
+pub(crate) struct ExternalFiles {
+    files: Mutex<HashMap<String, Arc<ReaderPool>>>,
+}
+
+impl ExternalFiles {
+    /// Returns the readers of the file at `url`, opening it if needed. Its reads are counted in
+    /// `counters`.
+    pub async fn get(
+        &self,
+        url: &str,
+        options: &OpenOptions,
+        counters: &Arc<ReadCounters>,
+    ) -> Result<Arc<ReaderPool>> {
+        let mut files = self.files.lock().await;
+        if let Some(readers) = files.get(url) {
+            return Ok(readers.clone());
+        }
+        let file = TensorBuffersFile::open(url, options, counters.clone())
+            .await
+            .map_err(|error| format!("Can't open external file {}: {}", url, error))?;
+        let readers = Arc::new(ReaderPool::new(url, options.clone(), file));
+        files.insert(url.to_string(), readers.clone());
+        Ok(readers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use tempfile::TempDir;
+    use tokio::fs::File;
+
+    use super::*;
+    use crate::{Tensor, TensorBuffersWrite, WriterOptions};
+
+    async fn write_file(path: &Path, tensors: Vec<Tensor<'_, f32>>) -> String {
+        let mut file = File::create(path).await.unwrap();
+        TensorBuffersWriter::new(&mut file).write(tensors, vec![]).await.unwrap();
+        format!("file://{}", path.display())
+    }
+
+    #[tokio::test]
+    async fn test_add_external() {
+        let dir = TempDir::new().unwrap();
+        std::fs::create_dir(dir.path().join("parts")).unwrap();
+        let base = vec![
+            Tensor::new("embed", &[1.0f32, 2.0, 3.0], vec![3]),
+            Tensor::new("layer.0", &[4.0f32, 5.0], vec![2]),
+        ];
+        let base = write_file(&dir.path().join("base.tb"), base).await;
+        let base = TensorBuffers::open(&base).await.unwrap();
+        let adapter = vec![Tensor::new("adapter", &[6.0f32], vec![1])];
+        let adapter = write_file(&dir.path().join("parts/adapter.tb"), adapter).await;
+        let adapter = TensorBuffers::open(&adapter).await.unwrap();
+
+        // Stitch both files together with a delta of its own.
+        let path = dir.path().join("composed.tb");
+        let mut file = File::create(&path).await.unwrap();
+        let mut writer = TensorBuffersWriter::new(&mut file);
+        writer.add_external(&base, "base.tb", &["embed", "layer.0"]).await.unwrap();
+        writer.add_external(&adapter, "parts/adapter.tb", &["adapter"]).await.unwrap();
+        writer.add_alias("embed", "lm_head");
+        let delta = vec![Tensor::new("layer.1", &[7.0f32, 8.0], vec![2])];
+        writer.write(delta, vec![]).await.unwrap();
+        assert!(writer.add_external(&base, "base.tb", &["missing"]).await.is_err());
+
+        let composed = TensorBuffers::open(&format!("file://{}", path.display())).await.unwrap();
+        for (name, data) in [
+            ("embed", &[1.0f32, 2.0, 3.0][..]),
+            ("lm_head", &[1.0, 2.0, 3.0]),
+            ("layer.0", &[4.0, 5.0]),
+            ("layer.1", &[7.0, 8.0]),
+            ("adapter", &[6.0]),
+        ] {
+            let tensor = composed.get_tensor_data_by_name::<f32>(name).await.unwrap();
+            assert_eq!(tensor.data(), data, "{}", name);
+        }
+
+        // References need format 1.4.0 and names of their own.
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        let options = WriterOptions::new().with_format_version("1.3.0");
+        let mut writer = TensorBuffersWriter::with_options(&mut bytes, options);
+        writer.add_external(&base, "base.tb", &["embed"]).await.unwrap();
+        let error = writer.write(Vec::<Tensor<f32>>::new(), vec![]).await.unwrap_err();
+        assert_eq!(error.to_string(), "Format version 1.3.0 does not support external data");
+        let mut writer = TensorBuffersWriter::new(&mut bytes);
+        writer.add_external(&base, "base.tb", &["embed"]).await.unwrap();
+        let tensors = vec![Tensor::new("embed", &[0.0f32], vec![1])];
+        assert!(writer.write(tensors, vec![]).await.is_err());
+    }
+
+    #[test]
+    fn test_resolve_location() {
+        assert_eq!(
+            resolve_location("file:///models/ft/diff.tb", "../base.tb"),
+            "file:///models/ft/../base.tb"
+        );
+        assert_eq!(
+            resolve_location("file:///models/diff.tb", "/data/base.tb"),
+            "file:///data/base.tb"
+        );
+        assert_eq!(
+            resolve_location("https://host/models/diff.tb", "base.tb"),
+            "https://host/models/base.tb"
+        );
+        assert_eq!(
+            resolve_location("https://host/models/diff.tb", "/base.tb"),
+            "https://host/base.tb"
+        );
+        assert_eq!(
+            resolve_location("file:///models/diff.tb", "https://host/base.tb"),
+            "https://host/base.tb"
+        );
+        // Locations relative to a relative path stay relative.
+        assert_eq!(resolve_location("ft/diff.tb", "base.tb"), "ft/base.tb");
+        assert_eq!(resolve_location("diff.tb", "base.tb"), "base.tb");
+        assert_eq!(resolve_location("ft/diff.tb", "/base.tb"), "/base.tb");
+    }
+}
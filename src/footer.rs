@@ -0,0 +1,476 @@
+use std::error::Error;
+#[cfg(feature = "async")]
+use std::{borrow::Cow, io};
+
+#[cfg(any(feature = "async", feature = "mmap"))]
+use flatbuffers::FLATBUFFERS_MAX_BUFFER_SIZE;
+
+#[cfg(feature = "async")]
+use crate::bloom_filter::BloomFilter;
+use crate::constants::{
+    BLOOM_FOOTER_MAGIC_BYTES, CHECKSUM_FOOTER_MAGIC_BYTES, FIXED_FOOTER_MAGIC_BYTES, MAGIC_BYTES,
+};
+#[cfg(any(feature = "async", feature = "mmap"))]
+use crate::{codec, Compression};
+
+/// Size of the footer without a checksum: the metadata size (u32) followed by the trailing magic
+/// bytes.
+const FOOTER_SIZE: usize = 8;
+
+/// Size of the footer with a metadata checksum.
+const CHECKSUM_FOOTER_SIZE: usize = FOOTER_SIZE + 4;
+
+/// Size of the fixed part of a format version 1.3.0 footer with a bloom filter, which adds the
+/// number of hashes, the filter size and the filter's CRC32C. The filter comes right before it.
+const BLOOM_FOOTER_SIZE: usize = CHECKSUM_FOOTER_SIZE + 12;
+
+/// Size of the fixed footer written since format version 1.4.0.
+const FIXED_FOOTER_SIZE: usize = 64;
+
+/// The most bytes at the end of a file needed to parse any footer. A bloom filter comes before
+/// them.
+pub(crate) const MAX_FOOTER_SIZE: usize = FIXED_FOOTER_SIZE;
+
+/// Fixed footer flag for files with a name index before the metadata.
+const FLAG_NAME_INDEX: u32 = 1;
+
+/// Fixed footer flag for files with a bloom filter between the metadata and the footer.
+const FLAG_BLOOM_FILTER: u32 = 2;
+
+/// Fixed footer flag for files whose metadata is compressed with Zstandard.
+const FLAG_COMPRESSED_METADATA: u32 = 4;
+
+/// Most bytes at the start of compressed metadata needed to read its decompressed size: the
+/// largest Zstandard frame header.
+#[cfg(feature = "async")]
+pub(crate) const MAX_METADATA_HEADER_SIZE: usize = 18;
+
+/// Zstandard level the metadata is compressed with.
+#[cfg(feature = "async")]
+const METADATA_COMPRESSION_LEVEL: i32 = 3;
+
+/// The end of a TensorBuffers file, which locates and protects the metadata.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Footer {
+    pub metadata_size: usize,
+    /// CRC32C of the metadata, written since format version 1.2.0.
+    pub metadata_checksum: Option<u32>,
+    /// Where the bloom filter over tensor ids is, written since format version 1.3.0.
+    pub filter: Option<FilterLayout>,
+    /// Whether the metadata is compressed with Zstandard, which format version 1.4.0 can flag.
+    /// `metadata_size` and `metadata_checksum` are then those of the compressed metadata.
+    pub compressed_metadata: bool,
+    /// What the fixed footer of format version 1.4.0 says about the file.
+    pub summary: Option<FooterSummary>,
+    /// Size of the footer without the bloom filter.
+    fixed_size: usize,
+}
+
+/// The bloom filter fields of a footer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct FilterLayout {
+    /// Size of the filter in bytes.
+    pub size: usize,
+    pub hashes: u32,
+    /// CRC32C of the filter.
+    pub checksum: u32,
+}
+
+/// What the fixed footer of a file says about it, which takes a single small read of the end of
+/// the file instead of loading the metadata.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FooterSummary {
+    /// Format version the file was written with.
+    pub version: String,
+    /// Number of tensors in the metadata.
+    pub tensors: usize,
+    /// Number of operations in the metadata.
+    pub operations: usize,
+    /// Where the metadata starts.
+    pub metadata_offset: u64,
+    /// Size of the metadata in bytes, as stored.
+    pub metadata_size: usize,
+    /// Whether the metadata is compressed.
+    pub compressed_metadata: bool,
+    /// Whether a name index precedes the metadata.
+    pub name_index: bool,
+    /// Whether a bloom filter over tensor ids follows the metadata.
+    pub bloom_filter: bool,
+}
+
+/// Reads a little-endian u32 at `offset`.
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+/// Reads a little-endian u64 at `offset`.
+fn u64_at(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+}
+
+impl Footer {
+    /// Parses and validates the footer.
+    ///
+    /// # Arguments
+    /// * `end` - The last `MAX_FOOTER_SIZE` bytes of the file, or the whole file if it is smaller.
+    /// * `file_size` - The size of the file in bytes.
+    pub fn parse(end: &[u8], file_size: u64) -> Result<Footer, Box<dyn Error + Send + Sync>> {
+        // The smallest valid file is the leading magic bytes followed by the footer.
+        let min_size = (MAGIC_BYTES.len() + FOOTER_SIZE) as u64;
+        if file_size < min_size || end.len() < FOOTER_SIZE {
+            return Err(format!(
+                "File is too small ({} bytes) to be a TensorBuffers file; it may be truncated",
+                file_size
+            )
+            .into());
+        }
+
+        let magic = &end[end.len() - MAGIC_BYTES.len()..];
+        let fixed_size = if magic == MAGIC_BYTES {
+            FOOTER_SIZE
+        } else if magic == CHECKSUM_FOOTER_MAGIC_BYTES {
+            CHECKSUM_FOOTER_SIZE
+        } else if magic == BLOOM_FOOTER_MAGIC_BYTES {
+            BLOOM_FOOTER_SIZE
+        } else if magic == FIXED_FOOTER_MAGIC_BYTES {
+            FIXED_FOOTER_SIZE
+        } else {
+            return Err("Invalid trailing magic bytes; the file may be truncated".into());
+        };
+        if file_size < (MAGIC_BYTES.len() + fixed_size) as u64 || end.len() < fixed_size {
+            return Err("Footer is truncated".into());
+        }
+        let fields = &end[end.len() - fixed_size..];
+        let footer = match fixed_size {
+            FIXED_FOOTER_SIZE => Self::parse_fixed(fields, file_size)?,
+            // [filter (n bytes)][hashes (4 bytes)][filter_size (4 bytes)][filter_checksum
+            // (4 bytes)] start the bloom footer, and every footer but the first ends with
+            // [metadata_checksum (4 bytes)][metadata_size (4 bytes)][magic_bytes (4 bytes)].
+            _ => Footer {
+                metadata_size: u32_at(fields, fixed_size - 8) as usize,
+                metadata_checksum: (fixed_size > FOOTER_SIZE)
+                    .then(|| u32_at(fields, fixed_size - 12)),
+                filter: (fixed_size == BLOOM_FOOTER_SIZE).then(|| FilterLayout {
+                    hashes: u32_at(fields, 0),
+                    size: u32_at(fields, 4) as usize,
+                    checksum: u32_at(fields, 8),
+                }),
+                compressed_metadata: false,
+                summary: None,
+                fixed_size,
+            },
+        };
+
+        let min_size = (MAGIC_BYTES.len() + footer.size()) as u64;
+        if file_size < min_size {
+            return Err(format!(
+                "Invalid bloom filter size {} for a file of {} bytes",
+                footer.filter.map_or(0, |filter| filter.size),
+                file_size
+            )
+            .into());
+        }
+        let metadata_size = footer.metadata_size;
+        if metadata_size == 0 || metadata_size as u64 > file_size - min_size {
+            return Err(format!(
+                "Invalid metadata size {} for a file of {} bytes",
+                metadata_size, file_size
+            )
+            .into());
+        }
+        if let Some(summary) = &footer.summary {
+            if summary.metadata_offset != file_size - (footer.size() + metadata_size) as u64 {
+                return Err(format!(
+                    "Footer places the metadata at byte {}, but it ends at the footer",
+                    summary.metadata_offset
+                )
+                .into());
+            }
+        }
+        Ok(footer)
+    }
+
+    /// Parses the fields of a fixed footer.
+    ///
+    /// The 64 bytes are the format version as three u16s and a reserved u16, the numbers of
+    /// tensors and operations (u32s), the metadata offset (u64), size and CRC32C (u32s), flags
+    /// (u32), the bloom filter's number of hashes (u32), offset (u64), size and CRC32C (u32s), a
+    /// CRC32C of the preceding 56 bytes (u32) and the trailing magic bytes, all little-endian.
+    fn parse_fixed(fields: &[u8], file_size: u64) -> Result<Footer, Box<dyn Error + Send + Sync>> {
+        if crc32c::crc32c(&fields[..56]) != u32_at(fields, 56) {
+            return Err("Footer checksum mismatch".into());
+        }
+        let version = (0..3)
+            .map(|i| u16::from_le_bytes([fields[2 * i], fields[2 * i + 1]]).to_string())
+            .collect::<Vec<_>>()
+            .join(".");
+        let flags = u32_at(fields, 32);
+        let filter = (flags & FLAG_BLOOM_FILTER != 0).then(|| FilterLayout {
+            hashes: u32_at(fields, 36),
+            size: u32_at(fields, 48) as usize,
+            checksum: u32_at(fields, 52),
+        });
+        let summary = FooterSummary {
+            version,
+            tensors: u32_at(fields, 8) as usize,
+            operations: u32_at(fields, 12) as usize,
+            metadata_offset: u64_at(fields, 16),
+            metadata_size: u32_at(fields, 24) as usize,
+            compressed_metadata: flags & FLAG_COMPRESSED_METADATA != 0,
+            name_index: flags & FLAG_NAME_INDEX != 0,
+            bloom_filter: filter.is_some(),
+        };
+        if let Some(filter) = filter {
+            let filter_offset = u64_at(fields, 40);
+            if filter_offset.checked_add((filter.size + FIXED_FOOTER_SIZE) as u64)
+                != Some(file_size)
+            {
+                return Err(format!(
+                    "Footer places the bloom filter at byte {}, but it ends at the footer",
+                    filter_offset
+                )
+                .into());
+            }
+        }
+        Ok(Footer {
+            metadata_size: summary.metadata_size,
+            metadata_checksum: Some(u32_at(fields, 28)),
+            filter,
+            compressed_metadata: summary.compressed_metadata,
+            summary: Some(summary),
+            fixed_size: FIXED_FOOTER_SIZE,
+        })
+    }
+
+    /// Returns the size of the footer in bytes, including the bloom filter.
+    pub fn size(&self) -> usize {
+        self.fixed_size + self.filter.map_or(0, |filter| filter.size)
+    }
+
+    /// Returns where the bloom filter starts in a file of `file_size` bytes, if there is one.
+    #[cfg(feature = "async")]
+    pub fn filter_offset(&self, file_size: u64) -> Option<u64> {
+        self.filter.map(|filter| file_size - (self.fixed_size + filter.size) as u64)
+    }
+}
+
+/// What a footer describes besides the metadata itself.
+#[cfg(feature = "async")]
+pub(crate) struct FooterContents<'f> {
+    /// The format version, which decides the footer's layout.
+    pub format_version: &'f str,
+    /// Where the metadata starts.
+    pub metadata_offset: u64,
+    pub tensors: usize,
+    pub operations: usize,
+    /// Whether a name index precedes the metadata.
+    pub name_index: bool,
+    /// Whether to compress the metadata, which needs format version 1.4.0 or later.
+    pub compressed_metadata: bool,
+    /// The bloom filter to store, which needs format version 1.3.0 or later.
+    pub filter: Option<&'f BloomFilter>,
+}
+
+/// Returns the metadata as stored in the file, compressed if `contents` asks for it.
+#[cfg(feature = "async")]
+pub(crate) fn encode_metadata<'m>(
+    metadata: &'m [u8],
+    contents: &FooterContents<'_>,
+) -> io::Result<Cow<'m, [u8]>> {
+    if !contents.compressed_metadata {
+        return Ok(Cow::Borrowed(metadata));
+    }
+    Ok(Cow::Owned(zstd::bulk::compress(metadata, METADATA_COMPRESSION_LEVEL)?))
+}
+
+/// Returns the decompressed size of compressed metadata, which its Zstandard frame header records.
+///
+/// # Arguments
+/// * `stored` - The compressed metadata, or at least its first `MAX_METADATA_HEADER_SIZE` bytes.
+#[cfg(any(feature = "async", feature = "mmap"))]
+pub(crate) fn decompressed_metadata_size(
+    stored: &[u8],
+) -> Result<usize, Box<dyn Error + Send + Sync>> {
+    let size = zstd::zstd_safe::get_frame_content_size(stored)
+        .ok()
+        .flatten()
+        .ok_or("Compressed metadata doesn't record its size")?;
+    if size > FLATBUFFERS_MAX_BUFFER_SIZE as u64 {
+        return Err(format!("Invalid decompressed metadata size {}", size).into());
+    }
+    Ok(size as usize)
+}
+
+/// Decompresses metadata stored compressed.
+///
+/// # Arguments
+/// * `stored` - The compressed metadata.
+/// * `max_size` - The largest decompressed size to accept.
+#[cfg(any(feature = "async", feature = "mmap"))]
+pub(crate) fn decompress_metadata(
+    stored: &[u8],
+    max_size: usize,
+) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+    let size = decompressed_metadata_size(stored)?;
+    if size > max_size {
+        return Err(
+            format!("Metadata of {} bytes exceeds the limit of {} bytes", size, max_size).into()
+        );
+    }
+    let mut metadata = vec![0; size];
+    codec::decompress(stored, Compression::Zstd, None, None, &mut metadata)?;
+    Ok(metadata)
+}
+
+/// Encodes the footer that follows the metadata and ends the file, in the layout of the format
+/// version: no checksum before 1.2.0, a metadata checksum in 1.2.0 and 1.3.0, where it may also
+/// hold a bloom filter, and the fixed footer since 1.4.0.
+///
+/// # Arguments
+/// * `metadata` - The finished FlatBuffers metadata.
+/// * `contents` - What else the footer describes.
+#[cfg(feature = "async")]
+pub(crate) fn encode_footer(metadata: &[u8], contents: &FooterContents<'_>) -> io::Result<Vec<u8>> {
+    let too_large = |what: &str| io::Error::other(format!("{} is larger than 4 GiB", what));
+    let metadata_size = u32::try_from(metadata.len()).map_err(|_| too_large("Metadata"))?;
+    let filter_size = contents.filter.map_or(0, |filter| filter.bits().len());
+    let filter_size = u32::try_from(filter_size).map_err(|_| too_large("Bloom filter"))?;
+    let mut footer = Vec::with_capacity(MAX_FOOTER_SIZE + filter_size as usize);
+
+    // The bloom filter bits come first in every layout that has one.
+    if let Some(filter) = contents.filter {
+        footer.extend_from_slice(filter.bits());
+    }
+    let filter_checksum = contents.filter.map_or(0, |filter| crc32c::crc32c(filter.bits()));
+    let hashes = contents.filter.map_or(0, |filter| filter.hashes());
+
+    match contents.format_version {
+        "1.0.0" | "1.1.0" => {
+            footer.extend_from_slice(&metadata_size.to_le_bytes());
+            footer.extend_from_slice(MAGIC_BYTES);
+        }
+        "1.2.0" | "1.3.0" => {
+            if contents.filter.is_some() {
+                footer.extend_from_slice(&hashes.to_le_bytes());
+                footer.extend_from_slice(&filter_size.to_le_bytes());
+                footer.extend_from_slice(&filter_checksum.to_le_bytes());
+            }
+            footer.extend_from_slice(&crc32c::crc32c(metadata).to_le_bytes());
+            footer.extend_from_slice(&metadata_size.to_le_bytes());
+            footer.extend_from_slice(match contents.filter {
+                Some(_) => BLOOM_FOOTER_MAGIC_BYTES,
+                None => CHECKSUM_FOOTER_MAGIC_BYTES,
+            });
+        }
+        version => {
+            let start = footer.len();
+            for part in version.split('.') {
+                let part = part.parse::<u16>().map_err(io::Error::other)?;
+                footer.extend_from_slice(&part.to_le_bytes());
+            }
+            footer.extend_from_slice(&0u16.to_le_bytes());
+            let tensors = u32::try_from(contents.tensors).map_err(io::Error::other)?;
+            let operations = u32::try_from(contents.operations).map_err(io::Error::other)?;
+            footer.extend_from_slice(&tensors.to_le_bytes());
+            footer.extend_from_slice(&operations.to_le_bytes());
+            footer.extend_from_slice(&contents.metadata_offset.to_le_bytes());
+            footer.extend_from_slice(&metadata_size.to_le_bytes());
+            footer.extend_from_slice(&crc32c::crc32c(metadata).to_le_bytes());
+            let mut flags = 0;
+            if contents.name_index {
+                flags |= FLAG_NAME_INDEX;
+            }
+            if contents.filter.is_some() {
+                flags |= FLAG_BLOOM_FILTER;
+            }
+            if contents.compressed_metadata {
+                flags |= FLAG_COMPRESSED_METADATA;
+            }
+            footer.extend_from_slice(&flags.to_le_bytes());
+            footer.extend_from_slice(&hashes.to_le_bytes());
+            let filter_offset = contents.metadata_offset + metadata_size as u64;
+            footer.extend_from_slice(&filter_offset.to_le_bytes());
+            footer.extend_from_slice(&filter_size.to_le_bytes());
+            footer.extend_from_slice(&filter_checksum.to_le_bytes());
+            let checksum = crc32c::crc32c(&footer[start..]);
+            footer.extend_from_slice(&checksum.to_le_bytes());
+            footer.extend_from_slice(FIXED_FOOTER_MAGIC_BYTES);
+            if footer.len() - start != FIXED_FOOTER_SIZE {
+                return Err(io::Error::other(format!("Invalid format version {}", version)));
+            }
+        }
+    }
+    Ok(footer)
+}
+
+#[cfg(all(test, feature = "async"))]
+mod tests {
+    use super::*;
+
+    fn contents<'f>(
+        format_version: &'f str,
+        filter: Option<&'f BloomFilter>,
+    ) -> FooterContents<'f> {
+        FooterContents {
+            format_version,
+            metadata_offset: 4,
+            tensors: 3,
+            operations: 1,
+            name_index: true,
+            compressed_metadata: false,
+            filter,
+        }
+    }
+
+    #[test]
+    fn test_footer_layouts() {
+        let metadata = [7; 20];
+        let filter = BloomFilter::new(&[1, 2, 3]);
+        for (version, filter, size) in [
+            ("1.1.0", None, FOOTER_SIZE),
+            ("1.2.0", None, CHECKSUM_FOOTER_SIZE),
+            ("1.3.0", Some(&filter), BLOOM_FOOTER_SIZE + 4),
+            ("1.4.0", None, FIXED_FOOTER_SIZE),
+            ("1.4.0", Some(&filter), FIXED_FOOTER_SIZE + 4),
+        ] {
+            let mut file = b"TBS1".to_vec();
+            file.extend_from_slice(&metadata);
+            file.extend_from_slice(&encode_footer(&metadata, &contents(version, filter)).unwrap());
+            let file_size = file.len() as u64;
+            let end = &file[file.len().saturating_sub(MAX_FOOTER_SIZE)..];
+            let footer = Footer::parse(end, file_size).unwrap();
+            assert_eq!((footer.size(), footer.metadata_size), (size, 20), "{}", version);
+            assert_eq!(footer.filter.is_some(), filter.is_some());
+            assert_eq!(footer.filter_offset(file_size), filter.map(|_| 24));
+            assert_eq!(footer.metadata_checksum.is_some(), version != "1.1.0");
+        }
+    }
+
+    #[test]
+    fn test_footer_summary() {
+        let mut file = b"TBS1".to_vec();
+        file.extend_from_slice(&[7; 20]);
+        file.extend_from_slice(&encode_footer(&[7; 20], &contents("1.4.0", None)).unwrap());
+        let footer = Footer::parse(&file[file.len() - 64..], file.len() as u64).unwrap();
+        assert_eq!(footer.summary.unwrap(), FooterSummary {
+            version: "1.4.0".to_string(),
+            tensors: 3,
+            operations: 1,
+            metadata_offset: 4,
+            metadata_size: 20,
+            compressed_metadata: false,
+            name_index: true,
+            bloom_filter: false,
+        });
+
+        // The footer checks itself and where it places the metadata.
+        let mut corrupted = file.clone();
+        corrupted[file.len() - 60] ^= 1;
+        let error = Footer::parse(&corrupted[file.len() - 64..], file.len() as u64).unwrap_err();
+        assert_eq!(error.to_string(), "Footer checksum mismatch");
+        let mut shifted = vec![0];
+        shifted.extend_from_slice(&file);
+        let error = Footer::parse(&shifted[shifted.len() - 64..], shifted.len() as u64);
+        assert!(error.unwrap_err().to_string().starts_with("Footer places the metadata"));
+    }
+}
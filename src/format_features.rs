@@ -0,0 +1,201 @@
+#[cfg(feature = "async")]
+use std::collections::BTreeSet;
+
+use crate::{generated::tensor_buffers::TensorBuffersMetadata, Result};
+#[cfg(feature = "async")]
+use crate::{
+    generated::tensor_buffers::{Compression, DataType as GeneratedDataType, TensorMetadata},
+    tensor::is_wide_shape,
+    DataType,
+};
+
+/// Tensor data is compressed with zstd or LZ4.
+pub const FEATURE_COMPRESSION: &str = "compression";
+
+/// Some tensor data is compressed with the Zstandard dictionary stored in the metadata.
+pub const FEATURE_ZSTD_DICTIONARY: &str = "zstd_dictionary";
+
+/// Some tensor data is stored in other files, located by the tensors' `external` tables.
+pub const FEATURE_EXTERNAL_DATA: &str = "external_data";
+
+/// Some tensor data is encoded with custom codecs, named by the tensors' `codec`.
+pub const FEATURE_CUSTOM_CODEC: &str = "custom_codec";
+
+/// Some tensors are of experimental types, described by their `extension` tables.
+pub const FEATURE_EXTENSION_DTYPE: &str = "extension_dtype";
+
+/// Some tensor shapes are stored in `shape64`.
+pub const FEATURE_SHAPE64: &str = "shape64";
+
+/// The tensors are stored as the parallel vectors of `tensor_columns` instead of in `tensors`.
+pub const FEATURE_COLUMNAR_METADATA: &str = "columnar_metadata";
+
+/// Some tensors hold quantized values, described by their `quantization` tables.
+pub const FEATURE_QUANTIZATION: &str = "quantization";
+
+/// Some tensors are of block-quantized types, such as `Q8_0` and `Q4_K`.
+pub const FEATURE_BLOCK_QUANTIZATION: &str = "block_quantization";
+
+/// Format features this reader supports. Files that list any other feature, such as the reserved
+/// `encryption`, `sparse` or `offsets64`, are refused.
+pub const SUPPORTED_FEATURES: &[&str] = &[
+    FEATURE_BLOCK_QUANTIZATION,
+    FEATURE_COLUMNAR_METADATA,
+    FEATURE_COMPRESSION,
+    FEATURE_CUSTOM_CODEC,
+    FEATURE_EXTENSION_DTYPE,
+    FEATURE_EXTERNAL_DATA,
+    FEATURE_QUANTIZATION,
+    FEATURE_SHAPE64,
+    FEATURE_ZSTD_DICTIONARY,
+];
+
+/// The format features a file uses, collected as its tensors are written and recorded in the
+/// metadata so readers that lack one refuse the file by name instead of misreading it.
+#[cfg(feature = "async")]
+#[derive(Clone, Debug, Default)]
+pub(crate) struct FormatFeatures(BTreeSet<&'static str>);
+
+#[cfg(feature = "async")]
+impl FormatFeatures {
+    /// Records the features needed to read a tensor stored with `compression` and `shape`.
+    pub fn add_tensor(&mut self, compression: Compression, shape: &[usize]) {
+        self.add(compression, is_wide_shape(shape));
+    }
+
+    /// Records the features needed to read a tensor copied from another file's metadata.
+    pub fn add_stored(&mut self, tensor: &TensorMetadata) {
+        self.add(tensor.compression(), tensor.shape64().is_some());
+        if tensor.external().is_some() {
+            self.0.insert(FEATURE_EXTERNAL_DATA);
+        }
+        if tensor.extension().is_some() {
+            self.0.insert(FEATURE_EXTENSION_DTYPE);
+        }
+        if matches!(tensor.data_type(), GeneratedDataType::Q8_0 | GeneratedDataType::Q4_K) {
+            self.0.insert(FEATURE_BLOCK_QUANTIZATION);
+        }
+        if tensor.quantization().is_some() {
+            self.add_quantization();
+        }
+    }
+
+    /// Records that the tensors are stored as columns.
+    pub fn add_columnar_metadata(&mut self) {
+        self.0.insert(FEATURE_COLUMNAR_METADATA);
+    }
+
+    /// Records the features needed to read a tensor of `data_type`.
+    pub fn add_data_type(&mut self, data_type: DataType) {
+        if let DataType::Extension(_) = data_type {
+            self.0.insert(FEATURE_EXTENSION_DTYPE);
+        }
+        if data_type.is_block_quantized() {
+            self.0.insert(FEATURE_BLOCK_QUANTIZATION);
+        }
+    }
+
+    /// Records that a tensor holds quantized values.
+    pub fn add_quantization(&mut self) {
+        self.0.insert(FEATURE_QUANTIZATION);
+    }
+
+    /// Records the features needed to read a tensor stored with `compression` and `shape` in
+    /// another file.
+    pub fn add_external(&mut self, compression: Compression, shape: &[usize]) {
+        self.add_tensor(compression, shape);
+        self.0.insert(FEATURE_EXTERNAL_DATA);
+    }
+
+    fn add(&mut self, compression: Compression, wide_shape: bool) {
+        if compression != Compression::None {
+            self.0.insert(FEATURE_COMPRESSION);
+        }
+        if compression == Compression::ZstdDictionary {
+            self.0.insert(FEATURE_ZSTD_DICTIONARY);
+        }
+        if compression == Compression::Custom {
+            self.0.insert(FEATURE_CUSTOM_CODEC);
+        }
+        if wide_shape {
+            self.0.insert(FEATURE_SHAPE64);
+        }
+    }
+
+    /// Returns the features in name order.
+    pub fn names(&self) -> Vec<&'static str> {
+        self.0.iter().copied().collect()
+    }
+}
+
+/// Checks that this reader supports every format feature the file lists.
+pub(crate) fn check_features(metadata: &TensorBuffersMetadata) -> Result<()> {
+    let unsupported = metadata
+        .features()
+        .into_iter()
+        .flatten()
+        .filter(|feature| !SUPPORTED_FEATURES.contains(feature))
+        .collect::<Vec<_>>();
+    if !unsupported.is_empty() {
+        return Err(format!(
+            "File needs format features this reader doesn't support: {}",
+            unsupported.join(", ")
+        )
+        .into());
+    }
+    Ok(())
+}
+
+#[cfg(all(test, feature = "async"))]
+mod tests {
+    use flatbuffers::FlatBufferBuilder;
+
+    use super::*;
+    use crate::generated::tensor_buffers::TensorBuffersMetadataArgs;
+
+    fn metadata_with(builder: &mut FlatBufferBuilder<'_>, features: &[&str]) {
+        let version = builder.create_string("1.4.0");
+        let features = features.iter().map(|name| builder.create_string(name)).collect::<Vec<_>>();
+        let features = builder.create_vector(&features);
+        let metadata = TensorBuffersMetadata::create(builder, &TensorBuffersMetadataArgs {
+            version: Some(version),
+            features: Some(features),
+            ..Default::default()
+        });
+        builder.finish(metadata, None);
+    }
+
+    #[test]
+    fn test_format_features() {
+        let mut features = FormatFeatures::default();
+        features.add_tensor(Compression::None, &[2, 2]);
+        assert!(features.names().is_empty());
+        features.add_tensor(Compression::Lz4, &[2]);
+        features.add_tensor(Compression::None, &[0, 1 << 33]);
+        features.add_tensor(Compression::Zstd, &[2]);
+        assert_eq!(features.names(), [FEATURE_COMPRESSION, FEATURE_SHAPE64]);
+        features.add_tensor(Compression::ZstdDictionary, &[2]);
+        features.add_external(Compression::None, &[2]);
+        features.add_tensor(Compression::Custom, &[2]);
+        assert_eq!(features.names(), [
+            FEATURE_COMPRESSION,
+            FEATURE_CUSTOM_CODEC,
+            FEATURE_EXTERNAL_DATA,
+            FEATURE_SHAPE64,
+            FEATURE_ZSTD_DICTIONARY
+        ]);
+
+        let mut builder = FlatBufferBuilder::new();
+        metadata_with(&mut builder, &features.names());
+        let metadata = flatbuffers::root::<TensorBuffersMetadata>(builder.finished_data());
+        assert!(check_features(&metadata.unwrap()).is_ok());
+
+        let mut builder = FlatBufferBuilder::new();
+        metadata_with(&mut builder, &["compression", "encryption", "sparse"]);
+        let metadata = flatbuffers::root::<TensorBuffersMetadata>(builder.finished_data());
+        assert_eq!(
+            check_features(&metadata.unwrap()).unwrap_err().to_string(),
+            "File needs format features this reader doesn't support: encryption, sparse"
+        );
+    }
+}
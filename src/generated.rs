@@ -3,11 +3,7 @@
 
 // @generated
 
-use core::mem;
-use core::cmp::Ordering;
 
-extern crate flatbuffers;
-use self::flatbuffers::{EndianScalar, Follow};
 
 #[allow(unused_imports, dead_code)]
 pub mod tensor_buffers {
@@ -21,10 +17,10 @@ pub mod tensor_buffers {
 #[deprecated(since = "2.0.0", note = "Use associated constants instead. This will no longer be generated in 2021.")]
 pub const ENUM_MIN_DATA_TYPE: i8 = 0;
 #[deprecated(since = "2.0.0", note = "Use associated constants instead. This will no longer be generated in 2021.")]
-pub const ENUM_MAX_DATA_TYPE: i8 = 10;
+pub const ENUM_MAX_DATA_TYPE: i8 = 14;
 #[deprecated(since = "2.0.0", note = "Use associated constants instead. This will no longer be generated in 2021.")]
 #[allow(non_camel_case_types)]
-pub const ENUM_VALUES_DATA_TYPE: [DataType; 11] = [
+pub const ENUM_VALUES_DATA_TYPE: [DataType; 15] = [
   DataType::None,
   DataType::Float32,
   DataType::Float64,
@@ -36,6 +32,10 @@ pub const ENUM_VALUES_DATA_TYPE: [DataType; 11] = [
   DataType::UInt16,
   DataType::UInt32,
   DataType::UInt64,
+  DataType::Float16,
+  DataType::Extension,
+  DataType::Q8_0,
+  DataType::Q4_K,
 ];
 
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
@@ -54,9 +54,13 @@ impl DataType {
   pub const UInt16: Self = Self(8);
   pub const UInt32: Self = Self(9);
   pub const UInt64: Self = Self(10);
+  pub const Float16: Self = Self(11);
+  pub const Extension: Self = Self(12);
+  pub const Q8_0: Self = Self(13);
+  pub const Q4_K: Self = Self(14);
 
   pub const ENUM_MIN: i8 = 0;
-  pub const ENUM_MAX: i8 = 10;
+  pub const ENUM_MAX: i8 = 14;
   pub const ENUM_VALUES: &'static [Self] = &[
     Self::None,
     Self::Float32,
@@ -69,6 +73,10 @@ impl DataType {
     Self::UInt16,
     Self::UInt32,
     Self::UInt64,
+    Self::Float16,
+    Self::Extension,
+    Self::Q8_0,
+    Self::Q4_K,
   ];
   /// Returns the variant's name or "" if unknown.
   pub fn variant_name(self) -> Option<&'static str> {
@@ -84,6 +92,10 @@ impl DataType {
       Self::UInt16 => Some("UInt16"),
       Self::UInt32 => Some("UInt32"),
       Self::UInt64 => Some("UInt64"),
+      Self::Float16 => Some("Float16"),
+      Self::Extension => Some("Extension"),
+      Self::Q8_0 => Some("Q8_0"),
+      Self::Q4_K => Some("Q4_K"),
       _ => None,
     }
   }
@@ -140,6 +152,281 @@ impl<'a> flatbuffers::Verifiable for DataType {
 
 impl flatbuffers::SimpleToVerifyInSlice for DataType {}
 #[deprecated(since = "2.0.0", note = "Use associated constants instead. This will no longer be generated in 2021.")]
+pub const ENUM_MIN_COMPRESSION: i8 = 0;
+#[deprecated(since = "2.0.0", note = "Use associated constants instead. This will no longer be generated in 2021.")]
+pub const ENUM_MAX_COMPRESSION: i8 = 4;
+#[deprecated(since = "2.0.0", note = "Use associated constants instead. This will no longer be generated in 2021.")]
+#[allow(non_camel_case_types)]
+pub const ENUM_VALUES_COMPRESSION: [Compression; 5] = [
+  Compression::None,
+  Compression::Zstd,
+  Compression::Lz4,
+  Compression::ZstdDictionary,
+  Compression::Custom,
+];
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[repr(transparent)]
+pub struct Compression(pub i8);
+#[allow(non_upper_case_globals)]
+impl Compression {
+  pub const None: Self = Self(0);
+  pub const Zstd: Self = Self(1);
+  pub const Lz4: Self = Self(2);
+  pub const ZstdDictionary: Self = Self(3);
+  pub const Custom: Self = Self(4);
+
+  pub const ENUM_MIN: i8 = 0;
+  pub const ENUM_MAX: i8 = 4;
+  pub const ENUM_VALUES: &'static [Self] = &[
+    Self::None,
+    Self::Zstd,
+    Self::Lz4,
+    Self::ZstdDictionary,
+    Self::Custom,
+  ];
+  /// Returns the variant's name or "" if unknown.
+  pub fn variant_name(self) -> Option<&'static str> {
+    match self {
+      Self::None => Some("None"),
+      Self::Zstd => Some("Zstd"),
+      Self::Lz4 => Some("Lz4"),
+      Self::ZstdDictionary => Some("ZstdDictionary"),
+      Self::Custom => Some("Custom"),
+      _ => None,
+    }
+  }
+}
+impl core::fmt::Debug for Compression {
+  fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+    if let Some(name) = self.variant_name() {
+      f.write_str(name)
+    } else {
+      f.write_fmt(format_args!("<UNKNOWN {:?}>", self.0))
+    }
+  }
+}
+impl<'a> flatbuffers::Follow<'a> for Compression {
+  type Inner = Self;
+  #[inline]
+  unsafe fn follow(buf: &'a [u8], loc: usize) -> Self::Inner {
+    let b = flatbuffers::read_scalar_at::<i8>(buf, loc);
+    Self(b)
+  }
+}
+
+impl flatbuffers::Push for Compression {
+    type Output = Compression;
+    #[inline]
+    unsafe fn push(&self, dst: &mut [u8], _written_len: usize) {
+        flatbuffers::emplace_scalar::<i8>(dst, self.0);
+    }
+}
+
+impl flatbuffers::EndianScalar for Compression {
+  type Scalar = i8;
+  #[inline]
+  fn to_little_endian(self) -> i8 {
+    self.0.to_le()
+  }
+  #[inline]
+  #[allow(clippy::wrong_self_convention)]
+  fn from_little_endian(v: i8) -> Self {
+    let b = i8::from_le(v);
+    Self(b)
+  }
+}
+
+impl<'a> flatbuffers::Verifiable for Compression {
+  #[inline]
+  fn run_verifier(
+    v: &mut flatbuffers::Verifier, pos: usize
+  ) -> Result<(), flatbuffers::InvalidFlatbuffer> {
+    use self::flatbuffers::Verifiable;
+    i8::run_verifier(v, pos)
+  }
+}
+
+impl flatbuffers::SimpleToVerifyInSlice for Compression {}
+#[deprecated(since = "2.0.0", note = "Use associated constants instead. This will no longer be generated in 2021.")]
+pub const ENUM_MIN_CHECKSUM_ALGORITHM: i8 = 0;
+#[deprecated(since = "2.0.0", note = "Use associated constants instead. This will no longer be generated in 2021.")]
+pub const ENUM_MAX_CHECKSUM_ALGORITHM: i8 = 2;
+#[deprecated(since = "2.0.0", note = "Use associated constants instead. This will no longer be generated in 2021.")]
+#[allow(non_camel_case_types)]
+pub const ENUM_VALUES_CHECKSUM_ALGORITHM: [ChecksumAlgorithm; 3] = [
+  ChecksumAlgorithm::None,
+  ChecksumAlgorithm::Crc32c,
+  ChecksumAlgorithm::XxHash64,
+];
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[repr(transparent)]
+pub struct ChecksumAlgorithm(pub i8);
+#[allow(non_upper_case_globals)]
+impl ChecksumAlgorithm {
+  pub const None: Self = Self(0);
+  pub const Crc32c: Self = Self(1);
+  pub const XxHash64: Self = Self(2);
+
+  pub const ENUM_MIN: i8 = 0;
+  pub const ENUM_MAX: i8 = 2;
+  pub const ENUM_VALUES: &'static [Self] = &[
+    Self::None,
+    Self::Crc32c,
+    Self::XxHash64,
+  ];
+  /// Returns the variant's name or "" if unknown.
+  pub fn variant_name(self) -> Option<&'static str> {
+    match self {
+      Self::None => Some("None"),
+      Self::Crc32c => Some("Crc32c"),
+      Self::XxHash64 => Some("XxHash64"),
+      _ => None,
+    }
+  }
+}
+impl core::fmt::Debug for ChecksumAlgorithm {
+  fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+    if let Some(name) = self.variant_name() {
+      f.write_str(name)
+    } else {
+      f.write_fmt(format_args!("<UNKNOWN {:?}>", self.0))
+    }
+  }
+}
+impl<'a> flatbuffers::Follow<'a> for ChecksumAlgorithm {
+  type Inner = Self;
+  #[inline]
+  unsafe fn follow(buf: &'a [u8], loc: usize) -> Self::Inner {
+    let b = flatbuffers::read_scalar_at::<i8>(buf, loc);
+    Self(b)
+  }
+}
+
+impl flatbuffers::Push for ChecksumAlgorithm {
+    type Output = ChecksumAlgorithm;
+    #[inline]
+    unsafe fn push(&self, dst: &mut [u8], _written_len: usize) {
+        flatbuffers::emplace_scalar::<i8>(dst, self.0);
+    }
+}
+
+impl flatbuffers::EndianScalar for ChecksumAlgorithm {
+  type Scalar = i8;
+  #[inline]
+  fn to_little_endian(self) -> i8 {
+    self.0.to_le()
+  }
+  #[inline]
+  #[allow(clippy::wrong_self_convention)]
+  fn from_little_endian(v: i8) -> Self {
+    let b = i8::from_le(v);
+    Self(b)
+  }
+}
+
+impl<'a> flatbuffers::Verifiable for ChecksumAlgorithm {
+  #[inline]
+  fn run_verifier(
+    v: &mut flatbuffers::Verifier, pos: usize
+  ) -> Result<(), flatbuffers::InvalidFlatbuffer> {
+    use self::flatbuffers::Verifiable;
+    i8::run_verifier(v, pos)
+  }
+}
+
+impl flatbuffers::SimpleToVerifyInSlice for ChecksumAlgorithm {}
+#[deprecated(since = "2.0.0", note = "Use associated constants instead. This will no longer be generated in 2021.")]
+pub const ENUM_MIN_CONSTRAINT_KIND: i8 = 0;
+#[deprecated(since = "2.0.0", note = "Use associated constants instead. This will no longer be generated in 2021.")]
+pub const ENUM_MAX_CONSTRAINT_KIND: i8 = 2;
+#[deprecated(since = "2.0.0", note = "Use associated constants instead. This will no longer be generated in 2021.")]
+#[allow(non_camel_case_types)]
+pub const ENUM_VALUES_CONSTRAINT_KIND: [ConstraintKind; 3] = [
+  ConstraintKind::Equal,
+  ConstraintKind::SameDim,
+  ConstraintKind::Tied,
+];
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[repr(transparent)]
+pub struct ConstraintKind(pub i8);
+#[allow(non_upper_case_globals)]
+impl ConstraintKind {
+  pub const Equal: Self = Self(0);
+  pub const SameDim: Self = Self(1);
+  pub const Tied: Self = Self(2);
+
+  pub const ENUM_MIN: i8 = 0;
+  pub const ENUM_MAX: i8 = 2;
+  pub const ENUM_VALUES: &'static [Self] = &[
+    Self::Equal,
+    Self::SameDim,
+    Self::Tied,
+  ];
+  /// Returns the variant's name or "" if unknown.
+  pub fn variant_name(self) -> Option<&'static str> {
+    match self {
+      Self::Equal => Some("Equal"),
+      Self::SameDim => Some("SameDim"),
+      Self::Tied => Some("Tied"),
+      _ => None,
+    }
+  }
+}
+impl core::fmt::Debug for ConstraintKind {
+  fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+    if let Some(name) = self.variant_name() {
+      f.write_str(name)
+    } else {
+      f.write_fmt(format_args!("<UNKNOWN {:?}>", self.0))
+    }
+  }
+}
+impl<'a> flatbuffers::Follow<'a> for ConstraintKind {
+  type Inner = Self;
+  #[inline]
+  unsafe fn follow(buf: &'a [u8], loc: usize) -> Self::Inner {
+    let b = flatbuffers::read_scalar_at::<i8>(buf, loc);
+    Self(b)
+  }
+}
+
+impl flatbuffers::Push for ConstraintKind {
+    type Output = ConstraintKind;
+    #[inline]
+    unsafe fn push(&self, dst: &mut [u8], _written_len: usize) {
+        flatbuffers::emplace_scalar::<i8>(dst, self.0);
+    }
+}
+
+impl flatbuffers::EndianScalar for ConstraintKind {
+  type Scalar = i8;
+  #[inline]
+  fn to_little_endian(self) -> i8 {
+    self.0.to_le()
+  }
+  #[inline]
+  #[allow(clippy::wrong_self_convention)]
+  fn from_little_endian(v: i8) -> Self {
+    let b = i8::from_le(v);
+    Self(b)
+  }
+}
+
+impl<'a> flatbuffers::Verifiable for ConstraintKind {
+  #[inline]
+  fn run_verifier(
+    v: &mut flatbuffers::Verifier, pos: usize
+  ) -> Result<(), flatbuffers::InvalidFlatbuffer> {
+    use self::flatbuffers::Verifiable;
+    i8::run_verifier(v, pos)
+  }
+}
+
+impl flatbuffers::SimpleToVerifyInSlice for ConstraintKind {}
+#[deprecated(since = "2.0.0", note = "Use associated constants instead. This will no longer be generated in 2021.")]
 pub const ENUM_MIN_OPERATION: i8 = 0;
 #[deprecated(since = "2.0.0", note = "Use associated constants instead. This will no longer be generated in 2021.")]
 pub const ENUM_MAX_OPERATION: i8 = 36;
@@ -364,79 +651,826 @@ impl<'a> flatbuffers::Verifiable for Operation {
 }
 
 impl flatbuffers::SimpleToVerifyInSlice for Operation {}
-pub enum TensorMetadataOffset {}
+pub enum ExternalDataOffset {}
 #[derive(Copy, Clone, PartialEq)]
 
-pub struct TensorMetadata<'a> {
+pub struct ExternalData<'a> {
   pub _tab: flatbuffers::Table<'a>,
 }
 
-impl<'a> flatbuffers::Follow<'a> for TensorMetadata<'a> {
-  type Inner = TensorMetadata<'a>;
+impl<'a> flatbuffers::Follow<'a> for ExternalData<'a> {
+  type Inner = ExternalData<'a>;
   #[inline]
   unsafe fn follow(buf: &'a [u8], loc: usize) -> Self::Inner {
     Self { _tab: flatbuffers::Table::new(buf, loc) }
   }
 }
 
-impl<'a> TensorMetadata<'a> {
-  pub const VT_ID: flatbuffers::VOffsetT = 4;
-  pub const VT_NAME: flatbuffers::VOffsetT = 6;
-  pub const VT_SHAPE: flatbuffers::VOffsetT = 8;
-  pub const VT_DATA_TYPE: flatbuffers::VOffsetT = 10;
-  pub const VT_DATA_OFFSET: flatbuffers::VOffsetT = 12;
-  pub const VT_DATA_SIZE: flatbuffers::VOffsetT = 14;
+impl<'a> ExternalData<'a> {
+  pub const VT_LOCATION: flatbuffers::VOffsetT = 4;
+  pub const VT_DATA_OFFSET: flatbuffers::VOffsetT = 6;
 
   #[inline]
   pub unsafe fn init_from_table(table: flatbuffers::Table<'a>) -> Self {
-    TensorMetadata { _tab: table }
+    ExternalData { _tab: table }
   }
   #[allow(unused_mut)]
   pub fn create<'bldr: 'args, 'args: 'mut_bldr, 'mut_bldr, A: flatbuffers::Allocator + 'bldr>(
     _fbb: &'mut_bldr mut flatbuffers::FlatBufferBuilder<'bldr, A>,
-    args: &'args TensorMetadataArgs<'args>
-  ) -> flatbuffers::WIPOffset<TensorMetadata<'bldr>> {
-    let mut builder = TensorMetadataBuilder::new(_fbb);
-    builder.add_id(args.id);
-    builder.add_data_size(args.data_size);
+    args: &'args ExternalDataArgs<'args>
+  ) -> flatbuffers::WIPOffset<ExternalData<'bldr>> {
+    let mut builder = ExternalDataBuilder::new(_fbb);
     builder.add_data_offset(args.data_offset);
-    if let Some(x) = args.shape { builder.add_shape(x); }
-    if let Some(x) = args.name { builder.add_name(x); }
-    builder.add_data_type(args.data_type);
+    if let Some(x) = args.location { builder.add_location(x); }
     builder.finish()
   }
 
 
   #[inline]
-  pub fn id(&self) -> u64 {
+  pub fn location(&self) -> &'a str {
     // Safety:
     // Created from valid Table for this object
     // which contains a valid value in this slot
-    unsafe { self._tab.get::<u64>(TensorMetadata::VT_ID, Some(0)).unwrap()}
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<&str>>(ExternalData::VT_LOCATION, None).unwrap()}
   }
   #[inline]
-  pub fn key_compare_less_than(&self, o: &TensorMetadata) -> bool {
-    self.id() < o.id()
+  pub fn data_offset(&self) -> u64 {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<u64>(ExternalData::VT_DATA_OFFSET, Some(0)).unwrap()}
   }
+}
 
+impl flatbuffers::Verifiable for ExternalData<'_> {
   #[inline]
-  pub fn key_compare_with_value(&self, val: u64) -> ::core::cmp::Ordering {
-    let key = self.id();
-    key.cmp(&val)
+  fn run_verifier(
+    v: &mut flatbuffers::Verifier, pos: usize
+  ) -> Result<(), flatbuffers::InvalidFlatbuffer> {
+    use self::flatbuffers::Verifiable;
+    v.visit_table(pos)?
+     .visit_field::<flatbuffers::ForwardsUOffset<&str>>("location", Self::VT_LOCATION, true)?
+     .visit_field::<u64>("data_offset", Self::VT_DATA_OFFSET, false)?
+     .finish();
+    Ok(())
   }
+}
+pub struct ExternalDataArgs<'a> {
+    pub location: Option<flatbuffers::WIPOffset<&'a str>>,
+    pub data_offset: u64,
+}
+impl<'a> Default for ExternalDataArgs<'a> {
   #[inline]
-  pub fn name(&self) -> &'a str {
-    // Safety:
-    // Created from valid Table for this object
-    // which contains a valid value in this slot
-    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<&str>>(TensorMetadata::VT_NAME, None).unwrap()}
+  fn default() -> Self {
+    ExternalDataArgs {
+      location: None, // required field
+      data_offset: 0,
+    }
   }
+}
+
+pub struct ExternalDataBuilder<'a: 'b, 'b, A: flatbuffers::Allocator + 'a> {
+  fbb_: &'b mut flatbuffers::FlatBufferBuilder<'a, A>,
+  start_: flatbuffers::WIPOffset<flatbuffers::TableUnfinishedWIPOffset>,
+}
+impl<'a: 'b, 'b, A: flatbuffers::Allocator + 'a> ExternalDataBuilder<'a, 'b, A> {
   #[inline]
-  pub fn shape(&self) -> Option<flatbuffers::Vector<'a, u32>> {
-    // Safety:
-    // Created from valid Table for this object
-    // which contains a valid value in this slot
-    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'a, u32>>>(TensorMetadata::VT_SHAPE, None)}
+  pub fn add_location(&mut self, location: flatbuffers::WIPOffset<&'b  str>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(ExternalData::VT_LOCATION, location);
+  }
+  #[inline]
+  pub fn add_data_offset(&mut self, data_offset: u64) {
+    self.fbb_.push_slot::<u64>(ExternalData::VT_DATA_OFFSET, data_offset, 0);
+  }
+  #[inline]
+  pub fn new(_fbb: &'b mut flatbuffers::FlatBufferBuilder<'a, A>) -> ExternalDataBuilder<'a, 'b, A> {
+    let start = _fbb.start_table();
+    ExternalDataBuilder {
+      fbb_: _fbb,
+      start_: start,
+    }
+  }
+  #[inline]
+  pub fn finish(self) -> flatbuffers::WIPOffset<ExternalData<'a>> {
+    let o = self.fbb_.end_table(self.start_);
+    self.fbb_.required(o, ExternalData::VT_LOCATION,"location");
+    flatbuffers::WIPOffset::new(o.value())
+  }
+}
+
+impl core::fmt::Debug for ExternalData<'_> {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    let mut ds = f.debug_struct("ExternalData");
+      ds.field("location", &self.location());
+      ds.field("data_offset", &self.data_offset());
+      ds.finish()
+  }
+}
+pub enum ExtensionTypeOffset {}
+#[derive(Copy, Clone, PartialEq)]
+
+pub struct ExtensionType<'a> {
+  pub _tab: flatbuffers::Table<'a>,
+}
+
+impl<'a> flatbuffers::Follow<'a> for ExtensionType<'a> {
+  type Inner = ExtensionType<'a>;
+  #[inline]
+  unsafe fn follow(buf: &'a [u8], loc: usize) -> Self::Inner {
+    Self { _tab: flatbuffers::Table::new(buf, loc) }
+  }
+}
+
+impl<'a> ExtensionType<'a> {
+  pub const VT_ID: flatbuffers::VOffsetT = 4;
+  pub const VT_ELEMENT_SIZE: flatbuffers::VOffsetT = 6;
+
+  #[inline]
+  pub unsafe fn init_from_table(table: flatbuffers::Table<'a>) -> Self {
+    ExtensionType { _tab: table }
+  }
+  #[allow(unused_mut)]
+  pub fn create<'bldr: 'args, 'args: 'mut_bldr, 'mut_bldr, A: flatbuffers::Allocator + 'bldr>(
+    _fbb: &'mut_bldr mut flatbuffers::FlatBufferBuilder<'bldr, A>,
+    args: &'args ExtensionTypeArgs<'args>
+  ) -> flatbuffers::WIPOffset<ExtensionType<'bldr>> {
+    let mut builder = ExtensionTypeBuilder::new(_fbb);
+    builder.add_element_size(args.element_size);
+    if let Some(x) = args.id { builder.add_id(x); }
+    builder.finish()
+  }
+
+
+  #[inline]
+  pub fn id(&self) -> &'a str {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<&str>>(ExtensionType::VT_ID, None).unwrap()}
+  }
+  #[inline]
+  pub fn element_size(&self) -> u32 {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<u32>(ExtensionType::VT_ELEMENT_SIZE, Some(0)).unwrap()}
+  }
+}
+
+impl flatbuffers::Verifiable for ExtensionType<'_> {
+  #[inline]
+  fn run_verifier(
+    v: &mut flatbuffers::Verifier, pos: usize
+  ) -> Result<(), flatbuffers::InvalidFlatbuffer> {
+    use self::flatbuffers::Verifiable;
+    v.visit_table(pos)?
+     .visit_field::<flatbuffers::ForwardsUOffset<&str>>("id", Self::VT_ID, true)?
+     .visit_field::<u32>("element_size", Self::VT_ELEMENT_SIZE, false)?
+     .finish();
+    Ok(())
+  }
+}
+pub struct ExtensionTypeArgs<'a> {
+    pub id: Option<flatbuffers::WIPOffset<&'a str>>,
+    pub element_size: u32,
+}
+impl<'a> Default for ExtensionTypeArgs<'a> {
+  #[inline]
+  fn default() -> Self {
+    ExtensionTypeArgs {
+      id: None, // required field
+      element_size: 0,
+    }
+  }
+}
+
+pub struct ExtensionTypeBuilder<'a: 'b, 'b, A: flatbuffers::Allocator + 'a> {
+  fbb_: &'b mut flatbuffers::FlatBufferBuilder<'a, A>,
+  start_: flatbuffers::WIPOffset<flatbuffers::TableUnfinishedWIPOffset>,
+}
+impl<'a: 'b, 'b, A: flatbuffers::Allocator + 'a> ExtensionTypeBuilder<'a, 'b, A> {
+  #[inline]
+  pub fn add_id(&mut self, id: flatbuffers::WIPOffset<&'b  str>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(ExtensionType::VT_ID, id);
+  }
+  #[inline]
+  pub fn add_element_size(&mut self, element_size: u32) {
+    self.fbb_.push_slot::<u32>(ExtensionType::VT_ELEMENT_SIZE, element_size, 0);
+  }
+  #[inline]
+  pub fn new(_fbb: &'b mut flatbuffers::FlatBufferBuilder<'a, A>) -> ExtensionTypeBuilder<'a, 'b, A> {
+    let start = _fbb.start_table();
+    ExtensionTypeBuilder {
+      fbb_: _fbb,
+      start_: start,
+    }
+  }
+  #[inline]
+  pub fn finish(self) -> flatbuffers::WIPOffset<ExtensionType<'a>> {
+    let o = self.fbb_.end_table(self.start_);
+    self.fbb_.required(o, ExtensionType::VT_ID,"id");
+    flatbuffers::WIPOffset::new(o.value())
+  }
+}
+
+impl core::fmt::Debug for ExtensionType<'_> {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    let mut ds = f.debug_struct("ExtensionType");
+      ds.field("id", &self.id());
+      ds.field("element_size", &self.element_size());
+      ds.finish()
+  }
+}
+pub enum ProvenanceMetadataOffset {}
+#[derive(Copy, Clone, PartialEq)]
+
+pub struct ProvenanceMetadata<'a> {
+  pub _tab: flatbuffers::Table<'a>,
+}
+
+impl<'a> flatbuffers::Follow<'a> for ProvenanceMetadata<'a> {
+  type Inner = ProvenanceMetadata<'a>;
+  #[inline]
+  unsafe fn follow(buf: &'a [u8], loc: usize) -> Self::Inner {
+    Self { _tab: flatbuffers::Table::new(buf, loc) }
+  }
+}
+
+impl<'a> ProvenanceMetadata<'a> {
+  pub const VT_SOURCE_COMMIT: flatbuffers::VOffsetT = 4;
+  pub const VT_DATASET_HASHES: flatbuffers::VOffsetT = 6;
+  pub const VT_CONFIG_HASH: flatbuffers::VOffsetT = 8;
+  pub const VT_PARENT_CHECKPOINT: flatbuffers::VOffsetT = 10;
+
+  #[inline]
+  pub unsafe fn init_from_table(table: flatbuffers::Table<'a>) -> Self {
+    ProvenanceMetadata { _tab: table }
+  }
+  #[allow(unused_mut)]
+  pub fn create<'bldr: 'args, 'args: 'mut_bldr, 'mut_bldr, A: flatbuffers::Allocator + 'bldr>(
+    _fbb: &'mut_bldr mut flatbuffers::FlatBufferBuilder<'bldr, A>,
+    args: &'args ProvenanceMetadataArgs<'args>
+  ) -> flatbuffers::WIPOffset<ProvenanceMetadata<'bldr>> {
+    let mut builder = ProvenanceMetadataBuilder::new(_fbb);
+    if let Some(x) = args.parent_checkpoint { builder.add_parent_checkpoint(x); }
+    if let Some(x) = args.config_hash { builder.add_config_hash(x); }
+    if let Some(x) = args.dataset_hashes { builder.add_dataset_hashes(x); }
+    if let Some(x) = args.source_commit { builder.add_source_commit(x); }
+    builder.finish()
+  }
+
+
+  #[inline]
+  pub fn source_commit(&self) -> Option<&'a str> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<&str>>(ProvenanceMetadata::VT_SOURCE_COMMIT, None)}
+  }
+  #[inline]
+  pub fn dataset_hashes(&self) -> Option<flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<&'a str>>> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<&'a str>>>>(ProvenanceMetadata::VT_DATASET_HASHES, None)}
+  }
+  #[inline]
+  pub fn config_hash(&self) -> Option<&'a str> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<&str>>(ProvenanceMetadata::VT_CONFIG_HASH, None)}
+  }
+  #[inline]
+  pub fn parent_checkpoint(&self) -> Option<&'a str> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<&str>>(ProvenanceMetadata::VT_PARENT_CHECKPOINT, None)}
+  }
+}
+
+impl flatbuffers::Verifiable for ProvenanceMetadata<'_> {
+  #[inline]
+  fn run_verifier(
+    v: &mut flatbuffers::Verifier, pos: usize
+  ) -> Result<(), flatbuffers::InvalidFlatbuffer> {
+    use self::flatbuffers::Verifiable;
+    v.visit_table(pos)?
+     .visit_field::<flatbuffers::ForwardsUOffset<&str>>("source_commit", Self::VT_SOURCE_COMMIT, false)?
+     .visit_field::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'_, flatbuffers::ForwardsUOffset<&'_ str>>>>("dataset_hashes", Self::VT_DATASET_HASHES, false)?
+     .visit_field::<flatbuffers::ForwardsUOffset<&str>>("config_hash", Self::VT_CONFIG_HASH, false)?
+     .visit_field::<flatbuffers::ForwardsUOffset<&str>>("parent_checkpoint", Self::VT_PARENT_CHECKPOINT, false)?
+     .finish();
+    Ok(())
+  }
+}
+pub struct ProvenanceMetadataArgs<'a> {
+    pub source_commit: Option<flatbuffers::WIPOffset<&'a str>>,
+    pub dataset_hashes: Option<flatbuffers::WIPOffset<flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<&'a str>>>>,
+    pub config_hash: Option<flatbuffers::WIPOffset<&'a str>>,
+    pub parent_checkpoint: Option<flatbuffers::WIPOffset<&'a str>>,
+}
+impl<'a> Default for ProvenanceMetadataArgs<'a> {
+  #[inline]
+  fn default() -> Self {
+    ProvenanceMetadataArgs {
+      source_commit: None,
+      dataset_hashes: None,
+      config_hash: None,
+      parent_checkpoint: None,
+    }
+  }
+}
+
+pub struct ProvenanceMetadataBuilder<'a: 'b, 'b, A: flatbuffers::Allocator + 'a> {
+  fbb_: &'b mut flatbuffers::FlatBufferBuilder<'a, A>,
+  start_: flatbuffers::WIPOffset<flatbuffers::TableUnfinishedWIPOffset>,
+}
+impl<'a: 'b, 'b, A: flatbuffers::Allocator + 'a> ProvenanceMetadataBuilder<'a, 'b, A> {
+  #[inline]
+  pub fn add_source_commit(&mut self, source_commit: flatbuffers::WIPOffset<&'b  str>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(ProvenanceMetadata::VT_SOURCE_COMMIT, source_commit);
+  }
+  #[inline]
+  pub fn add_dataset_hashes(&mut self, dataset_hashes: flatbuffers::WIPOffset<flatbuffers::Vector<'b , flatbuffers::ForwardsUOffset<&'b  str>>>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(ProvenanceMetadata::VT_DATASET_HASHES, dataset_hashes);
+  }
+  #[inline]
+  pub fn add_config_hash(&mut self, config_hash: flatbuffers::WIPOffset<&'b  str>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(ProvenanceMetadata::VT_CONFIG_HASH, config_hash);
+  }
+  #[inline]
+  pub fn add_parent_checkpoint(&mut self, parent_checkpoint: flatbuffers::WIPOffset<&'b  str>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(ProvenanceMetadata::VT_PARENT_CHECKPOINT, parent_checkpoint);
+  }
+  #[inline]
+  pub fn new(_fbb: &'b mut flatbuffers::FlatBufferBuilder<'a, A>) -> ProvenanceMetadataBuilder<'a, 'b, A> {
+    let start = _fbb.start_table();
+    ProvenanceMetadataBuilder {
+      fbb_: _fbb,
+      start_: start,
+    }
+  }
+  #[inline]
+  pub fn finish(self) -> flatbuffers::WIPOffset<ProvenanceMetadata<'a>> {
+    let o = self.fbb_.end_table(self.start_);
+    flatbuffers::WIPOffset::new(o.value())
+  }
+}
+
+impl core::fmt::Debug for ProvenanceMetadata<'_> {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    let mut ds = f.debug_struct("ProvenanceMetadata");
+      ds.field("source_commit", &self.source_commit());
+      ds.field("dataset_hashes", &self.dataset_hashes());
+      ds.field("config_hash", &self.config_hash());
+      ds.field("parent_checkpoint", &self.parent_checkpoint());
+      ds.finish()
+  }
+}
+pub enum ConstraintMetadataOffset {}
+#[derive(Copy, Clone, PartialEq)]
+
+pub struct ConstraintMetadata<'a> {
+  pub _tab: flatbuffers::Table<'a>,
+}
+
+impl<'a> flatbuffers::Follow<'a> for ConstraintMetadata<'a> {
+  type Inner = ConstraintMetadata<'a>;
+  #[inline]
+  unsafe fn follow(buf: &'a [u8], loc: usize) -> Self::Inner {
+    Self { _tab: flatbuffers::Table::new(buf, loc) }
+  }
+}
+
+impl<'a> ConstraintMetadata<'a> {
+  pub const VT_KIND: flatbuffers::VOffsetT = 4;
+  pub const VT_TENSORS: flatbuffers::VOffsetT = 6;
+  pub const VT_AXES: flatbuffers::VOffsetT = 8;
+
+  #[inline]
+  pub unsafe fn init_from_table(table: flatbuffers::Table<'a>) -> Self {
+    ConstraintMetadata { _tab: table }
+  }
+  #[allow(unused_mut)]
+  pub fn create<'bldr: 'args, 'args: 'mut_bldr, 'mut_bldr, A: flatbuffers::Allocator + 'bldr>(
+    _fbb: &'mut_bldr mut flatbuffers::FlatBufferBuilder<'bldr, A>,
+    args: &'args ConstraintMetadataArgs<'args>
+  ) -> flatbuffers::WIPOffset<ConstraintMetadata<'bldr>> {
+    let mut builder = ConstraintMetadataBuilder::new(_fbb);
+    if let Some(x) = args.axes { builder.add_axes(x); }
+    if let Some(x) = args.tensors { builder.add_tensors(x); }
+    builder.add_kind(args.kind);
+    builder.finish()
+  }
+
+
+  #[inline]
+  pub fn kind(&self) -> ConstraintKind {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<ConstraintKind>(ConstraintMetadata::VT_KIND, Some(ConstraintKind::Equal)).unwrap()}
+  }
+  #[inline]
+  pub fn tensors(&self) -> Option<flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<&'a str>>> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<&'a str>>>>(ConstraintMetadata::VT_TENSORS, None)}
+  }
+  #[inline]
+  pub fn axes(&self) -> Option<flatbuffers::Vector<'a, u32>> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'a, u32>>>(ConstraintMetadata::VT_AXES, None)}
+  }
+}
+
+impl flatbuffers::Verifiable for ConstraintMetadata<'_> {
+  #[inline]
+  fn run_verifier(
+    v: &mut flatbuffers::Verifier, pos: usize
+  ) -> Result<(), flatbuffers::InvalidFlatbuffer> {
+    use self::flatbuffers::Verifiable;
+    v.visit_table(pos)?
+     .visit_field::<ConstraintKind>("kind", Self::VT_KIND, false)?
+     .visit_field::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'_, flatbuffers::ForwardsUOffset<&'_ str>>>>("tensors", Self::VT_TENSORS, false)?
+     .visit_field::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'_, u32>>>("axes", Self::VT_AXES, false)?
+     .finish();
+    Ok(())
+  }
+}
+pub struct ConstraintMetadataArgs<'a> {
+    pub kind: ConstraintKind,
+    pub tensors: Option<flatbuffers::WIPOffset<flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<&'a str>>>>,
+    pub axes: Option<flatbuffers::WIPOffset<flatbuffers::Vector<'a, u32>>>,
+}
+impl<'a> Default for ConstraintMetadataArgs<'a> {
+  #[inline]
+  fn default() -> Self {
+    ConstraintMetadataArgs {
+      kind: ConstraintKind::Equal,
+      tensors: None,
+      axes: None,
+    }
+  }
+}
+
+pub struct ConstraintMetadataBuilder<'a: 'b, 'b, A: flatbuffers::Allocator + 'a> {
+  fbb_: &'b mut flatbuffers::FlatBufferBuilder<'a, A>,
+  start_: flatbuffers::WIPOffset<flatbuffers::TableUnfinishedWIPOffset>,
+}
+impl<'a: 'b, 'b, A: flatbuffers::Allocator + 'a> ConstraintMetadataBuilder<'a, 'b, A> {
+  #[inline]
+  pub fn add_kind(&mut self, kind: ConstraintKind) {
+    self.fbb_.push_slot::<ConstraintKind>(ConstraintMetadata::VT_KIND, kind, ConstraintKind::Equal);
+  }
+  #[inline]
+  pub fn add_tensors(&mut self, tensors: flatbuffers::WIPOffset<flatbuffers::Vector<'b , flatbuffers::ForwardsUOffset<&'b  str>>>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(ConstraintMetadata::VT_TENSORS, tensors);
+  }
+  #[inline]
+  pub fn add_axes(&mut self, axes: flatbuffers::WIPOffset<flatbuffers::Vector<'b , u32>>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(ConstraintMetadata::VT_AXES, axes);
+  }
+  #[inline]
+  pub fn new(_fbb: &'b mut flatbuffers::FlatBufferBuilder<'a, A>) -> ConstraintMetadataBuilder<'a, 'b, A> {
+    let start = _fbb.start_table();
+    ConstraintMetadataBuilder {
+      fbb_: _fbb,
+      start_: start,
+    }
+  }
+  #[inline]
+  pub fn finish(self) -> flatbuffers::WIPOffset<ConstraintMetadata<'a>> {
+    let o = self.fbb_.end_table(self.start_);
+    flatbuffers::WIPOffset::new(o.value())
+  }
+}
+
+impl core::fmt::Debug for ConstraintMetadata<'_> {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    let mut ds = f.debug_struct("ConstraintMetadata");
+      ds.field("kind", &self.kind());
+      ds.field("tensors", &self.tensors());
+      ds.field("axes", &self.axes());
+      ds.finish()
+  }
+}
+pub enum QuantizationMetadataOffset {}
+#[derive(Copy, Clone, PartialEq)]
+
+pub struct QuantizationMetadata<'a> {
+  pub _tab: flatbuffers::Table<'a>,
+}
+
+impl<'a> flatbuffers::Follow<'a> for QuantizationMetadata<'a> {
+  type Inner = QuantizationMetadata<'a>;
+  #[inline]
+  unsafe fn follow(buf: &'a [u8], loc: usize) -> Self::Inner {
+    Self { _tab: flatbuffers::Table::new(buf, loc) }
+  }
+}
+
+impl<'a> QuantizationMetadata<'a> {
+  pub const VT_ORIGINAL_TYPE: flatbuffers::VOffsetT = 4;
+  pub const VT_BITS: flatbuffers::VOffsetT = 6;
+  pub const VT_SHAPE: flatbuffers::VOffsetT = 8;
+  pub const VT_AXIS: flatbuffers::VOffsetT = 10;
+  pub const VT_SCALES: flatbuffers::VOffsetT = 12;
+  pub const VT_ZERO_POINTS: flatbuffers::VOffsetT = 14;
+  pub const VT_GROUP_SIZE: flatbuffers::VOffsetT = 16;
+  pub const VT_SCALES_OFFSET: flatbuffers::VOffsetT = 18;
+
+  #[inline]
+  pub unsafe fn init_from_table(table: flatbuffers::Table<'a>) -> Self {
+    QuantizationMetadata { _tab: table }
+  }
+  #[allow(unused_mut)]
+  pub fn create<'bldr: 'args, 'args: 'mut_bldr, 'mut_bldr, A: flatbuffers::Allocator + 'bldr>(
+    _fbb: &'mut_bldr mut flatbuffers::FlatBufferBuilder<'bldr, A>,
+    args: &'args QuantizationMetadataArgs<'args>
+  ) -> flatbuffers::WIPOffset<QuantizationMetadata<'bldr>> {
+    let mut builder = QuantizationMetadataBuilder::new(_fbb);
+    builder.add_scales_offset(args.scales_offset);
+    builder.add_group_size(args.group_size);
+    if let Some(x) = args.zero_points { builder.add_zero_points(x); }
+    if let Some(x) = args.scales { builder.add_scales(x); }
+    builder.add_axis(args.axis);
+    if let Some(x) = args.shape { builder.add_shape(x); }
+    builder.add_bits(args.bits);
+    builder.add_original_type(args.original_type);
+    builder.finish()
+  }
+
+
+  #[inline]
+  pub fn original_type(&self) -> DataType {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<DataType>(QuantizationMetadata::VT_ORIGINAL_TYPE, Some(DataType::None)).unwrap()}
+  }
+  #[inline]
+  pub fn bits(&self) -> u8 {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<u8>(QuantizationMetadata::VT_BITS, Some(0)).unwrap()}
+  }
+  #[inline]
+  pub fn shape(&self) -> Option<flatbuffers::Vector<'a, u64>> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'a, u64>>>(QuantizationMetadata::VT_SHAPE, None)}
+  }
+  #[inline]
+  pub fn axis(&self) -> i32 {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<i32>(QuantizationMetadata::VT_AXIS, Some(-1)).unwrap()}
+  }
+  #[inline]
+  pub fn scales(&self) -> Option<flatbuffers::Vector<'a, f32>> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'a, f32>>>(QuantizationMetadata::VT_SCALES, None)}
+  }
+  #[inline]
+  pub fn zero_points(&self) -> Option<flatbuffers::Vector<'a, i32>> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'a, i32>>>(QuantizationMetadata::VT_ZERO_POINTS, None)}
+  }
+  #[inline]
+  pub fn group_size(&self) -> u32 {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<u32>(QuantizationMetadata::VT_GROUP_SIZE, Some(0)).unwrap()}
+  }
+  #[inline]
+  pub fn scales_offset(&self) -> u64 {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<u64>(QuantizationMetadata::VT_SCALES_OFFSET, Some(0)).unwrap()}
+  }
+}
+
+impl flatbuffers::Verifiable for QuantizationMetadata<'_> {
+  #[inline]
+  fn run_verifier(
+    v: &mut flatbuffers::Verifier, pos: usize
+  ) -> Result<(), flatbuffers::InvalidFlatbuffer> {
+    use self::flatbuffers::Verifiable;
+    v.visit_table(pos)?
+     .visit_field::<DataType>("original_type", Self::VT_ORIGINAL_TYPE, false)?
+     .visit_field::<u8>("bits", Self::VT_BITS, false)?
+     .visit_field::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'_, u64>>>("shape", Self::VT_SHAPE, false)?
+     .visit_field::<i32>("axis", Self::VT_AXIS, false)?
+     .visit_field::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'_, f32>>>("scales", Self::VT_SCALES, false)?
+     .visit_field::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'_, i32>>>("zero_points", Self::VT_ZERO_POINTS, false)?
+     .visit_field::<u32>("group_size", Self::VT_GROUP_SIZE, false)?
+     .visit_field::<u64>("scales_offset", Self::VT_SCALES_OFFSET, false)?
+     .finish();
+    Ok(())
+  }
+}
+pub struct QuantizationMetadataArgs<'a> {
+    pub original_type: DataType,
+    pub bits: u8,
+    pub shape: Option<flatbuffers::WIPOffset<flatbuffers::Vector<'a, u64>>>,
+    pub axis: i32,
+    pub scales: Option<flatbuffers::WIPOffset<flatbuffers::Vector<'a, f32>>>,
+    pub zero_points: Option<flatbuffers::WIPOffset<flatbuffers::Vector<'a, i32>>>,
+    pub group_size: u32,
+    pub scales_offset: u64,
+}
+impl<'a> Default for QuantizationMetadataArgs<'a> {
+  #[inline]
+  fn default() -> Self {
+    QuantizationMetadataArgs {
+      original_type: DataType::None,
+      bits: 0,
+      shape: None,
+      axis: -1,
+      scales: None,
+      zero_points: None,
+      group_size: 0,
+      scales_offset: 0,
+    }
+  }
+}
+
+pub struct QuantizationMetadataBuilder<'a: 'b, 'b, A: flatbuffers::Allocator + 'a> {
+  fbb_: &'b mut flatbuffers::FlatBufferBuilder<'a, A>,
+  start_: flatbuffers::WIPOffset<flatbuffers::TableUnfinishedWIPOffset>,
+}
+impl<'a: 'b, 'b, A: flatbuffers::Allocator + 'a> QuantizationMetadataBuilder<'a, 'b, A> {
+  #[inline]
+  pub fn add_original_type(&mut self, original_type: DataType) {
+    self.fbb_.push_slot::<DataType>(QuantizationMetadata::VT_ORIGINAL_TYPE, original_type, DataType::None);
+  }
+  #[inline]
+  pub fn add_bits(&mut self, bits: u8) {
+    self.fbb_.push_slot::<u8>(QuantizationMetadata::VT_BITS, bits, 0);
+  }
+  #[inline]
+  pub fn add_shape(&mut self, shape: flatbuffers::WIPOffset<flatbuffers::Vector<'b , u64>>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(QuantizationMetadata::VT_SHAPE, shape);
+  }
+  #[inline]
+  pub fn add_axis(&mut self, axis: i32) {
+    self.fbb_.push_slot::<i32>(QuantizationMetadata::VT_AXIS, axis, -1);
+  }
+  #[inline]
+  pub fn add_scales(&mut self, scales: flatbuffers::WIPOffset<flatbuffers::Vector<'b , f32>>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(QuantizationMetadata::VT_SCALES, scales);
+  }
+  #[inline]
+  pub fn add_zero_points(&mut self, zero_points: flatbuffers::WIPOffset<flatbuffers::Vector<'b , i32>>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(QuantizationMetadata::VT_ZERO_POINTS, zero_points);
+  }
+  #[inline]
+  pub fn add_group_size(&mut self, group_size: u32) {
+    self.fbb_.push_slot::<u32>(QuantizationMetadata::VT_GROUP_SIZE, group_size, 0);
+  }
+  #[inline]
+  pub fn add_scales_offset(&mut self, scales_offset: u64) {
+    self.fbb_.push_slot::<u64>(QuantizationMetadata::VT_SCALES_OFFSET, scales_offset, 0);
+  }
+  #[inline]
+  pub fn new(_fbb: &'b mut flatbuffers::FlatBufferBuilder<'a, A>) -> QuantizationMetadataBuilder<'a, 'b, A> {
+    let start = _fbb.start_table();
+    QuantizationMetadataBuilder {
+      fbb_: _fbb,
+      start_: start,
+    }
+  }
+  #[inline]
+  pub fn finish(self) -> flatbuffers::WIPOffset<QuantizationMetadata<'a>> {
+    let o = self.fbb_.end_table(self.start_);
+    flatbuffers::WIPOffset::new(o.value())
+  }
+}
+
+impl core::fmt::Debug for QuantizationMetadata<'_> {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    let mut ds = f.debug_struct("QuantizationMetadata");
+      ds.field("original_type", &self.original_type());
+      ds.field("bits", &self.bits());
+      ds.field("shape", &self.shape());
+      ds.field("axis", &self.axis());
+      ds.field("scales", &self.scales());
+      ds.field("zero_points", &self.zero_points());
+      ds.field("group_size", &self.group_size());
+      ds.field("scales_offset", &self.scales_offset());
+      ds.finish()
+  }
+}
+pub enum TensorMetadataOffset {}
+#[derive(Copy, Clone, PartialEq)]
+
+pub struct TensorMetadata<'a> {
+  pub _tab: flatbuffers::Table<'a>,
+}
+
+impl<'a> flatbuffers::Follow<'a> for TensorMetadata<'a> {
+  type Inner = TensorMetadata<'a>;
+  #[inline]
+  unsafe fn follow(buf: &'a [u8], loc: usize) -> Self::Inner {
+    Self { _tab: flatbuffers::Table::new(buf, loc) }
+  }
+}
+
+impl<'a> TensorMetadata<'a> {
+  pub const VT_ID: flatbuffers::VOffsetT = 4;
+  pub const VT_NAME: flatbuffers::VOffsetT = 6;
+  pub const VT_SHAPE: flatbuffers::VOffsetT = 8;
+  pub const VT_DATA_TYPE: flatbuffers::VOffsetT = 10;
+  pub const VT_DATA_OFFSET: flatbuffers::VOffsetT = 12;
+  pub const VT_DATA_SIZE: flatbuffers::VOffsetT = 14;
+  pub const VT_COMPRESSION: flatbuffers::VOffsetT = 16;
+  pub const VT_STORED_SIZE: flatbuffers::VOffsetT = 18;
+  pub const VT_CHECKSUM_ALGORITHM: flatbuffers::VOffsetT = 20;
+  pub const VT_CHECKSUM: flatbuffers::VOffsetT = 22;
+  pub const VT_SHAPE64: flatbuffers::VOffsetT = 24;
+  pub const VT_EXTERNAL: flatbuffers::VOffsetT = 26;
+  pub const VT_ALIAS_OF: flatbuffers::VOffsetT = 28;
+  pub const VT_CODEC: flatbuffers::VOffsetT = 30;
+  pub const VT_EXTENSION: flatbuffers::VOffsetT = 32;
+  pub const VT_USER_METADATA: flatbuffers::VOffsetT = 34;
+  pub const VT_QUANTIZATION: flatbuffers::VOffsetT = 36;
+
+  #[inline]
+  pub unsafe fn init_from_table(table: flatbuffers::Table<'a>) -> Self {
+    TensorMetadata { _tab: table }
+  }
+  #[allow(unused_mut)]
+  pub fn create<'bldr: 'args, 'args: 'mut_bldr, 'mut_bldr, A: flatbuffers::Allocator + 'bldr>(
+    _fbb: &'mut_bldr mut flatbuffers::FlatBufferBuilder<'bldr, A>,
+    args: &'args TensorMetadataArgs<'args>
+  ) -> flatbuffers::WIPOffset<TensorMetadata<'bldr>> {
+    let mut builder = TensorMetadataBuilder::new(_fbb);
+    builder.add_checksum(args.checksum);
+    builder.add_id(args.id);
+    if let Some(x) = args.quantization { builder.add_quantization(x); }
+    if let Some(x) = args.user_metadata { builder.add_user_metadata(x); }
+    if let Some(x) = args.extension { builder.add_extension(x); }
+    if let Some(x) = args.codec { builder.add_codec(x); }
+    if let Some(x) = args.alias_of { builder.add_alias_of(x); }
+    if let Some(x) = args.external { builder.add_external(x); }
+    if let Some(x) = args.shape64 { builder.add_shape64(x); }
+    builder.add_stored_size(args.stored_size);
+    builder.add_data_size(args.data_size);
+    builder.add_data_offset(args.data_offset);
+    if let Some(x) = args.shape { builder.add_shape(x); }
+    if let Some(x) = args.name { builder.add_name(x); }
+    builder.add_checksum_algorithm(args.checksum_algorithm);
+    builder.add_compression(args.compression);
+    builder.add_data_type(args.data_type);
+    builder.finish()
+  }
+
+
+  #[inline]
+  pub fn id(&self) -> u64 {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<u64>(TensorMetadata::VT_ID, Some(0)).unwrap()}
+  }
+  #[inline]
+  pub fn key_compare_less_than(&self, o: &TensorMetadata) -> bool {
+    self.id() < o.id()
+  }
+
+  #[inline]
+  pub fn key_compare_with_value(&self, val: u64) -> ::core::cmp::Ordering {
+    let key = self.id();
+    key.cmp(&val)
+  }
+  #[inline]
+  pub fn name(&self) -> &'a str {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<&str>>(TensorMetadata::VT_NAME, None).unwrap()}
+  }
+  #[inline]
+  pub fn shape(&self) -> Option<flatbuffers::Vector<'a, u32>> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'a, u32>>>(TensorMetadata::VT_SHAPE, None)}
   }
   #[inline]
   pub fn data_type(&self) -> DataType {
@@ -459,6 +1493,83 @@ impl<'a> TensorMetadata<'a> {
     // which contains a valid value in this slot
     unsafe { self._tab.get::<u32>(TensorMetadata::VT_DATA_SIZE, Some(0)).unwrap()}
   }
+  #[inline]
+  pub fn compression(&self) -> Compression {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<Compression>(TensorMetadata::VT_COMPRESSION, Some(Compression::None)).unwrap()}
+  }
+  #[inline]
+  pub fn stored_size(&self) -> u32 {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<u32>(TensorMetadata::VT_STORED_SIZE, Some(0)).unwrap()}
+  }
+  #[inline]
+  pub fn checksum_algorithm(&self) -> ChecksumAlgorithm {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<ChecksumAlgorithm>(TensorMetadata::VT_CHECKSUM_ALGORITHM, Some(ChecksumAlgorithm::None)).unwrap()}
+  }
+  #[inline]
+  pub fn checksum(&self) -> u64 {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<u64>(TensorMetadata::VT_CHECKSUM, Some(0)).unwrap()}
+  }
+  #[inline]
+  pub fn shape64(&self) -> Option<flatbuffers::Vector<'a, u64>> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'a, u64>>>(TensorMetadata::VT_SHAPE64, None)}
+  }
+  #[inline]
+  pub fn external(&self) -> Option<ExternalData<'a>> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<ExternalData>>(TensorMetadata::VT_EXTERNAL, None)}
+  }
+  #[inline]
+  pub fn alias_of(&self) -> Option<&'a str> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<&str>>(TensorMetadata::VT_ALIAS_OF, None)}
+  }
+  #[inline]
+  pub fn codec(&self) -> Option<&'a str> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<&str>>(TensorMetadata::VT_CODEC, None)}
+  }
+  #[inline]
+  pub fn extension(&self) -> Option<ExtensionType<'a>> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<ExtensionType>>(TensorMetadata::VT_EXTENSION, None)}
+  }
+  #[inline]
+  pub fn user_metadata(&self) -> Option<flatbuffers::Vector<'a, u8>> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'a, u8>>>(TensorMetadata::VT_USER_METADATA, None)}
+  }
+  #[inline]
+  pub fn quantization(&self) -> Option<QuantizationMetadata<'a>> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<QuantizationMetadata>>(TensorMetadata::VT_QUANTIZATION, None)}
+  }
 }
 
 impl flatbuffers::Verifiable for TensorMetadata<'_> {
@@ -474,6 +1585,17 @@ impl flatbuffers::Verifiable for TensorMetadata<'_> {
      .visit_field::<DataType>("data_type", Self::VT_DATA_TYPE, false)?
      .visit_field::<u32>("data_offset", Self::VT_DATA_OFFSET, false)?
      .visit_field::<u32>("data_size", Self::VT_DATA_SIZE, false)?
+     .visit_field::<Compression>("compression", Self::VT_COMPRESSION, false)?
+     .visit_field::<u32>("stored_size", Self::VT_STORED_SIZE, false)?
+     .visit_field::<ChecksumAlgorithm>("checksum_algorithm", Self::VT_CHECKSUM_ALGORITHM, false)?
+     .visit_field::<u64>("checksum", Self::VT_CHECKSUM, false)?
+     .visit_field::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'_, u64>>>("shape64", Self::VT_SHAPE64, false)?
+     .visit_field::<flatbuffers::ForwardsUOffset<ExternalData>>("external", Self::VT_EXTERNAL, false)?
+     .visit_field::<flatbuffers::ForwardsUOffset<&str>>("alias_of", Self::VT_ALIAS_OF, false)?
+     .visit_field::<flatbuffers::ForwardsUOffset<&str>>("codec", Self::VT_CODEC, false)?
+     .visit_field::<flatbuffers::ForwardsUOffset<ExtensionType>>("extension", Self::VT_EXTENSION, false)?
+     .visit_field::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'_, u8>>>("user_metadata", Self::VT_USER_METADATA, false)?
+     .visit_field::<flatbuffers::ForwardsUOffset<QuantizationMetadata>>("quantization", Self::VT_QUANTIZATION, false)?
      .finish();
     Ok(())
   }
@@ -485,6 +1607,17 @@ pub struct TensorMetadataArgs<'a> {
     pub data_type: DataType,
     pub data_offset: u32,
     pub data_size: u32,
+    pub compression: Compression,
+    pub stored_size: u32,
+    pub checksum_algorithm: ChecksumAlgorithm,
+    pub checksum: u64,
+    pub shape64: Option<flatbuffers::WIPOffset<flatbuffers::Vector<'a, u64>>>,
+    pub external: Option<flatbuffers::WIPOffset<ExternalData<'a>>>,
+    pub alias_of: Option<flatbuffers::WIPOffset<&'a str>>,
+    pub codec: Option<flatbuffers::WIPOffset<&'a str>>,
+    pub extension: Option<flatbuffers::WIPOffset<ExtensionType<'a>>>,
+    pub user_metadata: Option<flatbuffers::WIPOffset<flatbuffers::Vector<'a, u8>>>,
+    pub quantization: Option<flatbuffers::WIPOffset<QuantizationMetadata<'a>>>,
 }
 impl<'a> Default for TensorMetadataArgs<'a> {
   #[inline]
@@ -496,64 +1629,261 @@ impl<'a> Default for TensorMetadataArgs<'a> {
       data_type: DataType::None,
       data_offset: 0,
       data_size: 0,
+      compression: Compression::None,
+      stored_size: 0,
+      checksum_algorithm: ChecksumAlgorithm::None,
+      checksum: 0,
+      shape64: None,
+      external: None,
+      alias_of: None,
+      codec: None,
+      extension: None,
+      user_metadata: None,
+      quantization: None,
+    }
+  }
+}
+
+pub struct TensorMetadataBuilder<'a: 'b, 'b, A: flatbuffers::Allocator + 'a> {
+  fbb_: &'b mut flatbuffers::FlatBufferBuilder<'a, A>,
+  start_: flatbuffers::WIPOffset<flatbuffers::TableUnfinishedWIPOffset>,
+}
+impl<'a: 'b, 'b, A: flatbuffers::Allocator + 'a> TensorMetadataBuilder<'a, 'b, A> {
+  #[inline]
+  pub fn add_id(&mut self, id: u64) {
+    self.fbb_.push_slot::<u64>(TensorMetadata::VT_ID, id, 0);
+  }
+  #[inline]
+  pub fn add_name(&mut self, name: flatbuffers::WIPOffset<&'b  str>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(TensorMetadata::VT_NAME, name);
+  }
+  #[inline]
+  pub fn add_shape(&mut self, shape: flatbuffers::WIPOffset<flatbuffers::Vector<'b , u32>>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(TensorMetadata::VT_SHAPE, shape);
+  }
+  #[inline]
+  pub fn add_data_type(&mut self, data_type: DataType) {
+    self.fbb_.push_slot::<DataType>(TensorMetadata::VT_DATA_TYPE, data_type, DataType::None);
+  }
+  #[inline]
+  pub fn add_data_offset(&mut self, data_offset: u32) {
+    self.fbb_.push_slot::<u32>(TensorMetadata::VT_DATA_OFFSET, data_offset, 0);
+  }
+  #[inline]
+  pub fn add_data_size(&mut self, data_size: u32) {
+    self.fbb_.push_slot::<u32>(TensorMetadata::VT_DATA_SIZE, data_size, 0);
+  }
+  #[inline]
+  pub fn add_compression(&mut self, compression: Compression) {
+    self.fbb_.push_slot::<Compression>(TensorMetadata::VT_COMPRESSION, compression, Compression::None);
+  }
+  #[inline]
+  pub fn add_stored_size(&mut self, stored_size: u32) {
+    self.fbb_.push_slot::<u32>(TensorMetadata::VT_STORED_SIZE, stored_size, 0);
+  }
+  #[inline]
+  pub fn add_checksum_algorithm(&mut self, checksum_algorithm: ChecksumAlgorithm) {
+    self.fbb_.push_slot::<ChecksumAlgorithm>(TensorMetadata::VT_CHECKSUM_ALGORITHM, checksum_algorithm, ChecksumAlgorithm::None);
+  }
+  #[inline]
+  pub fn add_checksum(&mut self, checksum: u64) {
+    self.fbb_.push_slot::<u64>(TensorMetadata::VT_CHECKSUM, checksum, 0);
+  }
+  #[inline]
+  pub fn add_shape64(&mut self, shape64: flatbuffers::WIPOffset<flatbuffers::Vector<'b , u64>>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(TensorMetadata::VT_SHAPE64, shape64);
+  }
+  #[inline]
+  pub fn add_external(&mut self, external: flatbuffers::WIPOffset<ExternalData<'b >>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<ExternalData>>(TensorMetadata::VT_EXTERNAL, external);
+  }
+  #[inline]
+  pub fn add_alias_of(&mut self, alias_of: flatbuffers::WIPOffset<&'b  str>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(TensorMetadata::VT_ALIAS_OF, alias_of);
+  }
+  #[inline]
+  pub fn add_codec(&mut self, codec: flatbuffers::WIPOffset<&'b  str>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(TensorMetadata::VT_CODEC, codec);
+  }
+  #[inline]
+  pub fn add_extension(&mut self, extension: flatbuffers::WIPOffset<ExtensionType<'b >>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<ExtensionType>>(TensorMetadata::VT_EXTENSION, extension);
+  }
+  #[inline]
+  pub fn add_user_metadata(&mut self, user_metadata: flatbuffers::WIPOffset<flatbuffers::Vector<'b , u8>>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(TensorMetadata::VT_USER_METADATA, user_metadata);
+  }
+  #[inline]
+  pub fn add_quantization(&mut self, quantization: flatbuffers::WIPOffset<QuantizationMetadata<'b >>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<QuantizationMetadata>>(TensorMetadata::VT_QUANTIZATION, quantization);
+  }
+  #[inline]
+  pub fn new(_fbb: &'b mut flatbuffers::FlatBufferBuilder<'a, A>) -> TensorMetadataBuilder<'a, 'b, A> {
+    let start = _fbb.start_table();
+    TensorMetadataBuilder {
+      fbb_: _fbb,
+      start_: start,
+    }
+  }
+  #[inline]
+  pub fn finish(self) -> flatbuffers::WIPOffset<TensorMetadata<'a>> {
+    let o = self.fbb_.end_table(self.start_);
+    self.fbb_.required(o, TensorMetadata::VT_NAME,"name");
+    flatbuffers::WIPOffset::new(o.value())
+  }
+}
+
+impl core::fmt::Debug for TensorMetadata<'_> {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    let mut ds = f.debug_struct("TensorMetadata");
+      ds.field("id", &self.id());
+      ds.field("name", &self.name());
+      ds.field("shape", &self.shape());
+      ds.field("data_type", &self.data_type());
+      ds.field("data_offset", &self.data_offset());
+      ds.field("data_size", &self.data_size());
+      ds.field("compression", &self.compression());
+      ds.field("stored_size", &self.stored_size());
+      ds.field("checksum_algorithm", &self.checksum_algorithm());
+      ds.field("checksum", &self.checksum());
+      ds.field("shape64", &self.shape64());
+      ds.field("external", &self.external());
+      ds.field("alias_of", &self.alias_of());
+      ds.field("codec", &self.codec());
+      ds.field("extension", &self.extension());
+      ds.field("user_metadata", &self.user_metadata());
+      ds.field("quantization", &self.quantization());
+      ds.finish()
+  }
+}
+pub enum OperationProfileOffset {}
+#[derive(Copy, Clone, PartialEq)]
+
+pub struct OperationProfile<'a> {
+  pub _tab: flatbuffers::Table<'a>,
+}
+
+impl<'a> flatbuffers::Follow<'a> for OperationProfile<'a> {
+  type Inner = OperationProfile<'a>;
+  #[inline]
+  unsafe fn follow(buf: &'a [u8], loc: usize) -> Self::Inner {
+    Self { _tab: flatbuffers::Table::new(buf, loc) }
+  }
+}
+
+impl<'a> OperationProfile<'a> {
+  pub const VT_LATENCY_NS: flatbuffers::VOffsetT = 4;
+  pub const VT_FLOPS: flatbuffers::VOffsetT = 6;
+  pub const VT_MEMORY_BYTES: flatbuffers::VOffsetT = 8;
+
+  #[inline]
+  pub unsafe fn init_from_table(table: flatbuffers::Table<'a>) -> Self {
+    OperationProfile { _tab: table }
+  }
+  #[allow(unused_mut)]
+  pub fn create<'bldr: 'args, 'args: 'mut_bldr, 'mut_bldr, A: flatbuffers::Allocator + 'bldr>(
+    _fbb: &'mut_bldr mut flatbuffers::FlatBufferBuilder<'bldr, A>,
+    args: &'args OperationProfileArgs
+  ) -> flatbuffers::WIPOffset<OperationProfile<'bldr>> {
+    let mut builder = OperationProfileBuilder::new(_fbb);
+    builder.add_memory_bytes(args.memory_bytes);
+    builder.add_flops(args.flops);
+    builder.add_latency_ns(args.latency_ns);
+    builder.finish()
+  }
+
+
+  #[inline]
+  pub fn latency_ns(&self) -> u64 {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<u64>(OperationProfile::VT_LATENCY_NS, Some(0)).unwrap()}
+  }
+  #[inline]
+  pub fn flops(&self) -> u64 {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<u64>(OperationProfile::VT_FLOPS, Some(0)).unwrap()}
+  }
+  #[inline]
+  pub fn memory_bytes(&self) -> u64 {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<u64>(OperationProfile::VT_MEMORY_BYTES, Some(0)).unwrap()}
+  }
+}
+
+impl flatbuffers::Verifiable for OperationProfile<'_> {
+  #[inline]
+  fn run_verifier(
+    v: &mut flatbuffers::Verifier, pos: usize
+  ) -> Result<(), flatbuffers::InvalidFlatbuffer> {
+    use self::flatbuffers::Verifiable;
+    v.visit_table(pos)?
+     .visit_field::<u64>("latency_ns", Self::VT_LATENCY_NS, false)?
+     .visit_field::<u64>("flops", Self::VT_FLOPS, false)?
+     .visit_field::<u64>("memory_bytes", Self::VT_MEMORY_BYTES, false)?
+     .finish();
+    Ok(())
+  }
+}
+pub struct OperationProfileArgs {
+    pub latency_ns: u64,
+    pub flops: u64,
+    pub memory_bytes: u64,
+}
+impl<'a> Default for OperationProfileArgs {
+  #[inline]
+  fn default() -> Self {
+    OperationProfileArgs {
+      latency_ns: 0,
+      flops: 0,
+      memory_bytes: 0,
     }
   }
 }
 
-pub struct TensorMetadataBuilder<'a: 'b, 'b, A: flatbuffers::Allocator + 'a> {
+pub struct OperationProfileBuilder<'a: 'b, 'b, A: flatbuffers::Allocator + 'a> {
   fbb_: &'b mut flatbuffers::FlatBufferBuilder<'a, A>,
   start_: flatbuffers::WIPOffset<flatbuffers::TableUnfinishedWIPOffset>,
 }
-impl<'a: 'b, 'b, A: flatbuffers::Allocator + 'a> TensorMetadataBuilder<'a, 'b, A> {
-  #[inline]
-  pub fn add_id(&mut self, id: u64) {
-    self.fbb_.push_slot::<u64>(TensorMetadata::VT_ID, id, 0);
-  }
-  #[inline]
-  pub fn add_name(&mut self, name: flatbuffers::WIPOffset<&'b  str>) {
-    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(TensorMetadata::VT_NAME, name);
-  }
+impl<'a: 'b, 'b, A: flatbuffers::Allocator + 'a> OperationProfileBuilder<'a, 'b, A> {
   #[inline]
-  pub fn add_shape(&mut self, shape: flatbuffers::WIPOffset<flatbuffers::Vector<'b , u32>>) {
-    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(TensorMetadata::VT_SHAPE, shape);
-  }
-  #[inline]
-  pub fn add_data_type(&mut self, data_type: DataType) {
-    self.fbb_.push_slot::<DataType>(TensorMetadata::VT_DATA_TYPE, data_type, DataType::None);
+  pub fn add_latency_ns(&mut self, latency_ns: u64) {
+    self.fbb_.push_slot::<u64>(OperationProfile::VT_LATENCY_NS, latency_ns, 0);
   }
   #[inline]
-  pub fn add_data_offset(&mut self, data_offset: u32) {
-    self.fbb_.push_slot::<u32>(TensorMetadata::VT_DATA_OFFSET, data_offset, 0);
+  pub fn add_flops(&mut self, flops: u64) {
+    self.fbb_.push_slot::<u64>(OperationProfile::VT_FLOPS, flops, 0);
   }
   #[inline]
-  pub fn add_data_size(&mut self, data_size: u32) {
-    self.fbb_.push_slot::<u32>(TensorMetadata::VT_DATA_SIZE, data_size, 0);
+  pub fn add_memory_bytes(&mut self, memory_bytes: u64) {
+    self.fbb_.push_slot::<u64>(OperationProfile::VT_MEMORY_BYTES, memory_bytes, 0);
   }
   #[inline]
-  pub fn new(_fbb: &'b mut flatbuffers::FlatBufferBuilder<'a, A>) -> TensorMetadataBuilder<'a, 'b, A> {
+  pub fn new(_fbb: &'b mut flatbuffers::FlatBufferBuilder<'a, A>) -> OperationProfileBuilder<'a, 'b, A> {
     let start = _fbb.start_table();
-    TensorMetadataBuilder {
+    OperationProfileBuilder {
       fbb_: _fbb,
       start_: start,
     }
   }
   #[inline]
-  pub fn finish(self) -> flatbuffers::WIPOffset<TensorMetadata<'a>> {
+  pub fn finish(self) -> flatbuffers::WIPOffset<OperationProfile<'a>> {
     let o = self.fbb_.end_table(self.start_);
-    self.fbb_.required(o, TensorMetadata::VT_NAME,"name");
     flatbuffers::WIPOffset::new(o.value())
   }
 }
 
-impl core::fmt::Debug for TensorMetadata<'_> {
+impl core::fmt::Debug for OperationProfile<'_> {
   fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-    let mut ds = f.debug_struct("TensorMetadata");
-      ds.field("id", &self.id());
-      ds.field("name", &self.name());
-      ds.field("shape", &self.shape());
-      ds.field("data_type", &self.data_type());
-      ds.field("data_offset", &self.data_offset());
-      ds.field("data_size", &self.data_size());
+    let mut ds = f.debug_struct("OperationProfile");
+      ds.field("latency_ns", &self.latency_ns());
+      ds.field("flops", &self.flops());
+      ds.field("memory_bytes", &self.memory_bytes());
       ds.finish()
   }
 }
@@ -577,6 +1907,8 @@ impl<'a> OperationMetadata<'a> {
   pub const VT_OPERATION: flatbuffers::VOffsetT = 6;
   pub const VT_OUTPUT: flatbuffers::VOffsetT = 8;
   pub const VT_INPUT_OPERATIONS: flatbuffers::VOffsetT = 10;
+  pub const VT_NAME: flatbuffers::VOffsetT = 12;
+  pub const VT_PROFILE: flatbuffers::VOffsetT = 14;
 
   #[inline]
   pub unsafe fn init_from_table(table: flatbuffers::Table<'a>) -> Self {
@@ -590,6 +1922,8 @@ impl<'a> OperationMetadata<'a> {
     let mut builder = OperationMetadataBuilder::new(_fbb);
     builder.add_output(args.output);
     builder.add_id(args.id);
+    if let Some(x) = args.profile { builder.add_profile(x); }
+    if let Some(x) = args.name { builder.add_name(x); }
     if let Some(x) = args.input_operations { builder.add_input_operations(x); }
     builder.add_operation(args.operation);
     builder.finish()
@@ -634,6 +1968,20 @@ impl<'a> OperationMetadata<'a> {
     // which contains a valid value in this slot
     unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'a, u64>>>(OperationMetadata::VT_INPUT_OPERATIONS, None)}
   }
+  #[inline]
+  pub fn name(&self) -> Option<&'a str> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<&str>>(OperationMetadata::VT_NAME, None)}
+  }
+  #[inline]
+  pub fn profile(&self) -> Option<OperationProfile<'a>> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<OperationProfile>>(OperationMetadata::VT_PROFILE, None)}
+  }
 }
 
 impl flatbuffers::Verifiable for OperationMetadata<'_> {
@@ -647,6 +1995,8 @@ impl flatbuffers::Verifiable for OperationMetadata<'_> {
      .visit_field::<Operation>("operation", Self::VT_OPERATION, false)?
      .visit_field::<u64>("output", Self::VT_OUTPUT, false)?
      .visit_field::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'_, u64>>>("input_operations", Self::VT_INPUT_OPERATIONS, false)?
+     .visit_field::<flatbuffers::ForwardsUOffset<&str>>("name", Self::VT_NAME, false)?
+     .visit_field::<flatbuffers::ForwardsUOffset<OperationProfile>>("profile", Self::VT_PROFILE, false)?
      .finish();
     Ok(())
   }
@@ -656,6 +2006,8 @@ pub struct OperationMetadataArgs<'a> {
     pub operation: Operation,
     pub output: u64,
     pub input_operations: Option<flatbuffers::WIPOffset<flatbuffers::Vector<'a, u64>>>,
+    pub name: Option<flatbuffers::WIPOffset<&'a str>>,
+    pub profile: Option<flatbuffers::WIPOffset<OperationProfile<'a>>>,
 }
 impl<'a> Default for OperationMetadataArgs<'a> {
   #[inline]
@@ -665,6 +2017,8 @@ impl<'a> Default for OperationMetadataArgs<'a> {
       operation: Operation::None,
       output: 0,
       input_operations: None,
+      name: None,
+      profile: None,
     }
   }
 }
@@ -691,6 +2045,14 @@ impl<'a: 'b, 'b, A: flatbuffers::Allocator + 'a> OperationMetadataBuilder<'a, 'b
     self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(OperationMetadata::VT_INPUT_OPERATIONS, input_operations);
   }
   #[inline]
+  pub fn add_name(&mut self, name: flatbuffers::WIPOffset<&'b  str>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(OperationMetadata::VT_NAME, name);
+  }
+  #[inline]
+  pub fn add_profile(&mut self, profile: flatbuffers::WIPOffset<OperationProfile<'b >>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<OperationProfile>>(OperationMetadata::VT_PROFILE, profile);
+  }
+  #[inline]
   pub fn new(_fbb: &'b mut flatbuffers::FlatBufferBuilder<'a, A>) -> OperationMetadataBuilder<'a, 'b, A> {
     let start = _fbb.start_table();
     OperationMetadataBuilder {
@@ -712,6 +2074,309 @@ impl core::fmt::Debug for OperationMetadata<'_> {
       ds.field("operation", &self.operation());
       ds.field("output", &self.output());
       ds.field("input_operations", &self.input_operations());
+      ds.field("name", &self.name());
+      ds.field("profile", &self.profile());
+      ds.finish()
+  }
+}
+pub enum TensorColumnsOffset {}
+#[derive(Copy, Clone, PartialEq)]
+
+pub struct TensorColumns<'a> {
+  pub _tab: flatbuffers::Table<'a>,
+}
+
+impl<'a> flatbuffers::Follow<'a> for TensorColumns<'a> {
+  type Inner = TensorColumns<'a>;
+  #[inline]
+  unsafe fn follow(buf: &'a [u8], loc: usize) -> Self::Inner {
+    Self { _tab: flatbuffers::Table::new(buf, loc) }
+  }
+}
+
+impl<'a> TensorColumns<'a> {
+  pub const VT_IDS: flatbuffers::VOffsetT = 4;
+  pub const VT_NAME_ENDS: flatbuffers::VOffsetT = 6;
+  pub const VT_NAMES: flatbuffers::VOffsetT = 8;
+  pub const VT_SHAPE_ENDS: flatbuffers::VOffsetT = 10;
+  pub const VT_DIMS: flatbuffers::VOffsetT = 12;
+  pub const VT_DIMS64: flatbuffers::VOffsetT = 14;
+  pub const VT_DATA_TYPES: flatbuffers::VOffsetT = 16;
+  pub const VT_DATA_OFFSETS: flatbuffers::VOffsetT = 18;
+  pub const VT_DATA_SIZES: flatbuffers::VOffsetT = 20;
+  pub const VT_COMPRESSIONS: flatbuffers::VOffsetT = 22;
+  pub const VT_STORED_SIZES: flatbuffers::VOffsetT = 24;
+  pub const VT_CHECKSUM_ALGORITHM: flatbuffers::VOffsetT = 26;
+  pub const VT_CHECKSUMS: flatbuffers::VOffsetT = 28;
+
+  #[inline]
+  pub unsafe fn init_from_table(table: flatbuffers::Table<'a>) -> Self {
+    TensorColumns { _tab: table }
+  }
+  #[allow(unused_mut)]
+  pub fn create<'bldr: 'args, 'args: 'mut_bldr, 'mut_bldr, A: flatbuffers::Allocator + 'bldr>(
+    _fbb: &'mut_bldr mut flatbuffers::FlatBufferBuilder<'bldr, A>,
+    args: &'args TensorColumnsArgs<'args>
+  ) -> flatbuffers::WIPOffset<TensorColumns<'bldr>> {
+    let mut builder = TensorColumnsBuilder::new(_fbb);
+    if let Some(x) = args.checksums { builder.add_checksums(x); }
+    if let Some(x) = args.stored_sizes { builder.add_stored_sizes(x); }
+    if let Some(x) = args.compressions { builder.add_compressions(x); }
+    if let Some(x) = args.data_sizes { builder.add_data_sizes(x); }
+    if let Some(x) = args.data_offsets { builder.add_data_offsets(x); }
+    if let Some(x) = args.data_types { builder.add_data_types(x); }
+    if let Some(x) = args.dims64 { builder.add_dims64(x); }
+    if let Some(x) = args.dims { builder.add_dims(x); }
+    if let Some(x) = args.shape_ends { builder.add_shape_ends(x); }
+    if let Some(x) = args.names { builder.add_names(x); }
+    if let Some(x) = args.name_ends { builder.add_name_ends(x); }
+    if let Some(x) = args.ids { builder.add_ids(x); }
+    builder.add_checksum_algorithm(args.checksum_algorithm);
+    builder.finish()
+  }
+
+
+  #[inline]
+  pub fn ids(&self) -> Option<flatbuffers::Vector<'a, u64>> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'a, u64>>>(TensorColumns::VT_IDS, None)}
+  }
+  #[inline]
+  pub fn name_ends(&self) -> Option<flatbuffers::Vector<'a, u32>> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'a, u32>>>(TensorColumns::VT_NAME_ENDS, None)}
+  }
+  #[inline]
+  pub fn names(&self) -> Option<flatbuffers::Vector<'a, u8>> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'a, u8>>>(TensorColumns::VT_NAMES, None)}
+  }
+  #[inline]
+  pub fn shape_ends(&self) -> Option<flatbuffers::Vector<'a, u32>> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'a, u32>>>(TensorColumns::VT_SHAPE_ENDS, None)}
+  }
+  #[inline]
+  pub fn dims(&self) -> Option<flatbuffers::Vector<'a, u32>> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'a, u32>>>(TensorColumns::VT_DIMS, None)}
+  }
+  #[inline]
+  pub fn dims64(&self) -> Option<flatbuffers::Vector<'a, u64>> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'a, u64>>>(TensorColumns::VT_DIMS64, None)}
+  }
+  #[inline]
+  pub fn data_types(&self) -> Option<flatbuffers::Vector<'a, DataType>> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'a, DataType>>>(TensorColumns::VT_DATA_TYPES, None)}
+  }
+  #[inline]
+  pub fn data_offsets(&self) -> Option<flatbuffers::Vector<'a, u32>> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'a, u32>>>(TensorColumns::VT_DATA_OFFSETS, None)}
+  }
+  #[inline]
+  pub fn data_sizes(&self) -> Option<flatbuffers::Vector<'a, u32>> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'a, u32>>>(TensorColumns::VT_DATA_SIZES, None)}
+  }
+  #[inline]
+  pub fn compressions(&self) -> Option<flatbuffers::Vector<'a, Compression>> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'a, Compression>>>(TensorColumns::VT_COMPRESSIONS, None)}
+  }
+  #[inline]
+  pub fn stored_sizes(&self) -> Option<flatbuffers::Vector<'a, u32>> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'a, u32>>>(TensorColumns::VT_STORED_SIZES, None)}
+  }
+  #[inline]
+  pub fn checksum_algorithm(&self) -> ChecksumAlgorithm {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<ChecksumAlgorithm>(TensorColumns::VT_CHECKSUM_ALGORITHM, Some(ChecksumAlgorithm::None)).unwrap()}
+  }
+  #[inline]
+  pub fn checksums(&self) -> Option<flatbuffers::Vector<'a, u64>> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'a, u64>>>(TensorColumns::VT_CHECKSUMS, None)}
+  }
+}
+
+impl flatbuffers::Verifiable for TensorColumns<'_> {
+  #[inline]
+  fn run_verifier(
+    v: &mut flatbuffers::Verifier, pos: usize
+  ) -> Result<(), flatbuffers::InvalidFlatbuffer> {
+    use self::flatbuffers::Verifiable;
+    v.visit_table(pos)?
+     .visit_field::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'_, u64>>>("ids", Self::VT_IDS, false)?
+     .visit_field::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'_, u32>>>("name_ends", Self::VT_NAME_ENDS, false)?
+     .visit_field::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'_, u8>>>("names", Self::VT_NAMES, false)?
+     .visit_field::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'_, u32>>>("shape_ends", Self::VT_SHAPE_ENDS, false)?
+     .visit_field::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'_, u32>>>("dims", Self::VT_DIMS, false)?
+     .visit_field::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'_, u64>>>("dims64", Self::VT_DIMS64, false)?
+     .visit_field::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'_, DataType>>>("data_types", Self::VT_DATA_TYPES, false)?
+     .visit_field::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'_, u32>>>("data_offsets", Self::VT_DATA_OFFSETS, false)?
+     .visit_field::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'_, u32>>>("data_sizes", Self::VT_DATA_SIZES, false)?
+     .visit_field::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'_, Compression>>>("compressions", Self::VT_COMPRESSIONS, false)?
+     .visit_field::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'_, u32>>>("stored_sizes", Self::VT_STORED_SIZES, false)?
+     .visit_field::<ChecksumAlgorithm>("checksum_algorithm", Self::VT_CHECKSUM_ALGORITHM, false)?
+     .visit_field::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'_, u64>>>("checksums", Self::VT_CHECKSUMS, false)?
+     .finish();
+    Ok(())
+  }
+}
+pub struct TensorColumnsArgs<'a> {
+    pub ids: Option<flatbuffers::WIPOffset<flatbuffers::Vector<'a, u64>>>,
+    pub name_ends: Option<flatbuffers::WIPOffset<flatbuffers::Vector<'a, u32>>>,
+    pub names: Option<flatbuffers::WIPOffset<flatbuffers::Vector<'a, u8>>>,
+    pub shape_ends: Option<flatbuffers::WIPOffset<flatbuffers::Vector<'a, u32>>>,
+    pub dims: Option<flatbuffers::WIPOffset<flatbuffers::Vector<'a, u32>>>,
+    pub dims64: Option<flatbuffers::WIPOffset<flatbuffers::Vector<'a, u64>>>,
+    pub data_types: Option<flatbuffers::WIPOffset<flatbuffers::Vector<'a, DataType>>>,
+    pub data_offsets: Option<flatbuffers::WIPOffset<flatbuffers::Vector<'a, u32>>>,
+    pub data_sizes: Option<flatbuffers::WIPOffset<flatbuffers::Vector<'a, u32>>>,
+    pub compressions: Option<flatbuffers::WIPOffset<flatbuffers::Vector<'a, Compression>>>,
+    pub stored_sizes: Option<flatbuffers::WIPOffset<flatbuffers::Vector<'a, u32>>>,
+    pub checksum_algorithm: ChecksumAlgorithm,
+    pub checksums: Option<flatbuffers::WIPOffset<flatbuffers::Vector<'a, u64>>>,
+}
+impl<'a> Default for TensorColumnsArgs<'a> {
+  #[inline]
+  fn default() -> Self {
+    TensorColumnsArgs {
+      ids: None,
+      name_ends: None,
+      names: None,
+      shape_ends: None,
+      dims: None,
+      dims64: None,
+      data_types: None,
+      data_offsets: None,
+      data_sizes: None,
+      compressions: None,
+      stored_sizes: None,
+      checksum_algorithm: ChecksumAlgorithm::None,
+      checksums: None,
+    }
+  }
+}
+
+pub struct TensorColumnsBuilder<'a: 'b, 'b, A: flatbuffers::Allocator + 'a> {
+  fbb_: &'b mut flatbuffers::FlatBufferBuilder<'a, A>,
+  start_: flatbuffers::WIPOffset<flatbuffers::TableUnfinishedWIPOffset>,
+}
+impl<'a: 'b, 'b, A: flatbuffers::Allocator + 'a> TensorColumnsBuilder<'a, 'b, A> {
+  #[inline]
+  pub fn add_ids(&mut self, ids: flatbuffers::WIPOffset<flatbuffers::Vector<'b , u64>>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(TensorColumns::VT_IDS, ids);
+  }
+  #[inline]
+  pub fn add_name_ends(&mut self, name_ends: flatbuffers::WIPOffset<flatbuffers::Vector<'b , u32>>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(TensorColumns::VT_NAME_ENDS, name_ends);
+  }
+  #[inline]
+  pub fn add_names(&mut self, names: flatbuffers::WIPOffset<flatbuffers::Vector<'b , u8>>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(TensorColumns::VT_NAMES, names);
+  }
+  #[inline]
+  pub fn add_shape_ends(&mut self, shape_ends: flatbuffers::WIPOffset<flatbuffers::Vector<'b , u32>>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(TensorColumns::VT_SHAPE_ENDS, shape_ends);
+  }
+  #[inline]
+  pub fn add_dims(&mut self, dims: flatbuffers::WIPOffset<flatbuffers::Vector<'b , u32>>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(TensorColumns::VT_DIMS, dims);
+  }
+  #[inline]
+  pub fn add_dims64(&mut self, dims64: flatbuffers::WIPOffset<flatbuffers::Vector<'b , u64>>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(TensorColumns::VT_DIMS64, dims64);
+  }
+  #[inline]
+  pub fn add_data_types(&mut self, data_types: flatbuffers::WIPOffset<flatbuffers::Vector<'b , DataType>>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(TensorColumns::VT_DATA_TYPES, data_types);
+  }
+  #[inline]
+  pub fn add_data_offsets(&mut self, data_offsets: flatbuffers::WIPOffset<flatbuffers::Vector<'b , u32>>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(TensorColumns::VT_DATA_OFFSETS, data_offsets);
+  }
+  #[inline]
+  pub fn add_data_sizes(&mut self, data_sizes: flatbuffers::WIPOffset<flatbuffers::Vector<'b , u32>>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(TensorColumns::VT_DATA_SIZES, data_sizes);
+  }
+  #[inline]
+  pub fn add_compressions(&mut self, compressions: flatbuffers::WIPOffset<flatbuffers::Vector<'b , Compression>>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(TensorColumns::VT_COMPRESSIONS, compressions);
+  }
+  #[inline]
+  pub fn add_stored_sizes(&mut self, stored_sizes: flatbuffers::WIPOffset<flatbuffers::Vector<'b , u32>>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(TensorColumns::VT_STORED_SIZES, stored_sizes);
+  }
+  #[inline]
+  pub fn add_checksum_algorithm(&mut self, checksum_algorithm: ChecksumAlgorithm) {
+    self.fbb_.push_slot::<ChecksumAlgorithm>(TensorColumns::VT_CHECKSUM_ALGORITHM, checksum_algorithm, ChecksumAlgorithm::None);
+  }
+  #[inline]
+  pub fn add_checksums(&mut self, checksums: flatbuffers::WIPOffset<flatbuffers::Vector<'b , u64>>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(TensorColumns::VT_CHECKSUMS, checksums);
+  }
+  #[inline]
+  pub fn new(_fbb: &'b mut flatbuffers::FlatBufferBuilder<'a, A>) -> TensorColumnsBuilder<'a, 'b, A> {
+    let start = _fbb.start_table();
+    TensorColumnsBuilder {
+      fbb_: _fbb,
+      start_: start,
+    }
+  }
+  #[inline]
+  pub fn finish(self) -> flatbuffers::WIPOffset<TensorColumns<'a>> {
+    let o = self.fbb_.end_table(self.start_);
+    flatbuffers::WIPOffset::new(o.value())
+  }
+}
+
+impl core::fmt::Debug for TensorColumns<'_> {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    let mut ds = f.debug_struct("TensorColumns");
+      ds.field("ids", &self.ids());
+      ds.field("name_ends", &self.name_ends());
+      ds.field("names", &self.names());
+      ds.field("shape_ends", &self.shape_ends());
+      ds.field("dims", &self.dims());
+      ds.field("dims64", &self.dims64());
+      ds.field("data_types", &self.data_types());
+      ds.field("data_offsets", &self.data_offsets());
+      ds.field("data_sizes", &self.data_sizes());
+      ds.field("compressions", &self.compressions());
+      ds.field("stored_sizes", &self.stored_sizes());
+      ds.field("checksum_algorithm", &self.checksum_algorithm());
+      ds.field("checksums", &self.checksums());
       ds.finish()
   }
 }
@@ -735,6 +2400,13 @@ impl<'a> TensorBuffersMetadata<'a> {
   pub const VT_MODEL: flatbuffers::VOffsetT = 6;
   pub const VT_TENSORS: flatbuffers::VOffsetT = 8;
   pub const VT_OPERATIONS: flatbuffers::VOffsetT = 10;
+  pub const VT_FEATURES: flatbuffers::VOffsetT = 12;
+  pub const VT_COMPRESSION_DICTIONARY: flatbuffers::VOffsetT = 14;
+  pub const VT_USER_METADATA: flatbuffers::VOffsetT = 16;
+  pub const VT_TENSOR_COLUMNS: flatbuffers::VOffsetT = 18;
+  pub const VT_MODEL_CARD: flatbuffers::VOffsetT = 20;
+  pub const VT_PROVENANCE: flatbuffers::VOffsetT = 22;
+  pub const VT_CONSTRAINTS: flatbuffers::VOffsetT = 24;
 
   #[inline]
   pub unsafe fn init_from_table(table: flatbuffers::Table<'a>) -> Self {
@@ -746,6 +2418,13 @@ impl<'a> TensorBuffersMetadata<'a> {
     args: &'args TensorBuffersMetadataArgs<'args>
   ) -> flatbuffers::WIPOffset<TensorBuffersMetadata<'bldr>> {
     let mut builder = TensorBuffersMetadataBuilder::new(_fbb);
+    if let Some(x) = args.constraints { builder.add_constraints(x); }
+    if let Some(x) = args.provenance { builder.add_provenance(x); }
+    if let Some(x) = args.model_card { builder.add_model_card(x); }
+    if let Some(x) = args.tensor_columns { builder.add_tensor_columns(x); }
+    if let Some(x) = args.user_metadata { builder.add_user_metadata(x); }
+    if let Some(x) = args.compression_dictionary { builder.add_compression_dictionary(x); }
+    if let Some(x) = args.features { builder.add_features(x); }
     if let Some(x) = args.operations { builder.add_operations(x); }
     if let Some(x) = args.tensors { builder.add_tensors(x); }
     if let Some(x) = args.model { builder.add_model(x); }
@@ -782,6 +2461,55 @@ impl<'a> TensorBuffersMetadata<'a> {
     // which contains a valid value in this slot
     unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<OperationMetadata>>>>(TensorBuffersMetadata::VT_OPERATIONS, None)}
   }
+  #[inline]
+  pub fn features(&self) -> Option<flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<&'a str>>> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<&'a str>>>>(TensorBuffersMetadata::VT_FEATURES, None)}
+  }
+  #[inline]
+  pub fn compression_dictionary(&self) -> Option<flatbuffers::Vector<'a, u8>> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'a, u8>>>(TensorBuffersMetadata::VT_COMPRESSION_DICTIONARY, None)}
+  }
+  #[inline]
+  pub fn user_metadata(&self) -> Option<flatbuffers::Vector<'a, u8>> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'a, u8>>>(TensorBuffersMetadata::VT_USER_METADATA, None)}
+  }
+  #[inline]
+  pub fn tensor_columns(&self) -> Option<TensorColumns<'a>> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<TensorColumns>>(TensorBuffersMetadata::VT_TENSOR_COLUMNS, None)}
+  }
+  #[inline]
+  pub fn model_card(&self) -> Option<&'a str> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<&str>>(TensorBuffersMetadata::VT_MODEL_CARD, None)}
+  }
+  #[inline]
+  pub fn provenance(&self) -> Option<ProvenanceMetadata<'a>> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<ProvenanceMetadata>>(TensorBuffersMetadata::VT_PROVENANCE, None)}
+  }
+  #[inline]
+  pub fn constraints(&self) -> Option<flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<ConstraintMetadata<'a>>>> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<ConstraintMetadata>>>>(TensorBuffersMetadata::VT_CONSTRAINTS, None)}
+  }
 }
 
 impl flatbuffers::Verifiable for TensorBuffersMetadata<'_> {
@@ -795,6 +2523,13 @@ impl flatbuffers::Verifiable for TensorBuffersMetadata<'_> {
      .visit_field::<flatbuffers::ForwardsUOffset<&str>>("model", Self::VT_MODEL, false)?
      .visit_field::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'_, flatbuffers::ForwardsUOffset<TensorMetadata>>>>("tensors", Self::VT_TENSORS, false)?
      .visit_field::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'_, flatbuffers::ForwardsUOffset<OperationMetadata>>>>("operations", Self::VT_OPERATIONS, false)?
+     .visit_field::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'_, flatbuffers::ForwardsUOffset<&'_ str>>>>("features", Self::VT_FEATURES, false)?
+     .visit_field::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'_, u8>>>("compression_dictionary", Self::VT_COMPRESSION_DICTIONARY, false)?
+     .visit_field::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'_, u8>>>("user_metadata", Self::VT_USER_METADATA, false)?
+     .visit_field::<flatbuffers::ForwardsUOffset<TensorColumns>>("tensor_columns", Self::VT_TENSOR_COLUMNS, false)?
+     .visit_field::<flatbuffers::ForwardsUOffset<&str>>("model_card", Self::VT_MODEL_CARD, false)?
+     .visit_field::<flatbuffers::ForwardsUOffset<ProvenanceMetadata>>("provenance", Self::VT_PROVENANCE, false)?
+     .visit_field::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'_, flatbuffers::ForwardsUOffset<ConstraintMetadata>>>>("constraints", Self::VT_CONSTRAINTS, false)?
      .finish();
     Ok(())
   }
@@ -804,6 +2539,13 @@ pub struct TensorBuffersMetadataArgs<'a> {
     pub model: Option<flatbuffers::WIPOffset<&'a str>>,
     pub tensors: Option<flatbuffers::WIPOffset<flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<TensorMetadata<'a>>>>>,
     pub operations: Option<flatbuffers::WIPOffset<flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<OperationMetadata<'a>>>>>,
+    pub features: Option<flatbuffers::WIPOffset<flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<&'a str>>>>,
+    pub compression_dictionary: Option<flatbuffers::WIPOffset<flatbuffers::Vector<'a, u8>>>,
+    pub user_metadata: Option<flatbuffers::WIPOffset<flatbuffers::Vector<'a, u8>>>,
+    pub tensor_columns: Option<flatbuffers::WIPOffset<TensorColumns<'a>>>,
+    pub model_card: Option<flatbuffers::WIPOffset<&'a str>>,
+    pub provenance: Option<flatbuffers::WIPOffset<ProvenanceMetadata<'a>>>,
+    pub constraints: Option<flatbuffers::WIPOffset<flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<ConstraintMetadata<'a>>>>>,
 }
 impl<'a> Default for TensorBuffersMetadataArgs<'a> {
   #[inline]
@@ -813,6 +2555,13 @@ impl<'a> Default for TensorBuffersMetadataArgs<'a> {
       model: None,
       tensors: None,
       operations: None,
+      features: None,
+      compression_dictionary: None,
+      user_metadata: None,
+      tensor_columns: None,
+      model_card: None,
+      provenance: None,
+      constraints: None,
     }
   }
 }
@@ -839,6 +2588,34 @@ impl<'a: 'b, 'b, A: flatbuffers::Allocator + 'a> TensorBuffersMetadataBuilder<'a
     self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(TensorBuffersMetadata::VT_OPERATIONS, operations);
   }
   #[inline]
+  pub fn add_features(&mut self, features: flatbuffers::WIPOffset<flatbuffers::Vector<'b , flatbuffers::ForwardsUOffset<&'b  str>>>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(TensorBuffersMetadata::VT_FEATURES, features);
+  }
+  #[inline]
+  pub fn add_compression_dictionary(&mut self, compression_dictionary: flatbuffers::WIPOffset<flatbuffers::Vector<'b , u8>>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(TensorBuffersMetadata::VT_COMPRESSION_DICTIONARY, compression_dictionary);
+  }
+  #[inline]
+  pub fn add_user_metadata(&mut self, user_metadata: flatbuffers::WIPOffset<flatbuffers::Vector<'b , u8>>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(TensorBuffersMetadata::VT_USER_METADATA, user_metadata);
+  }
+  #[inline]
+  pub fn add_tensor_columns(&mut self, tensor_columns: flatbuffers::WIPOffset<TensorColumns<'b >>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<TensorColumns>>(TensorBuffersMetadata::VT_TENSOR_COLUMNS, tensor_columns);
+  }
+  #[inline]
+  pub fn add_model_card(&mut self, model_card: flatbuffers::WIPOffset<&'b  str>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(TensorBuffersMetadata::VT_MODEL_CARD, model_card);
+  }
+  #[inline]
+  pub fn add_provenance(&mut self, provenance: flatbuffers::WIPOffset<ProvenanceMetadata<'b >>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<ProvenanceMetadata>>(TensorBuffersMetadata::VT_PROVENANCE, provenance);
+  }
+  #[inline]
+  pub fn add_constraints(&mut self, constraints: flatbuffers::WIPOffset<flatbuffers::Vector<'b , flatbuffers::ForwardsUOffset<ConstraintMetadata<'b >>>>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(TensorBuffersMetadata::VT_CONSTRAINTS, constraints);
+  }
+  #[inline]
   pub fn new(_fbb: &'b mut flatbuffers::FlatBufferBuilder<'a, A>) -> TensorBuffersMetadataBuilder<'a, 'b, A> {
     let start = _fbb.start_table();
     TensorBuffersMetadataBuilder {
@@ -861,6 +2638,13 @@ impl core::fmt::Debug for TensorBuffersMetadata<'_> {
       ds.field("model", &self.model());
       ds.field("tensors", &self.tensors());
       ds.field("operations", &self.operations());
+      ds.field("features", &self.features());
+      ds.field("compression_dictionary", &self.compression_dictionary());
+      ds.field("user_metadata", &self.user_metadata());
+      ds.field("tensor_columns", &self.tensor_columns());
+      ds.field("model_card", &self.model_card());
+      ds.field("provenance", &self.provenance());
+      ds.field("constraints", &self.constraints());
       ds.finish()
   }
 }
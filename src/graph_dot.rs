@@ -0,0 +1,127 @@
+use std::fmt::Write;
+
+use fnv::FnvHashMap;
+
+use crate::{InferredShape, TensorGraph, TensorId, TensorOperationId};
+#[cfg(feature = "async")]
+use crate::{Result, TensorBuffers};
+
+fn escape(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Renders the operation graph in GraphViz DOT format.
+///
+/// Every operation becomes a node labelled with its name (when set), operation type, id and
+/// output tensor, and every input becomes an edge from the producing operation.
+///
+/// # Arguments
+/// * `graph` - The operation graph to render.
+/// * `tensor_names` - Names of known tensors. Tensors without a name are shown by id.
+/// * `shapes` - Inferred operation outputs. Operations without an entry are shown without a shape.
+pub fn graph_to_dot(
+    graph: &TensorGraph,
+    tensor_names: &FnvHashMap<TensorId, String>,
+    shapes: &[InferredShape],
+) -> String {
+    let shapes: FnvHashMap<TensorOperationId, &[usize]> =
+        shapes.iter().map(|shape| (shape.operation_id(), shape.shape())).collect();
+
+    let mut dot = String::from("digraph tensorbuffers {\n    node [shape=box];\n");
+    for op in graph.operations() {
+        let output = *op.output();
+        let mut tensor = match tensor_names.get(&output) {
+            Some(name) => name.clone(),
+            None => format!("tensor {}", output),
+        };
+        if let Some(shape) = shapes.get(&op.id()) {
+            write!(tensor, " {:?}", shape).unwrap();
+        }
+        let operation = match op.name() {
+            Some(name) => format!("{} ({:?} #{})", name, op.operation(), op.id()),
+            None => format!("{:?} #{}", op.operation(), op.id()),
+        };
+        let label = format!("{}\\n{}", escape(&operation), escape(&tensor));
+        writeln!(dot, "    op{} [label=\"{}\"];", op.id(), label).unwrap();
+    }
+    for op in graph.operations() {
+        for input_id in op.input_operations() {
+            writeln!(dot, "    op{} -> op{};", input_id, op.id()).unwrap();
+        }
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+#[cfg(feature = "async")]
+impl TensorBuffers {
+    /// Renders the stored operation graph in GraphViz DOT format, labelling stored tensors by
+    /// name. Shapes are included when inference succeeds for the whole graph.
+    pub async fn graph_to_dot(&self) -> Result<String> {
+        let graph = self.graph().await?;
+        let tensor_names = self.graph_tensor_names(&graph).await;
+        let shapes = self.infer_shapes().await.unwrap_or_default();
+        Ok(graph_to_dot(&graph, &tensor_names, &shapes))
+    }
+
+    /// Returns the names of the stored tensors the operations of `graph` output.
+    pub(crate) async fn graph_tensor_names(
+        &self,
+        graph: &TensorGraph,
+    ) -> FnvHashMap<TensorId, String> {
+        let mut tensor_names = FnvHashMap::default();
+        for op in graph.operations() {
+            if let Ok(Some(name)) = self.tensor_name(*op.output()).await {
+                tensor_names.insert(*op.output(), name.to_string());
+            }
+        }
+        tensor_names
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "async")]
+    use tempfile::NamedTempFile;
+    #[cfg(feature = "async")]
+    use tokio::fs::File;
+
+    use super::*;
+    #[cfg(feature = "async")]
+    use crate::{utils::hash_key, Tensor, TensorBuffersWrite, TensorBuffersWriter};
+    use crate::{Operation, TensorOperation};
+
+    #[test]
+    fn test_graph_to_dot() {
+        let graph = TensorGraph::new(vec![
+            TensorOperation::new(1, Operation::None, vec![], 10),
+            TensorOperation::new(2, Operation::Exp, vec![1], 20).with_name("act"),
+        ]);
+        let mut tensor_names = FnvHashMap::default();
+        tensor_names.insert(10, "x \"in\"".to_string());
+        let dot = graph_to_dot(&graph, &tensor_names, &[]);
+        assert_eq!(
+            dot,
+            "digraph tensorbuffers {\n    node [shape=box];\n    op1 [label=\"None #1\\nx \\\"in\\\"\"];\n    op2 [label=\"act (Exp #2)\\ntensor 20\"];\n    op1 -> op2;\n}\n"
+        );
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn test_tensor_buffers_graph_to_dot() {
+        let x = Tensor::new("x", &[1.0f32, 2.0, 3.0, 4.0], vec![2, 2]);
+        let operations = vec![
+            TensorOperation::new(1, Operation::None, vec![], x.id()),
+            TensorOperation::new(2, Operation::Transpose, vec![1], hash_key("y")),
+        ];
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        TensorBuffersWriter::new(&mut file).write(vec![x], operations).await.unwrap();
+
+        let url = format!("file://{}", tmp.path().display());
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        let dot = tensor_buffers.graph_to_dot().await.unwrap();
+        assert!(dot.contains("op1 [label=\"None #1\\nx [2, 2]\"];"));
+        assert!(dot.contains("op1 -> op2;"));
+    }
+}
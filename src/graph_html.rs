@@ -0,0 +1,288 @@
+use std::fmt::Write;
+
+use fnv::FnvHashMap;
+
+use crate::{InferredShape, TensorGraph, TensorId, TensorOperationId};
+#[cfg(feature = "async")]
+use crate::{Result, TensorBuffers};
+
+/// The page `graph_to_html` fills in. It lays the operations out in rows by their depth in the
+/// graph and draws them as SVG, with no scripts or styles fetched from elsewhere.
+const HTML_TEMPLATE: &str = r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>TensorBuffers graph</title>
+<style>
+  body { font-family: system-ui, sans-serif; margin: 0; display: flex; height: 100vh; }
+  #canvas { flex: 1; overflow: auto; }
+  #details { width: 320px; padding: 12px; border-left: 1px solid #ccc; overflow: auto; }
+  #details h2 { font-size: 16px; margin-top: 0; }
+  #details td { padding: 2px 8px 2px 0; vertical-align: top; font-size: 13px; }
+  .node rect { fill: #eef3fb; stroke: #4a6fa5; rx: 4; }
+  .node.source rect { fill: #f3f3f3; stroke: #888; }
+  .node.selected rect { stroke: #d9480f; stroke-width: 2; }
+  .node text { font-size: 12px; pointer-events: none; }
+  .node { cursor: pointer; }
+  .edge { stroke: #999; fill: none; marker-end: url(#arrow); }
+</style>
+</head>
+<body>
+<div id="canvas"><svg id="graph" xmlns="http://www.w3.org/2000/svg">
+<defs><marker id="arrow" viewBox="0 0 10 10" refX="10" refY="5" markerWidth="6" markerHeight="6" orient="auto"><path d="M0,0 L10,5 L0,10 z" fill="#999"/></marker></defs>
+</svg></div>
+<div id="details"><h2>TensorBuffers graph</h2><p id="summary"></p><p>Click an operation for its details.</p></div>
+<script>
+const graph = __GRAPH__;
+const WIDTH = 200, HEIGHT = 44, GAP_X = 40, GAP_Y = 60;
+
+function formatBytes(bytes) {
+  const units = ["B", "KiB", "MiB", "GiB", "TiB"];
+  let unit = 0;
+  while (bytes >= 1024 && unit < units.length - 1) { bytes /= 1024; unit++; }
+  return (unit ? bytes.toFixed(1) : bytes) + " " + units[unit];
+}
+
+// The depth of an operation is one more than that of its deepest input.
+const byId = new Map(graph.nodes.map(node => [node.id, node]));
+const depths = new Map();
+function depth(node, visiting = new Set()) {
+  if (depths.has(node.id)) return depths.get(node.id);
+  visiting.add(node.id);
+  let result = 0;
+  for (const input of node.inputs) {
+    const producer = byId.get(input);
+    if (producer && !visiting.has(input)) result = Math.max(result, depth(producer, visiting) + 1);
+  }
+  visiting.delete(node.id);
+  depths.set(node.id, result);
+  return result;
+}
+const rows = [];
+for (const node of graph.nodes) (rows[depth(node)] ||= []).push(node);
+
+const positions = new Map();
+const columns = Math.max(1, ...rows.map(row => row.length));
+rows.forEach((row, y) => row.forEach((node, x) => {
+  const offset = (columns - row.length) * (WIDTH + GAP_X) / 2;
+  positions.set(node.id, { x: GAP_X + offset + x * (WIDTH + GAP_X), y: GAP_Y / 2 + y * (HEIGHT + GAP_Y) });
+}));
+
+const svg = document.getElementById("graph");
+const NS = "http://www.w3.org/2000/svg";
+svg.setAttribute("width", GAP_X + columns * (WIDTH + GAP_X));
+svg.setAttribute("height", GAP_Y + rows.length * (HEIGHT + GAP_Y));
+for (const node of graph.nodes) {
+  const to = positions.get(node.id);
+  for (const input of node.inputs) {
+    const from = positions.get(input);
+    if (!from) continue;
+    const path = document.createElementNS(NS, "path");
+    const [x1, y1, x2, y2] = [from.x + WIDTH / 2, from.y + HEIGHT, to.x + WIDTH / 2, to.y];
+    path.setAttribute("d", `M${x1},${y1} C${x1},${(y1 + y2) / 2} ${x2},${(y1 + y2) / 2} ${x2},${y2}`);
+    path.setAttribute("class", "edge");
+    svg.appendChild(path);
+  }
+}
+
+function show(node, group) {
+  document.querySelectorAll(".node.selected").forEach(other => other.classList.remove("selected"));
+  group.classList.add("selected");
+  const rows = [
+    ["Operation", node.operation + " #" + node.id],
+    ["Name", node.name],
+    ["Output", node.tensor],
+    ["Shape", node.shape && "[" + node.shape.join(", ") + "]"],
+    ["Data type", node.data_type],
+    ["Size", node.bytes != null && formatBytes(node.bytes)],
+    ["Latency", node.latency_ns != null && (node.latency_ns / 1e6).toFixed(3) + " ms"],
+    ["Inputs", node.inputs.map(id => byId.has(id) ? byId.get(id).tensor : "#" + id).join(", ")],
+  ];
+  const table = document.createElement("table");
+  for (const [label, value] of rows.filter(([, value]) => value)) {
+    const row = table.insertRow();
+    row.insertCell().textContent = label;
+    row.insertCell().textContent = value;
+  }
+  const details = document.getElementById("details");
+  details.replaceChildren(details.firstChild, document.getElementById("summary"), table);
+}
+
+for (const node of graph.nodes) {
+  const { x, y } = positions.get(node.id);
+  const group = document.createElementNS(NS, "g");
+  group.setAttribute("class", node.operation === "None" ? "node source" : "node");
+  group.setAttribute("transform", `translate(${x},${y})`);
+  const rect = document.createElementNS(NS, "rect");
+  rect.setAttribute("width", WIDTH);
+  rect.setAttribute("height", HEIGHT);
+  group.appendChild(rect);
+  const lines = [
+    node.name ? `${node.name} (${node.operation})` : node.operation,
+    node.tensor + (node.shape ? " [" + node.shape.join(", ") + "]" : ""),
+  ];
+  lines.forEach((line, i) => {
+    const text = document.createElementNS(NS, "text");
+    text.setAttribute("x", 8);
+    text.setAttribute("y", 18 + i * 16);
+    text.textContent = line.length > 30 ? line.slice(0, 29) + "…" : line;
+    group.appendChild(text);
+  });
+  const title = document.createElementNS(NS, "title");
+  title.textContent = lines.join("\n");
+  group.appendChild(title);
+  group.addEventListener("click", () => show(node, group));
+  svg.appendChild(group);
+}
+
+const bytes = graph.nodes.reduce((total, node) => total + (node.bytes || 0), 0);
+document.getElementById("summary").textContent =
+  `${graph.nodes.length} operations, ${formatBytes(bytes)} of outputs with known shapes`;
+</script>
+</body>
+</html>
+"##;
+
+/// Writes `value` as a JSON string. `<`, `>` and `&` are escaped too, so names can't end the
+/// script the data is embedded in.
+fn write_json_string(json: &mut String, value: &str) {
+    json.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '<' | '>' | '&' => write!(json, "\\u{:04x}", c as u32).unwrap(),
+            c if c.is_control() => write!(json, "\\u{:04x}", c as u32).unwrap(),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+}
+
+/// Renders the operation graph as a self-contained HTML page that draws it in a browser, for
+/// sharing model-structure reviews without extra tooling. Operations are laid out by depth, and
+/// clicking one shows its output's shape, data type and size, and its measured latency if
+/// profiled.
+///
+/// # Arguments
+/// * `graph` - The operation graph to render.
+/// * `tensor_names` - Names of known tensors. Tensors without a name are shown by id.
+/// * `shapes` - Inferred operation outputs. Operations without an entry are shown without a shape
+///   or size.
+pub fn graph_to_html(
+    graph: &TensorGraph,
+    tensor_names: &FnvHashMap<TensorId, String>,
+    shapes: &[InferredShape],
+) -> String {
+    let shapes: FnvHashMap<TensorOperationId, &InferredShape> =
+        shapes.iter().map(|shape| (shape.operation_id(), shape)).collect();
+
+    let mut json = String::from("{\"nodes\":[");
+    for (i, op) in graph.operations().iter().enumerate() {
+        if i > 0 {
+            json.push(',');
+        }
+        write!(json, "{{\"id\":{},\"operation\":", op.id()).unwrap();
+        write_json_string(&mut json, &format!("{:?}", op.operation()));
+        if let Some(name) = op.name() {
+            json.push_str(",\"name\":");
+            write_json_string(&mut json, name);
+        }
+        json.push_str(",\"tensor\":");
+        match tensor_names.get(op.output()) {
+            Some(name) => write_json_string(&mut json, name),
+            None => write_json_string(&mut json, &format!("tensor {}", op.output())),
+        }
+        if let Some(shape) = shapes.get(&op.id()) {
+            write!(json, ",\"shape\":{:?},\"data_type\":", shape.shape()).unwrap();
+            write_json_string(&mut json, &format!("{:?}", shape.data_type()));
+            let elements = shape.shape().iter().map(|&dim| dim as u64).product();
+            if let Some(bytes) = shape.data_type().data_size(elements) {
+                write!(json, ",\"bytes\":{}", bytes).unwrap();
+            }
+        }
+        if let Some(latency) = op.profile().and_then(|profile| profile.latency) {
+            write!(json, ",\"latency_ns\":{}", latency.as_nanos()).unwrap();
+        }
+        write!(json, ",\"inputs\":{:?}}}", op.input_operations()).unwrap();
+    }
+    json.push_str("]}");
+    HTML_TEMPLATE.replace("__GRAPH__", &json)
+}
+
+#[cfg(feature = "async")]
+impl TensorBuffers {
+    /// Writes the stored operation graph to `path` as a self-contained HTML page, see
+    /// `graph_to_html`, labelling stored tensors by name. Shapes and sizes are included when
+    /// inference succeeds for the whole graph.
+    pub async fn export_graph_html(&self, path: impl AsRef<std::path::Path>) -> Result<()> {
+        let graph = self.graph().await?;
+        let tensor_names = self.graph_tensor_names(&graph).await;
+        let shapes = self.infer_shapes().await.unwrap_or_default();
+        tokio::fs::write(path, graph_to_html(&graph, &tensor_names, &shapes)).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    #[cfg(feature = "async")]
+    use tempfile::NamedTempFile;
+    #[cfg(feature = "async")]
+    use tokio::fs::File;
+
+    use super::*;
+    #[cfg(feature = "async")]
+    use crate::{utils::hash_key, Tensor, TensorBuffersWrite, TensorBuffersWriter};
+    use crate::{Operation, OperationProfile, TensorOperation};
+
+    #[test]
+    fn test_graph_to_html() {
+        let profile = OperationProfile::new().with_latency(Duration::from_micros(5));
+        let graph = TensorGraph::new(vec![
+            TensorOperation::new(1, Operation::None, vec![], 10),
+            TensorOperation::new(2, Operation::Exp, vec![1], 20)
+                .with_name("act")
+                .with_profile(profile),
+        ]);
+        let mut tensor_names = FnvHashMap::default();
+        tensor_names.insert(10, "x</script>\"".to_string());
+        let html = graph_to_html(&graph, &tensor_names, &[]);
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains(
+            "const graph = {\"nodes\":[{\"id\":1,\"operation\":\"None\",\"tensor\":\
+             \"x\\u003c/script\\u003e\\\"\",\"inputs\":[]},{\"id\":2,\"operation\":\"Exp\",\
+             \"name\":\"act\",\"tensor\":\"tensor 20\",\"latency_ns\":5000,\"inputs\":[1]}]};"
+        ));
+        // Everything the page needs is in it.
+        assert!(!html.contains("<script src") && !html.contains("<link"));
+        assert_eq!(html.matches("</script>").count(), 1);
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn test_export_graph_html() {
+        let x = Tensor::new("x", &[1.0f32, 2.0, 3.0, 4.0, 5.0, 6.0], vec![2, 3]);
+        let operations = vec![
+            TensorOperation::new(1, Operation::None, vec![], x.id()),
+            TensorOperation::new(2, Operation::Transpose, vec![1], hash_key("y")),
+        ];
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        TensorBuffersWriter::new(&mut file).write(vec![x], operations).await.unwrap();
+
+        let url = format!("file://{}", tmp.path().display());
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        let page = NamedTempFile::new().unwrap();
+        tensor_buffers.export_graph_html(page.path()).await.unwrap();
+        let html = std::fs::read_to_string(page.path()).unwrap();
+        assert!(html.contains(
+            "{\"id\":1,\"operation\":\"None\",\"tensor\":\"x\",\"shape\":[2, 3],\
+             \"data_type\":\"Float32\",\"bytes\":24,\"inputs\":[]}"
+        ));
+        assert!(html.contains("\"tensor\":\"tensor "));
+        assert!(html.contains("\"shape\":[3, 2]"));
+    }
+}
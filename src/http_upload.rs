@@ -0,0 +1,252 @@
+use std::{
+    future::Future,
+    io::{Error, ErrorKind, Result},
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use bytes::{Bytes, BytesMut};
+use futures::channel::mpsc;
+use tokio::{io::AsyncWrite, task::JoinHandle};
+use tracing::debug;
+
+use crate::{utils::loggable_url, TensorBuffersSink, WriterOptions};
+
+/// Size of the chunks the request body is sent in.
+const UPLOAD_CHUNK_SIZE: usize = 1 << 20;
+
+/// Most chunks queued for the request at once. Writes wait while the queue is full, so a slow
+/// upload holds back the writer instead of buffering the file.
+const MAX_QUEUED_CHUNKS: usize = 4;
+
+/// Uploads what is written to it as the body of one HTTP PUT request with chunked transfer
+/// encoding, e.g. to a presigned object storage URL, without staging the file locally.
+///
+/// The request starts when the upload is created and its body is streamed in 1 MiB chunks as
+/// bytes are written. Shutting the writer down ends the body and waits for the response, failing
+/// unless the server accepted the upload. Dropping it before then aborts the request, so the server
+/// doesn't store a truncated file. Must be created within a Tokio runtime.
+pub struct HttpUpload {
+    url: String,
+    sender: Option<mpsc::Sender<Result<Bytes>>>,
+    buffer: BytesMut,
+    request: Option<JoinHandle<reqwest::Result<reqwest::Response>>>,
+    uploaded: u64,
+}
+
+impl HttpUpload {
+    /// Starts uploading to `url` with `client`, which can carry authentication headers.
+    pub fn new(client: &reqwest::Client, url: &str) -> Self {
+        let (sender, receiver) = mpsc::channel(MAX_QUEUED_CHUNKS);
+        let request = client
+            .put(url)
+            .header(reqwest::header::CONTENT_TYPE, "application/octet-stream")
+            .body(reqwest::Body::wrap_stream(receiver))
+            .send();
+        HttpUpload {
+            url: url.to_string(),
+            sender: Some(sender),
+            buffer: BytesMut::with_capacity(UPLOAD_CHUNK_SIZE),
+            request: Some(tokio::spawn(request)),
+            uploaded: 0,
+        }
+    }
+
+    /// Returns the number of bytes handed to the request so far.
+    pub fn uploaded(&self) -> u64 {
+        self.uploaded
+    }
+
+    /// Hands the buffered bytes to the request once it has room for them.
+    fn poll_send_buffer(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        if self.buffer.is_empty() {
+            return Poll::Ready(Ok(()));
+        }
+        let ended =
+            |_| Error::new(ErrorKind::BrokenPipe, "The upload ended before all data was sent");
+        let sender = self
+            .sender
+            .as_mut()
+            .ok_or_else(|| Error::new(ErrorKind::BrokenPipe, "The upload is finished"))?;
+        ready!(sender.poll_ready(cx)).map_err(ended)?;
+        let chunk = self.buffer.split().freeze();
+        self.uploaded += chunk.len() as u64;
+        sender.start_send(Ok(chunk)).map_err(ended)?;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for HttpUpload {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<Result<usize>> {
+        let this = self.get_mut();
+        if this.buffer.len() >= UPLOAD_CHUNK_SIZE {
+            ready!(this.poll_send_buffer(cx))?;
+        }
+        let len = buf.len().min(UPLOAD_CHUNK_SIZE - this.buffer.len());
+        this.buffer.extend_from_slice(&buf[..len]);
+        Poll::Ready(Ok(len))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut().poll_send_buffer(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_send_buffer(cx))?;
+        // Dropping the sender ends the body.
+        this.sender = None;
+        let Some(request) = this.request.as_mut() else {
+            return Poll::Ready(Ok(()));
+        };
+        let response = ready!(Pin::new(request).poll(cx));
+        this.request = None;
+        let response = response
+            .map_err(Error::other)?
+            .map_err(|error| Error::other(format!("Upload failed: {}", error)))?;
+        if !response.status().is_success() {
+            return Poll::Ready(Err(Error::other(format!(
+                "Upload failed with status {}",
+                response.status()
+            ))));
+        }
+        debug!(url = loggable_url(&this.url), bytes = this.uploaded, "Finished upload");
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Drop for HttpUpload {
+    fn drop(&mut self) {
+        let Some(request) = self.request.take() else {
+            return;
+        };
+        // Failing the body makes the client abort the request instead of ending the body as if
+        // the upload were complete. A clone of the sender has a slot of its own, so the error is
+        // queued even when the body is full.
+        if let Some(sender) = &self.sender {
+            let aborted = Error::new(ErrorKind::Interrupted, "The upload was dropped");
+            let _ = sender.clone().try_send(Err(aborted));
+        }
+        request.abort();
+        debug!(url = loggable_url(&self.url), bytes = self.uploaded, "Aborted upload");
+    }
+}
+
+impl TensorBuffersSink<HttpUpload> {
+    /// Creates a sink that uploads the file to `url` as tensors arrive, see [`HttpUpload`], so
+    /// export pipelines can publish straight to a bucket. The upload succeeds once the sink is
+    /// closed.
+    ///
+    /// # Arguments
+    /// * `client` - The client to send the request with.
+    /// * `url` - Where to upload the file.
+    /// * `options` - The write settings, as for [`TensorBuffersSink::new`].
+    pub fn upload(
+        client: &reqwest::Client,
+        url: &str,
+        options: WriterOptions,
+    ) -> crate::Result<Self> {
+        TensorBuffersSink::new(HttpUpload::new(client, url), options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{stream, SinkExt, StreamExt};
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+        net::TcpListener,
+    };
+
+    use super::*;
+    use crate::{Tensor, TensorAny, TensorBuffers};
+
+    /// Accepts one PUT request with a chunked body, answers with `status` and returns the body.
+    async fn receive(listener: TcpListener, status: &str) -> Vec<u8> {
+        try_receive(listener, status).await.expect("The request body didn't end")
+    }
+
+    /// Like `receive`, but returns `None` if the connection closes before the body ends, which
+    /// servers treat as a failed upload.
+    async fn try_receive(listener: TcpListener, status: &str) -> Option<Vec<u8>> {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut stream = BufReader::new(stream);
+        let mut line = String::new();
+        loop {
+            line.clear();
+            stream.read_line(&mut line).await.unwrap();
+            if line == "\r\n" {
+                break;
+            }
+        }
+        let mut body = Vec::new();
+        loop {
+            line.clear();
+            if stream.read_line(&mut line).await.ok()? == 0 {
+                return None;
+            }
+            let size = usize::from_str_radix(line.trim(), 16).ok()?;
+            let mut chunk = vec![0; size + 2];
+            stream.read_exact(&mut chunk).await.ok()?;
+            if size == 0 {
+                break;
+            }
+            body.extend_from_slice(&chunk[..size]);
+        }
+        let response = format!("HTTP/1.1 {}\r\nContent-Length: 0\r\n\r\n", status);
+        stream.get_mut().write_all(response.as_bytes()).await.unwrap();
+        Some(body)
+    }
+
+    #[tokio::test]
+    async fn test_upload_sink() {
+        let tensors = || {
+            vec![
+                TensorAny::from(Tensor::from_vec("w", vec![0.5f32; 600_000], vec![600, 1000])),
+                TensorAny::from(Tensor::from_vec("ids", vec![1i64, 2, 3], vec![3])),
+            ]
+        };
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/bucket/model.tb", listener.local_addr().unwrap());
+        let server = tokio::spawn(receive(listener, "200 OK"));
+        let client = reqwest::Client::new();
+        let mut sink = TensorBuffersSink::upload(&client, &url, WriterOptions::new()).unwrap();
+        stream::iter(tensors()).map(Ok).forward(&mut sink).await.unwrap();
+        let uploaded = server.await.unwrap();
+        assert_eq!(sink.into_inner().uploaded(), uploaded.len() as u64);
+
+        // The upload holds the same bytes as a file written locally.
+        let tmp = tempfile::NamedTempFile::new().unwrap();
+        let file = tokio::fs::File::create(tmp.path()).await.unwrap();
+        let mut local = TensorBuffersSink::new(file, WriterOptions::new()).unwrap();
+        stream::iter(tensors()).map(Ok).forward(&mut local).await.unwrap();
+        assert_eq!(std::fs::read(tmp.path()).unwrap(), uploaded);
+        let url = format!("file://{}", tmp.path().display());
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        let ids = tensor_buffers.get_tensor_data_by_name::<i64>("ids").await.unwrap();
+        assert_eq!(ids.data(), [1, 2, 3]);
+
+        // A rejected upload fails closing the sink.
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/bucket/model.tb", listener.local_addr().unwrap());
+        let server = tokio::spawn(receive(listener, "403 Forbidden"));
+        let mut sink = TensorBuffersSink::upload(&client, &url, WriterOptions::new()).unwrap();
+        let result = stream::iter(tensors()).map(Ok).forward(&mut sink).await;
+        server.await.unwrap();
+        assert!(result.unwrap_err().to_string().contains("403"));
+        assert!(sink.close().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_dropped_upload_is_aborted() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/bucket/model.tb", listener.local_addr().unwrap());
+        let server = tokio::spawn(try_receive(listener, "200 OK"));
+        let mut upload = HttpUpload::new(&reqwest::Client::new(), &url);
+        // More than the queue holds, so the body is being sent when the upload is dropped.
+        upload.write_all(&vec![7; 8 * UPLOAD_CHUNK_SIZE]).await.unwrap();
+        upload.flush().await.unwrap();
+        drop(upload);
+        assert!(server.await.unwrap().is_none());
+    }
+}
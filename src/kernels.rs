@@ -0,0 +1,308 @@
+use std::any::TypeId;
+
+use bytemuck::{cast_slice, cast_slice_mut, Pod};
+use rayon::prelude::*;
+
+#[cfg(feature = "async")]
+use crate::num_trait::Float;
+pub(crate) use crate::simd::ArithmeticOp;
+use crate::{simd, Num, Result};
+
+impl ArithmeticOp {
+    fn apply<T: Num>(self, a: T, b: T) -> T {
+        match self {
+            ArithmeticOp::Add => a + b,
+            ArithmeticOp::Sub => a - b,
+            ArithmeticOp::Mul => a * b,
+            ArithmeticOp::Div => a / b,
+        }
+    }
+}
+
+/// Element count above which element-wise kernels split their work across the rayon pool.
+const PARALLEL_CHUNK_LEN: usize = 1 << 15;
+
+fn is<T: Pod, U: Pod>() -> bool {
+    TypeId::of::<T>() == TypeId::of::<U>()
+}
+
+/// Computes `out[i] = lhs[i] op rhs[i]` on equally sized slices,
+/// splitting large inputs into chunks processed in parallel.
+fn arithmetic_slices<T: Num + Pod>(op: ArithmeticOp, lhs: &[T], rhs: &[T], out: &mut [T]) {
+    if out.len() <= PARALLEL_CHUNK_LEN {
+        return arithmetic_chunk(op, lhs, rhs, out);
+    }
+    out.par_chunks_mut(PARALLEL_CHUNK_LEN)
+        .zip(lhs.par_chunks(PARALLEL_CHUNK_LEN))
+        .zip(rhs.par_chunks(PARALLEL_CHUNK_LEN))
+        .for_each(|((out, lhs), rhs)| arithmetic_chunk(op, lhs, rhs, out));
+}
+
+/// Computes `out[i] = lhs[i] op rhs[i]` using the vectorized kernels for f32 and f64.
+fn arithmetic_chunk<T: Num + Pod>(op: ArithmeticOp, lhs: &[T], rhs: &[T], out: &mut [T]) {
+    if is::<T, f32>() {
+        simd::arithmetic_f32(op, cast_slice(lhs), cast_slice(rhs), cast_slice_mut(out));
+    } else if is::<T, f64>() {
+        simd::arithmetic_f64(op, cast_slice(lhs), cast_slice(rhs), cast_slice_mut(out));
+    } else {
+        for ((out, &a), &b) in out.iter_mut().zip(lhs).zip(rhs) {
+            *out = op.apply(a, b);
+        }
+    }
+}
+
+/// Computes `y[i] += a * x[i]`, using the vectorized kernels for f32 and f64.
+#[cfg(feature = "async")]
+fn axpy<T: Num + Pod>(a: T, x: &[T], y: &mut [T]) {
+    if is::<T, f32>() {
+        simd::axpy_f32(cast_slice::<T, f32>(&[a])[0], cast_slice(x), cast_slice_mut(y));
+    } else if is::<T, f64>() {
+        simd::axpy_f64(cast_slice::<T, f64>(&[a])[0], cast_slice(x), cast_slice_mut(y));
+    } else {
+        for (y, &x) in y.iter_mut().zip(x) {
+            *y = *y + a * x;
+        }
+    }
+}
+
+/// Applies `op` element-wise with the same broadcasting rules as `binary`.
+pub(crate) fn arithmetic<T: Num + Pod>(
+    op: ArithmeticOp,
+    lhs: &[T],
+    lhs_shape: &[usize],
+    rhs: &[T],
+    rhs_shape: &[usize],
+) -> Result<(Vec<T>, Vec<usize>)> {
+    if lhs_shape == rhs_shape {
+        let mut out = vec![T::zero(); lhs.len()];
+        arithmetic_slices(op, lhs, rhs, &mut out);
+        return Ok((out, lhs_shape.to_vec()));
+    }
+    if rhs.len() == 1 || lhs.len() == 1 {
+        return binary(lhs, lhs_shape, rhs, rhs_shape, |a, b| op.apply(a, b));
+    }
+    if !rhs.is_empty() && lhs_shape.ends_with(rhs_shape) {
+        let mut out = vec![T::zero(); lhs.len()];
+        for (lhs, out) in lhs.chunks(rhs.len()).zip(out.chunks_mut(rhs.len())) {
+            arithmetic_slices(op, lhs, rhs, out);
+        }
+        return Ok((out, lhs_shape.to_vec()));
+    }
+    if !lhs.is_empty() && rhs_shape.ends_with(lhs_shape) {
+        let mut out = vec![T::zero(); rhs.len()];
+        for (rhs, out) in rhs.chunks(lhs.len()).zip(out.chunks_mut(lhs.len())) {
+            arithmetic_slices(op, lhs, rhs, out);
+        }
+        return Ok((out, rhs_shape.to_vec()));
+    }
+    binary(lhs, lhs_shape, rhs, rhs_shape, |a, b| op.apply(a, b))
+}
+
+/// Applies `f` to every element of `input`, in parallel for large inputs.
+#[cfg(feature = "async")]
+pub(crate) fn unary<T, F>(input: &[T], f: F) -> Vec<T>
+where
+    T: Copy + Send + Sync,
+    F: Fn(T) -> T + Sync,
+{
+    if input.len() <= PARALLEL_CHUNK_LEN {
+        return input.iter().map(|&x| f(x)).collect();
+    }
+    input.par_iter().with_min_len(PARALLEL_CHUNK_LEN).map(|&x| f(x)).collect()
+}
+
+/// Applies `f` element-wise to two inputs with broadcasting.
+/// `lhs_shape`/`rhs_shape` must be equal, one operand must hold a single element,
+/// or the shorter shape must be a suffix of the longer one (e.g. a bias row added to a matrix).
+///
+/// # Returns
+/// Returns the output data together with its shape.
+pub(crate) fn binary<T, F>(
+    lhs: &[T],
+    lhs_shape: &[usize],
+    rhs: &[T],
+    rhs_shape: &[usize],
+    f: F,
+) -> Result<(Vec<T>, Vec<usize>)>
+where
+    T: Copy,
+    F: Fn(T, T) -> T,
+{
+    if lhs_shape == rhs_shape {
+        let data = lhs.iter().zip(rhs).map(|(&a, &b)| f(a, b)).collect();
+        return Ok((data, lhs_shape.to_vec()));
+    }
+    if rhs.len() == 1 {
+        return Ok((lhs.iter().map(|&a| f(a, rhs[0])).collect(), lhs_shape.to_vec()));
+    }
+    if lhs.len() == 1 {
+        return Ok((rhs.iter().map(|&b| f(lhs[0], b)).collect(), rhs_shape.to_vec()));
+    }
+    if lhs_shape.ends_with(rhs_shape) {
+        let data = lhs.iter().zip(rhs.iter().cycle()).map(|(&a, &b)| f(a, b)).collect();
+        return Ok((data, lhs_shape.to_vec()));
+    }
+    if rhs_shape.ends_with(lhs_shape) {
+        let data = lhs.iter().cycle().zip(rhs).map(|(&a, &b)| f(a, b)).collect();
+        return Ok((data, rhs_shape.to_vec()));
+    }
+    Err(format!("Shapes {:?} and {:?} cannot be broadcast together", lhs_shape, rhs_shape).into())
+}
+
+/// Returns the output shape of a broadcasting element-wise operation, following the same rules
+/// as `binary` and `arithmetic`.
+pub(crate) fn broadcast_shape(lhs_shape: &[usize], rhs_shape: &[usize]) -> Result<Vec<usize>> {
+    let elements = |shape: &[usize]| shape.iter().product::<usize>();
+    if lhs_shape == rhs_shape || elements(rhs_shape) == 1 {
+        Ok(lhs_shape.to_vec())
+    } else if elements(lhs_shape) == 1 {
+        Ok(rhs_shape.to_vec())
+    } else if lhs_shape.ends_with(rhs_shape) {
+        Ok(lhs_shape.to_vec())
+    } else if rhs_shape.ends_with(lhs_shape) {
+        Ok(rhs_shape.to_vec())
+    } else {
+        Err(format!("Shapes {:?} and {:?} cannot be broadcast together", lhs_shape, rhs_shape)
+            .into())
+    }
+}
+
+#[cfg(feature = "async")]
+pub(crate) fn sum<T: Num>(input: &[T]) -> T {
+    input.iter().fold(T::zero(), |acc, &x| acc + x)
+}
+
+/// Returns the index of the largest element, or `None` for empty input.
+#[cfg(feature = "async")]
+pub(crate) fn argmax<T: Num>(input: &[T]) -> Option<usize> {
+    let mut best: Option<(usize, T)> = None;
+    for (i, &x) in input.iter().enumerate() {
+        match best {
+            Some((_, max)) if x <= max => {}
+            _ => best = Some((i, x)),
+        }
+    }
+    best.map(|(i, _)| i)
+}
+
+/// Computes softmax independently over consecutive rows of `row_len` elements.
+#[cfg(feature = "async")]
+pub(crate) fn softmax<T: Float>(input: &[T], row_len: usize) -> Vec<T> {
+    let mut output = Vec::with_capacity(input.len());
+    for row in input.chunks(row_len.max(1)) {
+        // Subtract the row maximum for numerical stability.
+        let max = row.iter().fold(T::neg_infinity(), |acc, &x| if x > acc { x } else { acc });
+        let exps = row.iter().map(|&x| (x - max).exp()).collect::<Vec<_>>();
+        let total = sum(&exps);
+        output.extend(exps.into_iter().map(|x| x / total));
+    }
+    output
+}
+
+/// Multiplies a row-major `[m, k]` matrix by a row-major `[k, n]` matrix.
+/// Each output row accumulates scaled rows of `rhs`, so the inner loop is a vectorized axpy.
+/// Rows are computed in parallel once the product is large enough to amortize scheduling.
+#[cfg(feature = "async")]
+pub(crate) fn matmul<T: Num + Pod>(lhs: &[T], rhs: &[T], m: usize, k: usize, n: usize) -> Vec<T> {
+    let mut output = vec![T::zero(); m * n];
+    if n == 0 {
+        return output;
+    }
+    let row = |(i, out_row): (usize, &mut [T])| {
+        for p in 0..k {
+            axpy(lhs[i * k + p], &rhs[p * n..(p + 1) * n], out_row);
+        }
+    };
+    if m * n * k <= PARALLEL_CHUNK_LEN {
+        output.chunks_mut(n).enumerate().for_each(row);
+    } else {
+        output.par_chunks_mut(n).enumerate().for_each(row);
+    }
+    output
+}
+
+/// Transposes a row-major `[rows, cols]` matrix.
+#[cfg(feature = "async")]
+pub(crate) fn transpose<T: Copy>(input: &[T], rows: usize, cols: usize) -> Vec<T> {
+    let mut output = Vec::with_capacity(input.len());
+    for c in 0..cols {
+        for r in 0..rows {
+            output.push(input[r * cols + c]);
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_binary_broadcasting() {
+        let (data, shape) = binary(&[1.0f32, 2.0], &[2], &[3.0, 4.0], &[2], |a, b| a + b).unwrap();
+        assert_eq!((data, shape), (vec![4.0, 6.0], vec![2]));
+
+        let (data, shape) =
+            binary(&[1.0f32, 2.0, 3.0, 4.0], &[2, 2], &[10.0, 20.0], &[2], |a, b| a + b).unwrap();
+        assert_eq!((data, shape), (vec![11.0, 22.0, 13.0, 24.0], vec![2, 2]));
+
+        let (data, _) = binary(&[2.0f32], &[], &[1.0, 2.0], &[2], |a, b| a * b).unwrap();
+        assert_eq!(data, vec![2.0, 4.0]);
+
+        assert!(binary(&[1.0f32, 2.0], &[2], &[1.0, 2.0, 3.0], &[3], |a, b| a + b).is_err());
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let lhs = (0..20).map(|i| i as f32).collect::<Vec<_>>();
+        let rhs = (0..10).map(|i| i as f32 * 2.0).collect::<Vec<_>>();
+        let (data, shape) = arithmetic(ArithmeticOp::Sub, &lhs, &[2, 10], &rhs, &[10]).unwrap();
+        assert_eq!(shape, vec![2, 10]);
+        assert_eq!(data[..3], [0.0, -1.0, -2.0]);
+        assert_eq!(data[10..13], [10.0, 9.0, 8.0]);
+
+        let (data, _) = arithmetic(ArithmeticOp::Mul, &[1i32, 2, 3], &[3], &[2], &[]).unwrap();
+        assert_eq!(data, vec![2, 4, 6]);
+        let (data, _) =
+            arithmetic(ArithmeticOp::Div, &[8.0f64, 9.0], &[2], &[2.0, 3.0], &[2]).unwrap();
+        assert_eq!(data, vec![4.0, 3.0]);
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_parallel_chunks() {
+        let len = PARALLEL_CHUNK_LEN * 3 + 5;
+        let lhs = (0..len).map(|i| (i % 100) as f32).collect::<Vec<_>>();
+        let rhs = vec![2.0f32; len];
+        let (data, _) = arithmetic(ArithmeticOp::Mul, &lhs, &[len], &rhs, &[len]).unwrap();
+        assert!(data.iter().zip(&lhs).all(|(&out, &x)| out == x * 2.0));
+        let negated = unary(&lhs, |x| -x);
+        assert!(negated.iter().zip(&lhs).all(|(&out, &x)| out == -x));
+
+        let (m, k, n) = (64, 32, 48);
+        let a = (0..m * k).map(|i| (i % 7) as f64).collect::<Vec<_>>();
+        let b = (0..k * n).map(|i| (i % 5) as f64).collect::<Vec<_>>();
+        let product = matmul(&a, &b, m, k, n);
+        let expected = (0..k).map(|p| a[k + p] * b[p * n + 3]).sum::<f64>();
+        assert_eq!(product[n + 3], expected);
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_matmul_and_transpose() {
+        let lhs = [1.0f32, 2.0, 3.0, 4.0, 5.0, 6.0];
+        let rhs = [7.0f32, 8.0, 9.0, 10.0, 11.0, 12.0];
+        assert_eq!(matmul(&lhs, &rhs, 2, 3, 2), vec![58.0, 64.0, 139.0, 154.0]);
+        assert_eq!(transpose(&lhs, 2, 3), vec![1.0, 4.0, 2.0, 5.0, 3.0, 6.0]);
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_reductions() {
+        assert_eq!(sum(&[1.0f64, 2.0, 3.0]), 6.0);
+        assert_eq!(argmax(&[1.0f32, 5.0, 3.0, 5.0]), Some(1));
+        assert_eq!(argmax::<f32>(&[]), None);
+        let probabilities = softmax(&[0.0f64, 0.0, 1.0, 1.0], 2);
+        assert_eq!(probabilities, vec![0.5, 0.5, 0.5, 0.5]);
+    }
+}
@@ -1,22 +1,230 @@
+#[cfg(feature = "async")]
+mod aligned_vec;
+#[cfg(feature = "async")]
+mod analysis;
+mod block_quantization;
+#[cfg(feature = "async")]
+mod bloom_filter;
+#[cfg(feature = "async")]
+mod chunk_reader;
+mod codec;
+mod codec_registry;
+mod compression_policy;
+#[cfg(feature = "async")]
+pub mod conformance;
 mod constants;
+mod constraints;
+#[cfg(feature = "async")]
+mod diff_export;
+#[cfg(feature = "async")]
+mod download;
+#[cfg(feature = "fec")]
+mod erasure_shards;
+#[cfg(feature = "async")]
+mod executor;
+mod extension_type;
+#[cfg(feature = "async")]
+mod external_data;
+mod footer;
+mod format_features;
 mod generated;
+mod graph_dot;
+mod graph_html;
+#[cfg(feature = "async")]
+mod http_upload;
+mod kernels;
+#[cfg(feature = "async")]
+mod lora;
+#[cfg(feature = "async")]
+mod memory_budget;
+#[cfg(feature = "async")]
+mod metadata_lookup;
+mod metrics;
+#[cfg(feature = "async")]
+mod mirrors;
+#[cfg(feature = "mmap")]
+mod mmap_tensor_buffers;
+#[cfg(feature = "async")]
+mod name_index;
 mod num_trait;
+mod observer;
+#[cfg(feature = "async")]
+mod onnx;
+#[cfg(feature = "async")]
+mod open_options;
+mod operation_profile;
+#[cfg(feature = "async")]
+mod optimizer;
+mod parity;
+#[cfg(feature = "async")]
+mod prefetch;
+mod provenance;
+mod quantization;
+#[cfg(feature = "async")]
+mod range_cache;
+#[cfg(feature = "async")]
+mod rate_limiter;
+mod read_mode;
+#[cfg(feature = "async")]
+mod read_plan;
+#[cfg(feature = "async")]
+mod reader_pool;
+#[cfg(feature = "async")]
+mod recover;
+#[cfg(feature = "serve")]
+mod serve;
+mod shape_inference;
+mod simd;
+mod slice_file;
+mod stats;
+#[cfg(feature = "async")]
+mod subgraph;
 mod tensor;
+mod tensor_any;
+#[cfg(feature = "async")]
 mod tensor_buffers;
+#[cfg(feature = "async")]
 mod tensor_buffers_file;
+#[cfg(feature = "async")]
 mod tensor_buffers_reader;
+#[cfg(feature = "async")]
+mod tensor_buffers_sink;
+#[cfg(feature = "async")]
+mod tensor_buffers_stream_reader;
+#[cfg(feature = "async")]
 mod tensor_buffers_writer;
+mod tensor_columns;
+mod tensor_compare;
+mod tensor_concat;
+mod tensor_display;
+#[cfg(feature = "async")]
+mod tensor_filter;
+mod tensor_graph;
+mod tensor_info;
+mod tensor_init;
 mod tensor_operation;
+mod tensor_ops;
+mod tensor_value;
+mod tensor_view;
+#[cfg(feature = "async")]
+mod timeouts;
+#[cfg(feature = "async")]
+mod trace;
+#[cfg(feature = "tui")]
+mod tui;
+mod untrusted;
+#[cfg(feature = "async")]
+mod usage_report;
+#[cfg(all(feature = "async", feature = "flexbuffers"))]
+mod user_metadata;
 mod utils;
+#[cfg(feature = "async")]
+mod value_cache;
+#[cfg(feature = "async")]
+mod verify;
+#[cfg(feature = "async")]
+mod warmup;
+mod writer_options;
 
-pub use generated::tensor_buffers::Operation;
-pub use num_trait::{DataType, Num, Zero};
+#[cfg(feature = "async")]
+pub use analysis::{AnalysisReport, CompressionEstimate, DuplicateGroup, ZeroRegion};
+pub use codec_registry::{register_codec, registered_codecs, unregister_codec, CustomCodec};
+pub use compression_policy::CompressionPolicy;
+pub use constraints::{ConstraintReport, TensorConstraint};
+#[cfg(feature = "async")]
+pub use diff_export::DiffReport;
+#[cfg(feature = "async")]
+pub use download::{download, DownloadReport};
+#[cfg(feature = "fec")]
+pub use erasure_shards::{reconstruct_shards, write_sharded, ShardOptions, ShardReport};
+#[cfg(feature = "async")]
+pub use executor::Executor;
+pub use extension_type::{
+    register_extension, registered_extensions, unregister_extension, ExtensionDecoder,
+    ExtensionType,
+};
+pub use footer::FooterSummary;
+pub use format_features::SUPPORTED_FEATURES;
+pub use generated::tensor_buffers::{ChecksumAlgorithm, Compression, Operation};
+pub use graph_dot::graph_to_dot;
+pub use graph_html::graph_to_html;
+pub use half::f16;
+#[cfg(feature = "async")]
+pub use http_upload::HttpUpload;
+#[cfg(feature = "async")]
+pub use lora::LoraReport;
+#[cfg(feature = "async")]
+pub use memory_budget::MemoryBudget;
+#[cfg(feature = "metrics")]
+pub use metrics::register_metrics;
+#[cfg(feature = "async")]
+pub use mirrors::MirrorProbe;
+#[cfg(feature = "mmap")]
+pub use mmap_tensor_buffers::MmapTensorBuffers;
+pub use num_trait::{CastFrom, DataType, Float, Int, Num, One, UInt, Zero};
+pub use observer::TensorBuffersObserver;
+#[cfg(feature = "async")]
+pub use onnx::OnnxExporter;
+#[cfg(feature = "async")]
+pub use open_options::OpenOptions;
+pub use operation_profile::OperationProfile;
+#[cfg(feature = "async")]
+pub use optimizer::{OptimizedGraph, Optimizer};
+#[cfg(feature = "fec")]
+pub use parity::{add_parity, repair, ParityOptions, RepairReport};
+#[cfg(feature = "async")]
+pub use prefetch::{PrefetchBudget, PrefetchPriority};
+pub use provenance::Provenance;
+pub use quantization::{QuantizationParams, QuantizationPolicy};
+#[cfg(feature = "async")]
+pub use range_cache::{DiskRangeCache, MemoryRangeCache, RangeCache, RangeKey};
+#[cfg(feature = "async")]
+pub use rate_limiter::RateLimiter;
+pub use read_mode::ReadMode;
+#[cfg(feature = "async")]
+pub use recover::{recover, RecoveryReport};
+#[cfg(feature = "serve")]
+pub use serve::TensorServer;
+pub use shape_inference::{infer_output_shape, InferredShape};
+pub use slice_file::{parse_slice, SliceFile};
+pub use stats::{ReadStats, WriteStats};
 pub use tensor::Tensor;
+pub use tensor_any::TensorAny;
+#[cfg(feature = "async")]
 pub use tensor_buffers::TensorBuffers;
+#[cfg(feature = "async")]
 pub use tensor_buffers_file::RemoteFile;
-pub use tensor_buffers_writer::TensorBuffersWriter;
+#[cfg(feature = "async")]
+pub use tensor_buffers_reader::{TensorBuffersRead, TensorBuffersReader};
+#[cfg(feature = "async")]
+pub use tensor_buffers_sink::TensorBuffersSink;
+#[cfg(feature = "async")]
+pub use tensor_buffers_stream_reader::TensorBuffersStreamReader;
+#[cfg(feature = "async")]
+pub use tensor_buffers_writer::{TensorBuffersWrite, TensorBuffersWriter, WriteProgress};
+pub use tensor_compare::TensorDiff;
+pub use tensor_graph::TensorGraph;
+pub use tensor_info::TensorInfo;
 pub use tensor_operation::TensorOperation;
+pub use tensor_value::TensorValue;
+pub use tensor_view::TensorView;
+#[cfg(feature = "async")]
+pub use timeouts::Timeouts;
+#[cfg(feature = "async")]
+pub use trace::{TraceRecorder, TracedTensor};
+#[cfg(feature = "tui")]
+pub use tui::Inspector;
+pub use untrusted::{parse_untrusted, UntrustedFile, UntrustedLimits};
+#[cfg(feature = "async")]
+pub use usage_report::{NamespaceUsage, UsageReport};
+#[cfg(all(feature = "async", feature = "flexbuffers"))]
+pub use user_metadata::{from_user_metadata, to_user_metadata};
+#[cfg(feature = "async")]
+pub use verify::VerifyReport;
+#[cfg(feature = "async")]
+pub use warmup::{WarmupOptions, WarmupReport};
+pub use writer_options::WriterOptions;
 
 pub type TensorId = u64;
 pub type TensorOperationId = u64;
-pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
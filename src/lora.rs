@@ -0,0 +1,360 @@
+use std::collections::HashMap;
+
+use futures::SinkExt;
+use half::slice::HalfFloatSliceExt;
+use tokio::io::AsyncWrite;
+use tracing::{info, instrument};
+
+use crate::{
+    extension_type::data_type_of,
+    f16,
+    generated::tensor_buffers::TensorMetadata,
+    kernels::{self, ArithmeticOp},
+    utils::hash_key,
+    DataType, Result, Tensor, TensorAny, TensorBuffers, TensorBuffersSink,
+};
+
+/// Prefix PEFT gives the names of an adapter's tensors.
+const PEFT_PREFIX: &str = "base_model.model.";
+
+/// What [`TensorBuffersSink::apply_lora`] wrote.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LoraReport {
+    /// Tensors the adapter updates, written merged with their update.
+    pub merged: Vec<String>,
+    /// Tensors the adapter leaves alone, copied from the base file.
+    pub copied: Vec<String>,
+}
+
+/// The names of the adapter's low-rank factors of one weight.
+#[derive(Default)]
+struct Factors<'n> {
+    a: Option<&'n str>,
+    b: Option<&'n str>,
+}
+
+impl<W> TensorBuffersSink<W>
+where
+    W: AsyncWrite + Unpin,
+{
+    /// Writes the tensors and operations of `base` with a LoRA adapter merged into its weights,
+    /// so a fine-tuned model becomes one file in a single call.
+    ///
+    /// Each weight `W` the adapter has factors for is written as `W + alpha * B·A`. The factors
+    /// of the weight `X.weight` are the adapter's `X.lora_A.weight`, of shape `[rank, ...]` with
+    /// as many elements per row as `W`, and `X.lora_B.weight`, of `rank` elements per row of `W`,
+    /// named as PEFT saves them, with or without its `base_model.model.` prefix. PEFT scales the
+    /// update by `lora_alpha / r`, which is the `alpha` to pass for its adapters. Weights and
+    /// factors may be Float32, Float16 or quantized, and merged weights are written as the
+    /// floating point type they were stored or quantized from. Other tensors are copied, except
+    /// quantized ones, which are written dequantized; the sink's quantization policy can
+    /// quantize them again. Tensors are loaded one at a time.
+    ///
+    /// # Arguments
+    /// * `base` - The model to update.
+    /// * `adapter` - The LoRA adapter, holding only factors.
+    /// * `alpha` - The scale of the update.
+    ///
+    /// # Returns
+    /// Which tensors were merged and which copied. Before writing anything, fails if the adapter
+    /// holds a tensor that isn't a factor, a factor without its pair, or factors of a weight
+    /// `base` lacks. A weight whose factors don't fit its shape fails the merge, leaving the file
+    /// incomplete.
+    #[instrument(skip_all)]
+    pub async fn apply_lora(
+        &mut self,
+        base: &TensorBuffers,
+        adapter: &TensorBuffers,
+        alpha: f32,
+    ) -> Result<LoraReport> {
+        let adapter_tensors = adapter.tensor_tables().await?.into_iter().flatten();
+        let factors = pair_factors(adapter_tensors.map(|tensor| tensor.name()))?;
+        let base_tensors = base.tensor_tables().await?.into_iter().flatten().collect::<Vec<_>>();
+        let in_base = |weight: &&String| base_tensors.iter().any(|tensor| tensor.name() == *weight);
+        if let Some(weight) = factors.keys().find(|weight| !in_base(weight)) {
+            return Err(
+                format!("The adapter updates {}, which the base doesn't have", weight).into()
+            );
+        }
+
+        let mut report = LoraReport::default();
+        for tensor in base_tensors {
+            let name = tensor.name();
+            let written = match factors.get(name) {
+                Some(Factors { a: Some(a), b: Some(b) }) => {
+                    report.merged.push(name.to_string());
+                    merge(base, adapter, &tensor, a, b, alpha).await?
+                }
+                _ => {
+                    report.copied.push(name.to_string());
+                    match tensor.quantization() {
+                        Some(_) => {
+                            let (values, data_type) = load_float(base, &tensor).await?;
+                            float_tensor(values, data_type)
+                        }
+                        None => base.read_tensor_any(tensor).await?,
+                    }
+                }
+            };
+            self.feed(written).await?;
+        }
+        for operation in base.get_tensor_operations().await? {
+            self.add_operation(operation);
+        }
+        self.flush().await?;
+        info!(merged = report.merged.len(), copied = report.copied.len(), "Applied LoRA adapter");
+        Ok(report)
+    }
+}
+
+/// Pairs the adapter tensors `names` by the weight they update.
+///
+/// # Returns
+/// The factors of each updated weight, or an error if a tensor isn't a factor or lacks its pair.
+fn pair_factors<'n>(names: impl Iterator<Item = &'n str>) -> Result<HashMap<String, Factors<'n>>> {
+    let mut factors = HashMap::<_, Factors>::new();
+    for name in names {
+        let unprefixed = name.strip_prefix(PEFT_PREFIX).unwrap_or(name);
+        let suffixes =
+            (unprefixed.strip_suffix(".lora_A.weight"), unprefixed.strip_suffix(".lora_B.weight"));
+        let (module, is_a) = match suffixes {
+            (Some(module), _) => (module, true),
+            (_, Some(module)) => (module, false),
+            _ => return Err(format!("Adapter tensor {} is not a LoRA factor", name).into()),
+        };
+        let entry = factors.entry(format!("{}.weight", module)).or_default();
+        match is_a {
+            true => entry.a = Some(name),
+            false => entry.b = Some(name),
+        }
+    }
+    let unpaired = |factors: &Factors| factors.a.is_none() || factors.b.is_none();
+    if let Some((weight, _)) = factors.iter().find(|(_, factors)| unpaired(factors)) {
+        return Err(format!("The adapter lacks one of the factors of {}", weight).into());
+    }
+    Ok(factors)
+}
+
+/// Loads the values of `tensor` as Float32, with the floating point type they were stored or
+/// quantized as.
+async fn load_float(
+    tensor_buffers: &TensorBuffers,
+    tensor: &TensorMetadata<'_>,
+) -> Result<(Tensor<'static, f32>, DataType)> {
+    let name = tensor.name();
+    if let Some(params) = tensor_buffers.read_quantization(tensor).await? {
+        let values = tensor_buffers.get_tensor_dequantized(name).await?;
+        return Ok((values, params.original_type));
+    }
+    match data_type_of(tensor)? {
+        DataType::Float32 => {
+            Ok((tensor_buffers.get_tensor_data_by_name(name).await?, DataType::Float32))
+        }
+        DataType::Float16 => {
+            let half = tensor_buffers.get_tensor_data_by_name::<f16>(name).await?;
+            let mut values = vec![0.0; half.elements()];
+            half.data().convert_to_f32_slice(&mut values);
+            Ok((Tensor::from_vec(name, values, half.shape().to_vec()), DataType::Float16))
+        }
+        data_type if data_type.is_block_quantized() => {
+            Ok((tensor_buffers.get_tensor_dequantized(name).await?, DataType::Float32))
+        }
+        data_type => {
+            Err(format!("Tensor {} holds {:?} values, which LoRA can't update", name, data_type)
+                .into())
+        }
+    }
+}
+
+/// Converts Float32 `values` to a tensor of `data_type`, Float32 or Float16.
+fn float_tensor(values: Tensor<'static, f32>, data_type: DataType) -> TensorAny {
+    match data_type {
+        DataType::Float16 => {
+            let mut half = vec![f16::ZERO; values.elements()];
+            half.convert_from_f32_slice(values.data());
+            Tensor::from_vec(values.name(), half, values.shape().to_vec()).into()
+        }
+        _ => values.into(),
+    }
+}
+
+/// Computes `W + alpha * B·A` for the weight `tensor` of `base` and the factors `a` and `b` of
+/// `adapter`.
+async fn merge(
+    base: &TensorBuffers,
+    adapter: &TensorBuffers,
+    tensor: &TensorMetadata<'_>,
+    a: &str,
+    b: &str,
+    alpha: f32,
+) -> Result<TensorAny> {
+    let (weight, data_type) = load_float(base, tensor).await?;
+    let adapter_tensor = |name| async move {
+        let metadata = adapter.stored_metadata(hash_key(name)).await?;
+        load_float(adapter, &metadata).await.map(|(values, _)| values)
+    };
+    let (a, b) = (adapter_tensor(a).await?, adapter_tensor(b).await?);
+    // Both factors and the weight are viewed as matrices of their first dimension's rows.
+    let rows = weight.shape().first().copied().unwrap_or(1);
+    let rank = a.shape().first().copied().unwrap_or(1);
+    let columns = weight.elements().checked_div(rows).unwrap_or(0);
+    if weight.shape().len() < 2 || a.elements() != rank * columns || b.elements() != rows * rank {
+        return Err(format!(
+            "LoRA factors of shapes {:?} and {:?} don't fit {} of shape {:?}",
+            a.shape(),
+            b.shape(),
+            tensor.name(),
+            weight.shape()
+        )
+        .into());
+    }
+    let scaled = b.data().iter().map(|&value| alpha * value).collect::<Vec<_>>();
+    let update = kernels::matmul(&scaled, a.data(), rows, rank, columns);
+    let shape = [weight.elements()];
+    let (merged, _) =
+        kernels::arithmetic(ArithmeticOp::Add, weight.data(), &shape, &update, &shape)?;
+    let merged = Tensor::from_vec(tensor.name(), merged, weight.shape().to_vec());
+    Ok(float_tensor(merged, data_type))
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::NamedTempFile;
+    use tokio::fs::File;
+
+    use super::*;
+    use crate::{Operation, QuantizationPolicy, TensorOperation, WriterOptions};
+
+    /// Writes `tensors` to a new file through a sink with `options`.
+    async fn write(tensors: Vec<TensorAny>, options: WriterOptions) -> NamedTempFile {
+        let tmp = NamedTempFile::new().unwrap();
+        let file = File::create(tmp.path()).await.unwrap();
+        let mut sink = TensorBuffersSink::new(file, options).unwrap();
+        sink.add_operation(TensorOperation::new(1, Operation::None, vec![], hash_key("q")));
+        for tensor in tensors {
+            sink.send(tensor).await.unwrap();
+        }
+        sink.close().await.unwrap();
+        tmp
+    }
+
+    async fn open(tmp: &NamedTempFile) -> TensorBuffers {
+        TensorBuffers::open(&format!("file://{}", tmp.path().display())).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_apply_lora() {
+        let weight = (0..12).map(|i| i as f32).collect::<Vec<_>>();
+        let half = weight.iter().map(|&value| f16::from_f32(value)).collect::<Vec<_>>();
+        let base = write(
+            vec![
+                Tensor::from_vec("q.weight", weight.clone(), vec![4, 3]).into(),
+                Tensor::from_vec("k.weight", half, vec![4, 3]).into(),
+                Tensor::from_vec("k.bias", vec![1.0f32; 4], vec![4]).into(),
+                Tensor::from_vec("ids", vec![7i64, 8], vec![2]).into(),
+            ],
+            WriterOptions::new(),
+        )
+        .await;
+        // Rank 2 factors: A is [2, 3] and B is [4, 2].
+        let a = vec![1.0f32, 0.0, 2.0, 0.0, 1.0, 0.0];
+        let b = vec![1.0f32, 0.0, 0.0, 1.0, 1.0, 1.0, 2.0, 0.0];
+        let adapter = write(
+            vec![
+                Tensor::from_vec("base_model.model.q.lora_A.weight", a.clone(), vec![2, 3]).into(),
+                Tensor::from_vec("base_model.model.q.lora_B.weight", b.clone(), vec![4, 2]).into(),
+                Tensor::from_vec("k.lora_A.weight", a, vec![2, 3]).into(),
+                Tensor::from_vec("k.lora_B.weight", b, vec![4, 2]).into(),
+            ],
+            WriterOptions::new(),
+        )
+        .await;
+        let (base, adapter) = (open(&base).await, open(&adapter).await);
+
+        let merged = NamedTempFile::new().unwrap();
+        let file = File::create(merged.path()).await.unwrap();
+        let mut sink = TensorBuffersSink::new(file, WriterOptions::new()).unwrap();
+        let mut report = sink.apply_lora(&base, &adapter, 0.5).await.unwrap();
+        sink.close().await.unwrap();
+        report.merged.sort();
+        report.copied.sort();
+        assert_eq!(report.merged, ["k.weight", "q.weight"]);
+        assert_eq!(report.copied, ["ids", "k.bias"]);
+
+        // B·A is [[1, 0, 2], [0, 1, 0], [1, 1, 2], [2, 0, 4]], halved.
+        let expected = [0.5, 1.0, 3.0, 3.0, 4.5, 5.0, 6.5, 7.5, 9.0, 10.0, 10.0, 13.0];
+        let merged = open(&merged).await;
+        let q = merged.get_tensor_data_by_name::<f32>("q.weight").await.unwrap();
+        assert_eq!((q.shape(), q.data()), (&[4, 3][..], &expected[..]));
+        let k = merged.get_tensor_data_by_name::<f16>("k.weight").await.unwrap();
+        assert!(k.data().iter().zip(expected).all(|(&k, e)| k == f16::from_f32(e)));
+        let ids = merged.get_tensor_data_by_name::<i64>("ids").await.unwrap();
+        assert_eq!(ids.data(), [7, 8]);
+        assert_eq!(merged.get_tensor_operations().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_apply_lora_rejects_mismatched_adapters() {
+        let weight = Tensor::from_vec("q.weight", vec![0.0f32; 12], vec![4, 3]);
+        let base = write(vec![weight.clone().into()], WriterOptions::new()).await;
+        let base = open(&base).await;
+        for (tensors, error) in [
+            (
+                vec![Tensor::from_vec("q.lora_A.weight", vec![0.0f32; 6], vec![2, 3])],
+                "The adapter lacks one of the factors of q.weight",
+            ),
+            (
+                vec![Tensor::from_vec("q.lora_magnitude", vec![0.0f32; 4], vec![4])],
+                "Adapter tensor q.lora_magnitude is not a LoRA factor",
+            ),
+            (
+                vec![
+                    Tensor::from_vec("v.lora_A.weight", vec![0.0f32; 6], vec![2, 3]),
+                    Tensor::from_vec("v.lora_B.weight", vec![0.0f32; 8], vec![4, 2]),
+                ],
+                "The adapter updates v.weight, which the base doesn't have",
+            ),
+            (
+                vec![
+                    Tensor::from_vec("q.lora_A.weight", vec![0.0f32; 6], vec![2, 3]),
+                    Tensor::from_vec("q.lora_B.weight", vec![0.0f32; 6], vec![3, 2]),
+                ],
+                "LoRA factors of shapes [2, 3] and [3, 2] don't fit q.weight of shape [4, 3]",
+            ),
+        ] {
+            let tensors = tensors.into_iter().map(TensorAny::from).collect();
+            let adapter = write(tensors, WriterOptions::new()).await;
+            let adapter = open(&adapter).await;
+            let mut sink = TensorBuffersSink::new(Vec::new(), WriterOptions::new()).unwrap();
+            let result = sink.apply_lora(&base, &adapter, 1.0).await;
+            assert_eq!(result.unwrap_err().to_string(), error);
+        }
+
+        // Quantized weights are merged from their dequantized values.
+        let policy = QuantizationPolicy::int8().with_min_elements(8);
+        let base = write(vec![weight.into()], WriterOptions::new().with_quantization(policy)).await;
+        let base = open(&base).await;
+        let adapter = write(
+            vec![
+                Tensor::from_vec("q.lora_A.weight", vec![1.0f32; 3], vec![1, 3]).into(),
+                Tensor::from_vec("q.lora_B.weight", vec![1.0f32; 4], vec![4, 1]).into(),
+            ],
+            WriterOptions::new(),
+        )
+        .await;
+        let adapter = open(&adapter).await;
+        let mut sink = TensorBuffersSink::new(Vec::new(), WriterOptions::new()).unwrap();
+        sink.apply_lora(&base, &adapter, 2.0).await.unwrap();
+        sink.close().await.unwrap();
+        let merged = write_bytes(sink.into_inner()).await;
+        let q = open(&merged).await.get_tensor_data_by_name::<f32>("q.weight").await.unwrap();
+        assert_eq!(q.data(), [2.0; 12]);
+    }
+
+    /// Saves `bytes` to a new file.
+    async fn write_bytes(bytes: Vec<u8>) -> NamedTempFile {
+        let tmp = NamedTempFile::new().unwrap();
+        std::fs::write(tmp.path(), bytes).unwrap();
+        tmp
+    }
+}
@@ -0,0 +1,163 @@
+use std::{env, process::ExitCode};
+
+use tensorbuffers::{conformance, Result, TensorBuffers};
+
+const USAGE: &str = "Usage: tensorbuffers graph --dot <file or url>
+       tensorbuffers graph --html <file or url> <output file>
+       tensorbuffers recover [--no-verify] <damaged file> <output file>
+       tensorbuffers verify <file or url> [tensor name...]
+       tensorbuffers check-constraints <file or url>
+       tensorbuffers model-card <file or url>
+       tensorbuffers conformance generate|check <dir>";
+
+/// Returns the usage, with the commands of the enabled optional features.
+fn usage() -> String {
+    #[allow(unused_mut)]
+    let mut usage = USAGE.to_string();
+    #[cfg(feature = "fec")]
+    usage.push_str("\n       tensorbuffers parity add|repair <file>");
+    #[cfg(feature = "fec")]
+    usage.push_str("\n       tensorbuffers reconstruct-shards <index file>");
+    #[cfg(feature = "serve")]
+    usage.push_str("\n       tensorbuffers serve <file or url> <address>");
+    #[cfg(feature = "tui")]
+    usage.push_str("\n       tensorbuffers tui <file or url>");
+    usage
+}
+
+/// Accepts plain paths as well as the `file://` and `https://` URLs understood by `TensorBuffers`.
+fn to_url(location: &str) -> String {
+    if location.contains("://") {
+        location.to_string()
+    } else {
+        format!("file://{}", location)
+    }
+}
+
+async fn run(args: &[String]) -> Result<()> {
+    let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+    match args.as_slice() {
+        ["graph", "--dot", location] => {
+            let tensor_buffers = TensorBuffers::open(&to_url(location)).await?;
+            print!("{}", tensor_buffers.graph_to_dot().await?);
+            Ok(())
+        }
+        ["graph", "--html", location, output] => {
+            let tensor_buffers = TensorBuffers::open(&to_url(location)).await?;
+            tensor_buffers.export_graph_html(output).await
+        }
+        ["recover", src, dst] | ["recover", "--no-verify", src, dst] => {
+            let report = tensorbuffers::recover(src, dst, args[1] != "--no-verify").await?;
+            for reason in &report.skipped {
+                eprintln!("Skipped: {}", reason);
+            }
+            eprintln!("Recovered {} tensors into {}", report.recovered.len(), dst);
+            Ok(())
+        }
+        ["verify", location, names @ ..] => {
+            let tensor_buffers = TensorBuffers::open(&to_url(location)).await?;
+            let names = (!names.is_empty()).then_some(names);
+            let report = tensor_buffers.verify_data(names).await?;
+            for (name, problem) in &report.failed {
+                eprintln!("{}: {}", name, problem);
+            }
+            if !report.unchecked.is_empty() {
+                eprintln!("{} tensors have no checksum", report.unchecked.len());
+            }
+            eprintln!("Verified {} tensors, {} bytes", report.verified, report.bytes);
+            match report.failed.len() {
+                0 => Ok(()),
+                n => Err(format!("{} corrupt tensors in {}", n, location).into()),
+            }
+        }
+        ["check-constraints", location] => {
+            let tensor_buffers = TensorBuffers::open(&to_url(location)).await?;
+            let report = tensor_buffers.check_constraints().await?;
+            for (constraint, problem) in &report.violated {
+                eprintln!("{:?}: {}", constraint, problem);
+            }
+            eprintln!("{} constraints hold", report.satisfied);
+            match report.violated.len() {
+                0 => Ok(()),
+                n => Err(format!("{} violated constraints in {}", n, location).into()),
+            }
+        }
+        ["model-card", location] => {
+            let tensor_buffers = TensorBuffers::open(&to_url(location)).await?;
+            match tensor_buffers.model_card().await? {
+                Some(card) => {
+                    print!("{}", card);
+                    Ok(())
+                }
+                None => Err(format!("{} has no model card", location).into()),
+            }
+        }
+        ["conformance", "generate", dir] => {
+            let paths = conformance::generate_fixtures(dir).await?;
+            eprintln!("Wrote {} fixtures into {}", paths.len(), dir);
+            Ok(())
+        }
+        ["conformance", "check", dir] => {
+            let problems = conformance::check_fixtures(dir).await?;
+            for problem in &problems {
+                eprintln!("{}", problem);
+            }
+            match problems.len() {
+                0 => Ok(()),
+                n => Err(format!("{} conformance problems in {}", n, dir).into()),
+            }
+        }
+        #[cfg(feature = "fec")]
+        ["parity", "add", path] => {
+            tensorbuffers::add_parity(path, tensorbuffers::ParityOptions::new()).await?;
+            eprintln!("Added parity to {}", path);
+            Ok(())
+        }
+        #[cfg(feature = "fec")]
+        ["parity", "repair", path] => {
+            let report = tensorbuffers::repair(path).await?;
+            for offset in &report.unrepairable {
+                eprintln!("Unrepairable sector at {}", offset);
+            }
+            eprintln!("Checked {} sectors, repaired {}", report.checked, report.repaired.len());
+            match report.unrepairable.len() {
+                0 => Ok(()),
+                n => Err(format!("{} damaged sectors can't be repaired in {}", n, path).into()),
+            }
+        }
+        #[cfg(feature = "fec")]
+        ["reconstruct-shards", path] => {
+            let report = tensorbuffers::reconstruct_shards(path).await?;
+            for restored in &report.restored {
+                eprintln!("Restored {}", restored.display());
+            }
+            eprintln!("{} shards were intact", report.intact);
+            Ok(())
+        }
+        #[cfg(feature = "serve")]
+        ["serve", location, address] => {
+            let tensor_buffers = TensorBuffers::open(&to_url(location)).await?;
+            let listener = tokio::net::TcpListener::bind(address).await?;
+            eprintln!("Serving {} on http://{}", location, listener.local_addr()?);
+            tensorbuffers::TensorServer::new(&tensor_buffers).serve(listener).await
+        }
+        #[cfg(feature = "tui")]
+        ["tui", location] => {
+            let tensor_buffers = TensorBuffers::open(&to_url(location)).await?;
+            tensorbuffers::Inspector::load(&tensor_buffers).await?.run().await
+        }
+        _ => Err(usage().into()),
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    match run(&args).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
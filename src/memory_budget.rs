@@ -0,0 +1,101 @@
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+use crate::Result;
+
+/// Limits on how much memory reading a TensorBuffers file may allocate, so a malicious or mistaken
+/// metadata entry can't make the reader allocate arbitrary amounts. Every limit is off by default.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MemoryBudget {
+    /// Largest tensor that is loaded, in bytes. Compressed tensors count both their stored and
+    /// decompressed data. Larger tensors fail to load.
+    pub max_tensor_bytes: Option<usize>,
+    /// Most memory allocated by reads in progress at once, in bytes. Reads wait for others to
+    /// finish while it is used up, and reads that need more than all of it fail.
+    pub max_outstanding_bytes: Option<usize>,
+}
+
+impl MemoryBudget {
+    /// Sets the largest tensor that is loaded.
+    pub fn with_max_tensor_bytes(mut self, bytes: usize) -> Self {
+        self.max_tensor_bytes = Some(bytes);
+        self
+    }
+
+    /// Sets the most memory allocated by reads in progress at once.
+    pub fn with_max_outstanding_bytes(mut self, bytes: usize) -> Self {
+        self.max_outstanding_bytes = Some(bytes);
+        self
+    }
+}
+
+/// Tracks the memory of reads in progress against a `MemoryBudget`.
+pub(crate) struct MemoryTracker {
+    budget: MemoryBudget,
+    outstanding: Option<Semaphore>,
+}
+
+impl MemoryTracker {
+    pub fn new(budget: MemoryBudget) -> Self {
+        // Permits are counted in u32, so larger budgets are capped, which no real read reaches.
+        let outstanding =
+            budget.max_outstanding_bytes.map(|bytes| Semaphore::new(bytes.min(u32::MAX as usize)));
+        MemoryTracker { budget, outstanding }
+    }
+
+    /// Reserves `bytes` for a read, waiting for other reads to finish if needed. The reservation
+    /// is released when the returned permit is dropped.
+    ///
+    /// # Arguments
+    /// * `what` - Describes the read in errors, e.g. "tensor x".
+    /// * `bytes` - The memory the read allocates.
+    pub async fn reserve(&self, what: &str, bytes: usize) -> Result<Option<SemaphorePermit<'_>>> {
+        if let Some(max) = self.budget.max_tensor_bytes {
+            if bytes > max {
+                return Err(format!(
+                    "Reading {} needs {} bytes, over the budget of {} bytes per tensor",
+                    what, bytes, max
+                )
+                .into());
+            }
+        }
+        let Some(outstanding) = &self.outstanding else {
+            return Ok(None);
+        };
+        let max = self.budget.max_outstanding_bytes.unwrap_or_default().min(u32::MAX as usize);
+        if bytes > max {
+            return Err(format!(
+                "Reading {} needs {} bytes, over the budget of {} outstanding bytes",
+                what, bytes, max
+            )
+            .into());
+        }
+        Ok(Some(outstanding.acquire_many(bytes as u32).await?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_reserve() {
+        let budget = MemoryBudget::default().with_max_tensor_bytes(100);
+        let tracker = MemoryTracker::new(budget.with_max_outstanding_bytes(150));
+        let error = tracker.reserve("tensor x", 101).await.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Reading tensor x needs 101 bytes, over the budget of 100 bytes per tensor"
+        );
+
+        let first = tracker.reserve("tensor x", 100).await.unwrap();
+        let blocked = tokio::time::timeout(Duration::from_millis(10), tracker.reserve("y", 100));
+        assert!(blocked.await.is_err());
+        drop(first);
+        assert!(tracker.reserve("tensor y", 100).await.unwrap().is_some());
+
+        let unlimited = MemoryTracker::new(MemoryBudget::default());
+        assert!(unlimited.reserve("tensor x", usize::MAX).await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,276 @@
+use std::{
+    collections::HashMap,
+    sync::{Mutex, OnceLock},
+};
+
+use flatbuffers::{ForwardsUOffset, Vector};
+
+use crate::{
+    generated::tensor_buffers::{TensorBuffersMetadata, TensorMetadata},
+    read_mode::is_sorted,
+    tensor_columns::Columns,
+    utils::{hash_key, unbound_lifetime},
+    Result, TensorBuffers, TensorInfo, TensorOperation, TensorOperationId,
+};
+
+/// What lookups of single entries in the metadata rely on, worked out once per file, so each
+/// lookup is a binary search over the sorted vectors of the metadata even with millions of
+/// entries.
+///
+/// The lookup belongs to a `TensorBuffers`, and what it keeps borrows the file's metadata buffer
+/// or buffers of its own, so it hands them out for its own borrow only.
+#[derive(Default)]
+pub(crate) struct MetadataLookup {
+    /// Whether the tensors and the operations are sorted by id, which strict mode requires. Only
+    /// lenient mode reads files where they aren't, and scans them.
+    sorted: OnceLock<(bool, bool)>,
+    /// The indices of the named operations, sorted by name and then id. Built on the first
+    /// lookup by name, since operation names are not stored sorted.
+    operation_names: OnceLock<Vec<usize>>,
+    /// The tensors' columns, if the file stores its tensors so.
+    columns: OnceLock<Option<Columns<'static>>>,
+    /// Tables of single tensors built from the columns, by index, each with its buffer.
+    entries: Mutex<HashMap<usize, (Vec<u8>, TensorMetadata<'static>)>>,
+    /// The metadata with a table per tensor, with its buffer, built from the columns on the first
+    /// use that visits every tensor.
+    expanded: OnceLock<(Vec<u8>, TensorBuffersMetadata<'static>)>,
+}
+
+impl MetadataLookup {
+    /// Records whether the vectors of `metadata` are sorted, once it passed the checks of the
+    /// read mode. Strict mode rejects unsorted files, so they are only checked in lenient mode.
+    pub fn set_sorted(&self, metadata: &TensorBuffersMetadata, checked: bool) {
+        let sorted = match checked {
+            true => (true, true),
+            false => (
+                match self.columns() {
+                    Some(columns) => is_sorted(columns.ids()),
+                    None => is_sorted(
+                        metadata.tensors().into_iter().flatten().map(|tensor| tensor.id()),
+                    ),
+                },
+                is_sorted(metadata.operations().into_iter().flatten().map(|op| op.id())),
+            ),
+        };
+        let _ = self.sorted.set(sorted);
+    }
+
+    /// Returns whether the tensors are sorted by id, so a failed binary search means the file has
+    /// no such tensor.
+    pub fn tensors_sorted(&self) -> bool {
+        self.sorted.get().is_none_or(|sorted| sorted.0)
+    }
+
+    /// Returns whether the operations are sorted by id.
+    pub fn operations_sorted(&self) -> bool {
+        self.sorted.get().is_none_or(|sorted| sorted.1)
+    }
+
+    /// Records the tensor columns of the file, if it stores its tensors as columns. They borrow
+    /// the metadata buffer, which the file keeps for as long as the lookup.
+    pub fn set_columns(&self, columns: Option<Columns<'static>>) {
+        let _ = self.columns.set(columns);
+    }
+
+    /// Returns the tensor columns of the file, if it stores its tensors as columns.
+    pub fn columns(&self) -> Option<Columns<'_>> {
+        self.columns.get().copied().flatten()
+    }
+
+    /// Returns the number of tensors in `metadata`, without building their tables.
+    pub fn tensor_count(&self, metadata: &TensorBuffersMetadata) -> usize {
+        match self.columns() {
+            Some(columns) => columns.len(),
+            None => metadata.tensors().map_or(0, |tensors| tensors.len()),
+        }
+    }
+
+    /// Returns the table of the tensor at `index` in the columns.
+    pub fn entry(&self, columns: &Columns<'_>, index: usize) -> TensorMetadata<'_> {
+        let mut entries = self.entries.lock().unwrap();
+        let (_, entry) = entries.entry(index).or_insert_with(|| {
+            let buffer = columns.entry_bytes(index);
+            // SAFETY: The entry was just built from the checked columns, and its buffer is kept
+            // next to it, unchanged, until the lookup is dropped.
+            let entry =
+                unsafe { flatbuffers::root_unchecked::<TensorMetadata>(unbound_lifetime(&buffer)) };
+            (buffer, entry)
+        });
+        *entry
+    }
+
+    /// Returns the tables of every tensor in `metadata`, building them from the columns on the
+    /// first call if the file stores its tensors as columns.
+    pub fn tensor_tables<'m>(
+        &'m self,
+        metadata: &TensorBuffersMetadata<'m>,
+    ) -> Option<Vector<'m, ForwardsUOffset<TensorMetadata<'m>>>> {
+        let Some(columns) = self.columns() else {
+            return metadata.tensors();
+        };
+        let (_, expanded) = self.expanded.get_or_init(|| {
+            let buffer = columns.expand(metadata);
+            // SAFETY: The metadata was just built from the checked columns, and its buffer is
+            // kept next to it, unchanged, until the lookup is dropped. It isn't verified, since
+            // files with over a million tensors exceed the verifier's table limit.
+            let expanded = unsafe {
+                flatbuffers::root_unchecked::<TensorBuffersMetadata>(unbound_lifetime(&buffer))
+            };
+            (buffer, expanded)
+        });
+        expanded.tensors()
+    }
+
+    /// Returns the indices of the named operations of `metadata`, sorted by name and then id.
+    pub fn operation_names(&self, metadata: &TensorBuffersMetadata<'_>) -> &[usize] {
+        self.operation_names.get_or_init(|| {
+            let operations = metadata.operations().into_iter().flatten().enumerate();
+            let mut names = operations
+                .filter_map(|(index, op)| Some((op.name()?, op.id(), index)))
+                .collect::<Vec<_>>();
+            names.sort_unstable();
+            names.into_iter().map(|(_, _, index)| index).collect()
+        })
+    }
+}
+
+impl TensorBuffers {
+    /// Returns the number of tensors in the file, without reading their entries.
+    pub async fn tensor_count(&self) -> Result<usize> {
+        let metadata_root = self.get_metadata_root().await?;
+        Ok(self.lookup.tensor_count(&metadata_root))
+    }
+
+    /// Returns the table of every tensor in id order. Files that store their tensors as columns
+    /// build the tables on the first call.
+    pub(crate) async fn tensor_tables(
+        &self,
+    ) -> Result<Option<Vector<'_, ForwardsUOffset<TensorMetadata<'_>>>>> {
+        let metadata_root = self.get_metadata_root().await?;
+        Ok(self.lookup.tensor_tables(&metadata_root))
+    }
+
+    /// Returns the number of operations in the file, without reading their entries.
+    pub async fn operation_count(&self) -> Result<usize> {
+        let metadata_root = self.get_metadata_root().await?;
+        Ok(metadata_root.operations().map_or(0, |operations| operations.len()))
+    }
+
+    /// Returns what the metadata says about the tensor named `tensor_name`, found by a binary
+    /// search over the tensors' ids without decoding any other entry.
+    ///
+    /// # Returns
+    /// An error if the file has no such tensor or the reader can't read it.
+    pub async fn get_tensor_metadata_by_name(&self, tensor_name: &str) -> Result<TensorInfo> {
+        self.get_tensor_metadata(hash_key(tensor_name)).await
+    }
+
+    /// Returns the operation with the given name. Names are optional, so unnamed operations
+    /// can only be found by id. The first lookup by name sorts the names of the operations, and
+    /// later lookups binary search them.
+    pub async fn get_operation_by_name(&self, name: &str) -> Result<TensorOperation> {
+        let metadata_root = self.get_metadata_root().await?;
+        let indices = self.lookup.operation_names(&metadata_root);
+        let operation = |index: usize| metadata_root.operations().unwrap().get(index);
+        let found = indices.partition_point(|&index| operation(index).name() < Some(name));
+        match indices.get(found).map(|&index| operation(index)) {
+            Some(other) if other.name() == Some(name) => {
+                self.get_tensor_operation_by_id(other.id()).await
+            }
+            _ => Err(format!("Operation {} not found in metadata", name).into()),
+        }
+    }
+
+    /// Returns the names of all named operations with their ids, sorted by name.
+    pub async fn get_operation_names(&self) -> Result<Vec<(String, TensorOperationId)>> {
+        let metadata_root = self.get_metadata_root().await?;
+        let indices = self.lookup.operation_names(&metadata_root);
+        let operation = |index: usize| metadata_root.operations().unwrap().get(index);
+        let names = indices.iter().map(|&index| operation(index));
+        Ok(names.map(|op| (op.name().unwrap_or_default().to_string(), op.id())).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::NamedTempFile;
+    use tokio::fs::File;
+
+    use super::*;
+    use crate::{
+        OpenOptions, Operation, ReadMode, Tensor, TensorBuffersWrite, TensorBuffersWriter,
+        WriterOptions,
+    };
+
+    #[tokio::test]
+    async fn test_metadata_lookup() {
+        let names = (0..1000).map(|i| format!("layers.{}.weight", i)).collect::<Vec<_>>();
+        let tensors =
+            names.iter().map(|name| Tensor::new(name, &[1.0f32, 2.0], vec![2])).collect::<Vec<_>>();
+        let operations = (0..100)
+            .map(|i| {
+                TensorOperation::new(i + 1, Operation::None, vec![], tensors[i as usize].id())
+                    .with_name(&format!("op{}", 99 - i))
+            })
+            .collect();
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        TensorBuffersWriter::new(&mut file).write(tensors, operations).await.unwrap();
+
+        let url = format!("file://{}", tmp.path().display());
+        for read_mode in [ReadMode::Strict, ReadMode::Lenient] {
+            let options = OpenOptions::new().with_read_mode(read_mode);
+            let tensor_buffers = TensorBuffers::open_with(&url, options).await.unwrap();
+            assert_eq!(tensor_buffers.tensor_count().await.unwrap(), 1000);
+            assert_eq!(tensor_buffers.operation_count().await.unwrap(), 100);
+            assert!(
+                tensor_buffers.lookup.tensors_sorted() && tensor_buffers.lookup.operations_sorted()
+            );
+
+            let info =
+                tensor_buffers.get_tensor_metadata_by_name("layers.517.weight").await.unwrap();
+            assert_eq!(
+                (info.name.as_str(), info.shape.as_slice()),
+                ("layers.517.weight", &[2][..])
+            );
+            let missing = tensor_buffers.get_tensor_metadata_by_name("missing").await.unwrap_err();
+            assert_eq!(missing.to_string(), "Tensor ID not found in metadata");
+
+            let operation = tensor_buffers.get_operation_by_name("op42").await.unwrap();
+            assert_eq!(operation.id(), 58);
+            let error = tensor_buffers.get_operation_by_name("op100").await.unwrap_err();
+            assert_eq!(error.to_string(), "Operation op100 not found in metadata");
+            let operation_names = tensor_buffers.get_operation_names().await.unwrap();
+            assert_eq!(operation_names.len(), 100);
+            assert_eq!(operation_names[0], ("op0".to_string(), 100));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_columnar_lookup() {
+        let names = (0..1000).map(|i| format!("layers.{}.weight", i)).collect::<Vec<_>>();
+        let tensors =
+            names.iter().map(|name| Tensor::new(name, &[1.0f32, 2.0], vec![2])).collect::<Vec<_>>();
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        let options = WriterOptions::new().with_columnar_metadata(true);
+        let mut writer = TensorBuffersWriter::with_options(&mut file, options);
+        writer.write(tensors, vec![]).await.unwrap();
+
+        let url = format!("file://{}", tmp.path().display());
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        assert_eq!(tensor_buffers.tensor_count().await.unwrap(), 1000);
+        for _ in 0..2 {
+            let info =
+                tensor_buffers.get_tensor_metadata_by_name("layers.517.weight").await.unwrap();
+            assert_eq!(info.name, "layers.517.weight");
+        }
+        // Single lookups build and keep only the tables they need.
+        assert_eq!(tensor_buffers.lookup.entries.lock().unwrap().len(), 1);
+        assert!(tensor_buffers.lookup.expanded.get().is_none());
+
+        let all = tensor_buffers.filter_tensors(|_| true).await.unwrap();
+        assert_eq!(all.len(), 1000);
+        assert!(tensor_buffers.lookup.expanded.get().is_some());
+    }
+}
@@ -0,0 +1,142 @@
+//! Prometheus metrics for reads, remote fetches and the executor cache.
+//!
+//! Metrics are only recorded with the `metrics` feature. Without it the recording functions are
+//! empty, so call sites don't need their own feature checks.
+
+#[cfg(feature = "metrics")]
+mod enabled {
+    use std::sync::LazyLock;
+    #[cfg(feature = "async")]
+    use std::time::Duration;
+
+    use prometheus::{Histogram, HistogramOpts, IntCounter, IntCounterVec, Opts, Registry, Result};
+
+    struct Metrics {
+        bytes_read: IntCounterVec,
+        range_requests: IntCounter,
+        range_request_retries: IntCounter,
+        cache_hits: IntCounter,
+        cache_misses: IntCounter,
+        tensor_load_seconds: Histogram,
+    }
+
+    static METRICS: LazyLock<Metrics> = LazyLock::new(|| Metrics {
+        bytes_read: IntCounterVec::new(
+            Opts::new("tensorbuffers_bytes_read_total", "Bytes read from TensorBuffers files"),
+            &["source"],
+        )
+        .unwrap(),
+        range_requests: IntCounter::new(
+            "tensorbuffers_range_requests_total",
+            "HTTP range requests sent to remote files, including retries",
+        )
+        .unwrap(),
+        range_request_retries: IntCounter::new(
+            "tensorbuffers_range_request_retries_total",
+            "HTTP range requests retried after a transient failure",
+        )
+        .unwrap(),
+        cache_hits: IntCounter::new(
+            "tensorbuffers_cache_hits_total",
+            "Executor results served from the cache",
+        )
+        .unwrap(),
+        cache_misses: IntCounter::new(
+            "tensorbuffers_cache_misses_total",
+            "Executor results looked up but not found in the cache",
+        )
+        .unwrap(),
+        tensor_load_seconds: Histogram::with_opts(HistogramOpts::new(
+            "tensorbuffers_tensor_load_seconds",
+            "Time to load the data of one tensor",
+        ))
+        .unwrap(),
+    });
+
+    /// Registers the crate's metrics with `registry`, e.g. `prometheus::default_registry()`.
+    ///
+    /// Metrics are recorded whether or not they are registered, and can be registered with
+    /// several registries.
+    pub fn register_metrics(registry: &Registry) -> Result<()> {
+        let metrics = &*METRICS;
+        registry.register(Box::new(metrics.bytes_read.clone()))?;
+        registry.register(Box::new(metrics.range_requests.clone()))?;
+        registry.register(Box::new(metrics.range_request_retries.clone()))?;
+        registry.register(Box::new(metrics.cache_hits.clone()))?;
+        registry.register(Box::new(metrics.cache_misses.clone()))?;
+        registry.register(Box::new(metrics.tensor_load_seconds.clone()))?;
+        Ok(())
+    }
+
+    #[cfg(feature = "async")]
+    pub(crate) fn record_bytes_read(source: &str, bytes: usize) {
+        METRICS.bytes_read.with_label_values(&[source]).inc_by(bytes as u64);
+    }
+
+    #[cfg(feature = "async")]
+    pub(crate) fn record_range_request() {
+        METRICS.range_requests.inc();
+    }
+
+    #[cfg(feature = "async")]
+    pub(crate) fn record_range_request_retry() {
+        METRICS.range_request_retries.inc();
+    }
+
+    #[cfg(feature = "async")]
+    pub(crate) fn record_cache_lookup(hit: bool) {
+        match hit {
+            true => METRICS.cache_hits.inc(),
+            false => METRICS.cache_misses.inc(),
+        }
+    }
+
+    #[cfg(feature = "async")]
+    pub(crate) fn record_tensor_load(duration: Duration) {
+        METRICS.tensor_load_seconds.observe(duration.as_secs_f64());
+    }
+}
+
+#[cfg(all(feature = "async", not(feature = "metrics")))]
+mod disabled {
+    use std::time::Duration;
+
+    pub(crate) fn record_bytes_read(_source: &str, _bytes: usize) {}
+
+    pub(crate) fn record_range_request() {}
+
+    pub(crate) fn record_range_request_retry() {}
+
+    pub(crate) fn record_cache_lookup(_hit: bool) {}
+
+    pub(crate) fn record_tensor_load(_duration: Duration) {}
+}
+
+#[cfg(all(feature = "async", not(feature = "metrics")))]
+pub(crate) use disabled::*;
+#[cfg(feature = "metrics")]
+pub use enabled::*;
+
+#[cfg(all(test, feature = "async", feature = "metrics"))]
+mod tests {
+    use prometheus::{Encoder, Registry, TextEncoder};
+
+    use super::*;
+
+    #[test]
+    fn test_register_metrics() {
+        let registry = Registry::new();
+        register_metrics(&registry).unwrap();
+        record_bytes_read("local", 16);
+        record_cache_lookup(true);
+        record_tensor_load(std::time::Duration::from_millis(5));
+
+        let mut text = Vec::new();
+        TextEncoder::new().encode(&registry.gather(), &mut text).unwrap();
+        let text = String::from_utf8(text).unwrap();
+        assert!(text.contains("tensorbuffers_bytes_read_total{source=\"local\"}"));
+        assert!(text.contains("tensorbuffers_cache_hits_total"));
+        assert!(text.contains("tensorbuffers_tensor_load_seconds_count"));
+        assert!(register_metrics(&registry).is_err());
+    }
+}
@@ -0,0 +1,277 @@
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex, OnceLock,
+    },
+    time::Duration,
+};
+
+use bytes::Bytes;
+use tokio::sync::OnceCell;
+use tracing::warn;
+
+use crate::{utils::loggable_url, TensorBuffers};
+
+/// What probing one origin of a remote file found when it was opened, see
+/// `TensorBuffers::mirror_probes`.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MirrorProbe {
+    /// The URL of the origin, the file's own or a mirror's.
+    pub url: String,
+    /// How long it took to answer the request for the file's size.
+    pub latency: Duration,
+    /// How fast it sent a range at the end of the file, in bytes per second.
+    pub bytes_per_second: f64,
+}
+
+/// How many bytes at the end of the file are compared across origins. They hold the 64-byte footer
+/// of 1.4.0 files, and the metadata checksum of files since 1.2.0.
+pub(crate) const FINGERPRINT_SIZE: u64 = 64;
+
+/// Whether an origin is known to serve the same file as the others.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum OriginState {
+    /// Not asked for the file's size and fingerprint yet.
+    Unchecked,
+    /// Reported the same size and fingerprint as the file was opened with.
+    Valid,
+    /// Serves another file, or changed while being read, so it is never read from again.
+    Rejected,
+}
+
+/// The origins one remote file is read from: the URL it was opened with, then its mirrors. Shared
+/// by the file's handles, so an origin found failing is skipped by all of them.
+#[derive(Debug)]
+pub(crate) struct Origins {
+    urls: Vec<String>,
+    states: Mutex<Vec<OriginState>>,
+    /// The ETag each origin reported when it was checked. ETags are specific to a server, so each
+    /// is only compared with later responses of the same origin.
+    etags: Mutex<Vec<Option<String>>>,
+    /// The size and fingerprint reported by the first origin that answered.
+    expected: OnceLock<(u64, Bytes)>,
+    /// The origins in the order they are preferred: the file's own URL, then its mirrors until
+    /// they are probed, then the fastest first. Origins that fail move to the back.
+    order: Mutex<Vec<usize>>,
+    /// How many of the preferred origins requests take turns between, 0 to neither probe nor
+    /// spread them.
+    spread: usize,
+    /// Counts requests, to take turns between the preferred origins.
+    next: AtomicUsize,
+    probes: OnceCell<Vec<MirrorProbe>>,
+}
+
+impl Origins {
+    pub fn new(url: &str, mirrors: &[String], spread: usize) -> Self {
+        let urls = std::iter::once(url.to_string()).chain(mirrors.iter().cloned()).collect();
+        Origins {
+            states: Mutex::new(vec![OriginState::Unchecked; mirrors.len() + 1]),
+            etags: Mutex::new(vec![None; mirrors.len() + 1]),
+            urls,
+            expected: OnceLock::new(),
+            order: Mutex::new((0..=mirrors.len()).collect()),
+            spread,
+            next: AtomicUsize::new(0),
+            probes: OnceCell::new(),
+        }
+    }
+
+    /// Returns the URL the file was opened with.
+    pub fn primary(&self) -> &str {
+        &self.urls[0]
+    }
+
+    pub fn url(&self, index: usize) -> &str {
+        &self.urls[index]
+    }
+
+    pub fn len(&self) -> usize {
+        self.urls.len()
+    }
+
+    pub fn state(&self, index: usize) -> OriginState {
+        self.states.lock().unwrap()[index]
+    }
+
+    /// Returns whether the origins are probed when the file is opened.
+    pub fn balanced(&self) -> bool {
+        self.spread > 0 && self.urls.len() > 1
+    }
+
+    /// Returns the origins to try a request on, leaving out those that serve another file: one of
+    /// the preferred origins, taking turns, then the others in order.
+    pub fn candidates(&self) -> Vec<usize> {
+        let states = self.states.lock().unwrap();
+        let mut candidates = self.order.lock().unwrap().clone();
+        candidates.retain(|&index| states[index] != OriginState::Rejected);
+        let preferred = self.spread.min(candidates.len());
+        if preferred > 1 {
+            let turn = self.next.fetch_add(1, Ordering::Relaxed) % preferred;
+            candidates[..=turn].rotate_right(1);
+        }
+        candidates
+    }
+
+    /// Moves an origin that failed behind the others.
+    pub fn demote(&self, index: usize) {
+        let mut order = self.order.lock().unwrap();
+        order.retain(|&other| other != index);
+        order.push(index);
+    }
+
+    /// Returns the size and fingerprint of the file, once an origin reported them.
+    pub fn expected(&self) -> Option<&(u64, Bytes)> {
+        self.expected.get()
+    }
+
+    /// Returns the ETag an origin reported when it was checked, if any.
+    pub fn etag(&self, index: usize) -> Option<String> {
+        self.etags.lock().unwrap()[index].clone()
+    }
+
+    /// Checks the size and fingerprint, the last `FINGERPRINT_SIZE` bytes, an origin reported
+    /// against those of the file, which the first origin to answer defines. The origin's ETag is
+    /// kept to notice it changing later, see `pinned`.
+    ///
+    /// # Returns
+    /// Whether the origin serves the same file. If not, it is never tried again.
+    pub fn check(&self, index: usize, size: u64, fingerprint: Bytes, etag: Option<String>) -> bool {
+        let (expected_size, expected_fingerprint) =
+            self.expected.get_or_init(|| (size, fingerprint.clone()));
+        let valid = *expected_size == size && *expected_fingerprint == fingerprint;
+        if !valid {
+            warn!(
+                url = loggable_url(self.url(index)),
+                size, expected_size, "Mirror serves a different file, not reading from it"
+            );
+        }
+        self.etags.lock().unwrap()[index] = etag;
+        self.states.lock().unwrap()[index] =
+            if valid { OriginState::Valid } else { OriginState::Rejected };
+        valid
+    }
+
+    /// Checks the ETag of a response from an origin against the one it reported when it was
+    /// checked. ETags are only compared when both were sent.
+    ///
+    /// # Returns
+    /// Whether the origin still serves the same file. If not, it is never tried again.
+    pub fn pinned(&self, index: usize, etag: Option<&str>) -> bool {
+        let unchanged = match (self.etags.lock().unwrap()[index].as_deref(), etag) {
+            (Some(pinned), Some(etag)) => pinned == etag,
+            _ => true,
+        };
+        if !unchanged {
+            warn!(
+                url = loggable_url(self.url(index)),
+                "Origin changed while reading, not reading from it"
+            );
+            self.states.lock().unwrap()[index] = OriginState::Rejected;
+        }
+        unchanged
+    }
+
+    /// Runs `probe` the first time any handle of the file calls this, and prefers the origins it
+    /// measured fastest first. Origins it didn't measure follow in order.
+    pub async fn probe_once(&self, probe: impl Future<Output = Vec<(usize, MirrorProbe)>>) {
+        self.probes
+            .get_or_init(|| async {
+                let mut probes = probe.await;
+                probes.sort_by(|(_, a), (_, b)| b.bytes_per_second.total_cmp(&a.bytes_per_second));
+                let mut order = probes.iter().map(|&(index, _)| index).collect::<Vec<_>>();
+                let unprobed = (0..self.urls.len()).filter(|index| !order.contains(index));
+                order.extend(unprobed.collect::<Vec<_>>());
+                *self.order.lock().unwrap() = order;
+                probes.into_iter().map(|(_, probe)| probe).collect()
+            })
+            .await;
+    }
+
+    /// Returns the origins probed, fastest first.
+    pub fn probes(&self) -> &[MirrorProbe] {
+        self.probes.get().map_or(&[], Vec::as_slice)
+    }
+}
+
+impl TensorBuffers {
+    /// Returns what probing the file's URL and mirrors found when it was opened, fastest first,
+    /// so applications can log which origins serve them. Empty unless the file was opened with
+    /// `OpenOptions::with_balanced_mirrors`.
+    pub fn mirror_probes(&self) -> Vec<MirrorProbe> {
+        self.options().bound_origins().map_or_else(Vec::new, |origins| origins.probes().to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::OpenOptions;
+
+    #[test]
+    fn test_origins() {
+        let mirrors = ["https://b/m.tb".to_string(), "https://c/m.tb".to_string()];
+        let origins = Origins::new("https://a/m.tb", &mirrors, 0);
+        assert_eq!(origins.primary(), "https://a/m.tb");
+        assert_eq!(origins.candidates(), [0, 1, 2]);
+        assert_eq!(origins.expected(), None);
+
+        let footer = Bytes::from_static(b"footer TBSF");
+        assert!(origins.check(0, 100, footer.clone(), Some("\"v1\"".to_string())));
+        // ETags differ across servers, so a mirror is checked on its size and footer only.
+        assert!(origins.check(2, 100, footer.clone(), Some("\"c-7\"".to_string())));
+        assert!(!origins.check(1, 100, Bytes::from_static(b"other TBSF"), None));
+        assert_eq!(origins.state(1), OriginState::Rejected);
+        assert_eq!(origins.candidates(), [0, 2]);
+
+        origins.demote(0);
+        assert_eq!(origins.candidates(), [2, 0]);
+        assert!(!origins.check(0, 99, footer.clone(), Some("\"v1\"".to_string())));
+        assert_eq!(origins.candidates(), [2]);
+        assert_eq!(origins.expected(), Some(&(100, footer)));
+
+        // Each origin is pinned to its own ETag while the file is read.
+        assert_eq!(origins.etag(2).as_deref(), Some("\"c-7\""));
+        assert!(origins.pinned(2, Some("\"c-7\"")));
+        assert!(origins.pinned(2, None));
+        assert!(!origins.pinned(2, Some("\"v1\"")));
+        assert_eq!(origins.state(2), OriginState::Rejected);
+        assert!(origins.candidates().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_origins_spread() {
+        let mirrors = ["https://b/m.tb".to_string(), "https://c/m.tb".to_string()];
+        let origins = Origins::new("https://a/m.tb", &mirrors, 2);
+        assert!(origins.balanced());
+        assert!(!Origins::new("https://a/m.tb", &[], 2).balanced());
+        let probe = |index: usize, bytes_per_second: f64| {
+            let url = origins.url(index).to_string();
+            (index, MirrorProbe { url, latency: Duration::from_millis(5), bytes_per_second })
+        };
+        // The file's own URL didn't answer, so it isn't ranked. Only the first probe counts.
+        let (slow, fast, other) = (probe(1, 1e6), probe(2, 4e6), probe(0, 9e9));
+        origins.probe_once(async { vec![slow, fast] }).await;
+        origins.probe_once(async { vec![other] }).await;
+        let urls = origins.probes().iter().map(|probe| probe.url.as_str()).collect::<Vec<_>>();
+        assert_eq!(urls, ["https://c/m.tb", "https://b/m.tb"]);
+
+        // Requests take turns between the two fastest, and fail over to the rest.
+        assert_eq!(origins.candidates(), [2, 1, 0]);
+        assert_eq!(origins.candidates(), [1, 2, 0]);
+        assert_eq!(origins.candidates(), [2, 1, 0]);
+        origins.demote(2);
+        assert_eq!(origins.candidates(), [0, 1, 2]);
+        assert_eq!(origins.candidates(), [1, 0, 2]);
+    }
+
+    #[tokio::test]
+    async fn test_mirrors_must_be_https() {
+        let mirrors = vec!["http://mirror.example/m.tb".to_string()];
+        let options = OpenOptions::new().with_mirrors(mirrors);
+        let error =
+            TensorBuffers::open_with("https://example.com/m.tb", options).await.err().unwrap();
+        assert!(error.to_string().contains("not an https:// URL"));
+    }
+}
@@ -0,0 +1,234 @@
+use std::{fs::File, ops::Range, path::Path};
+
+use bytemuck::{try_cast_slice, Pod};
+use flatbuffers::FLATBUFFERS_MAX_BUFFER_SIZE;
+use memmap2::Mmap;
+
+use crate::{
+    codec,
+    footer::decompress_metadata,
+    generated::tensor_buffers::{Compression, TensorBuffersMetadata, TensorMetadata},
+    read_mode::find_tensor,
+    slice_file::{check_data_layout, check_data_type, locate_metadata, parse_metadata},
+    tensor::stored_shape,
+    tensor_columns::expand_columns,
+    utils::hash_key,
+    Num, Result, Tensor, TensorInfo,
+};
+
+/// A local TensorBuffers file mapped into memory and read synchronously, the hot path for
+/// inference servers that restart often. Opening maps the file and parses and checks the metadata
+/// once; [`MmapTensorBuffers::get`] then returns tensor data straight from the map, without copies,
+/// allocations or an async runtime.
+///
+/// Tensors must be stored uncompressed and aligned for their element type, which
+/// `WriterOptions::with_alignment` ensures. Checksums aren't verified on reads, since that would
+/// touch every page of the data; `verify` checks them on demand.
+///
+/// Like every memory map, the reader assumes the file isn't truncated or modified while it is
+/// open. Replace files by renaming a new file over them instead.
+///
+/// ```no_run
+/// use tensorbuffers::MmapTensorBuffers;
+///
+/// let weights = MmapTensorBuffers::open("model.tb").unwrap();
+/// let embedding: &[f32] = weights.get("embed.weight").unwrap();
+/// ```
+pub struct MmapTensorBuffers {
+    mmap: Mmap,
+    /// The metadata if it is stored compressed or as columns, decompressed or rebuilt with a
+    /// table per tensor on open. Otherwise it is read from the map.
+    owned_metadata: Option<Vec<u8>>,
+    /// Where the metadata is stored in the file.
+    metadata_range: Range<usize>,
+}
+
+impl MmapTensorBuffers {
+    /// Opens and maps a file, then parses its metadata and checks that the data of every tensor
+    /// lies within the file.
+    ///
+    /// # Arguments
+    /// * `path` - The path of the file.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path)
+            .map_err(|error| format!("Failed to open {}: {}", path.display(), error))?;
+        // SAFETY: The map is read-only, and the file must not change while it is mapped, as the
+        // type's documentation says.
+        let mmap = unsafe { Mmap::map(&file)? };
+        let (footer, metadata_range) = locate_metadata(&mmap)?;
+        let mut owned_metadata = match footer.compressed_metadata {
+            true => Some(decompress_metadata(
+                &mmap[metadata_range.clone()],
+                FLATBUFFERS_MAX_BUFFER_SIZE,
+            )?),
+            false => None,
+        };
+        let stored = match &owned_metadata {
+            Some(metadata) => metadata,
+            None => &mmap[metadata_range.clone()],
+        };
+        let expanded = expand_columns(&parse_metadata(stored)?)?;
+        if expanded.is_some() {
+            owned_metadata = expanded;
+        }
+        let tensor_buffers = MmapTensorBuffers { mmap, owned_metadata, metadata_range };
+
+        let data_end = tensor_buffers.metadata_range.start as u64;
+        for tensor in tensor_buffers.metadata().tensors().into_iter().flatten() {
+            if tensor.external().is_none() {
+                check_data_layout(&tensor, data_end)?;
+            }
+        }
+        Ok(tensor_buffers)
+    }
+
+    /// Returns a tensor's data, borrowed from the map.
+    ///
+    /// # Arguments
+    /// * `name` - The name of the tensor, stored with element type `T`. Tensors of extension
+    ///   types are read as `u8`.
+    ///
+    /// # Returns
+    /// An error if the tensor is missing, of another type, compressed, stored in another file or
+    /// not aligned for `T`.
+    pub fn get<T>(&self, name: &str) -> Result<&[T]>
+    where
+        T: Pod + Num,
+    {
+        let metadata = self.find(name)?;
+        check_data_type::<T>(&metadata)?;
+        if metadata.external().is_some() {
+            return Err(format!("Tensor {} is stored in another file", name).into());
+        }
+        if metadata.compression() != Compression::None {
+            return Err(format!("Tensor {} is compressed and can't be borrowed", name).into());
+        }
+        // `open` checked that the data lies within the file.
+        let offset = metadata.data_offset() as usize;
+        let bytes = &self.mmap[offset..offset + metadata.data_size() as usize];
+        try_cast_slice(bytes).map_err(|_| {
+            format!(
+                "Tensor {} at offset {} isn't aligned for its elements; write the file with \
+                 WriterOptions::with_alignment",
+                name, offset
+            )
+            .into()
+        })
+    }
+
+    /// Returns a tensor borrowing its data from the map, see [`MmapTensorBuffers::get`].
+    pub fn tensor<T>(&self, name: &str) -> Result<Tensor<'_, T>>
+    where
+        T: Pod + Num,
+    {
+        let data = self.get::<T>(name)?;
+        let metadata = self.find(name)?;
+        Ok(Tensor::new(metadata.name(), data, stored_shape(&metadata)?))
+    }
+
+    /// Returns what the metadata says about a tensor.
+    pub fn tensor_info(&self, name: &str) -> Result<TensorInfo> {
+        TensorInfo::from_metadata(&self.find(name)?)
+    }
+
+    /// Returns the names of the tensors in the file, in id order.
+    pub fn tensor_names(&self) -> impl Iterator<Item = &str> {
+        self.metadata().tensors().into_iter().flatten().map(|tensor| tensor.name())
+    }
+
+    /// Returns the format version of the file.
+    pub fn version(&self) -> &str {
+        self.metadata().version()
+    }
+
+    /// Checks the data of every tensor stored in the file against its checksum, reading all of
+    /// it.
+    pub fn verify(&self) -> Result<()> {
+        for tensor in self.metadata().tensors().into_iter().flatten() {
+            if tensor.external().is_none() {
+                let offset = tensor.data_offset() as usize;
+                let stored = &self.mmap[offset..offset + codec::stored_size(&tensor)];
+                codec::verify_checksum(&tensor, stored)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn metadata_bytes(&self) -> &[u8] {
+        match &self.owned_metadata {
+            Some(metadata) => metadata,
+            None => &self.mmap[self.metadata_range.clone()],
+        }
+    }
+
+    fn metadata(&self) -> TensorBuffersMetadata<'_> {
+        // SAFETY: `open` verified the metadata or built it from verified columns, and neither it
+        // nor the map change afterwards.
+        unsafe { flatbuffers::root_unchecked::<TensorBuffersMetadata>(self.metadata_bytes()) }
+    }
+
+    fn find(&self, name: &str) -> Result<TensorMetadata<'_>> {
+        find_tensor(&self.metadata(), hash_key(name))
+            .ok_or_else(|| format!("Tensor {} not found", name).into())
+    }
+}
+
+#[cfg(all(test, feature = "async"))]
+mod tests {
+    use tempfile::NamedTempFile;
+    use tokio::fs::File;
+
+    use super::*;
+    use crate::{TensorBuffersWrite, TensorBuffersWriter, WriterOptions};
+
+    #[tokio::test]
+    async fn test_mmap_tensor_buffers() {
+        let weights = (0..64).map(|i| i as f64).collect::<Vec<_>>();
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        let options = WriterOptions::new().with_alignment(64).with_compressed_metadata(true);
+        let tensors = vec![
+            Tensor::new("w", weights.as_slice(), vec![8, 8]),
+            Tensor::new("b", &[0.5], vec![1]),
+        ];
+        TensorBuffersWriter::with_options(&mut file, options).write(tensors, vec![]).await.unwrap();
+
+        let mmap = MmapTensorBuffers::open(tmp.path()).unwrap();
+        assert_eq!(mmap.get::<f64>("w").unwrap(), weights);
+        assert_eq!(mmap.get::<f64>("b").unwrap(), [0.5]);
+        let w = mmap.tensor::<f64>("w").unwrap();
+        assert_eq!((w.name(), w.shape()), ("w", &[8, 8][..]));
+        assert_eq!(mmap.tensor_info("w").unwrap().data_size, 512);
+        assert_eq!(mmap.tensor_names().count(), 2);
+        assert!(mmap.get::<f32>("w").is_err());
+        assert_eq!(mmap.get::<f64>("missing").unwrap_err().to_string(), "Tensor missing not found");
+        mmap.verify().unwrap();
+
+        // Compressed tensors can't be borrowed from the map.
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        let options = WriterOptions::new().with_compression(Compression::Zstd, 3);
+        let tensor = Tensor::new("w", weights.as_slice(), vec![8, 8]);
+        TensorBuffersWriter::with_options(&mut file, options)
+            .write(vec![tensor], vec![])
+            .await
+            .unwrap();
+        let mmap = MmapTensorBuffers::open(tmp.path()).unwrap();
+        assert_eq!(
+            mmap.get::<f64>("w").unwrap_err().to_string(),
+            "Tensor w is compressed and can't be borrowed"
+        );
+        assert!(MmapTensorBuffers::open("/missing.tb").is_err());
+
+        // Columnar metadata is rebuilt with a table per tensor on open.
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        let options = WriterOptions::new().with_alignment(64).with_columnar_metadata(true);
+        let tensors = vec![Tensor::new("w", weights.as_slice(), vec![8, 8])];
+        TensorBuffersWriter::with_options(&mut file, options).write(tensors, vec![]).await.unwrap();
+        let mmap = MmapTensorBuffers::open(tmp.path()).unwrap();
+        assert_eq!(mmap.get::<f64>("w").unwrap(), weights);
+        assert_eq!(mmap.tensor_names().collect::<Vec<_>>(), ["w"]);
+    }
+}
@@ -0,0 +1,298 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use flatbuffers::{FlatBufferBuilder, WIPOffset};
+use tokio::sync::OnceCell;
+
+use crate::{
+    codec::StoredData,
+    constants::{MAGIC_BYTES, NAME_INDEX_MAGIC_BYTES},
+    footer::{Footer, MAX_FOOTER_SIZE},
+    generated::tensor_buffers::TensorMetadata,
+    num_trait::DataType,
+    tensor::build_tensor_table,
+    utils::unbound_lifetime,
+    QuantizationParams, Result, TensorBuffers, TensorId,
+};
+
+/// Size of an index entry: the tensor id (u64), the offset (u64) and size (u32) of its record.
+const ENTRY_SIZE: usize = 20;
+
+/// Size of the trailer that ends the index: the number of entries (u32) and the magic bytes.
+const TRAILER_SIZE: usize = 8;
+
+/// A tensor to list in the metadata and the name index, described by its parts.
+#[derive(Clone, Copy)]
+pub(crate) struct IndexedTensor<'t> {
+    pub id: TensorId,
+    pub name: &'t str,
+    pub data_type: DataType,
+    pub shape: &'t [usize],
+    pub data_size: usize,
+    pub stored: StoredData,
+    /// The location of the file the data is stored in, if not this one.
+    pub external: Option<&'t str>,
+    /// The name of the tensor whose data this alias shares, if it is one.
+    pub alias_of: Option<&'t str>,
+    /// Application-defined metadata of the tensor, if any.
+    pub user_metadata: Option<&'t [u8]>,
+    /// How the tensor's values were quantized, if they were.
+    pub quantization: Option<&'t QuantizationParams>,
+    /// Where the quantization's scales are stored in the file, if not in the metadata.
+    pub scales_offset: Option<u64>,
+}
+
+impl IndexedTensor<'_> {
+    /// Builds the tensor's metadata table.
+    pub fn build_table<'b>(
+        &self,
+        builder: &mut FlatBufferBuilder<'b>,
+    ) -> WIPOffset<TensorMetadata<'b>> {
+        build_tensor_table(
+            builder,
+            self.id,
+            self.name,
+            self.data_type,
+            self.shape,
+            self.data_size,
+            &self.stored,
+            self.external,
+            self.alias_of,
+            self.user_metadata,
+            self.quantization,
+            self.scales_offset,
+        )
+    }
+}
+
+/// Encodes the name index section, which lets readers find one tensor's metadata with a few small
+/// reads instead of loading the whole metadata.
+///
+/// The section holds each tensor's metadata as a FlatBuffer of its own, followed by entries
+/// sorted by tensor id that locate them, and a trailer with the number of entries and
+/// `NAME_INDEX_MAGIC_BYTES`. It goes right before the metadata, so readers find it from the footer.
+///
+/// # Arguments
+/// * `start` - Where the section starts in the file.
+/// * `tensors` - The tensors to index, in any order. Their ids must be unique.
+pub(crate) fn encode_name_index(start: u64, tensors: &mut [IndexedTensor<'_>]) -> Vec<u8> {
+    tensors.sort_by_key(|tensor| tensor.id);
+    let mut section = Vec::new();
+    let mut entries = Vec::with_capacity(tensors.len() * ENTRY_SIZE + TRAILER_SIZE);
+    let mut builder = FlatBufferBuilder::new();
+    for tensor in tensors.iter() {
+        builder.reset();
+        let table = tensor.build_table(&mut builder);
+        builder.finish(table, None);
+        let record = builder.finished_data();
+        entries.extend_from_slice(&tensor.id.to_le_bytes());
+        entries.extend_from_slice(&(start + section.len() as u64).to_le_bytes());
+        entries.extend_from_slice(&(record.len() as u32).to_le_bytes());
+        section.extend_from_slice(record);
+    }
+    entries.extend_from_slice(&(tensors.len() as u32).to_le_bytes());
+    entries.extend_from_slice(NAME_INDEX_MAGIC_BYTES);
+    section.extend_from_slice(&entries);
+    section
+}
+
+/// Where a file's name index entries are.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct NameIndex {
+    entries_offset: u64,
+    count: usize,
+}
+
+impl NameIndex {
+    /// Parses the trailer that ends the name index.
+    ///
+    /// # Arguments
+    /// * `trailer` - The `TRAILER_SIZE` bytes before the metadata.
+    /// * `end` - Where the metadata, and so the index, starts.
+    ///
+    /// # Returns
+    /// `None` if the file has no name index.
+    fn parse(trailer: &[u8], end: u64) -> Result<Option<NameIndex>> {
+        if trailer[4..] != *NAME_INDEX_MAGIC_BYTES {
+            return Ok(None);
+        }
+        let count = u32::from_le_bytes(trailer[..4].try_into()?) as usize;
+        let entries_size = (count * ENTRY_SIZE + TRAILER_SIZE) as u64;
+        match end.checked_sub(entries_size) {
+            Some(entries_offset) if entries_offset >= MAGIC_BYTES.len() as u64 => {
+                Ok(Some(NameIndex { entries_offset, count }))
+            }
+            _ => {
+                Err(format!("Name index of {} entries doesn't fit before the metadata", count)
+                    .into())
+            }
+        }
+    }
+}
+
+/// Parses an index entry into the tensor id and the offset and size of its record.
+fn parse_entry(entry: &[u8]) -> Result<(TensorId, u64, usize)> {
+    let id = u64::from_le_bytes(entry[..8].try_into()?);
+    let offset = u64::from_le_bytes(entry[8..16].try_into()?);
+    let size = u32::from_le_bytes(entry[16..20].try_into()?) as usize;
+    Ok((id, offset, size))
+}
+
+/// A file's name index and the tensor metadata looked up through it, each table with the record
+/// it was read from.
+#[derive(Default)]
+pub(crate) struct NameIndexCache {
+    index: OnceCell<Option<NameIndex>>,
+    tensors: Mutex<HashMap<TensorId, (Vec<u8>, TensorMetadata<'static>)>>,
+}
+
+impl NameIndexCache {
+    /// Looks up a tensor by id with a binary search over the name index, reading one entry at a
+    /// time and then the tensor's record.
+    ///
+    /// # Returns
+    /// `None` if the file has no name index, otherwise whether it lists the tensor.
+    pub async fn find(
+        &self,
+        tensor_buffers: &TensorBuffers,
+        tensor_id: TensorId,
+    ) -> Result<Option<Option<TensorMetadata<'_>>>> {
+        let index = self.index.get_or_try_init(|| load_index(tensor_buffers)).await?;
+        let Some(index) = *index else {
+            return Ok(None);
+        };
+        if let Some((_, metadata)) = self.tensors.lock().unwrap().get(&tensor_id) {
+            return Ok(Some(Some(*metadata)));
+        }
+
+        let (mut low, mut high) = (0, index.count);
+        while low < high {
+            let middle = (low + high) / 2;
+            let offset = index.entries_offset + (middle * ENTRY_SIZE) as u64;
+            let (id, record_offset, record_size) =
+                parse_entry(&tensor_buffers.read_bytes(offset, ENTRY_SIZE).await?)?;
+            if id < tensor_id {
+                low = middle + 1;
+            } else if id > tensor_id {
+                high = middle;
+            } else {
+                if record_offset + record_size as u64 > index.entries_offset {
+                    return Err(
+                        format!("Name index entry for tensor {:#x} is out of bounds", id).into()
+                    );
+                }
+                let record = tensor_buffers.read_bytes(record_offset, record_size).await?;
+                // SAFETY: The record is kept next to its table, unchanged, until the cache is
+                // dropped.
+                let stored = unsafe { unbound_lifetime(&record) };
+                let metadata = flatbuffers::root::<TensorMetadata>(stored)
+                    .map_err(|_| format!("Invalid name index record for tensor {:#x}", id))?;
+                if metadata.id() != tensor_id {
+                    return Err(format!(
+                        "Name index entry for tensor {:#x} points at tensor {:#x}",
+                        tensor_id,
+                        metadata.id()
+                    )
+                    .into());
+                }
+                self.tensors.lock().unwrap().insert(tensor_id, (record, metadata));
+                return Ok(Some(Some(metadata)));
+            }
+        }
+        Ok(Some(None))
+    }
+}
+
+/// Reads the footer and the bytes before the metadata to find the name index, if there is one.
+async fn load_index(tensor_buffers: &TensorBuffers) -> Result<Option<NameIndex>> {
+    let file_size = tensor_buffers.file_size().await?;
+    let end_size = file_size.min(MAX_FOOTER_SIZE as u64);
+    let end = tensor_buffers.read_bytes(file_size - end_size, end_size as usize).await?;
+    let footer = Footer::parse(&end, file_size)?;
+    let metadata_start = file_size - (footer.size() + footer.metadata_size) as u64;
+    if metadata_start < (MAGIC_BYTES.len() + TRAILER_SIZE) as u64 {
+        return Ok(None);
+    }
+    let trailer_offset = metadata_start - TRAILER_SIZE as u64;
+    let trailer = tensor_buffers.read_bytes(trailer_offset, TRAILER_SIZE).await?;
+    NameIndex::parse(&trailer, metadata_start)
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::NamedTempFile;
+    use tokio::fs::File;
+
+    use super::*;
+    use crate::{
+        utils::hash_key, OpenOptions, Tensor, TensorBuffersWrite, TensorBuffersWriter,
+        WriterOptions,
+    };
+
+    #[test]
+    fn test_encode_name_index() {
+        let mut tensors = ["b", "a"].map(|name| IndexedTensor {
+            id: hash_key(name),
+            name,
+            data_type: DataType::UInt8,
+            shape: &[2],
+            data_size: 2,
+            stored: StoredData::raw(4, 2),
+            external: None,
+            alias_of: None,
+            user_metadata: None,
+            quantization: None,
+            scales_offset: None,
+        });
+        let section = encode_name_index(100, &mut tensors);
+        let end = 100 + section.len() as u64;
+        let trailer = &section[section.len() - TRAILER_SIZE..];
+        let index = NameIndex::parse(trailer, end).unwrap().unwrap();
+        assert_eq!(index.count, 2);
+
+        let entries_start = (index.entries_offset - 100) as usize;
+        let mut ids = Vec::new();
+        for entry in section[entries_start..].chunks(ENTRY_SIZE).take(2) {
+            let (id, offset, size) = parse_entry(entry).unwrap();
+            let start = (offset - 100) as usize;
+            let record = flatbuffers::root::<TensorMetadata>(&section[start..start + size]);
+            assert_eq!(record.unwrap().id(), id);
+            ids.push(id);
+        }
+        assert!(ids.is_sorted());
+
+        assert_eq!(NameIndex::parse(b"\0\0\0\0TBS1", end).unwrap(), None);
+        assert!(NameIndex::parse(trailer, 20).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_find_by_name_index() {
+        let values = (0..64).map(|i| i as f32).collect::<Vec<_>>();
+        let names = (0..100).map(|i| format!("layer.{}", i)).collect::<Vec<_>>();
+        let tensors = names.iter().map(|name| Tensor::new(name, &values, vec![8, 8])).collect();
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        let options = WriterOptions::new().with_name_index(true);
+        let mut writer = TensorBuffersWriter::with_options(&mut file, options);
+        writer.write(tensors, vec![]).await.unwrap();
+
+        // Corrupt the metadata, which fails any read that loads it, but not indexed lookups.
+        let mut bytes = std::fs::read(tmp.path()).unwrap();
+        let footer = Footer::parse(&bytes[bytes.len() - MAX_FOOTER_SIZE..], bytes.len() as u64);
+        let summary = footer.unwrap().summary.unwrap();
+        bytes[summary.metadata_offset as usize + summary.metadata_size / 2] ^= 1;
+        std::fs::write(tmp.path(), &bytes).unwrap();
+
+        let url = format!("file://{}", tmp.path().display());
+        let options = OpenOptions::new().with_name_index(true);
+        let tensor_buffers = TensorBuffers::open_with(&url, options).await.unwrap();
+        let tensor = tensor_buffers.get_tensor_data_by_name::<f32>("layer.42").await.unwrap();
+        assert_eq!((tensor.name(), tensor.data()), ("layer.42", &values[..]));
+        let error = tensor_buffers.get_tensor_data_by_name::<f32>("missing").await.unwrap_err();
+        assert_eq!(error.to_string(), "Tensor ID not found in metadata");
+        assert!(tensor_buffers.get_metadata_root().await.is_err());
+
+        // Without the option, the metadata is loaded as usual.
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        assert!(tensor_buffers.get_tensor_data_by_name::<f32>("layer.42").await.is_err());
+    }
+}
@@ -4,9 +4,12 @@ use core::{
     ops::{Add, Div, Mul, Neg, Sub},
 };
 
-use crate::generated;
+use half::f16;
+
+use crate::{generated, ExtensionType};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DataType {
     Int8,
     Int16,
@@ -16,8 +19,67 @@ pub enum DataType {
     UInt16,
     UInt32,
     UInt64,
+    Float16,
     Float32,
     Float64,
+    /// An experimental type stored as the raw bytes of its elements, see [`ExtensionType`].
+    Extension(ExtensionType),
+    /// GGML's Q8_0 blocks of 32 values, each an f16 scale followed by 32 signed bytes, stored as
+    /// the raw bytes of the blocks.
+    Q8_0,
+    /// GGML's Q4_K super-blocks of 256 values: an f16 scale and min, the 6-bit scales and mins of
+    /// eight 32-value sub-blocks relative to them, then the 4-bit values, stored as the raw bytes
+    /// of the blocks.
+    Q4K,
+}
+
+impl DataType {
+    /// Returns the size of one element in bytes, or of one block of `block_size` elements for
+    /// block-quantized types.
+    pub fn size(&self) -> usize {
+        match self {
+            DataType::Int8 | DataType::UInt8 => 1,
+            DataType::Int16 | DataType::UInt16 | DataType::Float16 => 2,
+            DataType::Int32 | DataType::UInt32 | DataType::Float32 => 4,
+            DataType::Int64 | DataType::UInt64 | DataType::Float64 => 8,
+            DataType::Extension(extension) => extension.element_size(),
+            DataType::Q8_0 => 34,
+            DataType::Q4K => 144,
+        }
+    }
+
+    /// Returns the number of elements stored together in a block, which is 1 unless the type is
+    /// block-quantized.
+    pub fn block_size(&self) -> usize {
+        match self {
+            DataType::Q8_0 => 32,
+            DataType::Q4K => 256,
+            _ => 1,
+        }
+    }
+
+    /// Returns whether elements are stored in blocks that share their scales.
+    pub fn is_block_quantized(&self) -> bool {
+        self.block_size() > 1
+    }
+
+    /// Returns whether tensors of the type hold raw bytes, loaded as `u8`, rather than elements
+    /// of a numeric type.
+    pub(crate) fn is_raw(&self) -> bool {
+        matches!(self, DataType::Extension(_)) || self.is_block_quantized()
+    }
+
+    /// Returns the size in bytes of `elements` elements.
+    ///
+    /// # Returns
+    /// `None` if the elements aren't a whole number of blocks, or their size overflows.
+    pub(crate) fn data_size(&self, elements: u64) -> Option<u64> {
+        let block_size = self.block_size() as u64;
+        match elements % block_size {
+            0 => (elements / block_size).checked_mul(self.size() as u64),
+            _ => None,
+        }
+    }
 }
 
 impl Into<generated::tensor_buffers::DataType> for DataType {
@@ -31,8 +93,35 @@ impl Into<generated::tensor_buffers::DataType> for DataType {
             DataType::UInt16 => generated::tensor_buffers::DataType::UInt16,
             DataType::UInt32 => generated::tensor_buffers::DataType::UInt32,
             DataType::UInt64 => generated::tensor_buffers::DataType::UInt64,
+            DataType::Float16 => generated::tensor_buffers::DataType::Float16,
             DataType::Float32 => generated::tensor_buffers::DataType::Float32,
             DataType::Float64 => generated::tensor_buffers::DataType::Float64,
+            DataType::Extension(_) => generated::tensor_buffers::DataType::Extension,
+            DataType::Q8_0 => generated::tensor_buffers::DataType::Q8_0,
+            DataType::Q4K => generated::tensor_buffers::DataType::Q4_K,
+        }
+    }
+}
+
+impl TryFrom<generated::tensor_buffers::DataType> for DataType {
+    type Error = String;
+
+    fn try_from(value: generated::tensor_buffers::DataType) -> Result<Self, Self::Error> {
+        match value {
+            generated::tensor_buffers::DataType::Int8 => Ok(DataType::Int8),
+            generated::tensor_buffers::DataType::Int16 => Ok(DataType::Int16),
+            generated::tensor_buffers::DataType::Int32 => Ok(DataType::Int32),
+            generated::tensor_buffers::DataType::Int64 => Ok(DataType::Int64),
+            generated::tensor_buffers::DataType::UInt8 => Ok(DataType::UInt8),
+            generated::tensor_buffers::DataType::UInt16 => Ok(DataType::UInt16),
+            generated::tensor_buffers::DataType::UInt32 => Ok(DataType::UInt32),
+            generated::tensor_buffers::DataType::UInt64 => Ok(DataType::UInt64),
+            generated::tensor_buffers::DataType::Float16 => Ok(DataType::Float16),
+            generated::tensor_buffers::DataType::Float32 => Ok(DataType::Float32),
+            generated::tensor_buffers::DataType::Float64 => Ok(DataType::Float64),
+            generated::tensor_buffers::DataType::Q8_0 => Ok(DataType::Q8_0),
+            generated::tensor_buffers::DataType::Q4_K => Ok(DataType::Q4K),
+            other => Err(format!("Unsupported tensor data type {:?}", other)),
         }
     }
 }
@@ -61,6 +150,8 @@ pub trait Num:
     + Sub<Output = Self>
     + Mul<Output = Self>
     + Div<Output = Self>
+    + Send
+    + Sync
 {
     fn data_type() -> DataType;
 }
@@ -73,7 +164,21 @@ pub trait Float: Num + Neg<Output = Self> {
     fn is_nan(&self) -> bool;
     fn is_infinite(&self) -> bool;
     fn is_finite(&self) -> bool;
-    // Add other relevant float methods if needed (e.g., sqrt, abs, etc.)
+    fn from_f64(value: f64) -> Self;
+    fn sqrt(self) -> Self;
+    fn exp(self) -> Self;
+    fn ln(self) -> Self;
+    fn tanh(self) -> Self;
+    fn abs(self) -> Self;
+    fn powf(self, exponent: Self) -> Self;
+}
+
+/// Element-wise numeric conversion between supported data types.
+///
+/// Conversions follow `as` semantics: floats are rounded to the nearest representable value,
+/// float to integer conversions truncate towards zero and saturate, and integer conversions wrap.
+pub trait CastFrom<T>: Sized {
+    fn cast_from(value: T) -> Self;
 }
 
 // Signed Integer trait
@@ -150,6 +255,27 @@ macro_rules! impl_float {
             fn is_finite(&self) -> bool {
                 <$t>::is_finite(*self)
             }
+            fn from_f64(value: f64) -> Self {
+                value as $t
+            }
+            fn sqrt(self) -> Self {
+                <$t>::sqrt(self)
+            }
+            fn exp(self) -> Self {
+                <$t>::exp(self)
+            }
+            fn ln(self) -> Self {
+                <$t>::ln(self)
+            }
+            fn tanh(self) -> Self {
+                <$t>::tanh(self)
+            }
+            fn abs(self) -> Self {
+                <$t>::abs(self)
+            }
+            fn powf(self, exponent: Self) -> Self {
+                <$t>::powf(self, exponent)
+            }
         }
     };
 }
@@ -168,6 +294,104 @@ impl_unsigned_integer!(u64, DataType::UInt64);
 impl_float!(f32, DataType::Float32);
 impl_float!(f64, DataType::Float64);
 
+impl Zero for f16 {
+    fn zero() -> Self {
+        f16::ZERO
+    }
+}
+
+impl One for f16 {
+    fn one() -> Self {
+        f16::ONE
+    }
+}
+
+impl Num for f16 {
+    fn data_type() -> DataType {
+        DataType::Float16
+    }
+}
+
+// Half precision math is computed in f32 and rounded back.
+impl Float for f16 {
+    fn nan() -> Self {
+        f16::NAN
+    }
+    fn infinity() -> Self {
+        f16::INFINITY
+    }
+    fn neg_infinity() -> Self {
+        f16::NEG_INFINITY
+    }
+    fn is_nan(&self) -> bool {
+        f16::is_nan(*self)
+    }
+    fn is_infinite(&self) -> bool {
+        f16::is_infinite(*self)
+    }
+    fn is_finite(&self) -> bool {
+        f16::is_finite(*self)
+    }
+    fn from_f64(value: f64) -> Self {
+        f16::from_f64(value)
+    }
+    fn sqrt(self) -> Self {
+        f16::from_f32(self.to_f32().sqrt())
+    }
+    fn exp(self) -> Self {
+        f16::from_f32(self.to_f32().exp())
+    }
+    fn ln(self) -> Self {
+        f16::from_f32(self.to_f32().ln())
+    }
+    fn tanh(self) -> Self {
+        f16::from_f32(self.to_f32().tanh())
+    }
+    fn abs(self) -> Self {
+        f16::from_f32(self.to_f32().abs())
+    }
+    fn powf(self, exponent: Self) -> Self {
+        f16::from_f32(self.to_f32().powf(exponent.to_f32()))
+    }
+}
+
+macro_rules! impl_cast {
+    ($($from:ty),*) => {
+        $(
+            impl_cast!(@from $from => i8, i16, i32, i64, u8, u16, u32, u64, f32, f64);
+
+            impl CastFrom<$from> for f16 {
+                fn cast_from(value: $from) -> Self {
+                    f16::from_f64(value as f64)
+                }
+            }
+
+            impl CastFrom<f16> for $from {
+                fn cast_from(value: f16) -> Self {
+                    value.to_f64() as $from
+                }
+            }
+        )*
+    };
+    (@from $from:ty => $($to:ty),*) => {
+        $(
+            impl CastFrom<$from> for $to {
+                fn cast_from(value: $from) -> Self {
+                    value as $to
+                }
+            }
+        )*
+    };
+}
+
+impl_cast!(i8, i16, i32, i64, u8, u16, u32, u64, f32, f64);
+
+impl CastFrom<f16> for f16 {
+    fn cast_from(value: f16) -> Self {
+        value
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -192,5 +416,34 @@ mod tests {
         assert_eq!(f64::one(), 1.0);
         assert_eq!(f32::data_type(), DataType::Float32);
         assert!(f64::nan().is_nan());
+        assert_eq!(
+            DataType::try_from(generated::tensor_buffers::DataType::Float64),
+            Ok(DataType::Float64)
+        );
+        assert!(DataType::try_from(generated::tensor_buffers::DataType::None).is_err());
+        assert_eq!(Float::sqrt(4.0f32), 2.0);
+        assert_eq!(f64::from_f64(0.5), 0.5);
+    }
+
+    #[test]
+    fn test_half() {
+        assert_eq!(f16::zero(), f16::from_f32(0.0));
+        assert_eq!(f16::data_type(), DataType::Float16);
+        assert_eq!(Float::sqrt(f16::from_f32(9.0)), f16::from_f32(3.0));
+        assert_eq!(
+            DataType::try_from(generated::tensor_buffers::DataType::Float16),
+            Ok(DataType::Float16)
+        );
+    }
+
+    #[test]
+    fn test_cast_from() {
+        assert_eq!(i32::cast_from(2.9f32), 2);
+        assert_eq!(u8::cast_from(-1.0f64), 0);
+        assert_eq!(u8::cast_from(300i32), 44);
+        assert_eq!(f64::cast_from(u64::MAX), 18446744073709551615.0);
+        assert_eq!(f32::cast_from(f16::from_f32(0.5)), 0.5);
+        assert_eq!(f16::cast_from(1.0e6f64), f16::INFINITY);
+        assert_eq!(i16::cast_from(f16::from_f32(-3.75)), -3);
     }
 }
@@ -0,0 +1,44 @@
+use std::{error::Error, time::Duration};
+
+/// Receives events from a [`crate::TensorBuffers`] so custom telemetry can hook into reads.
+///
+/// Register an observer with `OpenOptions::with_observer`. Every method has an empty default, so
+/// implementations only override the events they need. Methods are called inline on the reading
+/// task and should return quickly.
+pub trait TensorBuffersObserver: Send + Sync {
+    /// Called before reading `len` bytes of the file starting at `offset`.
+    fn on_fetch_start(&self, _offset: u64, _len: usize) {}
+
+    /// Called after the bytes requested by the matching `on_fetch_start` were read.
+    fn on_fetch_finish(&self, _offset: u64, _len: usize, _elapsed: Duration) {}
+
+    /// Called after the file's metadata was read and validated, once per `TensorBuffers`.
+    ///
+    /// # Arguments
+    /// * `tensors` - The number of tensors the metadata describes.
+    /// * `bytes` - The size of the metadata in bytes.
+    /// * `elapsed` - The time taken to read and validate the metadata.
+    fn on_metadata_loaded(&self, _tensors: usize, _bytes: usize, _elapsed: Duration) {}
+
+    /// Called after a tensor's data was loaded and validated.
+    ///
+    /// # Arguments
+    /// * `name` - The name of the tensor.
+    /// * `bytes` - The size of the tensor's data in bytes.
+    /// * `elapsed` - The time taken to look up and read the tensor.
+    fn on_tensor_loaded(&self, _name: &str, _bytes: usize, _elapsed: Duration) {}
+
+    /// Called after a tensor's data was read by any of the read paths: loads of one or several
+    /// tensors, and streams or readers of its raw bytes once the last byte was read. Fires once per
+    /// read, so it is the event to log which tensors a process actually touched.
+    ///
+    /// # Arguments
+    /// * `name` - The name of the tensor.
+    /// * `bytes` - The size of the tensor's data in bytes.
+    /// * `elapsed` - The time taken to look up and read the tensor.
+    fn on_tensor_read(&self, _name: &str, _bytes: usize, _elapsed: Duration) {}
+
+    /// Called when opening the file, reading its metadata, loading a tensor or streaming its bytes
+    /// fails, before the error is returned to the caller.
+    fn on_error(&self, _error: &dyn Error) {}
+}
@@ -0,0 +1,532 @@
+//! Export of stored graphs to the ONNX protobuf format.
+//!
+//! Only the subset of `onnx.proto` needed to describe a graph with initializers is encoded, so
+//! no protobuf dependency is required. Operations are mapped to ONNX nodes following the
+//! executor's semantics, which sometimes takes more than one node (e.g. `MSELoss`).
+
+use bytemuck::Pod;
+use fnv::{FnvHashMap, FnvHashSet};
+
+use crate::{
+    constants::VERSION,
+    executor::{required_operations, resolve_outputs},
+    num_trait::Float,
+    utils::hash_key,
+    DataType, Operation, Result, TensorBuffers, TensorGraph, TensorId, TensorOperation,
+    TensorOperationId,
+};
+
+const IR_VERSION: u64 = 8;
+const OPSET_VERSION: u64 = 17;
+/// Name of the shared `[-1]` int64 initializer used for flattening and last-axis reductions.
+const MINUS_ONE: &str = "tensorbuffers/minus_one";
+
+/// Protobuf wire types.
+const VARINT: u32 = 0;
+const LEN: u32 = 2;
+const FIXED32: u32 = 5;
+
+/// ONNX `AttributeProto.AttributeType` values.
+const ATTRIBUTE_FLOAT: u64 = 1;
+const ATTRIBUTE_INT: u64 = 2;
+
+/// ONNX `TensorProto.DataType` values.
+const ONNX_INT64: i32 = 7;
+
+fn onnx_data_type(data_type: DataType) -> i32 {
+    match data_type {
+        DataType::Float32 => 1,
+        DataType::UInt8 => 2,
+        DataType::Int8 => 3,
+        DataType::UInt16 => 4,
+        DataType::Int16 => 5,
+        DataType::Int32 => 6,
+        DataType::Int64 => ONNX_INT64,
+        DataType::Float64 => 11,
+        DataType::UInt32 => 12,
+        DataType::UInt64 => 13,
+        DataType::Float16 => 10,
+        // ONNX has no extension or block-quantized types, so they are left UNDEFINED.
+        DataType::Extension(_) | DataType::Q8_0 | DataType::Q4K => 0,
+    }
+}
+
+/// A protobuf message under construction.
+#[derive(Default)]
+struct Message(Vec<u8>);
+
+impl Message {
+    fn varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.0.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.0.push(value as u8);
+    }
+
+    fn key(&mut self, field: u32, wire_type: u32) {
+        self.varint(u64::from(field << 3 | wire_type));
+    }
+
+    fn uint(&mut self, field: u32, value: u64) -> &mut Self {
+        self.key(field, VARINT);
+        self.varint(value);
+        self
+    }
+
+    fn int(&mut self, field: u32, value: i64) -> &mut Self {
+        self.uint(field, value as u64)
+    }
+
+    fn float(&mut self, field: u32, value: f32) -> &mut Self {
+        self.key(field, FIXED32);
+        self.0.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    fn bytes(&mut self, field: u32, value: &[u8]) -> &mut Self {
+        self.key(field, LEN);
+        self.varint(value.len() as u64);
+        self.0.extend_from_slice(value);
+        self
+    }
+
+    fn string(&mut self, field: u32, value: &str) -> &mut Self {
+        self.bytes(field, value.as_bytes())
+    }
+
+    fn message(&mut self, field: u32, value: &Message) -> &mut Self {
+        self.bytes(field, &value.0)
+    }
+}
+
+fn tensor_proto(name: &str, data_type: i32, shape: &[usize], raw_data: &[u8]) -> Message {
+    let mut tensor = Message::default();
+    for &dim in shape {
+        tensor.int(1, dim as i64);
+    }
+    tensor.int(2, i64::from(data_type)).string(8, name).bytes(9, raw_data);
+    tensor
+}
+
+fn value_info(name: &str, data_type: i32, shape: Option<&[usize]>) -> Message {
+    let mut tensor_type = Message::default();
+    tensor_type.int(1, i64::from(data_type));
+    if let Some(shape) = shape {
+        let mut shape_proto = Message::default();
+        for &dim in shape {
+            shape_proto.message(1, Message::default().int(1, dim as i64));
+        }
+        tensor_type.message(2, &shape_proto);
+    }
+    let mut value = Message::default();
+    value.string(1, name).message(2, Message::default().message(1, &tensor_type));
+    value
+}
+
+/// Collects ONNX nodes and initializers while the graph is translated.
+#[derive(Default)]
+struct GraphBuilder {
+    nodes: Vec<Message>,
+    initializers: Vec<Message>,
+    needs_minus_one: bool,
+}
+
+impl GraphBuilder {
+    fn node(&mut self, op_type: &str, inputs: &[&str], output: &str) -> &mut Message {
+        let mut node = Message::default();
+        for input in inputs {
+            node.string(1, input);
+        }
+        node.string(2, output).string(3, output).string(4, op_type);
+        self.nodes.push(node);
+        self.nodes.last_mut().unwrap()
+    }
+
+    fn minus_one(&mut self) -> &'static str {
+        self.needs_minus_one = true;
+        MINUS_ONE
+    }
+
+    /// Appends the nodes computing `operation` from `inputs` into `output`.
+    fn operation(
+        &mut self,
+        operation: Operation,
+        inputs: &[&str],
+        output: &str,
+        data_type: i32,
+    ) -> Result<()> {
+        let expected = match operation {
+            Operation::Concat | Operation::FC => None,
+            Operation::Add
+            | Operation::Sub
+            | Operation::Mul
+            | Operation::Div
+            | Operation::Pow
+            | Operation::MatMul
+            | Operation::MSELoss
+            | Operation::L1Loss
+            | Operation::CrossEntropyLoss => Some(2),
+            _ => Some(1),
+        };
+        if let Some(expected) = expected {
+            if inputs.len() != expected {
+                return Err(format!(
+                    "{:?} expects {} inputs, found {}",
+                    operation,
+                    expected,
+                    inputs.len()
+                )
+                .into());
+            }
+        }
+
+        let op_type = match operation {
+            Operation::Add => "Add",
+            Operation::Sub => "Sub",
+            Operation::Mul => "Mul",
+            Operation::Div => "Div",
+            Operation::Pow => "Pow",
+            Operation::Sqrt => "Sqrt",
+            Operation::Sigmoid => "Sigmoid",
+            Operation::Tanh => "Tanh",
+            Operation::ReLU => "Relu",
+            Operation::Softplus => "Softplus",
+            Operation::Log => "Log",
+            Operation::Exp => "Exp",
+            Operation::Abs => "Abs",
+            Operation::Dropout => "Identity",
+            Operation::MatMul => "MatMul",
+            Operation::Transpose => "Transpose",
+            Operation::Sqr => {
+                self.node("Mul", &[inputs[0], inputs[0]], output);
+                return Ok(());
+            }
+            Operation::LeakyReLU => {
+                let mut alpha = Message::default();
+                alpha.string(1, "alpha").float(2, 0.01).uint(20, ATTRIBUTE_FLOAT);
+                self.node("LeakyRelu", inputs, output).message(5, &alpha);
+                return Ok(());
+            }
+            Operation::Softmax => {
+                self.node("Softmax", inputs, output).message(5, &int_attribute("axis", -1));
+                return Ok(());
+            }
+            Operation::Sum | Operation::Mean => {
+                let op_type = if operation == Operation::Sum { "ReduceSum" } else { "ReduceMean" };
+                self.node(op_type, inputs, output).message(5, &int_attribute("keepdims", 0));
+                return Ok(());
+            }
+            Operation::Argmax => {
+                // The executor takes the argmax of the flattened tensor and returns it as T.
+                let flat = format!("{}/flat", output);
+                let index = format!("{}/index", output);
+                let minus_one = self.minus_one();
+                self.node("Reshape", &[inputs[0], minus_one], &flat);
+                self.node("ArgMax", &[&flat], &index)
+                    .message(5, &int_attribute("axis", 0))
+                    .message(5, &int_attribute("keepdims", 0));
+                self.node("Cast", &[&index], output)
+                    .message(5, &int_attribute("to", i64::from(data_type)));
+                return Ok(());
+            }
+            Operation::Flatten => {
+                let minus_one = self.minus_one();
+                self.node("Reshape", &[inputs[0], minus_one], output);
+                return Ok(());
+            }
+            Operation::Concat => {
+                if inputs.is_empty() {
+                    return Err("Concat expects at least one input".into());
+                }
+                self.node("Concat", inputs, output).message(5, &int_attribute("axis", 0));
+                return Ok(());
+            }
+            Operation::FC => {
+                if inputs.len() != 2 && inputs.len() != 3 {
+                    return Err(format!("FC expects 2 or 3 inputs, found {}", inputs.len()).into());
+                }
+                self.node("Gemm", inputs, output);
+                return Ok(());
+            }
+            Operation::MSELoss | Operation::L1Loss => {
+                let difference = format!("{}/difference", output);
+                let errors = format!("{}/errors", output);
+                self.node("Sub", inputs, &difference);
+                if operation == Operation::MSELoss {
+                    self.node("Mul", &[&difference, &difference], &errors);
+                } else {
+                    self.node("Abs", &[&difference], &errors);
+                }
+                self.node("ReduceMean", &[&errors], output)
+                    .message(5, &int_attribute("keepdims", 0));
+                return Ok(());
+            }
+            Operation::CrossEntropyLoss => {
+                // -sum(target * ln(prediction)) averaged over rows of the last axis.
+                let log = format!("{}/log", output);
+                let terms = format!("{}/terms", output);
+                let rows = format!("{}/rows", output);
+                let mean = format!("{}/mean", output);
+                let minus_one = self.minus_one();
+                self.node("Log", &[inputs[0]], &log);
+                self.node("Mul", &[inputs[1], &log], &terms);
+                let keepdims = int_attribute("keepdims", 0);
+                self.node("ReduceSum", &[&terms, minus_one], &rows).message(5, &keepdims);
+                self.node("ReduceMean", &[&rows], &mean).message(5, &keepdims);
+                self.node("Neg", &[&mean], output);
+                return Ok(());
+            }
+            _ => return Err(format!("operation {:?} cannot be exported to ONNX", operation).into()),
+        };
+        self.node(op_type, inputs, output);
+        Ok(())
+    }
+}
+
+fn int_attribute(name: &str, value: i64) -> Message {
+    let mut attribute = Message::default();
+    attribute.string(1, name).int(3, value).uint(20, ATTRIBUTE_INT);
+    attribute
+}
+
+/// Exports the operation graph and weights stored in a TensorBuffers file as an ONNX model.
+///
+/// Sources listed as inputs become graph inputs; every other stored source becomes an
+/// initializer holding its data.
+pub struct OnnxExporter<'a> {
+    tensor_buffers: &'a TensorBuffers,
+}
+
+impl<'a> OnnxExporter<'a> {
+    pub fn new(tensor_buffers: &'a TensorBuffers) -> Self {
+        OnnxExporter { tensor_buffers }
+    }
+
+    /// Encodes the operations needed for the named outputs as a serialized ONNX `ModelProto`.
+    ///
+    /// # Arguments
+    /// * `output_names` - Names of the tensors exposed as graph outputs.
+    /// * `input_names` - Names of source tensors exposed as graph inputs instead of initializers.
+    ///   Sources without stored data are always graph inputs.
+    ///
+    /// # Returns
+    /// Returns an error if the graph contains an operation that has no ONNX mapping.
+    pub async fn export<T>(&self, output_names: &[&str], input_names: &[&str]) -> Result<Vec<u8>>
+    where
+        T: Float + Pod,
+    {
+        let graph = self.tensor_buffers.graph().await?;
+        let targets = resolve_outputs(&graph, output_names)?;
+        let live = required_operations(&graph, targets.iter().map(|&(_, id)| id))?;
+        let inputs: FnvHashMap<TensorId, &str> =
+            input_names.iter().map(|&name| (hash_key(name), name)).collect();
+        let shapes: FnvHashMap<TensorId, Vec<usize>> =
+            match self.tensor_buffers.infer_shapes().await {
+                Ok(shapes) => {
+                    shapes.into_iter().map(|s| (s.output(), s.shape().to_vec())).collect()
+                }
+                Err(_) => FnvHashMap::default(),
+            };
+        let data_type = onnx_data_type(T::data_type());
+
+        let mut names: FnvHashMap<TensorId, String> = targets
+            .iter()
+            .map(|&(name, id)| (graph_output(&graph, id), name.to_string()))
+            .collect();
+        let mut builder = GraphBuilder::default();
+        let mut graph_inputs = Vec::new();
+        let mut initialized = FnvHashSet::default();
+        for op in graph.operations_in_topological_order()? {
+            if !live.contains(&op.id()) {
+                continue;
+            }
+            let output = *op.output();
+            if *op.operation() == Operation::None {
+                let stored = self.tensor_buffers.stored_metadata(output).await.ok();
+                let name = match (inputs.get(&output), &stored) {
+                    (Some(name), _) => name.to_string(),
+                    (None, Some(metadata)) => metadata.name().to_string(),
+                    (None, None) => format!("tensor_{}", output),
+                };
+                names.entry(output).or_insert_with(|| name.clone());
+                if stored.is_none() || inputs.contains_key(&output) {
+                    graph_inputs.push(value_info(
+                        &name,
+                        data_type,
+                        shapes.get(&output).map(|s| &s[..]),
+                    ));
+                } else if initialized.insert(output) {
+                    let tensor = self.tensor_buffers.get_tensor_data_by_id::<T>(output).await?;
+                    builder.initializers.push(tensor_proto(
+                        &name,
+                        data_type,
+                        tensor.shape(),
+                        bytemuck::cast_slice(tensor.data()),
+                    ));
+                }
+                continue;
+            }
+
+            let output_name =
+                names.entry(output).or_insert_with(|| format!("tensor_{}", output)).clone();
+            let input_names = op
+                .input_operations()
+                .iter()
+                .map(|id| {
+                    let input = graph.get_operation(*id).ok_or("Unknown input operation")?;
+                    Ok(names[input.output()].clone())
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let input_names = input_names.iter().map(String::as_str).collect::<Vec<_>>();
+            builder
+                .operation(*op.operation(), &input_names, &output_name, data_type)
+                .map_err(|e| format!("Operation {} failed: {}", op.id(), e))?;
+        }
+        if builder.needs_minus_one {
+            builder.initializers.push(tensor_proto(
+                MINUS_ONE,
+                ONNX_INT64,
+                &[1],
+                &(-1i64).to_le_bytes(),
+            ));
+        }
+
+        let mut graph_proto = Message::default();
+        for node in &builder.nodes {
+            graph_proto.message(1, node);
+        }
+        graph_proto.string(2, "tensorbuffers");
+        for initializer in &builder.initializers {
+            graph_proto.message(5, initializer);
+        }
+        for input in &graph_inputs {
+            graph_proto.message(11, input);
+        }
+        for &(name, id) in &targets {
+            let shape = shapes.get(&graph_output(&graph, id)).map(|s| &s[..]);
+            graph_proto.message(12, &value_info(name, data_type, shape));
+        }
+
+        let mut model = Message::default();
+        model
+            .uint(1, IR_VERSION)
+            .string(2, "tensorbuffers")
+            .string(3, VERSION)
+            .message(7, &graph_proto)
+            .message(8, Message::default().string(1, "").uint(2, OPSET_VERSION));
+        Ok(model.0)
+    }
+}
+
+fn graph_output(graph: &TensorGraph, operation_id: TensorOperationId) -> TensorId {
+    graph.get_operation(operation_id).map(TensorOperation::output).copied().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::NamedTempFile;
+    use tokio::fs::File;
+
+    use super::*;
+    use crate::{Tensor, TensorBuffersWrite, TensorBuffersWriter};
+
+    /// Decodes one level of a protobuf message into `(field, wire type, payload)` entries.
+    fn decode(mut buf: &[u8]) -> Vec<(u32, u32, Vec<u8>)> {
+        fn varint(buf: &mut &[u8]) -> u64 {
+            let mut value = 0;
+            for shift in (0..).step_by(7) {
+                let byte = buf[0];
+                *buf = &buf[1..];
+                value |= u64::from(byte & 0x7f) << shift;
+                if byte < 0x80 {
+                    break;
+                }
+            }
+            value
+        }
+        let mut fields = Vec::new();
+        while !buf.is_empty() {
+            let key = varint(&mut buf) as u32;
+            let payload = match key & 7 {
+                VARINT => varint(&mut buf).to_le_bytes().to_vec(),
+                FIXED32 => {
+                    let (payload, rest) = buf.split_at(4);
+                    buf = rest;
+                    payload.to_vec()
+                }
+                _ => {
+                    let len = varint(&mut buf) as usize;
+                    let (payload, rest) = buf.split_at(len);
+                    buf = rest;
+                    payload.to_vec()
+                }
+            };
+            fields.push((key >> 3, key & 7, payload));
+        }
+        fields
+    }
+
+    fn strings(fields: &[(u32, u32, Vec<u8>)], field: u32) -> Vec<String> {
+        fields
+            .iter()
+            .filter(|(f, _, _)| *f == field)
+            .map(|(_, _, payload)| String::from_utf8(payload.clone()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_message_encoding() {
+        let mut message = Message::default();
+        message.uint(1, 300).string(2, "hi").int(3, -1);
+        assert_eq!(&message.0[..5], &[0x08, 0xac, 0x02, 0x12, 0x02]);
+        let fields = decode(&message.0);
+        assert_eq!(strings(&fields, 2), vec!["hi"]);
+        assert_eq!(fields[2].2, (-1i64).to_le_bytes());
+    }
+
+    #[tokio::test]
+    async fn test_export() {
+        let x = Tensor::new("x", &[1.0f32, 2.0], vec![1, 2]);
+        let w = Tensor::new("w", &[1.0f32, 0.0, 0.0, 1.0], vec![2, 2]);
+        let b = Tensor::new("b", &[0.5f32, 0.5], vec![2]);
+        let operations = vec![
+            TensorOperation::new(1, Operation::None, vec![], x.id()),
+            TensorOperation::new(2, Operation::None, vec![], w.id()),
+            TensorOperation::new(3, Operation::None, vec![], b.id()),
+            TensorOperation::new(4, Operation::FC, vec![1, 2, 3], hash_key("h")),
+            TensorOperation::new(5, Operation::Argmax, vec![4], hash_key("y")),
+            TensorOperation::new(6, Operation::Conv2D, vec![1], hash_key("unused")),
+        ];
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        TensorBuffersWriter::new(&mut file).write(vec![x, w, b], operations).await.unwrap();
+
+        let url = format!("file://{}", tmp.path().display());
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        let exporter = OnnxExporter::new(&tensor_buffers);
+        let model = decode(&exporter.export::<f32>(&["y"], &["x"]).await.unwrap());
+        assert_eq!(model[0], (1, VARINT, IR_VERSION.to_le_bytes().to_vec()));
+
+        let graph = decode(&model.iter().find(|(field, _, _)| *field == 7).unwrap().2);
+        let op_types = graph
+            .iter()
+            .filter(|(field, _, _)| *field == 1)
+            .map(|(_, _, node)| strings(&decode(node), 4).remove(0))
+            .collect::<Vec<_>>();
+        assert_eq!(op_types, vec!["Gemm", "Reshape", "ArgMax", "Cast"]);
+        let initializers = graph
+            .iter()
+            .filter(|(field, _, _)| *field == 5)
+            .map(|(_, _, tensor)| strings(&decode(tensor), 8).remove(0))
+            .collect::<Vec<_>>();
+        assert_eq!(initializers, vec!["w", "b", MINUS_ONE]);
+        let graph_inputs = graph.iter().filter(|(field, _, _)| *field == 11).collect::<Vec<_>>();
+        assert_eq!(strings(&decode(&graph_inputs[0].2), 1), vec!["x"]);
+        let graph_outputs = graph.iter().filter(|(field, _, _)| *field == 12).collect::<Vec<_>>();
+        assert_eq!(strings(&decode(&graph_outputs[0].2), 1), vec!["y"]);
+
+        // Conv2D has no mapping, so requesting it fails.
+        assert!(exporter.export::<f32>(&["unused"], &[]).await.is_err());
+    }
+}
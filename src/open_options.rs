@@ -0,0 +1,305 @@
+use std::{fmt, sync::Arc};
+
+use crate::{
+    aligned_vec::DEFAULT_ALIGNMENT, memory_budget::MemoryBudget, mirrors::Origins,
+    observer::TensorBuffersObserver, prefetch::PrefetchBudget, range_cache::RangeCache,
+    rate_limiter::RateLimiter, read_mode::ReadMode, timeouts::Timeouts,
+};
+
+/// Number of times a failed range request is retried by default.
+const DEFAULT_MAX_RETRIES: u32 = 2;
+
+/// Settings for [`crate::TensorBuffers::open_with`], built up with the `with_*` methods.
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use tensorbuffers::{OpenOptions, Timeouts};
+///
+/// let options = OpenOptions::new()
+///     .with_max_retries(5)
+///     .with_timeouts(Timeouts::default().with_request_timeout(Duration::from_secs(10)));
+/// assert_eq!(options.max_retries(), 5);
+/// ```
+#[derive(Clone)]
+pub struct OpenOptions {
+    client: Option<reqwest::Client>,
+    max_retries: u32,
+    timeouts: Timeouts,
+    observer: Option<Arc<dyn TensorBuffersObserver>>,
+    verify_checksums: bool,
+    read_mode: ReadMode,
+    memory_budget: MemoryBudget,
+    buffer_alignment: usize,
+    prefetch_budget: PrefetchBudget,
+    name_index: bool,
+    metadata_only: bool,
+    rate_limiter: Option<RateLimiter>,
+    range_cache: Option<Arc<dyn RangeCache>>,
+    mirrors: Vec<String>,
+    balanced_mirrors: usize,
+    /// The origins of the file `TensorBuffers::open_with` opened, shared by its handles.
+    origins: Option<Arc<Origins>>,
+}
+
+impl OpenOptions {
+    /// Creates options with the defaults: a new HTTP client, two retries, no time limits, no
+    /// observer, checksum verification, strict reads, no memory limits, 64-byte aligned tensor
+    /// data and up to four prefetch reads at once.
+    pub fn new() -> Self {
+        OpenOptions {
+            client: None,
+            max_retries: DEFAULT_MAX_RETRIES,
+            timeouts: Timeouts::default(),
+            observer: None,
+            verify_checksums: true,
+            read_mode: ReadMode::Strict,
+            memory_budget: MemoryBudget::default(),
+            buffer_alignment: DEFAULT_ALIGNMENT,
+            prefetch_budget: PrefetchBudget::default(),
+            name_index: false,
+            metadata_only: false,
+            rate_limiter: None,
+            range_cache: None,
+            mirrors: Vec::new(),
+            balanced_mirrors: 0,
+            origins: None,
+        }
+    }
+
+    /// Sends remote requests with `client`, e.g. to share its connection pool, proxy or TLS
+    /// settings with the rest of the application.
+    pub fn with_client(mut self, client: reqwest::Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Sets how many times a remote range request that fails to send or gets a server error is
+    /// retried.
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Sets the request timeout and the open and load deadlines.
+    pub fn with_timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    /// Reports fetches, loaded tensors and errors to `observer`.
+    pub fn with_observer(mut self, observer: Arc<dyn TensorBuffersObserver>) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    /// Sets whether tensor data is checked against its stored checksum when loaded. Files written
+    /// without checksums are never checked.
+    pub fn with_verify_checksums(mut self, verify_checksums: bool) -> Self {
+        self.verify_checksums = verify_checksums;
+        self
+    }
+
+    /// Sets whether metadata problems fail reads or are skipped with a warning.
+    pub fn with_read_mode(mut self, read_mode: ReadMode) -> Self {
+        self.read_mode = read_mode;
+        self
+    }
+
+    /// Limits how much memory tensor reads may allocate.
+    pub fn with_memory_budget(mut self, memory_budget: MemoryBudget) -> Self {
+        self.memory_budget = memory_budget;
+        self
+    }
+
+    /// Sets the boundary in bytes that the data of loaded tensors starts on, so SIMD kernels and
+    /// GPU uploads can use it without copying. Must be a power of two; the alignment of the
+    /// element type is used if it is larger.
+    pub fn with_buffer_alignment(mut self, buffer_alignment: usize) -> Self {
+        self.buffer_alignment = buffer_alignment;
+        self
+    }
+
+    /// Limits the concurrency, bandwidth and memory of `TensorBuffers::prefetch`.
+    pub fn with_prefetch_budget(mut self, prefetch_budget: PrefetchBudget) -> Self {
+        self.prefetch_budget = prefetch_budget;
+        self
+    }
+
+    /// Sets whether tensors are looked up through the file's name index, if it has one, until
+    /// the metadata is loaded for other reasons. Each lookup then takes a few small reads instead
+    /// of loading the whole metadata first, which pays off for files with very many tensors.
+    pub fn with_name_index(mut self, name_index: bool) -> Self {
+        self.name_index = name_index;
+        self
+    }
+
+    /// Limits remote requests with `rate_limiter`, which every file opened with a clone of it
+    /// shares, so many files loading at once stay within one budget.
+    pub fn with_rate_limiter(mut self, rate_limiter: RateLimiter) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    /// Looks up the byte ranges of remote files in `range_cache` before requesting them, and caches
+    /// every range fetched.
+    pub fn with_range_cache(mut self, range_cache: Arc<dyn RangeCache>) -> Self {
+        self.range_cache = Some(range_cache);
+        self
+    }
+
+    /// Reads remote files from `mirrors` when their own URL fails: a range request that still
+    /// fails after its retries is sent to the next mirror, and later requests stay there. Each
+    /// mirror is asked for the file's size and ETag before it is first read from, and left out if
+    /// they differ from the file's. Mirrors must be `https://` URLs of the same file.
+    pub fn with_mirrors(mut self, mirrors: Vec<String>) -> Self {
+        self.mirrors = mirrors;
+        self
+    }
+
+    /// Probes a remote file's URL and mirrors when it is opened, timing a range at the end of the
+    /// file from each, and spreads range requests across the `count` fastest, taking turns, so a
+    /// cold load draws on the bandwidth of several origins. The others serve as failovers. With 0,
+    /// the default, nothing is probed and requests go to one origin at a time, the file's own URL
+    /// first. Only applies when mirrors are set.
+    pub fn with_balanced_mirrors(mut self, count: usize) -> Self {
+        self.balanced_mirrors = count;
+        self
+    }
+
+    /// Sets whether only the metadata may be read. Loading, streaming, prefetching, verifying or
+    /// warming up tensor data then fails instead of transferring it, so tools that only inspect
+    /// files never fetch their contents by accident.
+    pub fn with_metadata_only(mut self, metadata_only: bool) -> Self {
+        self.metadata_only = metadata_only;
+        self
+    }
+
+    /// Returns the HTTP client, if one was set.
+    pub fn client(&self) -> Option<&reqwest::Client> {
+        self.client.as_ref()
+    }
+
+    /// Returns how many times a failed range request is retried.
+    pub fn max_retries(&self) -> u32 {
+        self.max_retries
+    }
+
+    /// Returns the time limits.
+    pub fn timeouts(&self) -> Timeouts {
+        self.timeouts
+    }
+
+    /// Returns the observer, if one was set.
+    pub fn observer(&self) -> Option<&Arc<dyn TensorBuffersObserver>> {
+        self.observer.as_ref()
+    }
+
+    /// Returns whether tensor data is checked against its stored checksum.
+    pub fn verify_checksums(&self) -> bool {
+        self.verify_checksums
+    }
+
+    /// Returns how metadata problems are handled.
+    pub fn read_mode(&self) -> ReadMode {
+        self.read_mode
+    }
+
+    /// Returns the memory limits.
+    pub fn memory_budget(&self) -> MemoryBudget {
+        self.memory_budget
+    }
+
+    /// Returns the boundary that the data of loaded tensors starts on.
+    pub fn buffer_alignment(&self) -> usize {
+        self.buffer_alignment
+    }
+
+    /// Returns the limits on prefetching.
+    pub fn prefetch_budget(&self) -> PrefetchBudget {
+        self.prefetch_budget
+    }
+
+    /// Returns whether tensors are looked up through the file's name index.
+    pub fn name_index(&self) -> bool {
+        self.name_index
+    }
+
+    /// Returns whether only the metadata may be read.
+    pub fn metadata_only(&self) -> bool {
+        self.metadata_only
+    }
+
+    /// Returns the limiter of remote requests, if one was set.
+    pub fn rate_limiter(&self) -> Option<&RateLimiter> {
+        self.rate_limiter.as_ref()
+    }
+
+    /// Returns the cache of remote byte ranges, if one was set.
+    pub fn range_cache(&self) -> Option<&Arc<dyn RangeCache>> {
+        self.range_cache.as_ref()
+    }
+
+    /// Returns the mirrors of remote files.
+    pub fn mirrors(&self) -> &[String] {
+        &self.mirrors
+    }
+
+    /// Returns how many of the fastest origins range requests are spread across.
+    pub fn balanced_mirrors(&self) -> usize {
+        self.balanced_mirrors
+    }
+
+    /// Makes the mirrors apply to `url` only, with one set of origins shared by every handle
+    /// opened with these options. Files opened with them at other URLs, like those external
+    /// tensors are stored in, are read from their own URL only.
+    pub(crate) fn with_origins_of(mut self, url: &str) -> Self {
+        if !self.mirrors.is_empty() {
+            let origins = Origins::new(url, &self.mirrors, self.balanced_mirrors);
+            self.origins = Some(Arc::new(origins));
+        }
+        self
+    }
+
+    /// Returns the origins the mirrors were made to apply to, if any.
+    pub(crate) fn bound_origins(&self) -> Option<&Arc<Origins>> {
+        self.origins.as_ref()
+    }
+
+    /// Returns the origins a remote file at `url` is read from.
+    pub(crate) fn origins(&self, url: &str) -> Arc<Origins> {
+        match &self.origins {
+            Some(origins) if origins.primary() == url => origins.clone(),
+            Some(_) => Arc::new(Origins::new(url, &[], 0)),
+            None => Arc::new(Origins::new(url, &self.mirrors, self.balanced_mirrors)),
+        }
+    }
+}
+
+impl Default for OpenOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for OpenOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OpenOptions")
+            .field("client", &self.client)
+            .field("max_retries", &self.max_retries)
+            .field("timeouts", &self.timeouts)
+            .field("observer", &self.observer.is_some())
+            .field("verify_checksums", &self.verify_checksums)
+            .field("read_mode", &self.read_mode)
+            .field("memory_budget", &self.memory_budget)
+            .field("buffer_alignment", &self.buffer_alignment)
+            .field("prefetch_budget", &self.prefetch_budget)
+            .field("name_index", &self.name_index)
+            .field("metadata_only", &self.metadata_only)
+            .field("rate_limiter", &self.rate_limiter)
+            .field("range_cache", &self.range_cache.is_some())
+            .field("mirrors", &self.mirrors)
+            .field("balanced_mirrors", &self.balanced_mirrors)
+            .finish()
+    }
+}
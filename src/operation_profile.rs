@@ -0,0 +1,168 @@
+use std::time::Duration;
+
+#[cfg(any(feature = "async", feature = "mmap"))]
+use flatbuffers::{FlatBufferBuilder, WIPOffset};
+
+use crate::generated::tensor_buffers::OperationProfile as ProfileMetadata;
+#[cfg(any(feature = "async", feature = "mmap"))]
+use crate::generated::tensor_buffers::OperationProfileArgs;
+#[cfg(feature = "async")]
+use crate::{Result, TensorBuffers, TensorOperationId};
+
+/// Execution stats measured for an operation, stored with it so profiling runs can annotate the
+/// graph for later analysis and scheduling. Every stat is optional.
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use tensorbuffers::{Operation, OperationProfile, TensorOperation};
+///
+/// let profile = OperationProfile::new()
+///     .with_latency(Duration::from_micros(420))
+///     .with_flops(2_000_000);
+/// let op = TensorOperation::new(4, Operation::MatMul, vec![1, 2], 40).with_profile(profile);
+/// assert_eq!(op.profile().and_then(|profile| profile.flops), Some(2_000_000));
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OperationProfile {
+    /// How long the operation took.
+    pub latency: Option<Duration>,
+    /// The floating point operations it performed.
+    pub flops: Option<u64>,
+    /// The peak memory it used, in bytes.
+    pub memory_bytes: Option<u64>,
+}
+
+impl OperationProfile {
+    /// Creates a profile with no stats measured.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records how long the operation took.
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = Some(latency);
+        self
+    }
+
+    /// Records the floating point operations the operation performed.
+    pub fn with_flops(mut self, flops: u64) -> Self {
+        self.flops = Some(flops);
+        self
+    }
+
+    /// Records the peak memory the operation used, in bytes.
+    pub fn with_memory_bytes(mut self, bytes: u64) -> Self {
+        self.memory_bytes = Some(bytes);
+        self
+    }
+
+    /// Returns the FLOPs performed per second, if both are measured and the latency isn't zero.
+    pub fn flops_per_second(&self) -> Option<f64> {
+        let (flops, seconds) = (self.flops?, self.latency?.as_secs_f64());
+        (seconds > 0.0).then(|| flops as f64 / seconds)
+    }
+
+    pub(crate) fn from_metadata(metadata: &ProfileMetadata) -> Self {
+        let measured = |value: u64| (value != 0).then_some(value);
+        OperationProfile {
+            latency: measured(metadata.latency_ns()).map(Duration::from_nanos),
+            flops: measured(metadata.flops()),
+            memory_bytes: measured(metadata.memory_bytes()),
+        }
+    }
+
+    /// Builds the table describing the profile. Stats that aren't measured are stored as 0, so a
+    /// measured latency under a nanosecond is stored as 1, and latencies beyond `u64::MAX`
+    /// nanoseconds saturate.
+    #[cfg(any(feature = "async", feature = "mmap"))]
+    pub(crate) fn build_table<'a>(
+        &self,
+        builder: &mut FlatBufferBuilder<'a>,
+    ) -> WIPOffset<ProfileMetadata<'a>> {
+        let latency_ns = self
+            .latency
+            .map_or(0, |latency| u64::try_from(latency.as_nanos()).unwrap_or(u64::MAX).max(1));
+        ProfileMetadata::create(builder, &OperationProfileArgs {
+            latency_ns,
+            flops: self.flops.unwrap_or(0),
+            memory_bytes: self.memory_bytes.unwrap_or(0),
+        })
+    }
+}
+
+#[cfg(feature = "async")]
+impl TensorBuffers {
+    /// Returns the execution stats stored with an operation.
+    ///
+    /// # Returns
+    /// `None` if the operation has no profile, or an error if the file has no such operation.
+    pub async fn operation_profile(
+        &self,
+        operation_id: TensorOperationId,
+    ) -> Result<Option<OperationProfile>> {
+        let operation = self.get_tensor_operation_by_id(operation_id).await?;
+        Ok(operation.profile().copied())
+    }
+
+    /// Returns the profiled operations with their stats, slowest first, so profiling runs can
+    /// find the operations worth optimizing or scheduling apart. Operations without a measured
+    /// latency follow, in id order.
+    pub async fn operation_profiles(&self) -> Result<Vec<(TensorOperationId, OperationProfile)>> {
+        let metadata_root = self.get_metadata_root().await?;
+        let operations = metadata_root.operations().into_iter().flatten();
+        let mut profiles = operations
+            .filter_map(|op| Some((op.id(), OperationProfile::from_metadata(&op.profile()?))))
+            .collect::<Vec<_>>();
+        // Stable, so ties and unmeasured latencies keep the id order of the metadata.
+        profiles.sort_by_key(|(_, profile)| std::cmp::Reverse(profile.latency));
+        Ok(profiles)
+    }
+}
+
+#[cfg(all(test, feature = "async"))]
+mod tests {
+    use tempfile::NamedTempFile;
+    use tokio::fs::File;
+
+    use super::*;
+    use crate::{
+        Operation, Tensor, TensorBuffers, TensorBuffersWrite, TensorBuffersWriter, TensorOperation,
+    };
+
+    #[tokio::test]
+    async fn test_operation_profiles() {
+        let matmul = OperationProfile::new()
+            .with_latency(Duration::from_micros(420))
+            .with_flops(2_000_000)
+            .with_memory_bytes(4096);
+        let exp = OperationProfile::new().with_latency(Duration::from_nanos(900));
+        let sum = OperationProfile::new().with_memory_bytes(16);
+        let operations = vec![
+            TensorOperation::new(1, Operation::None, vec![], 10),
+            TensorOperation::new(2, Operation::Exp, vec![1], 20).with_profile(exp),
+            TensorOperation::new(3, Operation::MatMul, vec![1, 2], 30).with_profile(matmul),
+            TensorOperation::new(4, Operation::Sum, vec![3], 40).with_profile(sum),
+        ];
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        let tensors = vec![Tensor::new("x", &[1.0f32; 4], vec![2, 2])];
+        TensorBuffersWriter::new(&mut file).write(tensors, operations).await.unwrap();
+
+        let url = format!("file://{}", tmp.path().display());
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        assert_eq!(tensor_buffers.operation_profile(3).await.unwrap(), Some(matmul));
+        assert_eq!(tensor_buffers.operation_profile(1).await.unwrap(), None);
+        assert!(tensor_buffers.operation_profile(9).await.is_err());
+        let profiles = tensor_buffers.operation_profiles().await.unwrap();
+        assert_eq!(profiles, [(3, matmul), (2, exp), (4, sum)]);
+        assert_eq!(matmul.flops_per_second(), Some(2_000_000.0 / 420e-6));
+        assert_eq!(sum.flops_per_second(), None);
+
+        // Profiles travel with the operations, e.g. through the graph.
+        let graph = tensor_buffers.graph().await.unwrap();
+        let exp_op = graph.operations().iter().find(|op| op.id() == 2).unwrap();
+        assert_eq!(exp_op.profile(), Some(&exp));
+    }
+}
@@ -0,0 +1,278 @@
+use bytemuck::Pod;
+use fnv::{FnvHashMap, FnvHashSet};
+use tokio::io::{AsyncSeek, AsyncWrite};
+
+use crate::{
+    executor::{evaluate, required_operations, resolve_outputs},
+    num_trait::Float,
+    utils::hash_key,
+    Operation, Result, Tensor, TensorBuffers, TensorBuffersWriter, TensorGraph, TensorId,
+    TensorOperation, TensorOperationId, TensorValue,
+};
+
+impl TensorGraph {
+    /// Removes every operation that the given operations do not transitively depend on.
+    pub fn eliminate_dead_operations(&self, outputs: &[TensorOperationId]) -> Result<TensorGraph> {
+        let live = required_operations(self, outputs.iter().copied())?;
+        let operations =
+            self.operations().iter().filter(|op| live.contains(&op.id())).cloned().collect();
+        Ok(TensorGraph::new(operations))
+    }
+
+    /// Fuses `Add(MatMul(x, w), b)` into `FC(x, w, b)` when the MatMul result is used only by
+    /// the Add and is not itself one of `outputs`. The fused operation keeps the Add's id and
+    /// output.
+    pub fn fuse_matmul_add(&self, outputs: &[TensorOperationId]) -> Result<TensorGraph> {
+        let mut fused = FnvHashSet::default();
+        let mut replacements = FnvHashMap::default();
+        for op in self.operations() {
+            let inputs = op.input_operations();
+            if *op.operation() != Operation::Add || inputs.len() != 2 || inputs[0] == inputs[1] {
+                continue;
+            }
+            for (i, &input_id) in inputs.iter().enumerate() {
+                let Some(matmul) = self.get_operation(input_id) else {
+                    continue;
+                };
+                if *matmul.operation() != Operation::MatMul
+                    || matmul.input_operations().len() != 2
+                    || outputs.contains(&input_id)
+                    || fused.contains(&input_id)
+                    || self.dependents_of(input_id)?.len() != 1
+                {
+                    continue;
+                }
+                let mut fc_inputs = matmul.input_operations().to_vec();
+                fc_inputs.push(inputs[1 - i]);
+                let fc = op.rewritten(Operation::FC, fc_inputs, *op.output());
+                replacements.insert(op.id(), fc);
+                fused.insert(input_id);
+                break;
+            }
+        }
+
+        let operations = self
+            .operations()
+            .iter()
+            .filter(|op| !fused.contains(&op.id()))
+            .map(|op| replacements.remove(&op.id()).unwrap_or_else(|| op.clone()))
+            .collect();
+        Ok(TensorGraph::new(operations))
+    }
+}
+
+/// The result of optimizing a stored graph: the rewritten operations plus the tensors
+/// produced by constant folding, which must be stored alongside them.
+#[derive(Debug, Clone)]
+pub struct OptimizedGraph<T> {
+    operations: Vec<TensorOperation>,
+    constants: Vec<(String, TensorValue<T>)>,
+}
+
+impl<T> OptimizedGraph<T> {
+    pub fn operations(&self) -> &[TensorOperation] {
+        &self.operations
+    }
+
+    /// Returns the folded tensors by name. Folded requested outputs keep their name and
+    /// folded intermediates are named `folded/<operation id>`.
+    pub fn constants(&self) -> &[(String, TensorValue<T>)] {
+        &self.constants
+    }
+}
+
+impl<T> OptimizedGraph<T>
+where
+    T: Float + Pod,
+{
+    /// Writes the optimized graph as a new TensorBuffers file, including the folded constants
+    /// and every tensor from `source` that the remaining operations still read.
+    pub async fn save<W>(
+        &self,
+        source: &TensorBuffers,
+        writer: &mut TensorBuffersWriter<W>,
+    ) -> Result<()>
+    where
+        W: AsyncWrite + AsyncSeek + Unpin + Send,
+    {
+        let tensors = self
+            .constants
+            .iter()
+            .map(|(name, value)| Tensor::new(name, value.data(), value.shape().to_vec()))
+            .collect();
+        source.write_operations(tensors, self.operations.clone(), writer).await
+    }
+}
+
+/// Rewrites the operation graph stored in a TensorBuffers file.
+///
+/// The passes run in order: dead-operation elimination, constant folding, and MatMul+Add fusion.
+/// A source (`Operation::None`) is constant when its tensor is stored in the file and it is not
+/// listed as a runtime input; operations whose inputs are all constant are evaluated once and
+/// replaced by a source reading the folded result.
+pub struct Optimizer<'a> {
+    tensor_buffers: &'a TensorBuffers,
+}
+
+impl<'a> Optimizer<'a> {
+    pub fn new(tensor_buffers: &'a TensorBuffers) -> Self {
+        Optimizer { tensor_buffers }
+    }
+
+    /// Optimizes the graph for computing the named outputs.
+    ///
+    /// # Arguments
+    /// * `output_names` - Names of the tensors the optimized graph must still produce.
+    /// * `input_names` - Names of source tensors provided at run time, which are never folded.
+    pub async fn optimize<T>(
+        &self,
+        output_names: &[&str],
+        input_names: &[&str],
+    ) -> Result<OptimizedGraph<T>>
+    where
+        T: Float + Pod,
+    {
+        let graph = self.tensor_buffers.graph().await?;
+        let targets = resolve_outputs(&graph, output_names)?;
+        let target_ids = targets.iter().map(|&(_, operation_id)| operation_id).collect::<Vec<_>>();
+        let target_names: FnvHashMap<TensorOperationId, &str> =
+            targets.iter().map(|&(name, operation_id)| (operation_id, name)).collect();
+        let inputs: FnvHashSet<TensorId> = input_names.iter().map(|name| hash_key(name)).collect();
+
+        let graph = graph.eliminate_dead_operations(&target_ids)?;
+
+        let mut constant = FnvHashSet::default();
+        let mut values: FnvHashMap<TensorOperationId, TensorValue<T>> = FnvHashMap::default();
+        let mut constants = Vec::new();
+        let mut operations = Vec::with_capacity(graph.operations().len());
+        for op in graph.operations_in_topological_order()? {
+            let input_ids = op.input_operations();
+            if *op.operation() == Operation::None {
+                let output = *op.output();
+                if !inputs.contains(&output)
+                    && self.tensor_buffers.stored_metadata(output).await.is_ok()
+                {
+                    constant.insert(op.id());
+                }
+                operations.push(op.clone());
+                continue;
+            }
+            if input_ids.is_empty() || !input_ids.iter().all(|id| constant.contains(id)) {
+                operations.push(op.clone());
+                continue;
+            }
+
+            for &input_id in input_ids {
+                if values.contains_key(&input_id) {
+                    continue;
+                }
+                let source = graph.get_operation(input_id).ok_or("Unknown input operation")?;
+                let tensor =
+                    self.tensor_buffers.get_tensor_data_by_id::<T>(*source.output()).await?;
+                let value = TensorValue::from(tensor);
+                values.insert(input_id, value);
+            }
+            let value = evaluate(op, &values)?;
+            let name = match target_names.get(&op.id()) {
+                Some(name) => name.to_string(),
+                None => format!("folded/{}", op.id()),
+            };
+            operations.push(op.rewritten(Operation::None, vec![], hash_key(&name)));
+            constant.insert(op.id());
+            values.insert(op.id(), value.clone());
+            constants.push((name, value));
+        }
+
+        // Folding leaves the inputs of folded operations unused.
+        let graph = TensorGraph::new(operations).eliminate_dead_operations(&target_ids)?;
+        let graph = graph.fuse_matmul_add(&target_ids)?;
+        let live: FnvHashSet<TensorId> = graph.operations().iter().map(|op| *op.output()).collect();
+        constants.retain(|(name, _)| live.contains(&hash_key(name)));
+
+        Ok(OptimizedGraph { operations: graph.operations().to_vec(), constants })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::NamedTempFile;
+    use tokio::fs::File;
+
+    use super::*;
+    use crate::{Executor, TensorBuffersWrite};
+
+    fn ids(graph: &TensorGraph) -> Vec<TensorOperationId> {
+        graph.operations().iter().map(|op| op.id()).collect()
+    }
+
+    #[test]
+    fn test_graph_passes() {
+        let graph = TensorGraph::new(vec![
+            TensorOperation::new(1, Operation::None, vec![], 10),
+            TensorOperation::new(2, Operation::None, vec![], 20),
+            TensorOperation::new(3, Operation::None, vec![], 30),
+            TensorOperation::new(4, Operation::MatMul, vec![1, 2], 40),
+            TensorOperation::new(5, Operation::Add, vec![3, 4], 50).with_name("dense"),
+            TensorOperation::new(6, Operation::Exp, vec![1], 60),
+        ]);
+        let live = graph.eliminate_dead_operations(&[5]).unwrap();
+        assert_eq!(ids(&live), vec![1, 2, 3, 4, 5]);
+
+        let fused = live.fuse_matmul_add(&[5]).unwrap();
+        assert_eq!(ids(&fused), vec![1, 2, 3, 5]);
+        let fc = fused.get_operation(5).unwrap();
+        assert_eq!(*fc.operation(), Operation::FC);
+        assert_eq!(fc.input_operations(), &[1, 2, 3]);
+        assert_eq!(*fc.output(), 50);
+        assert_eq!(fc.name(), Some("dense"));
+
+        // The MatMul result is requested, so it must survive.
+        let kept = live.fuse_matmul_add(&[4, 5]).unwrap();
+        assert_eq!(ids(&kept), vec![1, 2, 3, 4, 5]);
+    }
+
+    #[tokio::test]
+    async fn test_optimize_and_save() {
+        let x = Tensor::new("x", &[1.0f32, 2.0], vec![1, 2]);
+        let w1 = Tensor::new("w1", &[1.0f32, 0.0, 0.0, 2.0], vec![2, 2]);
+        let w2 = Tensor::new("w2", &[3.0f32, 0.0, 0.0, 1.0], vec![2, 2]);
+        let b = Tensor::new("b", &[0.5f32, 0.5], vec![2]);
+        let operations = vec![
+            TensorOperation::new(1, Operation::None, vec![], x.id()),
+            TensorOperation::new(2, Operation::None, vec![], w1.id()),
+            TensorOperation::new(3, Operation::None, vec![], w2.id()),
+            TensorOperation::new(4, Operation::MatMul, vec![2, 3], hash_key("w")),
+            TensorOperation::new(5, Operation::MatMul, vec![1, 4], hash_key("xw")),
+            TensorOperation::new(6, Operation::None, vec![], b.id()),
+            TensorOperation::new(7, Operation::Add, vec![5, 6], hash_key("y")),
+            TensorOperation::new(8, Operation::Exp, vec![1], hash_key("unused")),
+        ];
+        let source_tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(source_tmp.path()).await.unwrap();
+        TensorBuffersWriter::new(&mut file).write(vec![x, w1, w2, b], operations).await.unwrap();
+
+        let url = format!("file://{}", source_tmp.path().display());
+        let source = TensorBuffers::open(&url).await.unwrap();
+        let optimized = Optimizer::new(&source).optimize::<f32>(&["y"], &["x"]).await.unwrap();
+
+        let mut operations = optimized.operations().iter().map(|op| op.id()).collect::<Vec<_>>();
+        operations.sort();
+        assert_eq!(operations, vec![1, 4, 6, 7]);
+        let fc = optimized.operations().iter().find(|op| op.id() == 7).unwrap();
+        assert_eq!(*fc.operation(), Operation::FC);
+        assert_eq!(optimized.constants().len(), 1);
+        assert_eq!(optimized.constants()[0].0, "folded/4");
+        assert_eq!(optimized.constants()[0].1.data(), &[3.0, 0.0, 0.0, 2.0]);
+
+        let optimized_tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(optimized_tmp.path()).await.unwrap();
+        optimized.save(&source, &mut TensorBuffersWriter::new(&mut file)).await.unwrap();
+
+        let url = format!("file://{}", optimized_tmp.path().display());
+        let rewritten = TensorBuffers::open(&url).await.unwrap();
+        let expected = Executor::new(&source).run::<f32>(&["y"]).await.unwrap();
+        let actual = Executor::new(&rewritten).run::<f32>(&["y"]).await.unwrap();
+        assert_eq!(actual["y"], expected["y"]);
+        assert_eq!(actual["y"].data(), &[3.5, 4.5]);
+    }
+}
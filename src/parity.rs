@@ -0,0 +1,469 @@
+//! The optional Reed-Solomon parity section, which lets a file with a few corrupted sectors be
+//! repaired in place.
+//!
+//! Readers only need to skip the section, so that part is always compiled. Adding parity to a file
+//! and repairing it take the `fec` feature.
+
+#[cfg(feature = "fec")]
+use std::path::Path;
+
+#[cfg(feature = "fec")]
+use reed_solomon_erasure::galois_8::ReedSolomon;
+#[cfg(feature = "fec")]
+use tokio::{
+    fs::{File, OpenOptions as FileOptions},
+    io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt},
+};
+#[cfg(feature = "fec")]
+use tracing::{info, instrument, warn};
+
+use crate::constants::PARITY_MAGIC_BYTES;
+#[cfg(feature = "fec")]
+use crate::Result;
+
+/// Size of the trailer that ends a parity section.
+pub(crate) const PARITY_TRAILER_SIZE: usize = 32;
+
+/// The trailer of a parity section, which describes how the bytes before it are protected.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct ParityTrailer {
+    /// Size of the file the parity protects, which the parity section follows.
+    pub protected_size: u64,
+    pub sector_size: u32,
+    /// Data sectors per Reed-Solomon group.
+    pub data_sectors: u16,
+    /// Parity sectors per group, the most damaged sectors a group can be repaired from.
+    pub parity_sectors: u16,
+    /// CRC32C of the sector checksums.
+    pub checksums_checksum: u32,
+}
+
+impl ParityTrailer {
+    /// Parses the trailer at the end of `end`, the last bytes of a file of `file_size` bytes.
+    ///
+    /// # Returns
+    /// `None` if the file doesn't end with an intact trailer whose section fills the file after
+    /// the protected bytes.
+    pub(crate) fn parse(end: &[u8], file_size: u64) -> Option<Self> {
+        let trailer = end.get(end.len().checked_sub(PARITY_TRAILER_SIZE)?..)?;
+        let u16_at = |offset: usize| u16::from_le_bytes([trailer[offset], trailer[offset + 1]]);
+        let u32_at =
+            |offset: usize| u32::from_le_bytes(trailer[offset..offset + 4].try_into().unwrap());
+        if &trailer[28..] != PARITY_MAGIC_BYTES || crc32c::crc32c(&trailer[..24]) != u32_at(24) {
+            return None;
+        }
+        let parsed = ParityTrailer {
+            protected_size: u64::from_le_bytes(trailer[..8].try_into().unwrap()),
+            sector_size: u32_at(8),
+            data_sectors: u16_at(12),
+            parity_sectors: u16_at(14),
+            checksums_checksum: u32_at(16),
+        };
+        let valid = parsed.sector_size > 0
+            && parsed.data_sectors > 0
+            && parsed.parity_sectors > 0
+            && parsed.section_size() == file_size.checked_sub(parsed.protected_size);
+        valid.then_some(parsed)
+    }
+
+    #[cfg(feature = "fec")]
+    pub(crate) fn encode(&self) -> [u8; PARITY_TRAILER_SIZE] {
+        let mut trailer = [0; PARITY_TRAILER_SIZE];
+        trailer[..8].copy_from_slice(&self.protected_size.to_le_bytes());
+        trailer[8..12].copy_from_slice(&self.sector_size.to_le_bytes());
+        trailer[12..14].copy_from_slice(&self.data_sectors.to_le_bytes());
+        trailer[14..16].copy_from_slice(&self.parity_sectors.to_le_bytes());
+        trailer[16..20].copy_from_slice(&self.checksums_checksum.to_le_bytes());
+        let checksum = crc32c::crc32c(&trailer[..24]);
+        trailer[24..28].copy_from_slice(&checksum.to_le_bytes());
+        trailer[28..].copy_from_slice(PARITY_MAGIC_BYTES);
+        trailer
+    }
+
+    /// Returns the number of data sectors, the last of which may be partial.
+    pub(crate) fn sectors(&self) -> u64 {
+        self.protected_size.div_ceil(self.sector_size as u64)
+    }
+
+    pub(crate) fn groups(&self) -> u64 {
+        self.sectors().div_ceil(self.data_sectors as u64)
+    }
+
+    /// Returns the number of sector checksums: one per data sector, then one per parity sector.
+    pub(crate) fn checksums(&self) -> u64 {
+        self.sectors() + self.groups() * self.parity_sectors as u64
+    }
+
+    /// Returns where the parity sector `index` of `group` starts.
+    #[cfg(feature = "fec")]
+    pub(crate) fn parity_offset(&self, group: u64, index: usize) -> u64 {
+        let sector = group * self.parity_sectors as u64 + index as u64;
+        self.protected_size + sector * self.sector_size as u64
+    }
+
+    /// Returns where the sector checksums start.
+    #[cfg(feature = "fec")]
+    pub(crate) fn checksums_offset(&self) -> u64 {
+        self.parity_offset(self.groups(), 0)
+    }
+
+    /// Returns the size of the section after the protected bytes, or `None` if it overflows.
+    fn section_size(&self) -> Option<u64> {
+        let parity = self.groups().checked_mul(self.parity_sectors as u64)?;
+        let parity_size = parity.checked_mul(self.sector_size as u64)?;
+        let checksums_size = self.checksums().checked_mul(4)?;
+        parity_size.checked_add(checksums_size)?.checked_add(PARITY_TRAILER_SIZE as u64)
+    }
+}
+
+/// Returns the size of a file of `file_size` bytes without its parity section, given its last
+/// bytes, or `file_size` if it has none.
+pub(crate) fn protected_size(end: &[u8], file_size: u64) -> u64 {
+    ParityTrailer::parse(end, file_size).map_or(file_size, |trailer| trailer.protected_size)
+}
+
+/// Returns a file held in memory without its parity section.
+pub(crate) fn without_parity(bytes: &[u8]) -> &[u8] {
+    &bytes[..protected_size(bytes, bytes.len() as u64) as usize]
+}
+
+/// How `add_parity` protects a file.
+#[cfg(feature = "fec")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParityOptions {
+    sector_size: u32,
+    data_sectors: u16,
+    parity_sectors: u16,
+}
+
+#[cfg(feature = "fec")]
+impl Default for ParityOptions {
+    /// 4 KiB sectors in groups of 64, each with 4 parity sectors, which adds about 6% to the file.
+    fn default() -> Self {
+        ParityOptions { sector_size: 4096, data_sectors: 64, parity_sectors: 4 }
+    }
+}
+
+#[cfg(feature = "fec")]
+impl ParityOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the size of the sectors the file is checked and repaired in. Damage is counted in
+    /// sectors, so they are best no larger than the storage's own.
+    pub fn with_sector_size(mut self, sector_size: u32) -> Self {
+        self.sector_size = sector_size;
+        self
+    }
+
+    /// Sets how many data sectors share parity sectors, and how many parity sectors they get.
+    /// Each group can be repaired with up to `parity_sectors` of its sectors damaged, and
+    /// together they can't exceed 256 sectors.
+    pub fn with_sectors(mut self, data_sectors: u16, parity_sectors: u16) -> Self {
+        self.data_sectors = data_sectors;
+        self.parity_sectors = parity_sectors;
+        self
+    }
+}
+
+/// What `repair` found and fixed.
+#[cfg(feature = "fec")]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RepairReport {
+    /// Number of sectors checked, data and parity.
+    pub checked: u64,
+    /// Where each sector that was damaged and rewritten starts.
+    pub repaired: Vec<u64>,
+    /// Where each damaged sector that couldn't be repaired starts, because too many sectors of
+    /// its group are damaged.
+    pub unrepairable: Vec<u64>,
+}
+
+#[cfg(feature = "fec")]
+impl RepairReport {
+    /// Returns whether the file is intact now.
+    pub fn is_ok(&self) -> bool {
+        self.unrepairable.is_empty()
+    }
+}
+
+/// Appends a Reed-Solomon parity section to a TensorBuffers file, so `repair` can restore it
+/// when a few of its sectors are corrupted, e.g. after years on a cheap storage tier. Readers skip
+/// the section, and files with it stay readable by this version without the `fec` feature.
+///
+/// The file is split into sectors, and each group of data sectors gets parity sectors. Every
+/// sector's CRC32C is stored too, so damaged sectors are found without other checksums. The
+/// trailer and the checksums aren't protected themselves, so damage to them makes the parity
+/// unusable, and to the trailer the file unreadable until it is removed.
+///
+/// # Arguments
+/// * `path` - The file, which must not have a parity section yet.
+/// * `options` - The size of the sectors and of their groups.
+///
+/// # Returns
+/// An error if the file already has parity or the options are invalid. The file is left as it
+/// was if adding the parity fails.
+#[cfg(feature = "fec")]
+#[instrument(skip_all, fields(path = %path.as_ref().display()))]
+pub async fn add_parity(path: impl AsRef<Path>, options: ParityOptions) -> Result<()> {
+    if options.sector_size == 0 {
+        return Err("The sector size must not be zero".into());
+    }
+    let data_sectors = options.data_sectors as usize;
+    let parity_sectors = options.parity_sectors as usize;
+    let codec = ReedSolomon::new(data_sectors, parity_sectors)
+        .map_err(|error| format!("Invalid parity sectors: {:?}", error))?;
+    let mut file = FileOptions::new().read(true).write(true).open(path.as_ref()).await?;
+    let protected_size = file.metadata().await?.len();
+    if find_trailer(&mut file).await?.is_some() {
+        return Err(format!("{} already has a parity section", path.as_ref().display()).into());
+    }
+    let mut trailer = ParityTrailer {
+        protected_size,
+        sector_size: options.sector_size,
+        data_sectors: options.data_sectors,
+        parity_sectors: options.parity_sectors,
+        checksums_checksum: 0,
+    };
+    let result = write_parity(&mut file, &codec, &mut trailer).await;
+    if result.is_err() {
+        // Bytes after the footer would make the file unreadable.
+        if let Err(error) = file.set_len(protected_size).await {
+            warn!(%error, "Failed to remove partial parity");
+        }
+    }
+    result?;
+    info!(groups = trailer.groups(), "Added parity");
+    Ok(())
+}
+
+/// Encodes and appends the parity sectors, their checksums and `trailer`, completing it.
+#[cfg(feature = "fec")]
+async fn write_parity(
+    file: &mut File,
+    codec: &ReedSolomon,
+    trailer: &mut ParityTrailer,
+) -> Result<()> {
+    let sector_size = trailer.sector_size as usize;
+    let mut data_checksums = Vec::with_capacity(trailer.sectors() as usize * 4);
+    let mut parity_checksums = Vec::new();
+    for group in 0..trailer.groups() {
+        let mut shards = read_group(file, trailer, group).await?;
+        shards.extend((0..trailer.parity_sectors).map(|_| vec![0; sector_size]));
+        codec.encode(&mut shards).map_err(|error| format!("{:?}", error))?;
+        let (data, parity) = shards.split_at(trailer.data_sectors as usize);
+        for (index, shard) in data.iter().enumerate() {
+            if group_sector(trailer, group, index) < trailer.sectors() {
+                data_checksums.extend_from_slice(&crc32c::crc32c(shard).to_le_bytes());
+            }
+        }
+        file.seek(std::io::SeekFrom::Start(trailer.parity_offset(group, 0))).await?;
+        for shard in parity {
+            parity_checksums.extend_from_slice(&crc32c::crc32c(shard).to_le_bytes());
+            file.write_all(shard).await?;
+        }
+    }
+    data_checksums.extend_from_slice(&parity_checksums);
+    trailer.checksums_checksum = crc32c::crc32c(&data_checksums);
+    file.seek(std::io::SeekFrom::Start(trailer.checksums_offset())).await?;
+    file.write_all(&data_checksums).await?;
+    file.write_all(&trailer.encode()).await?;
+    file.sync_all().await?;
+    Ok(())
+}
+
+/// Checks every sector of a file with a parity section against its checksum and rewrites the
+/// damaged ones in place from the rest of their group, data and parity sectors alike.
+///
+/// # Arguments
+/// * `path` - The file, written with `add_parity`.
+///
+/// # Returns
+/// The sectors repaired and those too damaged to repair, or an error if the file has no intact
+/// parity section.
+#[cfg(feature = "fec")]
+#[instrument(skip_all, fields(path = %path.as_ref().display()))]
+pub async fn repair(path: impl AsRef<Path>) -> Result<RepairReport> {
+    let mut file = FileOptions::new().read(true).write(true).open(path.as_ref()).await?;
+    let trailer = find_trailer(&mut file)
+        .await?
+        .ok_or_else(|| format!("{} has no intact parity section", path.as_ref().display()))?;
+    let mut checksums = vec![0; trailer.checksums() as usize * 4];
+    file.seek(std::io::SeekFrom::Start(trailer.checksums_offset())).await?;
+    file.read_exact(&mut checksums).await?;
+    if crc32c::crc32c(&checksums) != trailer.checksums_checksum {
+        return Err("The parity section's sector checksums are damaged".into());
+    }
+    let checksum = |index: u64| {
+        let offset = index as usize * 4;
+        u32::from_le_bytes(checksums[offset..offset + 4].try_into().unwrap())
+    };
+
+    let codec = ReedSolomon::new(trailer.data_sectors as usize, trailer.parity_sectors as usize)
+        .map_err(|error| format!("Invalid parity sectors: {:?}", error))?;
+    let sector_size = trailer.sector_size as u64;
+    let mut report = RepairReport { checked: trailer.checksums(), ..Default::default() };
+    for group in 0..trailer.groups() {
+        let mut shards = read_group(&mut file, &trailer, group)
+            .await?
+            .into_iter()
+            .enumerate()
+            .map(|(index, shard)| {
+                let sector = group_sector(&trailer, group, index);
+                // Sectors past the end of the file are zeros that aren't stored.
+                let intact =
+                    sector >= trailer.sectors() || crc32c::crc32c(&shard) == checksum(sector);
+                (shard, intact, sector * sector_size)
+            })
+            .collect::<Vec<_>>();
+        file.seek(std::io::SeekFrom::Start(trailer.parity_offset(group, 0))).await?;
+        for index in 0..trailer.parity_sectors as usize {
+            let mut shard = vec![0; sector_size as usize];
+            file.read_exact(&mut shard).await?;
+            let sector = trailer.sectors() + group * trailer.parity_sectors as u64 + index as u64;
+            let intact = crc32c::crc32c(&shard) == checksum(sector);
+            shards.push((shard, intact, trailer.parity_offset(group, index)));
+        }
+
+        let damaged = shards.iter().filter(|(_, intact, _)| !intact).map(|(.., offset)| *offset);
+        let damaged = damaged.collect::<Vec<_>>();
+        if damaged.is_empty() {
+            continue;
+        }
+        if damaged.len() > trailer.parity_sectors as usize {
+            warn!(group, damaged = damaged.len(), "Too many damaged sectors to repair");
+            report.unrepairable.extend(damaged);
+            continue;
+        }
+        let mut present =
+            shards.iter_mut().map(|(shard, intact, _)| (shard, *intact)).collect::<Vec<_>>();
+        codec.reconstruct(&mut present).map_err(|error| format!("{:?}", error))?;
+        for (shard, intact, offset) in &shards {
+            if *intact {
+                continue;
+            }
+            // The last data sector is only stored up to the end of the protected bytes.
+            let end = (offset + sector_size).min(match *offset < trailer.protected_size {
+                true => trailer.protected_size,
+                false => trailer.checksums_offset(),
+            });
+            file.seek(std::io::SeekFrom::Start(*offset)).await?;
+            file.write_all(&shard[..(end - offset) as usize]).await?;
+        }
+        report.repaired.extend(damaged);
+    }
+    file.sync_all().await?;
+    info!(repaired = report.repaired.len(), unrepairable = report.unrepairable.len(), "Repaired");
+    Ok(report)
+}
+
+/// Returns the trailer of the file's parity section, if it has an intact one.
+#[cfg(feature = "fec")]
+async fn find_trailer(file: &mut File) -> Result<Option<ParityTrailer>> {
+    let file_size = file.metadata().await?.len();
+    if file_size < PARITY_TRAILER_SIZE as u64 {
+        return Ok(None);
+    }
+    let mut end = [0; PARITY_TRAILER_SIZE];
+    file.seek(std::io::SeekFrom::Start(file_size - end.len() as u64)).await?;
+    file.read_exact(&mut end).await?;
+    Ok(ParityTrailer::parse(&end, file_size))
+}
+
+/// Returns the index in the file of the data sector `index` of `group`.
+#[cfg(feature = "fec")]
+fn group_sector(trailer: &ParityTrailer, group: u64, index: usize) -> u64 {
+    group * trailer.data_sectors as u64 + index as u64
+}
+
+/// Reads the data sectors of `group`, padding the last with zeros and filling sectors past the
+/// end of the protected bytes with them.
+#[cfg(feature = "fec")]
+async fn read_group(file: &mut File, trailer: &ParityTrailer, group: u64) -> Result<Vec<Vec<u8>>> {
+    let sector_size = trailer.sector_size as u64;
+    let start = group_sector(trailer, group, 0) * sector_size;
+    let size = (trailer.data_sectors as u64 * sector_size).min(trailer.protected_size - start);
+    let mut data = vec![0; trailer.data_sectors as usize * sector_size as usize];
+    file.seek(std::io::SeekFrom::Start(start)).await?;
+    file.read_exact(&mut data[..size as usize]).await?;
+    Ok(data.chunks_exact(sector_size as usize).map(<[u8]>::to_vec).collect())
+}
+
+#[cfg(all(test, feature = "fec"))]
+mod tests {
+    use tempfile::NamedTempFile;
+
+    use super::*;
+    use crate::{parse_slice, Tensor, TensorBuffers, TensorBuffersWrite, TensorBuffersWriter};
+
+    async fn write_file(path: &Path) -> Vec<f32> {
+        let data = (0..3000).map(|i| i as f32).collect::<Vec<_>>();
+        let mut file = File::create(path).await.unwrap();
+        let tensors =
+            vec![Tensor::new("w", &data, vec![3000]), Tensor::new("b", &[1.0f32], vec![1])];
+        TensorBuffersWriter::new(&mut file).write(tensors, vec![]).await.unwrap();
+        data
+    }
+
+    #[tokio::test]
+    async fn test_repair() {
+        let tmp = NamedTempFile::new().unwrap();
+        let data = write_file(tmp.path()).await;
+        let original = std::fs::read(tmp.path()).unwrap();
+        let options = ParityOptions::new().with_sector_size(512).with_sectors(8, 2);
+        add_parity(tmp.path(), options).await.unwrap();
+        let protected = std::fs::read(tmp.path()).unwrap();
+        assert_eq!(&protected[..original.len()], original);
+        let trailer = ParityTrailer::parse(&protected, protected.len() as u64).unwrap();
+        assert_eq!((trailer.sectors(), trailer.groups()), (original.len().div_ceil(512) as u64, 3));
+        let error = add_parity(tmp.path(), options).await.unwrap_err();
+        assert!(error.to_string().ends_with("already has a parity section"));
+
+        // Readers skip the parity section, from files and from memory.
+        let url = format!("file://{}", tmp.path().display());
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        assert_eq!(tensor_buffers.file_size().await.unwrap(), original.len() as u64);
+        assert_eq!(tensor_buffers.get_tensor_data_by_name::<f32>("w").await.unwrap().data(), data);
+        assert!(parse_slice(&protected).unwrap().tensor::<f32>("b").is_ok());
+
+        let report = repair(tmp.path()).await.unwrap();
+        assert_eq!(report, RepairReport { checked: trailer.checksums(), ..Default::default() });
+
+        // Two damaged sectors per group are repaired, including the footer's and parity sectors.
+        let mut damaged = protected.clone();
+        let parity = trailer.parity_offset(1, 1) as usize;
+        for offset in [100, 1000, 4200, parity + 7, original.len() - 10] {
+            damaged[offset] ^= 0xff;
+        }
+        std::fs::write(tmp.path(), &damaged).unwrap();
+        let report = repair(tmp.path()).await.unwrap();
+        let last = (original.len() - 10) / 512 * 512;
+        assert_eq!(report.repaired, [0, 512, 4096, parity as u64, last as u64]);
+        assert!(report.is_ok());
+        assert_eq!(std::fs::read(tmp.path()).unwrap(), protected);
+
+        // Three damaged sectors in a group are too many.
+        let mut damaged = protected.clone();
+        for offset in [0, 600, 1200] {
+            damaged[offset] ^= 1;
+        }
+        std::fs::write(tmp.path(), &damaged).unwrap();
+        let report = repair(tmp.path()).await.unwrap();
+        assert_eq!(report.unrepairable, [0, 512, 1024]);
+        assert!(!report.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_add_parity_errors() {
+        let tmp = NamedTempFile::new().unwrap();
+        write_file(tmp.path()).await;
+        let original = std::fs::read(tmp.path()).unwrap();
+        assert!(add_parity(tmp.path(), ParityOptions::new().with_sector_size(0)).await.is_err());
+        let too_many = ParityOptions::new().with_sectors(250, 10);
+        assert!(add_parity(tmp.path(), too_many).await.is_err());
+        assert_eq!(std::fs::read(tmp.path()).unwrap(), original);
+        let error = repair(tmp.path()).await.unwrap_err();
+        assert!(error.to_string().ends_with("has no intact parity section"));
+    }
+}
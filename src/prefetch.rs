@@ -0,0 +1,265 @@
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap, HashSet},
+    sync::{Arc, Mutex, OnceLock},
+    time::Duration,
+};
+
+use bytes::Bytes;
+use tokio::{
+    sync::{Notify, OwnedSemaphorePermit, Semaphore},
+    task::JoinHandle,
+    time::{sleep_until, Instant},
+};
+use tracing::{debug, warn};
+
+use crate::reader_pool::ReaderPool;
+
+/// Number of prefetch reads in flight at once by default.
+const DEFAULT_MAX_CONCURRENT: usize = 4;
+
+/// How urgently a prefetched tensor is needed. Queued requests are fetched highest priority
+/// first, and in request order within a priority.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PrefetchPriority {
+    /// Warming up a model that isn't serving yet.
+    #[default]
+    Warmup,
+    /// Needed soon by a model that is serving.
+    HotPath,
+}
+
+/// Limits on the background prefetcher, so warming the next model doesn't starve the current one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PrefetchBudget {
+    /// Most prefetch reads in flight at once, each on its own handle to the file.
+    pub max_concurrent: usize,
+    /// Fastest rate at which reads are started, in bytes per second. Unlimited if `None`.
+    pub max_bytes_per_second: Option<u64>,
+    /// Most prefetched data held until loads use it, in bytes. Fetching waits while it is used up,
+    /// and larger tensors are not prefetched. Unlimited if `None`.
+    pub max_cached_bytes: Option<usize>,
+}
+
+impl Default for PrefetchBudget {
+    fn default() -> Self {
+        PrefetchBudget {
+            max_concurrent: DEFAULT_MAX_CONCURRENT,
+            max_bytes_per_second: None,
+            max_cached_bytes: None,
+        }
+    }
+}
+
+impl PrefetchBudget {
+    /// Sets the most prefetch reads in flight at once.
+    pub fn with_max_concurrent(mut self, max_concurrent: usize) -> Self {
+        self.max_concurrent = max_concurrent;
+        self
+    }
+
+    /// Sets the fastest rate at which reads are started.
+    pub fn with_max_bytes_per_second(mut self, bytes: u64) -> Self {
+        self.max_bytes_per_second = Some(bytes);
+        self
+    }
+
+    /// Sets the most prefetched data held until loads use it.
+    pub fn with_max_cached_bytes(mut self, bytes: usize) -> Self {
+        self.max_cached_bytes = Some(bytes);
+        self
+    }
+}
+
+/// A byte range of stored tensor data, which deduplicated tensors share.
+type Region = (u64, usize);
+
+/// A queued prefetch.
+#[derive(PartialEq, Eq)]
+struct Request {
+    priority: PrefetchPriority,
+    sequence: u64,
+    region: Region,
+}
+
+impl Ord for Request {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // `BinaryHeap` pops the greatest request: the highest priority, then the oldest.
+        self.priority.cmp(&other.priority).then(other.sequence.cmp(&self.sequence))
+    }
+}
+
+impl PartialOrd for Request {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[derive(Default)]
+struct Cache {
+    entries: HashMap<Region, (Bytes, Option<OwnedSemaphorePermit>)>,
+    in_flight: HashSet<Region>,
+    bytes: usize,
+}
+
+struct Shared {
+    readers: Arc<ReaderPool>,
+    budget: PrefetchBudget,
+    queue: Mutex<(BinaryHeap<Request>, u64)>,
+    queued: Notify,
+    cache: Mutex<Cache>,
+    cache_space: Option<Arc<Semaphore>>,
+}
+
+/// Fetches stored tensor data in the background on handles from the file's reader pool, keeping
+/// it until a load uses it. Its task starts with the first request and stops when the prefetcher
+/// is dropped.
+pub(crate) struct Prefetcher {
+    shared: Arc<Shared>,
+    task: OnceLock<JoinHandle<()>>,
+}
+
+impl Prefetcher {
+    pub fn new(readers: Arc<ReaderPool>, budget: PrefetchBudget) -> Self {
+        // Permits are counted in u32, so larger budgets are capped, as in `run`.
+        let cache_space = budget
+            .max_cached_bytes
+            .map(|bytes| Arc::new(Semaphore::new(bytes.min(u32::MAX as usize))));
+        let shared = Shared {
+            readers,
+            budget,
+            queue: Mutex::new((BinaryHeap::new(), 0)),
+            queued: Notify::new(),
+            cache: Mutex::new(Cache::default()),
+            cache_space,
+        };
+        Prefetcher { shared: Arc::new(shared), task: OnceLock::new() }
+    }
+
+    /// Queues a region of stored data to be fetched.
+    ///
+    /// # Arguments
+    /// * `offset` - Where the data starts in the file.
+    /// * `size` - The size of the data in bytes.
+    /// * `priority` - How urgently the data is needed.
+    pub fn request(&self, offset: u64, size: usize, priority: PrefetchPriority) {
+        self.task.get_or_init(|| tokio::spawn(run(self.shared.clone())));
+        let mut queue = self.shared.queue.lock().unwrap();
+        let (requests, sequence) = &mut *queue;
+        *sequence += 1;
+        requests.push(Request { priority, sequence: *sequence, region: (offset, size) });
+        self.shared.queued.notify_one();
+    }
+
+    /// Removes and returns the prefetched data of a region, if it has been fetched.
+    pub fn take(&self, offset: u64, size: usize) -> Option<Bytes> {
+        let mut cache = self.shared.cache.lock().unwrap();
+        let entry = cache.entries.remove(&(offset, size));
+        self.shared.readers.counters().record_cache_lookup(entry.is_some());
+        let (bytes, _permit) = entry?;
+        cache.bytes -= bytes.len();
+        Some(bytes)
+    }
+
+    /// Returns the number of prefetched bytes waiting to be used.
+    pub fn cached_bytes(&self) -> usize {
+        self.shared.cache.lock().unwrap().bytes
+    }
+}
+
+impl Drop for Prefetcher {
+    fn drop(&mut self) {
+        if let Some(task) = self.task.get() {
+            task.abort();
+        }
+    }
+}
+
+/// Starts reads of queued requests in priority order within the budget.
+async fn run(shared: Arc<Shared>) {
+    let budget = shared.budget;
+    let concurrency = Arc::new(Semaphore::new(budget.max_concurrent.max(1)));
+    let max_cached_bytes = budget.max_cached_bytes.unwrap_or_default().min(u32::MAX as usize);
+    let mut next_start = Instant::now();
+    loop {
+        let request = shared.queue.lock().unwrap().0.pop();
+        let Some(Request { region, .. }) = request else {
+            shared.queued.notified().await;
+            continue;
+        };
+        let (offset, size) = region;
+        {
+            let mut cache = shared.cache.lock().unwrap();
+            if cache.entries.contains_key(&region) || !cache.in_flight.insert(region) {
+                continue;
+            }
+        }
+        let space = match &shared.cache_space {
+            Some(_) if size > max_cached_bytes => {
+                warn!(offset, size, "Not prefetching data larger than the cache budget");
+                shared.cache.lock().unwrap().in_flight.remove(&region);
+                continue;
+            }
+            Some(space) => Some(space.clone().acquire_many_owned(size as u32).await.unwrap()),
+            None => None,
+        };
+        let permit = concurrency.clone().acquire_owned().await.unwrap();
+        if let Some(rate) = budget.max_bytes_per_second {
+            sleep_until(next_start).await;
+            let duration = Duration::from_secs_f64(size as f64 / rate.max(1) as f64);
+            next_start = next_start.max(Instant::now()) + duration;
+        }
+        let shared = shared.clone();
+        tokio::spawn(async move {
+            fetch(&shared, region, space).await;
+            drop(permit);
+        });
+    }
+}
+
+/// Reads a region into the cache.
+async fn fetch(shared: &Shared, region: Region, space: Option<OwnedSemaphorePermit>) {
+    let (offset, size) = region;
+    let mut data = vec![0; size];
+    // Errors are turned into strings right away, since the task must stay `Send`.
+    let result = match shared.readers.get().await.map_err(|error| error.to_string()) {
+        Ok(mut reader) => {
+            reader.read_at(offset, &mut data).await.map_err(|error| error.to_string())
+        }
+        Err(error) => Err(error),
+    };
+
+    let mut cache = shared.cache.lock().unwrap();
+    cache.in_flight.remove(&region);
+    match result {
+        Ok(()) => {
+            debug!(offset, size, "Prefetched tensor data");
+            cache.bytes += size;
+            cache.entries.insert(region, (Bytes::from(data), space));
+        }
+        Err(error) => warn!(offset, size, %error, "Failed to prefetch tensor data"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_order() {
+        let mut queue = BinaryHeap::new();
+        for (sequence, priority) in [
+            PrefetchPriority::Warmup,
+            PrefetchPriority::HotPath,
+            PrefetchPriority::Warmup,
+            PrefetchPriority::HotPath,
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            queue.push(Request { priority, sequence: sequence as u64, region: (0, 0) });
+        }
+        let order = std::iter::from_fn(|| queue.pop()).map(|r| r.sequence).collect::<Vec<_>>();
+        assert_eq!(order, [1, 3, 0, 2]);
+    }
+}
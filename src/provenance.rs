@@ -0,0 +1,153 @@
+#[cfg(any(feature = "async", feature = "mmap"))]
+use flatbuffers::{FlatBufferBuilder, WIPOffset};
+
+#[cfg(any(feature = "async", feature = "mmap"))]
+use crate::generated::tensor_buffers::{ProvenanceMetadata, ProvenanceMetadataArgs};
+
+/// Where a file's weights came from, so published artifacts can be traced back to the code, data
+/// and run that produced them. Every field is optional.
+///
+/// ```
+/// use tensorbuffers::Provenance;
+///
+/// let provenance = Provenance::new()
+///     .with_source_commit("3f2c1e0")
+///     .with_dataset_hash("sha256:9b71d2")
+///     .with_parent_checkpoint("base-7b");
+/// assert_eq!(provenance.dataset_hashes, ["sha256:9b71d2"]);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Provenance {
+    /// The git commit of the training code.
+    pub source_commit: Option<String>,
+    /// Hashes of the datasets trained on, e.g. `sha256:` digests.
+    pub dataset_hashes: Vec<String>,
+    /// The hash of the training configuration.
+    pub config_hash: Option<String>,
+    /// The id of the checkpoint training started from, e.g. the base of a fine-tuned model.
+    pub parent_checkpoint: Option<String>,
+}
+
+impl Provenance {
+    /// Creates provenance with no fields set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the git commit of the training code.
+    pub fn with_source_commit(mut self, commit: &str) -> Self {
+        self.source_commit = Some(commit.to_string());
+        self
+    }
+
+    /// Adds the hash of a dataset trained on.
+    pub fn with_dataset_hash(mut self, hash: &str) -> Self {
+        self.dataset_hashes.push(hash.to_string());
+        self
+    }
+
+    /// Records the hash of the training configuration.
+    pub fn with_config_hash(mut self, hash: &str) -> Self {
+        self.config_hash = Some(hash.to_string());
+        self
+    }
+
+    /// Records the id of the checkpoint training started from.
+    pub fn with_parent_checkpoint(mut self, id: &str) -> Self {
+        self.parent_checkpoint = Some(id.to_string());
+        self
+    }
+
+    #[cfg(any(feature = "async", feature = "mmap"))]
+    pub(crate) fn from_metadata(metadata: &ProvenanceMetadata) -> Self {
+        Self {
+            source_commit: metadata.source_commit().map(str::to_string),
+            dataset_hashes: metadata
+                .dataset_hashes()
+                .into_iter()
+                .flatten()
+                .map(str::to_string)
+                .collect(),
+            config_hash: metadata.config_hash().map(str::to_string),
+            parent_checkpoint: metadata.parent_checkpoint().map(str::to_string),
+        }
+    }
+
+    /// Builds the table describing the provenance. Fields that aren't set are left out.
+    #[cfg(any(feature = "async", feature = "mmap"))]
+    pub(crate) fn build_table<'a>(
+        &self,
+        builder: &mut FlatBufferBuilder<'a>,
+    ) -> WIPOffset<ProvenanceMetadata<'a>> {
+        let source_commit =
+            self.source_commit.as_deref().map(|commit| builder.create_string(commit));
+        let dataset_hashes = (!self.dataset_hashes.is_empty()).then(|| {
+            let hashes = self
+                .dataset_hashes
+                .iter()
+                .map(|hash| builder.create_string(hash))
+                .collect::<Vec<_>>();
+            builder.create_vector(&hashes)
+        });
+        let config_hash = self.config_hash.as_deref().map(|hash| builder.create_string(hash));
+        let parent_checkpoint =
+            self.parent_checkpoint.as_deref().map(|id| builder.create_string(id));
+        ProvenanceMetadata::create(builder, &ProvenanceMetadataArgs {
+            source_commit,
+            dataset_hashes,
+            config_hash,
+            parent_checkpoint,
+        })
+    }
+}
+
+#[cfg(all(test, feature = "async"))]
+mod tests {
+    use futures::SinkExt;
+    use tempfile::NamedTempFile;
+    use tokio::fs::File;
+
+    use super::*;
+    use crate::{
+        Tensor, TensorBuffers, TensorBuffersSink, TensorBuffersWrite, TensorBuffersWriter,
+        WriterOptions,
+    };
+
+    #[tokio::test]
+    async fn test_provenance() {
+        let provenance = Provenance::new()
+            .with_source_commit("3f2c1e0a9d")
+            .with_dataset_hash("sha256:9b71d2")
+            .with_dataset_hash("sha256:04e6aa")
+            .with_config_hash("sha256:77c1f0")
+            .with_parent_checkpoint("base-7b@2026-09-01");
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        let options = WriterOptions::new().with_columnar_metadata(true);
+        let mut writer = TensorBuffersWriter::with_options(&mut file, options);
+        writer.set_provenance(provenance.clone());
+        let tensors = vec![Tensor::new("w", &[1.0f32; 4], vec![4])];
+        writer.write(tensors, vec![]).await.unwrap();
+
+        let url = format!("file://{}", tmp.path().display());
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        assert_eq!(tensor_buffers.provenance().await.unwrap(), Some(provenance));
+
+        // Unset fields are left out and read back as unset.
+        let partial = Provenance::new().with_parent_checkpoint("run-41");
+        let file = File::create(tmp.path()).await.unwrap();
+        let mut sink = TensorBuffersSink::new(file, WriterOptions::new()).unwrap();
+        sink.set_provenance(partial.clone());
+        sink.close().await.unwrap();
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        let read = tensor_buffers.provenance().await.unwrap().unwrap();
+        assert_eq!(read, partial);
+        assert!(read.dataset_hashes.is_empty() && read.source_commit.is_none());
+
+        let mut file = File::create(tmp.path()).await.unwrap();
+        TensorBuffersWriter::new(&mut file).write::<f32>(vec![], vec![]).await.unwrap();
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        assert_eq!(tensor_buffers.provenance().await.unwrap(), None);
+    }
+}
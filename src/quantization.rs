@@ -0,0 +1,849 @@
+#[cfg(feature = "async")]
+use flatbuffers::{FlatBufferBuilder, WIPOffset};
+#[cfg(feature = "async")]
+use half::{f16, slice::HalfFloatSliceExt};
+
+use crate::DataType;
+#[cfg(feature = "async")]
+use crate::{
+    extension_type::data_type_of,
+    generated::tensor_buffers::{QuantizationMetadata, QuantizationMetadataArgs, TensorMetadata},
+    simd,
+    utils::hash_key,
+    Result, Tensor, TensorBuffers,
+};
+
+/// Size of a scale (f32) and its zero point (i32) stored in the file.
+#[cfg(feature = "async")]
+const SCALE_SIZE: usize = 8;
+
+/// Which tensors a writer quantizes, and how, so a smaller artifact can be written straight from
+/// full-precision weights.
+///
+/// Float32 and Float16 tensors the policy doesn't exempt are stored as 8-bit or 4-bit integers
+/// with scales and zero points computed from their data: one for the whole tensor, one per index
+/// of the first dimension, e.g. per output channel of a linear layer's weight, or one per group of
+/// consecutive elements of each row, which int4 weights need to stay accurate. Readers get the
+/// floating point values back with `TensorBuffers::get_tensor_dequantized`.
+///
+/// ```
+/// use tensorbuffers::QuantizationPolicy;
+///
+/// let policy = QuantizationPolicy::int4()
+///     .with_group_size(128)
+///     .with_min_elements(4096)
+///     .with_skipped("lm_head.*");
+/// assert_eq!(policy.bits, 4);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QuantizationPolicy {
+    /// Bits per quantized value, 8 or 4.
+    pub bits: u8,
+    /// Whether each index of the first dimension gets its own scale. Tensors of fewer than two
+    /// dimensions get one scale either way.
+    pub per_channel: bool,
+    /// How many consecutive elements of the last dimension share a scale, with each row scaled
+    /// on its own, e.g. 128 for the weights of linear layers. Takes precedence over
+    /// `per_channel`.
+    pub group_size: Option<usize>,
+    /// Tensors with fewer elements than this, e.g. biases and norms, keep full precision.
+    pub min_elements: usize,
+    /// Names of tensors that keep full precision. A name ending in `*` matches every tensor whose
+    /// name starts with the rest.
+    pub skipped: Vec<String>,
+}
+
+impl QuantizationPolicy {
+    /// Quantizes to 8-bit integers, one scale per tensor.
+    pub fn int8() -> Self {
+        QuantizationPolicy {
+            bits: 8,
+            per_channel: false,
+            group_size: None,
+            min_elements: 0,
+            skipped: Vec::new(),
+        }
+    }
+
+    /// Quantizes to 4-bit integers, one scale per tensor.
+    pub fn int4() -> Self {
+        QuantizationPolicy { bits: 4, ..Self::int8() }
+    }
+
+    /// Sets whether each index of the first dimension gets its own scale.
+    pub fn with_per_channel(mut self, per_channel: bool) -> Self {
+        self.per_channel = per_channel;
+        self
+    }
+
+    /// Gives each group of `size` consecutive elements of every row its own scale. Rows whose
+    /// length isn't a multiple of `size` end in a smaller group.
+    pub fn with_group_size(mut self, size: usize) -> Self {
+        self.group_size = Some(size);
+        self
+    }
+
+    /// Sets the fewest elements a tensor must have to be quantized.
+    pub fn with_min_elements(mut self, elements: usize) -> Self {
+        self.min_elements = elements;
+        self
+    }
+
+    /// Keeps the tensors `name` matches at full precision.
+    pub fn with_skipped(mut self, name: &str) -> Self {
+        self.skipped.push(name.to_string());
+        self
+    }
+
+    /// Returns whether the tensor `name`, of `data_type` and `shape`, is quantized.
+    #[cfg(feature = "async")]
+    pub(crate) fn applies(&self, name: &str, data_type: DataType, shape: &[usize]) -> bool {
+        let elements = shape.iter().product::<usize>();
+        let skipped = self.skipped.iter().any(|pattern| match pattern.strip_suffix('*') {
+            Some(prefix) => name.starts_with(prefix),
+            None => name == pattern,
+        });
+        matches!(data_type, DataType::Float32 | DataType::Float16)
+            && !shape.is_empty()
+            && elements > 0
+            && elements >= self.min_elements
+            && !skipped
+    }
+
+    /// Checks that the policy asks for a bit width and group size the format can store.
+    #[cfg(feature = "async")]
+    pub(crate) fn validate(&self) -> Result<()> {
+        if !matches!(self.bits, 4 | 8) {
+            return Err(format!("Unsupported quantization to {} bits", self.bits).into());
+        }
+        match self.group_size {
+            Some(size) if size == 0 || u32::try_from(size).is_err() => {
+                Err(format!("Unsupported quantization group size {}", size).into())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Default for QuantizationPolicy {
+    fn default() -> Self {
+        Self::int8()
+    }
+}
+
+/// How the stored integers of a quantized tensor map back to floating point values, as
+/// `(stored - zero_point) * scale`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct QuantizationParams {
+    /// The type of the values before quantization, Float32 or Float16.
+    pub original_type: DataType,
+    /// Bits per stored value. 8-bit values are stored as `Int8` data, and 4-bit values packed two
+    /// per `UInt8` byte along the last dimension, low nibble first.
+    pub bits: u8,
+    /// The shape of the dequantized tensor.
+    pub shape: Vec<usize>,
+    /// The dimension with a scale per index, or `None` for one scale.
+    pub axis: Option<usize>,
+    /// How many consecutive indices of `axis` share a scale, with each slice along `axis` scaled
+    /// on its own, or `None` for a scale per index of `axis` that all slices share.
+    pub group_size: Option<usize>,
+    /// The scale of each index of `axis`, or the single scale. With `group_size`, the scales are
+    /// laid out like the tensor with `axis` shrunk to its number of groups.
+    pub scales: Vec<f32>,
+    /// The stored value of 0.0 for each scale.
+    pub zero_points: Vec<i32>,
+}
+
+#[cfg(feature = "async")]
+impl QuantizationParams {
+    /// Reads the quantization of the tensor `name`, checking that it is one this reader can
+    /// dequantize.
+    ///
+    /// # Arguments
+    /// * `stored_scales` - The scales and zero points stored in the file, if the metadata says
+    ///   they are, see `scales_region`.
+    pub(crate) fn from_metadata(
+        metadata: &QuantizationMetadata,
+        name: &str,
+        stored_scales: Option<&[u8]>,
+    ) -> Result<Self> {
+        let (scales, zero_points) = match stored_scales {
+            Some(bytes) => {
+                let (scales, zero_points) = bytes.split_at(bytes.len() / SCALE_SIZE * 4);
+                let scales =
+                    scales.chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]));
+                let zero_points = zero_points
+                    .chunks_exact(4)
+                    .map(|b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]));
+                (scales.collect(), zero_points.collect())
+            }
+            None => (
+                metadata.scales().into_iter().flatten().collect(),
+                metadata.zero_points().into_iter().flatten().collect(),
+            ),
+        };
+        let params = QuantizationParams {
+            original_type: DataType::try_from(metadata.original_type())
+                .map_err(|_| format!("Tensor {} was quantized from an unknown type", name))?,
+            bits: metadata.bits(),
+            shape: quantized_shape(metadata),
+            axis: usize::try_from(metadata.axis()).ok(),
+            group_size: group_size(metadata),
+            scales,
+            zero_points,
+        };
+        let channels = scale_count(&params.shape, params.axis, params.group_size);
+        let problem = if !matches!(params.original_type, DataType::Float32 | DataType::Float16) {
+            format!("an original type of {:?}", params.original_type)
+        } else if !matches!(params.bits, 4 | 8) {
+            format!("{}-bit values", params.bits)
+        } else if channels != Some(params.scales.len()) {
+            format!("{} scales for shape {:?}", params.scales.len(), params.shape)
+        } else if params.zero_points.len() != params.scales.len() {
+            format!("{} zero points for {} scales", params.zero_points.len(), params.scales.len())
+        } else {
+            return Ok(params);
+        };
+        Err(format!("Tensor {} is quantized with {}", name, problem).into())
+    }
+
+    /// Builds the table describing the quantization, with the scales and zero points stored at
+    /// `scales_offset` of the file if given, or in the table.
+    pub(crate) fn build_table<'a>(
+        &self,
+        builder: &mut FlatBufferBuilder<'a>,
+        scales_offset: Option<u64>,
+    ) -> WIPOffset<QuantizationMetadata<'a>> {
+        let shape = builder.create_vector_from_iter(self.shape.iter().map(|&dim| dim as u64));
+        let (scales, zero_points) = match scales_offset {
+            Some(_) => (None, None),
+            None => (
+                Some(builder.create_vector(&self.scales)),
+                Some(builder.create_vector(&self.zero_points)),
+            ),
+        };
+        QuantizationMetadata::create(builder, &QuantizationMetadataArgs {
+            original_type: self.original_type.into(),
+            bits: self.bits,
+            shape: Some(shape),
+            axis: self.axis.map_or(-1, |axis| axis as i32),
+            scales,
+            zero_points,
+            group_size: self.group_size.map_or(0, |size| size as u32),
+            scales_offset: scales_offset.unwrap_or(0),
+        })
+    }
+
+    /// Returns the data type the quantized values are stored as.
+    pub(crate) fn stored_data_type(&self) -> DataType {
+        match self.bits {
+            8 => DataType::Int8,
+            _ => DataType::UInt8,
+        }
+    }
+
+    /// Returns the shape the quantized values are stored in. 4-bit values halve the last
+    /// dimension, rounding up.
+    pub(crate) fn stored_shape(&self) -> Vec<usize> {
+        let mut shape = self.shape.clone();
+        if let (4, Some(last)) = (self.bits, shape.last_mut()) {
+            *last = last.div_ceil(2);
+        }
+        shape
+    }
+
+    /// Converts stored values back to the floating point values they approximate.
+    ///
+    /// # Returns
+    /// An error if `stored` doesn't hold the values of the whole tensor.
+    pub(crate) fn dequantize(&self, stored: &[u8]) -> Result<Vec<f32>> {
+        let expected = self.stored_shape().iter().product::<usize>();
+        if stored.len() != expected {
+            return Err(format!(
+                "Quantized data holds {} bytes, but shape {:?} of {}-bit values needs {}",
+                stored.len(),
+                self.shape,
+                self.bits,
+                expected
+            )
+            .into());
+        }
+        let elements = self.shape.iter().product::<usize>();
+        let mut values = vec![0.0; elements];
+        if elements == 0 {
+            return Ok(values);
+        }
+        let unpacked;
+        let codes = match self.bits {
+            8 => bytemuck::cast_slice::<u8, i8>(stored),
+            _ => {
+                unpacked = self.unpack(stored);
+                &unpacked[..]
+            }
+        };
+        let (dim, inner) = match self.axis {
+            Some(axis) => (self.shape[axis], self.shape[axis + 1..].iter().product::<usize>()),
+            None => (1, elements),
+        };
+        match self.group_size {
+            // Consecutive elements share a scale in runs of `inner`, cycling through the scales.
+            None => {
+                let runs = codes.chunks(inner).zip(values.chunks_mut(inner));
+                for (i, (codes, values)) in runs.enumerate() {
+                    let channel = i % self.scales.len();
+                    let (zero_point, scale) = (self.zero_points[channel], self.scales[channel]);
+                    simd::dequantize_i8(codes, zero_point, scale, values);
+                }
+            }
+            // Groups of the last dimension are runs of consecutive elements, in scale order.
+            Some(group_size) if inner == 1 => {
+                let codes = codes.chunks(dim).flat_map(|row| row.chunks(group_size));
+                let values = values.chunks_mut(dim).flat_map(|row| row.chunks_mut(group_size));
+                for (i, (codes, values)) in codes.zip(values).enumerate() {
+                    simd::dequantize_i8(codes, self.zero_points[i], self.scales[i], values);
+                }
+            }
+            // Each run of `inner` elements has a scale per element, shared by the run's group.
+            Some(group_size) => {
+                let groups = dim.div_ceil(group_size);
+                let runs = codes.chunks(inner).zip(values.chunks_mut(inner));
+                for (i, (codes, values)) in runs.enumerate() {
+                    let (outer, index) = (i / dim, i % dim);
+                    let first = (outer * groups + index / group_size) * inner;
+                    let scales = &self.scales[first..first + inner];
+                    let zero_points = &self.zero_points[first..first + inner];
+                    for (((&code, value), &scale), &zero_point) in
+                        codes.iter().zip(values).zip(scales).zip(zero_points)
+                    {
+                        *value = (code as i32 - zero_point) as f32 * scale;
+                    }
+                }
+            }
+        }
+        Ok(values)
+    }
+
+    /// Sign-extends packed 4-bit values to one byte each, dropping the padding nibble that ends
+    /// each row of an odd length.
+    fn unpack(&self, stored: &[u8]) -> Vec<i8> {
+        let row = self.shape.last().copied().unwrap_or(1);
+        let mut codes = vec![0; 2 * stored.len()];
+        simd::unpack_i4(stored, &mut codes);
+        if row % 2 == 1 {
+            let mut kept = 0;
+            for start in (0..codes.len()).step_by(row + 1) {
+                codes.copy_within(start..start + row, kept);
+                kept += row;
+            }
+            codes.truncate(kept);
+        }
+        codes
+    }
+}
+
+/// Returns the shape of the dequantized tensor `metadata` describes.
+#[cfg(feature = "async")]
+fn quantized_shape(metadata: &QuantizationMetadata) -> Vec<usize> {
+    metadata.shape().into_iter().flatten().map(|dim| dim as usize).collect()
+}
+
+/// Returns the group size `metadata` records, if the scales are group-wise.
+#[cfg(feature = "async")]
+fn group_size(metadata: &QuantizationMetadata) -> Option<usize> {
+    (metadata.group_size() > 0).then_some(metadata.group_size() as usize)
+}
+
+/// Returns how many scales a tensor of `shape` quantized along `axis` in groups of `group_size`
+/// has, or `None` if `axis` isn't one of its dimensions.
+#[cfg(feature = "async")]
+fn scale_count(shape: &[usize], axis: Option<usize>, group_size: Option<usize>) -> Option<usize> {
+    match (axis, group_size) {
+        (None, None) => Some(1),
+        (None, Some(_)) => None,
+        (Some(axis), None) => shape.get(axis).copied(),
+        (Some(axis), Some(group_size)) => {
+            let groups = shape.get(axis)?.div_ceil(group_size);
+            let mut others = shape.iter().enumerate().filter(|&(i, _)| i != axis);
+            others.try_fold(groups, |count, (_, &dim)| count.checked_mul(dim))
+        }
+    }
+}
+
+/// Returns where the scales and zero points of a tensor's quantization are stored in the file
+/// and their size: little-endian f32 scales, then as many little-endian i32 zero points.
+///
+/// # Returns
+/// `None` if the metadata holds them, or an error if the tensor `name` has no such dimension as
+/// the quantization's axis.
+#[cfg(feature = "async")]
+pub(crate) fn scales_region(
+    metadata: &QuantizationMetadata,
+    name: &str,
+) -> Result<Option<(u64, usize)>> {
+    if metadata.scales_offset() == 0 {
+        return Ok(None);
+    }
+    let axis = usize::try_from(metadata.axis()).ok();
+    scale_count(&quantized_shape(metadata), axis, group_size(metadata))
+        .and_then(|count| count.checked_mul(SCALE_SIZE))
+        .map(|size| Some((metadata.scales_offset(), size)))
+        .ok_or_else(|| {
+            format!("Tensor {} is quantized along axis {}", name, metadata.axis()).into()
+        })
+}
+
+/// Lays out the scales and zero points of group-wise quantizations, which are too many for the
+/// metadata, one after another in a section of the file, as `scales_region` reads them.
+///
+/// # Returns
+/// The section's bytes, and where in it each quantization's scales start, `None` for those the
+/// metadata holds.
+#[cfg(feature = "async")]
+pub(crate) fn encode_scales<'q>(
+    quantizations: impl IntoIterator<Item = Option<&'q QuantizationParams>>,
+) -> (Vec<u8>, Vec<Option<u64>>) {
+    let mut section = Vec::new();
+    let positions = quantizations
+        .into_iter()
+        .map(|params| {
+            let params = params.filter(|params| params.group_size.is_some())?;
+            let position = section.len() as u64;
+            section.extend(params.scales.iter().flat_map(|scale| scale.to_le_bytes()));
+            section.extend(params.zero_points.iter().flat_map(|point| point.to_le_bytes()));
+            Some(position)
+        })
+        .collect();
+    (section, positions)
+}
+
+/// A tensor's data quantized as a [`QuantizationPolicy`] asks.
+#[cfg(feature = "async")]
+pub(crate) struct Quantized {
+    pub params: QuantizationParams,
+    /// The stored values.
+    pub bytes: Vec<u8>,
+    /// The shape the values are stored in.
+    pub shape: Vec<usize>,
+}
+
+/// Quantizes the data of the tensor `name` if `policy` applies to it.
+///
+/// # Arguments
+/// * `data_type` - The type of the tensor's elements.
+/// * `data` - The tensor's data as little-endian bytes.
+/// * `shape` - The tensor's shape.
+///
+/// # Returns
+/// `None` if the tensor keeps full precision, which tensors holding infinities or NaNs do too,
+/// since no scale covers them.
+#[cfg(feature = "async")]
+pub(crate) fn quantize(
+    name: &str,
+    data_type: DataType,
+    data: &[u8],
+    shape: &[usize],
+    policy: &QuantizationPolicy,
+) -> Option<Quantized> {
+    if !policy.applies(name, data_type, shape) {
+        return None;
+    }
+    let values = match data_type {
+        DataType::Float32 => {
+            data.chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect()
+        }
+        _ => data
+            .chunks_exact(2)
+            .map(|b| f16::from_le_bytes([b[0], b[1]]).to_f32())
+            .collect::<Vec<_>>(),
+    };
+    if values.iter().any(|value| !value.is_finite()) {
+        return None;
+    }
+    // Each run of consecutive elements gets a scale, in the order the scales are laid out.
+    let last = shape[shape.len() - 1];
+    let (axis, runs) = match policy.group_size {
+        Some(group_size) => (
+            Some(shape.len() - 1),
+            values.chunks(last).flat_map(|row| row.chunks(group_size)).collect::<Vec<_>>(),
+        ),
+        None if policy.per_channel && shape.len() >= 2 => {
+            (Some(0), values.chunks(values.len() / shape[0]).collect())
+        }
+        None => (None, vec![&values[..]]),
+    };
+    let (min, max) = (-(1i32 << (policy.bits - 1)), (1i32 << (policy.bits - 1)) - 1);
+    let mut scales = Vec::with_capacity(runs.len());
+    let mut zero_points = Vec::with_capacity(runs.len());
+    let mut stored = Vec::with_capacity(values.len());
+    for chunk in runs {
+        // The range includes 0.0, so zeros, e.g. of padding or pruned weights, stay exact.
+        let low = chunk.iter().fold(0.0f32, |low, &value| low.min(value));
+        let high = chunk.iter().fold(0.0f32, |high, &value| high.max(value));
+        let scale = match (high - low) / (max - min) as f32 {
+            // All zeros, which any scale stores exactly.
+            scale if scale == 0.0 => 1.0,
+            scale if scale.is_normal() => scale,
+            _ => return None,
+        };
+        let zero_point = (min - (low / scale).round() as i32).clamp(min, max);
+        scales.push(scale);
+        zero_points.push(zero_point);
+        stored.extend(
+            chunk
+                .iter()
+                .map(|&value| ((value / scale).round() as i32 + zero_point).clamp(min, max)),
+        );
+    }
+
+    let params = QuantizationParams {
+        original_type: data_type,
+        bits: policy.bits,
+        shape: shape.to_vec(),
+        axis,
+        group_size: policy.group_size,
+        scales,
+        zero_points,
+    };
+    let bytes = match policy.bits {
+        8 => stored.iter().map(|&value| value as i8 as u8).collect(),
+        _ => stored
+            .chunks(last)
+            .flat_map(|row| row.chunks(2))
+            .map(|pair| (pair[0] as u8 & 0x0f) | (pair.get(1).map_or(0, |&high| high as u8) << 4))
+            .collect(),
+    };
+    let shape = params.stored_shape();
+    Some(Quantized { params, bytes, shape })
+}
+
+#[cfg(feature = "async")]
+impl TensorBuffers {
+    /// Returns how the tensor `tensor_name` was quantized.
+    ///
+    /// # Returns
+    /// `None` if the tensor isn't quantized, or an error if the file has no such tensor.
+    pub async fn get_tensor_quantization(
+        &self,
+        tensor_name: &str,
+    ) -> Result<Option<QuantizationParams>> {
+        let tensor = self.find_tensor_metadata(hash_key(tensor_name)).await?;
+        let tensor =
+            tensor.ok_or_else(|| format!("Tensor {} not found in metadata", tensor_name))?;
+        self.read_quantization(&tensor).await
+    }
+
+    /// Reads how `tensor` was quantized, loading its scales and zero points from the file if the
+    /// metadata doesn't hold them.
+    ///
+    /// # Returns
+    /// `None` if the tensor isn't quantized.
+    pub(crate) async fn read_quantization(
+        &self,
+        tensor: &TensorMetadata<'_>,
+    ) -> Result<Option<QuantizationParams>> {
+        let Some(quantization) = tensor.quantization() else {
+            return Ok(None);
+        };
+        let stored_scales = match scales_region(&quantization, tensor.name())? {
+            Some((offset, size)) => {
+                let file_size = self.file_size().await?;
+                if offset.checked_add(size as u64).map_or(true, |end| end > file_size) {
+                    return Err(
+                        format!("Tensor {} has scales outside the file", tensor.name()).into()
+                    );
+                }
+                Some(self.read_bytes(offset, size).await?)
+            }
+            None => None,
+        };
+        QuantizationParams::from_metadata(&quantization, tensor.name(), stored_scales.as_deref())
+            .map(Some)
+    }
+
+    /// Loads the tensor `tensor_name` as Float32 values, dequantizing it with vectorized kernels
+    /// if it was quantized when written, see [`QuantizationPolicy`], or is of a block-quantized
+    /// type. Tensors that are neither are loaded as they are, so they must be Float32.
+    pub async fn get_tensor_dequantized(&self, tensor_name: &str) -> Result<Tensor<'static, f32>> {
+        match self.load_dequantized(tensor_name).await? {
+            Some(tensor) => Ok(tensor),
+            None => self.get_tensor_data_by_name::<f32>(tensor_name).await,
+        }
+    }
+
+    /// Loads the tensor `tensor_name` as Float16 values, like `get_tensor_dequantized`. Tensors
+    /// that aren't quantized must be Float16.
+    pub async fn get_tensor_dequantized_f16(
+        &self,
+        tensor_name: &str,
+    ) -> Result<Tensor<'static, f16>> {
+        let Some(tensor) = self.load_dequantized(tensor_name).await? else {
+            return self.get_tensor_data_by_name::<f16>(tensor_name).await;
+        };
+        let mut values = vec![f16::ZERO; tensor.elements()];
+        values.convert_from_f32_slice(tensor.data());
+        Ok(Tensor::from_vec(tensor_name, values, tensor.shape().to_vec()))
+    }
+
+    /// Loads the tensor `tensor_name` as Float32 values if it is quantized.
+    ///
+    /// # Returns
+    /// `None` if the tensor isn't quantized.
+    async fn load_dequantized(&self, tensor_name: &str) -> Result<Option<Tensor<'static, f32>>> {
+        let tensor = self.find_tensor_metadata(hash_key(tensor_name)).await?;
+        let tensor =
+            tensor.ok_or_else(|| format!("Tensor {} not found in metadata", tensor_name))?;
+        if data_type_of(&tensor)?.is_block_quantized() {
+            let stored = self.get_tensor_data_by_name::<u8>(tensor_name).await?;
+            return stored.dequantize_blocks().map(Some);
+        }
+        let Some(params) = self.read_quantization(&tensor).await? else {
+            return Ok(None);
+        };
+        let values = match params.bits {
+            8 => {
+                let stored = self.get_tensor_data_by_name::<i8>(tensor_name).await?;
+                params.dequantize(bytemuck::cast_slice(stored.data()))?
+            }
+            _ => {
+                let stored = self.get_tensor_data_by_name::<u8>(tensor_name).await?;
+                params.dequantize(stored.data())?
+            }
+        };
+        Ok(Some(Tensor::from_vec(tensor_name, values, params.shape)))
+    }
+}
+
+#[cfg(all(test, feature = "async"))]
+mod tests {
+    use futures::SinkExt;
+    use tempfile::NamedTempFile;
+    use tokio::fs::File;
+
+    use super::*;
+    use crate::{
+        TensorAny, TensorBuffersSink, TensorBuffersWrite, TensorBuffersWriter, WriterOptions,
+    };
+
+    /// Returns the largest difference between `a` and `b`.
+    fn max_error(a: &[f32], b: &[f32]) -> f32 {
+        a.iter().zip(b).map(|(a, b)| (a - b).abs()).fold(0.0, f32::max)
+    }
+
+    #[test]
+    fn test_quantize() {
+        let values = (0..24).map(|i| (i as f32 - 6.0) * 0.25).collect::<Vec<_>>();
+        let data = bytemuck::cast_slice::<f32, u8>(&values);
+        for (policy, scales, tolerance) in [
+            (QuantizationPolicy::int8(), 1, 0.012),
+            (QuantizationPolicy::int8().with_per_channel(true), 4, 0.01),
+            (QuantizationPolicy::int4(), 1, 0.2),
+            (QuantizationPolicy::int4().with_per_channel(true), 4, 0.15),
+            // Rows of 6 split into a group of 4 and a smaller one of 2.
+            (QuantizationPolicy::int4().with_group_size(4), 8, 0.07),
+        ] {
+            let quantized = quantize("w", DataType::Float32, data, &[4, 6], &policy).unwrap();
+            let params = &quantized.params;
+            assert_eq!(params.scales.len(), scales);
+            assert_eq!(quantized.bytes.len(), params.stored_shape().iter().product::<usize>());
+            let dequantized = params.dequantize(&quantized.bytes).unwrap();
+            assert!(max_error(&values, &dequantized) <= tolerance, "{:?}", policy);
+            // Zeros stay exact.
+            assert_eq!(dequantized[6], 0.0);
+        }
+
+        // An odd last dimension pads each row of 4-bit values to whole bytes.
+        let quantized =
+            quantize("w", DataType::Float32, data, &[8, 3], &QuantizationPolicy::int4()).unwrap();
+        assert_eq!(quantized.params.stored_shape(), [8, 2]);
+        let dequantized = quantized.params.dequantize(&quantized.bytes).unwrap();
+        assert!(max_error(&values, &dequantized) <= 0.2);
+        assert!(quantized.params.dequantize(&quantized.bytes[1..]).is_err());
+
+        let half = values.iter().map(|&value| f16::from_f32(value)).collect::<Vec<_>>();
+        let quantized = quantize(
+            "w",
+            DataType::Float16,
+            bytemuck::cast_slice(&half),
+            &[24],
+            &QuantizationPolicy::int8(),
+        )
+        .unwrap();
+        assert_eq!(quantized.params.original_type, DataType::Float16);
+
+        let policy = QuantizationPolicy::int8().with_min_elements(32).with_skipped("norm.*");
+        assert!(quantize("w", DataType::Float32, data, &[24], &policy).is_none());
+        let policy = QuantizationPolicy::int8().with_skipped("norm.*");
+        assert!(quantize("norm.w", DataType::Float32, data, &[24], &policy).is_none());
+        assert!(quantize("w", DataType::Int32, data, &[24], &policy).is_none());
+        let infinite = bytemuck::cast_slice::<f32, u8>(&[1.0, f32::INFINITY]);
+        assert!(quantize("w", DataType::Float32, infinite, &[2], &policy).is_none());
+        let zeros = quantize("w", DataType::Float32, &[0; 16], &[4], &policy).unwrap();
+        assert_eq!(zeros.params.dequantize(&zeros.bytes).unwrap(), [0.0; 4]);
+        assert!(QuantizationPolicy { bits: 2, ..policy.clone() }.validate().is_err());
+        assert!(policy.with_group_size(0).validate().is_err());
+    }
+
+    #[test]
+    fn test_dequantize_groups_along_axis() {
+        // Groups of 2 along the first dimension of a [4, 2] tensor: each column of each group has
+        // its own scale, laid out as [2, 2].
+        let params = QuantizationParams {
+            original_type: DataType::Float32,
+            bits: 8,
+            shape: vec![4, 2],
+            axis: Some(0),
+            group_size: Some(2),
+            scales: vec![1.0, 2.0, 3.0, 4.0],
+            zero_points: vec![0, 1, 0, 1],
+        };
+        let values = params.dequantize(&[1, 1, 2, 2, 1, 1, 2, 2]).unwrap();
+        assert_eq!(values, [1.0, 0.0, 2.0, 2.0, 3.0, 0.0, 6.0, 4.0]);
+        assert_eq!(scale_count(&params.shape, params.axis, params.group_size), Some(4));
+        assert_eq!(scale_count(&[4, 3], Some(1), Some(2)), Some(8));
+        assert_eq!(scale_count(&[4, 3], Some(2), None), None);
+    }
+
+    #[tokio::test]
+    async fn test_quantize_on_write() {
+        let weight = (0..64 * 32).map(|i| ((i % 97) as f32 - 48.0) / 16.0).collect::<Vec<_>>();
+        let tensors = vec![
+            Tensor::new("layer.weight", &weight, vec![64, 32]),
+            Tensor::new("layer.bias", &weight[..64], vec![64]),
+        ];
+        let tmp = NamedTempFile::new().unwrap();
+        let url = format!("file://{}", tmp.path().display());
+        let mut sizes = Vec::new();
+        for (policy, tolerance) in [
+            (None, 0.0),
+            (Some(QuantizationPolicy::int8().with_per_channel(true)), 0.02),
+            (Some(QuantizationPolicy::int4().with_per_channel(true)), 0.4),
+        ] {
+            let mut options = WriterOptions::new();
+            if let Some(policy) = policy.clone() {
+                options = options.with_quantization(policy.with_min_elements(256));
+            }
+            let mut file = File::create(tmp.path()).await.unwrap();
+            let mut writer = TensorBuffersWriter::with_options(&mut file, options);
+            writer.write(tensors.clone(), vec![]).await.unwrap();
+            sizes.push(std::fs::metadata(tmp.path()).unwrap().len());
+
+            let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+            let read = tensor_buffers.get_tensor_dequantized("layer.weight").await.unwrap();
+            assert_eq!(read.shape(), [64, 32]);
+            assert!(max_error(&weight, read.data()) <= tolerance);
+            // Tensors the policy exempts keep full precision.
+            let bias = tensor_buffers.get_tensor_dequantized("layer.bias").await.unwrap();
+            assert_eq!(bias.data(), &weight[..64]);
+            assert!(tensor_buffers.get_tensor_quantization("layer.bias").await.unwrap().is_none());
+
+            let params = tensor_buffers.get_tensor_quantization("layer.weight").await.unwrap();
+            assert_eq!(params.as_ref().map(|params| params.bits), policy.map(|policy| policy.bits));
+            if let Some(params) = params {
+                assert_eq!((params.axis, params.scales.len()), (Some(0), 64));
+                assert!(tensor_buffers
+                    .get_tensor_data_by_name::<f32>("layer.weight")
+                    .await
+                    .is_err());
+            }
+        }
+        // The weight's 8 KiB shrink to 2 KiB as int8 and 1 KiB as int4, plus 512 bytes of scales
+        // and zero points.
+        assert!(sizes[1] + 5 * 1024 < sizes[0] && sizes[2] + 1000 < sizes[1]);
+
+        // Sinks quantize each tensor as it arrives.
+        let file = File::create(tmp.path()).await.unwrap();
+        let options = WriterOptions::new().with_quantization(QuantizationPolicy::int4());
+        let mut sink = TensorBuffersSink::new(file, options).unwrap();
+        sink.send(TensorAny::from(tensors[0].clone().into_owned())).await.unwrap();
+        sink.close().await.unwrap();
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        let read = tensor_buffers.get_tensor_dequantized("layer.weight").await.unwrap();
+        assert!(max_error(&weight, read.data()) <= 0.4);
+        let half = tensor_buffers.get_tensor_dequantized_f16("layer.weight").await.unwrap();
+        assert!(half.data().iter().zip(read.data()).all(|(&a, &b)| a == f16::from_f32(b)));
+        let stored = tensor_buffers.get_tensor_data_by_name::<u8>("layer.weight").await.unwrap();
+        assert_eq!(stored.shape(), [64, 16]);
+
+        // Columnar metadata can't describe quantized tensors.
+        let options = WriterOptions::new()
+            .with_quantization(QuantizationPolicy::int8())
+            .with_columnar_metadata(true);
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        let mut writer = TensorBuffersWriter::with_options(&mut bytes, options);
+        assert!(writer.write(tensors, vec![]).await.is_err());
+        assert!(WriterOptions::new()
+            .with_quantization(QuantizationPolicy::int8())
+            .with_format_version("1.3.0")
+            .validate()
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_group_wise_quantization() {
+        // Every fourth group of 8 elements holds outliers that would swamp a per-row scale.
+        let weight = (0..64 * 32)
+            .map(|i| ((i % 5) as f32 - 2.0) * if (i / 8) % 4 == 0 { 8.0 } else { 0.125 })
+            .collect::<Vec<_>>();
+        let small = |values: &[f32]| {
+            let pairs = weight.iter().zip(values).enumerate().filter(|(i, _)| (i / 8) % 4 != 0);
+            pairs.map(|(_, (a, b))| (a - b).abs()).fold(0.0, f32::max)
+        };
+        let tensors = vec![Tensor::new("layer.weight", &weight, vec![64, 32])];
+        let tmp = NamedTempFile::new().unwrap();
+        let url = format!("file://{}", tmp.path().display());
+        let mut errors = Vec::new();
+        for policy in [
+            QuantizationPolicy::int4().with_per_channel(true),
+            QuantizationPolicy::int4().with_group_size(8),
+        ] {
+            let options = WriterOptions::new()
+                .with_quantization(policy)
+                .with_name_index(true)
+                .with_leading_metadata(true);
+            let mut file = File::create(tmp.path()).await.unwrap();
+            let mut writer = TensorBuffersWriter::with_options(&mut file, options);
+            writer.write(tensors.clone(), vec![]).await.unwrap();
+            let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+            let read = tensor_buffers.get_tensor_dequantized("layer.weight").await.unwrap();
+            errors.push(small(read.data()));
+        }
+        // Per-row scales round the small values to 0, while each group keeps its own range.
+        assert!(errors[0] >= 0.2 && errors[1] <= 0.02, "{:?}", errors);
+
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        let params = tensor_buffers.get_tensor_quantization("layer.weight").await.unwrap().unwrap();
+        assert_eq!((params.axis, params.group_size, params.scales.len()), (Some(1), Some(8), 256));
+        // The scales are stored after the data rather than in the metadata.
+        let metadata = tensor_buffers.stored_metadata(hash_key("layer.weight")).await.unwrap();
+        let quantization = metadata.quantization().unwrap();
+        assert!(quantization.scales().is_none() && quantization.scales_offset() > 0);
+        let expected = tensor_buffers.get_tensor_dequantized("layer.weight").await.unwrap();
+
+        // Sinks, recovered files and files referring to the tensor read the same values.
+        let file = File::create(tmp.path()).await.unwrap();
+        let options =
+            WriterOptions::new().with_quantization(QuantizationPolicy::int4().with_group_size(8));
+        let mut sink = TensorBuffersSink::new(file, options).unwrap();
+        sink.send(TensorAny::from(tensors[0].clone().into_owned())).await.unwrap();
+        sink.close().await.unwrap();
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        let read = tensor_buffers.get_tensor_dequantized("layer.weight").await.unwrap();
+        assert_eq!(read.data(), expected.data());
+
+        let recovered = NamedTempFile::new().unwrap();
+        let report = crate::recover(tmp.path(), recovered.path(), true).await.unwrap();
+        assert_eq!(report.recovered, ["layer.weight"]);
+        let recovered_url = format!("file://{}", recovered.path().display());
+        let recovered_buffers = TensorBuffers::open(&recovered_url).await.unwrap();
+        let read = recovered_buffers.get_tensor_dequantized("layer.weight").await.unwrap();
+        assert_eq!(read.data(), expected.data());
+
+        let location = tmp.path().file_name().unwrap().to_str().unwrap();
+        let mut writer = TensorBuffersWriter::new(File::create(recovered.path()).await.unwrap());
+        writer.add_external(&tensor_buffers, location, &["layer.weight"]).await.unwrap();
+        writer.write::<f32>(vec![], vec![]).await.unwrap();
+        let referring = TensorBuffers::open(&recovered_url).await.unwrap();
+        let read = referring.get_tensor_dequantized("layer.weight").await.unwrap();
+        assert_eq!(read.data(), expected.data());
+    }
+}
@@ -0,0 +1,265 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+use bytes::Bytes;
+use futures::{future::BoxFuture, FutureExt};
+use tracing::debug;
+
+/// Identifies a byte range of a remote file in a [`RangeCache`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RangeKey {
+    /// The URL of the file.
+    pub url: String,
+    /// The ETag the server reported when the file was opened, if any, so ranges cached from an
+    /// earlier version of the file aren't served for a newer one.
+    pub etag: Option<String>,
+    /// The offset of the first byte of the range.
+    pub start: u64,
+    /// The offset after the last byte of the range.
+    pub end: u64,
+}
+
+impl RangeKey {
+    /// Returns a one-line description of the key, usable as a key in external stores.
+    pub fn to_line(&self) -> String {
+        format!("{} {} {} {}", self.start, self.end, self.etag.as_deref().unwrap_or("-"), self.url)
+    }
+}
+
+/// A cache of byte ranges read from remote files, consulted before each range request and filled
+/// with every response, so repeated loads of the same tensors skip the network.
+///
+/// Set one with `OpenOptions::with_range_cache`. [`MemoryRangeCache`] and [`DiskRangeCache`] are
+/// built in; implement the trait to keep ranges elsewhere, e.g. in shared memory or memcached.
+/// Caching is best effort: a failing cache should miss rather than fail the read.
+pub trait RangeCache: Send + Sync {
+    /// Returns the bytes cached for `key`, if any. Bytes of a different length than the range
+    /// are ignored.
+    fn get<'a>(&'a self, key: &'a RangeKey) -> BoxFuture<'a, Option<Bytes>>;
+
+    /// Caches the bytes of `key`.
+    fn put<'a>(&'a self, key: &'a RangeKey, bytes: Bytes) -> BoxFuture<'a, ()>;
+}
+
+/// A least-recently-used [`RangeCache`] in memory, bounded by the total size of its ranges.
+pub struct MemoryRangeCache {
+    inner: Mutex<MemoryInner>,
+}
+
+struct MemoryInner {
+    budget: usize,
+    used: usize,
+    tick: u64,
+    entries: HashMap<RangeKey, (Bytes, u64)>,
+    recency: BTreeMap<u64, RangeKey>,
+}
+
+impl MemoryRangeCache {
+    /// Creates a cache holding at most `budget_bytes` bytes of ranges. Larger ranges are not
+    /// cached.
+    pub fn new(budget_bytes: usize) -> Self {
+        MemoryRangeCache {
+            inner: Mutex::new(MemoryInner {
+                budget: budget_bytes,
+                used: 0,
+                tick: 0,
+                entries: HashMap::new(),
+                recency: BTreeMap::new(),
+            }),
+        }
+    }
+
+    /// Returns the number of bytes cached.
+    pub fn used(&self) -> usize {
+        self.inner.lock().unwrap().used
+    }
+
+    /// Drops every cached range.
+    pub fn clear(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.entries.clear();
+        inner.recency.clear();
+        inner.used = 0;
+    }
+}
+
+impl MemoryInner {
+    fn get(&mut self, key: &RangeKey) -> Option<Bytes> {
+        self.tick += 1;
+        let tick = self.tick;
+        let (bytes, last_used) = self.entries.get_mut(key)?;
+        let previous = std::mem::replace(last_used, tick);
+        let bytes = bytes.clone();
+        self.recency.remove(&previous);
+        self.recency.insert(tick, key.clone());
+        Some(bytes)
+    }
+
+    fn put(&mut self, key: &RangeKey, bytes: Bytes) {
+        if let Some((old, last_used)) = self.entries.remove(key) {
+            self.used -= old.len();
+            self.recency.remove(&last_used);
+        }
+        if bytes.len() > self.budget {
+            return;
+        }
+        while self.used + bytes.len() > self.budget {
+            let Some((_, oldest)) = self.recency.pop_first() else {
+                break;
+            };
+            if let Some((evicted, _)) = self.entries.remove(&oldest) {
+                self.used -= evicted.len();
+            }
+        }
+        self.tick += 1;
+        self.used += bytes.len();
+        self.recency.insert(self.tick, key.clone());
+        self.entries.insert(key.clone(), (bytes, self.tick));
+    }
+}
+
+impl RangeCache for MemoryRangeCache {
+    fn get<'a>(&'a self, key: &'a RangeKey) -> BoxFuture<'a, Option<Bytes>> {
+        futures::future::ready(self.inner.lock().unwrap().get(key)).boxed()
+    }
+
+    fn put<'a>(&'a self, key: &'a RangeKey, bytes: Bytes) -> BoxFuture<'a, ()> {
+        self.inner.lock().unwrap().put(key, bytes);
+        futures::future::ready(()).boxed()
+    }
+}
+
+impl fmt::Debug for MemoryRangeCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let inner = self.inner.lock().unwrap();
+        f.debug_struct("MemoryRangeCache")
+            .field("budget", &inner.budget)
+            .field("used", &inner.used)
+            .field("ranges", &inner.entries.len())
+            .finish()
+    }
+}
+
+/// A [`RangeCache`] keeping each range in a file of a directory, so ranges survive restarts and
+/// are shared by processes using the same directory.
+///
+/// Each file starts with its key on one line, which is checked on reads, followed by the bytes.
+/// Files are written under a temporary name and renamed, so readers never see partial ranges.
+/// Nothing is evicted; remove the directory's files to free the space.
+#[derive(Debug)]
+pub struct DiskRangeCache {
+    dir: PathBuf,
+    writes: AtomicU64,
+}
+
+impl DiskRangeCache {
+    /// Creates a cache in `dir`, which is created on the first write if it doesn't exist.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        DiskRangeCache { dir: dir.into(), writes: AtomicU64::new(0) }
+    }
+
+    /// Returns the directory the ranges are kept in.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn path(&self, header: &str) -> PathBuf {
+        let hash = xxhash_rust::xxh64::xxh64(header.as_bytes(), 0);
+        self.dir.join(format!("{:016x}.range", hash))
+    }
+
+    async fn read(&self, key: &RangeKey) -> Option<Bytes> {
+        let header = format!("{}\n", key.to_line());
+        let data = tokio::fs::read(self.path(&header)).await.ok()?;
+        if !data.starts_with(header.as_bytes()) {
+            return None;
+        }
+        Some(Bytes::from(data).slice(header.len()..))
+    }
+
+    async fn write(&self, key: &RangeKey, bytes: &[u8]) -> std::io::Result<()> {
+        let header = format!("{}\n", key.to_line());
+        let path = self.path(&header);
+        let write = self.writes.fetch_add(1, Ordering::Relaxed);
+        let tmp = path.with_extension(format!("tmp{}-{}", std::process::id(), write));
+        tokio::fs::create_dir_all(&self.dir).await?;
+        let mut data = Vec::with_capacity(header.len() + bytes.len());
+        data.extend_from_slice(header.as_bytes());
+        data.extend_from_slice(bytes);
+        tokio::fs::write(&tmp, data).await?;
+        if let Err(error) = tokio::fs::rename(&tmp, &path).await {
+            let _ = tokio::fs::remove_file(&tmp).await;
+            return Err(error);
+        }
+        Ok(())
+    }
+}
+
+impl RangeCache for DiskRangeCache {
+    fn get<'a>(&'a self, key: &'a RangeKey) -> BoxFuture<'a, Option<Bytes>> {
+        self.read(key).boxed()
+    }
+
+    fn put<'a>(&'a self, key: &'a RangeKey, bytes: Bytes) -> BoxFuture<'a, ()> {
+        async move {
+            if let Err(error) = self.write(key, &bytes).await {
+                debug!(%error, dir = %self.dir.display(), "Failed to cache range");
+            }
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn key(start: u64, end: u64) -> RangeKey {
+        RangeKey { url: "https://example.com/model.tb".to_string(), etag: None, start, end }
+    }
+
+    #[tokio::test]
+    async fn test_memory_range_cache() {
+        let cache = MemoryRangeCache::new(10);
+        cache.put(&key(0, 4), Bytes::from_static(b"TBS1")).await;
+        cache.put(&key(4, 8), Bytes::from_static(b"abcd")).await;
+        assert_eq!(cache.get(&key(0, 4)).await.unwrap(), "TBS1");
+        assert!(cache.get(&key(0, 8)).await.is_none());
+
+        // The least recently used range makes room for a new one.
+        cache.put(&key(8, 12), Bytes::from_static(b"efgh")).await;
+        assert_eq!(cache.used(), 8);
+        assert!(cache.get(&key(4, 8)).await.is_none());
+        assert!(cache.get(&key(0, 4)).await.is_some());
+
+        // Ranges larger than the budget are not cached.
+        cache.put(&key(0, 11), Bytes::from_static(b"0123456789a")).await;
+        assert!(cache.get(&key(0, 11)).await.is_none());
+        cache.clear();
+        assert_eq!(cache.used(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_disk_range_cache() {
+        let dir = TempDir::new().unwrap();
+        let cache = DiskRangeCache::new(dir.path().join("ranges"));
+        assert!(cache.get(&key(0, 4)).await.is_none());
+        cache.put(&key(0, 4), Bytes::from_static(b"TBS1")).await;
+        assert_eq!(cache.get(&key(0, 4)).await.unwrap(), "TBS1");
+
+        // Another cache on the same directory sees the range, but not for another file version.
+        let shared = DiskRangeCache::new(cache.dir());
+        assert_eq!(shared.get(&key(0, 4)).await.unwrap(), "TBS1");
+        let changed = RangeKey { etag: Some("\"v2\"".to_string()), ..key(0, 4) };
+        assert!(shared.get(&changed).await.is_none());
+    }
+}
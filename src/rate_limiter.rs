@@ -0,0 +1,126 @@
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use tokio::{
+    sync::{OwnedSemaphorePermit, Semaphore},
+    time::{sleep_until, Instant},
+};
+
+/// Limits the HTTP requests that remote files send, shared by every [`crate::TensorBuffers`]
+/// opened with a clone of it, so a host loading many models from one registry at once doesn't
+/// overwhelm it. Set it with `OpenOptions::with_rate_limiter`.
+///
+/// ```
+/// use tensorbuffers::{OpenOptions, RateLimiter};
+///
+/// // At most eight requests in flight and 100 MB/s across every file opened with `options`.
+/// let limiter = RateLimiter::new(8, Some(100_000_000));
+/// let options = OpenOptions::new().with_rate_limiter(limiter.clone());
+/// assert_eq!(limiter.max_concurrent_requests(), 8);
+/// ```
+#[derive(Clone)]
+pub struct RateLimiter {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    max_concurrent_requests: usize,
+    max_bytes_per_second: Option<u64>,
+    requests: Arc<Semaphore>,
+    /// When the next request may start to keep within the byte rate.
+    next_start: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    /// Creates a limiter.
+    ///
+    /// # Arguments
+    /// * `max_concurrent_requests` - Most requests in flight at once, including the requests for
+    ///   file sizes sent when opening files. At least one.
+    /// * `max_bytes_per_second` - Fastest rate at which range requests are started, in bytes per
+    ///   second. Unlimited if `None`.
+    pub fn new(max_concurrent_requests: usize, max_bytes_per_second: Option<u64>) -> Self {
+        let max_concurrent_requests = max_concurrent_requests.max(1);
+        RateLimiter {
+            inner: Arc::new(Inner {
+                max_concurrent_requests,
+                max_bytes_per_second,
+                requests: Arc::new(Semaphore::new(max_concurrent_requests)),
+                next_start: Mutex::new(Instant::now()),
+            }),
+        }
+    }
+
+    /// Returns the most requests in flight at once.
+    pub fn max_concurrent_requests(&self) -> usize {
+        self.inner.max_concurrent_requests
+    }
+
+    /// Returns the fastest rate at which range requests are started, if limited.
+    pub fn max_bytes_per_second(&self) -> Option<u64> {
+        self.inner.max_bytes_per_second
+    }
+
+    /// Returns the number of requests in flight.
+    pub fn in_flight(&self) -> usize {
+        self.inner.max_concurrent_requests - self.inner.requests.available_permits()
+    }
+
+    /// Waits until a request for `bytes` bytes may start. The request counts as in flight until
+    /// the returned permit is dropped.
+    pub(crate) async fn acquire(&self, bytes: u64) -> OwnedSemaphorePermit {
+        let permit = self.inner.requests.clone().acquire_owned().await.unwrap();
+        if let Some(rate) = self.inner.max_bytes_per_second {
+            let start = {
+                let mut next_start = self.inner.next_start.lock().unwrap();
+                let start = (*next_start).max(Instant::now());
+                *next_start = start + Duration::from_secs_f64(bytes as f64 / rate.max(1) as f64);
+                start
+            };
+            sleep_until(start).await;
+        }
+        permit
+    }
+}
+
+impl fmt::Debug for RateLimiter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RateLimiter")
+            .field("max_concurrent_requests", &self.inner.max_concurrent_requests)
+            .field("max_bytes_per_second", &self.inner.max_bytes_per_second)
+            .field("in_flight", &self.in_flight())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_rate_limiter() {
+        let limiter = RateLimiter::new(2, None);
+        let shared = limiter.clone();
+        let first = limiter.acquire(0).await;
+        let _second = shared.acquire(0).await;
+        assert_eq!(limiter.in_flight(), 2);
+        let third = tokio::time::timeout(Duration::from_millis(20), shared.acquire(0)).await;
+        assert!(third.is_err());
+        drop(first);
+        let third = tokio::time::timeout(Duration::from_secs(5), shared.acquire(0)).await;
+        assert!(third.is_ok());
+
+        // Requests start no faster than the byte rate allows, across clones.
+        let limiter = RateLimiter::new(8, Some(10_000));
+        let shared = limiter.clone();
+        let start = Instant::now();
+        for _ in 0..2 {
+            drop(limiter.acquire(500).await);
+            drop(shared.acquire(500).await);
+        }
+        assert!(start.elapsed() >= Duration::from_millis(150));
+    }
+}
@@ -0,0 +1,163 @@
+use crate::{
+    generated::tensor_buffers::{
+        ChecksumAlgorithm, Compression, DataType, TensorBuffersMetadata, TensorMetadata,
+    },
+    tensor_columns::Columns,
+};
+
+/// How [`crate::TensorBuffers`] treats metadata it cannot fully make sense of.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ReadMode {
+    /// Fails on first use if any tensor has an unknown data type, codec or checksum algorithm or a
+    /// missing shape, if tensors or operations are not sorted by id, or if an operation is
+    /// unknown. Suited to production loaders, which should never run on a partly readable file.
+    /// Format 1.0.0 didn't require sorted ids, so such files are searched linearly instead.
+    #[default]
+    Strict,
+    /// Logs each such problem as a warning and keeps going: unreadable tensors are skipped, and
+    /// unsorted tensors and operations are still found by a linear scan. Suited to conversion and
+    /// recovery tooling. The warnings are also available from `TensorBuffers::warnings`.
+    Lenient,
+}
+
+/// Returns why the tensor can't be read, if it can't.
+pub(crate) fn tensor_problem(tensor: &TensorMetadata) -> Option<String> {
+    entry_problem(
+        tensor.name(),
+        tensor.data_type(),
+        tensor.shape().is_some() || tensor.shape64().is_some(),
+        tensor.compression(),
+        tensor.checksum_algorithm(),
+    )
+}
+
+/// Returns why a tensor described by these parts of its metadata can't be read, if it can't.
+pub(crate) fn entry_problem(
+    name: &str,
+    data_type: DataType,
+    has_shape: bool,
+    compression: Compression,
+    checksum_algorithm: ChecksumAlgorithm,
+) -> Option<String> {
+    let problem = if data_type == DataType::None || data_type.variant_name().is_none() {
+        format!("an unknown data type {:?}", data_type)
+    } else if !has_shape {
+        "no shape".to_string()
+    } else if compression.variant_name().is_none() {
+        format!("an unknown compression {:?}", compression)
+    } else if checksum_algorithm.variant_name().is_none() {
+        format!("an unknown checksum algorithm {:?}", checksum_algorithm)
+    } else {
+        return None;
+    };
+    Some(format!("Tensor {} has {}", name, problem))
+}
+
+/// Returns every problem in the metadata that strict mode rejects, in file order.
+pub(crate) fn check_metadata(metadata: &TensorBuffersMetadata) -> Vec<String> {
+    let mut problems = Vec::new();
+    if let Some(tensors) = metadata.tensors() {
+        problems.extend(tensors.iter().filter_map(|tensor| tensor_problem(&tensor)));
+        if requires_sorted_ids(metadata) && !is_sorted(tensors.iter().map(|tensor| tensor.id())) {
+            problems.push("Tensors are not sorted by id".to_string());
+        }
+    }
+    match Columns::of(metadata) {
+        Ok(columns) => problems.extend(columns.iter().flat_map(Columns::problems)),
+        Err(error) => problems.push(error.to_string()),
+    }
+    if let Some(operations) = metadata.operations() {
+        for operation in operations.iter() {
+            if operation.operation().variant_name().is_none() {
+                problems.push(format!(
+                    "Operation {} has an unknown operation {:?}",
+                    operation.id(),
+                    operation.operation()
+                ));
+            }
+        }
+        if requires_sorted_ids(metadata)
+            && !is_sorted(operations.iter().map(|operation| operation.id()))
+        {
+            problems.push("Operations are not sorted by id".to_string());
+        }
+    }
+    problems
+}
+
+/// Returns whether the file's format version requires tensors and operations sorted by id. Writers
+/// of 1.0.0 listed them in the order they were given.
+pub(crate) fn requires_sorted_ids(metadata: &TensorBuffersMetadata) -> bool {
+    metadata.version() != "1.0.0"
+}
+
+/// Looks up a tensor by id, with a linear scan if the binary search misses in a file that doesn't
+/// require sorted ids.
+pub(crate) fn find_tensor<'a>(
+    metadata: &TensorBuffersMetadata<'a>,
+    tensor_id: u64,
+) -> Option<TensorMetadata<'a>> {
+    let tensors = metadata.tensors()?;
+    tensors.lookup_by_key(tensor_id, |field, key| field.key_compare_with_value(*key)).or_else(
+        || match requires_sorted_ids(metadata) {
+            true => None,
+            false => tensors.iter().find(|tensor| tensor.id() == tensor_id),
+        },
+    )
+}
+
+/// Returns whether the ids are strictly increasing, which binary search by id relies on.
+pub(crate) fn is_sorted(ids: impl Iterator<Item = u64>) -> bool {
+    let mut previous = None;
+    for id in ids {
+        if previous.is_some_and(|previous| previous >= id) {
+            return false;
+        }
+        previous = Some(id);
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use flatbuffers::FlatBufferBuilder;
+
+    use super::*;
+    use crate::generated::tensor_buffers::{
+        TensorBuffersMetadataArgs, TensorMetadata, TensorMetadataArgs,
+    };
+
+    #[test]
+    fn test_check_metadata() {
+        let mut builder = FlatBufferBuilder::new();
+        let mut tensors = Vec::new();
+        for (id, data_type, with_shape) in
+            [(2, DataType::Float32, true), (1, DataType(42), true), (3, DataType::Int8, false)]
+        {
+            let name = builder.create_string(&format!("t{}", id));
+            let shape = with_shape.then(|| builder.create_vector::<u32>(&[2]));
+            let args =
+                TensorMetadataArgs { id, name: Some(name), shape, data_type, ..Default::default() };
+            tensors.push(TensorMetadata::create(&mut builder, &args));
+        }
+        let tensors = builder.create_vector(&tensors);
+        let version = builder.create_string("1.1.0");
+        let args = TensorBuffersMetadataArgs {
+            version: Some(version),
+            tensors: Some(tensors),
+            ..Default::default()
+        };
+        let root = TensorBuffersMetadata::create(&mut builder, &args);
+        builder.finish(root, None);
+        let metadata = flatbuffers::root::<TensorBuffersMetadata>(builder.finished_data()).unwrap();
+
+        let problems = check_metadata(&metadata);
+        assert_eq!(problems, [
+            "Tensor t1 has an unknown data type <UNKNOWN 42>",
+            "Tensor t3 has no shape",
+            "Tensors are not sorted by id",
+        ]);
+        assert!(is_sorted([1, 2, 5].into_iter()));
+        assert!(!is_sorted([1, 1].into_iter()));
+    }
+}
@@ -0,0 +1,79 @@
+/// Largest gap between two regions that is read through rather than split into two reads. Reading
+/// a few unused bytes is cheaper than another seek or range request.
+pub(crate) const MAX_GAP: u64 = 64 << 10;
+
+/// Largest read that regions are merged into, which bounds the memory of a coalesced read.
+pub(crate) const MAX_READ_SIZE: usize = 64 << 20;
+
+/// One read of a plan and the regions it covers.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct PlannedRead {
+    pub offset: u64,
+    pub size: usize,
+    /// Each covered region as its index among the planned regions and its range within the read.
+    pub parts: Vec<(usize, std::ops::Range<usize>)>,
+}
+
+/// Plans reads of `regions` sorted by offset, merging regions that overlap, touch or lie at most
+/// `max_gap` bytes apart, as long as the merged read stays within `max_read_size`. Regions larger
+/// than `max_read_size` get a read of their own.
+///
+/// # Arguments
+/// * `regions` - The offset and size of each region, in any order.
+/// * `max_gap` - The largest gap to read through.
+/// * `max_read_size` - The largest merged read.
+///
+/// # Returns
+/// The reads in file order, which together cover every region exactly once.
+pub(crate) fn plan_reads(
+    regions: &[(u64, usize)],
+    max_gap: u64,
+    max_read_size: usize,
+) -> Vec<PlannedRead> {
+    let mut order = (0..regions.len()).collect::<Vec<_>>();
+    order.sort_by_key(|&index| regions[index]);
+
+    let mut reads: Vec<PlannedRead> = Vec::new();
+    for index in order {
+        let (offset, size) = regions[index];
+        let end = offset + size as u64;
+        if let Some(read) = reads.last_mut() {
+            let read_end = read.offset + read.size as u64;
+            let merged_size = end.max(read_end) - read.offset;
+            if offset <= read_end + max_gap && merged_size <= max_read_size as u64 {
+                read.size = merged_size as usize;
+                let start = (offset - read.offset) as usize;
+                read.parts.push((index, start..start + size));
+                continue;
+            }
+        }
+        reads.push(PlannedRead { offset, size, parts: vec![(index, 0..size)] });
+    }
+    reads
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_reads() {
+        // Out of order, with a shared (deduplicated) region, a small gap and a large one.
+        let regions = [(100, 10), (4, 96), (4, 96), (120, 8), (10_000, 4)];
+        let reads = plan_reads(&regions, 16, 1 << 20);
+        assert_eq!(reads, [
+            PlannedRead {
+                offset: 4,
+                size: 124,
+                parts: vec![(1, 0..96), (2, 0..96), (0, 96..106), (3, 116..124)],
+            },
+            PlannedRead { offset: 10_000, size: 4, parts: vec![(4, 0..4)] },
+        ]);
+
+        // The read size limit splits merges, but never a single region.
+        let reads = plan_reads(&[(0, 8), (8, 8), (16, 32)], 0, 16);
+        let sizes = reads.iter().map(|read| (read.offset, read.size)).collect::<Vec<_>>();
+        assert_eq!(sizes, [(0, 16), (16, 32)]);
+        assert!(plan_reads(&[], MAX_GAP, MAX_READ_SIZE).is_empty());
+    }
+}
@@ -0,0 +1,118 @@
+use std::{
+    ops::{Deref, DerefMut},
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    open_options::OpenOptions, stats::ReadCounters, tensor_buffers_file::TensorBuffersFile,
+    tensor_buffers_reader::TensorBuffersReader, Result,
+};
+
+type Reader = TensorBuffersReader<TensorBuffersFile>;
+
+/// Handles to one file, so reads can run concurrently instead of queueing on a single handle.
+/// Handles are opened as concurrent reads need them and kept for reuse.
+pub(crate) struct ReaderPool {
+    url: String,
+    options: OpenOptions,
+    counters: Arc<ReadCounters>,
+    idle: Mutex<Vec<Reader>>,
+}
+
+impl ReaderPool {
+    /// Creates a pool that starts with the already opened `file`. Handles opened later count
+    /// their reads where `file` does.
+    pub fn new(url: &str, options: OpenOptions, file: TensorBuffersFile) -> Self {
+        let counters = file.counters().clone();
+        let idle = Mutex::new(vec![TensorBuffersReader::new(file)]);
+        ReaderPool { url: url.to_string(), options, counters, idle }
+    }
+
+    /// Returns where the reads of the file are counted.
+    pub fn counters(&self) -> &Arc<ReadCounters> {
+        &self.counters
+    }
+
+    /// Returns the URL of the file.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Takes an idle handle, or opens a new one if every handle is in use. The handle returns to
+    /// the pool when the guard is dropped.
+    pub async fn get(&self) -> Result<PooledReader<'_>> {
+        let idle = self.idle.lock().unwrap().pop();
+        let reader = match idle {
+            Some(reader) => reader,
+            None => {
+                let file = TensorBuffersFile::open(&self.url, &self.options, self.counters.clone())
+                    .await?;
+                TensorBuffersReader::new(file)
+            }
+        };
+        Ok(PooledReader { pool: self, reader: Some(reader) })
+    }
+
+    /// Returns the number of idle handles.
+    #[cfg(test)]
+    fn idle(&self) -> usize {
+        self.idle.lock().unwrap().len()
+    }
+}
+
+/// A handle taken from a [`ReaderPool`].
+pub(crate) struct PooledReader<'a> {
+    pool: &'a ReaderPool,
+    reader: Option<Reader>,
+}
+
+impl Deref for PooledReader<'_> {
+    type Target = Reader;
+
+    fn deref(&self) -> &Reader {
+        self.reader.as_ref().expect("the reader is only taken on drop")
+    }
+}
+
+impl DerefMut for PooledReader<'_> {
+    fn deref_mut(&mut self) -> &mut Reader {
+        self.reader.as_mut().expect("the reader is only taken on drop")
+    }
+}
+
+impl Drop for PooledReader<'_> {
+    fn drop(&mut self) {
+        // Every read seeks first, so a handle left mid-read by an error or a cancelled read is
+        // still fine to reuse.
+        if let Some(reader) = self.reader.take() {
+            self.pool.idle.lock().unwrap().push(reader);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::NamedTempFile;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_reader_pool() {
+        let tmp = NamedTempFile::new().unwrap();
+        std::fs::write(tmp.path(), b"0123456789").unwrap();
+        let url = format!("file://{}", tmp.path().display());
+        let file =
+            TensorBuffersFile::open(&url, &OpenOptions::new(), Arc::default()).await.unwrap();
+        let pool = ReaderPool::new(&url, OpenOptions::new(), file);
+
+        let mut first = pool.get().await.unwrap();
+        let mut second = pool.get().await.unwrap();
+        let (mut a, mut b) = ([0; 2], [0; 3]);
+        first.read_at(2, &mut a).await.unwrap();
+        second.read_at(5, &mut b).await.unwrap();
+        assert_eq!((&a, &b), (b"23", b"567"));
+        drop((first, second));
+        assert_eq!(pool.idle(), 2);
+        assert_eq!(pool.get().await.unwrap().file_size().await.unwrap(), 10);
+    }
+}
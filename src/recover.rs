@@ -0,0 +1,419 @@
+use std::{collections::HashMap, path::Path};
+
+use flatbuffers::{FlatBufferBuilder, WIPOffset, FLATBUFFERS_MAX_BUFFER_SIZE};
+use tokio::{
+    fs::File,
+    io::{AsyncWriteExt, BufWriter},
+};
+use tracing::{info, instrument, warn};
+
+use crate::{
+    codec,
+    constants::{
+        BLOOM_FOOTER_MAGIC_BYTES, CHECKSUM_FOOTER_MAGIC_BYTES, FIXED_FOOTER_MAGIC_BYTES,
+        MAGIC_BYTES, VERSION,
+    },
+    footer::{decompress_metadata, Footer, FooterContents, MAX_FOOTER_SIZE},
+    format_features::FormatFeatures,
+    generated::tensor_buffers::{
+        ExtensionType, ExtensionTypeArgs, ExternalData, ExternalDataArgs, QuantizationMetadata,
+        QuantizationMetadataArgs, TensorBuffersMetadata, TensorMetadata, TensorMetadataArgs,
+    },
+    quantization::scales_region,
+    read_mode::tensor_problem,
+    slice_file::check_data_layout,
+    tensor_buffers_reader::TensorBuffersReader,
+    tensor_buffers_writer::write_metadata,
+    tensor_columns::expand_columns,
+    Provenance, Result, TensorBuffers, TensorConstraint, TensorOperation,
+};
+
+/// How many bytes are searched at a time when looking for a footer.
+const SCAN_CHUNK_SIZE: u64 = 1 << 20;
+
+/// What `recover` salvaged from a damaged file.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RecoveryReport {
+    /// Names of the tensors written to the new file.
+    pub recovered: Vec<String>,
+    /// Why each of the other tensors was left out.
+    pub skipped: Vec<String>,
+}
+
+/// Salvages every intact tensor of a damaged TensorBuffers file into a new file.
+///
+/// The metadata is located through the last footer in `src` whose metadata parses and matches its
+/// checksum, so files with bytes appended after the footer or a damaged final footer can still be
+/// recovered. Files that lost every copy of their metadata, e.g. by truncation, can't be. Tensors
+/// whose metadata is unreadable, whose data lies outside the file or fails to decompress, or,
+/// with `verify_checksums`, whose data doesn't match its checksum, are skipped. Tensors stored in
+/// other files keep referring to them, with relative locations now taken from `dst`. Quantization
+/// scales stored outside the metadata are copied after the tensor's data, and tensors whose
+/// scales are unreadable are skipped too. Operations are copied as they are.
+///
+/// # Arguments
+/// * `src` - The damaged file.
+/// * `dst` - The file to write, which is replaced if it exists.
+/// * `verify_checksums` - Whether to skip tensors whose data doesn't match its checksum.
+///
+/// # Returns
+/// The names of the recovered tensors and the reasons the others were skipped.
+#[instrument(skip_all, fields(src = %src.as_ref().display()))]
+pub async fn recover(
+    src: impl AsRef<Path>,
+    dst: impl AsRef<Path>,
+    verify_checksums: bool,
+) -> Result<RecoveryReport> {
+    let mut reader = TensorBuffersReader::new(File::open(src.as_ref()).await?);
+    let (metadata_start, metadata) = find_metadata(&mut reader)
+        .await?
+        .ok_or_else(|| format!("No intact metadata found in {}", src.as_ref().display()))?;
+    let metadata = flatbuffers::root::<TensorBuffersMetadata>(&metadata)?;
+    let expanded = expand_columns(&metadata)?;
+    let metadata = match &expanded {
+        Some(expanded) => flatbuffers::root::<TensorBuffersMetadata>(expanded)?,
+        None => metadata,
+    };
+
+    let mut report = RecoveryReport::default();
+    let mut out = BufWriter::new(File::create(dst).await?);
+    out.write_all(MAGIC_BYTES).await?;
+    let mut offset = MAGIC_BYTES.len() as u64;
+    // Tensors deduplicated in the source stay deduplicated.
+    let mut copied = HashMap::new();
+    let mut builder = FlatBufferBuilder::new();
+    let mut tensors = Vec::new();
+    let mut features = FormatFeatures::default();
+    let dictionary = metadata.compression_dictionary().map(|dictionary| dictionary.bytes());
+    for tensor in metadata.tensors().into_iter().flatten() {
+        let region = (tensor.data_offset(), codec::stored_size(&tensor));
+        let scales = match salvage_scales(&mut reader, &tensor, metadata_start).await {
+            Ok(scales) => scales,
+            Err(error) => {
+                warn!(tensor = tensor.name(), %error, "Skipping damaged tensor");
+                report.skipped.push(error.to_string());
+                continue;
+            }
+        };
+        let new_offset = match copied.get(&region) {
+            // Data stored in another file isn't there to salvage, so the reference is kept.
+            _ if tensor.external().is_some() => match tensor_problem(&tensor) {
+                Some(problem) => {
+                    warn!(tensor = tensor.name(), %problem, "Skipping damaged tensor");
+                    report.skipped.push(problem);
+                    continue;
+                }
+                None => 0,
+            },
+            Some(&new_offset) => new_offset,
+            None => {
+                match salvage(&mut reader, &tensor, dictionary, metadata_start, verify_checksums)
+                    .await
+                {
+                    Ok(stored) => {
+                        out.write_all(&stored).await?;
+                        copied.insert(region, offset);
+                        offset += stored.len() as u64;
+                        offset - stored.len() as u64
+                    }
+                    Err(error) => {
+                        warn!(tensor = tensor.name(), %error, "Skipping damaged tensor");
+                        report.skipped.push(error.to_string());
+                        continue;
+                    }
+                }
+            }
+        };
+        let scales_offset = match scales {
+            Some(scales) => {
+                out.write_all(&scales).await?;
+                offset += scales.len() as u64;
+                Some(offset - scales.len() as u64)
+            }
+            None => None,
+        };
+        tensors.push(copy_tensor_table(&mut builder, &tensor, new_offset, scales_offset)?);
+        features.add_stored(&tensor);
+        report.recovered.push(tensor.name().to_string());
+    }
+
+    let operations = metadata
+        .operations()
+        .into_iter()
+        .flatten()
+        .map(|op| TensorOperation::build_table(&mut builder, TensorOperation::with_metadata(&op)))
+        .collect::<Vec<_>>();
+    let provenance = metadata.provenance().map(|provenance| Provenance::from_metadata(&provenance));
+    // Constraints naming tensors that weren't recovered are kept, for checks to report.
+    let constraints = metadata.constraints().into_iter().flatten();
+    let constraints = constraints
+        .filter_map(|constraint| TensorConstraint::from_metadata(&constraint).ok())
+        .collect::<Vec<_>>();
+    let root = TensorBuffers::build_versioned_table(
+        &mut builder,
+        VERSION,
+        &tensors,
+        None,
+        &operations,
+        &features,
+        dictionary,
+        metadata.user_metadata().map(|bytes| bytes.bytes()),
+        metadata.model_card(),
+        provenance.as_ref(),
+        &constraints,
+    );
+    builder.finish(root, None);
+    let contents = FooterContents {
+        format_version: VERSION,
+        metadata_offset: offset,
+        tensors: tensors.len(),
+        operations: operations.len(),
+        name_index: false,
+        compressed_metadata: false,
+        filter: None,
+    };
+    write_metadata(&mut out, builder.finished_data(), &contents).await?;
+    out.into_inner().sync_all().await?;
+    info!(recovered = report.recovered.len(), skipped = report.skipped.len(), "Recovered file");
+    Ok(report)
+}
+
+/// Finds the last footer whose metadata is intact.
+///
+/// # Returns
+/// The offset and bytes of the metadata, or `None` if no footer has intact metadata.
+async fn find_metadata(reader: &mut TensorBuffersReader<File>) -> Result<Option<(u64, Vec<u8>)>> {
+    let file_size = reader.file_size().await?;
+    let mut end = file_size;
+    while end > MAGIC_BYTES.len() as u64 {
+        // Chunks overlap by three bytes so magic bytes across a boundary are found.
+        let start = end.saturating_sub(SCAN_CHUNK_SIZE);
+        let mut chunk = vec![0; ((end + 3).min(file_size) - start) as usize];
+        reader.read_at(start, &mut chunk).await?;
+        for i in (0..chunk.len().saturating_sub(3)).rev() {
+            let magic = &chunk[i..i + 4];
+            if magic == MAGIC_BYTES
+                || magic == CHECKSUM_FOOTER_MAGIC_BYTES
+                || magic == BLOOM_FOOTER_MAGIC_BYTES
+                || magic == FIXED_FOOTER_MAGIC_BYTES
+            {
+                if let Some(found) = read_metadata_at(reader, start + i as u64).await {
+                    return Ok(Some(found));
+                }
+            }
+        }
+        end = start;
+    }
+    Ok(None)
+}
+
+/// Reads the metadata of the footer whose trailing magic bytes start at `magic_offset`, if it
+/// parses and matches its checksum.
+async fn read_metadata_at(
+    reader: &mut TensorBuffersReader<File>,
+    magic_offset: u64,
+) -> Option<(u64, Vec<u8>)> {
+    // Parse the footer as if the file ended with it.
+    let end = magic_offset + MAGIC_BYTES.len() as u64;
+    let footer_start = end.saturating_sub(MAX_FOOTER_SIZE as u64);
+    let mut footer = vec![0; (end - footer_start) as usize];
+    reader.read_at(footer_start, &mut footer).await.ok()?;
+    let footer = Footer::parse(&footer, end).ok()?;
+    let metadata_start = end - (footer.size() + footer.metadata_size) as u64;
+
+    let mut metadata = vec![0; footer.metadata_size];
+    reader.read_at(metadata_start, &mut metadata).await.ok()?;
+    if footer.metadata_checksum.is_some_and(|expected| crc32c::crc32c(&metadata) != expected) {
+        return None;
+    }
+    if footer.compressed_metadata {
+        metadata = decompress_metadata(&metadata, FLATBUFFERS_MAX_BUFFER_SIZE).ok()?;
+    }
+    flatbuffers::root::<TensorBuffersMetadata>(&metadata).ok()?;
+    Some((metadata_start, metadata))
+}
+
+/// Reads a tensor's stored data if it is intact.
+async fn salvage(
+    reader: &mut TensorBuffersReader<File>,
+    tensor: &TensorMetadata<'_>,
+    dictionary: Option<&[u8]>,
+    data_end: u64,
+    verify_checksums: bool,
+) -> Result<Vec<u8>> {
+    if let Some(problem) = tensor_problem(tensor) {
+        return Err(problem.into());
+    }
+    check_data_layout(tensor, data_end)?;
+    let mut stored = vec![0; codec::stored_size(tensor)];
+    reader.read_at(tensor.data_offset() as u64, &mut stored).await?;
+    if verify_checksums {
+        codec::verify_checksum(tensor, &stored)?;
+    }
+    // The size comes from metadata that may be damaged, so it isn't allocated unless the stored
+    // bytes can decompress to it. Custom codecs can't tell, so they are limited to the data's size.
+    let size = tensor.data_size() as usize;
+    let max_size = usize::try_from(data_end).unwrap_or(usize::MAX);
+    let (compression, codec) = (tensor.compression(), tensor.codec());
+    codec::check_decompressed_size(&stored, compression, dictionary, codec, size, max_size)
+        .map_err(|error| format!("Tensor {} can't be decompressed: {}", tensor.name(), error))?;
+    Ok(stored)
+}
+
+/// Reads the quantization scales and zero points of a tensor that its metadata doesn't hold, if
+/// they lie within the data, which ends at `data_end`.
+async fn salvage_scales(
+    reader: &mut TensorBuffersReader<File>,
+    tensor: &TensorMetadata<'_>,
+    data_end: u64,
+) -> Result<Option<Vec<u8>>> {
+    let Some(quantization) = tensor.quantization() else {
+        return Ok(None);
+    };
+    let Some((offset, size)) = scales_region(&quantization, tensor.name())? else {
+        return Ok(None);
+    };
+    if offset.checked_add(size as u64).map_or(true, |end| end > data_end) {
+        return Err(format!("Tensor {} has scales outside the data", tensor.name()).into());
+    }
+    let mut scales = vec![0; size];
+    reader.read_at(offset, &mut scales).await?;
+    Ok(Some(scales))
+}
+
+/// Copies how a tensor was quantized, with the scales the metadata doesn't hold now at
+/// `scales_offset`.
+fn copy_quantization<'a>(
+    builder: &mut FlatBufferBuilder<'a>,
+    quantization: &QuantizationMetadata,
+    scales_offset: Option<u64>,
+) -> WIPOffset<QuantizationMetadata<'a>> {
+    let shape = quantization.shape().map(|shape| builder.create_vector_from_iter(shape.iter()));
+    let scales = quantization.scales().map(|scales| builder.create_vector_from_iter(scales.iter()));
+    let zero_points =
+        quantization.zero_points().map(|points| builder.create_vector_from_iter(points.iter()));
+    QuantizationMetadata::create(builder, &QuantizationMetadataArgs {
+        original_type: quantization.original_type(),
+        bits: quantization.bits(),
+        shape,
+        axis: quantization.axis(),
+        scales,
+        zero_points,
+        group_size: quantization.group_size(),
+        scales_offset: scales_offset.unwrap_or(0),
+    })
+}
+
+/// Copies a tensor's metadata, pointing it at `data_offset` and its quantization at
+/// `scales_offset`. Tensors stored in another file keep pointing at it.
+fn copy_tensor_table<'a>(
+    builder: &mut FlatBufferBuilder<'a>,
+    tensor: &TensorMetadata,
+    data_offset: u64,
+    scales_offset: Option<u64>,
+) -> Result<WIPOffset<TensorMetadata<'a>>> {
+    let name = builder.create_string(tensor.name());
+    let shape = tensor.shape().map(|shape| builder.create_vector_from_iter(shape.iter()));
+    let shape64 = tensor.shape64().map(|shape| builder.create_vector_from_iter(shape.iter()));
+    let external = tensor.external().map(|external| {
+        let location = builder.create_string(external.location());
+        ExternalData::create(builder, &ExternalDataArgs {
+            location: Some(location),
+            data_offset: external.data_offset(),
+        })
+    });
+    let alias_of = tensor.alias_of().map(|name| builder.create_string(name));
+    let codec = tensor.codec().map(|codec| builder.create_string(codec));
+    let extension = tensor.extension().map(|extension| {
+        let id = builder.create_string(extension.id());
+        ExtensionType::create(builder, &ExtensionTypeArgs {
+            id: Some(id),
+            element_size: extension.element_size(),
+        })
+    });
+    let user_metadata = tensor.user_metadata().map(|bytes| builder.create_vector(bytes.bytes()));
+    let quantization = tensor
+        .quantization()
+        .map(|quantization| copy_quantization(builder, &quantization, scales_offset));
+    Ok(TensorMetadata::create(builder, &TensorMetadataArgs {
+        id: tensor.id(),
+        name: Some(name),
+        shape,
+        shape64,
+        data_type: tensor.data_type(),
+        data_offset: u32::try_from(data_offset)
+            .map_err(|_| "Recovered tensor data is larger than 4 GiB")?,
+        data_size: tensor.data_size(),
+        compression: tensor.compression(),
+        stored_size: tensor.stored_size(),
+        checksum_algorithm: tensor.checksum_algorithm(),
+        checksum: tensor.checksum(),
+        external,
+        alias_of,
+        codec,
+        extension,
+        user_metadata,
+        quantization,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::NamedTempFile;
+
+    use super::*;
+    use crate::{
+        ChecksumAlgorithm, Tensor, TensorBuffersWrite, TensorBuffersWriter, WriterOptions,
+    };
+
+    #[tokio::test]
+    async fn test_recover() {
+        let tensors = vec![
+            Tensor::new("a", &[1.0f32, 2.0, 3.0], vec![3]),
+            Tensor::new("b", &[4.0f32, 5.0, 6.0], vec![3]),
+            Tensor::new("c", &[1.0f32, 2.0, 3.0], vec![3]),
+        ];
+        let src = NamedTempFile::new().unwrap();
+        let mut file = File::create(src.path()).await.unwrap();
+        let options = WriterOptions::new()
+            .with_checksum(ChecksumAlgorithm::Crc32c)
+            .with_dedup(true)
+            .with_sort_by_name(true);
+        let mut writer = TensorBuffersWriter::with_options(&mut file, options);
+        writer.set_model_card("# Test model");
+        writer.set_provenance(Provenance::new().with_source_commit("3f2c1e0"));
+        writer.add_constraint(TensorConstraint::Equal("a".into(), "c".into()));
+        writer.write(tensors, vec![]).await.unwrap();
+        drop(file);
+
+        // Corrupt b, which follows the shared data of a and c, and append a partial upload.
+        let mut bytes = std::fs::read(src.path()).unwrap();
+        bytes[16] ^= 1;
+        let partial = bytes[..20].to_vec();
+        bytes.extend_from_slice(&partial);
+        std::fs::write(src.path(), &bytes).unwrap();
+
+        let dst = NamedTempFile::new().unwrap();
+        let report = recover(src.path(), dst.path(), true).await.unwrap();
+        assert_eq!(report.recovered, ["a", "c"]);
+        assert_eq!(report.skipped.len(), 1);
+        assert!(report.skipped[0].contains("checksum mismatch for tensor b"));
+
+        let url = format!("file://{}", dst.path().display());
+        let recovered = TensorBuffers::open(&url).await.unwrap();
+        let c = recovered.get_tensor_data_by_name::<f32>("c").await.unwrap();
+        assert_eq!(c.data(), [1.0, 2.0, 3.0]);
+        assert!(recovered.get_tensor_data_by_name::<f32>("b").await.is_err());
+        assert_eq!(recovered.model_card().await.unwrap(), Some("# Test model"));
+        let provenance = recovered.provenance().await.unwrap().unwrap();
+        assert_eq!(provenance.source_commit.as_deref(), Some("3f2c1e0"));
+        assert!(recovered.check_constraints().await.unwrap().is_ok());
+
+        let report = recover(src.path(), dst.path(), false).await.unwrap();
+        assert_eq!(report.recovered.len(), 3);
+
+        std::fs::write(src.path(), &bytes[..bytes.len() / 2]).unwrap();
+        let error = recover(src.path(), dst.path(), true).await.unwrap_err();
+        assert!(error.to_string().starts_with("No intact metadata found"));
+    }
+}
@@ -0,0 +1,516 @@
+//! A small HTTP server that exposes a TensorBuffers file, enabled with the `serve` feature.
+//!
+//! Routes:
+//! * `GET /tensors` - Metadata of every tensor as JSON.
+//! * `GET /tensors/{name}` - Metadata of one tensor as JSON.
+//! * `GET /data/{name}` - Raw little-endian data of one tensor. Supports `Range`.
+//! * `GET /file` - The whole TensorBuffers file. Supports `Range`, so remote readers can open it.
+//!
+//! `HEAD` is accepted wherever `GET` is. Names are percent-decoded. Data is streamed in reads of
+//! `STREAM_CHUNK_SIZE` bytes, so downloading a whole multi-GB file never holds it in memory.
+
+use std::{convert::Infallible, error::Error, future::Future, ops::Range, sync::Arc};
+
+use bytes::Bytes;
+use futures::{
+    stream::{self, FuturesUnordered},
+    StreamExt,
+};
+use http_body_util::{combinators::UnsyncBoxBody, BodyExt, Full, StreamBody};
+use hyper::{
+    body::{Frame, Incoming},
+    header::{self, HeaderValue},
+    server::conn::http1,
+    service::service_fn,
+    Method, Request, Response, StatusCode,
+};
+use hyper_util::rt::TokioIo;
+use serde::Serialize;
+use tokio::net::TcpListener;
+use tracing::{debug, warn};
+
+use crate::{
+    codec,
+    generated::tensor_buffers::{Compression, TensorMetadata},
+    read_mode::tensor_problem,
+    reader_pool::ReaderPool,
+    tensor::stored_shape,
+    utils::hash_key,
+    Result, TensorBuffers,
+};
+
+/// Bytes read from the file per chunk of a streamed response body.
+const STREAM_CHUNK_SIZE: u64 = 1 << 20;
+
+/// The body of a response: buffered for JSON and errors, streamed for data.
+type Body = UnsyncBoxBody<Bytes, Box<dyn Error + Send + Sync>>;
+
+/// The JSON description of a tensor's metadata. Optional fields are left out when unset, and
+/// `data_offset` is left out for tensors stored in other files, whose offset is in `external`.
+#[derive(Serialize)]
+struct TensorJson<'a> {
+    id: u64,
+    name: &'a str,
+    data_type: String,
+    shape: Vec<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data_offset: Option<u32>,
+    data_size: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    compression: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stored_size: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    codec: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    external: Option<ExternalJson<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    alias_of: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+struct ExternalJson<'a> {
+    location: &'a str,
+    data_offset: u64,
+}
+
+impl<'a> TensorJson<'a> {
+    /// Describes a tensor.
+    ///
+    /// # Returns
+    /// An error if the tensor's shape can't be read.
+    fn new(metadata: &TensorMetadata<'a>) -> Result<Self> {
+        let compressed = metadata.compression() != Compression::None;
+        Ok(TensorJson {
+            id: metadata.id(),
+            name: metadata.name(),
+            data_type: format!("{:?}", metadata.data_type()),
+            shape: stored_shape(metadata)?,
+            data_offset: metadata.external().is_none().then(|| metadata.data_offset()),
+            data_size: metadata.data_size(),
+            compression: compressed.then(|| format!("{:?}", metadata.compression())),
+            stored_size: compressed.then(|| metadata.stored_size()),
+            codec: metadata.codec(),
+            external: metadata.external().map(|external| ExternalJson {
+                location: external.location(),
+                data_offset: external.data_offset(),
+            }),
+            alias_of: metadata.alias_of(),
+        })
+    }
+}
+
+/// Decodes `%XX` escapes. Returns `None` for malformed escapes or invalid UTF-8.
+fn percent_decode(value: &str) -> Option<String> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = value.get(i + 1..i + 3)?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+/// Parses a single `bytes=` range against a body of `len` bytes. Returns `None` if the range is
+/// malformed or unsatisfiable.
+fn parse_range(value: &str, len: u64) -> Option<Range<u64>> {
+    let (start, end) = value.strip_prefix("bytes=")?.split_once('-')?;
+    let range = match (start.trim(), end.trim()) {
+        ("", suffix) => {
+            let suffix = suffix.parse::<u64>().ok()?.min(len);
+            len - suffix..len
+        }
+        (start, "") => start.parse::<u64>().ok()?..len,
+        (start, end) => {
+            let end = end.parse::<u64>().ok()?.checked_add(1)?.min(len);
+            start.parse::<u64>().ok()?..end
+        }
+    };
+    (range.start < range.end).then_some(range)
+}
+
+fn full(body: impl Into<Bytes>) -> Body {
+    Full::new(body.into()).map_err(|never: Infallible| match never {}).boxed_unsync()
+}
+
+/// Streams the bytes `range` of the file `readers` read, one `STREAM_CHUNK_SIZE` read at a time.
+fn stream(readers: Arc<ReaderPool>, range: Range<u64>) -> Body {
+    let chunks = stream::try_unfold(range, move |range| {
+        let readers = readers.clone();
+        async move {
+            if range.is_empty() {
+                return Ok(None);
+            }
+            let size = (range.end - range.start).min(STREAM_CHUNK_SIZE);
+            let mut chunk = vec![0; size as usize];
+            readers.get().await?.read_at(range.start, &mut chunk).await?;
+            Ok(Some((Frame::data(Bytes::from(chunk)), range.start + size..range.end)))
+        }
+    });
+    StreamBody::new(chunks).boxed_unsync()
+}
+
+fn response(status: StatusCode, content_type: &str, body: Body) -> Response<Body> {
+    let mut response = Response::new(body);
+    *response.status_mut() = status;
+    response
+        .headers_mut()
+        .insert(header::CONTENT_TYPE, HeaderValue::from_str(content_type).unwrap());
+    response
+}
+
+fn error(status: StatusCode, message: &str) -> Response<Body> {
+    response(status, "text/plain; charset=utf-8", full(message.to_string()))
+}
+
+fn json(value: &impl Serialize) -> Result<Response<Body>> {
+    Ok(response(StatusCode::OK, "application/json", full(serde_json::to_vec(value)?)))
+}
+
+/// Serves a TensorBuffers file over HTTP/1.1.
+pub struct TensorServer<'a> {
+    tensor_buffers: &'a TensorBuffers,
+}
+
+impl<'a> TensorServer<'a> {
+    pub fn new(tensor_buffers: &'a TensorBuffers) -> Self {
+        TensorServer { tensor_buffers }
+    }
+
+    /// Accepts and serves connections until an error occurs.
+    pub async fn serve(&self, listener: TcpListener) -> Result<()> {
+        self.serve_with_shutdown(listener, futures::future::pending()).await
+    }
+
+    /// Accepts and serves connections until `shutdown` completes.
+    ///
+    /// # Arguments
+    /// * `listener` - The socket to accept connections on.
+    /// * `shutdown` - Stops accepting connections when it completes. Open connections are dropped.
+    pub async fn serve_with_shutdown<F>(&self, listener: TcpListener, shutdown: F) -> Result<()>
+    where
+        F: Future<Output = ()>,
+    {
+        // Connections are polled on this task rather than spawned, so requests can borrow the
+        // file without requiring it to be 'static.
+        let mut connections = FuturesUnordered::new();
+        tokio::pin!(shutdown);
+        loop {
+            tokio::select! {
+                accepted = listener.accept() => match accepted {
+                    Ok((stream, address)) => {
+                        debug!("Accepted connection from {}", address);
+                        let service = service_fn(|request| async move {
+                            Ok::<_, Infallible>(self.handle(request).await)
+                        });
+                        connections.push(
+                            http1::Builder::new().serve_connection(TokioIo::new(stream), service),
+                        );
+                    }
+                    Err(e) => warn!("Failed to accept connection: {}", e),
+                },
+                Some(result) = connections.next(), if !connections.is_empty() => {
+                    if let Err(e) = result {
+                        debug!("Connection closed with error: {}", e);
+                    }
+                }
+                _ = &mut shutdown => return Ok(()),
+            }
+        }
+    }
+
+    async fn handle(&self, request: Request<Incoming>) -> Response<Body> {
+        let head = request.method() == Method::HEAD;
+        if !head && request.method() != Method::GET {
+            return error(StatusCode::METHOD_NOT_ALLOWED, "Only GET and HEAD are supported");
+        }
+        let range = request.headers().get(header::RANGE).and_then(|value| value.to_str().ok());
+        let path = request.uri().path();
+        let result = if path == "/tensors" {
+            self.list().await
+        } else if path == "/file" {
+            self.file(range, head).await
+        } else if let Some(name) = path.strip_prefix("/tensors/") {
+            self.metadata(name).await
+        } else if let Some(name) = path.strip_prefix("/data/") {
+            self.data(name, range, head).await
+        } else {
+            Ok(error(StatusCode::NOT_FOUND, "Not found"))
+        };
+        let mut response = result.unwrap_or_else(|e| {
+            warn!("Failed to serve {}: {}", path, e);
+            error(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string())
+        });
+        if head {
+            *response.body_mut() = full(Bytes::new());
+        }
+        response
+    }
+
+    async fn lookup(&self, name: &str) -> Result<Option<TensorMetadata<'a>>> {
+        let Some(name) = percent_decode(name) else {
+            return Ok(None);
+        };
+        let metadata = self.tensor_buffers.find_tensor_metadata(hash_key(&name)).await?;
+        // Tensors skipped in lenient mode are not served.
+        Ok(metadata.filter(|metadata| tensor_problem(metadata).is_none()))
+    }
+
+    async fn list(&self) -> Result<Response<Body>> {
+        #[derive(Serialize)]
+        struct List<'a> {
+            tensors: Vec<TensorJson<'a>>,
+        }
+
+        let tensors = self.tensor_buffers.tensor_tables().await?.into_iter().flatten();
+        let readable = tensors.filter(|metadata| tensor_problem(metadata).is_none());
+        let tensors = readable.map(|metadata| TensorJson::new(&metadata)).collect::<Result<_>>()?;
+        json(&List { tensors })
+    }
+
+    async fn metadata(&self, name: &str) -> Result<Response<Body>> {
+        let Some(metadata) = self.lookup(name).await? else {
+            return Ok(error(StatusCode::NOT_FOUND, "Tensor not found"));
+        };
+        json(&TensorJson::new(&metadata)?)
+    }
+
+    async fn data(&self, name: &str, range: Option<&str>, head: bool) -> Result<Response<Body>> {
+        let Some(metadata) = self.lookup(name).await? else {
+            return Ok(error(StatusCode::NOT_FOUND, "Tensor not found"));
+        };
+        // The stored bytes, which are still compressed if the tensor was written compressed, read
+        // from the file holding them.
+        let (readers, offset) = self.tensor_buffers.locate_data(&metadata).await?;
+        let len = codec::stored_size(&metadata) as u64;
+        self.ranged(readers, offset, len, range, head).await
+    }
+
+    async fn file(&self, range: Option<&str>, head: bool) -> Result<Response<Body>> {
+        let len = self.tensor_buffers.file_size().await?;
+        self.ranged(self.tensor_buffers.readers().clone(), 0, len, range, head).await
+    }
+
+    /// Responds with the bytes `[offset, offset + len)` of the file `readers` read, or the part
+    /// selected by a `Range` header, streamed so the response never holds it all in memory.
+    /// `HEAD` requests only get the headers, without reading any data.
+    async fn ranged(
+        &self,
+        readers: Arc<ReaderPool>,
+        offset: u64,
+        len: u64,
+        range: Option<&str>,
+        head: bool,
+    ) -> Result<Response<Body>> {
+        let (status, selected) = match range {
+            None => (StatusCode::OK, 0..len),
+            Some(value) => match parse_range(value, len) {
+                Some(selected) => (StatusCode::PARTIAL_CONTENT, selected),
+                None => {
+                    let mut response = error(StatusCode::RANGE_NOT_SATISFIABLE, "Invalid range");
+                    let content_range = format!("bytes */{}", len);
+                    response.headers_mut().insert(header::CONTENT_RANGE, content_range.parse()?);
+                    return Ok(response);
+                }
+            },
+        };
+        let size = selected.end - selected.start;
+        let body = match head {
+            true => full(Bytes::new()),
+            false => stream(readers, offset + selected.start..offset + selected.end),
+        };
+        let mut response = response(status, "application/octet-stream", body);
+        let headers = response.headers_mut();
+        headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+        headers.insert(header::CONTENT_LENGTH, HeaderValue::from(size));
+        if status == StatusCode::PARTIAL_CONTENT {
+            let content_range = format!("bytes {}-{}/{}", selected.start, selected.end - 1, len);
+            headers.insert(header::CONTENT_RANGE, content_range.parse()?);
+        }
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytemuck::cast_slice;
+    use flatbuffers::FlatBufferBuilder;
+    use tempfile::NamedTempFile;
+    use tokio::fs::File;
+
+    use super::*;
+    use crate::{
+        generated::tensor_buffers::{ExternalData, ExternalDataArgs, TensorMetadataArgs},
+        Tensor, TensorBuffersWrite, TensorBuffersWriter,
+    };
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range("bytes=0-3", 10), Some(0..4));
+        assert_eq!(parse_range("bytes=4-", 10), Some(4..10));
+        assert_eq!(parse_range("bytes=-3", 10), Some(7..10));
+        assert_eq!(parse_range("bytes=8-100", 10), Some(8..10));
+        assert_eq!(parse_range("bytes=10-", 10), None);
+        assert_eq!(parse_range("bytes=5-2", 10), None);
+        assert_eq!(parse_range("items=0-1", 10), None);
+        assert_eq!(percent_decode("layer%2F0%20w"), Some("layer/0 w".to_string()));
+        assert_eq!(percent_decode("bad%2"), None);
+    }
+
+    #[test]
+    fn test_tensor_json() {
+        let mut builder = FlatBufferBuilder::new();
+        let name = builder.create_string("w");
+        let shape = builder.create_vector(&[2u32]);
+        let location = builder.create_string("base.tb");
+        let external = ExternalData::create(&mut builder, &ExternalDataArgs {
+            location: Some(location),
+            data_offset: 64,
+        });
+        let tensor = TensorMetadata::create(&mut builder, &TensorMetadataArgs {
+            name: Some(name),
+            shape: Some(shape),
+            data_size: 8,
+            external: Some(external),
+            ..Default::default()
+        });
+        builder.finish(tensor, None);
+        let metadata = flatbuffers::root::<TensorMetadata>(builder.finished_data()).unwrap();
+        let json = serde_json::to_string(&TensorJson::new(&metadata).unwrap()).unwrap();
+        assert!(json.contains("\"shape\":[2],\"data_size\":8,"));
+        assert!(json.ends_with("\"external\":{\"location\":\"base.tb\",\"data_offset\":64}}"));
+
+        // A tensor without a shape is an error, not an empty shape.
+        let mut builder = FlatBufferBuilder::new();
+        let name = builder.create_string("w");
+        let tensor = TensorMetadata::create(&mut builder, &TensorMetadataArgs {
+            name: Some(name),
+            data_size: 8,
+            ..Default::default()
+        });
+        builder.finish(tensor, None);
+        let metadata = flatbuffers::root::<TensorMetadata>(builder.finished_data()).unwrap();
+        let error = TensorJson::new(&metadata).err().unwrap();
+        assert_eq!(error.to_string(), "Tensor w has no shape");
+    }
+
+    #[tokio::test]
+    async fn test_serve() {
+        let data = [1.0f32, 2.0, 3.0, 4.0];
+        let tensor = Tensor::new("layer/0 \"w\"", &data, vec![2, 2]);
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        TensorBuffersWriter::new(&mut file).write(vec![tensor], vec![]).await.unwrap();
+
+        let url = format!("file://{}", tmp.path().display());
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let server = TensorServer::new(&tensor_buffers);
+        let (stop, stopped) = futures::channel::oneshot::channel::<()>();
+
+        let client = async {
+            let client = reqwest::Client::new();
+            let list = client.get(format!("{}/tensors", base)).send().await.unwrap();
+            assert_eq!(list.status(), 200);
+            let list = list.text().await.unwrap();
+            assert!(list.starts_with("{\"tensors\":[{\"id\":"));
+            assert!(list.contains(
+                "\"name\":\"layer/0 \\\"w\\\"\",\"data_type\":\"Float32\",\"shape\":[2,2],\"data_offset\":4,\"data_size\":16}"
+            ));
+
+            let name = "layer%2F0%20%22w%22";
+            let metadata = client.get(format!("{}/tensors/{}", base, name)).send().await.unwrap();
+            assert_eq!(metadata.status(), 200);
+
+            let full = client.get(format!("{}/data/{}", base, name)).send().await.unwrap();
+            assert_eq!(full.status(), 200);
+            assert_eq!(&full.bytes().await.unwrap()[..], cast_slice::<f32, u8>(&data));
+
+            let partial = client
+                .get(format!("{}/data/{}", base, name))
+                .header("Range", "bytes=4-11")
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(partial.status(), 206);
+            assert_eq!(partial.headers()["content-range"], "bytes 4-11/16");
+            assert_eq!(&partial.bytes().await.unwrap()[..], cast_slice::<f32, u8>(&data[1..3]));
+
+            let head = client.head(format!("{}/file", base)).send().await.unwrap();
+            let file_size = std::fs::metadata(tmp.path()).unwrap().len();
+            assert_eq!(head.headers()["content-length"], file_size.to_string().as_str());
+            let magic = client
+                .get(format!("{}/file", base))
+                .header("Range", "bytes=-4")
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(&magic.bytes().await.unwrap()[..], b"TBSF");
+
+            let missing = client.get(format!("{}/data/missing", base)).send().await.unwrap();
+            assert_eq!(missing.status(), 404);
+            let invalid = client
+                .get(format!("{}/file", base))
+                .header("Range", "bytes=100000-")
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(invalid.status(), 416);
+            let post = client.post(format!("{}/file", base)).send().await.unwrap();
+            assert_eq!(post.status(), 405);
+            stop.send(()).unwrap();
+        };
+        let serve = server.serve_with_shutdown(listener, async {
+            stopped.await.ok();
+        });
+        let (served, ()) = tokio::join!(serve, client);
+        served.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_serve_streams_large_bodies() {
+        // Larger than a streamed chunk, so the body is sent in several reads.
+        let data = (0..400_000).map(|i| i as f32).collect::<Vec<_>>();
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        let tensor = Tensor::new("w", &data, vec![data.len()]);
+        TensorBuffersWriter::new(&mut file).write(vec![tensor], vec![]).await.unwrap();
+
+        let url = format!("file://{}", tmp.path().display());
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let server = TensorServer::new(&tensor_buffers);
+        let (stop, stopped) = futures::channel::oneshot::channel::<()>();
+
+        let client = async {
+            let client = reqwest::Client::new();
+            let tensor = client.get(format!("{}/data/w", base)).send().await.unwrap();
+            assert_eq!(&tensor.bytes().await.unwrap()[..], cast_slice::<f32, u8>(&data));
+            let file = client.get(format!("{}/file", base)).send().await.unwrap();
+            assert_eq!(file.bytes().await.unwrap(), std::fs::read(tmp.path()).unwrap());
+            let range = client
+                .get(format!("{}/data/w", base))
+                .header("Range", "bytes=1048572-1048579")
+                .send()
+                .await
+                .unwrap();
+            let bytes = range.bytes().await.unwrap();
+            assert_eq!(&bytes[..], cast_slice::<f32, u8>(&data[262_143..262_145]));
+            stop.send(()).unwrap();
+        };
+        let serve = server.serve_with_shutdown(listener, async {
+            stopped.await.ok();
+        });
+        let (served, ()) = tokio::join!(serve, client);
+        served.unwrap();
+    }
+}
@@ -0,0 +1,301 @@
+use fnv::FnvHashMap;
+
+#[cfg(feature = "async")]
+use crate::{extension_type::data_type_of, tensor::stored_shape, TensorBuffers};
+use crate::{
+    kernels::broadcast_shape, DataType, Operation, Result, TensorGraph, TensorId, TensorOperationId,
+};
+
+/// The inferred output of one operation in the graph.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InferredShape {
+    operation_id: TensorOperationId,
+    output: TensorId,
+    shape: Vec<usize>,
+    data_type: DataType,
+}
+
+impl InferredShape {
+    pub fn operation_id(&self) -> TensorOperationId {
+        self.operation_id
+    }
+
+    pub fn output(&self) -> TensorId {
+        self.output
+    }
+
+    pub fn shape(&self) -> &[usize] {
+        &self.shape
+    }
+
+    pub fn data_type(&self) -> DataType {
+        self.data_type
+    }
+}
+
+fn expect_inputs(inputs: &[&[usize]], expected: usize) -> Result<()> {
+    if inputs.len() != expected {
+        return Err(format!("expected {} inputs, found {}", expected, inputs.len()).into());
+    }
+    Ok(())
+}
+
+fn matrix_dims(shape: &[usize]) -> Result<(usize, usize)> {
+    match shape {
+        &[rows, cols] => Ok((rows, cols)),
+        _ => Err(format!("expected a 2-D tensor, found shape {:?}", shape).into()),
+    }
+}
+
+fn matmul_shape(lhs: &[usize], rhs: &[usize]) -> Result<Vec<usize>> {
+    let (m, k) = matrix_dims(lhs)?;
+    let (rhs_k, n) = matrix_dims(rhs)?;
+    if k != rhs_k {
+        return Err(format!("cannot multiply shapes {:?} and {:?}", lhs, rhs).into());
+    }
+    Ok(vec![m, n])
+}
+
+/// Returns the output shape of `operation` applied to inputs of the given shapes,
+/// following the semantics of the executor.
+///
+/// # Returns
+/// Returns an error if the input count or shapes are invalid for the operation, or if the
+/// operation is not supported by the executor.
+pub fn infer_output_shape(operation: Operation, inputs: &[&[usize]]) -> Result<Vec<usize>> {
+    match operation {
+        Operation::Add | Operation::Sub | Operation::Mul | Operation::Div | Operation::Pow => {
+            expect_inputs(inputs, 2)?;
+            broadcast_shape(inputs[0], inputs[1])
+        }
+        Operation::Sqr
+        | Operation::Sqrt
+        | Operation::Sigmoid
+        | Operation::Tanh
+        | Operation::ReLU
+        | Operation::LeakyReLU
+        | Operation::Softplus
+        | Operation::Log
+        | Operation::Exp
+        | Operation::Abs
+        | Operation::Dropout
+        | Operation::Softmax => {
+            expect_inputs(inputs, 1)?;
+            Ok(inputs[0].to_vec())
+        }
+        Operation::Flatten => {
+            expect_inputs(inputs, 1)?;
+            Ok(vec![inputs[0].iter().product()])
+        }
+        Operation::Sum | Operation::Mean | Operation::Argmax => {
+            expect_inputs(inputs, 1)?;
+            Ok(Vec::new())
+        }
+        Operation::MSELoss | Operation::L1Loss => {
+            expect_inputs(inputs, 2)?;
+            broadcast_shape(inputs[0], inputs[1])?;
+            Ok(Vec::new())
+        }
+        Operation::CrossEntropyLoss => {
+            expect_inputs(inputs, 2)?;
+            if inputs[0] != inputs[1] {
+                return Err(format!(
+                    "prediction shape {:?} does not match target shape {:?}",
+                    inputs[0], inputs[1]
+                )
+                .into());
+            }
+            Ok(Vec::new())
+        }
+        Operation::MatMul => {
+            expect_inputs(inputs, 2)?;
+            matmul_shape(inputs[0], inputs[1])
+        }
+        Operation::FC => {
+            if inputs.len() != 2 && inputs.len() != 3 {
+                return Err(format!("expected 2 or 3 inputs, found {}", inputs.len()).into());
+            }
+            let product = matmul_shape(inputs[0], inputs[1])?;
+            match inputs.get(2) {
+                Some(bias) => broadcast_shape(&product, bias),
+                None => Ok(product),
+            }
+        }
+        Operation::Transpose => {
+            expect_inputs(inputs, 1)?;
+            let (rows, cols) = matrix_dims(inputs[0])?;
+            Ok(vec![cols, rows])
+        }
+        Operation::Concat => {
+            let first = inputs.first().ok_or("expected at least one input")?;
+            let inner = first.get(1..).ok_or("cannot concatenate rank-0 tensors")?;
+            let mut rows = 0;
+            for input in inputs {
+                if input.get(1..) != Some(inner) {
+                    return Err(format!(
+                        "cannot concatenate shapes {:?} and {:?} along axis 0",
+                        first, input
+                    )
+                    .into());
+                }
+                rows += input[0];
+            }
+            let mut shape = vec![rows];
+            shape.extend_from_slice(inner);
+            Ok(shape)
+        }
+        _ => Err(format!("operation {:?} is not supported by the executor", operation).into()),
+    }
+}
+
+impl TensorGraph {
+    /// Propagates source tensor shapes through the graph.
+    ///
+    /// # Arguments
+    /// * `source` - Returns the shape and data type of a stored tensor, used for the outputs of
+    ///   `Operation::None` nodes.
+    ///
+    /// # Returns
+    /// Returns the inferred output of every operation in topological order, or an error naming
+    /// the first operation whose inputs are invalid.
+    pub fn infer_shapes<F>(&self, source: F) -> Result<Vec<InferredShape>>
+    where
+        F: Fn(TensorId) -> Option<(Vec<usize>, DataType)>,
+    {
+        let mut inferred: FnvHashMap<TensorOperationId, InferredShape> = FnvHashMap::default();
+        let mut ordered = Vec::with_capacity(self.operations().len());
+        for op in self.operations_in_topological_order()? {
+            let output = *op.output();
+            let (shape, data_type) = if *op.operation() == Operation::None {
+                source(output).ok_or_else(|| {
+                    format!("Operation {} reads unknown tensor {}", op.id(), output)
+                })?
+            } else {
+                let inputs =
+                    op.input_operations().iter().map(|id| &inferred[id]).collect::<Vec<_>>();
+                let data_type = inputs.first().map(|input| input.data_type).ok_or_else(|| {
+                    format!("Operation {} ({:?}) has no inputs", op.id(), op.operation())
+                })?;
+                if let Some(input) = inputs.iter().find(|input| input.data_type != data_type) {
+                    return Err(format!(
+                        "Operation {} ({:?}) mixes data types {:?} and {:?}",
+                        op.id(),
+                        op.operation(),
+                        data_type,
+                        input.data_type
+                    )
+                    .into());
+                }
+                let shapes = inputs.iter().map(|input| input.shape.as_slice()).collect::<Vec<_>>();
+                let shape = infer_output_shape(*op.operation(), &shapes).map_err(|e| {
+                    format!("Operation {} ({:?}) failed: {}", op.id(), op.operation(), e)
+                })?;
+                (shape, data_type)
+            };
+            let result = InferredShape { operation_id: op.id(), output, shape, data_type };
+            ordered.push(result.clone());
+            inferred.insert(op.id(), result);
+        }
+        Ok(ordered)
+    }
+}
+
+#[cfg(feature = "async")]
+impl TensorBuffers {
+    /// Infers the output shape and data type of every stored operation from tensor metadata
+    /// alone, without reading any tensor data.
+    pub async fn infer_shapes(&self) -> Result<Vec<InferredShape>> {
+        let graph = self.graph().await?;
+        let mut sources = FnvHashMap::default();
+        for op in graph.operations() {
+            if *op.operation() != Operation::None {
+                continue;
+            }
+            let metadata = self.stored_metadata(*op.output()).await?;
+            let shape = stored_shape(&metadata).unwrap_or_default();
+            let data_type = data_type_of(&metadata)?;
+            sources.insert(*op.output(), (shape, data_type));
+        }
+        graph.infer_shapes(|tensor_id| sources.get(&tensor_id).cloned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "async")]
+    use tempfile::NamedTempFile;
+    #[cfg(feature = "async")]
+    use tokio::fs::File;
+
+    use super::*;
+    use crate::TensorOperation;
+    #[cfg(feature = "async")]
+    use crate::{utils::hash_key, Tensor, TensorBuffersWrite, TensorBuffersWriter};
+
+    #[test]
+    fn test_infer_output_shape() {
+        let shape = infer_output_shape(Operation::MatMul, &[&[2, 3], &[3, 4]]).unwrap();
+        assert_eq!(shape, vec![2, 4]);
+        let shape = infer_output_shape(Operation::FC, &[&[2, 3], &[3, 4], &[4]]).unwrap();
+        assert_eq!(shape, vec![2, 4]);
+        let shape = infer_output_shape(Operation::Concat, &[&[2, 3], &[5, 3]]).unwrap();
+        assert_eq!(shape, vec![7, 3]);
+        assert_eq!(infer_output_shape(Operation::Mean, &[&[2, 3]]).unwrap(), Vec::<usize>::new());
+        assert!(infer_output_shape(Operation::MatMul, &[&[2, 3], &[4, 3]]).is_err());
+        assert!(infer_output_shape(Operation::Add, &[&[2, 3], &[2]]).is_err());
+        assert!(infer_output_shape(Operation::Conv2D, &[&[2, 3]]).is_err());
+    }
+
+    #[test]
+    fn test_graph_infer_shapes() {
+        let graph = TensorGraph::new(vec![
+            TensorOperation::new(1, Operation::None, vec![], 10),
+            TensorOperation::new(2, Operation::None, vec![], 20),
+            TensorOperation::new(3, Operation::MatMul, vec![1, 2], 30),
+            TensorOperation::new(4, Operation::Transpose, vec![3], 40),
+        ]);
+        let source = |tensor_id| match tensor_id {
+            10 => Some((vec![2, 3], DataType::Float32)),
+            20 => Some((vec![3, 5], DataType::Float32)),
+            _ => None,
+        };
+        let shapes = graph.infer_shapes(source).unwrap();
+        assert_eq!(shapes.len(), 4);
+        assert_eq!(shapes[3].operation_id(), 4);
+        assert_eq!(shapes[3].shape(), &[5, 2]);
+        assert_eq!(shapes[3].data_type(), DataType::Float32);
+
+        let mixed = |tensor_id| match tensor_id {
+            10 => Some((vec![2, 3], DataType::Float32)),
+            _ => Some((vec![3, 5], DataType::Float64)),
+        };
+        assert!(graph.infer_shapes(mixed).is_err());
+        assert!(graph.infer_shapes(|_| None).is_err());
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn test_tensor_buffers_infer_shapes() {
+        let x = Tensor::new("x", &[1.0f32, 2.0, 3.0, 4.0, 5.0, 6.0], vec![2, 3]);
+        let b = Tensor::new("b", &[1.0f32, 2.0, 3.0], vec![3]);
+        let operations = vec![
+            TensorOperation::new(1, Operation::None, vec![], x.id()),
+            TensorOperation::new(2, Operation::None, vec![], b.id()),
+            TensorOperation::new(3, Operation::Add, vec![1, 2], hash_key("y")),
+            TensorOperation::new(4, Operation::Sum, vec![3], hash_key("total")),
+        ];
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        let mut writer = TensorBuffersWriter::new(&mut file);
+        writer.write(vec![x, b], operations).await.unwrap();
+
+        let url = format!("file://{}", tmp.path().display());
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        let shapes = tensor_buffers.infer_shapes().await.unwrap();
+        let y = shapes.iter().find(|shape| shape.output() == hash_key("y")).unwrap();
+        assert_eq!(y.shape(), &[2, 3]);
+        let total = shapes.iter().find(|shape| shape.output() == hash_key("total")).unwrap();
+        assert!(total.shape().is_empty());
+    }
+}
@@ -0,0 +1,425 @@
+//! Vectorized f32/f64 kernels used by `kernels` when the element type allows it, and the
+//! dequantization kernels `quantization` loads quantized tensors with.
+//! AVX2+FMA is detected at runtime on x86_64, NEON is always available on aarch64,
+//! and every other target falls back to the scalar loops.
+
+/// Element-wise arithmetic supported by the vectorized kernels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ArithmeticOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+macro_rules! impl_scalar {
+    ($t:ty, $arithmetic:ident, $axpy:ident) => {
+        fn $arithmetic(op: ArithmeticOp, lhs: &[$t], rhs: &[$t], out: &mut [$t]) {
+            for ((out, &a), &b) in out.iter_mut().zip(lhs).zip(rhs) {
+                *out = match op {
+                    ArithmeticOp::Add => a + b,
+                    ArithmeticOp::Sub => a - b,
+                    ArithmeticOp::Mul => a * b,
+                    ArithmeticOp::Div => a / b,
+                };
+            }
+        }
+
+        #[cfg(feature = "async")]
+        fn $axpy(a: $t, x: &[$t], y: &mut [$t]) {
+            for (y, &x) in y.iter_mut().zip(x) {
+                *y += a * x;
+            }
+        }
+    };
+}
+
+impl_scalar!(f32, scalar_arithmetic_f32, scalar_axpy_f32);
+impl_scalar!(f64, scalar_arithmetic_f64, scalar_axpy_f64);
+
+fn scalar_dequantize_i8(codes: &[i8], zero_point: i32, scale: f32, out: &mut [f32]) {
+    for (out, &code) in out.iter_mut().zip(codes) {
+        *out = (code as i32 - zero_point) as f32 * scale;
+    }
+}
+
+#[cfg(feature = "async")]
+fn scalar_unpack_i4(packed: &[u8], out: &mut [i8]) {
+    for (pair, &byte) in out.chunks_exact_mut(2).zip(packed) {
+        pair[0] = ((byte << 4) as i8) >> 4;
+        pair[1] = (byte as i8) >> 4;
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+fn has_avx2() -> bool {
+    is_x86_feature_detected!("avx2") && is_x86_feature_detected!("fma")
+}
+
+#[cfg(target_arch = "x86_64")]
+mod avx2 {
+    use std::arch::x86_64::*;
+
+    use super::ArithmeticOp;
+
+    macro_rules! impl_avx2 {
+        (
+            $t:ty, $lanes:expr, $arithmetic:ident, $axpy:ident, $scalar_arithmetic:ident,
+            $scalar_axpy:ident, $load:ident, $store:ident, $splat:ident, $add:ident, $sub:ident,
+            $mul:ident, $div:ident, $fmadd:ident
+        ) => {
+            #[target_feature(enable = "avx2,fma")]
+            pub(super) unsafe fn $arithmetic(
+                op: ArithmeticOp,
+                lhs: &[$t],
+                rhs: &[$t],
+                out: &mut [$t],
+            ) {
+                let vectorized = out.len() / $lanes * $lanes;
+                for i in (0..vectorized).step_by($lanes) {
+                    let a = $load(lhs.as_ptr().add(i));
+                    let b = $load(rhs.as_ptr().add(i));
+                    let c = match op {
+                        ArithmeticOp::Add => $add(a, b),
+                        ArithmeticOp::Sub => $sub(a, b),
+                        ArithmeticOp::Mul => $mul(a, b),
+                        ArithmeticOp::Div => $div(a, b),
+                    };
+                    $store(out.as_mut_ptr().add(i), c);
+                }
+                super::$scalar_arithmetic(
+                    op,
+                    &lhs[vectorized..],
+                    &rhs[vectorized..],
+                    &mut out[vectorized..],
+                );
+            }
+
+            #[cfg(feature = "async")]
+            #[target_feature(enable = "avx2,fma")]
+            pub(super) unsafe fn $axpy(a: $t, x: &[$t], y: &mut [$t]) {
+                let vectorized = y.len() / $lanes * $lanes;
+                let va = $splat(a);
+                for i in (0..vectorized).step_by($lanes) {
+                    let vx = $load(x.as_ptr().add(i));
+                    let vy = $load(y.as_ptr().add(i));
+                    $store(y.as_mut_ptr().add(i), $fmadd(va, vx, vy));
+                }
+                super::$scalar_axpy(a, &x[vectorized..], &mut y[vectorized..]);
+            }
+        };
+    }
+
+    impl_avx2!(
+        f32,
+        8,
+        arithmetic_f32,
+        axpy_f32,
+        scalar_arithmetic_f32,
+        scalar_axpy_f32,
+        _mm256_loadu_ps,
+        _mm256_storeu_ps,
+        _mm256_set1_ps,
+        _mm256_add_ps,
+        _mm256_sub_ps,
+        _mm256_mul_ps,
+        _mm256_div_ps,
+        _mm256_fmadd_ps
+    );
+    impl_avx2!(
+        f64,
+        4,
+        arithmetic_f64,
+        axpy_f64,
+        scalar_arithmetic_f64,
+        scalar_axpy_f64,
+        _mm256_loadu_pd,
+        _mm256_storeu_pd,
+        _mm256_set1_pd,
+        _mm256_add_pd,
+        _mm256_sub_pd,
+        _mm256_mul_pd,
+        _mm256_div_pd,
+        _mm256_fmadd_pd
+    );
+
+    #[target_feature(enable = "avx2,fma")]
+    pub(super) unsafe fn dequantize_i8(codes: &[i8], zero_point: i32, scale: f32, out: &mut [f32]) {
+        let vectorized = out.len() / 8 * 8;
+        let (zero_point_v, scale_v) = (_mm256_set1_epi32(zero_point), _mm256_set1_ps(scale));
+        for i in (0..vectorized).step_by(8) {
+            let bytes = _mm_loadl_epi64(codes.as_ptr().add(i) as *const __m128i);
+            let values = _mm256_sub_epi32(_mm256_cvtepi8_epi32(bytes), zero_point_v);
+            _mm256_storeu_ps(
+                out.as_mut_ptr().add(i),
+                _mm256_mul_ps(_mm256_cvtepi32_ps(values), scale_v),
+            );
+        }
+        super::scalar_dequantize_i8(
+            &codes[vectorized..],
+            zero_point,
+            scale,
+            &mut out[vectorized..],
+        );
+    }
+
+    #[cfg(feature = "async")]
+    #[target_feature(enable = "avx2,fma")]
+    pub(super) unsafe fn unpack_i4(packed: &[u8], out: &mut [i8]) {
+        let vectorized = packed.len() / 16 * 16;
+        let (mask, sign) = (_mm_set1_epi8(0x0f), _mm_set1_epi8(8));
+        for i in (0..vectorized).step_by(16) {
+            let bytes = _mm_loadu_si128(packed.as_ptr().add(i) as *const __m128i);
+            // (nibble ^ 8) - 8 sign-extends a 4-bit two's complement value.
+            let low = _mm_sub_epi8(_mm_xor_si128(_mm_and_si128(bytes, mask), sign), sign);
+            let high = _mm_and_si128(_mm_srli_epi16(bytes, 4), mask);
+            let high = _mm_sub_epi8(_mm_xor_si128(high, sign), sign);
+            let out = out.as_mut_ptr().add(2 * i) as *mut __m128i;
+            _mm_storeu_si128(out, _mm_unpacklo_epi8(low, high));
+            _mm_storeu_si128(out.add(1), _mm_unpackhi_epi8(low, high));
+        }
+        super::scalar_unpack_i4(&packed[vectorized..], &mut out[2 * vectorized..]);
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod neon {
+    use std::arch::aarch64::*;
+
+    use super::ArithmeticOp;
+
+    macro_rules! impl_neon {
+        (
+            $t:ty, $lanes:expr, $arithmetic:ident, $axpy:ident, $scalar_arithmetic:ident,
+            $scalar_axpy:ident, $load:ident, $store:ident, $splat:ident, $add:ident, $sub:ident,
+            $mul:ident, $div:ident, $fmadd:ident
+        ) => {
+            pub(super) unsafe fn $arithmetic(
+                op: ArithmeticOp,
+                lhs: &[$t],
+                rhs: &[$t],
+                out: &mut [$t],
+            ) {
+                let vectorized = out.len() / $lanes * $lanes;
+                for i in (0..vectorized).step_by($lanes) {
+                    let a = $load(lhs.as_ptr().add(i));
+                    let b = $load(rhs.as_ptr().add(i));
+                    let c = match op {
+                        ArithmeticOp::Add => $add(a, b),
+                        ArithmeticOp::Sub => $sub(a, b),
+                        ArithmeticOp::Mul => $mul(a, b),
+                        ArithmeticOp::Div => $div(a, b),
+                    };
+                    $store(out.as_mut_ptr().add(i), c);
+                }
+                super::$scalar_arithmetic(
+                    op,
+                    &lhs[vectorized..],
+                    &rhs[vectorized..],
+                    &mut out[vectorized..],
+                );
+            }
+
+            #[cfg(feature = "async")]
+            pub(super) unsafe fn $axpy(a: $t, x: &[$t], y: &mut [$t]) {
+                let vectorized = y.len() / $lanes * $lanes;
+                let va = $splat(a);
+                for i in (0..vectorized).step_by($lanes) {
+                    let vx = $load(x.as_ptr().add(i));
+                    let vy = $load(y.as_ptr().add(i));
+                    // vfmaq computes vy + vx * va.
+                    $store(y.as_mut_ptr().add(i), $fmadd(vy, vx, va));
+                }
+                super::$scalar_axpy(a, &x[vectorized..], &mut y[vectorized..]);
+            }
+        };
+    }
+
+    impl_neon!(
+        f32,
+        4,
+        arithmetic_f32,
+        axpy_f32,
+        scalar_arithmetic_f32,
+        scalar_axpy_f32,
+        vld1q_f32,
+        vst1q_f32,
+        vdupq_n_f32,
+        vaddq_f32,
+        vsubq_f32,
+        vmulq_f32,
+        vdivq_f32,
+        vfmaq_f32
+    );
+    impl_neon!(
+        f64,
+        2,
+        arithmetic_f64,
+        axpy_f64,
+        scalar_arithmetic_f64,
+        scalar_axpy_f64,
+        vld1q_f64,
+        vst1q_f64,
+        vdupq_n_f64,
+        vaddq_f64,
+        vsubq_f64,
+        vmulq_f64,
+        vdivq_f64,
+        vfmaq_f64
+    );
+
+    pub(super) unsafe fn dequantize_i8(codes: &[i8], zero_point: i32, scale: f32, out: &mut [f32]) {
+        let vectorized = out.len() / 8 * 8;
+        let (zero_point_v, scale_v) = (vdupq_n_s32(zero_point), vdupq_n_f32(scale));
+        for i in (0..vectorized).step_by(8) {
+            let wide = vmovl_s8(vld1_s8(codes.as_ptr().add(i)));
+            let low = vsubq_s32(vmovl_s16(vget_low_s16(wide)), zero_point_v);
+            let high = vsubq_s32(vmovl_high_s16(wide), zero_point_v);
+            vst1q_f32(out.as_mut_ptr().add(i), vmulq_f32(vcvtq_f32_s32(low), scale_v));
+            vst1q_f32(out.as_mut_ptr().add(i + 4), vmulq_f32(vcvtq_f32_s32(high), scale_v));
+        }
+        super::scalar_dequantize_i8(
+            &codes[vectorized..],
+            zero_point,
+            scale,
+            &mut out[vectorized..],
+        );
+    }
+
+    #[cfg(feature = "async")]
+    pub(super) unsafe fn unpack_i4(packed: &[u8], out: &mut [i8]) {
+        let vectorized = packed.len() / 16 * 16;
+        let (mask, sign) = (vdupq_n_u8(0x0f), vdupq_n_s8(8));
+        for i in (0..vectorized).step_by(16) {
+            let bytes = vld1q_u8(packed.as_ptr().add(i));
+            // (nibble ^ 8) - 8 sign-extends a 4-bit two's complement value.
+            let low = vreinterpretq_s8_u8(vandq_u8(bytes, mask));
+            let high = vreinterpretq_s8_u8(vshrq_n_u8::<4>(bytes));
+            let low = vsubq_s8(veorq_s8(low, sign), sign);
+            let high = vsubq_s8(veorq_s8(high, sign), sign);
+            // Storing the pair interleaved puts each byte's low nibble first.
+            vst2q_s8(out.as_mut_ptr().add(2 * i), int8x16x2_t(low, high));
+        }
+        super::scalar_unpack_i4(&packed[vectorized..], &mut out[2 * vectorized..]);
+    }
+}
+
+macro_rules! impl_dispatch {
+    ($t:ty, $arithmetic:ident, $axpy:ident, $scalar_arithmetic:ident, $scalar_axpy:ident) => {
+        /// Computes `out[i] = lhs[i] op rhs[i]`. All slices must have the same length.
+        pub(crate) fn $arithmetic(op: ArithmeticOp, lhs: &[$t], rhs: &[$t], out: &mut [$t]) {
+            assert!(lhs.len() == out.len() && rhs.len() == out.len());
+            #[cfg(target_arch = "x86_64")]
+            if has_avx2() {
+                // SAFETY: AVX2 and FMA support was checked at runtime.
+                return unsafe { avx2::$arithmetic(op, lhs, rhs, out) };
+            }
+            #[cfg(target_arch = "aarch64")]
+            // SAFETY: NEON is part of the aarch64 baseline.
+            return unsafe { neon::$arithmetic(op, lhs, rhs, out) };
+            #[allow(unreachable_code)]
+            $scalar_arithmetic(op, lhs, rhs, out);
+        }
+
+        /// Computes `y[i] += a * x[i]`. Both slices must have the same length.
+        #[cfg(feature = "async")]
+        pub(crate) fn $axpy(a: $t, x: &[$t], y: &mut [$t]) {
+            assert!(x.len() == y.len());
+            #[cfg(target_arch = "x86_64")]
+            if has_avx2() {
+                // SAFETY: AVX2 and FMA support was checked at runtime.
+                return unsafe { avx2::$axpy(a, x, y) };
+            }
+            #[cfg(target_arch = "aarch64")]
+            // SAFETY: NEON is part of the aarch64 baseline.
+            return unsafe { neon::$axpy(a, x, y) };
+            #[allow(unreachable_code)]
+            $scalar_axpy(a, x, y);
+        }
+    };
+}
+
+impl_dispatch!(f32, arithmetic_f32, axpy_f32, scalar_arithmetic_f32, scalar_axpy_f32);
+impl_dispatch!(f64, arithmetic_f64, axpy_f64, scalar_arithmetic_f64, scalar_axpy_f64);
+
+/// Computes `out[i] = (codes[i] - zero_point) * scale`. Both slices must have the same length.
+pub(crate) fn dequantize_i8(codes: &[i8], zero_point: i32, scale: f32, out: &mut [f32]) {
+    assert!(codes.len() == out.len());
+    #[cfg(target_arch = "x86_64")]
+    if has_avx2() {
+        // SAFETY: AVX2 and FMA support was checked at runtime.
+        return unsafe { avx2::dequantize_i8(codes, zero_point, scale, out) };
+    }
+    #[cfg(target_arch = "aarch64")]
+    // SAFETY: NEON is part of the aarch64 baseline.
+    return unsafe { neon::dequantize_i8(codes, zero_point, scale, out) };
+    #[allow(unreachable_code)]
+    scalar_dequantize_i8(codes, zero_point, scale, out);
+}
+
+/// Sign-extends the 4-bit values packed two per byte, low nibble first, into `out`, which must be
+/// twice as long as `packed`.
+#[cfg(feature = "async")]
+pub(crate) fn unpack_i4(packed: &[u8], out: &mut [i8]) {
+    assert!(out.len() == 2 * packed.len());
+    #[cfg(target_arch = "x86_64")]
+    if has_avx2() {
+        // SAFETY: AVX2 and FMA support was checked at runtime.
+        return unsafe { avx2::unpack_i4(packed, out) };
+    }
+    #[cfg(target_arch = "aarch64")]
+    // SAFETY: NEON is part of the aarch64 baseline.
+    return unsafe { neon::unpack_i4(packed, out) };
+    #[allow(unreachable_code)]
+    scalar_unpack_i4(packed, out);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arithmetic_matches_scalar() {
+        // Odd length so both the vector body and the scalar tail are exercised.
+        let lhs = (0..37).map(|i| i as f32 * 0.5 + 1.0).collect::<Vec<_>>();
+        let rhs = (0..37).map(|i| 3.0 - i as f32 * 0.25).collect::<Vec<_>>();
+        for op in [ArithmeticOp::Add, ArithmeticOp::Sub, ArithmeticOp::Mul, ArithmeticOp::Div] {
+            let mut expected = vec![0.0; 37];
+            let mut actual = vec![0.0; 37];
+            scalar_arithmetic_f32(op, &lhs, &rhs, &mut expected);
+            arithmetic_f32(op, &lhs, &rhs, &mut actual);
+            assert_eq!(actual, expected, "{:?}", op);
+        }
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_axpy_matches_scalar() {
+        let x = (0..19).map(|i| i as f64).collect::<Vec<_>>();
+        let mut expected = vec![1.0; 19];
+        let mut actual = vec![1.0; 19];
+        scalar_axpy_f64(2.0, &x, &mut expected);
+        axpy_f64(2.0, &x, &mut actual);
+        assert_eq!(actual, expected);
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_dequantize_matches_scalar() {
+        let packed = (0..37).map(|i| (i * 37 + 11) as u8).collect::<Vec<_>>();
+        let mut expected = vec![0; 74];
+        let mut codes = vec![0; 74];
+        scalar_unpack_i4(&packed, &mut expected);
+        unpack_i4(&packed, &mut codes);
+        assert_eq!(codes, expected);
+        assert_eq!(&codes[..4], [-5, 0, 0, 3]);
+
+        let codes = (0..37).map(|i| (i * 7 - 128) as i8).collect::<Vec<_>>();
+        let mut expected = vec![0.0; 37];
+        let mut actual = vec![0.0; 37];
+        scalar_dequantize_i8(&codes, -3, 0.125, &mut expected);
+        dequantize_i8(&codes, -3, 0.125, &mut actual);
+        assert_eq!(actual, expected);
+        assert_eq!(actual[0], -15.625);
+    }
+}
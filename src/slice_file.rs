@@ -0,0 +1,326 @@
+use std::{mem::size_of, ops::Range};
+
+use bytemuck::{cast_slice_mut, Pod};
+
+use crate::{
+    codec,
+    constants::{MAGIC_BYTES, SUPPORTED_VERSIONS},
+    extension_type::data_type_of,
+    footer::{Footer, MAX_FOOTER_SIZE},
+    format_features::check_features,
+    generated::tensor_buffers::{TensorBuffersMetadata, TensorMetadata},
+    parity::without_parity,
+    read_mode::{check_metadata, find_tensor},
+    tensor::stored_shape,
+    utils::hash_key,
+    Num, Result, Tensor, TensorInfo, TensorOperation,
+};
+
+/// A TensorBuffers file parsed from bytes the caller already holds, e.g. a memory map or a buffer
+/// handed to a plugin. Parsing is synchronous and borrows the metadata from the bytes instead of
+/// copying it, and this module needs neither tokio nor reqwest, so it is available without the
+/// default `async` feature.
+#[derive(Clone, Copy, Debug)]
+pub struct SliceFile<'a> {
+    bytes: &'a [u8],
+    metadata: TensorBuffersMetadata<'a>,
+    /// Where the metadata starts, which ends the data section.
+    metadata_start: usize,
+}
+
+/// Parses a TensorBuffers file held in memory.
+///
+/// Checks the magic bytes, footer and metadata checksum, and rejects metadata that strict reads
+/// reject as well as unsupported versions and features. Files with compressed or columnar metadata
+/// are rejected, since the parsed file borrows its metadata from `bytes`. Use `parse_untrusted` for
+/// bytes that may be hostile.
+///
+/// # Arguments
+/// * `bytes` - The whole file.
+///
+/// # Returns
+/// The parsed file, which borrows `bytes`.
+pub fn parse_slice(bytes: &[u8]) -> Result<SliceFile<'_>> {
+    let (footer, range) = locate_metadata(bytes)?;
+    if footer.compressed_metadata {
+        return Err("Compressed metadata is not supported for files parsed from a slice".into());
+    }
+    let metadata_start = range.start;
+    let metadata = parse_metadata(&bytes[range])?;
+    if metadata.tensor_columns().is_some() {
+        return Err("Columnar metadata is not supported for files parsed from a slice".into());
+    }
+    Ok(SliceFile { bytes, metadata, metadata_start })
+}
+
+impl<'a> SliceFile<'a> {
+    /// Returns the format version of the file.
+    pub fn version(&self) -> &'a str {
+        self.metadata.version()
+    }
+
+    /// Returns the names of the tensors in the file, in id order.
+    pub fn tensor_names(&self) -> impl Iterator<Item = &'a str> {
+        self.metadata.tensors().into_iter().flatten().map(|tensor| tensor.name())
+    }
+
+    /// Returns the operations stored in the file.
+    pub fn operations(&self) -> Vec<TensorOperation> {
+        let operations = self.metadata.operations().into_iter().flatten();
+        operations.map(|op| TensorOperation::with_metadata(&op)).collect()
+    }
+
+    /// Returns what the metadata says about a tensor.
+    pub fn tensor_info(&self, name: &str) -> Result<TensorInfo> {
+        TensorInfo::from_metadata(&self.find(name)?)
+    }
+
+    /// Returns where a tensor's data is stored in the file, after checking that the range lies
+    /// within the data section.
+    ///
+    /// # Returns
+    /// The byte range of the stored data, which is compressed if the tensor is.
+    pub fn tensor_range(&self, name: &str) -> Result<Range<usize>> {
+        let metadata = self.find(name)?;
+        if metadata.external().is_some() {
+            return Err(format!("Tensor {} is stored in another file", name).into());
+        }
+        check_data_layout(&metadata, self.metadata_start as u64)?;
+        let offset = metadata.data_offset() as usize;
+        Ok(offset..offset + codec::stored_size(&metadata))
+    }
+
+    /// Returns the stored bytes of a tensor without copying them.
+    pub fn tensor_bytes(&self, name: &str) -> Result<&'a [u8]> {
+        Ok(&self.bytes[self.tensor_range(name)?])
+    }
+
+    /// Decodes a tensor, checking its data against its checksum.
+    ///
+    /// # Arguments
+    /// * `name` - The name of the tensor.
+    pub fn tensor<T>(&self, name: &str) -> Result<Tensor<'static, T>>
+    where
+        T: Pod + Num,
+    {
+        let stored = self.tensor_bytes(name)?;
+        decode_stored(self.metadata, self.find(name)?, stored)
+    }
+
+    fn find(&self, name: &str) -> Result<TensorMetadata<'a>> {
+        find_tensor(&self.metadata, hash_key(name))
+            .ok_or_else(|| format!("Tensor {} not found", name).into())
+    }
+}
+
+/// Locates the metadata of a file held in memory, after checking its magic bytes, footer and
+/// metadata checksum. A parity section at the end is skipped.
+///
+/// # Returns
+/// The footer and the byte range of the metadata as stored.
+pub(crate) fn locate_metadata(bytes: &[u8]) -> Result<(Footer, Range<usize>)> {
+    let bytes = without_parity(bytes);
+    if !bytes.starts_with(MAGIC_BYTES) {
+        return Err("Invalid magic bytes".into());
+    }
+    let file_size = bytes.len() as u64;
+    let footer = Footer::parse(&bytes[bytes.len().saturating_sub(MAX_FOOTER_SIZE)..], file_size)?;
+    // `Footer::parse` checked that the metadata and footer fit after the leading magic bytes.
+    let metadata_start = bytes.len() - footer.size() - footer.metadata_size;
+    let range = metadata_start..metadata_start + footer.metadata_size;
+    if let Some(expected) = footer.metadata_checksum {
+        if crc32c::crc32c(&bytes[range.clone()]) != expected {
+            return Err("Metadata checksum mismatch".into());
+        }
+    }
+    Ok((footer, range))
+}
+
+/// Parses uncompressed metadata, rejecting what strict reads reject as well as unsupported
+/// versions and features.
+pub(crate) fn parse_metadata(bytes: &[u8]) -> Result<TensorBuffersMetadata<'_>> {
+    let metadata = flatbuffers::root::<TensorBuffersMetadata>(bytes)
+        .map_err(|error| format!("Invalid metadata: {}", error))?;
+    if !SUPPORTED_VERSIONS.contains(&metadata.version()) {
+        return Err(format!("Unsupported format version {}", metadata.version()).into());
+    }
+    check_features(&metadata)?;
+    if let Some(problem) = check_metadata(&metadata).into_iter().next() {
+        return Err(problem.into());
+    }
+    Ok(metadata)
+}
+
+/// Decodes a tensor from its stored data, checking the data against its checksum.
+pub(crate) fn decode_stored<T>(
+    root: TensorBuffersMetadata,
+    metadata: TensorMetadata,
+    stored: &[u8],
+) -> Result<Tensor<'static, T>>
+where
+    T: Pod + Num,
+{
+    check_data_type::<T>(&metadata)?;
+    codec::verify_checksum(&metadata, stored)?;
+    let mut data = vec![T::zero(); metadata.data_size() as usize / size_of::<T>()];
+    let dictionary = root.compression_dictionary().map(|dictionary| dictionary.bytes());
+    let out = cast_slice_mut(&mut data);
+    codec::decompress(stored, metadata.compression(), dictionary, metadata.codec(), out)?;
+    Tensor::new_with_metadata_and_data(metadata, data)
+}
+
+/// Returns where a tensor's stored data starts, in its own file or in the file holding it.
+pub(crate) fn stored_offset(metadata: &TensorMetadata) -> u64 {
+    match metadata.external() {
+        Some(external) => external.data_offset(),
+        None => metadata.data_offset() as u64,
+    }
+}
+
+/// Checks that a tensor is stored with element type `T`.
+/// Tensors of extension and block-quantized types are stored as their bytes, so they load as `u8`.
+pub(crate) fn check_data_type<T: Num>(metadata: &TensorMetadata) -> Result<()> {
+    let stored = match metadata.data_type() {
+        crate::generated::tensor_buffers::DataType::Extension
+        | crate::generated::tensor_buffers::DataType::Q8_0
+        | crate::generated::tensor_buffers::DataType::Q4_K => {
+            crate::generated::tensor_buffers::DataType::UInt8
+        }
+        data_type => data_type,
+    };
+    if stored != T::data_type().into() {
+        return Err(format!(
+            "Tensor data type mismatch: expected {:?}, found {:?}",
+            T::data_type(),
+            metadata.data_type()
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Checks that the tensor's data size matches its shape and data type, and that its stored data
+/// lies within the file, so corrupt metadata can't produce a misshapen tensor or a short read.
+/// For data stored in another file, `file_size` is the size of that file.
+pub(crate) fn check_data_layout(metadata: &TensorMetadata, file_size: u64) -> Result<()> {
+    let name = metadata.name();
+    let data_type = data_type_of(metadata)?;
+    let shape = stored_shape(metadata)?;
+    let elements = shape.iter().try_fold(1u64, |elements, &dim| elements.checked_mul(dim as u64));
+    let expected = elements.and_then(|elements| data_type.data_size(elements));
+    if expected != Some(metadata.data_size() as u64) {
+        let needs = match (elements, expected) {
+            (_, Some(bytes)) => bytes.to_string(),
+            (Some(elements), None) if elements % data_type.block_size() as u64 != 0 => {
+                format!("whole blocks of {} elements", data_type.block_size())
+            }
+            _ => "more than 2^64".to_string(),
+        };
+        return Err(format!(
+            "Tensor {} has a data size of {} bytes, but shape {:?} of {:?} needs {}",
+            name,
+            metadata.data_size(),
+            shape,
+            data_type,
+            needs
+        )
+        .into());
+    }
+
+    let offset = stored_offset(metadata);
+    let end = offset + codec::stored_size(metadata) as u64;
+    if end > file_size {
+        return Err(format!(
+            "Tensor {} data range [{}, {}) is out of bounds for a file of {} bytes",
+            name, offset, end, file_size
+        )
+        .into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use flatbuffers::FlatBufferBuilder;
+
+    use super::*;
+    use crate::generated::tensor_buffers::{DataType, TensorMetadataArgs};
+    #[cfg(feature = "async")]
+    use crate::{Compression, TensorBuffersWrite, TensorBuffersWriter, WriterOptions};
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn test_parse_slice() {
+        let values = (0..256).map(|i| (i % 8) as f32).collect::<Vec<_>>();
+        let tensors = vec![
+            Tensor::new("w", values.as_slice(), vec![16, 16]),
+            Tensor::new("b", &[1.0f32, 2.0], vec![2]),
+        ];
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        let options = WriterOptions::new().with_compression(Compression::Lz4, 0);
+        TensorBuffersWriter::with_options(&mut bytes, options)
+            .write(tensors, vec![])
+            .await
+            .unwrap();
+        let bytes = bytes.into_inner();
+
+        let file = parse_slice(&bytes).unwrap();
+        assert_eq!(file.version(), crate::constants::VERSION);
+        let mut names = file.tensor_names().collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, ["b", "w"]);
+        let info = file.tensor_info("w").unwrap();
+        assert_eq!((info.shape.as_slice(), info.compression), (&[16, 16][..], Compression::Lz4));
+        let range = file.tensor_range("w").unwrap();
+        assert_eq!(range.len(), info.stored_size as usize);
+        assert_eq!(file.tensor_bytes("w").unwrap(), &bytes[range]);
+        assert_eq!(file.tensor::<f32>("w").unwrap().data(), values);
+        assert_eq!(file.tensor::<f32>("b").unwrap().data(), [1.0, 2.0]);
+        assert!(file.tensor::<i32>("b").is_err());
+        assert_eq!(
+            file.tensor_range("missing").unwrap_err().to_string(),
+            "Tensor missing not found"
+        );
+
+        assert_eq!(parse_slice(&bytes[1..]).unwrap_err().to_string(), "Invalid magic bytes");
+        let mut corrupted = bytes.clone();
+        let metadata = locate_metadata(&bytes).unwrap().1;
+        corrupted[metadata.start + 8] ^= 1;
+        assert_eq!(parse_slice(&corrupted).unwrap_err().to_string(), "Metadata checksum mismatch");
+    }
+
+    #[test]
+    fn test_check_data_layout() {
+        fn check(shape: &[u32], data_size: u32, file_size: u64) -> Result<()> {
+            let mut builder = FlatBufferBuilder::new();
+            let name = builder.create_string("w");
+            let shape = builder.create_vector(shape);
+            let args = TensorMetadataArgs {
+                name: Some(name),
+                shape: Some(shape),
+                data_type: DataType::Float32,
+                data_offset: 4,
+                data_size,
+                ..Default::default()
+            };
+            let metadata = TensorMetadata::create(&mut builder, &args);
+            builder.finish(metadata, None);
+            let metadata = flatbuffers::root::<TensorMetadata>(builder.finished_data()).unwrap();
+            check_data_layout(&metadata, file_size)
+        }
+
+        assert!(check(&[2, 3], 24, 28).is_ok());
+        let error = check(&[2, 3], 20, 28).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Tensor w has a data size of 20 bytes, but shape [2, 3] of Float32 needs 24"
+        );
+        let error = check(&[2, 3], 24, 27).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Tensor w data range [4, 28) is out of bounds for a file of 27 bytes"
+        );
+        let huge = [u32::MAX; 3];
+        assert!(check(&huge, 24, 28).unwrap_err().to_string().ends_with("needs more than 2^64"));
+    }
+}
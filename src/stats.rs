@@ -0,0 +1,127 @@
+#[cfg(feature = "async")]
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// What a [`crate::TensorBuffers`] has read since it was opened, see `TensorBuffers::stats`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReadStats {
+    /// Bytes read from the file and the files its tensors' data is stored in.
+    pub bytes_read: u64,
+    /// HTTP range requests sent to remote files, including retries.
+    pub range_requests: u64,
+    /// HTTP range requests retried after a transient failure.
+    pub retries: u64,
+    /// HTTP range requests sent to a mirror after the origin before it failed.
+    pub failovers: u64,
+    /// Tensor loads served from prefetched data.
+    pub cache_hits: u64,
+    /// Tensor loads that read their data from the file.
+    pub cache_misses: u64,
+}
+
+/// What a [`crate::TensorBuffersWriter`] has written since it was created, see
+/// `TensorBuffersWriter::stats`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WriteStats {
+    /// Bytes written, including metadata, padding and footers.
+    pub bytes_written: u64,
+    /// Tensors whose data was written.
+    pub tensors_written: u64,
+    /// Completed calls to `write`.
+    pub writes: u64,
+}
+
+/// Counts the reads of one opened file, shared by its handles and the files its tensors refer to.
+#[cfg(feature = "async")]
+#[derive(Debug, Default)]
+pub(crate) struct ReadCounters {
+    bytes_read: AtomicU64,
+    range_requests: AtomicU64,
+    retries: AtomicU64,
+    failovers: AtomicU64,
+    cache_hits: AtomicU64,
+    cache_misses: AtomicU64,
+}
+
+#[cfg(feature = "async")]
+impl ReadCounters {
+    pub fn record_bytes_read(&self, bytes: usize) {
+        self.bytes_read.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_range_request(&self) {
+        self.range_requests.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_retry(&self) {
+        self.retries.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_failover(&self) {
+        self.failovers.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_cache_lookup(&self, hit: bool) {
+        let counter = if hit { &self.cache_hits } else { &self.cache_misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns the counts so far.
+    pub fn snapshot(&self) -> ReadStats {
+        ReadStats {
+            bytes_read: self.bytes_read.load(Ordering::Relaxed),
+            range_requests: self.range_requests.load(Ordering::Relaxed),
+            retries: self.retries.load(Ordering::Relaxed),
+            failovers: self.failovers.load(Ordering::Relaxed),
+            cache_hits: self.cache_hits.load(Ordering::Relaxed),
+            cache_misses: self.cache_misses.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "async"))]
+mod tests {
+    use std::time::Duration;
+
+    use tempfile::NamedTempFile;
+    use tokio::fs::File;
+
+    use super::*;
+    use crate::{PrefetchPriority, Tensor, TensorBuffers, TensorBuffersWrite, TensorBuffersWriter};
+
+    #[tokio::test]
+    async fn test_stats() {
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        let mut writer = TensorBuffersWriter::new(&mut file);
+        let a = vec![1.0f32; 256];
+        let b = vec![2.0f32; 64];
+        let tensors = vec![Tensor::new("a", &a, vec![256]), Tensor::new("b", &b, vec![64])];
+        writer.write(tensors, vec![]).await.unwrap();
+        let stats = writer.stats();
+        assert_eq!((stats.tensors_written, stats.writes), (2, 1));
+        assert_eq!(stats.bytes_written, std::fs::metadata(tmp.path()).unwrap().len());
+
+        let url = format!("file://{}", tmp.path().display());
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        assert_eq!(tensor_buffers.stats(), ReadStats::default());
+        tensor_buffers.get_tensor_data_by_name::<f32>("a").await.unwrap();
+        let stats = tensor_buffers.stats();
+        assert!(stats.bytes_read >= 1024);
+        assert_eq!((stats.cache_hits, stats.cache_misses), (0, 1));
+        assert_eq!((stats.range_requests, stats.retries), (0, 0));
+
+        tensor_buffers.prefetch(&["b"], PrefetchPriority::HotPath).await.unwrap();
+        let wait = async {
+            while tensor_buffers.prefetched_bytes() < 256 {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(5), wait).await.unwrap();
+        tensor_buffers.get_tensor_data_by_name::<f32>("b").await.unwrap();
+        let after = tensor_buffers.stats();
+        assert_eq!((after.cache_hits, after.cache_misses), (1, 1));
+        assert_eq!(after.bytes_read, stats.bytes_read + 256);
+    }
+}
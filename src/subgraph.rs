@@ -0,0 +1,196 @@
+use bytemuck::Pod;
+use fnv::FnvHashSet;
+use tokio::io::{AsyncSeek, AsyncWrite};
+
+use crate::{
+    executor::resolve_outputs, num_trait::Num, Operation, Result, Tensor, TensorBuffers,
+    TensorBuffersWrite, TensorBuffersWriter, TensorGraph, TensorId, TensorOperation,
+    TensorOperationId,
+};
+
+impl TensorGraph {
+    /// Extracts the operations needed to compute `outputs` from `inputs`.
+    ///
+    /// Traversal stops at the input operations, which become sources (`Operation::None`) reading
+    /// their output tensor, so the extracted graph can be fed those tensors directly. Sources
+    /// reached without passing through an input, such as weights, are kept.
+    ///
+    /// # Returns
+    /// Returns an error if an id is unknown or an input does not lead to any output.
+    pub fn extract_subgraph(
+        &self,
+        inputs: &[TensorOperationId],
+        outputs: &[TensorOperationId],
+    ) -> Result<TensorGraph> {
+        let boundary: FnvHashSet<TensorOperationId> = inputs.iter().copied().collect();
+        let mut visited = FnvHashSet::default();
+        let mut stack = outputs.to_vec();
+        while let Some(id) = stack.pop() {
+            if !visited.insert(id) {
+                continue;
+            }
+            let op = self.get_operation(id).ok_or_else(|| format!("Unknown operation {}", id))?;
+            if !boundary.contains(&id) {
+                stack.extend_from_slice(op.input_operations());
+            }
+        }
+        if let Some(id) = inputs.iter().find(|id| !visited.contains(id)) {
+            return Err(format!("Operation {} is not on a path to the outputs", id).into());
+        }
+
+        let operations = self
+            .operations()
+            .iter()
+            .filter(|op| visited.contains(&op.id()))
+            .map(|op| {
+                if boundary.contains(&op.id()) {
+                    op.rewritten(Operation::None, vec![], *op.output())
+                } else {
+                    op.clone()
+                }
+            })
+            .collect();
+        Ok(TensorGraph::new(operations))
+    }
+}
+
+impl TensorBuffers {
+    /// Extracts the part of the stored graph between the named tensors, for example to split an
+    /// encoder from a decoder. See [`TensorGraph::extract_subgraph`].
+    ///
+    /// # Arguments
+    /// * `input_names` - Names of the tensors the subgraph starts from.
+    /// * `output_names` - Names of the tensors the subgraph computes.
+    pub async fn extract_subgraph(
+        &self,
+        input_names: &[&str],
+        output_names: &[&str],
+    ) -> Result<TensorGraph> {
+        let graph = self.graph().await?;
+        let ids = |names| -> Result<Vec<TensorOperationId>> {
+            Ok(resolve_outputs(&graph, names)?.into_iter().map(|(_, id)| id).collect())
+        };
+        graph.extract_subgraph(&ids(input_names)?, &ids(output_names)?)
+    }
+
+    /// Writes `graph` as a new TensorBuffers file, copying every tensor its sources read from
+    /// this file. Sources whose tensors are not stored here are left as runtime inputs.
+    pub async fn write_graph<T, W>(
+        &self,
+        graph: &TensorGraph,
+        writer: &mut TensorBuffersWriter<W>,
+    ) -> Result<()>
+    where
+        T: Num + Pod,
+        W: AsyncWrite + AsyncSeek + Unpin + Send,
+    {
+        self.write_operations::<T, W>(Vec::new(), graph.operations().to_vec(), writer).await
+    }
+
+    /// Writes `operations` with `tensors`, adding the stored tensors read by the operations'
+    /// sources that `tensors` does not already provide.
+    pub(crate) async fn write_operations<T, W>(
+        &self,
+        mut tensors: Vec<Tensor<'_, T>>,
+        operations: Vec<TensorOperation>,
+        writer: &mut TensorBuffersWriter<W>,
+    ) -> Result<()>
+    where
+        T: Num + Pod,
+        W: AsyncWrite + AsyncSeek + Unpin + Send,
+    {
+        let mut written: FnvHashSet<TensorId> = tensors.iter().map(|tensor| tensor.id()).collect();
+        for op in &operations {
+            let output = *op.output();
+            if *op.operation() != Operation::None || written.contains(&output) {
+                continue;
+            }
+            // Runtime inputs have no stored data to copy.
+            if self.stored_metadata(output).await.is_err() {
+                continue;
+            }
+            tensors.push(self.get_tensor_data_by_id::<T>(output).await?);
+            written.insert(output);
+        }
+        writer.write(tensors, operations).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::NamedTempFile;
+    use tokio::fs::File;
+
+    use super::*;
+    use crate::{utils::hash_key, Executor};
+
+    fn ids(graph: &TensorGraph) -> Vec<TensorOperationId> {
+        graph.operations().iter().map(|op| op.id()).collect()
+    }
+
+    #[test]
+    fn test_extract_subgraph() {
+        let graph = TensorGraph::new(vec![
+            TensorOperation::new(1, Operation::None, vec![], 10),
+            TensorOperation::new(2, Operation::None, vec![], 20),
+            TensorOperation::new(3, Operation::MatMul, vec![1, 2], 30),
+            TensorOperation::new(4, Operation::ReLU, vec![3], 40).with_name("encoded"),
+            TensorOperation::new(5, Operation::None, vec![], 50),
+            TensorOperation::new(6, Operation::MatMul, vec![4, 5], 60),
+        ]);
+        let decoder = graph.extract_subgraph(&[4], &[6]).unwrap();
+        assert_eq!(ids(&decoder), vec![4, 5, 6]);
+        let input = decoder.get_operation(4).unwrap();
+        assert_eq!(*input.operation(), Operation::None);
+        assert!(input.input_operations().is_empty());
+        assert_eq!(*input.output(), 40);
+        assert_eq!(input.name(), Some("encoded"));
+
+        let encoder = graph.extract_subgraph(&[1], &[4]).unwrap();
+        assert_eq!(ids(&encoder), vec![1, 2, 3, 4]);
+
+        assert!(graph.extract_subgraph(&[5], &[4]).is_err());
+        assert!(graph.extract_subgraph(&[], &[7]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_write_extracted_subgraph() {
+        let x = Tensor::new("x", &[1.0f32, -2.0], vec![1, 2]);
+        let w1 = Tensor::new("w1", &[1.0f32, 0.0, 0.0, 1.0], vec![2, 2]);
+        let w2 = Tensor::new("w2", &[2.0f32, 3.0], vec![2, 1]);
+        let operations = vec![
+            TensorOperation::new(1, Operation::None, vec![], x.id()),
+            TensorOperation::new(2, Operation::None, vec![], w1.id()),
+            TensorOperation::new(3, Operation::MatMul, vec![1, 2], hash_key("h")),
+            TensorOperation::new(4, Operation::ReLU, vec![3], hash_key("encoded")),
+            TensorOperation::new(5, Operation::None, vec![], w2.id()),
+            TensorOperation::new(6, Operation::MatMul, vec![4, 5], hash_key("y")),
+        ];
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        TensorBuffersWriter::new(&mut file).write(vec![x, w1, w2], operations).await.unwrap();
+
+        let url = format!("file://{}", tmp.path().display());
+        let source = TensorBuffers::open(&url).await.unwrap();
+        let encoder = source.extract_subgraph(&["x"], &["encoded"]).await.unwrap();
+        assert_eq!(ids(&encoder), vec![1, 2, 3, 4]);
+        let decoder = source.extract_subgraph(&["encoded"], &["y"]).await.unwrap();
+        assert_eq!(ids(&decoder), vec![4, 5, 6]);
+
+        let encoder_tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(encoder_tmp.path()).await.unwrap();
+        source
+            .write_graph::<f32, _>(&encoder, &mut TensorBuffersWriter::new(&mut file))
+            .await
+            .unwrap();
+
+        let url = format!("file://{}", encoder_tmp.path().display());
+        let extracted = TensorBuffers::open(&url).await.unwrap();
+        assert!(extracted.stored_metadata(hash_key("w2")).await.is_err());
+        let expected = Executor::new(&source).run::<f32>(&["encoded"]).await.unwrap();
+        let actual = Executor::new(&extracted).run::<f32>(&["encoded"]).await.unwrap();
+        assert_eq!(actual["encoded"], expected["encoded"]);
+        assert_eq!(actual["encoded"].data(), &[1.0, 0.0]);
+    }
+}
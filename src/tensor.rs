@@ -1,43 +1,197 @@
-use std::fmt::Debug;
+use std::{
+    any::type_name,
+    borrow::Cow,
+    fmt::{self, Debug},
+    ops::Deref,
+};
+#[cfg(feature = "async")]
+use std::{marker::PhantomData, mem::size_of, slice};
 
-use bytemuck::{cast_slice, Pod};
+use bytemuck::{cast_slice, try_cast_slice, Pod};
+use bytes::Bytes;
+#[cfg(feature = "async")]
 use flatbuffers::{FlatBufferBuilder, WIPOffset};
 
+#[cfg(feature = "async")]
+use crate::{
+    aligned_vec::AlignedVec,
+    codec::StoredData,
+    generated::tensor_buffers::{
+        Compression, ExtensionType as ExtensionTable, ExtensionTypeArgs, ExternalData,
+        ExternalDataArgs, TensorMetadataArgs,
+    },
+    quantization::QuantizationParams,
+};
 use crate::{
-    generated::tensor_buffers::{TensorMetadata, TensorMetadataArgs},
-    num_trait::{DataType, Num},
+    block_quantization,
+    extension_type::{data_type_of, registered_extension},
+    generated::tensor_buffers::TensorMetadata,
+    num_trait::{CastFrom, DataType, Num},
     utils::hash_key,
-    Result, TensorId,
+    ExtensionType, Result, TensorId,
 };
 
+/// A named tensor with its shape and data.
+///
+/// Tensors built with `Tensor::new` borrow their name and data, which avoids copies when
+/// writing. Tensors read from a file are `Tensor<'static, T>` and hold their data in reference
+/// counted `Bytes`, so clones share it across threads without copying. Their data starts on the
+/// boundary set by `OpenOptions::with_buffer_alignment`, 64 bytes by default.
+///
+/// A tensor with an empty shape is a scalar and holds exactly one element; a tensor with a zero
+/// dimension holds no elements. Both are written and read like any other tensor.
+///
+/// With the `serde` feature, tensors serialize their id, name, data type, shape and data.
+/// Deserialization derives the id from the name and checks the data type and element count.
 #[derive(Debug, Clone)]
-pub struct Tensor<'a, T> {
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(
+        try_from = "SerializedTensor<T>",
+        bound(deserialize = "T: Num + Debug + serde::Deserialize<'de>")
+    )
+)]
+pub struct Tensor<'a, T>
+where
+    T: Clone,
+{
     id: TensorId,
-    name: &'a str,
-    data: &'a [T],
+    name: Cow<'a, str>,
+    data: TensorData<'a, T>,
     data_type: DataType,
     shape: Vec<usize>,
 }
 
+/// The data of a tensor, wherever it lives.
+#[derive(Clone)]
+enum TensorData<'a, T> {
+    Borrowed(&'a [T]),
+    Owned(Vec<T>),
+    /// Data loaded from a file, which clones share instead of copying.
+    #[cfg(feature = "async")]
+    Shared(SharedData<T>),
+}
+
+/// Reference-counted elements of type `T`, aligned for SIMD kernels and GPU uploads.
+#[cfg(feature = "async")]
+struct SharedData<T> {
+    /// Always holds the bytes of an `AlignedVec<T>`, so they are valid, aligned elements.
+    bytes: Bytes,
+    _type: PhantomData<T>,
+}
+
+#[cfg(feature = "async")]
+impl<T: Pod + Send> SharedData<T> {
+    fn new(data: AlignedVec<T>) -> Self {
+        SharedData { bytes: Bytes::from_owner(data), _type: PhantomData }
+    }
+}
+
+#[cfg(feature = "async")]
+impl<T> SharedData<T> {
+    fn as_slice(&self) -> &[T] {
+        let len = self.bytes.len() / size_of::<T>().max(1);
+        // SAFETY: the bytes came from an `AlignedVec<T>` and are never mutated.
+        unsafe { slice::from_raw_parts(self.bytes.as_ptr() as *const T, len) }
+    }
+}
+
+#[cfg(feature = "async")]
+impl<T> Clone for SharedData<T> {
+    fn clone(&self) -> Self {
+        SharedData { bytes: self.bytes.clone(), _type: PhantomData }
+    }
+}
+
+impl<T> TensorData<'_, T>
+where
+    T: Clone,
+{
+    fn into_owned(self) -> TensorData<'static, T> {
+        match self {
+            TensorData::Borrowed(data) => TensorData::Owned(data.to_vec()),
+            TensorData::Owned(data) => TensorData::Owned(data),
+            #[cfg(feature = "async")]
+            TensorData::Shared(data) => TensorData::Shared(data),
+        }
+    }
+
+    fn into_vec(self) -> Vec<T> {
+        match self {
+            TensorData::Owned(data) => data,
+            data => data.to_vec(),
+        }
+    }
+}
+
+impl<T> Deref for TensorData<'_, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        match self {
+            TensorData::Borrowed(data) => data,
+            TensorData::Owned(data) => data,
+            #[cfg(feature = "async")]
+            TensorData::Shared(data) => data.as_slice(),
+        }
+    }
+}
+
+impl<T: Debug> Debug for TensorData<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.deref().fmt(f)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T: serde::Serialize> serde::Serialize for TensorData<'_, T> {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        self.deref().serialize(serializer)
+    }
+}
+
 impl<'a, T> Tensor<'a, T>
 where
     T: Num + Debug,
 {
+    /// Creates a tensor that borrows its name and data. The data isn't checked against the
+    /// shape until the tensor is written.
     pub fn new(name: &'a str, data: &'a [T], shape: Vec<usize>) -> Self {
         let data_type = T::data_type();
-        Tensor { id: hash_key(name), name, data, data_type, shape }
+        Tensor {
+            id: hash_key(name),
+            name: Cow::Borrowed(name),
+            data: TensorData::Borrowed(data),
+            data_type,
+            shape,
+        }
+    }
+
+    /// Creates a tensor that owns its name and data.
+    pub fn from_vec(name: &str, data: Vec<T>, shape: Vec<usize>) -> Tensor<'static, T> {
+        Tensor {
+            id: hash_key(name),
+            name: Cow::Owned(name.to_string()),
+            data: TensorData::Owned(data),
+            data_type: T::data_type(),
+            shape,
+        }
     }
 
     pub fn id(&self) -> TensorId {
         self.id
     }
 
-    pub fn name(&self) -> &'a str {
-        self.name
+    pub fn name(&self) -> &str {
+        &self.name
     }
 
     pub fn data(&self) -> &[T] {
-        self.data
+        &self.data
     }
 
     pub fn shape(&self) -> &[usize] {
@@ -47,57 +201,405 @@ where
     pub fn data_type(&self) -> DataType {
         self.data_type
     }
+
+    /// Returns the number of dimensions, 0 for a scalar.
+    pub fn rank(&self) -> usize {
+        self.shape.len()
+    }
+
+    /// Returns whether the tensor is a scalar, i.e. has an empty shape.
+    pub fn is_scalar(&self) -> bool {
+        self.shape.is_empty()
+    }
+
+    /// Returns the number of elements the shape holds: 1 for a scalar, 0 if any dimension is 0.
+    pub fn elements(&self) -> usize {
+        self.shape.iter().product()
+    }
+
+    /// Copies borrowed name and data so the tensor no longer depends on their lifetime.
+    pub fn into_owned(self) -> Tensor<'static, T> {
+        Tensor {
+            id: self.id,
+            name: Cow::Owned(self.name.into_owned()),
+            data: self.data.into_owned(),
+            data_type: self.data_type,
+            shape: self.shape,
+        }
+    }
+
+    /// Returns the data, copying it unless the tensor was built from a `Vec`.
+    pub fn into_data(self) -> Vec<T> {
+        self.data.into_vec()
+    }
+
+    /// Converts every element to `U`, returning a new owned tensor with the same name and shape.
+    ///
+    /// Conversions follow the rules of [`CastFrom`].
+    pub fn astype<U>(&self) -> Tensor<'static, U>
+    where
+        U: Num + Debug + CastFrom<T>,
+    {
+        let data = self.data.iter().map(|&value| U::cast_from(value)).collect();
+        Tensor::from_vec(&self.name, data, self.shape.clone())
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct SerializedTensor<T> {
+    name: String,
+    data_type: DataType,
+    shape: Vec<usize>,
+    data: Vec<T>,
+}
+
+#[cfg(feature = "serde")]
+impl<T> TryFrom<SerializedTensor<T>> for Tensor<'_, T>
+where
+    T: Num + Debug,
+{
+    type Error = String;
+
+    fn try_from(tensor: SerializedTensor<T>) -> std::result::Result<Self, Self::Error> {
+        if tensor.data_type != T::data_type() {
+            return Err(format!(
+                "Data type mismatch for tensor '{}': expected {:?}, found {:?}",
+                tensor.name,
+                T::data_type(),
+                tensor.data_type
+            ));
+        }
+        let elements = tensor.shape.iter().product::<usize>();
+        if elements != tensor.data.len() {
+            return Err(format!(
+                "Shape {:?} of tensor '{}' expects {} elements, found {}",
+                tensor.shape,
+                tensor.name,
+                elements,
+                tensor.data.len()
+            ));
+        }
+        Ok(Tensor {
+            id: hash_key(&tensor.name),
+            name: Cow::Owned(tensor.name),
+            data: TensorData::Owned(tensor.data),
+            data_type: T::data_type(),
+            shape: tensor.shape,
+        })
+    }
 }
 
 impl<'a, T> Tensor<'a, T>
 where
     T: Pod + Num + Debug,
 {
-    pub fn new_with_metadata_and_data(
-        metadata: TensorMetadata<'a>,
-        bytes: Vec<u8>,
+    /// Creates an owned tensor from stored metadata and the data it describes.
+    pub(crate) fn new_with_metadata_and_data(
+        metadata: TensorMetadata<'_>,
+        data: Vec<T>,
+    ) -> Result<Tensor<'static, T>> {
+        Self::with_metadata(metadata, TensorData::Owned(data))
+    }
+
+    /// Returns the data reinterpreted as elements of `U`, e.g. bytes, without copying.
+    ///
+    /// # Returns
+    /// An error if the data isn't aligned for `U` or its size isn't a multiple of `U`'s.
+    pub fn as_slice<U: Pod>(&self) -> Result<&[U]> {
+        try_cast_slice(self.data()).map_err(|error| {
+            format!("Can't view tensor {} as {}: {}", self.name, type_name::<U>(), error).into()
+        })
+    }
+
+    /// Returns the data as `Bytes`. Tensors read from a file share their buffer, so this is
+    /// cheap; other tensors copy their data.
+    pub fn to_bytes(&self) -> Bytes {
+        #[cfg(feature = "async")]
+        if let TensorData::Shared(data) = &self.data {
+            return data.bytes.clone();
+        }
+        Bytes::copy_from_slice(cast_slice(&self.data))
+    }
+
+    /// Creates a tensor from stored metadata and data loaded into an aligned buffer.
+    #[cfg(feature = "async")]
+    pub(crate) fn from_aligned(
+        metadata: TensorMetadata<'_>,
+        data: AlignedVec<T>,
+    ) -> Result<Tensor<'static, T>> {
+        Self::with_metadata(metadata, TensorData::Shared(SharedData::new(data)))
+    }
+
+    fn with_metadata(
+        metadata: TensorMetadata<'_>,
+        data: TensorData<'static, T>,
+    ) -> Result<Tensor<'static, T>> {
+        let shape = stored_shape(&metadata)?;
+        // Extension and block-quantized tensors keep their type, and hold several bytes per
+        // element or block.
+        let data_type = match data_type_of(&metadata)? {
+            data_type if data_type.is_raw() => data_type,
+            _ => T::data_type(),
+        };
+        let elements = size_of_val(&*data) / data_type.size() * data_type.block_size();
+        if shape.iter().product::<usize>() != elements {
+            return Err(format!(
+                "Tensor shape {:?} does not match its {} elements",
+                shape, elements
+            )
+            .into());
+        }
+        Ok(Tensor {
+            id: metadata.id(),
+            name: Cow::Owned(metadata.name().to_string()),
+            data,
+            data_type,
+            shape,
+        })
+    }
+
+    /// Returns the number of elements in the data, which differs from its length for extension
+    /// and block-quantized tensors.
+    pub(crate) fn data_elements(&self) -> usize {
+        size_of_val(self.data()) / self.data_type.size() * self.data_type.block_size()
+    }
+}
+
+impl Tensor<'static, u8> {
+    /// Creates a tensor of an experimental element type from the bytes of its elements, to be
+    /// written like any other tensor. The shape counts elements, not bytes.
+    ///
+    /// # Returns
+    /// An error if the type's elements are empty or the data isn't a whole number of elements.
+    pub fn from_extension(
+        name: &str,
+        extension: ExtensionType,
+        data: Vec<u8>,
+        shape: Vec<usize>,
+    ) -> Result<Self> {
+        let size = extension.element_size();
+        if size == 0 || data.len() % size != 0 {
+            return Err(format!(
+                "Tensor {} has {} bytes, not a whole number of {}-byte {} elements",
+                name,
+                data.len(),
+                size,
+                extension.id()
+            )
+            .into());
+        }
+        Ok(Tensor {
+            id: hash_key(name),
+            name: Cow::Owned(name.to_string()),
+            data: TensorData::Owned(data),
+            data_type: DataType::Extension(extension),
+            shape,
+        })
+    }
+
+    /// Creates a tensor of a block-quantized type, e.g. `DataType::Q8_0`, from the raw bytes of
+    /// its blocks as llama.cpp lays them out, so GGUF tensors can be stored without converting
+    /// them. The shape counts elements, not blocks.
+    ///
+    /// # Returns
+    /// An error if the type isn't block-quantized, the last dimension isn't a whole number of
+    /// blocks, or the data doesn't hold every block.
+    pub fn from_blocks(
+        name: &str,
+        data_type: DataType,
+        data: Vec<u8>,
+        shape: Vec<usize>,
     ) -> Result<Self> {
-        let owned_buf: Box<[u8]> = bytes.to_vec().into_boxed_slice();
-        let leaked_buf: &'a [u8] = Box::leak(owned_buf);
-        let data = cast_slice::<u8, T>(leaked_buf);
-        let id = metadata.id();
-        let name = metadata.name();
-        let shape = metadata
-            .shape()
-            .ok_or("Failed to get tensor shape from metadata")?
-            .iter()
-            .map(|dim| dim as usize)
-            .collect::<Vec<_>>();
-        Ok(Tensor { id, name, data: &data, data_type: T::data_type(), shape })
-    }
-
-    pub fn build_table(
-        builder: &mut FlatBufferBuilder<'a>,
-        tensor: &Tensor<'a, T>,
-        data_offset: usize,
-    ) -> WIPOffset<TensorMetadata<'a>> {
-        // Convert shape to u32 for FlatBuffers.
-        let shape = tensor.shape().iter().map(|&dim| dim as u32).collect::<Vec<u32>>();
-        let shape_offset = builder.create_vector::<u32>(&shape);
-        let data_type = tensor.data_type();
-        let data_bytes = cast_slice::<T, u8>(tensor.data());
-        let name = builder.create_string(tensor.name());
-
-        // Create FlatBuffers metadata for this tensor.
-        TensorMetadata::create(builder, &TensorMetadataArgs {
-            id: tensor.id(),
-            name: Some(name),
-            data_type: data_type.into(),
-            data_offset: data_offset as u32,
-            data_size: data_bytes.len() as u32,
-            shape: Some(shape_offset),
-            ..Default::default()
+        if !data_type.is_block_quantized() {
+            return Err(format!("{:?} is not a block-quantized data type", data_type).into());
+        }
+        let block_size = data_type.block_size();
+        // Blocks don't span rows, so each row is a whole number of them.
+        let row = shape.last().copied().unwrap_or(1);
+        if row % block_size != 0 {
+            return Err(format!(
+                "Tensor {} has rows of {} elements, not a whole number of {}-element {:?} blocks",
+                name, row, block_size, data_type
+            )
+            .into());
+        }
+        let elements = shape.iter().product::<usize>();
+        let expected = elements / block_size * data_type.size();
+        if data.len() != expected {
+            return Err(format!(
+                "Tensor {} has {} bytes, but shape {:?} of {:?} needs {}",
+                name,
+                data.len(),
+                shape,
+                data_type,
+                expected
+            )
+            .into());
+        }
+        Ok(Tensor {
+            id: hash_key(name),
+            name: Cow::Owned(name.to_string()),
+            data: TensorData::Owned(data),
+            data_type,
+            shape,
         })
     }
 }
 
+impl Tensor<'_, u8> {
+    /// Converts a tensor of an extension type to f32 with the decoder registered for the type.
+    ///
+    /// # Returns
+    /// An error if the tensor isn't of an extension type, no decoder is registered for it, or
+    /// decoding fails.
+    pub fn decode_extension(&self) -> Result<Tensor<'static, f32>> {
+        let DataType::Extension(extension) = self.data_type else {
+            return Err(format!("Tensor {} is not of an extension type", self.name).into());
+        };
+        let decoder = registered_extension(extension)?;
+        let mut data = vec![0.0; self.data_elements()];
+        decoder.decode(self.data(), &mut data)?;
+        Ok(Tensor::from_vec(&self.name, data, self.shape.clone()))
+    }
+
+    /// Converts a tensor of a block-quantized type to f32 the way llama.cpp does.
+    ///
+    /// # Returns
+    /// An error if the tensor isn't of a block-quantized type.
+    pub fn dequantize_blocks(&self) -> Result<Tensor<'static, f32>> {
+        let mut data = vec![0.0; self.data_elements()];
+        block_quantization::dequantize_blocks(self.data_type, self.data(), &mut data)
+            .map_err(|error| format!("Tensor {}: {}", self.name, error))?;
+        Ok(Tensor::from_vec(&self.name, data, self.shape.clone()))
+    }
+}
+
+/// Returns a tensor's shape from its metadata, which holds the dimensions as u32s or, if one
+/// exceeds that, as u64s.
+///
+/// # Returns
+/// An error if the metadata has no shape or a dimension doesn't fit in a `usize`.
+pub(crate) fn stored_shape(metadata: &TensorMetadata) -> Result<Vec<usize>> {
+    let shape = match (metadata.shape64(), metadata.shape()) {
+        (Some(shape), _) => shape.iter().collect::<Vec<_>>(),
+        (None, Some(shape)) => shape.iter().map(u64::from).collect(),
+        (None, None) => return Err(format!("Tensor {} has no shape", metadata.name()).into()),
+    };
+    shape
+        .into_iter()
+        .map(|dim| {
+            usize::try_from(dim).map_err(|_| {
+                format!(
+                    "Tensor {} has a dimension of {}, too large for this platform",
+                    metadata.name(),
+                    dim
+                )
+                .into()
+            })
+        })
+        .collect()
+}
+
+/// Returns whether a shape has a dimension beyond u32, so it must be stored in `shape64`.
+#[cfg(feature = "async")]
+pub(crate) fn is_wide_shape(shape: &[usize]) -> bool {
+    shape.iter().any(|&dim| u32::try_from(dim).is_err())
+}
+
+/// Builds the metadata table of a tensor from its parts, for writers that no longer hold the
+/// tensor's data.
+///
+/// # Arguments
+/// * `data_size` - The size of the tensor's data in bytes, before compression.
+/// * `stored` - Where and how the data is stored.
+/// * `external` - The location of the file the data is stored in, if not the file being written.
+/// * `alias_of` - The name of the tensor whose data this alias shares, if it is one.
+/// * `user_metadata` - Application-defined metadata of the tensor, if any.
+/// * `quantization` - How the tensor's values were quantized, if they were.
+/// * `scales_offset` - Where the quantization's scales are stored in the file, if not in the
+///   metadata.
+#[cfg(feature = "async")]
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn build_tensor_table<'a>(
+    builder: &mut FlatBufferBuilder<'a>,
+    id: TensorId,
+    name: &str,
+    data_type: DataType,
+    shape: &[usize],
+    data_size: usize,
+    stored: &StoredData,
+    external: Option<&str>,
+    alias_of: Option<&str>,
+    user_metadata: Option<&[u8]>,
+    quantization: Option<&QuantizationParams>,
+    scales_offset: Option<u64>,
+) -> WIPOffset<TensorMetadata<'a>> {
+    // Dimensions are stored as u32s, which older readers understand, unless one doesn't fit.
+    let (shape, shape64) = if is_wide_shape(shape) {
+        (None, Some(builder.create_vector_from_iter(shape.iter().map(|&dim| dim as u64))))
+    } else {
+        (Some(builder.create_vector_from_iter(shape.iter().map(|&dim| dim as u32))), None)
+    };
+    let name = builder.create_string(name);
+    // Data stored in another file is located by the external table, which has room for 64-bit
+    // offsets, and `data_offset` is left at 0.
+    let external = external.map(|location| {
+        let location = builder.create_string(location);
+        ExternalData::create(builder, &ExternalDataArgs {
+            location: Some(location),
+            data_offset: stored.offset,
+        })
+    });
+    let alias_of = alias_of.map(|name| builder.create_string(name));
+    let codec = stored.codec.map(|codec| builder.create_string(codec));
+    let extension = match data_type {
+        DataType::Extension(extension) => {
+            let id = builder.create_string(extension.id());
+            Some(ExtensionTable::create(builder, &ExtensionTypeArgs {
+                id: Some(id),
+                element_size: extension.element_size() as u32,
+            }))
+        }
+        _ => None,
+    };
+    let user_metadata = user_metadata.map(|bytes| builder.create_vector(bytes));
+    let quantization =
+        quantization.map(|quantization| quantization.build_table(builder, scales_offset));
+    let data_offset = match external {
+        Some(_) => 0,
+        None => stored.offset as u32,
+    };
+
+    // Create FlatBuffers metadata for this tensor.
+    TensorMetadata::create(builder, &TensorMetadataArgs {
+        id,
+        name: Some(name),
+        data_type: data_type.into(),
+        data_offset,
+        data_size: data_size as u32,
+        shape,
+        shape64,
+        compression: stored.compression,
+        stored_size: match stored.compression {
+            Compression::None => 0,
+            _ => stored.size as u32,
+        },
+        checksum_algorithm: stored.checksum_algorithm,
+        checksum: stored.checksum,
+        external,
+        alias_of,
+        codec,
+        extension,
+        user_metadata,
+        quantization,
+    })
+}
+
 #[cfg(test)]
 mod tests {
+    use half::f16;
+
     use super::*;
 
     #[test]
@@ -139,12 +641,24 @@ mod tests {
         assert_eq!(tensor.data_type(), DataType::UInt8);
     }
 
+    #[test]
+    fn test_tensor_scalar_and_empty() {
+        let scalar = Tensor::scalar("s", 2.5f32);
+        assert_eq!((scalar.shape(), scalar.data()), (&[][..], &[2.5][..]));
+        assert!(scalar.is_scalar());
+        assert_eq!((scalar.rank(), scalar.elements()), (0, 1));
+
+        let empty = Tensor::<f32>::zeros("e", vec![3, 0]);
+        assert!(!empty.is_scalar() && empty.data().is_empty());
+        assert_eq!((empty.rank(), empty.elements()), (2, 0));
+    }
+
     #[test]
     fn test_tensor_clone() {
         let data: Vec<f64> = vec![1.0, 2.0];
         let shape = vec![2];
         let name = "input_4";
-        let tensor1 = Tensor::new(name, &data, shape.clone());
+        let tensor1 = Tensor::new(name, &data, shape);
         let tensor2 = tensor1.clone();
 
         assert_eq!(tensor1.id(), tensor2.id());
@@ -152,4 +666,60 @@ mod tests {
         assert_eq!(tensor1.shape(), tensor2.shape());
         assert_eq!(tensor1.data_type(), tensor2.data_type());
     }
+
+    #[test]
+    fn test_tensor_owned() {
+        let data: Vec<f32> = vec![1.0, 2.0];
+        let tensor = Tensor::new("borrowed", &data, vec![2]).into_owned();
+        drop(data);
+        assert_eq!(tensor.data(), &[1.0, 2.0]);
+
+        let tensor = Tensor::from_vec("owned", vec![3i64, 4, 5], vec![3]);
+        assert_eq!(tensor.id(), hash_key("owned"));
+        assert_eq!(tensor.name(), "owned");
+        assert_eq!(tensor.data_type(), DataType::Int64);
+        // Owned tensors can move to other threads.
+        let data = std::thread::spawn(move || tensor.into_data()).join().unwrap();
+        assert_eq!(data, vec![3, 4, 5]);
+    }
+
+    #[test]
+    fn test_tensor_astype() {
+        let data = [f16::from_f32(1.5), f16::from_f32(-2.0)];
+        let tensor = Tensor::new("half", &data, vec![2, 1]);
+        let single = tensor.astype::<f32>();
+        assert_eq!(single.id(), tensor.id());
+        assert_eq!(single.shape(), &[2, 1]);
+        assert_eq!(single.data_type(), DataType::Float32);
+        assert_eq!(single.data(), &[1.5, -2.0]);
+        assert_eq!(single.astype::<i8>().data(), &[1, -2]);
+        assert_eq!(single.astype::<f16>().data(), &data);
+    }
+
+    #[test]
+    fn test_tensor_as_slice() {
+        let data = [1u16, 2, 3];
+        let tensor = Tensor::new("x", &data, vec![3]);
+        assert_eq!(tensor.as_slice::<u8>().unwrap(), [1, 0, 2, 0, 3, 0]);
+        let error = tensor.as_slice::<u32>().unwrap_err().to_string();
+        assert!(error.starts_with("Can't view tensor x as u32"));
+        assert_eq!(&tensor.to_bytes()[..], [1, 0, 2, 0, 3, 0]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_tensor_serde() {
+        let data = [1.0f32, 2.0];
+        let tensor = Tensor::new("w", &data, vec![2, 1]);
+        let json = serde_json::to_string(&tensor).unwrap();
+        let decoded: Tensor<f32> = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.id(), tensor.id());
+        assert_eq!(decoded.name(), "w");
+        assert_eq!(decoded.shape(), &[2, 1]);
+        assert_eq!(decoded.data(), &data);
+
+        assert!(serde_json::from_str::<Tensor<f64>>(&json).is_err());
+        let truncated = r#"{"name":"w","data_type":"Float32","shape":[3],"data":[1.0]}"#;
+        assert!(serde_json::from_str::<Tensor<f32>>(truncated).is_err());
+    }
 }
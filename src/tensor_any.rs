@@ -0,0 +1,141 @@
+#[cfg(feature = "async")]
+use std::mem::size_of;
+
+use bytemuck::cast_slice;
+#[cfg(feature = "async")]
+use bytemuck::cast_slice_mut;
+
+#[cfg(feature = "async")]
+use crate::{
+    codec, extension_type::data_type_of, generated::tensor_buffers::TensorMetadata,
+    num_trait::Zero, Result,
+};
+use crate::{f16, num_trait::DataType, Tensor, TensorId};
+
+macro_rules! tensor_any {
+    ($($variant:ident($type:ty)),* $(,)?) => {
+        /// An owned tensor of any supported data type, so tensors of different types can travel
+        /// through one stream, e.g. into a [`TensorBuffersSink`](crate::TensorBuffersSink).
+        #[derive(Debug, Clone)]
+        pub enum TensorAny {
+            $($variant(Tensor<'static, $type>),)*
+        }
+
+        $(
+            impl From<Tensor<'static, $type>> for TensorAny {
+                fn from(tensor: Tensor<'static, $type>) -> Self {
+                    TensorAny::$variant(tensor)
+                }
+            }
+        )*
+
+        impl TensorAny {
+            pub fn id(&self) -> TensorId {
+                match self {
+                    $(TensorAny::$variant(tensor) => tensor.id(),)*
+                }
+            }
+
+            pub fn name(&self) -> &str {
+                match self {
+                    $(TensorAny::$variant(tensor) => tensor.name(),)*
+                }
+            }
+
+            pub fn shape(&self) -> &[usize] {
+                match self {
+                    $(TensorAny::$variant(tensor) => tensor.shape(),)*
+                }
+            }
+
+            pub fn data_type(&self) -> DataType {
+                match self {
+                    $(TensorAny::$variant(tensor) => tensor.data_type(),)*
+                }
+            }
+
+            /// Returns the tensor's data as bytes.
+            pub fn data_bytes(&self) -> &[u8] {
+                match self {
+                    $(TensorAny::$variant(tensor) => cast_slice(tensor.data()),)*
+                }
+            }
+
+            /// Decodes a tensor from its metadata and stored data, checking the data against its
+            /// checksum. `dictionary` is the file's compression dictionary, if it has one.
+            #[cfg(feature = "async")]
+            pub(crate) fn decode(
+                metadata: TensorMetadata<'_>,
+                stored: &[u8],
+                dictionary: Option<&[u8]>,
+            ) -> Result<TensorAny> {
+                codec::verify_checksum(&metadata, stored)?;
+                match data_type_of(&metadata)? {
+                    $(DataType::$variant => {
+                        let elements = metadata.data_size() as usize / size_of::<$type>();
+                        let mut data = vec![<$type>::zero(); elements];
+                        let out = cast_slice_mut(&mut data);
+                        codec::decompress(
+                            stored,
+                            metadata.compression(),
+                            dictionary,
+                            metadata.codec(),
+                            out,
+                        )?;
+                        Ok(Tensor::new_with_metadata_and_data(metadata, data)?.into())
+                    })*
+                    // Extension and block-quantized tensors hold the bytes of their elements.
+                    DataType::Extension(_) | DataType::Q8_0 | DataType::Q4K => {
+                        let mut data = vec![0u8; metadata.data_size() as usize];
+                        codec::decompress(
+                            stored,
+                            metadata.compression(),
+                            dictionary,
+                            metadata.codec(),
+                            &mut data,
+                        )?;
+                        Ok(Tensor::new_with_metadata_and_data(metadata, data)?.into())
+                    }
+                }
+            }
+
+            /// Returns the number of elements in the tensor's data.
+            #[cfg(feature = "async")]
+            pub(crate) fn elements(&self) -> usize {
+                match self {
+                    $(TensorAny::$variant(tensor) => tensor.data_elements(),)*
+                }
+            }
+        }
+    };
+}
+
+tensor_any! {
+    Int8(i8),
+    Int16(i16),
+    Int32(i32),
+    Int64(i64),
+    UInt8(u8),
+    UInt16(u16),
+    UInt32(u32),
+    UInt64(u64),
+    Float16(f16),
+    Float32(f32),
+    Float64(f64),
+}
+
+#[cfg(all(test, feature = "async"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tensor_any() {
+        let tensor = TensorAny::from(Tensor::from_vec("ids", vec![1u16, 2, 3], vec![3]));
+        assert!(matches!(tensor, TensorAny::UInt16(_)));
+        assert_eq!(tensor.id(), crate::utils::hash_key("ids"));
+        assert_eq!((tensor.name(), tensor.shape()), ("ids", &[3][..]));
+        assert_eq!(tensor.data_type(), DataType::UInt16);
+        assert_eq!(tensor.data_bytes(), [1, 0, 2, 0, 3, 0]);
+        assert_eq!(tensor.elements(), 3);
+    }
+}
@@ -1,63 +1,380 @@
-use std::{mem::size_of, sync::Mutex};
+use std::{
+    io,
+    mem::size_of,
+    sync::{Arc, OnceLock},
+    time::Instant,
+};
 
-use bytemuck::Pod;
-use bytes::BytesMut;
+use bytemuck::{cast_slice, cast_slice_mut, Pod};
+use bytes::{Bytes, BytesMut};
 use flatbuffers::{FlatBufferBuilder, WIPOffset};
-use tokio::sync::OnceCell;
+use futures::{stream, Stream, StreamExt, TryStreamExt};
+use tokio::{io::AsyncRead, sync::OnceCell};
+use tracing::{field::Empty, instrument, warn, Span};
 
 use crate::{
-    constants::VERSION,
+    aligned_vec::AlignedVec,
+    bloom_filter::{read_bloom_filter, BloomFilter},
+    chunk_reader::{ChunkReader, READER_CHUNK_SIZE},
+    codec::{self, Checksummer},
+    external_data::{resolve_location, ExternalFiles},
+    footer::{Footer, MAX_FOOTER_SIZE},
+    format_features::{check_features, FormatFeatures},
     generated::tensor_buffers::{
-        OperationMetadata, TensorBuffersMetadata, TensorBuffersMetadataArgs, TensorMetadata,
+        Compression, OperationMetadata, TensorBuffersMetadata, TensorBuffersMetadataArgs,
+        TensorColumns, TensorMetadata,
     },
+    memory_budget::MemoryTracker,
+    metadata_lookup::MetadataLookup,
+    metrics,
+    name_index::NameIndexCache,
     num_trait::Num,
+    observer::TensorBuffersObserver,
+    open_options::OpenOptions,
+    prefetch::{PrefetchPriority, Prefetcher},
+    read_mode::{check_metadata, requires_sorted_ids, tensor_problem, ReadMode},
+    read_plan::{plan_reads, MAX_GAP, MAX_READ_SIZE},
+    reader_pool::ReaderPool,
+    slice_file::{check_data_layout, check_data_type, stored_offset},
+    stats::{ReadCounters, ReadStats},
     tensor_buffers_file::TensorBuffersFile,
-    tensor_buffers_reader::{TensorBuffersRead, TensorBuffersReader},
-    tensor_operation,
-    utils::hash_key,
-    Result, Tensor, TensorId, TensorOperation, TensorOperationId,
+    tensor_buffers_reader::METADATA_TAIL_SIZE,
+    tensor_columns::Columns,
+    timeouts::with_deadline,
+    utils::{elapsed_ms, hash_key, loggable_url, unbound_lifetime},
+    FooterSummary, Provenance, Result, Tensor, TensorAny, TensorConstraint, TensorGraph, TensorId,
+    TensorInfo, TensorOperation, TensorOperationId,
 };
+
+/// Size of the chunks compressed tensor data is read and decompressed in.
+const DECOMPRESS_CHUNK_SIZE: usize = 1 << 20;
+
 /// A struct to represent a collection of tensors stored in a memory-mapped file.
 /// This struct provides methods to read tensor metadata and data from the file.
-pub struct TensorBuffers<'a> {
-    metadata_root: OnceCell<TensorBuffersMetadata<'a>>,
-    reader: Mutex<TensorBuffersReader<TensorBuffersFile>>,
+pub struct TensorBuffers {
+    /// The metadata, read once and shared by every call for as long as the file is open.
+    metadata: OnceCell<Bytes>,
+    readers: Arc<ReaderPool>,
+    options: OpenOptions,
+    warnings: OnceLock<Vec<String>>,
+    memory: MemoryTracker,
+    prefetcher: Prefetcher,
+    name_index: NameIndexCache,
+    pub(crate) lookup: MetadataLookup,
+    bloom_filter: OnceCell<Option<BloomFilter>>,
+    external_files: ExternalFiles,
 }
 
-impl<'a> TensorBuffers<'a> {
+impl TensorBuffers {
     pub async fn open(url: &str) -> Result<Self> {
-        let file = TensorBuffersFile::open(url).await?;
-        let reader = TensorBuffersReader::new(file);
-        Ok(TensorBuffers { metadata_root: OnceCell::new(), reader: Mutex::new(reader) })
+        Self::open_with(url, OpenOptions::new()).await
     }
 
-    async fn get_metadata_root(&self) -> Result<TensorBuffersMetadata<'a>> {
-        if let Some(metadata_root) = self.metadata_root.get() {
-            return Ok(*metadata_root);
+    /// Opens the file at `url` with the given options.
+    ///
+    /// # Arguments
+    /// * `url` - A `file://` or `https://` URL.
+    /// * `options` - The HTTP client, retries, time limits and observer to use. Opening fails if
+    ///   it exceeds `timeouts.open`, and each later `get_tensor_data_*` call fails if it exceeds
+    ///   `timeouts.load`.
+    #[instrument(skip_all, fields(url = loggable_url(url)))]
+    pub async fn open_with(url: &str, options: OpenOptions) -> Result<Self> {
+        if !options.buffer_alignment().is_power_of_two() {
+            return Err(format!(
+                "Buffer alignment {} is not a power of two",
+                options.buffer_alignment()
+            )
+            .into());
         }
-        let metadata_size = self.reader.lock().unwrap().get_metadata_size().await?;
+        if let Some(mirror) = options.mirrors().iter().find(|url| !url.starts_with("https://")) {
+            return Err(format!("Mirror {} is not an https:// URL", loggable_url(mirror)).into());
+        }
+        let options = options.with_origins_of(url);
+        let counters = Arc::new(ReadCounters::default());
+        let open = async { Ok(TensorBuffersFile::open(url, &options, counters).await?) };
+        let file = match with_deadline(options.timeouts().open, "opening file", open).await {
+            Ok(file) => file,
+            Err(error) => {
+                if let Some(observer) = options.observer() {
+                    observer.on_error(error.as_ref());
+                }
+                return Err(error);
+            }
+        };
+        let readers = Arc::new(ReaderPool::new(url, options.clone(), file));
+        Ok(TensorBuffers {
+            metadata: OnceCell::new(),
+            prefetcher: Prefetcher::new(readers.clone(), options.prefetch_budget()),
+            readers,
+            memory: MemoryTracker::new(options.memory_budget()),
+            options,
+            warnings: OnceLock::new(),
+            name_index: NameIndexCache::default(),
+            lookup: MetadataLookup::default(),
+            bloom_filter: OnceCell::new(),
+            external_files: ExternalFiles::default(),
+        })
+    }
 
-        let mut buf = BytesMut::with_capacity(metadata_size);
-        self.reader.lock().unwrap().read_metadata(&mut buf).await?;
+    /// Returns what the file has read since it was opened: bytes, range requests and their
+    /// retries, and loads served from prefetched data or not, counting the files its tensors'
+    /// data is stored in, so applications can log load efficiency and catch regressions.
+    pub fn stats(&self) -> ReadStats {
+        self.readers.counters().snapshot()
+    }
+
+    /// Returns the options the file was opened with.
+    pub(crate) fn options(&self) -> &OpenOptions {
+        &self.options
+    }
+
+    /// Fails if the file was opened for reading its metadata only.
+    pub(crate) fn check_data_reads(&self) -> Result<()> {
+        match self.options.metadata_only() {
+            true => Err("File was opened for metadata only and can't read tensor data".into()),
+            false => Ok(()),
+        }
+    }
+
+    /// Calls `f` with the registered observer, if any.
+    fn observe(&self, f: impl FnOnce(&dyn TensorBuffersObserver)) {
+        if let Some(observer) = self.options.observer() {
+            f(observer.as_ref());
+        }
+    }
+
+    pub(crate) async fn get_metadata_root(&self) -> Result<TensorBuffersMetadata<'_>> {
+        // Concurrent first reads wait for a single load instead of each loading the metadata.
+        let metadata = self
+            .metadata
+            .get_or_try_init(|| self.load_metadata_root())
+            .await
+            .inspect_err(|error| self.observe(|observer| observer.on_error(error.as_ref())))?;
+        // SAFETY: `load_metadata_root` verified the metadata, which doesn't change afterwards.
+        Ok(unsafe { flatbuffers::root_unchecked::<TensorBuffersMetadata>(metadata) })
+    }
+
+    #[instrument(level = "debug", skip_all, fields(bytes = Empty, elapsed_ms = Empty))]
+    async fn load_metadata_root(&self) -> Result<Bytes> {
+        let start = Instant::now();
+        let mut reader = self.readers.get().await?;
+        let file_size = reader.file_size().await?;
+        // A single read of the end of the file covers the footer and the metadata of most files.
+        let tail_size = file_size.min(METADATA_TAIL_SIZE as u64) as usize;
+        let offset = file_size - tail_size as u64;
+        self.observe(|observer| observer.on_fetch_start(offset, tail_size));
+        let fetch_start = Instant::now();
+        reader.read_tail(tail_size).await?;
+        self.observe(|observer| observer.on_fetch_finish(offset, tail_size, fetch_start.elapsed()));
+        let footer = reader.tail_footer(file_size)?;
+        let metadata_size = footer.metadata_size;
+        let missing = reader.metadata_missing(&footer);
+        let offset = file_size - (metadata_size + footer.size()) as u64;
+        if missing > 0 {
+            self.observe(|observer| observer.on_fetch_start(offset, missing));
+        }
+        let fetch_start = Instant::now();
+        let metadata = reader.take_metadata(&footer, file_size).await?;
+        drop(reader);
+        if missing > 0 {
+            self.observe(|observer| {
+                observer.on_fetch_finish(offset, missing, fetch_start.elapsed())
+            });
+        }
 
-        // Clone the buffer into a Box<[u8]> to extend its lifetime
-        let owned_buf: Box<[u8]> = buf.to_vec().into_boxed_slice();
-        // Leak the boxed buffer to get a 'a reference
-        let leaked_buf: &'a [u8] = Box::leak(owned_buf);
-        let metadata_root = flatbuffers::root::<TensorBuffersMetadata>(leaked_buf)
+        let metadata = Bytes::from(metadata);
+        // SAFETY: Once loaded, the file keeps the metadata, unchanged, for as long as its lookup,
+        // which holds the tensor columns borrowed from it.
+        let stored = unsafe { unbound_lifetime(&metadata) };
+        let metadata_root = flatbuffers::root::<TensorBuffersMetadata>(stored)
             .map_err(|_| "Failed to read metadata from mmap")?;
-        self.metadata_root.set(metadata_root).map_err(|_| {
-            "Failed to set metadata root. This should not happen if the metadata is read correctly."
-        })?;
-        Ok(*self.metadata_root.get().unwrap())
+        check_features(&metadata_root)?;
+        let columns = Columns::of(&metadata_root)?;
+        let problems = check_metadata(&metadata_root);
+        if self.options.read_mode() == ReadMode::Strict && !problems.is_empty() {
+            return Err(format!("Invalid metadata: {}", problems.join("; ")).into());
+        }
+        for problem in &problems {
+            warn!(problem = problem.as_str(), "Skipping unreadable metadata");
+        }
+        // The columns borrow the metadata, so they are only kept once nothing can fail the load.
+        self.lookup.set_columns(columns);
+        // Strict mode rejected unsorted ids unless the format version allows them.
+        let checked = requires_sorted_ids(&metadata_root)
+            && (self.options.read_mode() == ReadMode::Strict || problems.is_empty());
+        self.lookup.set_sorted(&metadata_root, checked);
+        let _ = self.warnings.set(problems);
+        let tensors = self.lookup.tensor_count(&metadata_root);
+        self.observe(|observer| {
+            observer.on_metadata_loaded(tensors, metadata_size, start.elapsed())
+        });
+        let span = Span::current();
+        span.record("bytes", metadata_size);
+        span.record("elapsed_ms", elapsed_ms(start));
+        Ok(metadata)
     }
 
-    pub async fn get_tensor_metadata(&self, tensor_id: TensorId) -> Result<TensorMetadata> {
+    /// Returns the file's compression dictionary if the tensor was compressed with it.
+    pub(crate) async fn compression_dictionary(
+        &self,
+        metadata: &TensorMetadata<'_>,
+    ) -> Result<Option<&[u8]>> {
+        if metadata.compression() != Compression::ZstdDictionary {
+            return Ok(None);
+        }
         let metadata_root = self.get_metadata_root().await?;
-        let tensors = metadata_root.tensors().ok_or("No tensors found")?;
+        Ok(metadata_root.compression_dictionary().map(|dictionary| dictionary.bytes()))
+    }
+
+    /// Returns the problems found in the metadata of a file opened in lenient mode, which are
+    /// also logged as warnings. Always empty in strict mode, where they fail the read instead.
+    pub async fn warnings(&self) -> Result<Vec<String>> {
+        self.get_metadata_root().await?;
+        Ok(self.warnings.get().cloned().unwrap_or_default())
+    }
+
+    /// Returns whether the file has a tensor named `tensor_name`. Until the metadata is loaded,
+    /// files with a bloom filter in their footer answer most checks for missing tensors from the
+    /// end of the file alone; other checks look the tensor up as usual.
+    pub async fn contains_tensor(&self, tensor_name: &str) -> Result<bool> {
+        let tensor_id = hash_key(tensor_name);
+        if self.metadata.get().is_none() {
+            let filter = self.bloom_filter.get_or_try_init(|| read_bloom_filter(self)).await?;
+            if filter.as_ref().is_some_and(|filter| !filter.may_contain(tensor_id)) {
+                return Ok(false);
+            }
+        }
+        Ok(self.find_tensor_metadata(tensor_id).await?.is_some())
+    }
+
+    /// Returns the format features the file needs, such as `compression`, in name order. Opening
+    /// succeeds only if this reader supports all of them.
+    pub async fn features(&self) -> Result<Vec<&str>> {
+        let metadata_root = self.get_metadata_root().await?;
+        Ok(metadata_root.features().into_iter().flatten().collect())
+    }
+
+    /// Returns the name of the tensor with the given id. Ids are hashes of the names, which the
+    /// metadata always stores, so this recovers a readable name for errors and graph dumps.
+    ///
+    /// # Returns
+    /// `None` if the file has no tensor with this id.
+    pub async fn tensor_name(&self, tensor_id: TensorId) -> Result<Option<&str>> {
+        Ok(self.find_tensor_metadata(tensor_id).await?.map(|tensor| tensor.name()))
+    }
+
+    /// Returns the file's aliases, tensors that share the data of another tensor, as pairs of the
+    /// alias's name and the name of the tensor it shares data with, in id order.
+    pub async fn aliases(&self) -> Result<Vec<(&str, &str)>> {
+        let metadata_root = self.get_metadata_root().await?;
+        let tensors = metadata_root.tensors().into_iter().flatten();
+        Ok(tensors.filter_map(|tensor| Some((tensor.name(), tensor.alias_of()?))).collect())
+    }
+
+    /// Returns the file's user metadata, the bytes set with `WriterOptions::with_user_metadata`.
+    ///
+    /// # Returns
+    /// `None` if the file has none.
+    pub async fn user_metadata(&self) -> Result<Option<&[u8]>> {
+        let metadata_root = self.get_metadata_root().await?;
+        Ok(metadata_root.user_metadata().map(|bytes| bytes.bytes()))
+    }
+
+    /// Returns the file's model card, the markdown set with `TensorBuffersWriter::set_model_card`
+    /// to describe the model for people, so the description travels with the weights.
+    ///
+    /// # Returns
+    /// `None` if the file has none.
+    pub async fn model_card(&self) -> Result<Option<&str>> {
+        let metadata_root = self.get_metadata_root().await?;
+        Ok(metadata_root.model_card())
+    }
+
+    /// Returns where the file's weights came from, as set with
+    /// `TensorBuffersWriter::set_provenance`.
+    ///
+    /// # Returns
+    /// `None` if the file records no provenance.
+    pub async fn provenance(&self) -> Result<Option<Provenance>> {
+        let metadata_root = self.get_metadata_root().await?;
+        Ok(metadata_root.provenance().map(|provenance| Provenance::from_metadata(&provenance)))
+    }
+
+    /// Returns the user metadata of a tensor, the bytes attached with
+    /// `TensorBuffersWriter::add_user_metadata`, without reading the whole metadata if the file
+    /// has a name index.
+    ///
+    /// # Returns
+    /// `None` if the tensor has none, or an error if the file has no such tensor.
+    pub async fn tensor_user_metadata(&self, tensor_name: &str) -> Result<Option<&[u8]>> {
+        let tensor = self.find_tensor_metadata(hash_key(tensor_name)).await?;
+        let tensor =
+            tensor.ok_or_else(|| format!("Tensor {} not found in metadata", tensor_name))?;
+        Ok(tensor.user_metadata().map(|bytes| bytes.bytes()))
+    }
+
+    /// Returns what the file's footer says about it, from a single read of the end of the file.
+    ///
+    /// # Returns
+    /// `None` for files written before format version 1.4.0, whose footers only locate the
+    /// metadata.
+    pub async fn footer_summary(&self) -> Result<Option<FooterSummary>> {
+        let file_size = self.file_size().await?;
+        let end_size = file_size.min(MAX_FOOTER_SIZE as u64);
+        let end = self.read_bytes(file_size - end_size, end_size as usize).await?;
+        Ok(Footer::parse(&end, file_size)?.summary)
+    }
+
+    /// Returns what the metadata says about a tensor.
+    ///
+    /// # Arguments
+    /// * `tensor_id` - The tensor's id, the hash of its name.
+    ///
+    /// # Returns
+    /// An error if the file has no such tensor or the reader can't read it.
+    pub async fn get_tensor_metadata(&self, tensor_id: TensorId) -> Result<TensorInfo> {
+        TensorInfo::from_metadata(&self.stored_metadata(tensor_id).await?)
+    }
+
+    /// Returns the stored metadata of a tensor, checking that the reader can read it.
+    pub(crate) async fn stored_metadata(&self, tensor_id: TensorId) -> Result<TensorMetadata<'_>> {
+        let result = self.find_tensor_metadata(tensor_id).await?;
+        let result = result.ok_or("Tensor ID not found in metadata")?;
+        match tensor_problem(&result) {
+            Some(problem) => Err(problem.into()),
+            None => Ok(result),
+        }
+    }
+
+    /// Looks up a tensor by id. Until the metadata is loaded, the file's name index is used if it
+    /// has one and `OpenOptions::with_name_index` is set. Falls back to the metadata and a linear
+    /// scan if the tensors are not sorted, in lenient mode or in 1.0.0 files. Returns `None` if
+    /// the file has no such tensor, and doesn't check whether the tensor is readable.
+    pub(crate) async fn find_tensor_metadata(
+        &self,
+        tensor_id: TensorId,
+    ) -> Result<Option<TensorMetadata<'_>>> {
+        if self.options.name_index() && self.metadata.get().is_none() {
+            match self.name_index.find(self, tensor_id).await? {
+                Some(Some(metadata)) => return Ok(Some(metadata)),
+                Some(None) if self.options.read_mode() == ReadMode::Strict => return Ok(None),
+                _ => {}
+            }
+        }
+        let metadata_root = self.get_metadata_root().await?;
+        if let Some(columns) = self.lookup.columns() {
+            let index = columns.find(tensor_id, self.lookup.tensors_sorted());
+            return Ok(index.map(|index| self.lookup.entry(&columns, index)));
+        }
+        let Some(tensors) = metadata_root.tensors() else {
+            return Ok(None);
+        };
         let result = tensors
             .lookup_by_key(tensor_id, |field, key| field.key_compare_with_value(*key))
-            .ok_or("Tensor ID not found in metadata")?;
+            .or_else(|| match self.lookup.tensors_sorted() {
+                true => None,
+                false => tensors.iter().find(|tensor| tensor.id() == tensor_id),
+            });
         Ok(result)
     }
 
@@ -69,11 +386,59 @@ impl<'a> TensorBuffers<'a> {
         let operations = metadata_root.operations().ok_or("No operations found")?;
         let result = operations
             .lookup_by_key(operation_id, |field, key| field.key_compare_with_value(*key))
+            .or_else(|| match self.lookup.operations_sorted() {
+                true => None,
+                false => operations.iter().find(|op| op.id() == operation_id),
+            })
             .ok_or("Operation ID not found in metadata")?;
         Ok(TensorOperation::with_metadata(&result))
     }
 
-    pub async fn get_tensor_data_by_name<T>(&self, tensor_name: &str) -> Result<Tensor<T>>
+    pub async fn get_tensor_operations(&self) -> Result<Vec<TensorOperation>> {
+        let metadata_root = self.get_metadata_root().await?;
+        let operations = match metadata_root.operations() {
+            Some(operations) => {
+                operations.iter().map(|op| TensorOperation::with_metadata(&op)).collect()
+            }
+            None => Vec::new(),
+        };
+        Ok(operations)
+    }
+
+    /// Builds the operation graph stored in the file.
+    pub async fn graph(&self) -> Result<TensorGraph> {
+        Ok(TensorGraph::new(self.get_tensor_operations().await?))
+    }
+
+    /// Returns all stored operations ordered so that every operation comes after its dependencies.
+    pub async fn operations_in_topological_order(&self) -> Result<Vec<TensorOperation>> {
+        let graph = self.graph().await?;
+        let ordered = graph.operations_in_topological_order()?;
+        Ok(ordered.into_iter().cloned().collect())
+    }
+
+    /// Returns the operations that the given operation directly takes as inputs.
+    pub async fn dependencies_of(
+        &self,
+        operation_id: TensorOperationId,
+    ) -> Result<Vec<TensorOperation>> {
+        let graph = self.graph().await?;
+        let dependencies = graph.dependencies_of(operation_id)?;
+        Ok(dependencies.into_iter().cloned().collect())
+    }
+
+    /// Returns the operations that directly take the given operation as an input.
+    pub async fn dependents_of(
+        &self,
+        operation_id: TensorOperationId,
+    ) -> Result<Vec<TensorOperation>> {
+        let graph = self.graph().await?;
+        let dependents = graph.dependents_of(operation_id)?;
+        Ok(dependents.into_iter().cloned().collect())
+    }
+
+    #[instrument(level = "debug", skip(self))]
+    pub async fn get_tensor_data_by_name<T>(&self, tensor_name: &str) -> Result<Tensor<'static, T>>
     where
         T: Pod + Num,
     {
@@ -81,62 +446,604 @@ impl<'a> TensorBuffers<'a> {
         self.get_tensor_data_by_id(tensor_id).await
     }
 
-    pub async fn get_tensor_data_by_id<T>(&self, tensor_id: TensorId) -> Result<Tensor<T>>
+    #[instrument(
+        level = "debug",
+        skip(self),
+        fields(name = Empty, bytes = Empty, elapsed_ms = Empty)
+    )]
+    pub async fn get_tensor_data_by_id<T>(&self, tensor_id: TensorId) -> Result<Tensor<'static, T>>
     where
         T: Pod + Num,
     {
-        let tensor_metadata = self.get_tensor_metadata(tensor_id).await?;
-        let data_type = tensor_metadata.data_type();
+        with_deadline(
+            self.options.timeouts().load,
+            "loading tensor",
+            self.load_tensor_data(tensor_id),
+        )
+        .await
+        .inspect_err(|error| self.observe(|observer| observer.on_error(error.as_ref())))
+    }
 
-        if data_type != T::data_type().into() {
-            return Err(format!(
-                "Tensor data type mismatch: expected {:?}, found {:?}",
-                T::data_type(),
-                data_type
-            )
-            .into());
+    async fn load_tensor_data<T>(&self, tensor_id: TensorId) -> Result<Tensor<'static, T>>
+    where
+        T: Pod + Num,
+    {
+        let start = Instant::now();
+        let tensor_metadata = self.stored_metadata(tensor_id).await?;
+        let span = Span::current();
+        span.record("name", tensor_metadata.name());
+        check_data_type::<T>(&tensor_metadata)?;
+
+        let size = tensor_metadata.data_size() as usize;
+        let stored_size = codec::stored_size(&tensor_metadata);
+        let (readers, offset) = self.locate_data(&tensor_metadata).await?;
+
+        // Read straight into an aligned buffer of `T` so the data is usable without a copy.
+        // Zstandard data is decompressed into it a chunk at a time as it is read, so only a chunk
+        // of it is held next to the tensor. Other compressed data is read into a separate buffer
+        // and decompressed into it.
+        let compression = tensor_metadata.compression();
+        let dictionary = self.compression_dictionary(&tensor_metadata).await?;
+        let prefetched = match tensor_metadata.external() {
+            Some(_) => None,
+            None => self.prefetcher.take(offset, stored_size),
+        };
+        let decompressor = match prefetched {
+            Some(_) => None,
+            None => codec::StreamingDecompressor::new(compression, dictionary)?,
+        };
+        let buffers_size = match (compression, &decompressor) {
+            (Compression::None, _) => size,
+            (_, Some(_)) => size + stored_size.min(DECOMPRESS_CHUNK_SIZE),
+            (_, None) => size + stored_size,
+        };
+        let what = format!("tensor {}", tensor_metadata.name());
+        let _reservation = self.memory.reserve(&what, buffers_size).await?;
+        let alignment = self.options.buffer_alignment();
+        let mut data = AlignedVec::from_elem(T::zero(), size / size_of::<T>(), alignment)?;
+        if let Some(decompressor) = decompressor {
+            let out = cast_slice_mut(data.as_mut_slice());
+            self.read_decompressing(&tensor_metadata, &readers, offset, decompressor, out).await?;
+            return self.finish_load(tensor_metadata, data, start);
+        }
+        let mut compressed = match compression {
+            Compression::None => Vec::new(),
+            _ => vec![0; stored_size],
+        };
+        {
+            let stored = match compression {
+                Compression::None => cast_slice_mut(data.as_mut_slice()),
+                _ => compressed.as_mut_slice(),
+            };
+            if let Some(prefetched) = prefetched {
+                stored.copy_from_slice(&prefetched);
+            } else {
+                self.observe(|observer| observer.on_fetch_start(offset, stored_size));
+                let fetch_start = Instant::now();
+                readers.get().await?.read_at(offset, stored).await?;
+                self.observe(|observer| {
+                    observer.on_fetch_finish(offset, stored_size, fetch_start.elapsed())
+                });
+            }
         }
+        let stored = match compression {
+            Compression::None => cast_slice(data.as_slice()),
+            _ => compressed.as_slice(),
+        };
+        if self.options.verify_checksums() {
+            codec::verify_checksum(&tensor_metadata, stored)?;
+        }
+        if compression != Compression::None {
+            let out = cast_slice_mut(data.as_mut_slice());
+            let codec = tensor_metadata.codec();
+            codec::decompress(&compressed, compression, dictionary, codec, out)?;
+        }
+        self.finish_load(tensor_metadata, data, start)
+    }
+
+    /// Reads a tensor's compressed data a chunk at a time, decompressing each chunk into `out`
+    /// and checking the data against its checksum as it arrives.
+    ///
+    /// # Arguments
+    /// * `readers` - The file the data is stored in.
+    /// * `offset` - Where the data starts in that file.
+    async fn read_decompressing(
+        &self,
+        tensor_metadata: &TensorMetadata<'_>,
+        readers: &ReaderPool,
+        offset: u64,
+        mut decompressor: codec::StreamingDecompressor,
+        out: &mut [u8],
+    ) -> Result<()> {
+        let stored_size = codec::stored_size(tensor_metadata);
+        let mut checksummer = match self.options.verify_checksums() {
+            true => Some(Checksummer::new(tensor_metadata.checksum_algorithm())?),
+            false => None,
+        };
+        let mut chunk = vec![0; stored_size.min(DECOMPRESS_CHUNK_SIZE)];
+        // Corrupt data is reported as a checksum mismatch, as when it is decompressed at once.
+        let mut failed = None;
+        let mut reader = readers.get().await?;
+        self.observe(|observer| observer.on_fetch_start(offset, stored_size));
+        let fetch_start = Instant::now();
+        let mut read = 0;
+        while read < stored_size {
+            let chunk = &mut chunk[..(stored_size - read).min(DECOMPRESS_CHUNK_SIZE)];
+            reader.read_at(offset + read as u64, chunk).await?;
+            if let Some(checksummer) = &mut checksummer {
+                checksummer.update(chunk);
+            }
+            if failed.is_none() {
+                failed = decompressor.decompress(chunk, out).err();
+            }
+            read += chunk.len();
+        }
+        drop(reader);
+        self.observe(|observer| {
+            observer.on_fetch_finish(offset, stored_size, fetch_start.elapsed())
+        });
+        if let Some(checksummer) = checksummer {
+            codec::check_checksum(tensor_metadata, checksummer.finish())?;
+        }
+        match failed {
+            Some(error) => Err(error),
+            None => decompressor.finish(out),
+        }
+    }
 
-        let offset = tensor_metadata.data_offset() as usize;
+    /// Wraps a loaded tensor's data, recording the load.
+    fn finish_load<T>(
+        &self,
+        tensor_metadata: TensorMetadata,
+        data: AlignedVec<T>,
+        start: Instant,
+    ) -> Result<Tensor<'static, T>>
+    where
+        T: Pod + Num,
+    {
         let size = tensor_metadata.data_size() as usize;
+        let tensor = Tensor::from_aligned(tensor_metadata, data)?;
+        metrics::record_tensor_load(start.elapsed());
+        self.observe(|observer| {
+            observer.on_tensor_loaded(tensor.name(), size, start.elapsed());
+            observer.on_tensor_read(tensor.name(), size, start.elapsed());
+        });
+        let span = Span::current();
+        span.record("bytes", size);
+        span.record("elapsed_ms", elapsed_ms(start));
+        Ok(tensor)
+    }
 
-        if offset.checked_add(size).is_none() {
+    /// Loads several tensors with as few reads as possible: their stored data is read in file
+    /// order, with neighbouring regions merged into large sequential reads, or few large range
+    /// requests for remote files, and then sliced per tensor. Much faster than loading tensors
+    /// one at a time on a cold cache, e.g. when loading a whole model.
+    ///
+    /// Merged reads are capped at 64 MiB, or at half of a memory budget if smaller.
+    ///
+    /// # Arguments
+    /// * `tensor_names` - The tensors to load, all of element type `T`.
+    ///
+    /// # Returns
+    /// The tensors in the order of `tensor_names`.
+    #[instrument(level = "debug", skip_all, fields(tensors = tensor_names.len(), reads = Empty))]
+    pub async fn get_tensors_data_by_name<T>(
+        &self,
+        tensor_names: &[&str],
+    ) -> Result<Vec<Tensor<'static, T>>>
+    where
+        T: Pod + Num,
+    {
+        let load = self.load_tensors_data::<T>(tensor_names);
+        with_deadline(self.options.timeouts().load, "loading tensors", load)
+            .await
+            .inspect_err(|error| self.observe(|observer| observer.on_error(error.as_ref())))
+    }
+
+    /// Loads tensors concurrently, at most `concurrency` at a time, each read on its own handle
+    /// to the file. Further loads start as earlier ones finish, and the first error is returned.
+    ///
+    /// # Arguments
+    /// * `tensor_names` - The tensors to load, all of element type `T`.
+    /// * `concurrency` - The most loads in progress at once.
+    ///
+    /// # Returns
+    /// The tensors in the order of `tensor_names`.
+    pub async fn load_many<T>(
+        &self,
+        tensor_names: &[&str],
+        concurrency: usize,
+    ) -> Result<Vec<Tensor<'static, T>>>
+    where
+        T: Pod + Num,
+    {
+        stream::iter(tensor_names)
+            .map(|name| self.get_tensor_data_by_name::<T>(name))
+            .buffered(concurrency.max(1))
+            .try_collect()
+            .await
+    }
+
+    /// Loads every readable tensor stored with element type `T` in id order, at most
+    /// `concurrency` at a time. Tensors of other types are left out.
+    pub async fn load_all<T>(&self, concurrency: usize) -> Result<Vec<Tensor<'static, T>>>
+    where
+        T: Pod + Num,
+    {
+        let names = self
+            .tensor_tables()
+            .await?
+            .into_iter()
+            .flatten()
+            .filter(|tensor| tensor_problem(tensor).is_none())
+            .filter(|tensor| tensor.data_type() == T::data_type().into())
+            .map(|tensor| tensor.name())
+            .collect::<Vec<_>>();
+        self.load_many(&names, concurrency).await
+    }
+
+    /// Streams a tensor's raw data in chunks of at most `chunk_size` bytes, so very large tensors
+    /// can be piped into GPU transfers or hashing without allocating the whole tensor at once.
+    /// Each chunk is read when the stream is polled, on a handle of its own. With checksum
+    /// verification on, a mismatch fails the stream after the last chunk.
+    ///
+    /// # Arguments
+    /// * `tensor_name` - The tensor to stream, which must be stored uncompressed.
+    /// * `chunk_size` - The largest chunk in bytes. Counts against the memory budget.
+    ///
+    /// # Returns
+    /// A stream of the tensor's bytes in file order.
+    pub async fn stream_tensor_bytes(
+        &self,
+        tensor_name: &str,
+        chunk_size: usize,
+    ) -> Result<impl Stream<Item = io::Result<Bytes>> + Send + use<'_>> {
+        let chunks = self
+            .tensor_chunks(tensor_name, chunk_size)
+            .await
+            .inspect_err(|error| self.observe(|observer| observer.on_error(error.as_ref())))?;
+        Ok(chunks.inspect_err(|error| self.observe(|observer| observer.on_error(error))))
+    }
+
+    /// Streams a tensor's raw bytes for `stream_tensor_bytes`, reporting the read once the last
+    /// chunk was read.
+    async fn tensor_chunks(
+        &self,
+        tensor_name: &str,
+        chunk_size: usize,
+    ) -> Result<impl Stream<Item = io::Result<Bytes>> + Send + use<'_>> {
+        if chunk_size == 0 {
+            return Err("Chunk size must be positive".into());
+        }
+        let tensor_metadata = self.stored_metadata(hash_key(tensor_name)).await?;
+        let (readers, start) = self.locate_data(&tensor_metadata).await?;
+        if tensor_metadata.compression() != Compression::None {
             return Err(
-                format!("Tensor data range [{}, {}) out of bounds", offset, offset + size,).into(),
+                format!("Tensor {} is compressed and can't be streamed", tensor_name).into()
             );
         }
+        let checksummer = match self.options.verify_checksums() {
+            true => Some(Checksummer::new(tensor_metadata.checksum_algorithm())?),
+            false => None,
+        };
+        let size = tensor_metadata.data_size() as usize;
+        let end = start + size as u64;
+        let started = Instant::now();
 
-        let mut buf = BytesMut::new();
-        buf.resize(size, 0);
-        self.reader.lock().unwrap().read_data_with_metadata(tensor_metadata, &mut buf).await?;
+        let state = (readers, start, checksummer);
+        Ok(stream::try_unfold(state, move |(readers, offset, mut checksummer)| async move {
+            if offset == end {
+                if let Some(checksummer) = checksummer {
+                    codec::check_checksum(&tensor_metadata, checksummer.finish())
+                        .map_err(|error| io::Error::other(error.to_string()))?;
+                }
+                self.observe(|observer| {
+                    observer.on_tensor_read(tensor_metadata.name(), size, started.elapsed())
+                });
+                return Ok(None);
+            }
+            let len = ((end - offset) as usize).min(chunk_size);
+            let what = format!("{} bytes of tensor {}", len, tensor_metadata.name());
+            let _reservation = self
+                .memory
+                .reserve(&what, len)
+                .await
+                .map_err(|error| io::Error::other(error.to_string()))?;
+            let mut chunk = BytesMut::zeroed(len);
+            let mut reader =
+                readers.get().await.map_err(|error| io::Error::other(error.to_string()))?;
+            reader
+                .read_at(offset, &mut chunk)
+                .await
+                .map_err(|error| io::Error::other(error.to_string()))?;
+            drop(reader);
+            if let Some(checksummer) = &mut checksummer {
+                checksummer.update(&chunk);
+            }
+            Ok(Some((chunk.freeze(), (readers, offset + len as u64, checksummer))))
+        }))
+    }
 
-        if size % size_of::<T>() != 0 {
-            return Err(format!(
-                "Tensor data size ({}) is not a multiple of type size ({})",
-                size,
-                size_of::<T>()
-            )
-            .into());
+    /// Reads a tensor's raw data as an `AsyncRead`, so it can be piped into uploads, hashers or
+    /// decompressors with `tokio::io::copy` without buffering the whole tensor. Data is read in
+    /// chunks of 64 KiB as the reader is polled, as `stream_tensor_bytes` does.
+    ///
+    /// # Arguments
+    /// * `tensor_name` - The tensor to read, which must be stored uncompressed.
+    ///
+    /// # Returns
+    /// A reader of the tensor's bytes. With checksum verification on, a mismatch fails the read
+    /// that reaches the end of the data.
+    pub async fn tensor_reader(
+        &self,
+        tensor_name: &str,
+    ) -> Result<impl AsyncRead + Send + use<'_>> {
+        let chunks = self.stream_tensor_bytes(tensor_name, READER_CHUNK_SIZE).await?;
+        Ok(ChunkReader::new(chunks))
+    }
+
+    async fn load_tensors_data<T>(&self, tensor_names: &[&str]) -> Result<Vec<Tensor<'static, T>>>
+    where
+        T: Pod + Num,
+    {
+        self.check_data_reads()?;
+        let start = Instant::now();
+        let file_size = self.file_size().await?;
+        let mut metadata = Vec::with_capacity(tensor_names.len());
+        // A file has at most one dictionary, shared by the tensors compressed with it.
+        let mut dictionary = None;
+        for name in tensor_names {
+            let tensor_metadata = self.stored_metadata(hash_key(name)).await?;
+            check_data_type::<T>(&tensor_metadata)?;
+            if tensor_metadata.external().is_none() {
+                check_data_layout(&tensor_metadata, file_size)?;
+            }
+            if dictionary.is_none() {
+                dictionary = self.compression_dictionary(&tensor_metadata).await?;
+            }
+            metadata.push(tensor_metadata);
+        }
+
+        let mut tensors = std::iter::repeat_with(|| None).take(metadata.len()).collect::<Vec<_>>();
+        let mut regions = Vec::new();
+        let mut indices = Vec::new();
+        for (index, tensor_metadata) in metadata.iter().enumerate() {
+            // Data stored in other files is loaded on its own, from the file holding it.
+            if tensor_metadata.external().is_some() {
+                tensors[index] = Some(self.load_tensor_data(tensor_metadata.id()).await?);
+                continue;
+            }
+            let offset = tensor_metadata.data_offset() as u64;
+            let stored_size = codec::stored_size(tensor_metadata);
+            match self.prefetcher.take(offset, stored_size) {
+                Some(prefetched) => {
+                    tensors[index] = Some(self.decode_tensor(
+                        *tensor_metadata,
+                        &prefetched,
+                        dictionary,
+                        start,
+                    )?)
+                }
+                None => {
+                    regions.push((offset, stored_size));
+                    indices.push(index);
+                }
+            }
         }
 
-        Tensor::new_with_metadata_and_data(tensor_metadata, buf.to_vec())
+        let budget = self.options.memory_budget();
+        let max_read_size = [budget.max_tensor_bytes, budget.max_outstanding_bytes]
+            .into_iter()
+            .flatten()
+            .fold(MAX_READ_SIZE, |max, bytes| max.min(bytes / 2));
+        let plan = plan_reads(&regions, MAX_GAP, max_read_size);
+        Span::current().record("reads", plan.len());
+        for read in plan {
+            // The read buffer and the tensors decoded from it count against the memory budget.
+            let decoded = read.parts.iter().map(|(part, _)| metadata[indices[*part]].data_size());
+            let decoded = decoded.map(|size| size as usize).sum::<usize>();
+            let what = format!("{} bytes at offset {}", read.size, read.offset);
+            let _reservation = self.memory.reserve(&what, read.size + decoded).await?;
+            let mut buf = vec![0; read.size];
+            self.observe(|observer| observer.on_fetch_start(read.offset, read.size));
+            let fetch_start = Instant::now();
+            self.readers.get().await?.read_at(read.offset, &mut buf).await?;
+            self.observe(|observer| {
+                observer.on_fetch_finish(read.offset, read.size, fetch_start.elapsed())
+            });
+            for (part, range) in read.parts {
+                let index = indices[part];
+                tensors[index] =
+                    Some(self.decode_tensor(metadata[index], &buf[range], dictionary, start)?);
+            }
+        }
+        Ok(tensors.into_iter().map(|tensor| tensor.expect("every tensor is planned")).collect())
+    }
+
+    /// Builds a tensor from its stored data, checking the checksum and decompressing it into an
+    /// aligned buffer.
+    fn decode_tensor<T>(
+        &self,
+        tensor_metadata: TensorMetadata,
+        stored: &[u8],
+        dictionary: Option<&[u8]>,
+        start: Instant,
+    ) -> Result<Tensor<'static, T>>
+    where
+        T: Pod + Num,
+    {
+        if self.options.verify_checksums() {
+            codec::verify_checksum(&tensor_metadata, stored)?;
+        }
+        let size = tensor_metadata.data_size() as usize;
+        let alignment = self.options.buffer_alignment();
+        let mut data = AlignedVec::from_elem(T::zero(), size / size_of::<T>(), alignment)?;
+        codec::decompress(
+            stored,
+            tensor_metadata.compression(),
+            dictionary,
+            tensor_metadata.codec(),
+            cast_slice_mut(data.as_mut_slice()),
+        )?;
+        let tensor = Tensor::from_aligned(tensor_metadata, data)?;
+        metrics::record_tensor_load(start.elapsed());
+        self.observe(|observer| {
+            observer.on_tensor_loaded(tensor.name(), size, start.elapsed());
+            observer.on_tensor_read(tensor.name(), size, start.elapsed());
+        });
+        Ok(tensor)
     }
 }
 
-impl<'a> TensorBuffers<'a> {
-    pub fn build_table(
+impl TensorBuffers {
+    /// Queues tensors to be fetched in the background, within the `PrefetchBudget` of the open
+    /// options, so that loading them later doesn't wait for the file. Fetched data is held until
+    /// the tensor is loaded. Returns once the tensors are queued.
+    ///
+    /// # Arguments
+    /// * `tensor_names` - The tensors to fetch.
+    /// * `priority` - How urgently they are needed; hot-path requests are fetched before queued
+    ///   warm-up requests.
+    ///
+    /// Tensors whose data is stored in another file are not prefetched.
+    pub async fn prefetch(&self, tensor_names: &[&str], priority: PrefetchPriority) -> Result<()> {
+        self.check_data_reads()?;
+        let file_size = self.file_size().await?;
+        for name in tensor_names {
+            let tensor_metadata = self.stored_metadata(hash_key(name)).await?;
+            if tensor_metadata.external().is_some() {
+                continue;
+            }
+            check_data_layout(&tensor_metadata, file_size)?;
+            let offset = tensor_metadata.data_offset() as u64;
+            self.prefetcher.request(offset, codec::stored_size(&tensor_metadata), priority);
+        }
+        Ok(())
+    }
+
+    /// Returns the number of prefetched bytes that no load has used yet.
+    pub fn prefetched_bytes(&self) -> usize {
+        self.prefetcher.cached_bytes()
+    }
+
+    /// Returns the total size of the file in bytes.
+    pub async fn file_size(&self) -> Result<u64> {
+        self.readers.get().await?.file_size().await
+    }
+
+    /// Returns the handles the file is read with.
+    #[cfg(feature = "serve")]
+    pub(crate) fn readers(&self) -> &Arc<ReaderPool> {
+        &self.readers
+    }
+
+    /// Reads `len` raw bytes of the file starting at `offset`.
+    pub async fn read_bytes(&self, offset: u64, len: usize) -> Result<Vec<u8>> {
+        self.read_bytes_from(&self.readers, offset, len).await
+    }
+
+    /// Reads `len` raw bytes starting at `offset` of the file `readers` read, which is this file
+    /// or one its tensors' data is stored in.
+    pub(crate) async fn read_bytes_from(
+        &self,
+        readers: &ReaderPool,
+        offset: u64,
+        len: usize,
+    ) -> Result<Vec<u8>> {
+        let what = format!("bytes at offset {}", offset);
+        let _reservation = self.memory.reserve(&what, len).await?;
+        let mut buf = vec![0; len];
+        self.observe(|observer| observer.on_fetch_start(offset, len));
+        let start = Instant::now();
+        readers.get().await?.read_at(offset, &mut buf).await?;
+        self.observe(|observer| observer.on_fetch_finish(offset, len, start.elapsed()));
+        Ok(buf)
+    }
+
+    /// Loads a tensor whatever its stored data type, checking its data against its checksum.
+    pub(crate) async fn read_tensor_any(&self, metadata: TensorMetadata<'_>) -> Result<TensorAny> {
+        let size = codec::stored_size(&metadata);
+        let (readers, offset) = self.locate_data(&metadata).await?;
+        let stored = self.read_bytes_from(&readers, offset, size).await?;
+        let dictionary = self.compression_dictionary(&metadata).await?;
+        TensorAny::decode(metadata, &stored, dictionary)
+    }
+
+    /// Returns the file a tensor's data is stored in and where the data starts there, after
+    /// checking that it lies within that file. Files that tensors refer to are opened with this
+    /// file's options on first use, with relative locations taken from this file's URL.
+    pub(crate) async fn locate_data(
+        &self,
+        tensor_metadata: &TensorMetadata<'_>,
+    ) -> Result<(Arc<ReaderPool>, u64)> {
+        self.check_data_reads()?;
+        let readers = match tensor_metadata.external() {
+            Some(external) => {
+                let url = resolve_location(self.readers.url(), external.location());
+                self.external_files.get(&url, &self.options, self.readers.counters()).await?
+            }
+            None => self.readers.clone(),
+        };
+        check_data_layout(tensor_metadata, readers.get().await?.file_size().await?)?;
+        Ok((readers, stored_offset(tensor_metadata)))
+    }
+}
+
+impl TensorBuffers {
+    /// Builds the file metadata table, recording `version` as the format version, the format
+    /// features the tensors need, the dictionary they were compressed with and the file's user
+    /// metadata, model card, provenance and constraints, if any. The tensors are stored as
+    /// `tensor_columns` if given, and as their tables otherwise.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn build_versioned_table<'a>(
         builder: &mut FlatBufferBuilder<'a>,
+        version: &str,
         tensor_metadata_offsets: &[WIPOffset<TensorMetadata<'a>>],
+        tensor_columns: Option<WIPOffset<TensorColumns<'a>>>,
         tensor_operation_offsets: &[WIPOffset<OperationMetadata<'a>>],
+        features: &FormatFeatures,
+        compression_dictionary: Option<&[u8]>,
+        user_metadata: Option<&[u8]>,
+        model_card: Option<&str>,
+        provenance: Option<&Provenance>,
+        constraints: &[TensorConstraint],
     ) -> WIPOffset<TensorBuffersMetadata<'a>> {
         // Create FlatBuffers metadata for the file.
-        let version_offset = builder.create_string(VERSION);
-        let tensors_offset = builder.create_vector(&tensor_metadata_offsets);
+        let version_offset = builder.create_string(version);
+        let tensors_offset = match tensor_columns {
+            Some(_) => None,
+            None => Some(builder.create_vector(&tensor_metadata_offsets)),
+        };
         let operations_offset = builder.create_vector(&tensor_operation_offsets);
+        // Files that need no features leave the list out, as files written before it existed.
+        let features = features.names();
+        let features_offset = (!features.is_empty()).then(|| {
+            let names = features.iter().map(|name| builder.create_string(name)).collect::<Vec<_>>();
+            builder.create_vector(&names)
+        });
+        let compression_dictionary =
+            compression_dictionary.map(|dictionary| builder.create_vector(dictionary));
+        let user_metadata = user_metadata.map(|bytes| builder.create_vector(bytes));
+        let model_card = model_card.map(|card| builder.create_string(card));
+        let provenance = provenance.map(|provenance| provenance.build_table(builder));
+        let constraints = (!constraints.is_empty()).then(|| {
+            let constraints = constraints
+                .iter()
+                .map(|constraint| constraint.build_table(builder))
+                .collect::<Vec<_>>();
+            builder.create_vector(&constraints)
+        });
         TensorBuffersMetadata::create(builder, &TensorBuffersMetadataArgs {
             version: Some(version_offset),
-            tensors: Some(tensors_offset),
+            tensors: tensors_offset,
             operations: Some(operations_offset),
+            features: features_offset,
+            compression_dictionary,
+            user_metadata,
+            tensor_columns,
+            model_card,
+            provenance,
+            constraints,
             ..Default::default()
         })
     }
@@ -144,14 +1051,22 @@ impl<'a> TensorBuffers<'a> {
 
 #[cfg(test)]
 mod tests {
+    use std::time::Duration;
+
     use bytemuck::cast_slice;
     use tempfile::NamedTempFile;
     use tokio::fs::File;
 
     use super::*;
     use crate::{
-        generated::tensor_buffers::TensorBuffersMetadata,
-        tensor_buffers_writer::TensorBuffersWrite, Operation, Tensor, TensorBuffersWriter,
+        constants::{MAGIC_BYTES, VERSION},
+        generated::tensor_buffers::{
+            DataType, OperationMetadata, OperationMetadataArgs, TensorMetadataArgs,
+        },
+        tensor_buffers_reader::{TensorBuffersRead, TensorBuffersReader},
+        tensor_buffers_writer::TensorBuffersWrite,
+        ChecksumAlgorithm, MemoryBudget, Operation, PrefetchBudget, Tensor, TensorBuffersWriter,
+        WriterOptions,
     };
 
     #[tokio::test]
@@ -197,6 +1112,948 @@ mod tests {
         let tensor_operation_metadata = tensor_buffers_metadata.operations().unwrap().get(0);
         assert!(tensor_operation_metadata.id() == 1);
         assert!(tensor_operation_metadata.operation() == Operation::None);
-        assert!(tensor_operation_metadata.input_operations().unwrap().len() == 0);
+        assert!(tensor_operation_metadata.input_operations().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_operations_in_topological_order() {
+        let tensor = Tensor::new("1", &[1.0f32, 2.0, 3.0], vec![3]);
+        let operations = vec![
+            TensorOperation::new(3, Operation::Add, vec![1, 2], 30),
+            TensorOperation::new(2, Operation::Sqr, vec![1], 20),
+            TensorOperation::new(1, Operation::None, vec![], tensor.id()),
+        ];
+        let tmp = NamedTempFile::new().unwrap();
+        let path = tmp.path().to_owned();
+        let mut file = File::create(&path).await.unwrap();
+        let mut writer = TensorBuffersWriter::new(&mut file);
+        writer.write(vec![tensor], operations).await.unwrap();
+
+        let url = format!("file://{}", path.display());
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        let order = tensor_buffers.operations_in_topological_order().await.unwrap();
+        let ids = order.iter().map(|op| op.id()).collect::<Vec<_>>();
+        assert_eq!(ids, vec![1, 2, 3]);
+
+        let dependencies = tensor_buffers.dependencies_of(3).await.unwrap();
+        assert_eq!(dependencies.iter().map(|op| op.id()).collect::<Vec<_>>(), vec![1, 2]);
+        let dependents = tensor_buffers.dependents_of(1).await.unwrap();
+        assert_eq!(dependents.iter().map(|op| op.id()).collect::<Vec<_>>(), vec![2, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_get_operation_by_name() {
+        let tensor = Tensor::new("x", &[1.0f32, 2.0, 3.0], vec![3]);
+        let operations = vec![
+            TensorOperation::new(1, Operation::None, vec![], tensor.id()).with_name("input"),
+            TensorOperation::new(2, Operation::Exp, vec![1], 20).with_name("activation"),
+            TensorOperation::new(3, Operation::Sum, vec![2], 30),
+        ];
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        TensorBuffersWriter::new(&mut file).write(vec![tensor], operations).await.unwrap();
+
+        let url = format!("file://{}", tmp.path().display());
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        let operation = tensor_buffers.get_operation_by_name("activation").await.unwrap();
+        assert_eq!(operation.id(), 2);
+        assert_eq!(operation.name(), Some("activation"));
+        assert!(tensor_buffers.get_operation_by_name("missing").await.is_err());
+        assert_eq!(tensor_buffers.get_tensor_operation_by_id(3).await.unwrap().name(), None);
+
+        let names = tensor_buffers.get_operation_names().await.unwrap();
+        assert_eq!(names, vec![("activation".to_string(), 2), ("input".to_string(), 1)]);
+    }
+
+    #[derive(Default)]
+    struct RecordingObserver {
+        events: std::sync::Mutex<Vec<String>>,
+    }
+
+    impl TensorBuffersObserver for RecordingObserver {
+        fn on_fetch_start(&self, offset: u64, len: usize) {
+            self.events.lock().unwrap().push(format!("start {} {}", offset, len));
+        }
+
+        fn on_fetch_finish(&self, offset: u64, len: usize, _elapsed: std::time::Duration) {
+            self.events.lock().unwrap().push(format!("finish {} {}", offset, len));
+        }
+
+        fn on_tensor_loaded(&self, name: &str, bytes: usize, _elapsed: std::time::Duration) {
+            self.events.lock().unwrap().push(format!("loaded {} {}", name, bytes));
+        }
+
+        fn on_metadata_loaded(&self, tensors: usize, _bytes: usize, _elapsed: std::time::Duration) {
+            self.events.lock().unwrap().push(format!("metadata {}", tensors));
+        }
+
+        fn on_tensor_read(&self, name: &str, bytes: usize, _elapsed: std::time::Duration) {
+            self.events.lock().unwrap().push(format!("read {} {}", name, bytes));
+        }
+
+        fn on_error(&self, _error: &dyn std::error::Error) {
+            self.events.lock().unwrap().push("error".to_string());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_observer() {
+        let tensor = Tensor::new("x", &[1.0f32, 2.0, 3.0], vec![3]);
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        TensorBuffersWriter::new(&mut file).write(vec![tensor], vec![]).await.unwrap();
+
+        let observer = Arc::new(RecordingObserver::default());
+        let url = format!("file://{}", tmp.path().display());
+        let options = OpenOptions::new().with_observer(observer.clone());
+        let tensor_buffers = TensorBuffers::open_with(&url, options).await.unwrap();
+        tensor_buffers.get_tensor_data_by_name::<f32>("x").await.unwrap();
+        assert!(tensor_buffers.get_tensor_data_by_name::<f32>("missing").await.is_err());
+
+        let events = observer.events.lock().unwrap().clone();
+        assert_eq!(events.len(), 8);
+        assert!(events[0].starts_with("start") && events[1].starts_with("finish"));
+        assert_eq!(&events[2..], [
+            "metadata 1",
+            "start 4 12",
+            "finish 4 12",
+            "loaded x 12",
+            "read x 12",
+            "error"
+        ]);
+
+        // Streamed reads are reported once the last chunk was read.
+        observer.events.lock().unwrap().clear();
+        let chunks = tensor_buffers.stream_tensor_bytes("x", 5).await.unwrap();
+        assert_eq!(chunks.try_collect::<Vec<_>>().await.unwrap().len(), 3);
+        assert_eq!(*observer.events.lock().unwrap(), ["read x 12"]);
+        assert!(tensor_buffers.stream_tensor_bytes("missing", 5).await.is_err());
+        assert_eq!(observer.events.lock().unwrap().last().unwrap(), "error");
+
+        let options = OpenOptions::new().with_observer(observer.clone());
+        assert!(TensorBuffers::open_with("file:///missing.tb", options).await.is_err());
+        assert_eq!(observer.events.lock().unwrap().last().unwrap(), "error");
+    }
+
+    fn assert_send<T: Send>(value: T) -> T {
+        value
+    }
+
+    #[tokio::test]
+    async fn test_futures_are_send() {
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        let tensor = Tensor::new("x", &[1.0f32, 2.0, 3.0], vec![3]);
+        let operations = vec![TensorOperation::new(1, Operation::None, vec![], tensor.id())];
+        let mut writer = TensorBuffersWriter::new(&mut file);
+        assert_send(writer.write(vec![tensor], operations)).await.unwrap();
+
+        // Readers can be shared between spawned tasks.
+        let url = format!("file://{}", tmp.path().display());
+        let tensor_buffers = Arc::new(assert_send(TensorBuffers::open(&url)).await.unwrap());
+        let tasks = (0..4).map(|_| {
+            let tensor_buffers = tensor_buffers.clone();
+            tokio::spawn(async move {
+                let x = tensor_buffers.get_tensor_data_by_name::<f32>("x").await?;
+                let batch = tensor_buffers.get_tensors_data_by_name::<f32>(&["x"]).await?;
+                let chunks = tensor_buffers.stream_tensor_bytes("x", 4).await?;
+                let chunks = chunks.try_collect::<Vec<_>>().await?;
+                tensor_buffers.prefetch(&["x"], PrefetchPriority::Warmup).await?;
+                tensor_buffers.infer_shapes().await?;
+                tensor_buffers.filter_tensors(|_| true).await?;
+                crate::Executor::new(&tensor_buffers).run::<f32>(&["x"]).await?;
+                Result::Ok((x.data().len(), batch.len(), chunks.len()))
+            })
+        });
+        for task in tasks.collect::<Vec<_>>() {
+            assert_eq!(task.await.unwrap().unwrap(), (3, 1, 3));
+        }
+        let missing = tokio::spawn(TensorBuffers::open("file:///missing.tb"));
+        assert!(missing.await.unwrap().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_contains_tensor() {
+        let values = [1.0f32, 2.0];
+        let names = (0..200).map(|i| format!("t.{}", i)).collect::<Vec<_>>();
+        let tensors = names.iter().map(|name| Tensor::new(name, &values, vec![2])).collect();
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        let options = WriterOptions::new().with_bloom_filter(true);
+        let mut writer = TensorBuffersWriter::with_options(&mut file, options);
+        writer.write(tensors, vec![]).await.unwrap();
+
+        let observer = Arc::new(RecordingObserver::default());
+        let url = format!("file://{}", tmp.path().display());
+        let options = OpenOptions::new().with_observer(observer.clone());
+        let tensor_buffers = TensorBuffers::open_with(&url, options).await.unwrap();
+        // The filter rules out a missing tensor with a single read of the end of the file.
+        assert!(!tensor_buffers.contains_tensor("missing").await.unwrap());
+        let file_size = std::fs::metadata(tmp.path()).unwrap().len();
+        assert_eq!(*observer.events.lock().unwrap(), [
+            format!("start 0 {}", file_size),
+            format!("finish 0 {}", file_size)
+        ]);
+        assert!(tensor_buffers.contains_tensor("t.123").await.unwrap());
+        assert!(!tensor_buffers.contains_tensor("missing").await.unwrap());
+
+        // Files without a filter load the metadata instead.
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        let tensor = Tensor::new("x", &values, vec![2]);
+        TensorBuffersWriter::new(&mut file).write(vec![tensor], vec![]).await.unwrap();
+        let url = format!("file://{}", tmp.path().display());
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        assert!(tensor_buffers.contains_tensor("x").await.unwrap());
+        assert!(!tensor_buffers.contains_tensor("missing").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_tensor_name() {
+        let values = [1.0f32, 2.0];
+        let tensors =
+            ["encoder.weight", "encoder.bias"].map(|name| Tensor::new(name, &values, vec![2]));
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        TensorBuffersWriter::new(&mut file).write(tensors.to_vec(), vec![]).await.unwrap();
+
+        let url = format!("file://{}", tmp.path().display());
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        for tensor in &tensors {
+            let name = tensor_buffers.tensor_name(tensor.id()).await.unwrap();
+            assert_eq!(name, Some(tensor.name()));
+        }
+        assert_eq!(tensor_buffers.tensor_name(hash_key("missing")).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_metadata_buffer() {
+        let values = [1.0f32, 2.0];
+        let tensors = vec![Tensor::new("a", &values, vec![2]), Tensor::new("b", &values, vec![2])];
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        TensorBuffersWriter::new(&mut file).write(tensors, vec![]).await.unwrap();
+
+        let url = format!("file://{}", tmp.path().display());
+        for _ in 0..3 {
+            let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+            let first = tensor_buffers.get_metadata_root().await.unwrap();
+            assert_eq!(tensor_buffers.get_tensor_metadata_by_name("b").await.unwrap().name, "b");
+            let second = tensor_buffers.get_metadata_root().await.unwrap();
+            // Every call parses the one buffer read on open instead of reading it again.
+            let metadata = tensor_buffers.metadata.get().unwrap().clone();
+            assert_eq!(first._tab.buf().as_ptr(), metadata.as_ptr());
+            assert_eq!(second._tab.buf().as_ptr(), metadata.as_ptr());
+            // Dropping the file frees the buffer instead of leaking it.
+            drop(tensor_buffers);
+            assert!(metadata.is_unique());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_footer_summary() {
+        let values = [1.0f32, 2.0];
+        let tensors = vec![Tensor::new("a", &values, vec![2]), Tensor::new("b", &values, vec![2])];
+        let operation =
+            TensorOperation::new(1, Operation::None, vec![hash_key("a")], hash_key("b"));
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        let options = WriterOptions::new().with_name_index(true).with_bloom_filter(true);
+        let mut writer = TensorBuffersWriter::with_options(&mut file, options);
+        writer.write(tensors, vec![operation]).await.unwrap();
+
+        let observer = Arc::new(RecordingObserver::default());
+        let url = format!("file://{}", tmp.path().display());
+        let options = OpenOptions::new().with_observer(observer.clone());
+        let tensor_buffers = TensorBuffers::open_with(&url, options).await.unwrap();
+        let summary = tensor_buffers.footer_summary().await.unwrap().unwrap();
+        assert_eq!(
+            (summary.version.as_str(), summary.tensors, summary.operations),
+            (VERSION, 2, 1)
+        );
+        assert!(summary.name_index && summary.bloom_filter);
+        let file_size = std::fs::metadata(tmp.path()).unwrap().len();
+        assert_eq!(observer.events.lock().unwrap().len(), 2);
+
+        // The metadata is where the footer says it is.
+        let bytes = std::fs::read(tmp.path()).unwrap();
+        let metadata_start = summary.metadata_offset as usize;
+        let metadata = &bytes[metadata_start..metadata_start + summary.metadata_size];
+        let root = flatbuffers::root::<TensorBuffersMetadata>(metadata).unwrap();
+        assert_eq!(root.tensors().unwrap().len(), 2);
+        assert!(metadata_start + summary.metadata_size < file_size as usize);
+
+        // Older footers have no summary.
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        let options = WriterOptions::new().with_format_version("1.3.0");
+        let mut writer = TensorBuffersWriter::with_options(&mut file, options);
+        writer.write(vec![Tensor::new("x", &values, vec![2])], vec![]).await.unwrap();
+        let url = format!("file://{}", tmp.path().display());
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        assert_eq!(tensor_buffers.footer_summary().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_compressed_metadata() {
+        let values = [1.0f32, 2.0];
+        let names = (0..500).map(|i| format!("experts.{}.weight", i)).collect::<Vec<_>>();
+        async fn write(names: &[String], values: &[f32], options: WriterOptions) -> Vec<u8> {
+            let tensors = names.iter().map(|name| Tensor::new(name, values, vec![2])).collect();
+            let mut bytes = std::io::Cursor::new(Vec::new());
+            let mut writer = TensorBuffersWriter::with_options(&mut bytes, options);
+            writer.write(tensors, vec![]).await.unwrap();
+            bytes.into_inner()
+        }
+        let plain = write(&names, &values, WriterOptions::new()).await;
+        let options = WriterOptions::new().with_compressed_metadata(true);
+        let compressed = write(&names, &values, options).await;
+        assert!(compressed.len() * 2 < plain.len(), "{} >= {}", compressed.len(), plain.len());
+
+        // The metadata decompresses to the same bytes.
+        async fn metadata(bytes: &[u8]) -> Vec<u8> {
+            let mut reader = TensorBuffersReader::new(std::io::Cursor::new(bytes));
+            let mut metadata = vec![0; reader.get_metadata_size().await.unwrap()];
+            reader.read_metadata(&mut metadata).await.unwrap();
+            metadata
+        }
+        assert_eq!(metadata(&compressed).await, metadata(&plain).await);
+
+        let tmp = NamedTempFile::new().unwrap();
+        std::fs::write(tmp.path(), &compressed).unwrap();
+        let url = format!("file://{}", tmp.path().display());
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        assert!(tensor_buffers.footer_summary().await.unwrap().unwrap().compressed_metadata);
+        let tensor = tensor_buffers.get_tensor_data_by_name::<f32>("experts.321.weight").await;
+        assert_eq!(tensor.unwrap().data(), values);
+        let untrusted = crate::parse_untrusted(&compressed, Default::default());
+        assert!(untrusted.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_compression_dictionary() {
+        // Many small norms that share their values but barely compress on their own.
+        let data = (0..300)
+            .map(|i| (0..64).map(|j| 1.0 + ((j * 7 + i % 3) % 13) as f32 / 64.0).collect())
+            .collect::<Vec<Vec<f32>>>();
+        let names = (0..300).map(|i| format!("layers.{}.norm", i)).collect::<Vec<_>>();
+        async fn write(names: &[String], data: &[Vec<f32>], options: WriterOptions) -> Vec<u8> {
+            let tensors =
+                names.iter().zip(data).map(|(name, data)| Tensor::new(name, data, vec![64]));
+            let mut bytes = std::io::Cursor::new(Vec::new());
+            let mut writer = TensorBuffersWriter::with_options(&mut bytes, options);
+            writer.write(tensors.collect(), vec![]).await.unwrap();
+            bytes.into_inner()
+        }
+        let options =
+            WriterOptions::new().with_compression(Compression::Zstd, 3).with_leading_metadata(true);
+        let plain = write(&names, &data, options.clone()).await;
+        let options = options.with_compression_dictionary(4 << 10);
+        let bytes = write(&names, &data, options.clone()).await;
+        assert!(bytes.len() < plain.len(), "{} >= {}", bytes.len(), plain.len());
+        let sequential = write(&names, &data, options.with_parallel_compression(false)).await;
+        assert!(bytes == sequential);
+
+        let tmp = NamedTempFile::new().unwrap();
+        std::fs::write(tmp.path(), &bytes).unwrap();
+        let url = format!("file://{}", tmp.path().display());
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        assert!(tensor_buffers.features().await.unwrap().contains(&"zstd_dictionary"));
+        let metadata = tensor_buffers.stored_metadata(hash_key("layers.7.norm")).await;
+        assert_eq!(metadata.unwrap().compression(), Compression::ZstdDictionary);
+        let tensor = tensor_buffers.get_tensor_data_by_name::<f32>("layers.7.norm").await;
+        assert_eq!(tensor.unwrap().data(), data[7]);
+        let tensors = tensor_buffers
+            .get_tensors_data_by_name::<f32>(&["layers.299.norm", "layers.8.norm"])
+            .await
+            .unwrap();
+        assert_eq!(tensors[0].data(), data[299]);
+        assert_eq!(tensors[1].data(), data[8]);
+
+        let untrusted = crate::parse_untrusted(&bytes, Default::default()).unwrap();
+        assert_eq!(untrusted.tensor::<f32>("layers.42.norm").unwrap().data(), data[42]);
+        let mut reader = crate::TensorBuffersStreamReader::new(&bytes[..]).await.unwrap();
+        let first = reader.next_tensor().await.unwrap().unwrap();
+        assert_eq!(first.data_bytes(), cast_slice::<f32, u8>(&data[0]));
+    }
+
+    #[tokio::test]
+    async fn test_scalar_and_empty_tensors() {
+        let scalar = [3.5f32];
+        let options = [
+            WriterOptions::new(),
+            WriterOptions::new().with_alignment(64).with_dedup(true),
+            WriterOptions::new().with_compression(Compression::Zstd, 3),
+            WriterOptions::new().with_compression(Compression::Lz4, 0),
+            WriterOptions::new().with_checksum(ChecksumAlgorithm::XxHash64),
+            WriterOptions::new().with_name_index(true).with_bloom_filter(true),
+            WriterOptions::new().with_leading_metadata(true),
+        ];
+        for options in options {
+            let tensors = vec![
+                Tensor::new("scalar", &scalar[..], vec![]),
+                Tensor::new("empty", &[], vec![0]),
+                Tensor::new("empty_rows", &[], vec![0, 4]),
+                Tensor::new("empty_too", &[], vec![3, 0]),
+            ];
+            let leading = options.leading_metadata();
+            let tmp = NamedTempFile::new().unwrap();
+            let mut file = File::create(tmp.path()).await.unwrap();
+            let mut writer = TensorBuffersWriter::with_options(&mut file, options);
+            writer.write(tensors, vec![]).await.unwrap();
+
+            let url = format!("file://{}", tmp.path().display());
+            let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+            let tensor = tensor_buffers.get_tensor_data_by_name::<f32>("scalar").await.unwrap();
+            assert_eq!((tensor.shape(), tensor.data()), (&[][..], &scalar[..]));
+            for (name, shape) in
+                [("empty", &[0][..]), ("empty_rows", &[0, 4]), ("empty_too", &[3, 0])]
+            {
+                let tensor = tensor_buffers.get_tensor_data_by_name::<f32>(name).await.unwrap();
+                assert_eq!((tensor.shape(), tensor.data()), (shape, &[][..]));
+            }
+            let bytes = std::fs::read(tmp.path()).unwrap();
+            let untrusted = crate::parse_untrusted(&bytes, Default::default()).unwrap();
+            assert_eq!(untrusted.tensor::<f32>("scalar").unwrap().shape(), &[] as &[usize]);
+            assert!(untrusted.tensor::<f32>("empty").unwrap().data().is_empty());
+            if leading {
+                let mut reader = crate::TensorBuffersStreamReader::new(&bytes[..]).await.unwrap();
+                let mut elements = Vec::new();
+                while let Some(tensor) = reader.next_tensor().await.unwrap() {
+                    elements.push(tensor.elements());
+                }
+                elements.sort();
+                assert_eq!(elements, [0, 0, 0, 1]);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_wide_shape() {
+        // An empty tensor can have a dimension beyond u32, stored in `shape64`.
+        let shape = vec![0, 5_000_000_000];
+        let tensors = vec![Tensor::new("wide", &[] as &[f32], shape.clone())];
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        TensorBuffersWriter::new(&mut file).write(tensors, vec![]).await.unwrap();
+
+        let url = format!("file://{}", tmp.path().display());
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        let metadata = tensor_buffers.get_metadata_root().await.unwrap();
+        let stored = metadata.tensors().unwrap().get(0);
+        assert!(stored.shape().is_none());
+        assert_eq!(tensor_buffers.features().await.unwrap(), ["shape64"]);
+        assert_eq!(stored.shape64().unwrap().iter().collect::<Vec<_>>(), [0, 5_000_000_000]);
+        let tensor = tensor_buffers.get_tensor_data_by_name::<f32>("wide").await.unwrap();
+        assert_eq!((tensor.shape(), tensor.data().len()), (&shape[..], 0));
+    }
+
+    #[tokio::test]
+    async fn test_write_with_options() {
+        let values = (0..1024).map(|i| (i % 16) as f32).collect::<Vec<_>>();
+        let tensors = || {
+            vec![
+                Tensor::new("b", &values, vec![1024]),
+                Tensor::new("a", &[1.0f32, 2.0, 3.0], vec![3]),
+                Tensor::new("c", &values, vec![32, 32]),
+            ]
+        };
+        let ids = tensors().iter().map(|tensor| tensor.id()).collect::<Vec<_>>();
+        for (compression, checksum) in [
+            (Compression::None, ChecksumAlgorithm::None),
+            (Compression::Zstd, ChecksumAlgorithm::Crc32c),
+            (Compression::Lz4, ChecksumAlgorithm::XxHash64),
+        ] {
+            let options = WriterOptions::new()
+                .with_alignment(64)
+                .with_compression(compression, 3)
+                .with_checksum(checksum)
+                .with_sort_by_name(true)
+                .with_dedup(true);
+            let tmp = NamedTempFile::new().unwrap();
+            let mut file = File::create(tmp.path()).await.unwrap();
+            let mut writer = TensorBuffersWriter::with_options(&mut file, options);
+            writer.write(tensors(), vec![]).await.unwrap();
+
+            let url = format!("file://{}", tmp.path().display());
+            let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+            let b = tensor_buffers.stored_metadata(ids[0]).await.unwrap();
+            let a = tensor_buffers.stored_metadata(ids[1]).await.unwrap();
+            let c = tensor_buffers.stored_metadata(ids[2]).await.unwrap();
+            let features = tensor_buffers.features().await.unwrap();
+            assert_eq!(features.is_empty(), compression == Compression::None);
+            assert_eq!(a.data_offset() % 64, 0);
+            assert_eq!(b.data_offset() % 64, 0);
+            assert!(a.data_offset() < b.data_offset());
+            assert_eq!(b.data_offset(), c.data_offset());
+            assert_eq!(b.compression(), compression);
+            assert_eq!(b.checksum_algorithm(), checksum);
+            if compression != Compression::None {
+                assert!(b.stored_size() < b.data_size());
+                // Too small to shrink, so stored uncompressed.
+                assert_eq!(a.compression(), Compression::None);
+            }
+
+            let c = tensor_buffers.get_tensor_data_by_name::<f32>("c").await.unwrap();
+            assert_eq!(c.data(), values.as_slice());
+            assert_eq!(c.shape(), [32, 32]);
+            let a = tensor_buffers.get_tensor_data_by_name::<f32>("a").await.unwrap();
+            assert_eq!(a.data(), [1.0, 2.0, 3.0]);
+        }
+
+        let mut file = File::create(NamedTempFile::new().unwrap().path()).await.unwrap();
+        let options = WriterOptions::new().with_alignment(3);
+        let mut writer = TensorBuffersWriter::with_options(&mut file, options);
+        assert!(writer.write(tensors(), vec![]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_streaming_decompression() {
+        // Several chunks of compressed data that compress to about half their size.
+        let mut state = 1u32;
+        let values = (0..1 << 20)
+            .map(|_| {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                u32::from_le_bytes((state >> 8).to_le_bytes().map(|byte| byte & 0x0f)) as f32
+            })
+            .collect::<Vec<_>>();
+        let tensor = Tensor::new("x", &values, vec![values.len()]);
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        let options = WriterOptions::new()
+            .with_compression(Compression::Zstd, 1)
+            .with_checksum(ChecksumAlgorithm::XxHash64);
+        let mut writer = TensorBuffersWriter::with_options(&mut file, options);
+        writer.write(vec![tensor], vec![]).await.unwrap();
+        drop(file);
+
+        // The budget fits the tensor and a chunk of its compressed data, but not all of it.
+        let url = format!("file://{}", tmp.path().display());
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        let metadata = tensor_buffers.stored_metadata(hash_key("x")).await.unwrap();
+        let size = metadata.data_size() as usize;
+        assert!(codec::stored_size(&metadata) > 2 * DECOMPRESS_CHUNK_SIZE);
+        assert!(codec::stored_size(&metadata) < size);
+        let budget = MemoryBudget::default().with_max_tensor_bytes(size + DECOMPRESS_CHUNK_SIZE);
+        let options = OpenOptions::new().with_memory_budget(budget);
+        let tensor_buffers = TensorBuffers::open_with(&url, options).await.unwrap();
+        let tensor = tensor_buffers.get_tensor_data_by_name::<f32>("x").await.unwrap();
+        assert!(tensor.data() == values);
+
+        // Corruption in the last chunk is still caught by the checksum.
+        let mut bytes = std::fs::read(tmp.path()).unwrap();
+        let end = metadata.data_offset() as usize + codec::stored_size(&metadata);
+        bytes[end - 100] ^= 1;
+        std::fs::write(tmp.path(), bytes).unwrap();
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        let error = tensor_buffers.get_tensor_data_by_name::<f32>("x").await.unwrap_err();
+        assert!(error.to_string().contains("checksum mismatch for tensor x"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_checksum_mismatch() {
+        let tensor = Tensor::new("x", &[1.0f32, 2.0, 3.0], vec![3]);
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        let options = WriterOptions::new().with_checksum(ChecksumAlgorithm::Crc32c);
+        let mut writer = TensorBuffersWriter::with_options(&mut file, options);
+        writer.write(vec![tensor], vec![]).await.unwrap();
+        drop(file);
+
+        // Flip a bit in the tensor's data, which starts right after the magic number.
+        let mut bytes = std::fs::read(tmp.path()).unwrap();
+        bytes[4] ^= 1;
+        std::fs::write(tmp.path(), bytes).unwrap();
+
+        let url = format!("file://{}", tmp.path().display());
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        let error = tensor_buffers.get_tensor_data_by_name::<f32>("x").await.unwrap_err();
+        assert!(error.to_string().contains("checksum mismatch for tensor x"));
+
+        let options = OpenOptions::new().with_verify_checksums(false);
+        let tensor_buffers = TensorBuffers::open_with(&url, options).await.unwrap();
+        assert!(tensor_buffers.get_tensor_data_by_name::<f32>("x").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_read_modes() {
+        // Two tensors out of id order, one of them with a data type this crate doesn't know.
+        let mut builder = FlatBufferBuilder::new();
+        let mut tensors = Vec::new();
+        for (name, data_type) in [("good", DataType::Float32), ("bad", DataType(42))] {
+            let (id, name) = (hash_key(name), builder.create_string(name));
+            let shape = builder.create_vector::<u32>(&[3]);
+            let args = TensorMetadataArgs {
+                id,
+                name: Some(name),
+                shape: Some(shape),
+                data_type,
+                data_offset: MAGIC_BYTES.len() as u32,
+                data_size: 12,
+                ..Default::default()
+            };
+            tensors.push(TensorMetadata::create(&mut builder, &args));
+        }
+        if hash_key("good") < hash_key("bad") {
+            tensors.reverse();
+        }
+        let tensors = builder.create_vector(&tensors);
+        let version = builder.create_string(VERSION);
+        let args = TensorBuffersMetadataArgs {
+            version: Some(version),
+            tensors: Some(tensors),
+            ..Default::default()
+        };
+        let root = TensorBuffersMetadata::create(&mut builder, &args);
+        builder.finish(root, None);
+        let metadata = builder.finished_data();
+        let data = [1.0f32, 2.0, 3.0];
+        let file = [
+            MAGIC_BYTES,
+            cast_slice(&data),
+            metadata,
+            &(metadata.len() as u32).to_le_bytes(),
+            MAGIC_BYTES,
+        ]
+        .concat();
+        let tmp = NamedTempFile::new().unwrap();
+        std::fs::write(tmp.path(), file).unwrap();
+        let url = format!("file://{}", tmp.path().display());
+
+        let strict = TensorBuffers::open(&url).await.unwrap();
+        let error = strict.get_tensor_data_by_name::<f32>("good").await.unwrap_err();
+        assert!(error.to_string().starts_with("Invalid metadata: Tensor bad has an unknown"));
+
+        let options = OpenOptions::new().with_read_mode(ReadMode::Lenient);
+        let lenient = TensorBuffers::open_with(&url, options).await.unwrap();
+        let good = lenient.get_tensor_data_by_name::<f32>("good").await.unwrap();
+        assert_eq!(good.data(), data);
+        let error = lenient.get_tensor_data_by_name::<f32>("bad").await.unwrap_err();
+        assert_eq!(error.to_string(), "Tensor bad has an unknown data type <UNKNOWN 42>");
+        let warnings = lenient.warnings().await.unwrap();
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(warnings[1], "Tensors are not sorted by id");
+    }
+
+    /// Builds a file of version `version` listing its tensors and operations out of id order, as
+    /// writers of 1.0.0 did when given them in that order.
+    fn unsorted_file(version: &str) -> Vec<u8> {
+        let mut builder = FlatBufferBuilder::new();
+        let mut entries = [("a", [1.0f32, 2.0, 3.0]), ("b", [4.0, 5.0, 6.0])];
+        entries.sort_by_key(|(name, _)| std::cmp::Reverse(hash_key(name)));
+        let mut tensors = Vec::new();
+        for (index, (name, _)) in entries.iter().enumerate() {
+            let (id, name) = (hash_key(name), builder.create_string(name));
+            let shape = builder.create_vector::<u32>(&[3]);
+            let args = TensorMetadataArgs {
+                id,
+                name: Some(name),
+                shape: Some(shape),
+                data_type: DataType::Float32,
+                data_offset: MAGIC_BYTES.len() as u32 + 12 * index as u32,
+                data_size: 12,
+                ..Default::default()
+            };
+            tensors.push(TensorMetadata::create(&mut builder, &args));
+        }
+        let tensors = builder.create_vector(&tensors);
+        let operations = [2, 1].map(|id| {
+            let args = OperationMetadataArgs { id, output: hash_key("a"), ..Default::default() };
+            OperationMetadata::create(&mut builder, &args)
+        });
+        let operations = builder.create_vector(&operations);
+        let version = builder.create_string(version);
+        let args = TensorBuffersMetadataArgs {
+            version: Some(version),
+            tensors: Some(tensors),
+            operations: Some(operations),
+            ..Default::default()
+        };
+        let root = TensorBuffersMetadata::create(&mut builder, &args);
+        builder.finish(root, None);
+        let metadata = builder.finished_data();
+        let data = entries.iter().flat_map(|(_, data)| *data).collect::<Vec<_>>();
+        [
+            MAGIC_BYTES,
+            cast_slice(&data),
+            metadata,
+            &(metadata.len() as u32).to_le_bytes(),
+            MAGIC_BYTES,
+        ]
+        .concat()
+    }
+
+    #[tokio::test]
+    async fn test_unsorted_baseline_file() {
+        let tmp = NamedTempFile::new().unwrap();
+        std::fs::write(tmp.path(), unsorted_file("1.0.0")).unwrap();
+        let url = format!("file://{}", tmp.path().display());
+
+        // 1.0.0 didn't require sorted ids, so strict mode finds every entry by a linear scan.
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        let a = tensor_buffers.get_tensor_data_by_name::<f32>("a").await.unwrap();
+        assert_eq!(a.data(), [1.0, 2.0, 3.0]);
+        let b = tensor_buffers.get_tensor_data_by_name::<f32>("b").await.unwrap();
+        assert_eq!(b.data(), [4.0, 5.0, 6.0]);
+        assert!(tensor_buffers.get_tensor_data_by_name::<f32>("missing").await.is_err());
+        for id in [1, 2] {
+            assert_eq!(tensor_buffers.get_tensor_operation_by_id(id).await.unwrap().id(), id);
+        }
+        assert!(tensor_buffers.warnings().await.unwrap().is_empty());
+
+        // Later versions promise sorted ids, so strict mode still rejects files breaking it.
+        std::fs::write(tmp.path(), unsorted_file("1.1.0")).unwrap();
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        let error = tensor_buffers.get_tensor_data_by_name::<f32>("a").await.unwrap_err();
+        assert!(error.to_string().contains("Tensors are not sorted by id"));
+    }
+
+    #[tokio::test]
+    async fn test_memory_budget() {
+        let tensor = Tensor::new("x", &[1.0f32, 2.0, 3.0], vec![3]);
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        TensorBuffersWriter::new(&mut file).write(vec![tensor], vec![]).await.unwrap();
+
+        let url = format!("file://{}", tmp.path().display());
+        let budget = MemoryBudget::default().with_max_tensor_bytes(8);
+        let options = OpenOptions::new().with_memory_budget(budget);
+        let tensor_buffers = TensorBuffers::open_with(&url, options).await.unwrap();
+        let error = tensor_buffers.get_tensor_data_by_name::<f32>("x").await.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Reading tensor x needs 12 bytes, over the budget of 8 bytes per tensor"
+        );
+        assert!(tensor_buffers.read_bytes(0, 8).await.is_ok());
+        assert!(tensor_buffers.read_bytes(0, 16).await.is_err());
+
+        let budget = MemoryBudget::default().with_max_outstanding_bytes(12);
+        let options = OpenOptions::new().with_memory_budget(budget);
+        let tensor_buffers = TensorBuffers::open_with(&url, options).await.unwrap();
+        let (x, y) = tokio::join!(
+            tensor_buffers.get_tensor_data_by_name::<f32>("x"),
+            tensor_buffers.get_tensor_data_by_name::<f32>("x")
+        );
+        assert_eq!(x.unwrap().data(), y.unwrap().data());
+    }
+
+    #[tokio::test]
+    async fn test_buffer_alignment() {
+        let values = (0..100).map(|i| i as f32).collect::<Vec<_>>();
+        let tensors =
+            vec![Tensor::new("raw", &values, vec![100]), Tensor::new("one", &[1.0f32], vec![1])];
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        let options = WriterOptions::new().with_compression(Compression::Lz4, 0);
+        let mut writer = TensorBuffersWriter::with_options(&mut file, options);
+        writer.write(tensors, vec![]).await.unwrap();
+
+        let url = format!("file://{}", tmp.path().display());
+        for alignment in [64, 4096] {
+            let options = OpenOptions::new().with_buffer_alignment(alignment);
+            let tensor_buffers = TensorBuffers::open_with(&url, options).await.unwrap();
+            let raw = tensor_buffers.get_tensor_data_by_name::<f32>("raw").await.unwrap();
+            assert_eq!(raw.data(), values);
+            assert_eq!(raw.data().as_ptr() as usize % alignment, 0);
+            let one = tensor_buffers.get_tensor_data_by_name::<f32>("one").await.unwrap();
+            assert_eq!(one.data().as_ptr() as usize % alignment, 0);
+            assert_eq!(one.clone().data().as_ptr() as usize % alignment, 0);
+        }
+
+        let options = OpenOptions::new().with_buffer_alignment(3);
+        let error = TensorBuffers::open_with(&url, options).await.err().unwrap();
+        assert_eq!(error.to_string(), "Buffer alignment 3 is not a power of two");
+    }
+
+    #[tokio::test]
+    async fn test_shared_tensor_data() {
+        let values = (0..1000).map(|i| i as f32).collect::<Vec<_>>();
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        let tensor = Tensor::new("w", &values, vec![1000]);
+        TensorBuffersWriter::new(&mut file).write(vec![tensor], vec![]).await.unwrap();
+
+        let url = format!("file://{}", tmp.path().display());
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        let tensor = tensor_buffers.get_tensor_data_by_name::<f32>("w").await.unwrap();
+        let address = tensor.data().as_ptr() as usize;
+        assert_eq!(tensor.to_bytes().as_ptr() as usize, address);
+        assert_eq!(tensor.as_slice::<u8>().unwrap().len(), 4000);
+
+        // Clones on other threads share the loaded buffer.
+        let workers = (0..4)
+            .map(|_| {
+                let tensor = tensor.clone();
+                std::thread::spawn(move || (tensor.data().as_ptr() as usize, tensor.data()[999]))
+            })
+            .collect::<Vec<_>>();
+        for worker in workers {
+            assert_eq!(worker.join().unwrap(), (address, 999.0));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_prefetch() {
+        let tensors = vec![
+            Tensor::new("a", &[1.0f32, 2.0], vec![2]),
+            Tensor::new("b", &[3.0f32, 4.0, 5.0], vec![3]),
+        ];
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        TensorBuffersWriter::new(&mut file).write(tensors, vec![]).await.unwrap();
+
+        let url = format!("file://{}", tmp.path().display());
+        let budget = PrefetchBudget::default().with_max_concurrent(1).with_max_cached_bytes(64);
+        let options = OpenOptions::new().with_prefetch_budget(budget).with_verify_checksums(false);
+        let tensor_buffers = TensorBuffers::open_with(&url, options).await.unwrap();
+        tensor_buffers.prefetch(&["a"], PrefetchPriority::Warmup).await.unwrap();
+        tensor_buffers.prefetch(&["b"], PrefetchPriority::HotPath).await.unwrap();
+        assert!(tensor_buffers.prefetch(&["missing"], PrefetchPriority::HotPath).await.is_err());
+        let wait = async {
+            while tensor_buffers.prefetched_bytes() < 20 {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(5), wait).await.unwrap();
+
+        // Loads use the prefetched data rather than the file, whose tensor data is now zeroed.
+        let mut bytes = std::fs::read(tmp.path()).unwrap();
+        bytes[4..24].fill(0);
+        std::fs::write(tmp.path(), &bytes).unwrap();
+        let b = tensor_buffers.get_tensor_data_by_name::<f32>("b").await.unwrap();
+        assert_eq!(b.data(), [3.0, 4.0, 5.0]);
+        assert_eq!(tensor_buffers.prefetched_bytes(), 8);
+        let a = tensor_buffers.get_tensor_data_by_name::<f32>("a").await.unwrap();
+        assert_eq!(a.data(), [1.0, 2.0]);
+        assert_eq!(tensor_buffers.prefetched_bytes(), 0);
+        let a = tensor_buffers.get_tensor_data_by_name::<f32>("a").await.unwrap();
+        assert_eq!(a.data(), [0.0, 0.0]);
+    }
+
+    #[tokio::test]
+    async fn test_get_tensors_data_by_name() {
+        let values = (0..64).map(|i| i as f32).collect::<Vec<_>>();
+        let tensors = vec![
+            Tensor::new("a", &values[..8], vec![8]),
+            Tensor::new("b", &values, vec![8, 8]),
+            Tensor::new("c", &values[..2], vec![2]),
+        ];
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        let options = WriterOptions::new()
+            .with_checksum(ChecksumAlgorithm::Crc32c)
+            .with_compression(Compression::Zstd, 0);
+        let mut writer = TensorBuffersWriter::with_options(&mut file, options);
+        writer.write(tensors, vec![]).await.unwrap();
+
+        let observer = Arc::new(RecordingObserver::default());
+        let url = format!("file://{}", tmp.path().display());
+        let options = OpenOptions::new().with_observer(observer.clone());
+        let tensor_buffers = TensorBuffers::open_with(&url, options).await.unwrap();
+        tensor_buffers.get_metadata_root().await.unwrap();
+        observer.events.lock().unwrap().clear();
+        let loaded =
+            tensor_buffers.get_tensors_data_by_name::<f32>(&["c", "a", "b"]).await.unwrap();
+        assert_eq!(loaded[0].data(), &values[..2]);
+        assert_eq!(loaded[1].data(), &values[..8]);
+        assert_eq!(loaded[2].data(), values);
+        assert_eq!(loaded[2].shape(), [8, 8]);
+
+        // All three tensors come from a single read.
+        let events = observer.events.lock().unwrap().clone();
+        assert_eq!(events.iter().filter(|event| event.starts_with("start")).count(), 1);
+        assert_eq!(events.iter().filter(|event| event.starts_with("loaded")).count(), 3);
+
+        let error = tensor_buffers.get_tensors_data_by_name::<f64>(&["a"]).await.unwrap_err();
+        assert!(error.to_string().starts_with("Tensor data type mismatch"));
+        assert!(tensor_buffers.get_tensors_data_by_name::<f32>(&["a", "x"]).await.is_err());
+        assert!(tensor_buffers.get_tensors_data_by_name::<f32>(&[]).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_load_many() {
+        let values = (0..32).map(|i| i as f32).collect::<Vec<_>>();
+        let names = (0..8).map(|i| format!("t{}", i)).collect::<Vec<_>>();
+        let tensors = names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| Tensor::new(name, &values[i..i + 4], vec![4]))
+            .collect();
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        TensorBuffersWriter::new(&mut file).write(tensors, vec![]).await.unwrap();
+
+        let url = format!("file://{}", tmp.path().display());
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        let loaded = tensor_buffers.load_many::<f32>(&["t5", "t1", "t5"], 2).await.unwrap();
+        assert_eq!(loaded[0].data(), &values[5..9]);
+        assert_eq!(loaded[1].data(), &values[1..5]);
+        assert_eq!(loaded[2].data(), &values[5..9]);
+        assert!(tensor_buffers.load_many::<f32>(&["t1", "missing"], 4).await.is_err());
+
+        for concurrency in [0, 1, 16] {
+            let all = tensor_buffers.load_all::<f32>(concurrency).await.unwrap();
+            assert_eq!(all.len(), 8);
+            let mut ids = all.iter().map(|tensor| tensor.id()).collect::<Vec<_>>();
+            ids.sort();
+            assert_eq!(ids, all.iter().map(|tensor| tensor.id()).collect::<Vec<_>>());
+        }
+        assert!(tensor_buffers.load_all::<i32>(4).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_stream_tensor_bytes() {
+        let values = (0..1000).map(|i| i as f32).collect::<Vec<_>>();
+        let tensors = vec![Tensor::new("w", &values, vec![1000]), Tensor::new("e", &[], vec![0])];
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        let options = WriterOptions::new().with_checksum(ChecksumAlgorithm::XxHash64);
+        let mut writer = TensorBuffersWriter::with_options(&mut file, options);
+        writer.write(tensors, vec![]).await.unwrap();
+
+        let url = format!("file://{}", tmp.path().display());
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        let chunks = tensor_buffers.stream_tensor_bytes("w", 1024).await.unwrap();
+        let chunks = chunks.try_collect::<Vec<_>>().await.unwrap();
+        assert_eq!(chunks.iter().map(|chunk| chunk.len()).collect::<Vec<_>>(), [
+            1024, 1024, 1024, 928
+        ]);
+        assert_eq!(chunks.concat(), cast_slice::<f32, u8>(&values));
+        let empty = tensor_buffers.stream_tensor_bytes("e", 16).await.unwrap();
+        assert_eq!(empty.try_collect::<Vec<_>>().await.unwrap().len(), 0);
+        assert!(tensor_buffers.stream_tensor_bytes("w", 0).await.is_err());
+
+        // Corrupt data is only detected after the last chunk.
+        let mut bytes = std::fs::read(tmp.path()).unwrap();
+        bytes[100] ^= 1;
+        std::fs::write(tmp.path(), &bytes).unwrap();
+        let chunks = tensor_buffers.stream_tensor_bytes("w", 3000).await.unwrap();
+        let results = chunks.collect::<Vec<_>>().await;
+        assert!(results[0].is_ok() && results[1].is_ok());
+        assert!(results[2].as_ref().unwrap_err().to_string().contains("checksum mismatch"));
+    }
+
+    #[tokio::test]
+    async fn test_tensor_reader() {
+        use tokio::io::AsyncReadExt;
+
+        let values = (0..40_000).map(|i| i as f32).collect::<Vec<_>>();
+        let tensors = vec![Tensor::new("w", &values, vec![40_000])];
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        let options = WriterOptions::new().with_checksum(ChecksumAlgorithm::Crc32c);
+        let mut writer = TensorBuffersWriter::with_options(&mut file, options);
+        writer.write(tensors, vec![]).await.unwrap();
+
+        let url = format!("file://{}", tmp.path().display());
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        let mut reader = tensor_buffers.tensor_reader("w").await.unwrap();
+        let mut head = [0; 8];
+        reader.read_exact(&mut head).await.unwrap();
+        assert_eq!(head, cast_slice::<f32, u8>(&values[..2]));
+        let mut out = Vec::new();
+        tokio::io::copy(&mut reader, &mut out).await.unwrap();
+        assert_eq!(out, cast_slice::<f32, u8>(&values[2..]));
+        assert!(tensor_buffers.tensor_reader("missing").await.is_err());
     }
 }
@@ -2,75 +2,388 @@ use std::{
     future::Future,
     io::{Error, ErrorKind, Result, SeekFrom},
     pin::Pin,
+    sync::Arc,
     task::{ready, Context, Poll},
+    time::{Duration, Instant},
 };
 
 use bytes::Bytes;
+use futures::future::join_all;
 use tokio::{
     fs::File,
     io::{AsyncRead, AsyncSeek, ReadBuf},
+    sync::OwnedSemaphorePermit,
+    time::sleep,
 };
-use tracing::{debug, info};
+use tracing::{debug, field::Empty, instrument, warn, Span};
+
+use crate::{
+    metrics,
+    mirrors::{MirrorProbe, OriginState, Origins, FINGERPRINT_SIZE},
+    open_options::OpenOptions,
+    range_cache::{RangeCache, RangeKey},
+    rate_limiter::RateLimiter,
+    stats::ReadCounters,
+    timeouts::Timeouts,
+    utils::{elapsed_ms, loggable_url},
+};
+
+/// Delay before the first retry, growing linearly with each further retry.
+const RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// Most bytes read from each origin to measure how fast it is.
+const PROBE_SIZE: u64 = 64 << 10;
+
 enum ReadState {
     Idle,
     Fetch(Pin<Box<dyn Future<Output = Result<Bytes>> + Send>>),
 }
 
 pub struct RemoteFile {
-    url: String,
+    origins: Arc<Origins>,
     offset: u64,
     file_size: u64,
+    etag: Option<String>,
+    http: HttpConfig,
     state: ReadState,
 }
 
+/// How a remote file sends its HTTP requests.
+#[derive(Clone)]
+struct HttpConfig {
+    client: reqwest::Client,
+    request_timeout: Option<Duration>,
+    max_retries: u32,
+    counters: Arc<ReadCounters>,
+    rate_limiter: Option<RateLimiter>,
+    range_cache: Option<Arc<dyn RangeCache>>,
+}
+
+impl HttpConfig {
+    /// Adds the request timeout, if any, to `request`.
+    fn prepare(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match self.request_timeout {
+            Some(timeout) => request.timeout(timeout),
+            None => request,
+        }
+    }
+
+    /// Waits until the rate limiter, if any, lets a request for `bytes` bytes start. The request
+    /// counts as in flight until the returned permit is dropped.
+    async fn acquire(&self, bytes: u64) -> Option<OwnedSemaphorePermit> {
+        match &self.rate_limiter {
+            Some(rate_limiter) => Some(rate_limiter.acquire(bytes).await),
+            None => None,
+        }
+    }
+}
+
 impl RemoteFile {
     pub async fn open(url: &str) -> Result<Self> {
-        let file_size = Self::fetch_file_size(url.to_string()).await?;
+        Self::open_with(url, &OpenOptions::new()).await
+    }
 
-        Ok(RemoteFile { url: url.to_string(), file_size, offset: 0, state: ReadState::Idle })
+    /// Opens a remote file whose HTTP requests each fail if they take longer than
+    /// `request_timeout`, including reading the response body.
+    pub async fn open_with_timeout(url: &str, request_timeout: Option<Duration>) -> Result<Self> {
+        let timeouts = Timeouts { request: request_timeout, ..Timeouts::default() };
+        Self::open_with(url, &OpenOptions::new().with_timeouts(timeouts)).await
+    }
+
+    /// Opens a remote file using the client, request timeout, retry count and mirrors from
+    /// `options`.
+    pub async fn open_with(url: &str, options: &OpenOptions) -> Result<Self> {
+        Self::open_counted(url, options, Arc::default()).await
+    }
+
+    /// Opens a remote file like `open_with` that counts its range requests in `counters`.
+    pub(crate) async fn open_counted(
+        url: &str,
+        options: &OpenOptions,
+        counters: Arc<ReadCounters>,
+    ) -> Result<Self> {
+        let http = HttpConfig {
+            client: options.client().cloned().unwrap_or_default(),
+            request_timeout: options.timeouts().request,
+            max_retries: options.max_retries(),
+            counters,
+            rate_limiter: options.rate_limiter().cloned(),
+            range_cache: options.range_cache().cloned(),
+        };
+        let origins = options.origins(url);
+        if origins.balanced() {
+            Self::probe_origins(&origins, &http).await;
+        }
+        let (file_size, etag) = Self::open_origin(&origins, &http).await?;
+
+        Ok(RemoteFile { origins, file_size, etag, offset: 0, http, state: ReadState::Idle })
+    }
+
+    /// Returns the size of the file in bytes, as the server reported it when the file was opened.
+    pub fn file_size(&self) -> u64 {
+        self.file_size
+    }
+
+    /// Returns the ETag the server reported for the file when it was opened, if any.
+    pub fn etag(&self) -> Option<&str> {
+        self.etag.as_deref()
     }
 }
 
 impl RemoteFile {
-    // Fetches and caches the file size from the remote server using a HEAD request.
-    async fn fetch_file_size(url: String) -> Result<u64> {
-        let client = reqwest::Client::new();
+    /// Asks the origins for the file's size and fingerprint, from the current one on, and sends
+    /// later requests to the first that answers with the file's. Origins probing already checked
+    /// aren't asked again.
+    ///
+    /// # Returns
+    /// The file's size and the ETag of the origin that answered.
+    async fn open_origin(origins: &Origins, http: &HttpConfig) -> Result<(u64, Option<String>)> {
+        let mut last_error = None;
+        for index in origins.candidates() {
+            let checked = match origins.state(index) {
+                OriginState::Valid => Ok(true),
+                _ => Self::check_origin(origins, index, http).await,
+            };
+            match checked {
+                Ok(true) => {
+                    let size = origins.expected().map_or(0, |(size, _)| *size);
+                    return Ok((size, origins.etag(index)));
+                }
+                Ok(false) => {}
+                Err(error) => {
+                    origins.demote(index);
+                    last_error = Some(error);
+                }
+            }
+        }
+        Err(last_error.unwrap_or_else(|| Error::new(ErrorKind::Other, "No mirror serves the file")))
+    }
+
+    /// Measures how fast each origin is, once per file, so requests prefer the fastest. Each is
+    /// asked for the file's size and timed sending up to `PROBE_SIZE` bytes at its end, which
+    /// reading it starts with anyway and which hold the fingerprint it is checked on.
+    async fn probe_origins(origins: &Origins, http: &HttpConfig) {
+        // A probe that fails isn't retried, the origin is only ranked last.
+        let http = &HttpConfig { max_retries: 0, ..http.clone() };
+        let probe = async {
+            let probes = join_all((0..origins.len()).map(|index| async move {
+                let start = Instant::now();
+                let url = origins.url(index).to_string();
+                let (size, etag) = Self::fetch_file_size(url.clone(), http.clone()).await.ok()?;
+                let latency = start.elapsed();
+                let (bytes_per_second, fingerprint) =
+                    Self::measure_throughput(&url, size, http).await?;
+                debug!(url = loggable_url(&url), ?latency, bytes_per_second, "Probed origin");
+                let probe = MirrorProbe { url, latency, bytes_per_second };
+                Some((index, size, fingerprint, etag, probe))
+            }))
+            .await;
+            // Checked in order, so the file's own URL defines its size and fingerprint if it
+            // answered. Origins serving another file aren't ranked.
+            probes
+                .into_iter()
+                .flatten()
+                .filter_map(|(index, size, fingerprint, etag, probe)| {
+                    origins.check(index, size, fingerprint, etag).then_some((index, probe))
+                })
+                .collect()
+        };
+        origins.probe_once(probe).await;
+    }
+
+    /// Times fetching the last `PROBE_SIZE` bytes of a file of `size` bytes from `url`.
+    ///
+    /// # Returns
+    /// The bytes per second and the file's fingerprint, or `None` if the request failed.
+    async fn measure_throughput(url: &str, size: u64, http: &HttpConfig) -> Option<(f64, Bytes)> {
+        let probe_size = size.min(PROBE_SIZE);
+        if probe_size == 0 {
+            return Some((0.0, Bytes::new()));
+        }
+        let start = Instant::now();
+        let (bytes, _) =
+            Self::fetch_range(url.to_string(), size - probe_size, probe_size, http.clone())
+                .await
+                .ok()?;
+        let bytes_per_second = bytes.len() as f64 / start.elapsed().as_secs_f64();
+        Some((bytes_per_second, Self::fingerprint(bytes)))
+    }
+
+    /// Asks an origin for the file's size and fingerprint and checks them against the file's.
+    /// Files without mirrors have nothing to compare, so they skip fetching the fingerprint.
+    async fn check_origin(origins: &Origins, index: usize, http: &HttpConfig) -> Result<bool> {
+        let url = origins.url(index).to_string();
+        let (size, etag) = Self::fetch_file_size(url.clone(), http.clone()).await?;
+        let fingerprint_size = size.min(FINGERPRINT_SIZE);
+        let fingerprint = match origins.len() > 1 && fingerprint_size > 0 {
+            true => {
+                let offset = size - fingerprint_size;
+                let (bytes, _) =
+                    Self::fetch_range(url, offset, fingerprint_size, http.clone()).await?;
+                Self::fingerprint(bytes)
+            }
+            false => Bytes::new(),
+        };
+        Ok(origins.check(index, size, fingerprint, etag))
+    }
+
+    /// Returns the last `FINGERPRINT_SIZE` bytes of a range fetched from the end of the file.
+    fn fingerprint(bytes: Bytes) -> Bytes {
+        bytes.slice(bytes.len().saturating_sub(FINGERPRINT_SIZE as usize)..)
+    }
+
+    // Fetches the file size and ETag from the remote server using a HEAD request.
+    #[instrument(level = "debug", skip_all, fields(url = loggable_url(&url), size = Empty))]
+    async fn fetch_file_size(url: String, http: HttpConfig) -> Result<(u64, Option<String>)> {
+        let _permit = http.acquire(0).await;
         let response =
-            client.head(url).send().await.map_err(|e| {
+            http.prepare(http.client.head(url)).send().await.map_err(|e| {
                 Error::new(ErrorKind::Other, format!("Failed to send request: {}", e))
             })?;
         if response.status().is_success() {
             if let Some(content_length) = response.headers().get(reqwest::header::CONTENT_LENGTH) {
-                debug!("Content-Length: {:?}", content_length);
                 if let Ok(size) = content_length.to_str() {
                     let parsed_size = size.parse::<u64>().map_err(|_| {
                         Error::new(ErrorKind::InvalidData, "Invalid content length")
                     })?;
-                    debug!("File size: {}", parsed_size);
-                    return Ok(parsed_size);
+                    Span::current().record("size", parsed_size);
+                    return Ok((parsed_size, Self::etag_of(&response)));
                 }
             }
         }
         Err(Error::new(ErrorKind::Other, "Failed to get file size"))
     }
 
-    async fn fetch_range(url: String, offset: u64, size: u64) -> Result<Bytes> {
-        let client = reqwest::Client::new();
+    /// Reads `[offset, offset + size)` from the range cache, if any, or fetches it and caches it.
+    async fn read_range(
+        origins: Arc<Origins>,
+        etag: Option<String>,
+        offset: u64,
+        size: u64,
+        http: HttpConfig,
+    ) -> Result<Bytes> {
+        let Some(cache) = http.range_cache.clone() else {
+            return Self::fetch_from_origins(&origins, offset, size, http).await;
+        };
+        // Mirrors serve the same file, so their ranges are cached under the file's own URL.
+        let url = origins.primary().to_string();
+        let key = RangeKey { url, etag, start: offset, end: offset + size };
+        if let Some(bytes) = cache.get(&key).await.filter(|bytes| bytes.len() as u64 == size) {
+            debug!(offset, size, "Read range from cache");
+            return Ok(bytes);
+        }
+        let bytes = Self::fetch_from_origins(&origins, offset, size, http).await?;
+        if bytes.len() as u64 == size {
+            cache.put(&key, bytes.clone()).await;
+        }
+        Ok(bytes)
+    }
+
+    /// Fetches `[offset, offset + size)` from the current origin, failing over to the next one
+    /// while the request fails. Mirrors not read from yet are first checked to serve the file, and
+    /// origins whose ETag changed since are left out.
+    async fn fetch_from_origins(
+        origins: &Origins,
+        offset: u64,
+        size: u64,
+        http: HttpConfig,
+    ) -> Result<Bytes> {
+        let mut last_error = None;
+        for (attempt, index) in origins.candidates().into_iter().enumerate() {
+            if origins.state(index) == OriginState::Unchecked {
+                match Self::check_origin(origins, index, &http).await {
+                    Ok(true) => {}
+                    Ok(false) => continue,
+                    Err(error) => {
+                        last_error = Some(error);
+                        continue;
+                    }
+                }
+            }
+            if attempt > 0 {
+                warn!(url = loggable_url(origins.url(index)), "Failing over to mirror");
+                http.counters.record_failover();
+            }
+            match Self::fetch_range(origins.url(index).to_string(), offset, size, http.clone())
+                .await
+            {
+                Ok((bytes, etag)) if origins.pinned(index, etag.as_deref()) => return Ok(bytes),
+                Ok(_) => {
+                    let error = "Remote file changed while reading";
+                    last_error = Some(Error::new(ErrorKind::InvalidData, error));
+                }
+                Err(error) => {
+                    origins.demote(index);
+                    last_error = Some(error);
+                }
+            }
+        }
+        Err(last_error.unwrap_or_else(|| Error::new(ErrorKind::Other, "No mirror serves the file")))
+    }
+
+    /// Fetches `[offset, offset + size)` from `url`, retrying requests that fail to send or get a
+    /// server error up to `http.max_retries` times.
+    ///
+    /// # Returns
+    /// The bytes and the ETag of the response, if any.
+    #[instrument(
+        level = "debug",
+        skip(url, http),
+        fields(url = loggable_url(&url), retries = Empty, elapsed_ms = Empty)
+    )]
+    async fn fetch_range(
+        url: String,
+        offset: u64,
+        size: u64,
+        http: HttpConfig,
+    ) -> Result<(Bytes, Option<String>)> {
+        let start = Instant::now();
         let range = format!("bytes={}-{}", offset, offset + size - 1);
-        let response =
-            client.get(url).header(reqwest::header::RANGE, range).send().await.map_err(|e| {
-                Error::new(ErrorKind::Other, format!("Failed to send request: {}", e))
-            })?;
+        let mut retries = 0;
+        // The permit is held until the response is read, and given back while waiting to retry.
+        let mut permit;
+        let response = loop {
+            permit = http.acquire(size).await;
+            metrics::record_range_request();
+            http.counters.record_range_request();
+            let request = http.client.get(&url).header(reqwest::header::RANGE, &range);
+            let result = http.prepare(request).send().await;
+            let retryable = match &result {
+                Ok(response) => response.status().is_server_error(),
+                Err(_) => true,
+            };
+            if !retryable || retries == http.max_retries {
+                break result.map_err(|e| {
+                    Error::new(ErrorKind::Other, format!("Failed to send request: {}", e))
+                })?;
+            }
+            retries += 1;
+            metrics::record_range_request_retry();
+            http.counters.record_retry();
+            Span::current().record("retries", retries);
+            warn!(retries, max_retries = http.max_retries, "Retrying range request");
+            drop(permit);
+            sleep(RETRY_DELAY * retries).await;
+        };
 
         if response.status().is_success() {
+            let etag = Self::etag_of(&response);
             let bytes = response.bytes().await.map_err(|e| {
                 Error::new(ErrorKind::Other, format!("Failed to read response: {}", e))
             })?;
-            Ok(bytes)
+            drop(permit);
+            Span::current().record("elapsed_ms", elapsed_ms(start));
+            Ok((bytes, etag))
         } else {
             Err(Error::new(ErrorKind::Other, "Failed to read remote file"))
         }
     }
+
+    /// Returns the ETag `response` was sent with, if any.
+    fn etag_of(response: &reqwest::Response) -> Option<String> {
+        let etag = response.headers().get(reqwest::header::ETAG)?;
+        etag.to_str().ok().map(str::to_string)
+    }
 }
 
 impl AsyncRead for RemoteFile {
@@ -88,13 +401,17 @@ impl AsyncRead for RemoteFile {
                     if size == 0 {
                         return Poll::Ready(Ok(()));
                     }
-                    debug!("Fetching {} bytes from offset {}", size, this.offset);
-                    let fut = Box::pin(Self::fetch_range(this.url.clone(), this.offset, size));
+                    let fut = Box::pin(Self::read_range(
+                        this.origins.clone(),
+                        this.etag.clone(),
+                        this.offset,
+                        size,
+                        this.http.clone(),
+                    ));
                     this.state = ReadState::Fetch(fut);
                 }
                 ReadState::Fetch(fut) => {
                     let bytes = ready!(fut.as_mut().poll(cx))?;
-                    debug!("Fetched {} bytes", bytes.len());
                     buf.put_slice(&bytes);
                     this.offset += bytes.len() as u64;
                     this.state = ReadState::Idle;
@@ -108,6 +425,9 @@ impl AsyncRead for RemoteFile {
 impl AsyncSeek for RemoteFile {
     fn start_seek(self: Pin<&mut Self>, position: SeekFrom) -> Result<()> {
         let this = self.get_mut();
+        // Drop any fetch left pending by a cancelled read, so it can't fill a later read with
+        // bytes from the old offset.
+        this.state = ReadState::Idle;
 
         match position {
             SeekFrom::Start(pos) => this.offset = pos,
@@ -124,7 +444,7 @@ impl AsyncSeek for RemoteFile {
                     .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "Seek overflow"))?;
             }
         }
-        debug!("Seek to offset: {}", this.offset);
+        debug!(offset = this.offset, "Seeked remote file");
         Ok(())
     }
 
@@ -135,24 +455,46 @@ impl AsyncSeek for RemoteFile {
 
 impl Drop for RemoteFile {
     fn drop(&mut self) {
-        info!("Dropping RemoteFile");
+        debug!(url = loggable_url(self.origins.primary()), "Dropping RemoteFile");
     }
 }
-pub enum TensorBuffersFile {
+/// A local or remote TensorBuffers file, counting the bytes read from it.
+pub struct TensorBuffersFile {
+    source: Source,
+    counters: Arc<ReadCounters>,
+}
+
+enum Source {
     Local(File),
     Remote(RemoteFile),
 }
 
 impl TensorBuffersFile {
-    pub async fn open(url: &str) -> Result<Self> {
-        if url.starts_with("file://") {
+    /// Opens a local or remote file. Remote files are read with the HTTP settings in `options`.
+    ///
+    /// # Arguments
+    /// * `url` - A `file://` or `https://` URL.
+    /// * `options` - The HTTP settings.
+    /// * `counters` - Where the bytes read and the range requests sent are counted.
+    pub async fn open(
+        url: &str,
+        options: &OpenOptions,
+        counters: Arc<ReadCounters>,
+    ) -> Result<Self> {
+        let source = if url.starts_with("file://") {
             let path = &url[7..];
-            Ok(TensorBuffersFile::Local(File::open(path).await?))
+            Source::Local(File::open(path).await?)
         } else if url.starts_with("https://") {
-            Ok(TensorBuffersFile::Remote(RemoteFile::open(url).await?))
+            Source::Remote(RemoteFile::open_counted(url, options, counters.clone()).await?)
         } else {
-            Err(Error::new(ErrorKind::InvalidInput, "Unsupported URI scheme"))
-        }
+            return Err(Error::new(ErrorKind::InvalidInput, "Unsupported URI scheme"));
+        };
+        Ok(TensorBuffersFile { source, counters })
+    }
+
+    /// Returns where the reads of the file are counted.
+    pub fn counters(&self) -> &Arc<ReadCounters> {
+        &self.counters
     }
 }
 
@@ -162,37 +504,49 @@ impl AsyncRead for TensorBuffersFile {
         cx: &mut Context<'_>,
         buf: &mut ReadBuf<'_>,
     ) -> Poll<Result<()>> {
-        match self.get_mut() {
-            TensorBuffersFile::Local(file) => Pin::new(file).poll_read(cx, buf),
-            TensorBuffersFile::Remote(remote) => Pin::new(remote).poll_read(cx, buf),
-        }
+        let filled = buf.filled().len();
+        let this = self.get_mut();
+        let (source, result) = match &mut this.source {
+            Source::Local(file) => ("local", ready!(Pin::new(file).poll_read(cx, buf))),
+            Source::Remote(remote) => ("remote", ready!(Pin::new(remote).poll_read(cx, buf))),
+        };
+        let bytes = buf.filled().len() - filled;
+        metrics::record_bytes_read(source, bytes);
+        this.counters.record_bytes_read(bytes);
+        Poll::Ready(result)
     }
 }
 
 impl AsyncSeek for TensorBuffersFile {
     fn start_seek(self: Pin<&mut Self>, position: SeekFrom) -> Result<()> {
-        match self.get_mut() {
-            TensorBuffersFile::Local(file) => Pin::new(file).start_seek(position),
-            TensorBuffersFile::Remote(remote) => Pin::new(remote).start_seek(position),
+        match &mut self.get_mut().source {
+            Source::Local(file) => Pin::new(file).start_seek(position),
+            Source::Remote(remote) => Pin::new(remote).start_seek(position),
         }
     }
 
     fn poll_complete(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<u64>> {
-        match self.get_mut() {
-            TensorBuffersFile::Local(file) => Pin::new(file).poll_complete(cx),
-            TensorBuffersFile::Remote(remote) => Pin::new(remote).poll_complete(cx),
+        match &mut self.get_mut().source {
+            Source::Local(file) => Pin::new(file).poll_complete(cx),
+            Source::Remote(remote) => Pin::new(remote).poll_complete(cx),
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
     use bytes::BytesMut;
-    use tokio::io::{AsyncReadExt, AsyncSeekExt};
+    use tokio::{
+        io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt},
+        net::TcpListener,
+    };
 
     use super::*;
 
     #[tokio::test]
+    #[allow(clippy::unused_io_amount)]
     async fn test_remote_file() {
         let mut remote_file = RemoteFile::open(
             "https://raw.githubusercontent.com/russellwmy/tensorbuffers/refs/heads/main/LICENSE",
@@ -210,4 +564,313 @@ mod tests {
         remote_file.read(&mut buf).await.unwrap();
         assert!(buf.len() == 1024);
     }
+
+    /// Accepts one connection and answers its request with `status` and `body`.
+    async fn respond(listener: &TcpListener, status: &str, body: &str) {
+        respond_with_etag(listener, status, "\"v1\"", body).await;
+    }
+
+    /// Accepts one connection and answers its request with `status`, `etag` and `body`.
+    async fn respond_with_etag(listener: &TcpListener, status: &str, etag: &str, body: &str) {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut request = [0; 1024];
+        let _ = stream.read(&mut request).await.unwrap();
+        let response = format!(
+            "HTTP/1.1 {}\r\nConnection: close\r\nETag: {}\r\nContent-Length: 4\r\n\r\n{}",
+            status, etag, body
+        );
+        stream.write_all(response.as_bytes()).await.unwrap();
+    }
+
+    /// Answers every request on `listener` like `respond`, range requests after `delay`, and
+    /// counts the range requests in `ranges`.
+    async fn serve(listener: TcpListener, delay: Duration, ranges: Arc<AtomicUsize>) {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let ranges = ranges.clone();
+            tokio::spawn(async move {
+                let mut request = [0; 1024];
+                let read = stream.read(&mut request).await.unwrap();
+                let (status, body) = match request[..read].starts_with(b"HEAD") {
+                    true => ("200 OK", ""),
+                    false => {
+                        ranges.fetch_add(1, Ordering::Relaxed);
+                        sleep(delay).await;
+                        ("206 Partial Content", "TBS1")
+                    }
+                };
+                let response = format!(
+                    "HTTP/1.1 {}\r\nConnection: close\r\nContent-Length: 4\r\n\r\n{}",
+                    status, body
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+            });
+        }
+    }
+
+    /// Serves a file from a slow and a fast origin and reads it `reads` times, with requests
+    /// spread across the `balanced` fastest.
+    ///
+    /// # Returns
+    /// The range requests each origin got, slow one first, including probes, and the probes.
+    async fn read_from_origins(balanced: usize, reads: usize) -> ([usize; 2], Vec<MirrorProbe>) {
+        let slow = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let fast = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/file", slow.local_addr().unwrap());
+        let mirrors = vec![format!("http://{}/file", fast.local_addr().unwrap())];
+        let ranges = [Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0))];
+        let servers = [
+            tokio::spawn(serve(slow, Duration::from_millis(100), ranges[0].clone())),
+            tokio::spawn(serve(fast, Duration::ZERO, ranges[1].clone())),
+        ];
+        let options = OpenOptions::new()
+            .with_mirrors(mirrors)
+            .with_balanced_mirrors(balanced)
+            .with_origins_of(&url);
+        let mut remote_file = RemoteFile::open_with(&url, &options).await.unwrap();
+        for _ in 0..reads {
+            let mut buf = [0; 4];
+            remote_file.seek(SeekFrom::Start(0)).await.unwrap();
+            remote_file.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"TBS1");
+        }
+        servers.iter().for_each(|server| server.abort());
+        let probes = options.bound_origins().unwrap().probes().to_vec();
+        (ranges.map(|ranges| ranges.load(Ordering::Relaxed)), probes)
+    }
+
+    #[tokio::test]
+    async fn test_remote_file_prefers_fastest_origin() {
+        let (ranges, probes) = read_from_origins(1, 3).await;
+        // Each origin was probed once, then every read went to the faster mirror.
+        assert_eq!(ranges, [1, 4]);
+        assert_eq!(probes.len(), 2);
+        assert!(probes[0].bytes_per_second > probes[1].bytes_per_second);
+    }
+
+    #[tokio::test]
+    async fn test_remote_file_spreads_requests_across_origins() {
+        let (ranges, probes) = read_from_origins(2, 4).await;
+        assert_eq!(ranges, [3, 3]);
+        assert_eq!(probes.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_remote_file_retries_server_errors() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/file", listener.local_addr().unwrap());
+        let server = async {
+            // HEAD for the file size, then a failed and a successful range request.
+            respond(&listener, "200 OK", "").await;
+            respond(&listener, "503 Service Unavailable", "busy").await;
+            respond(&listener, "206 Partial Content", "TBS1").await;
+        };
+        let counters = Arc::new(ReadCounters::default());
+        let client = async {
+            let options = OpenOptions::new();
+            let mut remote_file =
+                RemoteFile::open_counted(&url, &options, counters.clone()).await.unwrap();
+            let mut buf = [0; 4];
+            remote_file.read_exact(&mut buf).await.unwrap();
+            buf
+        };
+        let ((), buf) = tokio::join!(server, client);
+        assert_eq!(&buf, b"TBS1");
+        let stats = counters.snapshot();
+        assert_eq!((stats.range_requests, stats.retries), (2, 1));
+    }
+
+    #[tokio::test]
+    async fn test_remote_file_rate_limiter() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/file", listener.local_addr().unwrap());
+        let server = async {
+            respond(&listener, "200 OK", "").await;
+            respond(&listener, "503 Service Unavailable", "busy").await;
+            respond(&listener, "206 Partial Content", "TBS1").await;
+        };
+        // A retry gives its request's place back, so one request at a time still succeeds.
+        let limiter = RateLimiter::new(1, None);
+        let client = async {
+            let options = OpenOptions::new().with_rate_limiter(limiter.clone());
+            let mut remote_file = RemoteFile::open_with(&url, &options).await.unwrap();
+            let mut buf = [0; 4];
+            remote_file.read_exact(&mut buf).await.unwrap();
+            buf
+        };
+        let ((), buf) = tokio::join!(server, client);
+        assert_eq!(&buf, b"TBS1");
+        assert_eq!(limiter.in_flight(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_remote_file_range_cache() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/file", listener.local_addr().unwrap());
+        // The second file only asks for the size, its range comes from the cache.
+        let server = async {
+            respond(&listener, "200 OK", "").await;
+            respond(&listener, "206 Partial Content", "TBS1").await;
+            respond(&listener, "200 OK", "").await;
+        };
+        let cache = Arc::new(crate::MemoryRangeCache::new(1024));
+        let counters = Arc::new(ReadCounters::default());
+        let client = async {
+            let options = OpenOptions::new().with_range_cache(cache.clone());
+            let mut bufs = [[0; 4]; 2];
+            for buf in &mut bufs {
+                let mut remote_file =
+                    RemoteFile::open_counted(&url, &options, counters.clone()).await.unwrap();
+                remote_file.read_exact(buf).await.unwrap();
+            }
+            bufs
+        };
+        let ((), bufs) = tokio::join!(server, client);
+        assert_eq!(bufs, [*b"TBS1"; 2]);
+        assert_eq!(counters.snapshot().range_requests, 1);
+        assert_eq!(cache.used(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_remote_file_request_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/file", listener.local_addr().unwrap());
+        // Answer the HEAD request, then leave every range request hanging.
+        let server = respond(&listener, "200 OK", "");
+        let client = async {
+            let timeout = Some(Duration::from_millis(50));
+            let mut remote_file = RemoteFile::open_with_timeout(&url, timeout).await.unwrap();
+            let mut buf = [0; 4];
+            remote_file.read_exact(&mut buf).await
+        };
+        let ((), result) = tokio::join!(server, client);
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_remote_file_without_retries() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/file", listener.local_addr().unwrap());
+        let server = async {
+            respond(&listener, "200 OK", "").await;
+            respond(&listener, "503 Service Unavailable", "busy").await;
+        };
+        let client = async {
+            let options = OpenOptions::new().with_max_retries(0);
+            let mut remote_file = RemoteFile::open_with(&url, &options).await.unwrap();
+            let mut buf = [0; 4];
+            remote_file.read_exact(&mut buf).await
+        };
+        let ((), result) = tokio::join!(server, client);
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_remote_file_fails_over_to_mirror() {
+        let primary = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mirror = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/file", primary.local_addr().unwrap());
+        let mirrors = vec![format!("http://{}/file", mirror.local_addr().unwrap())];
+        // Each origin is asked for the file's size and its last bytes before it is read from.
+        let primary_server = async {
+            respond(&primary, "200 OK", "").await;
+            respond(&primary, "206 Partial Content", "TBS1").await;
+            respond(&primary, "404 Not Found", "gone").await;
+        };
+        // The mirror keeps serving the reads after it failed over.
+        let mirror_server = async {
+            respond(&mirror, "200 OK", "").await;
+            respond(&mirror, "206 Partial Content", "TBS1").await;
+            respond(&mirror, "206 Partial Content", "TBS1").await;
+            respond(&mirror, "206 Partial Content", "TBS1").await;
+        };
+        let counters = Arc::new(ReadCounters::default());
+        let client = async {
+            let options = OpenOptions::new().with_max_retries(0).with_mirrors(mirrors);
+            let mut remote_file =
+                RemoteFile::open_counted(&url, &options, counters.clone()).await.unwrap();
+            let mut bufs = [[0; 4]; 2];
+            for buf in &mut bufs {
+                remote_file.seek(SeekFrom::Start(0)).await.unwrap();
+                remote_file.read_exact(buf).await.unwrap();
+            }
+            bufs
+        };
+        let ((), (), bufs) = tokio::join!(primary_server, mirror_server, client);
+        assert_eq!(bufs, [*b"TBS1"; 2]);
+        let stats = counters.snapshot();
+        assert_eq!((stats.range_requests, stats.failovers), (5, 1));
+    }
+
+    #[tokio::test]
+    async fn test_remote_file_accepts_mirror_with_other_etag() {
+        let primary = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mirror = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/file", primary.local_addr().unwrap());
+        let mirrors = vec![format!("http://{}/file", mirror.local_addr().unwrap())];
+        let primary_server = async {
+            respond(&primary, "200 OK", "").await;
+            respond(&primary, "206 Partial Content", "TBS1").await;
+            respond(&primary, "404 Not Found", "gone").await;
+        };
+        // Servers make up their own ETags, so the mirror is checked on the file's last bytes.
+        let mirror_server = async {
+            respond_with_etag(&mirror, "200 OK", "\"c-7\"", "").await;
+            respond_with_etag(&mirror, "206 Partial Content", "\"c-7\"", "TBS1").await;
+            respond_with_etag(&mirror, "206 Partial Content", "\"c-7\"", "TBS1").await;
+        };
+        let client = async {
+            let options = OpenOptions::new().with_max_retries(0).with_mirrors(mirrors);
+            let mut remote_file = RemoteFile::open_with(&url, &options).await.unwrap();
+            let mut buf = [0; 4];
+            remote_file.read_exact(&mut buf).await.unwrap();
+            buf
+        };
+        let ((), (), buf) = tokio::join!(primary_server, mirror_server, client);
+        assert_eq!(&buf, b"TBS1");
+    }
+
+    #[tokio::test]
+    async fn test_remote_file_skips_mirror_of_other_file() {
+        let primary = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mirror = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/file", primary.local_addr().unwrap());
+        let mirrors = vec![format!("http://{}/file", mirror.local_addr().unwrap())];
+        let primary_server = async {
+            respond(&primary, "200 OK", "").await;
+            respond(&primary, "206 Partial Content", "TBS1").await;
+            respond(&primary, "404 Not Found", "gone").await;
+        };
+        // The mirror has the same size and ETag, but ends with other bytes.
+        let mirror_server = async {
+            respond(&mirror, "200 OK", "").await;
+            respond(&mirror, "206 Partial Content", "TBSC").await;
+        };
+        let client = async {
+            let options = OpenOptions::new().with_max_retries(0).with_mirrors(mirrors);
+            let mut remote_file = RemoteFile::open_with(&url, &options).await.unwrap();
+            let mut buf = [0; 4];
+            remote_file.read_exact(&mut buf).await
+        };
+        let ((), (), result) = tokio::join!(primary_server, mirror_server, client);
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_remote_file_fails_when_origin_changes() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/file", listener.local_addr().unwrap());
+        // The file is replaced after it was opened, so its range comes with another ETag.
+        let server = async {
+            respond(&listener, "200 OK", "").await;
+            respond_with_etag(&listener, "206 Partial Content", "\"v2\"", "TBS2").await;
+        };
+        let client = async {
+            let mut remote_file = RemoteFile::open(&url).await.unwrap();
+            let mut buf = [0; 4];
+            remote_file.read_exact(&mut buf).await
+        };
+        let ((), result) = tokio::join!(server, client);
+        assert!(result.unwrap_err().to_string().contains("changed while reading"));
+    }
 }
@@ -1,14 +1,31 @@
-use std::{error::Error, io::SeekFrom};
+use std::{borrow::Cow, error::Error, future::Future, io::SeekFrom};
 
+use flatbuffers::FLATBUFFERS_MAX_BUFFER_SIZE;
 use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt};
 
-use crate::{constants::MAGIC_BYTES, generated::tensor_buffers::TensorMetadata};
+use crate::{
+    codec,
+    constants::MAGIC_BYTES,
+    footer::{
+        decompress_metadata, decompressed_metadata_size, Footer, MAX_FOOTER_SIZE,
+        MAX_METADATA_HEADER_SIZE,
+    },
+    generated::tensor_buffers::TensorMetadata,
+    parity::{protected_size, PARITY_TRAILER_SIZE},
+};
+
+/// Bytes read from the end of a file at once when loading its metadata, which covers the footer
+/// and the metadata of most files in a single read.
+pub(crate) const METADATA_TAIL_SIZE: usize = 64 << 10;
 
 /// Trait for reading tensor data and metadata from an async source.
-/// Allows for different implementations of how tensors are read.
+/// Allows for different implementations of how tensors are read. Its futures are `Send`, so
+/// reads can run in spawned tasks.
 pub trait TensorBuffersRead {
     /// Reads the size of the metadata section from the file.
-    async fn get_metadata_size(&mut self) -> Result<usize, Box<dyn Error>>;
+    fn get_metadata_size(
+        &mut self,
+    ) -> impl Future<Output = Result<usize, Box<dyn Error + Send + Sync>>> + Send;
 
     /// Reads the metadata of the TensorBuffers file into the provided buffer.
     /// The metadata is expected to be at the end of the file, preceded by its size and magic bytes.
@@ -17,8 +34,11 @@ pub trait TensorBuffersRead {
     /// * `buf` - A mutable slice of `u8` to store the read metadata.
     ///
     /// # Returns
-    /// Returns `Ok(())` on success, or a `Box<dyn Error>` on failure.
-    async fn read_metadata(&mut self, buf: &mut [u8]) -> Result<(), Box<dyn Error>>;
+    /// Returns `Ok(())` on success, or a `Box<dyn Error + Send + Sync>` on failure.
+    fn read_metadata(
+        &mut self,
+        buf: &mut [u8],
+    ) -> impl Future<Output = Result<(), Box<dyn Error + Send + Sync>>> + Send;
 
     /// Reads the data for a specific tensor based on its metadata into the provided buffer.
     ///
@@ -27,12 +47,12 @@ pub trait TensorBuffersRead {
     /// * `buf` - A mutable slice of `u8` to store the read tensor data.
     ///
     /// # Returns
-    /// Returns `Ok(())` on success, or a `Box<dyn Error>` on failure.
-    async fn read_data_with_metadata<'a>(
+    /// Returns `Ok(())` on success, or a `Box<dyn Error + Send + Sync>` on failure.
+    fn read_data_with_metadata<'a>(
         &mut self,
         metadata: TensorMetadata<'a>,
         buf: &mut [u8],
-    ) -> Result<(), Box<dyn Error>>;
+    ) -> impl Future<Output = Result<(), Box<dyn Error + Send + Sync>>> + Send;
 }
 
 /// Struct that implements `TensorBuffersRead` for any type that implements `AsyncRead` and `AsyncSeek`.
@@ -41,6 +61,11 @@ where
     R: AsyncRead + AsyncSeek, // R must support async reading and seeking.
 {
     reader: R, // The underlying async reader.
+    /// The last bytes of the file, as read by `read_tail`. Metadata reads reuse it instead of
+    /// allocating on every call.
+    tail: Vec<u8>,
+    /// The size of the file without its parity section, once `file_size` read it.
+    size: Option<u64>,
 }
 
 impl<'a, R> TensorBuffersReader<R>
@@ -52,53 +77,184 @@ where
     /// # Arguments
     /// * `reader` - An object that implements `AsyncRead` and `AsyncSeek`.
     pub fn new(reader: R) -> Self {
-        TensorBuffersReader { reader }
+        TensorBuffersReader { reader, tail: Vec::new(), size: None }
     }
 }
 
-impl<R> TensorBuffersRead for TensorBuffersReader<R>
+impl<R> TensorBuffersReader<R>
 where
     R: AsyncRead + AsyncSeek + Unpin,
 {
-    async fn get_metadata_size(&mut self) -> Result<usize, Box<dyn Error>> {
-        // Seek to 8 bytes before the end of the file:
-        // [metadata_size (4 bytes)][magic_bytes (4 bytes)] are at the end.
-        self.reader.seek(SeekFrom::End(-8)).await?;
+    /// Returns the size of the underlying file in bytes, without its parity section if it has
+    /// one. The size is read once per reader.
+    pub async fn file_size(&mut self) -> Result<u64, Box<dyn Error + Send + Sync>> {
+        if let Some(size) = self.size {
+            return Ok(size);
+        }
+        let file_size = self.reader.seek(SeekFrom::End(0)).await?;
+        let mut end = [0; PARITY_TRAILER_SIZE];
+        let end = &mut end[..file_size.min(PARITY_TRAILER_SIZE as u64) as usize];
+        self.read_at(file_size - end.len() as u64, end).await?;
+        let size = protected_size(end, file_size);
+        self.size = Some(size);
+        Ok(size)
+    }
 
-        // Read the 4 bytes representing the metadata size (little-endian u32).
-        let mut metadata_size_buff = [0; 4];
-        self.reader.read_exact(&mut metadata_size_buff).await?;
+    /// Reads and validates the footer, so truncated or corrupted files fail here instead of
+    /// producing a garbage parse of whatever bytes happen to precede the end of the file.
+    pub(crate) async fn read_footer(&mut self) -> Result<Footer, Box<dyn Error + Send + Sync>> {
+        let file_size = self.file_size().await?;
+        let mut end = [0; MAX_FOOTER_SIZE];
+        let end = &mut end[..file_size.min(MAX_FOOTER_SIZE as u64) as usize];
+        self.read_at(file_size - end.len() as u64, end).await?;
+        Footer::parse(end, file_size)
+    }
 
-        let metadata_size = u32::from_le_bytes(metadata_size_buff) as usize;
-        Ok(metadata_size)
+    /// Reads the last `size` bytes of the file, or the whole file if it is smaller, into the
+    /// reader's buffer, replacing what it held.
+    ///
+    /// # Returns
+    /// The size of the file.
+    pub(crate) async fn read_tail(
+        &mut self,
+        size: usize,
+    ) -> Result<u64, Box<dyn Error + Send + Sync>> {
+        let file_size = self.file_size().await?;
+        let size = file_size.min(size as u64) as usize;
+        // Reads of one file have the same size, so the buffer is only allocated on the first.
+        if self.tail.len() != size {
+            self.tail = vec![0; size];
+        }
+        self.reader.seek(SeekFrom::Start(file_size - size as u64)).await?;
+        self.reader.read_exact(&mut self.tail).await?;
+        Ok(file_size)
     }
 
-    /// Reads the metadata section from the file into `buf`.
-    /// Assumes file layout: [tensor data][metadata][metadata_size][magic_bytes]
-    async fn read_metadata(&mut self, buf: &mut [u8]) -> Result<(), Box<dyn Error>> {
-        // Seek to the start to verify the initial magic bytes.
-        self.reader.seek(SeekFrom::Start(0)).await?;
+    /// Parses the footer at the end of the buffer filled by `read_tail`.
+    pub(crate) fn tail_footer(
+        &self,
+        file_size: u64,
+    ) -> Result<Footer, Box<dyn Error + Send + Sync>> {
+        Footer::parse(&self.tail[self.tail.len().saturating_sub(MAX_FOOTER_SIZE)..], file_size)
+    }
+
+    /// Returns how many bytes of the metadata that `footer` describes precede the buffer filled
+    /// by `read_tail`, which `take_metadata` reads.
+    pub(crate) fn metadata_missing(&self, footer: &Footer) -> usize {
+        let stored_size = footer.metadata_size + footer.size();
+        footer.metadata_size.min(stored_size.saturating_sub(self.tail.len()))
+    }
+
+    /// Returns the metadata that `footer` describes, checking it against its checksum and
+    /// decompressing it if it is compressed. Bytes the last `read_tail` holds are taken from
+    /// there, so the metadata is returned in a buffer of its own, allocated once at its size.
+    pub(crate) async fn take_metadata(
+        &mut self,
+        footer: &Footer,
+        file_size: u64,
+    ) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+        let metadata = self.stored_metadata(footer, file_size).await?;
+        if footer.compressed_metadata {
+            return decompress_metadata(&metadata, FLATBUFFERS_MAX_BUFFER_SIZE);
+        }
+        Ok(metadata.into_owned())
+    }
+
+    /// Checks the leading magic bytes and returns the metadata as stored, borrowed from the
+    /// buffer filled by `read_tail` if it holds all of it. Otherwise the bytes before it are read
+    /// into a new buffer, which the rest are copied after.
+    async fn stored_metadata(
+        &mut self,
+        footer: &Footer,
+        file_size: u64,
+    ) -> Result<Cow<'_, [u8]>, Box<dyn Error + Send + Sync>> {
+        // The magic bytes are only read if the buffer doesn't hold the whole file.
         let mut magic_buf = [0; 4];
-        self.reader.read_exact(&mut magic_buf).await?;
+        if self.tail.len() as u64 == file_size {
+            magic_buf.copy_from_slice(&self.tail[..4]);
+        } else {
+            self.reader.seek(SeekFrom::Start(0)).await?;
+            self.reader.read_exact(&mut magic_buf).await?;
+        }
         if magic_buf != MAGIC_BYTES {
             return Err("Invalid magic bytes".into());
         }
 
-        // Get the size of the metadata section.
-        let metadata_size = self.get_metadata_size().await?;
+        // The metadata directly precedes the footer.
+        let missing = self.metadata_missing(footer);
+        let end = self.tail.len().saturating_sub(footer.size());
+        let held = &self.tail[end - (footer.metadata_size - missing)..end];
+        let metadata = match missing {
+            0 => Cow::Borrowed(held),
+            _ => {
+                let mut metadata = vec![0; footer.metadata_size];
+                let stored_size = footer.metadata_size + footer.size();
+                self.reader.seek(SeekFrom::Start(file_size - stored_size as u64)).await?;
+                self.reader.read_exact(&mut metadata[..missing]).await?;
+                metadata[missing..].copy_from_slice(held);
+                Cow::Owned(metadata)
+            }
+        };
 
-        // Ensure the provided buffer is large enough.
-        if buf.len() < metadata_size {
-            return Err("Buffer size is insufficient".into());
+        // Catch partial uploads and flipped bits before anything is parsed.
+        if let Some(expected) = footer.metadata_checksum {
+            let actual = crc32c::crc32c(&metadata);
+            if actual != expected {
+                return Err(format!(
+                    "Metadata checksum mismatch: expected {:#x}, found {:#x}",
+                    expected, actual
+                )
+                .into());
+            }
         }
+        Ok(metadata)
+    }
 
-        // Seek to the start of the metadata section:
-        // It's located (metadata_size + 8) bytes before the end.
-        self.reader.seek(SeekFrom::End(-(metadata_size as i64 + 8))).await?;
-
-        // Read the metadata into the buffer.
+    /// Reads `buf.len()` raw bytes starting at `offset`.
+    pub async fn read_at(
+        &mut self,
+        offset: u64,
+        buf: &mut [u8],
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.reader.seek(SeekFrom::Start(offset)).await?;
         self.reader.read_exact(buf).await?;
+        Ok(())
+    }
+}
 
+impl<R> TensorBuffersRead for TensorBuffersReader<R>
+where
+    R: AsyncRead + AsyncSeek + Unpin + Send,
+{
+    async fn get_metadata_size(&mut self) -> Result<usize, Box<dyn Error + Send + Sync>> {
+        let footer = self.read_footer().await?;
+        if !footer.compressed_metadata {
+            return Ok(footer.metadata_size);
+        }
+        let metadata_start =
+            self.file_size().await? - (footer.size() + footer.metadata_size) as u64;
+        let mut header = [0; MAX_METADATA_HEADER_SIZE];
+        let header = &mut header[..footer.metadata_size.min(MAX_METADATA_HEADER_SIZE)];
+        self.read_at(metadata_start, header).await?;
+        decompressed_metadata_size(header)
+    }
+
+    /// Reads the metadata section from the file into `buf`.
+    /// Assumes file layout: [tensor data][metadata][footer], where the footer holds a checksum of
+    /// the metadata in files written since format version 1.2.0.
+    async fn read_metadata(&mut self, buf: &mut [u8]) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let file_size = self.read_tail(METADATA_TAIL_SIZE).await?;
+        let footer = self.tail_footer(file_size)?;
+        let mut metadata = self.stored_metadata(&footer, file_size).await?;
+        if footer.compressed_metadata {
+            metadata = Cow::Owned(decompress_metadata(&metadata, FLATBUFFERS_MAX_BUFFER_SIZE)?);
+        }
+
+        // Ensure the provided buffer is large enough.
+        if buf.len() < metadata.len() {
+            return Err("Buffer size is insufficient".into());
+        }
+        buf[..metadata.len()].copy_from_slice(&metadata);
         Ok(())
     }
 
@@ -108,9 +264,9 @@ where
         &mut self,
         tensor_metadata: TensorMetadata<'a>,
         buf: &mut [u8],
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
         let offset = tensor_metadata.data_offset() as u64;
-        let size = tensor_metadata.data_size() as usize;
+        let size = codec::stored_size(&tensor_metadata);
 
         // Seek to the tensor's data offset.
         self.reader.seek(SeekFrom::Start(offset)).await?;
@@ -136,8 +292,12 @@ mod tests {
 
     use super::*;
     use crate::{
+        constants::{
+            BLOOM_FOOTER_MAGIC_BYTES, CHECKSUM_FOOTER_MAGIC_BYTES, FIXED_FOOTER_MAGIC_BYTES,
+        },
         generated::tensor_buffers::TensorBuffersMetadata,
-        tensor_buffers_writer::TensorBuffersWrite, Tensor, TensorBuffersWriter,
+        tensor_buffers_writer::TensorBuffersWrite,
+        Tensor, TensorBuffersWriter, WriterOptions,
     };
 
     #[tokio::test]
@@ -174,4 +334,140 @@ mod tests {
         reader.read_data_with_metadata(tensor_metadata, &mut tensor_buf).await.unwrap();
         assert_eq!(tensor_buf.len(), 3 * size_of::<f32>());
     }
+
+    #[tokio::test]
+    async fn test_footer_validation() {
+        let tensor = Tensor::new("1", &[1.0f32, 2.0, 3.0], vec![3]);
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        // The metadata size is the last field but the magic bytes of footers before 1.4.0.
+        let options = WriterOptions::new().with_format_version("1.3.0");
+        let mut writer = TensorBuffersWriter::with_options(&mut file, options);
+        writer.write(vec![tensor], vec![]).await.unwrap();
+        let bytes = std::fs::read(tmp.path()).unwrap();
+
+        async fn metadata_size(bytes: &[u8]) -> Result<usize, Box<dyn Error + Send + Sync>> {
+            TensorBuffersReader::new(std::io::Cursor::new(bytes)).get_metadata_size().await
+        }
+        assert!(metadata_size(&bytes).await.is_ok());
+
+        let truncated = metadata_size(&bytes[..bytes.len() - 3]).await.unwrap_err();
+        assert!(truncated.to_string().starts_with("Invalid trailing magic bytes"));
+        let tiny = metadata_size(&bytes[..10]).await.unwrap_err();
+        assert!(tiny.to_string().starts_with("File is too small (10 bytes)"));
+
+        let mut oversized = bytes.clone();
+        let footer = oversized.len() - 8;
+        oversized[footer..footer + 4].copy_from_slice(&(bytes.len() as u32).to_le_bytes());
+        let error = metadata_size(&oversized).await.unwrap_err();
+        assert!(error.to_string().starts_with("Invalid metadata size"));
+    }
+
+    #[tokio::test]
+    async fn test_metadata_checksum() {
+        async fn write(options: WriterOptions) -> Vec<u8> {
+            let tensor = Tensor::new("1", &[1.0f32, 2.0, 3.0], vec![3]);
+            let mut bytes = std::io::Cursor::new(Vec::new());
+            let mut writer = TensorBuffersWriter::with_options(&mut bytes, options);
+            writer.write(vec![tensor], vec![]).await.unwrap();
+            bytes.into_inner()
+        }
+        async fn read_metadata(bytes: &[u8]) -> Result<Footer, Box<dyn Error + Send + Sync>> {
+            let mut reader = TensorBuffersReader::new(std::io::Cursor::new(bytes));
+            let footer = reader.read_footer().await?;
+            reader.read_metadata(&mut vec![0; footer.metadata_size]).await?;
+            Ok(footer)
+        }
+
+        let bytes = write(WriterOptions::new()).await;
+        assert!(bytes.ends_with(FIXED_FOOTER_MAGIC_BYTES));
+        let footer = read_metadata(&bytes).await.unwrap();
+        assert_eq!(footer.size(), MAX_FOOTER_SIZE);
+
+        // Flip a bit in the last byte of the metadata.
+        let mut corrupted = bytes.clone();
+        let last = corrupted.len() - footer.size() - 1;
+        corrupted[last] ^= 1;
+        let error = read_metadata(&corrupted).await.unwrap_err();
+        assert!(error.to_string().starts_with("Metadata checksum mismatch"));
+
+        // Files written before 1.2.0 have no checksum.
+        let legacy = write(WriterOptions::new().with_format_version("1.1.0")).await;
+        assert!(legacy.ends_with(MAGIC_BYTES));
+        assert_eq!(read_metadata(&legacy).await.unwrap().metadata_checksum, None);
+        let checksummed = write(WriterOptions::new().with_format_version("1.3.0")).await;
+        assert!(checksummed.ends_with(CHECKSUM_FOOTER_MAGIC_BYTES));
+        assert_eq!(read_metadata(&checksummed).await.unwrap().size(), 12);
+
+        // The bloom filter sits between the metadata and the rest of the footer.
+        for (version, magic, size) in
+            [("1.3.0", BLOOM_FOOTER_MAGIC_BYTES, 24), ("1.4.0", FIXED_FOOTER_MAGIC_BYTES, 64)]
+        {
+            let options = WriterOptions::new().with_format_version(version);
+            let bloom = write(options.with_bloom_filter(true)).await;
+            assert!(bloom.ends_with(magic));
+            let footer = read_metadata(&bloom).await.unwrap();
+            let filter = footer.filter.unwrap();
+            assert_eq!((filter.size, filter.hashes), (2, 7));
+            assert_eq!(footer.size(), size + 2);
+            assert_eq!(
+                bloom.len() - footer.size() - footer.metadata_size,
+                checksummed.len() - 12 - footer.metadata_size
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_metadata_tail() {
+        async fn take_metadata(bytes: &[u8]) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+            let mut reader = TensorBuffersReader::new(std::io::Cursor::new(bytes));
+            let file_size = reader.read_tail(METADATA_TAIL_SIZE).await?;
+            let footer = reader.tail_footer(file_size)?;
+            reader.take_metadata(&footer, file_size).await
+        }
+
+        // Metadata larger than the first read is completed by a second one.
+        let names = (0..2000).map(|i| format!("layers.{}.weight", i)).collect::<Vec<_>>();
+        let tensors = names.iter().map(|name| Tensor::new(name, &[1u8], vec![1])).collect();
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        TensorBuffersWriter::new(&mut bytes).write(tensors, vec![]).await.unwrap();
+        let bytes = bytes.into_inner();
+        let metadata = take_metadata(&bytes).await.unwrap();
+        assert!(metadata.len() > METADATA_TAIL_SIZE);
+        let root = flatbuffers::root::<TensorBuffersMetadata>(&metadata).unwrap();
+        assert_eq!(root.tensors().unwrap().len(), 2000);
+        let mut read = vec![0; metadata.len()];
+        TensorBuffersReader::new(std::io::Cursor::new(&bytes))
+            .read_metadata(&mut read)
+            .await
+            .unwrap();
+        assert_eq!(read, metadata);
+
+        // Its first byte is covered by the checksum, and the magic bytes are read separately.
+        let mut corrupted = bytes.clone();
+        corrupted[bytes.len() - MAX_FOOTER_SIZE - metadata.len()] ^= 1;
+        let error = take_metadata(&corrupted).await.unwrap_err();
+        assert!(error.to_string().starts_with("Metadata checksum mismatch"));
+        let mut corrupted = bytes.clone();
+        corrupted[0] ^= 1;
+        assert_eq!(take_metadata(&corrupted).await.unwrap_err().to_string(), "Invalid magic bytes");
+
+        // Small files are read whole, so their magic bytes come from the same read.
+        let tensor = Tensor::new("1", &[1.0f32, 2.0, 3.0], vec![3]);
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        TensorBuffersWriter::new(&mut bytes).write(vec![tensor], vec![]).await.unwrap();
+        let mut bytes = bytes.into_inner();
+        let metadata = take_metadata(&bytes).await.unwrap();
+        assert!(flatbuffers::root::<TensorBuffersMetadata>(&metadata).is_ok());
+
+        // Later reads reuse the reader's buffer.
+        let mut reader = TensorBuffersReader::new(std::io::Cursor::new(&bytes));
+        let mut read = vec![0; metadata.len()];
+        reader.read_metadata(&mut read).await.unwrap();
+        let buffer = reader.tail.as_ptr();
+        reader.read_metadata(&mut read).await.unwrap();
+        assert_eq!((reader.tail.as_ptr(), read), (buffer, metadata));
+        bytes[0] ^= 1;
+        assert_eq!(take_metadata(&bytes).await.unwrap_err().to_string(), "Invalid magic bytes");
+    }
 }
@@ -0,0 +1,507 @@
+use std::{
+    collections::HashMap,
+    io::{Error, Result},
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use flatbuffers::FlatBufferBuilder;
+use futures::Sink;
+use tokio::io::AsyncWrite;
+use tracing::debug;
+
+use crate::{
+    bloom_filter::BloomFilter,
+    codec::StoredData,
+    constants::MAGIC_BYTES,
+    constraints::check_constraints,
+    footer::{encode_footer, encode_metadata, FooterContents},
+    format_features::FormatFeatures,
+    name_index::{encode_name_index, IndexedTensor},
+    num_trait::DataType,
+    quantization::{encode_scales, quantize, QuantizationParams},
+    tensor::build_tensor_table,
+    tensor_any::TensorAny,
+    tensor_buffers_writer::{
+        check_data_end, check_data_size, check_data_type_support, check_name, check_shape,
+        encode_data, invalid_input,
+    },
+    tensor_columns::build_columns,
+    utils::hash_key,
+    writer_options::WriterOptions,
+    Provenance, TensorBuffers, TensorConstraint, TensorId, TensorOperation,
+};
+
+/// What the metadata needs to know about a tensor whose data has been written.
+struct WrittenTensor {
+    id: TensorId,
+    name: String,
+    data_type: DataType,
+    shape: Vec<usize>,
+    data_size: usize,
+    stored: StoredData,
+    quantization: Option<QuantizationParams>,
+    /// Where the quantization's scales are stored, once they are written after the data.
+    scales_offset: Option<u64>,
+}
+
+/// Writes tensors to a file as they arrive, so tensor-producing pipelines can `forward` straight
+/// into it without collecting every tensor first.
+///
+/// Each tensor's data is written as soon as the writer accepts it and only its metadata is kept.
+/// Closing the sink writes the metadata and the footer, so a file is complete only once the sink
+/// is closed. Tensors are stored in arrival order, so options that need every tensor up front,
+/// `sort_by_name`, `dedup` and `leading_metadata`, are not supported.
+pub struct TensorBuffersSink<W>
+where
+    W: AsyncWrite + Unpin,
+{
+    writer: W,
+    options: WriterOptions,
+    /// Bytes waiting for the writer, from `written` on.
+    pending: Vec<u8>,
+    written: usize,
+    /// Size of the file so far, including pending bytes.
+    size: u64,
+    tensors: Vec<WrittenTensor>,
+    names: HashMap<TensorId, String>,
+    operations: Vec<TensorOperation>,
+    /// User metadata of tensors, by tensor name.
+    user_metadata: HashMap<String, Vec<u8>>,
+    /// Constraints between the tensors.
+    constraints: Vec<TensorConstraint>,
+    /// The markdown model card and the provenance, if any.
+    model_card: Option<String>,
+    provenance: Option<Provenance>,
+    finished: bool,
+}
+
+impl<W> TensorBuffersSink<W>
+where
+    W: AsyncWrite + Unpin,
+{
+    /// Creates a sink that writes to `writer` as configured by `options`.
+    ///
+    /// # Arguments
+    /// * `writer` - The destination, positioned where the file starts.
+    /// * `options` - The write settings.
+    ///
+    /// # Returns
+    /// An error if the options are invalid or ask for sorting, deduplication, a leading copy of
+    /// the metadata or a compression dictionary.
+    pub fn new(writer: W, options: WriterOptions) -> crate::Result<Self> {
+        options.validate()?;
+        if options.sort_by_name() || options.dedup() {
+            return Err("A sink writes tensors as they arrive, so it can't sort or deduplicate \
+                        them"
+                .into());
+        }
+        if options.leading_metadata() {
+            return Err("A sink can't write the metadata before the tensors it describes".into());
+        }
+        if options.compression_dictionary() > 0 {
+            return Err("A sink can't train a compression dictionary on tensors yet to come".into());
+        }
+        Ok(TensorBuffersSink {
+            writer,
+            options,
+            pending: Vec::new(),
+            written: 0,
+            size: 0,
+            tensors: Vec::new(),
+            names: HashMap::new(),
+            operations: Vec::new(),
+            user_metadata: HashMap::new(),
+            constraints: Vec::new(),
+            model_card: None,
+            provenance: None,
+            finished: false,
+        })
+    }
+
+    /// Adds an operation to the metadata written when the sink is closed.
+    pub fn add_operation(&mut self, operation: TensorOperation) {
+        self.operations.push(operation);
+    }
+
+    /// Attaches `bytes` to the tensor `tensor_name` as its user metadata, see
+    /// `TensorBuffersWriter::add_user_metadata`. Closing the sink fails unless a tensor of that
+    /// name was written.
+    pub fn add_user_metadata(&mut self, tensor_name: &str, bytes: Vec<u8>) {
+        self.user_metadata.insert(tensor_name.to_string(), bytes);
+    }
+
+    /// Declares a relationship between tensors, see `TensorBuffersWriter::add_constraint`.
+    /// Closing the sink fails unless the constraint names at least two tensors, all written.
+    pub fn add_constraint(&mut self, constraint: TensorConstraint) {
+        self.constraints.push(constraint);
+    }
+
+    /// Stores `markdown` as the file's model card, see `TensorBuffersWriter::set_model_card`.
+    pub fn set_model_card(&mut self, markdown: &str) {
+        self.model_card = Some(markdown.to_string());
+    }
+
+    /// Records where the weights came from, see `TensorBuffersWriter::set_provenance`.
+    pub fn set_provenance(&mut self, provenance: Provenance) {
+        self.provenance = Some(provenance);
+    }
+
+    /// Returns the number of tensors accepted so far.
+    pub fn tensors_written(&self) -> usize {
+        self.tensors.len()
+    }
+
+    /// Returns the underlying writer. Unless the sink was closed, the file is incomplete.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Queues bytes for the writer.
+    fn queue(&mut self, bytes: &[u8]) {
+        self.pending.extend_from_slice(bytes);
+        self.size += bytes.len() as u64;
+    }
+
+    /// Queues the leading magic bytes, unless the file has already started.
+    fn start(&mut self) {
+        if self.size == 0 {
+            self.queue(MAGIC_BYTES);
+        }
+    }
+
+    /// Encodes a tensor's data and queues it for the writer.
+    fn push(&mut self, tensor: TensorAny) -> crate::Result<()> {
+        if self.finished {
+            return Err("Can't write tensors after the sink is closed".into());
+        }
+        check_name(tensor.name())?;
+        check_shape(tensor.name(), tensor.shape(), tensor.elements())?;
+        check_data_size(tensor.name(), tensor.data_bytes().len())?;
+        check_data_type_support(tensor.name(), tensor.data_type(), &self.options)?;
+        if let Some(other) = self.names.get(&tensor.id()) {
+            return Err(format!("Tensors {} and {} have the same id", other, tensor.name()).into());
+        }
+        self.start();
+
+        let offset = self.size.next_multiple_of(self.options.alignment() as u64);
+        let quantized = self.options.quantization().and_then(|policy| {
+            let (name, data_type) = (tensor.name(), tensor.data_type());
+            quantize(name, data_type, tensor.data_bytes(), tensor.shape(), policy)
+        });
+        let (data_type, shape, data) = match &quantized {
+            Some(quantized) => {
+                (quantized.params.stored_data_type(), quantized.shape.clone(), &quantized.bytes[..])
+            }
+            None => (tensor.data_type(), tensor.shape().to_vec(), tensor.data_bytes()),
+        };
+        let codec = self.options.compression_for(tensor.name(), data_type, data.len());
+        let (stored, bytes) = encode_data(data, offset, &self.options, codec, None)?;
+        check_data_end(offset + bytes.len() as u64)?;
+        self.queue(&vec![0; (offset - self.size) as usize]);
+        self.queue(&bytes);
+
+        self.names.insert(tensor.id(), tensor.name().to_string());
+        self.tensors.push(WrittenTensor {
+            id: tensor.id(),
+            name: tensor.name().to_string(),
+            data_type,
+            shape,
+            data_size: data.len(),
+            stored,
+            quantization: quantized.map(|quantized| quantized.params),
+            scales_offset: None,
+        });
+        Ok(())
+    }
+
+    /// Describes the tensors accepted so far, in their current order.
+    fn indexed(&self) -> Vec<IndexedTensor<'_>> {
+        self.tensors
+            .iter()
+            .map(|tensor| IndexedTensor {
+                id: tensor.id,
+                name: &tensor.name,
+                data_type: tensor.data_type,
+                shape: &tensor.shape,
+                data_size: tensor.data_size,
+                stored: tensor.stored,
+                external: None,
+                alias_of: None,
+                user_metadata: self.user_metadata.get(&tensor.name).map(Vec::as_slice),
+                quantization: tensor.quantization.as_ref(),
+                scales_offset: tensor.scales_offset,
+            })
+            .collect()
+    }
+
+    /// Queues the metadata and the footer that end the file.
+    fn finish(&mut self) -> Result<()> {
+        let unknown = |name: &&String| self.names.get(&hash_key(name)) != Some(*name);
+        if let Some(name) = self.user_metadata.keys().find(unknown) {
+            return Err(invalid_input(
+                format!("User metadata refers to {}, which is not a tensor", name).into(),
+            ));
+        }
+        check_constraints(&self.constraints, |name| {
+            self.names.get(&hash_key(name)).map(String::as_str) == Some(name)
+        })
+        .map_err(invalid_input)?;
+        self.start();
+        // Group-wise scales are too many for the metadata, so a section after the data holds them.
+        let (scales, positions) =
+            encode_scales(self.tensors.iter().map(|tensor| tensor.quantization.as_ref()));
+        for (tensor, position) in self.tensors.iter_mut().zip(positions) {
+            tensor.scales_offset = position.map(|position| self.size + position);
+        }
+        self.queue(&scales);
+        if self.options.name_index() {
+            let section = encode_name_index(self.size, &mut self.indexed());
+            self.queue(&section);
+        }
+        let mut builder = FlatBufferBuilder::new();
+        let mut features = FormatFeatures::default();
+
+        // Tables are keyed by id, so they must be sorted for lookups to binary search them.
+        self.tensors.sort_by_key(|tensor| tensor.id);
+        let tensor_columns = match self.options.columnar_metadata() {
+            true => {
+                features.add_columnar_metadata();
+                Some(build_columns(&mut builder, &self.indexed()).map_err(invalid_input)?)
+            }
+            false => None,
+        };
+        let mut tensor_offsets = Vec::new();
+        for tensor in &self.tensors {
+            features.add_tensor(tensor.stored.compression, &tensor.shape);
+            features.add_data_type(tensor.data_type);
+            if tensor.quantization.is_some() {
+                features.add_quantization();
+            }
+            if tensor_columns.is_none() {
+                tensor_offsets.push(build_tensor_table(
+                    &mut builder,
+                    tensor.id,
+                    &tensor.name,
+                    tensor.data_type,
+                    &tensor.shape,
+                    tensor.data_size,
+                    &tensor.stored,
+                    None,
+                    None,
+                    self.user_metadata.get(&tensor.name).map(Vec::as_slice),
+                    tensor.quantization.as_ref(),
+                    tensor.scales_offset,
+                ));
+            }
+        }
+
+        let mut operations = std::mem::take(&mut self.operations);
+        let operations_count = operations.len();
+        operations.sort_by_key(|op| op.id());
+        let operation_offsets = operations
+            .into_iter()
+            .map(|op| TensorOperation::build_table(&mut builder, op))
+            .collect::<Vec<_>>();
+
+        let metadata = TensorBuffers::build_versioned_table(
+            &mut builder,
+            self.options.format_version(),
+            &tensor_offsets,
+            tensor_columns,
+            &operation_offsets,
+            &features,
+            None,
+            self.options.user_metadata(),
+            self.model_card.as_deref(),
+            self.provenance.as_ref(),
+            &self.constraints,
+        );
+        builder.finish(metadata, None);
+        let metadata = builder.finished_data();
+        let filter = self.options.bloom_filter().then(|| {
+            BloomFilter::new(&self.tensors.iter().map(|tensor| tensor.id).collect::<Vec<_>>())
+        });
+        let contents = FooterContents {
+            format_version: self.options.format_version(),
+            metadata_offset: self.size,
+            tensors: self.tensors.len(),
+            operations: operations_count,
+            name_index: self.options.name_index(),
+            compressed_metadata: self.options.compressed_metadata(),
+            filter: filter.as_ref(),
+        };
+        let metadata = encode_metadata(metadata, &contents)?;
+        let footer = encode_footer(&metadata, &contents)?;
+        self.queue(&metadata);
+        self.queue(&footer);
+        self.finished = true;
+        debug!(tensors = self.tensors.len(), bytes = self.size, "Finished tensor buffers");
+        Ok(())
+    }
+
+    /// Writes pending bytes until the writer has taken all of them.
+    fn poll_write_pending(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        while self.written < self.pending.len() {
+            let pending = &self.pending[self.written..];
+            let written = ready!(Pin::new(&mut self.writer).poll_write(cx, pending))?;
+            if written == 0 {
+                return Poll::Ready(Err(Error::from(std::io::ErrorKind::WriteZero)));
+            }
+            self.written += written;
+        }
+        self.pending.clear();
+        self.written = 0;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<W> Sink<TensorAny> for TensorBuffersSink<W>
+where
+    W: AsyncWrite + Unpin,
+{
+    type Error = Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        // Waiting for the previous tensor to be written keeps one tensor in memory at a time.
+        self.get_mut().poll_write_pending(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, tensor: TensorAny) -> Result<()> {
+        self.get_mut().push(tensor).map_err(invalid_input)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_write_pending(cx))?;
+        Pin::new(&mut this.writer).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_write_pending(cx))?;
+        if !this.finished {
+            this.finish()?;
+            ready!(this.poll_write_pending(cx))?;
+        }
+        Pin::new(&mut this.writer).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{stream, SinkExt, StreamExt};
+    use tempfile::NamedTempFile;
+    use tokio::fs::File;
+
+    use super::*;
+    use crate::{
+        f16, utils::hash_key, Compression, CompressionPolicy, DataType, OpenOptions, Operation,
+        Tensor,
+    };
+
+    #[tokio::test]
+    async fn test_forward_into_sink() {
+        let tmp = NamedTempFile::new().unwrap();
+        let file = File::create(tmp.path()).await.unwrap();
+        let options = WriterOptions::new()
+            .with_compression(Compression::Lz4, 0)
+            .with_name_index(true)
+            .with_user_metadata(b"export".to_vec());
+        let mut sink = TensorBuffersSink::new(file, options).unwrap();
+        let weights = Tensor::from_vec("weights", vec![0.5f32; 256], vec![16, 16]);
+        sink.add_operation(TensorOperation::new(1, Operation::None, vec![], weights.id()));
+        sink.add_user_metadata("weights", b"frozen".to_vec());
+        sink.set_model_card("# Export");
+
+        let tensors = vec![
+            TensorAny::from(weights),
+            TensorAny::from(Tensor::from_vec("ids", vec![1i64, 2, 3], vec![3])),
+            TensorAny::from(Tensor::from_vec("mask", vec![1u8, 0], vec![2])),
+            TensorAny::from(Tensor::scalar("step", 7i64)),
+            TensorAny::from(Tensor::<f32>::zeros("empty", vec![0, 4])),
+        ];
+        stream::iter(tensors).map(Ok).forward(&mut sink).await.unwrap();
+        assert_eq!(sink.tensors_written(), 5);
+
+        let url = format!("file://{}", tmp.path().display());
+        let options = OpenOptions::new().with_name_index(true);
+        let tensor_buffers = TensorBuffers::open_with(&url, options).await.unwrap();
+        let weights = tensor_buffers.get_tensor_data_by_name::<f32>("weights").await.unwrap();
+        assert_eq!((weights.shape(), weights.data()), (&[16, 16][..], &[0.5; 256][..]));
+        let ids = tensor_buffers.get_tensor_data_by_name::<i64>("ids").await.unwrap();
+        assert_eq!(ids.data(), [1, 2, 3]);
+        let mask = tensor_buffers.get_tensor_data_by_name::<u8>("mask").await.unwrap();
+        assert_eq!(mask.data(), [1, 0]);
+        let step = tensor_buffers.get_tensor_data_by_name::<i64>("step").await.unwrap();
+        assert_eq!((step.shape(), step.data()), (&[][..], &[7][..]));
+        let empty = tensor_buffers.get_tensor_data_by_name::<f32>("empty").await.unwrap();
+        assert_eq!((empty.shape(), empty.elements()), (&[0, 4][..], 0));
+        assert_eq!(tensor_buffers.get_tensor_operations().await.unwrap().len(), 1);
+        let frozen = tensor_buffers.tensor_user_metadata("weights").await.unwrap();
+        assert_eq!(frozen, Some(&b"frozen"[..]));
+        assert_eq!(tensor_buffers.user_metadata().await.unwrap(), Some(&b"export"[..]));
+        assert_eq!(tensor_buffers.model_card().await.unwrap(), Some("# Export"));
+    }
+
+    #[tokio::test]
+    async fn test_sink_compression_policy() {
+        let policy = CompressionPolicy::default()
+            .with_min_size(1024)
+            .with_skipped_data_type(DataType::Float16)
+            .with_override("head.*", Compression::Lz4, 0);
+        let options = WriterOptions::new()
+            .with_compression(Compression::Zstd, 3)
+            .with_compression_policy(policy);
+        let mut sink = TensorBuffersSink::new(Vec::new(), options).unwrap();
+        let tensors = vec![
+            TensorAny::from(Tensor::from_vec("w", vec![0.5f32; 1024], vec![1024])),
+            TensorAny::from(Tensor::from_vec("bias", vec![0.5f32; 16], vec![16])),
+            TensorAny::from(Tensor::from_vec("w16", vec![f16::ONE; 1024], vec![1024])),
+            TensorAny::from(Tensor::from_vec("head.bias", vec![0.5f32; 16], vec![16])),
+        ];
+        stream::iter(tensors).map(Ok).forward(&mut sink).await.unwrap();
+
+        let tmp = NamedTempFile::new().unwrap();
+        std::fs::write(tmp.path(), sink.into_inner()).unwrap();
+        let url = format!("file://{}", tmp.path().display());
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        for (name, compression) in [
+            ("w", Compression::Zstd),
+            ("bias", Compression::None),
+            ("w16", Compression::None),
+            ("head.bias", Compression::Lz4),
+        ] {
+            let metadata = tensor_buffers.stored_metadata(hash_key(name)).await.unwrap();
+            assert_eq!(metadata.compression(), compression, "{}", name);
+        }
+        let bias = tensor_buffers.get_tensor_data_by_name::<f32>("head.bias").await.unwrap();
+        assert_eq!(bias.data(), [0.5; 16]);
+    }
+
+    #[tokio::test]
+    async fn test_sink_rejects_invalid_tensors() {
+        let options = WriterOptions::new().with_dedup(true);
+        assert!(TensorBuffersSink::new(Vec::new(), options).is_err());
+        let options = WriterOptions::new().with_leading_metadata(true);
+        assert!(TensorBuffersSink::new(Vec::new(), options).is_err());
+
+        let mut sink = TensorBuffersSink::new(Vec::new(), WriterOptions::new()).unwrap();
+        let misshapen = Tensor::from_vec("x", vec![1.0f32, 2.0, 3.0], vec![2, 2]);
+        let error = sink.send(misshapen.into()).await.unwrap_err();
+        assert_eq!(error.to_string(), "Tensor x has shape [2, 2], but 3 elements");
+
+        let tensor = TensorAny::from(Tensor::from_vec("x", vec![1.0f32], vec![1]));
+        sink.send(tensor.clone()).await.unwrap();
+        let error = sink.send(tensor).await.unwrap_err();
+        assert_eq!(error.to_string(), "Tensors x and x have the same id");
+        sink.close().await.unwrap();
+        assert!(sink.send(Tensor::from_vec("y", vec![1u8], vec![1]).into()).await.is_err());
+        assert!(sink.into_inner().starts_with(MAGIC_BYTES));
+
+        let mut sink = TensorBuffersSink::new(Vec::new(), WriterOptions::new()).unwrap();
+        sink.add_user_metadata("missing", b"lost".to_vec());
+        let error = sink.close().await.unwrap_err();
+        assert_eq!(error.to_string(), "User metadata refers to missing, which is not a tensor");
+    }
+}
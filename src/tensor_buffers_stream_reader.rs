@@ -0,0 +1,227 @@
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::{
+    codec,
+    constants::{LEADING_METADATA_MAGIC_BYTES, MAGIC_BYTES, SUPPORTED_VERSIONS},
+    format_features::check_features,
+    generated::tensor_buffers::{TensorBuffersMetadata, TensorMetadata},
+    read_mode::check_metadata,
+    slice_file::check_data_layout,
+    tensor_any::TensorAny,
+    tensor_buffers_writer::LEADING_HEADER_SIZE,
+    tensor_columns::expand_columns,
+    Result, TensorOperation,
+};
+
+/// Reads a TensorBuffers file front to back, e.g. from a pipe, a tar entry or an HTTP response
+/// without range requests. Needs files written with `WriterOptions::with_leading_metadata`, whose
+/// metadata precedes the tensor data, and yields the tensors in the order they are stored.
+/// Files with tensors stored in other files can't be streamed.
+pub struct TensorBuffersStreamReader<'a, R>
+where
+    R: AsyncRead + Unpin,
+{
+    reader: R,
+    metadata: TensorBuffersMetadata<'a>,
+    /// The tensors in the order their data is stored.
+    tensors: Vec<TensorMetadata<'a>>,
+    next: usize,
+    /// Bytes read from the file so far.
+    position: u64,
+    /// The stored data read last, kept for deduplicated tensors that share it.
+    last_region: Option<(u64, Vec<u8>)>,
+}
+
+impl<'a, R> TensorBuffersStreamReader<'a, R>
+where
+    R: AsyncRead + Unpin,
+{
+    /// Reads the leading magic bytes and the copy of the metadata that follows them.
+    ///
+    /// # Arguments
+    /// * `reader` - The file, positioned at its start.
+    ///
+    /// # Returns
+    /// An error if the file has no leading copy of the metadata, or if it is invalid.
+    pub async fn new(mut reader: R) -> Result<Self> {
+        let mut header = [0; MAGIC_BYTES.len() + LEADING_HEADER_SIZE];
+        reader.read_exact(&mut header).await?;
+        if header[..4] != *MAGIC_BYTES {
+            return Err("Invalid magic bytes".into());
+        }
+        if header[4..8] != *LEADING_METADATA_MAGIC_BYTES {
+            return Err("File has no leading copy of the metadata".into());
+        }
+        let size = u32::from_le_bytes(header[8..12].try_into()?) as usize;
+        let checksum = u32::from_le_bytes(header[12..16].try_into()?);
+        let mut metadata = vec![0; size];
+        reader.read_exact(&mut metadata).await?;
+        if crc32c::crc32c(&metadata) != checksum {
+            return Err("Metadata checksum mismatch".into());
+        }
+
+        // The metadata lives as long as the reader's tensors, like that of `TensorBuffers`.
+        let metadata: &'a [u8] = Box::leak(metadata.into_boxed_slice());
+        let metadata = flatbuffers::root::<TensorBuffersMetadata>(metadata)
+            .map_err(|error| format!("Invalid metadata: {}", error))?;
+        if !SUPPORTED_VERSIONS.contains(&metadata.version()) {
+            return Err(format!("Unsupported format version {}", metadata.version()).into());
+        }
+        check_features(&metadata)?;
+        if let Some(problem) = check_metadata(&metadata).into_iter().next() {
+            return Err(problem.into());
+        }
+        let metadata = match expand_columns(&metadata)? {
+            Some(expanded) => {
+                let expanded: &'a [u8] = expanded.leak();
+                // SAFETY: The metadata was just built from the checked columns.
+                unsafe { flatbuffers::root_unchecked::<TensorBuffersMetadata>(expanded) }
+            }
+            None => metadata,
+        };
+        let mut tensors = metadata.tensors().into_iter().flatten().collect::<Vec<_>>();
+        for tensor in &tensors {
+            // Data stored in other files isn't in the stream.
+            if tensor.external().is_some() {
+                return Err(format!("Tensor {} is stored in another file", tensor.name()).into());
+            }
+            // The file size isn't known yet; reads past the end fail when they get there.
+            check_data_layout(tensor, u64::MAX)?;
+        }
+        tensors.sort_by_key(|tensor| tensor.data_offset());
+        let position = (header.len() + size) as u64;
+        Ok(TensorBuffersStreamReader {
+            reader,
+            metadata,
+            tensors,
+            next: 0,
+            position,
+            last_region: None,
+        })
+    }
+
+    /// Returns the format version of the file.
+    pub fn version(&self) -> &'a str {
+        self.metadata.version()
+    }
+
+    /// Returns the names of the tensors in the order `next_tensor` yields them.
+    pub fn tensor_names(&self) -> Vec<&'a str> {
+        self.tensors.iter().map(|tensor| tensor.name()).collect()
+    }
+
+    /// Returns the operations stored in the file.
+    pub fn operations(&self) -> Vec<TensorOperation> {
+        let operations = self.metadata.operations().into_iter().flatten();
+        operations.map(|op| TensorOperation::with_metadata(&op)).collect()
+    }
+
+    /// Reads the next tensor, skipping the padding before its data and checking the data against
+    /// its checksum.
+    ///
+    /// # Returns
+    /// `None` once every tensor has been read.
+    pub async fn next_tensor(&mut self) -> Result<Option<TensorAny>> {
+        let Some(&tensor) = self.tensors.get(self.next) else {
+            return Ok(None);
+        };
+        self.next += 1;
+        let offset = tensor.data_offset() as u64;
+        let size = codec::stored_size(&tensor);
+        if let Some((region, stored)) = &self.last_region {
+            if *region == offset && stored.len() == size {
+                return TensorAny::decode(tensor, stored, self.dictionary()).map(Some);
+            }
+        }
+        if offset < self.position {
+            return Err(format!(
+                "Tensor {} starts at byte {}, before the end of the previous tensor",
+                tensor.name(),
+                offset
+            )
+            .into());
+        }
+
+        let padding = offset - self.position;
+        let mut skipped = (&mut self.reader).take(padding);
+        let skipped = tokio::io::copy(&mut skipped, &mut tokio::io::sink()).await?;
+        if skipped != padding {
+            return Err(format!("File ends before the data of tensor {}", tensor.name()).into());
+        }
+        let mut stored = vec![0; size];
+        self.reader.read_exact(&mut stored).await?;
+        self.position = offset + size as u64;
+        let decoded = TensorAny::decode(tensor, &stored, self.dictionary())?;
+        self.last_region = Some((offset, stored));
+        Ok(Some(decoded))
+    }
+
+    fn dictionary(&self) -> Option<&'a [u8]> {
+        self.metadata.compression_dictionary().map(|dictionary| dictionary.bytes())
+    }
+
+    /// Returns the underlying reader, positioned after the data read so far.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Compression, Operation, Tensor, TensorBuffers, TensorBuffersWrite, TensorBuffersWriter,
+        WriterOptions,
+    };
+
+    async fn write(options: WriterOptions) -> Vec<u8> {
+        let values = (0..256).map(|i| (i % 8) as f32).collect::<Vec<_>>();
+        let tensors = vec![
+            Tensor::new("b", &values, vec![16, 16]),
+            Tensor::new("a", &values[..8], vec![8]),
+            Tensor::new("copy", &values, vec![256]),
+        ];
+        let operation = TensorOperation::new(1, Operation::None, vec![], tensors[0].id());
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        let mut writer = TensorBuffersWriter::with_options(&mut bytes, options);
+        writer.write(tensors, vec![operation]).await.unwrap();
+        bytes.into_inner()
+    }
+
+    #[tokio::test]
+    async fn test_stream_reader() {
+        let options = WriterOptions::new()
+            .with_leading_metadata(true)
+            .with_alignment(64)
+            .with_compression(Compression::Zstd, 3)
+            .with_dedup(true);
+        let bytes = write(options).await;
+
+        let mut reader = TensorBuffersStreamReader::new(&bytes[..]).await.unwrap();
+        // "b" and "copy" share their deduplicated data, which comes first.
+        let names = reader.tensor_names();
+        assert_eq!(names[2], "a");
+        assert!(names.contains(&"b") && names.contains(&"copy"));
+        assert_eq!(reader.operations().len(), 1);
+        let mut tensors = Vec::new();
+        while let Some(tensor) = reader.next_tensor().await.unwrap() {
+            tensors.push(tensor);
+        }
+        assert_eq!(tensors.len(), 3);
+        assert_eq!(tensors[1].data_bytes(), tensors[0].data_bytes());
+        assert_eq!(tensors[2].shape(), [8]);
+
+        // Readers that seek to the footer read the file as usual.
+        let tmp = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(tmp.path(), &bytes).unwrap();
+        let url = format!("file://{}", tmp.path().display());
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        let tensor = tensor_buffers.get_tensor_data_by_name::<f32>("b").await.unwrap();
+        assert_eq!(bytemuck::cast_slice::<f32, u8>(tensor.data()), tensors[0].data_bytes());
+
+        // Files without the copy can't be read front to back.
+        let bytes = write(WriterOptions::new()).await;
+        let error = TensorBuffersStreamReader::new(&bytes[..]).await.err().unwrap();
+        assert_eq!(error.to_string(), "File has no leading copy of the metadata");
+    }
+}
@@ -1,13 +1,47 @@
-use std::io::Result;
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet, VecDeque},
+    future::Future,
+    io::{Error, ErrorKind, Result},
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use bytemuck::Pod;
 use flatbuffers::FlatBufferBuilder;
-use tokio::io::{AsyncSeek, AsyncWrite, AsyncWriteExt};
+use futures::channel::oneshot;
+use tokio::io::{AsyncSeek, AsyncSeekExt, AsyncWrite, AsyncWriteExt};
+use tracing::{debug_span, field::Empty, instrument, Instrument, Span};
 
-use crate::{constants::MAGIC_BYTES, Num, Tensor, TensorBuffers, TensorOperation};
+use crate::{
+    bloom_filter::BloomFilter,
+    codec::{self, CompressionDictionary, StoredData},
+    codec_registry::registered_codec,
+    constants::{
+        LEADING_METADATA_MAGIC_BYTES, MAGIC_BYTES, MAX_NAME_LENGTH, MIN_VERSION_FOR_FEATURES,
+        NAMESPACE_SEPARATOR,
+    },
+    constraints::check_constraints,
+    external_data::ExternalTensor,
+    footer::{encode_footer, encode_metadata, FooterContents},
+    format_features::FormatFeatures,
+    generated::tensor_buffers::Compression,
+    name_index::{encode_name_index, IndexedTensor},
+    quantization::{encode_scales, quantize, Quantized},
+    stats::WriteStats,
+    tensor_columns::build_columns,
+    utils::{elapsed_ms, hash_key},
+    writer_options::WriterOptions,
+    DataType, Num, Provenance, Tensor, TensorBuffers, TensorConstraint, TensorOperation,
+};
+
+/// Size of the header of the leading metadata copy: the magic bytes, then the size and CRC32C of
+/// the metadata (little-endian u32s).
+pub(crate) const LEADING_HEADER_SIZE: usize = 12;
 
 // Define a trait for writing tensors to a destination.
 // This trait abstracts the logic for serializing and writing tensors.
+// Its futures are `Send`, so writes can run in spawned tasks.
 pub trait TensorBuffersWrite {
     /// Writes an iterator of tensors to the implementing writer.
     ///
@@ -15,12 +49,12 @@ pub trait TensorBuffersWrite {
     /// * `tensors` - An iterator yielding `Tensor` objects.
     ///
     /// # Returns
-    /// Returns `Ok(())` on success, or a `Box<dyn Error>` on failure.
-    async fn write<'a, T>(
+    /// Returns `Ok(())` on success, or a `Box<dyn Error + Send + Sync>` on failure.
+    fn write<'a, T>(
         &mut self,
         tensors: Vec<Tensor<'a, T>>,
         operations: Vec<TensorOperation>,
-    ) -> Result<()>
+    ) -> impl Future<Output = Result<()>> + Send
     where
         T: Pod + Num; // T must be plain old data and implement the custom Num trait.
 }
@@ -31,6 +65,82 @@ where
     W: AsyncWrite + AsyncSeek + Unpin, // W must support async writing and seeking.
 {
     writer: W, // The underlying async writer.
+    options: WriterOptions,
+    /// Aliases for the next write, as the names of the tensors they share data with and their own.
+    aliases: Vec<(String, String)>,
+    /// User metadata of tensors in the next write, by tensor name.
+    user_metadata: Vec<(String, Vec<u8>)>,
+    /// Constraints between the tensors of the next write.
+    constraints: Vec<TensorConstraint>,
+    /// The markdown model card and the provenance of every later write.
+    model_card: Option<String>,
+    provenance: Option<Provenance>,
+    /// Tensors stored in other files for the next write, and the dictionary they need, if any.
+    external: Vec<ExternalTensor>,
+    external_dictionary: Option<Vec<u8>>,
+    progress: Option<ProgressCallback>,
+    stats: WriteStats,
+}
+
+/// Called by a [`TensorBuffersWriter`] after each tensor's data is written.
+type ProgressCallback = Box<dyn FnMut(WriteProgress<'_>) + Send>;
+
+/// How far a [`TensorBuffersWriter`] is through writing tensor data, reported after each tensor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WriteProgress<'p> {
+    /// The name of the tensor just written.
+    pub tensor_name: &'p str,
+    /// Bytes of tensor data written so far including this tensor's, before compression.
+    pub bytes_written: u64,
+    /// Bytes of tensor data the write holds in total, before compression.
+    pub total_bytes: u64,
+    /// Number of tensors written so far including this one.
+    pub tensors_written: usize,
+    /// Number of tensors the write holds in total.
+    pub total_tensors: usize,
+    /// Time since the write started.
+    pub elapsed: Duration,
+}
+
+/// Counts the tensors written so far to report progress.
+struct ProgressCounter {
+    start: Instant,
+    bytes_written: u64,
+    total_bytes: u64,
+    tensors_written: usize,
+    total_tensors: usize,
+}
+
+impl ProgressCounter {
+    fn new<T: Pod + Num>(tensors: &[Tensor<'_, T>], start: Instant) -> Self {
+        ProgressCounter {
+            start,
+            bytes_written: 0,
+            total_bytes: tensors.iter().map(|tensor| size_of_val(tensor.data()) as u64).sum(),
+            tensors_written: 0,
+            total_tensors: tensors.len(),
+        }
+    }
+
+    /// Counts `tensor` as written and reports it to `callback`, if any.
+    fn written<T: Pod + Num>(
+        &mut self,
+        tensor: &Tensor<'_, T>,
+        callback: &mut Option<ProgressCallback>,
+    ) {
+        self.bytes_written += size_of_val(tensor.data()) as u64;
+        self.tensors_written += 1;
+        if let Some(callback) = callback {
+            callback(WriteProgress {
+                tensor_name: tensor.name(),
+                bytes_written: self.bytes_written,
+                total_bytes: self.total_bytes,
+                tensors_written: self.tensors_written,
+                total_tensors: self.total_tensors,
+                elapsed: self.start.elapsed(),
+            });
+        }
+    }
 }
 
 impl<W> TensorBuffersWriter<W>
@@ -42,17 +152,811 @@ where
     /// # Arguments
     /// * `writer` - An object that implements AsyncWrite and AsyncSeek.
     pub fn new(writer: W) -> Self {
-        TensorBuffersWriter { writer }
+        Self::with_options(writer, WriterOptions::new())
+    }
+
+    /// Creates a `TensorBuffersWriter` that lays out, compresses and checksums tensor data as
+    /// configured by `options`.
+    ///
+    /// # Arguments
+    /// * `writer` - An object that implements AsyncWrite and AsyncSeek.
+    /// * `options` - The write settings. They are validated when writing.
+    pub fn with_options(writer: W, options: WriterOptions) -> Self {
+        TensorBuffersWriter {
+            writer,
+            options,
+            aliases: Vec::new(),
+            user_metadata: Vec::new(),
+            constraints: Vec::new(),
+            model_card: None,
+            provenance: None,
+            external: Vec::new(),
+            external_dictionary: None,
+            progress: None,
+            stats: WriteStats::default(),
+        }
+    }
+
+    /// Returns what the writer has written since it was created, so applications can log
+    /// export sizes and catch regressions.
+    pub fn stats(&self) -> WriteStats {
+        self.stats
+    }
+
+    /// Calls `callback` after each tensor's data is written, with the tensor's name and how much
+    /// of the write is done, so export tools can show progress and estimate the time remaining.
+    /// Tensors are reported in the order their data is written, deduplicated tensors along with
+    /// the others. Replaces any earlier callback and applies to every later write.
+    pub fn on_progress<F>(&mut self, callback: F) -> &mut Self
+    where
+        F: FnMut(WriteProgress<'_>) + Send + 'static,
+    {
+        self.progress = Some(Box::new(callback));
+        self
+    }
+
+    /// Adds a tensor named `new_name` that shares the data of the tensor `existing`, e.g. to tie
+    /// an LM head to the input embeddings without storing them twice. Readers load aliases like
+    /// any other tensor. The alias is written by the next `write`, which fails if it doesn't write
+    /// a tensor named `existing` or already has a tensor named `new_name`.
+    ///
+    /// # Arguments
+    /// * `existing` - The name of the tensor whose data is shared.
+    /// * `new_name` - The name of the alias.
+    pub fn add_alias(&mut self, existing: &str, new_name: &str) -> &mut Self {
+        self.aliases.push((existing.to_string(), new_name.to_string()));
+        self
+    }
+
+    /// Attaches `bytes` to the tensor `tensor_name` as its user metadata, e.g. a FlexBuffer of an
+    /// organization's internal fields, which readers return unchanged from
+    /// `TensorBuffers::tensor_user_metadata`. Applies to the next `write`, which fails if it
+    /// doesn't write a tensor or alias named `tensor_name`.
+    pub fn add_user_metadata(&mut self, tensor_name: &str, bytes: Vec<u8>) -> &mut Self {
+        self.user_metadata.push((tensor_name.to_string(), bytes));
+        self
+    }
+
+    /// Declares a relationship between tensors, e.g. that the LM head is tied to the input
+    /// embeddings, which readers check with `TensorBuffers::check_constraints` to catch tying
+    /// broken by a conversion. Applies to the next `write`, which fails if the constraint names
+    /// fewer than two tensors or one it doesn't write.
+    pub fn add_constraint(&mut self, constraint: TensorConstraint) -> &mut Self {
+        self.constraints.push(constraint);
+        self
+    }
+
+    /// Stores `markdown` as the file's model card, the human-readable description of the model,
+    /// its intended uses and limitations, which readers return from `TensorBuffers::model_card`.
+    /// Replaces any earlier card and applies to every later write.
+    pub fn set_model_card(&mut self, markdown: &str) -> &mut Self {
+        self.model_card = Some(markdown.to_string());
+        self
+    }
+
+    /// Records where the weights came from, such as the commit of the training code and the
+    /// datasets trained on, which readers return from `TensorBuffers::provenance`. Replaces any
+    /// earlier provenance and applies to every later write.
+    pub fn set_provenance(&mut self, provenance: Provenance) -> &mut Self {
+        self.provenance = Some(provenance);
+        self
+    }
+
+    /// Adds a tensor whose data stays in another file to the next write.
+    ///
+    /// # Arguments
+    /// * `tensor` - The tensor, located in the other file.
+    /// * `dictionary` - The compression dictionary of the other file, if the tensor needs it. The
+    ///   written file stores it as its own, so every tensor added must need the same one.
+    pub(crate) fn add_external_tensor(
+        &mut self,
+        tensor: ExternalTensor,
+        dictionary: Option<&[u8]>,
+    ) -> crate::Result<()> {
+        if let Some(dictionary) = dictionary.filter(|_| tensor.needs_dictionary()) {
+            match &self.external_dictionary {
+                Some(other) if other != dictionary => {
+                    return Err(format!(
+                        "Tensor {} needs a different compression dictionary than the other \
+                         tensors stored elsewhere",
+                        tensor.name
+                    )
+                    .into());
+                }
+                _ => self.external_dictionary = Some(dictionary.to_vec()),
+            }
+        }
+        self.external.push(tensor);
+        Ok(())
+    }
+}
+
+/// Converts a crate error into an I/O error for the writer.
+pub(crate) fn invalid_input(error: Box<dyn std::error::Error + Send + Sync>) -> Error {
+    Error::new(ErrorKind::InvalidInput, error.to_string())
+}
+
+/// Checks that every tensor has a valid name, that its shape matches its data and that no two
+/// tensors, including those stored in other files and aliases, share a name, since tensors are
+/// looked up by the hash of their name. Aliases must name a tensor being written or stored
+/// elsewhere, and user metadata and constraints any tensor the metadata lists.
+fn check_tensors<T>(
+    tensors: &[Tensor<'_, T>],
+    external: &[ExternalTensor],
+    aliases: &[(String, String)],
+    user_metadata: &[(String, Vec<u8>)],
+    constraints: &[TensorConstraint],
+) -> crate::Result<()>
+where
+    T: Pod + Num,
+{
+    let mut names = HashMap::with_capacity(tensors.len() + external.len() + aliases.len());
+    for tensor in tensors {
+        check_name(tensor.name())?;
+        check_shape(tensor.name(), tensor.shape(), tensor.data_elements())?;
+        check_data_size(tensor.name(), size_of_val(tensor.data()))?;
+        if let Some(other) = names.insert(tensor.id(), tensor.name()) {
+            return Err(format!("Tensors {} and {} have the same id", other, tensor.name()).into());
+        }
+    }
+    for tensor in external {
+        check_name(&tensor.name)?;
+        if let Some(other) = names.insert(tensor.id, &tensor.name) {
+            return Err(format!("Tensors {} and {} have the same id", other, tensor.name).into());
+        }
+    }
+    let targets = names.values().copied().collect::<HashSet<_>>();
+    for (existing, name) in aliases {
+        if !targets.contains(existing.as_str()) {
+            return Err(
+                format!("Alias {} refers to {}, which is not a tensor", name, existing).into()
+            );
+        }
+        check_name(name)?;
+        if let Some(other) = names.insert(hash_key(name), name) {
+            return Err(format!("Tensors {} and {} have the same id", other, name).into());
+        }
+    }
+    for (name, _) in user_metadata {
+        if names.get(&hash_key(name)) != Some(&name.as_str()) {
+            return Err(format!("User metadata refers to {}, which is not a tensor", name).into());
+        }
+    }
+    check_constraints(constraints, |name| names.get(&hash_key(name)) == Some(&name))
+}
+
+/// Checks that a tensor name is one other tools can handle: non-empty, at most `MAX_NAME_LENGTH`
+/// bytes, free of control characters, and made of non-empty namespaces joined by
+/// `NAMESPACE_SEPARATOR`.
+pub(crate) fn check_name(name: &str) -> crate::Result<()> {
+    if name.is_empty() {
+        return Err("Tensor names must not be empty".into());
+    }
+    if name.len() > MAX_NAME_LENGTH {
+        return Err(format!(
+            "Tensor name {:?}... is {} bytes long, more than the {} allowed",
+            name.chars().take(32).collect::<String>(),
+            name.len(),
+            MAX_NAME_LENGTH
+        )
+        .into());
+    }
+    if name.chars().any(char::is_control) {
+        return Err(format!("Tensor name {:?} contains a control character", name).into());
+    }
+    if name.split(NAMESPACE_SEPARATOR).any(str::is_empty) {
+        return Err(format!(
+            "Tensor name {:?} has an empty namespace around '{}'",
+            name, NAMESPACE_SEPARATOR
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Checks that a tensor's shape holds exactly `elements` elements.
+pub(crate) fn check_shape(name: &str, shape: &[usize], elements: usize) -> crate::Result<()> {
+    let expected = shape.iter().try_fold(1usize, |n, &dim| n.checked_mul(dim));
+    if expected != Some(elements) {
+        return Err(
+            format!("Tensor {} has shape {:?}, but {} elements", name, shape, elements).into()
+        );
+    }
+    Ok(())
+}
+
+/// Checks that the format version being written can describe tensors of `data_type`.
+pub(crate) fn check_data_type_support(
+    name: &str,
+    data_type: DataType,
+    options: &WriterOptions,
+) -> crate::Result<()> {
+    let needs = match data_type {
+        DataType::Extension(_) => "extension data types",
+        _ if data_type.is_block_quantized() => "block-quantized data types",
+        _ => return Ok(()),
+    };
+    if !options.supports(MIN_VERSION_FOR_FEATURES) {
+        return Err(format!(
+            "Format version {} does not support {}, needed by tensor {}",
+            options.format_version(),
+            needs,
+            name
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Checks that a tensor's data, before compression, fits in the format's 32-bit sizes.
+pub(crate) fn check_data_size(name: &str, size: usize) -> crate::Result<()> {
+    if size > u32::MAX as usize {
+        return Err(format!(
+            "Tensor {} has {} bytes of data, beyond the 4 GiB the format can describe",
+            name, size
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Checks that tensor data ending at `data_end` fits in the format's 32-bit offsets and sizes.
+pub(crate) fn check_data_end(data_end: u64) -> crate::Result<()> {
+    if data_end > u32::MAX as u64 {
+        return Err(format!(
+            "Tensor data ends at byte {}, beyond the 4 GiB the format can address",
+            data_end
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Compresses and checksums a tensor's data as `options` ask, keeping data that doesn't shrink
+/// uncompressed, so reading it costs nothing extra. Data encoded with a custom codec is kept
+/// whatever its size.
+///
+/// # Arguments
+/// * `data` - The tensor's data.
+/// * `offset` - Where the data will be stored, already aligned.
+/// * `options` - The write settings.
+/// * `(compression, level)` - The codec and level chosen for the tensor.
+/// * `dictionary` - The dictionary trained for the file, which replaces plain Zstandard.
+///
+/// # Returns
+/// Where and how the data is stored, and the bytes to store.
+pub(crate) fn encode_data<'d>(
+    data: &'d [u8],
+    offset: u64,
+    options: &WriterOptions,
+    (compression, level): (Compression, i32),
+    dictionary: Option<&CompressionDictionary>,
+) -> crate::Result<(StoredData, Cow<'d, [u8]>)> {
+    let mut custom_codec = None;
+    let (compression, compressed) = match dictionary {
+        Some(dictionary) if compression == Compression::Zstd => (
+            Compression::ZstdDictionary,
+            Cow::Owned(codec::compress_with_dictionary(data, dictionary)?),
+        ),
+        _ if compression == Compression::Custom => {
+            let id = options
+                .custom_codec()
+                .ok_or("Compression::Custom needs a codec set with with_custom_codec")?;
+            let codec = registered_codec(id)?;
+            custom_codec = Some(codec.id());
+            (compression, Cow::Owned(codec.encode(data)?))
+        }
+        _ => (compression, codec::compress(data, compression, level)?),
+    };
+    let (compression, bytes) = if custom_codec.is_some() || compressed.len() < data.len() {
+        (compression, compressed)
+    } else {
+        (Compression::None, Cow::Borrowed(data))
+    };
+    let checksum_algorithm = options.checksum();
+    let checksum = codec::checksum(&bytes, checksum_algorithm)?;
+    let stored = StoredData {
+        offset,
+        size: bytes.len(),
+        compression,
+        checksum_algorithm,
+        checksum,
+        codec: custom_codec,
+    };
+    Ok((stored, bytes))
+}
+
+/// Most samples taken from each tensor to train a compression dictionary, and their size.
+const DICTIONARY_SAMPLES_PER_TENSOR: usize = 8;
+const DICTIONARY_SAMPLE_SIZE: usize = 4 << 10;
+
+/// Most bytes of samples to train a compression dictionary on, as a multiple of its size. Zstandard
+/// suggests about 100 times the dictionary size; more only slows training down.
+const DICTIONARY_TRAINING_RATIO: usize = 100;
+
+/// Trains the compression dictionary `options` ask for on evenly spaced samples of the data of
+/// each tensor the compression policy leaves to Zstandard.
+///
+/// # Returns
+/// `None` if no dictionary is asked for or too little data was written to train one.
+fn train_dictionary<T>(
+    tensors: &[Tensor<'_, T>],
+    quantized: &[Option<Quantized>],
+    options: &WriterOptions,
+) -> Option<CompressionDictionary>
+where
+    T: Pod + Num,
+{
+    let max_size = options.compression_dictionary();
+    if max_size == 0 {
+        return None;
+    }
+    let budget = max_size.saturating_mul(DICTIONARY_TRAINING_RATIO);
+    let mut samples = Vec::new();
+    let mut sampled = 0;
+    for (i, tensor) in tensors.iter().enumerate() {
+        let data = stored_bytes(tensors, quantized, i);
+        let data_type = stored_data_type(quantized, i, T::data_type());
+        let (compression, _) = options.compression_for(tensor.name(), data_type, data.len());
+        if compression != Compression::Zstd {
+            continue;
+        }
+        let chunks = data.chunks(DICTIONARY_SAMPLE_SIZE);
+        let step = chunks.len().div_ceil(DICTIONARY_SAMPLES_PER_TENSOR).max(1);
+        for sample in chunks.step_by(step) {
+            if sampled + sample.len() > budget {
+                break;
+            }
+            sampled += sample.len();
+            samples.push(sample);
+        }
+    }
+    CompressionDictionary::train(&samples, max_size, options.compression_level())
+}
+
+/// Quantizes the tensors the quantization policy of `options` applies to, if any.
+///
+/// # Returns
+/// The quantized data of each tensor, `None` for tensors written as they are.
+fn quantize_tensors<T>(tensors: &[Tensor<'_, T>], options: &WriterOptions) -> Vec<Option<Quantized>>
+where
+    T: Pod + Num,
+{
+    tensors
+        .iter()
+        .map(|tensor| {
+            let policy = options.quantization()?;
+            let data = bytemuck::cast_slice::<T, u8>(tensor.data());
+            quantize(tensor.name(), T::data_type(), data, tensor.shape(), policy)
+        })
+        .collect()
+}
+
+/// Returns the bytes stored for `tensors[i]`: its quantized data if it was quantized, or its data.
+fn stored_bytes<'d, T>(
+    tensors: &'d [Tensor<'_, T>],
+    quantized: &'d [Option<Quantized>],
+    i: usize,
+) -> &'d [u8]
+where
+    T: Pod + Num,
+{
+    match &quantized[i] {
+        Some(quantized) => &quantized.bytes,
+        None => bytemuck::cast_slice::<T, u8>(tensors[i].data()),
+    }
+}
+
+/// Returns the type of the elements stored for `tensors[i]`.
+fn stored_data_type(quantized: &[Option<Quantized>], i: usize, data_type: DataType) -> DataType {
+    quantized[i].as_ref().map_or(data_type, |quantized| quantized.params.stored_data_type())
+}
+
+/// Most tensors whose data may be waiting to be written at once, encoded or being encoded.
+const MAX_PENDING_TENSORS: usize = 256;
+
+/// Most bytes of tensor data that may be waiting to be written at once. Data being compressed on
+/// the rayon pool is copied, so this bounds the extra memory parallel compression takes.
+const MAX_PENDING_BYTES: usize = 64 << 20;
+
+/// Encodes data that `encode_data` can take ownership of, returning it unchanged if it is stored
+/// uncompressed. The offset of the returned `StoredData` is left at 0.
+fn encode_owned(
+    data: Vec<u8>,
+    options: &WriterOptions,
+    codec: (Compression, i32),
+    dictionary: Option<&CompressionDictionary>,
+) -> crate::Result<(StoredData, Vec<u8>)> {
+    let (stored, bytes) = encode_data(&data, 0, options, codec, dictionary)?;
+    let bytes = match bytes {
+        Cow::Owned(bytes) => bytes,
+        Cow::Borrowed(_) => data,
+    };
+    Ok((stored, bytes))
+}
+
+/// A tensor's encoded data, or the result of encoding it on the rayon pool.
+enum Encoding<'d> {
+    /// The data duplicates that of an earlier tensor, which stores it.
+    Shared,
+    Done(StoredData, Cow<'d, [u8]>),
+    Pending(oneshot::Receiver<crate::Result<(StoredData, Vec<u8>)>>),
+}
+
+/// Lays tensor data out one tensor after another, aligning it and storing the data of identical
+/// tensors once if the options ask for it.
+///
+/// With compression and `WriterOptions::with_parallel_compression`, tensors are compressed on the
+/// rayon pool ahead of the writer, which takes them in order as they complete, so the layout is
+/// the same as if they had been compressed one by one.
+struct DataLayout<'d, 'a, 'o, T>
+where
+    T: Clone,
+{
+    tensors: &'d [Tensor<'a, T>],
+    /// The quantized data of each tensor, stored instead of its data.
+    quantized: &'d [Option<Quantized>],
+    options: &'o WriterOptions,
+    dictionary: Option<Arc<CompressionDictionary>>,
+    order: std::vec::IntoIter<usize>,
+    /// Tensors being encoded, in the order their data is written, with their sizes.
+    pending: VecDeque<(usize, usize, Encoding<'d>)>,
+    pending_bytes: usize,
+    /// Where the data placed so far ends.
+    end: u64,
+    by_hash: HashMap<u64, Vec<usize>>,
+    stored_at: Vec<Option<StoredData>>,
+    /// The tensor whose data each deduplicated tensor shares.
+    duplicate_of: Vec<Option<usize>>,
+}
+
+impl<'d, 'a, 'o, T> DataLayout<'d, 'a, 'o, T>
+where
+    T: Pod + Num,
+{
+    /// Creates a layout of `tensors` in the given order, whose data starts at `start`.
+    fn new(
+        tensors: &'d [Tensor<'a, T>],
+        quantized: &'d [Option<Quantized>],
+        order: Vec<usize>,
+        start: u64,
+        options: &'o WriterOptions,
+        dictionary: Option<Arc<CompressionDictionary>>,
+    ) -> Self {
+        DataLayout {
+            tensors,
+            quantized,
+            options,
+            dictionary,
+            order: order.into_iter(),
+            pending: VecDeque::new(),
+            pending_bytes: 0,
+            end: start,
+            by_hash: HashMap::new(),
+            stored_at: vec![None; tensors.len()],
+            duplicate_of: vec![None; tensors.len()],
+        }
+    }
+
+    fn data(&self, i: usize) -> &'d [u8] {
+        stored_bytes(self.tensors, self.quantized, i)
+    }
+
+    /// Returns whether the data of `tensors[i]` duplicates that of a tensor before it, recording
+    /// which one if so.
+    fn find_duplicate(&mut self, i: usize) -> bool {
+        if !self.options.dedup() {
+            return false;
+        }
+        let data = self.data(i);
+        let same = self.by_hash.entry(xxhash_rust::xxh64::xxh64(data, 0)).or_default();
+        let (tensors, quantized) = (self.tensors, self.quantized);
+        let duplicate = same.iter().copied().find(|&j| stored_bytes(tensors, quantized, j) == data);
+        match duplicate {
+            Some(j) => self.duplicate_of[i] = Some(j),
+            None => same.push(i),
+        }
+        duplicate.is_some()
+    }
+
+    /// Starts encoding the data of `tensors[i]`, on the rayon pool if the options compress it.
+    fn start_encoding(&mut self, i: usize) -> crate::Result<()> {
+        let data = self.data(i);
+        let data_type = stored_data_type(self.quantized, i, T::data_type());
+        let codec = self.options.compression_for(self.tensors[i].name(), data_type, data.len());
+        let parallel = self.options.parallel_compression() && codec.0 != Compression::None;
+        let encoding = if parallel {
+            let (sender, receiver) = oneshot::channel();
+            let (data, options) = (data.to_vec(), self.options.clone());
+            let dictionary = self.dictionary.clone();
+            rayon::spawn(move || {
+                let encoded = encode_owned(data, &options, codec, dictionary.as_deref());
+                let _ = sender.send(encoded);
+            });
+            Encoding::Pending(receiver)
+        } else {
+            let (stored, bytes) =
+                encode_data(data, 0, self.options, codec, self.dictionary.as_deref())?;
+            Encoding::Done(stored, bytes)
+        };
+        self.pending.push_back((i, data.len(), encoding));
+        self.pending_bytes += data.len();
+        Ok(())
+    }
+
+    /// Places the data of the next tensor after the data placed so far. Tensors whose data
+    /// duplicates that of a tensor before them share its data and place no bytes.
+    ///
+    /// # Returns
+    /// The index of the tensor, the number of zero bytes of padding and the bytes to write after
+    /// them, or `None` once all the data has been placed.
+    async fn next(&mut self) -> crate::Result<Option<(usize, usize, Cow<'d, [u8]>)>> {
+        while self.pending.is_empty()
+            || self.pending.len() < MAX_PENDING_TENSORS && self.pending_bytes < MAX_PENDING_BYTES
+        {
+            let Some(i) = self.order.next() else {
+                break;
+            };
+            match self.find_duplicate(i) {
+                true => self.pending.push_back((i, 0, Encoding::Shared)),
+                false => self.start_encoding(i)?,
+            }
+        }
+        let Some((i, size, encoding)) = self.pending.pop_front() else {
+            return Ok(None);
+        };
+        self.pending_bytes -= size;
+        let (mut stored, bytes) = match encoding {
+            Encoding::Shared => return Ok(Some((i, 0, Cow::Borrowed(&[])))),
+            Encoding::Done(stored, bytes) => (stored, bytes),
+            Encoding::Pending(receiver) => {
+                let (stored, bytes) = receiver.await.map_err(|_| "Compression was cancelled")??;
+                (stored, Cow::Owned(bytes))
+            }
+        };
+
+        stored.offset = self.end.next_multiple_of(self.options.alignment() as u64);
+        let padding = (stored.offset - self.end) as usize;
+        self.end = stored.offset + bytes.len() as u64;
+        self.stored_at[i] = Some(stored);
+        Ok(Some((i, padding, bytes)))
+    }
+
+    /// Returns where and how each tensor's data is stored, in the order of the tensors. Every
+    /// tensor must have been placed.
+    fn into_stored(self) -> Vec<StoredData> {
+        let mut stored = self.stored_at;
+        for (i, duplicate_of) in self.duplicate_of.into_iter().enumerate() {
+            if let Some(j) = duplicate_of {
+                stored[i] = stored[j];
+            }
+        }
+        stored.into_iter().map(Option::unwrap).collect()
+    }
+}
+
+/// Describes every tensor the metadata lists: the written tensors, whose data is stored as
+/// `stored` says, quantized if `quantized` holds their quantized data, the tensors stored in other
+/// files, and the aliases, which share the data of the
+/// tensor they name, with the user metadata attached to them. `scales_offsets` places the scales
+/// of the written tensors, then of those stored in other files, that the metadata doesn't hold.
+/// Aliases must have been checked by `check_tensors`.
+fn describe_tensors<'t, T>(
+    tensors: &'t [Tensor<'_, T>],
+    quantized: &'t [Option<Quantized>],
+    scales_offsets: &[Option<u64>],
+    stored: &[StoredData],
+    external: &'t [ExternalTensor],
+    aliases: &'t [(String, String)],
+    user_metadata: &'t [(String, Vec<u8>)],
+) -> Vec<IndexedTensor<'t>>
+where
+    T: Pod + Num,
+{
+    let mut described = tensors
+        .iter()
+        .zip(quantized)
+        .zip(stored)
+        .zip(scales_offsets)
+        .map(|(((tensor, quantized), stored), &scales_offset)| {
+            let described = IndexedTensor {
+                id: tensor.id(),
+                name: tensor.name(),
+                data_type: tensor.data_type(),
+                shape: tensor.shape(),
+                data_size: size_of_val(tensor.data()),
+                stored: *stored,
+                external: None,
+                alias_of: None,
+                user_metadata: None,
+                quantization: None,
+                scales_offset: None,
+            };
+            match quantized {
+                Some(quantized) => IndexedTensor {
+                    data_type: quantized.params.stored_data_type(),
+                    shape: &quantized.shape,
+                    data_size: quantized.bytes.len(),
+                    quantization: Some(&quantized.params),
+                    scales_offset,
+                    ..described
+                },
+                None => described,
+            }
+        })
+        .chain(external.iter().zip(&scales_offsets[tensors.len()..]).map(
+            |(tensor, &scales_offset)| IndexedTensor {
+                id: tensor.id,
+                name: &tensor.name,
+                data_type: tensor.data_type,
+                shape: &tensor.shape,
+                data_size: tensor.data_size,
+                stored: tensor.stored,
+                external: Some(&tensor.location),
+                alias_of: None,
+                user_metadata: None,
+                quantization: tensor.quantization.as_ref(),
+                scales_offset,
+            },
+        ))
+        .collect::<Vec<_>>();
+    let by_name = described.iter().map(|tensor| (tensor.name, *tensor)).collect::<HashMap<_, _>>();
+    for (existing, name) in aliases {
+        let target = by_name[existing.as_str()];
+        described.push(IndexedTensor {
+            id: hash_key(name),
+            name,
+            alias_of: Some(existing),
+            user_metadata: None,
+            ..target
+        });
+    }
+    for (name, bytes) in user_metadata {
+        if let Some(tensor) = described.iter_mut().find(|tensor| tensor.name == name) {
+            tensor.user_metadata = Some(bytes);
+        }
+    }
+    described
+}
+
+/// Builds the FlatBuffers metadata for the tensors `describe_tensors` describes, with the file's
+/// model card, provenance and constraints, if any.
+///
+/// # Returns
+/// An error if the options ask for columnar metadata, which can't describe some of the tensors.
+fn build_metadata(
+    tensors: &[IndexedTensor<'_>],
+    operations: Vec<TensorOperation>,
+    options: &WriterOptions,
+    dictionary: Option<&[u8]>,
+    model_card: Option<&str>,
+    provenance: Option<&Provenance>,
+    constraints: &[TensorConstraint],
+) -> crate::Result<FlatBufferBuilder<'static>> {
+    let mut builder = FlatBufferBuilder::new();
+
+    // Build FlatBuffers metadata for all tensors.
+    // Tables are keyed by id, so they must be sorted for lookups to binary search them.
+    let mut tensor_metadata_offsets = Vec::with_capacity(tensors.len());
+    let mut features = FormatFeatures::default();
+    let mut tensor_order = (0..tensors.len()).collect::<Vec<_>>();
+    tensor_order.sort_by_key(|&i| tensors[i].id);
+    let tensor_columns = match options.columnar_metadata() {
+        true => {
+            features.add_columnar_metadata();
+            let sorted = tensor_order.iter().map(|&i| tensors[i]).collect::<Vec<_>>();
+            Some(build_columns(&mut builder, &sorted)?)
+        }
+        false => None,
+    };
+
+    for i in tensor_order {
+        // Create FlatBuffers metadata for this tensor, unless the columns describe it.
+        let tensor = &tensors[i];
+        if tensor_columns.is_none() {
+            tensor_metadata_offsets.push(tensor.build_table(&mut builder));
+        }
+        match tensor.external {
+            Some(_) => features.add_external(tensor.stored.compression, tensor.shape),
+            None => features.add_tensor(tensor.stored.compression, tensor.shape),
+        }
+        features.add_data_type(tensor.data_type);
+        if tensor.quantization.is_some() {
+            features.add_quantization();
+        }
+    }
+
+    let mut operations = operations;
+    operations.sort_by_key(|op| op.id());
+    let mut operations_metadata_offsets = Vec::with_capacity(operations.len());
+    for op in operations {
+        // Serialize the operation.
+        let operation_metadata = TensorOperation::build_table(&mut builder, op);
+        operations_metadata_offsets.push(operation_metadata);
+    }
+
+    let tensor_buffers_metadata = TensorBuffers::build_versioned_table(
+        &mut builder,
+        options.format_version(),
+        &tensor_metadata_offsets,
+        tensor_columns,
+        &operations_metadata_offsets,
+        &features,
+        dictionary,
+        options.user_metadata(),
+        model_card,
+        provenance,
+        constraints,
+    );
+    builder.finish(tensor_buffers_metadata, None);
+    Ok(builder)
+}
+
+/// Checks that tensor data regions lie within the data section, which ends at `data_end`, fit in
+/// the format's 32-bit offsets and sizes, and don't overlap unless deduplicated.
+fn check_layout(stored: &[StoredData], data_end: u64) -> crate::Result<()> {
+    check_data_end(data_end)?;
+    let mut regions = stored.iter().map(|data| (data.offset, data.size as u64)).collect::<Vec<_>>();
+    regions.sort_unstable();
+    regions.dedup();
+    let mut previous_end = MAGIC_BYTES.len() as u64;
+    for (offset, size) in regions {
+        if offset < previous_end || offset + size > data_end {
+            return Err(format!(
+                "Tensor data region [{}, {}) overlaps another region or lies outside the data",
+                offset,
+                offset + size
+            )
+            .into());
+        }
+        previous_end = offset + size;
     }
+    Ok(())
+}
+
+/// Writes the metadata and the footer that ends the file, and flushes the writer.
+///
+/// # Arguments
+/// * `writer` - The destination, positioned right after the tensor data.
+/// * `metadata` - The finished FlatBuffers metadata, which is compressed if `contents` says so.
+/// * `contents` - What the footer describes besides the metadata.
+///
+/// # Returns
+/// The number of bytes written.
+pub(crate) async fn write_metadata<W>(
+    writer: &mut W,
+    metadata: &[u8],
+    contents: &FooterContents<'_>,
+) -> Result<u64>
+where
+    W: AsyncWrite + Unpin,
+{
+    let metadata = encode_metadata(metadata, contents)?;
+    let footer = encode_footer(&metadata, contents)?;
+    writer.write_all(&metadata).await?;
+    writer.write_all(&footer).await?;
+    writer.flush().await?;
+    Ok((metadata.len() + footer.len()) as u64)
 }
 
 // Implements the serialization and writing logic for tensors.
 impl<W> TensorBuffersWrite for TensorBuffersWriter<W>
 where
-    W: AsyncWrite + AsyncSeek + Unpin,
+    W: AsyncWrite + AsyncSeek + Unpin + Send,
 {
     /// Serializes and writes tensors to the underlying writer in a custom format.
-    /// The format: magic bytes | tensor data | FlatBuffers metadata | metadata size | magic bytes.
+    /// The format: magic bytes | [metadata copy] | tensor data | [name index] | FlatBuffers
+    /// metadata | footer.
+    #[instrument(
+        skip_all,
+        fields(
+            tensors = tensors.len(),
+            external = self.external.len(),
+            operations = operations.len(),
+            bytes = Empty,
+            elapsed_ms = Empty
+        )
+    )]
     async fn write<'a, T>(
         &mut self,
         tensors: Vec<Tensor<'a, T>>,
@@ -61,61 +965,241 @@ where
     where
         T: Pod + Num,
     {
+        let start = Instant::now();
+        let aliases = std::mem::take(&mut self.aliases);
+        let user_metadata = std::mem::take(&mut self.user_metadata);
+        let constraints = std::mem::take(&mut self.constraints);
+        let external = std::mem::take(&mut self.external);
+        let external = external.as_slice();
+        let external_dictionary = self.external_dictionary.take();
+        self.options.validate().map_err(invalid_input)?;
+        check_tensors(&tensors, external, &aliases, &user_metadata, &constraints)
+            .map_err(invalid_input)?;
+        for tensor in &tensors {
+            check_data_type_support(tensor.name(), tensor.data_type(), &self.options)
+                .map_err(invalid_input)?;
+        }
+        if !external.is_empty() && !self.options.supports(MIN_VERSION_FOR_FEATURES) {
+            return Err(invalid_input(
+                format!(
+                    "Format version {} does not support external data",
+                    self.options.format_version()
+                )
+                .into(),
+            ));
+        }
+        if external_dictionary.is_some() && self.options.compression_dictionary() > 0 {
+            return Err(invalid_input(
+                "Tensors stored in other files use their file's compression dictionary, so this \
+                 file can't train its own"
+                    .into(),
+            ));
+        }
         // Write the initial magic bytes to identify the file format.
+        let file_start = self.writer.stream_position().await?;
         self.writer.write_all(MAGIC_BYTES).await?;
 
-        // Track the starting offset for each tensor's data.
-        let mut data_offsets = Vec::with_capacity(tensors.len());
         // Offset starts after the magic bytes.
-        let mut current_offset = 4u64;
+        let mut current_offset = MAGIC_BYTES.len() as u64;
 
-        // Write each tensor's data and record its offset.
-        for t in tensors.iter() {
-            data_offsets.push(current_offset as u32);
-            // Convert tensor data to bytes.
-            let data_bytes = bytemuck::cast_slice::<T, u8>(t.data());
-            let data_size = data_bytes.len() as u32;
-            self.writer.write_all(data_bytes).await?;
-            current_offset += data_size as u64;
+        let mut order = (0..tensors.len()).collect::<Vec<_>>();
+        if self.options.sort_by_name() {
+            order.sort_by(|&a, &b| tensors[a].name().cmp(tensors[b].name()));
         }
+        let quantized = quantize_tensors(&tensors, &self.options);
+        // Group-wise scales are too many for the metadata, so a section after the data holds them.
+        let (scales, scale_positions) = encode_scales(
+            quantized
+                .iter()
+                .map(|quantized| quantized.as_ref().map(|quantized| &quantized.params))
+                .chain(external.iter().map(|tensor| tensor.quantization.as_ref())),
+        );
+        let place_scales = |data_end: u64| {
+            scale_positions
+                .iter()
+                .map(|position| position.map(|position| data_end + position))
+                .collect::<Vec<_>>()
+        };
+        let dictionary = train_dictionary(&tensors, &quantized, &self.options).map(Arc::new);
+        let mut progress = ProgressCounter::new(&tensors, start);
+        let dictionary_bytes = dictionary
+            .as_deref()
+            .map(CompressionDictionary::bytes)
+            .or(external_dictionary.as_deref());
 
-        let mut builder = FlatBufferBuilder::new();
-
-        // Build FlatBuffers metadata for all tensors.
-        let mut tensor_metadata_offsets = Vec::with_capacity(tensors.len());
-
-        for (i, t) in tensors.iter().enumerate() {
-            // Create FlatBuffers metadata for this tensor.
-            let tensor_metadata = Tensor::build_table(&mut builder, &t, data_offsets[i] as usize);
-            tensor_metadata_offsets.push(tensor_metadata);
+        // Write each tensor's data and record where and how it is stored.
+        let data_span = debug_span!("write_data", bytes = Empty, elapsed_ms = Empty);
+        let (stored, leading_metadata) = async {
+            let mut leading_metadata = None;
+            let stored = if self.options.leading_metadata() {
+                // The metadata copy must point past itself, so lay the data out from an aligned
+                // start first, then move it after the copy.
+                let alignment = self.options.alignment() as u64;
+                let mut layout = DataLayout::new(
+                    &tensors,
+                    &quantized,
+                    order,
+                    alignment,
+                    &self.options,
+                    dictionary.clone(),
+                );
+                let mut chunks = Vec::with_capacity(tensors.len());
+                while let Some(chunk) = layout.next().await.map_err(invalid_input)? {
+                    chunks.push(chunk);
+                }
+                let data_end = layout.end;
+                let mut stored = layout.into_stored();
+                let options = &self.options;
+                let (model_card, provenance) =
+                    (self.model_card.as_deref(), self.provenance.as_ref());
+                let build = |stored: &[StoredData], data_end: u64| {
+                    let described = describe_tensors(
+                        &tensors,
+                        &quantized,
+                        &place_scales(data_end),
+                        stored,
+                        external,
+                        &aliases,
+                        &user_metadata,
+                    );
+                    let operations = operations.clone();
+                    let dictionary = dictionary_bytes;
+                    build_metadata(
+                        &described,
+                        operations,
+                        options,
+                        dictionary,
+                        model_card,
+                        provenance,
+                        &constraints,
+                    )
+                };
+                let size = build(&stored, data_end).map_err(invalid_input)?.finished_data().len();
+                let data_start = ((MAGIC_BYTES.len() + LEADING_HEADER_SIZE + size) as u64)
+                    .next_multiple_of(alignment);
+                for data in &mut stored {
+                    data.offset += data_start - alignment;
+                }
+                let builder =
+                    build(&stored, data_end + data_start - alignment).map_err(invalid_input)?;
+                let metadata = builder.finished_data();
+                let metadata_size = u32::try_from(metadata.len())
+                    .ok()
+                    .filter(|&moved| moved as usize == size)
+                    .ok_or_else(|| Error::other("Metadata can't be copied before the data"))?;
+                self.writer.write_all(LEADING_METADATA_MAGIC_BYTES).await?;
+                self.writer.write_all(&metadata_size.to_le_bytes()).await?;
+                self.writer.write_all(&crc32c::crc32c(metadata).to_le_bytes()).await?;
+                self.writer.write_all(metadata).await?;
+                current_offset += (LEADING_HEADER_SIZE + size) as u64;
+                self.writer.write_all(&vec![0; (data_start - current_offset) as usize]).await?;
+                current_offset = data_start;
+                for (i, padding, bytes) in chunks {
+                    self.writer.write_all(&vec![0; padding]).await?;
+                    self.writer.write_all(&bytes).await?;
+                    current_offset += (padding + bytes.len()) as u64;
+                    progress.written(&tensors[i], &mut self.progress);
+                }
+                leading_metadata = Some(builder);
+                stored
+            } else {
+                let mut layout = DataLayout::new(
+                    &tensors,
+                    &quantized,
+                    order,
+                    current_offset,
+                    &self.options,
+                    dictionary.clone(),
+                );
+                while let Some((i, padding, bytes)) = layout.next().await.map_err(invalid_input)? {
+                    self.writer.write_all(&vec![0; padding]).await?;
+                    self.writer.write_all(&bytes).await?;
+                    current_offset += (padding + bytes.len()) as u64;
+                    progress.written(&tensors[i], &mut self.progress);
+                }
+                layout.into_stored()
+            };
+            let span = Span::current();
+            span.record("bytes", current_offset - MAGIC_BYTES.len() as u64);
+            span.record("elapsed_ms", elapsed_ms(start));
+            Result::Ok((stored, leading_metadata))
         }
+        .instrument(data_span)
+        .await?;
+        let scales_offsets = place_scales(current_offset);
+        self.writer.write_all(&scales).await?;
+        current_offset += scales.len() as u64;
 
-        let mut operations_metadata_offsets = Vec::with_capacity(operations.len());
-        // Write the operations to the writer.
-        for op in operations {
-            // Serialize and write the operation.
-            let operation_metadata = TensorOperation::build_table(&mut builder, op);
-            operations_metadata_offsets.push(operation_metadata);
+        // Fail loudly rather than write metadata that points at the wrong bytes.
+        let written = self.writer.stream_position().await? - file_start;
+        if written != current_offset {
+            return Err(Error::other(format!(
+                "Wrote {} bytes of tensor data, but the layout expects {}",
+                written, current_offset
+            )));
         }
+        check_layout(&stored, current_offset).map_err(|error| Error::other(error.to_string()))?;
 
-        let tensor_buffers_metadata = TensorBuffers::build_table(
-            &mut builder,
-            &tensor_metadata_offsets,
-            &operations_metadata_offsets,
+        let mut described = describe_tensors(
+            &tensors,
+            &quantized,
+            &scales_offsets,
+            &stored,
+            external,
+            &aliases,
+            &user_metadata,
         );
-        builder.finish(tensor_buffers_metadata, None);
+        if self.options.name_index() {
+            let section = encode_name_index(current_offset, &mut described);
+            self.writer.write_all(&section).await?;
+            current_offset += section.len() as u64;
+        }
+
+        let operations_count = operations.len();
+        let metadata_span = debug_span!("build_metadata", bytes = Empty, elapsed_ms = Empty);
+        let builder = match leading_metadata {
+            Some(builder) => builder,
+            None => metadata_span.in_scope(|| {
+                let metadata_start = Instant::now();
+                let builder = build_metadata(
+                    &described,
+                    operations,
+                    &self.options,
+                    dictionary_bytes,
+                    self.model_card.as_deref(),
+                    self.provenance.as_ref(),
+                    &constraints,
+                )
+                .map_err(invalid_input)?;
+                let span = Span::current();
+                span.record("bytes", builder.finished_data().len());
+                span.record("elapsed_ms", elapsed_ms(metadata_start));
+                Result::Ok(builder)
+            })?,
+        };
 
-        // Write FlatBuffers metadata to the writer.
         let flatbuffer_data = builder.finished_data();
-        self.writer.write_all(flatbuffer_data).await?;
-        let metadata_size = flatbuffer_data.len() as u32;
 
-        // Write the size of the metadata (little-endian u32).
-        self.writer.write(metadata_size.to_le_bytes().as_ref()).await?;
+        let filter = self.options.bloom_filter().then(|| {
+            BloomFilter::new(&described.iter().map(|tensor| tensor.id).collect::<Vec<_>>())
+        });
+        let contents = FooterContents {
+            format_version: self.options.format_version(),
+            metadata_offset: current_offset,
+            tensors: described.len(),
+            operations: operations_count,
+            name_index: self.options.name_index(),
+            compressed_metadata: self.options.compressed_metadata(),
+            filter: filter.as_ref(),
+        };
+        let metadata_size = write_metadata(&mut self.writer, flatbuffer_data, &contents).await?;
 
-        // Write trailing magic bytes to mark the end of the file.
-        self.writer.write(MAGIC_BYTES).await?;
-        self.writer.flush().await?;
+        self.stats.bytes_written += current_offset + metadata_size;
+        self.stats.tensors_written += tensors.len() as u64;
+        self.stats.writes += 1;
+        let span = Span::current();
+        span.record("bytes", current_offset + metadata_size);
+        span.record("elapsed_ms", elapsed_ms(start));
         Ok(())
     }
 }
@@ -151,4 +1235,219 @@ mod tests {
         file.seek(SeekFrom::Start(0)).await.unwrap();
         assert!(file.metadata().await.unwrap().len() > 0);
     }
+
+    #[tokio::test]
+    async fn test_write_rejects_invalid_tensors() {
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        let mut writer = TensorBuffersWriter::new(&mut bytes);
+        let misshapen = Tensor::new("1", &[1.0f32, 2.0, 3.0], vec![2, 2]);
+        let error = writer.write(vec![misshapen], vec![]).await.unwrap_err();
+        assert_eq!(error.to_string(), "Tensor 1 has shape [2, 2], but 3 elements");
+
+        let tensor = Tensor::new("1", &[1.0f32], vec![1]);
+        let error = writer.write(vec![tensor.clone(), tensor], vec![]).await.unwrap_err();
+        assert_eq!(error.to_string(), "Tensors 1 and 1 have the same id");
+        assert!(bytes.get_ref().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_add_alias() {
+        let embeddings = (0..1024).map(|i| i as f32).collect::<Vec<_>>();
+        let tensors = vec![
+            Tensor::new("embed.weight", &embeddings, vec![32, 32]),
+            Tensor::new("norm.weight", &[1.0f32; 32], vec![32]),
+        ];
+        let options = WriterOptions::new()
+            .with_checksum(crate::ChecksumAlgorithm::Crc32c)
+            .with_name_index(true)
+            .with_leading_metadata(true);
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        let mut writer = TensorBuffersWriter::with_options(&mut file, options.clone());
+        writer.add_alias("embed.weight", "lm_head.weight");
+        writer.write(tensors.clone(), vec![]).await.unwrap();
+
+        let url = format!("file://{}", tmp.path().display());
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        assert_eq!(tensor_buffers.aliases().await.unwrap(), [("lm_head.weight", "embed.weight")]);
+        let head = tensor_buffers.get_tensor_data_by_name::<f32>("lm_head.weight").await.unwrap();
+        assert_eq!(
+            (head.name(), head.shape(), head.data()),
+            ("lm_head.weight", &[32, 32][..], &embeddings[..])
+        );
+        let embed = tensor_buffers.stored_metadata(crate::utils::hash_key("embed.weight"));
+        let head = tensor_buffers.stored_metadata(crate::utils::hash_key("lm_head.weight"));
+        assert_eq!(embed.await.unwrap().data_offset(), head.await.unwrap().data_offset());
+        let opened =
+            TensorBuffers::open_with(&url, crate::OpenOptions::new().with_name_index(true));
+        let head = opened.await.unwrap().get_tensor_data_by_name::<f32>("lm_head.weight").await;
+        assert_eq!(head.unwrap().data(), &embeddings[..]);
+
+        // Aliases must name a tensor being written and a name of their own.
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        let mut writer = TensorBuffersWriter::with_options(&mut bytes, options);
+        writer.add_alias("missing", "lm_head.weight");
+        let error = writer.write(tensors.clone(), vec![]).await.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Alias lm_head.weight refers to missing, which is not a tensor"
+        );
+        writer.add_alias("embed.weight", "norm.weight");
+        let error = writer.write(tensors, vec![]).await.unwrap_err();
+        assert_eq!(error.to_string(), "Tensors norm.weight and norm.weight have the same id");
+    }
+
+    #[tokio::test]
+    async fn test_user_metadata() {
+        let tensors = vec![
+            Tensor::new("embed.weight", &[1.0f32; 16], vec![4, 4]),
+            Tensor::new("norm.weight", &[1.0f32; 4], vec![4]),
+        ];
+        let options =
+            WriterOptions::new().with_name_index(true).with_user_metadata(b"org".to_vec());
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        let mut writer = TensorBuffersWriter::with_options(&mut file, options.clone());
+        writer.add_alias("embed.weight", "lm_head.weight");
+        writer.add_user_metadata("embed.weight", b"tied".to_vec());
+        writer.add_user_metadata("lm_head.weight", b"head".to_vec());
+        writer.write(tensors.clone(), vec![]).await.unwrap();
+
+        let url = format!("file://{}", tmp.path().display());
+        let opened =
+            TensorBuffers::open_with(&url, crate::OpenOptions::new().with_name_index(true));
+        let tensor_buffers = opened.await.unwrap();
+        let embed = tensor_buffers.tensor_user_metadata("embed.weight").await.unwrap();
+        assert_eq!(embed, Some(&b"tied"[..]));
+        let head = tensor_buffers.tensor_user_metadata("lm_head.weight").await.unwrap();
+        assert_eq!(head, Some(&b"head"[..]));
+        assert_eq!(tensor_buffers.tensor_user_metadata("norm.weight").await.unwrap(), None);
+        assert!(tensor_buffers.tensor_user_metadata("missing").await.is_err());
+        assert_eq!(tensor_buffers.user_metadata().await.unwrap(), Some(&b"org"[..]));
+
+        // User metadata must name a tensor the file lists.
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        let mut writer = TensorBuffersWriter::with_options(&mut bytes, options);
+        writer.add_user_metadata("missing", b"lost".to_vec());
+        let error = writer.write(tensors, vec![]).await.unwrap_err();
+        assert_eq!(error.to_string(), "User metadata refers to missing, which is not a tensor");
+    }
+
+    #[tokio::test]
+    async fn test_model_card() {
+        let card = "# Tiny model\n\nFor tests only.\n";
+        let tensors = vec![Tensor::new("w", &[1.0f32; 4], vec![4])];
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        let options = WriterOptions::new().with_leading_metadata(true);
+        let mut writer = TensorBuffersWriter::with_options(&mut file, options);
+        writer.set_model_card(card);
+        writer.write(tensors.clone(), vec![]).await.unwrap();
+
+        let url = format!("file://{}", tmp.path().display());
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        assert_eq!(tensor_buffers.model_card().await.unwrap(), Some(card));
+
+        let mut file = File::create(tmp.path()).await.unwrap();
+        TensorBuffersWriter::new(&mut file).write(tensors, vec![]).await.unwrap();
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        assert_eq!(tensor_buffers.model_card().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_write_progress() {
+        let data = [vec![1.0f32; 1024], vec![2.0f32; 256], vec![1.0f32; 1024]];
+        let tensors = ["a", "b", "c"]
+            .iter()
+            .zip(&data)
+            .map(|(name, data)| Tensor::new(name, data, vec![data.len()]))
+            .collect::<Vec<_>>();
+        for options in [
+            WriterOptions::new(),
+            WriterOptions::new().with_compression(Compression::Zstd, 3).with_dedup(true),
+            WriterOptions::new().with_leading_metadata(true).with_sort_by_name(true),
+        ] {
+            let reports = Arc::new(std::sync::Mutex::new(Vec::new()));
+            let mut bytes = std::io::Cursor::new(Vec::new());
+            let mut writer = TensorBuffersWriter::with_options(&mut bytes, options);
+            let seen = reports.clone();
+            writer.on_progress(move |progress| {
+                assert_eq!((progress.total_bytes, progress.total_tensors), (9216, 3));
+                let report = (progress.tensor_name.to_string(), progress.bytes_written);
+                seen.lock().unwrap().push((report, progress.tensors_written));
+            });
+            writer.write(tensors.clone(), vec![]).await.unwrap();
+            let reports = reports.lock().unwrap();
+            assert_eq!(*reports, [
+                (("a".to_string(), 4096), 1),
+                (("b".to_string(), 5120), 2),
+                (("c".to_string(), 9216), 3),
+            ]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_parallel_compression() {
+        // More tensors than may be pending at once, some of them duplicates.
+        let data = (0..300).map(|i| vec![(i % 40) as f32; 64 + i]).collect::<Vec<_>>();
+        let names = (0..300).map(|i| format!("t{}", i)).collect::<Vec<_>>();
+        let mut tensors = Vec::new();
+        for (name, data) in names.iter().zip(&data) {
+            tensors.push(Tensor::new(name, data, vec![data.len()]));
+        }
+        async fn write(tensors: &[Tensor<'_, f32>], options: WriterOptions) -> Vec<u8> {
+            let mut bytes = std::io::Cursor::new(Vec::new());
+            let mut writer = TensorBuffersWriter::with_options(&mut bytes, options);
+            writer.write(tensors.to_vec(), vec![]).await.unwrap();
+            bytes.into_inner()
+        }
+        for options in [
+            WriterOptions::new().with_compression(Compression::Zstd, 3),
+            WriterOptions::new()
+                .with_compression(Compression::Lz4, 0)
+                .with_checksum(crate::ChecksumAlgorithm::Crc32c)
+                .with_alignment(64)
+                .with_dedup(true),
+            WriterOptions::new()
+                .with_compression(Compression::Zstd, 1)
+                .with_dedup(true)
+                .with_leading_metadata(true),
+            WriterOptions::new().with_compression(Compression::Zstd, 3).with_compression_policy(
+                crate::CompressionPolicy::default().with_min_size(1024).with_override(
+                    "t1*",
+                    Compression::Lz4,
+                    0,
+                ),
+            ),
+        ] {
+            let parallel = write(&tensors, options.clone()).await;
+            let sequential = write(&tensors, options.with_parallel_compression(false)).await;
+            assert!(parallel == sequential);
+        }
+    }
+
+    #[test]
+    fn test_check_name() {
+        for name in ["w", "encoder.layers.0.weight", "layer/0 \"w\"", "onnx::MatMul_12"] {
+            assert!(check_name(name).is_ok(), "{}", name);
+        }
+        let long = "x".repeat(MAX_NAME_LENGTH + 1);
+        for name in ["", &long, "a\0b", "line\nbreak", ".w", "w.", "layers..0"] {
+            assert!(check_name(name).is_err(), "{:?}", name);
+        }
+        assert_eq!(
+            check_name("layers..0").unwrap_err().to_string(),
+            "Tensor name \"layers..0\" has an empty namespace around '.'"
+        );
+    }
+
+    #[test]
+    fn test_check_layout() {
+        let regions = [StoredData::raw(4, 8), StoredData::raw(12, 4), StoredData::raw(4, 8)];
+        assert!(check_layout(&regions, 16).is_ok());
+        assert!(check_layout(&regions, 15).is_err());
+        assert!(check_layout(&[StoredData::raw(4, 8), StoredData::raw(8, 8)], 16).is_err());
+        assert!(check_layout(&[StoredData::raw(0, 4)], 16).is_err());
+        assert!(check_layout(&[], u32::MAX as u64 + 1).is_err());
+    }
 }
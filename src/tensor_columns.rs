@@ -0,0 +1,507 @@
+use flatbuffers::Vector;
+#[cfg(any(feature = "async", feature = "mmap"))]
+use flatbuffers::{FlatBufferBuilder, WIPOffset};
+
+#[cfg(feature = "async")]
+use crate::{
+    generated::tensor_buffers::TensorColumnsArgs, name_index::IndexedTensor,
+    num_trait::DataType as ElementType, tensor::is_wide_shape,
+};
+use crate::{
+    generated::tensor_buffers::{
+        ChecksumAlgorithm, Compression, DataType, TensorBuffersMetadata, TensorColumns,
+    },
+    read_mode::{entry_problem, is_sorted},
+    Result, TensorId,
+};
+#[cfg(any(feature = "async", feature = "mmap"))]
+use crate::{
+    generated::tensor_buffers::{TensorBuffersMetadataArgs, TensorMetadata, TensorMetadataArgs},
+    Provenance, TensorConstraint, TensorOperation,
+};
+
+/// Builds the columns that describe `tensors`, which must be sorted by id.
+///
+/// # Returns
+/// An error if a tensor needs a table of its own: tensors stored in other files, aliases, tensors
+/// encoded with custom codecs or of extension types and tensors with user metadata or quantized
+/// values do.
+#[cfg(feature = "async")]
+pub(crate) fn build_columns<'b>(
+    builder: &mut FlatBufferBuilder<'b>,
+    tensors: &[IndexedTensor<'_>],
+) -> Result<WIPOffset<TensorColumns<'b>>> {
+    let checksum_algorithm =
+        tensors.first().map_or(ChecksumAlgorithm::None, |tensor| tensor.stored.checksum_algorithm);
+    for tensor in tensors {
+        let needs = if tensor.external.is_some() {
+            "data stored in another file"
+        } else if tensor.alias_of.is_some() {
+            "an alias"
+        } else if tensor.stored.codec.is_some() {
+            "a custom codec"
+        } else if matches!(tensor.data_type, ElementType::Extension(_)) {
+            "an extension data type"
+        } else if tensor.user_metadata.is_some() {
+            "user metadata"
+        } else if tensor.quantization.is_some() {
+            "quantized values"
+        } else if tensor.stored.checksum_algorithm != checksum_algorithm {
+            "a checksum algorithm of its own"
+        } else {
+            continue;
+        };
+        return Err(format!(
+            "Columnar metadata can't describe {}, needed by tensor {}",
+            needs, tensor.name
+        )
+        .into());
+    }
+
+    let mut names = Vec::new();
+    let mut name_ends = Vec::with_capacity(tensors.len());
+    let mut dims = Vec::new();
+    let mut shape_ends = Vec::with_capacity(tensors.len());
+    for tensor in tensors {
+        names.extend_from_slice(tensor.name.as_bytes());
+        name_ends.push(names.len() as u32);
+        dims.extend(tensor.shape.iter().map(|&dim| dim as u64));
+        shape_ends.push(dims.len() as u32);
+    }
+    let column = |f: fn(&IndexedTensor) -> u32| tensors.iter().map(f).collect::<Vec<_>>();
+    let data_offsets = column(|tensor| tensor.stored.offset as u32);
+    let data_sizes = column(|tensor| tensor.data_size as u32);
+    let compressed = tensors.iter().any(|tensor| tensor.stored.compression != Compression::None);
+
+    // Like shapes in tables, dimensions are stored as u32s unless one doesn't fit.
+    let wide = tensors.iter().any(|tensor| is_wide_shape(tensor.shape));
+    let ids = builder.create_vector_from_iter(tensors.iter().map(|tensor| tensor.id));
+    let name_ends = builder.create_vector(&name_ends);
+    let names = builder.create_vector(&names);
+    let shape_ends = builder.create_vector(&shape_ends);
+    let (dims, dims64) = match wide {
+        true => (None, Some(builder.create_vector(&dims))),
+        false => (Some(builder.create_vector_from_iter(dims.iter().map(|&dim| dim as u32))), None),
+    };
+    let data_types = builder.create_vector_from_iter(
+        tensors.iter().map(|tensor| -> DataType { tensor.data_type.into() }),
+    );
+    let data_offsets = builder.create_vector(&data_offsets);
+    let data_sizes = builder.create_vector(&data_sizes);
+    let (compressions, stored_sizes) = match compressed {
+        true => {
+            let compressions = tensors.iter().map(|tensor| tensor.stored.compression);
+            let compressions = builder.create_vector_from_iter(compressions);
+            let stored_sizes = column(|tensor| match tensor.stored.compression {
+                Compression::None => 0,
+                _ => tensor.stored.size as u32,
+            });
+            (Some(compressions), Some(builder.create_vector(&stored_sizes)))
+        }
+        false => (None, None),
+    };
+    let checksums = (checksum_algorithm != ChecksumAlgorithm::None).then(|| {
+        builder.create_vector_from_iter(tensors.iter().map(|tensor| tensor.stored.checksum))
+    });
+    Ok(TensorColumns::create(builder, &TensorColumnsArgs {
+        ids: Some(ids),
+        name_ends: Some(name_ends),
+        names: Some(names),
+        shape_ends: Some(shape_ends),
+        dims,
+        dims64,
+        data_types: Some(data_types),
+        data_offsets: Some(data_offsets),
+        data_sizes: Some(data_sizes),
+        compressions,
+        stored_sizes,
+        checksum_algorithm,
+        checksums,
+    }))
+}
+
+/// A file's tensor columns, checked to agree with each other, so each tensor's entry can be read
+/// from them without further checks.
+#[derive(Clone, Copy)]
+pub(crate) struct Columns<'a> {
+    columns: TensorColumns<'a>,
+    ids: Vector<'a, u64>,
+    names: &'a str,
+    name_ends: Vector<'a, u32>,
+    #[cfg(any(feature = "async", feature = "mmap"))]
+    shape_ends: Vector<'a, u32>,
+}
+
+impl<'a> Columns<'a> {
+    /// Returns the columns of `metadata`, if it stores its tensors as columns.
+    ///
+    /// # Returns
+    /// An error if a column is missing or holds another number of tensors than the ids, if the
+    /// names or shapes are out of bounds, or if the names aren't UTF-8.
+    pub fn of(metadata: &TensorBuffersMetadata<'a>) -> Result<Option<Self>> {
+        let Some(columns) = metadata.tensor_columns() else {
+            return Ok(None);
+        };
+        let missing = |column: &str| format!("Tensor columns have no {}", column);
+        let ids = columns.ids().ok_or_else(|| missing("ids"))?;
+        let name_ends = columns.name_ends().ok_or_else(|| missing("name_ends"))?;
+        let names = columns.names().ok_or_else(|| missing("names"))?;
+        let shape_ends = columns.shape_ends().ok_or_else(|| missing("shape_ends"))?;
+        let data_types = columns.data_types().ok_or_else(|| missing("data_types"))?;
+        let data_offsets = columns.data_offsets().ok_or_else(|| missing("data_offsets"))?;
+        let data_sizes = columns.data_sizes().ok_or_else(|| missing("data_sizes"))?;
+        let dims_len = match (columns.dims64(), columns.dims()) {
+            (Some(dims), _) => dims.len(),
+            (None, Some(dims)) => dims.len(),
+            (None, None) => return Err(missing("dims").into()),
+        };
+        if columns.compressions().is_some() != columns.stored_sizes().is_some() {
+            return Err("Tensor columns have only one of compressions and stored_sizes".into());
+        }
+        if columns.checksum_algorithm() != ChecksumAlgorithm::None && columns.checksums().is_none()
+        {
+            return Err(missing("checksums").into());
+        }
+
+        let len = ids.len();
+        let lengths = [
+            ("name_ends", name_ends.len()),
+            ("shape_ends", shape_ends.len()),
+            ("data_types", data_types.len()),
+            ("data_offsets", data_offsets.len()),
+            ("data_sizes", data_sizes.len()),
+            ("compressions", columns.compressions().map_or(len, |column| column.len())),
+            ("stored_sizes", columns.stored_sizes().map_or(len, |column| column.len())),
+            ("checksums", columns.checksums().map_or(len, |column| column.len())),
+        ];
+        if let Some((column, other)) = lengths.into_iter().find(|&(_, other)| other != len) {
+            return Err(format!("Tensor columns have {} ids, but {} {}", len, other, column).into());
+        }
+        let names = std::str::from_utf8(names.bytes())
+            .map_err(|_| "Tensor columns have names that aren't UTF-8")?;
+        if !ends_within(name_ends, names.len(), |end| names.is_char_boundary(end)) {
+            return Err("Tensor columns have name ends out of order or out of bounds".into());
+        }
+        if !ends_within(shape_ends, dims_len, |_| true) {
+            return Err("Tensor columns have shape ends out of order or out of bounds".into());
+        }
+        Ok(Some(Columns {
+            columns,
+            ids,
+            names,
+            name_ends,
+            #[cfg(any(feature = "async", feature = "mmap"))]
+            shape_ends,
+        }))
+    }
+
+    /// Returns the number of tensors.
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    /// Returns the tensors' ids, in the order of the columns.
+    pub fn ids(&self) -> impl Iterator<Item = TensorId> + 'a {
+        self.ids.iter()
+    }
+
+    /// Returns the index of the tensor with the given id, found by a binary search if the ids
+    /// are `sorted` and a scan otherwise.
+    #[cfg(feature = "async")]
+    pub fn find(&self, tensor_id: TensorId, sorted: bool) -> Option<usize> {
+        if !sorted {
+            return self.ids().position(|id| id == tensor_id);
+        }
+        let (mut low, mut high) = (0, self.len());
+        while low < high {
+            let middle = (low + high) / 2;
+            match self.ids.get(middle) {
+                id if id < tensor_id => low = middle + 1,
+                id if id > tensor_id => high = middle,
+                _ => return Some(middle),
+            }
+        }
+        None
+    }
+
+    /// Returns the name of the tensor at `index`.
+    pub fn name(&self, index: usize) -> &'a str {
+        let start = match index {
+            0 => 0,
+            _ => self.name_ends.get(index - 1) as usize,
+        };
+        &self.names[start..self.name_ends.get(index) as usize]
+    }
+
+    /// Returns every problem with the tensors that strict mode rejects, in id order.
+    pub fn problems(&self) -> Vec<String> {
+        let checksum_algorithm = self.columns.checksum_algorithm();
+        let mut problems = (0..self.len())
+            .filter_map(|index| {
+                entry_problem(
+                    self.name(index),
+                    self.data_type(index),
+                    true,
+                    self.compression(index),
+                    checksum_algorithm,
+                )
+            })
+            .collect::<Vec<_>>();
+        if !is_sorted(self.ids()) {
+            problems.push("Tensors are not sorted by id".to_string());
+        }
+        problems
+    }
+
+    /// Builds the table of the tensor at `index`, as files without columns store it.
+    #[cfg(any(feature = "async", feature = "mmap"))]
+    pub fn build_entry<'b>(
+        &self,
+        builder: &mut FlatBufferBuilder<'b>,
+        index: usize,
+    ) -> WIPOffset<TensorMetadata<'b>> {
+        let start = match index {
+            0 => 0,
+            _ => self.shape_ends.get(index - 1) as usize,
+        };
+        let dims = start..self.shape_ends.get(index) as usize;
+        let (shape, shape64) = match (self.columns.dims64(), self.columns.dims()) {
+            (Some(column), _) => {
+                (None, Some(builder.create_vector_from_iter(dims.map(|i| column.get(i)))))
+            }
+            (None, Some(column)) => {
+                (Some(builder.create_vector_from_iter(dims.map(|i| column.get(i)))), None)
+            }
+            (None, None) => (None, None),
+        };
+        let name = builder.create_string(self.name(index));
+        TensorMetadata::create(builder, &TensorMetadataArgs {
+            id: self.ids.get(index),
+            name: Some(name),
+            shape,
+            shape64,
+            data_type: self.data_type(index),
+            data_offset: self.columns.data_offsets().map_or(0, |column| column.get(index)),
+            data_size: self.columns.data_sizes().map_or(0, |column| column.get(index)),
+            compression: self.compression(index),
+            stored_size: self.columns.stored_sizes().map_or(0, |column| column.get(index)),
+            checksum_algorithm: self.columns.checksum_algorithm(),
+            checksum: self.columns.checksums().map_or(0, |column| column.get(index)),
+            ..Default::default()
+        })
+    }
+
+    /// Builds the table of the tensor at `index` as a FlatBuffer of its own.
+    #[cfg(feature = "async")]
+    pub fn entry_bytes(&self, index: usize) -> Vec<u8> {
+        let mut builder = FlatBufferBuilder::with_capacity(128);
+        let entry = self.build_entry(&mut builder, index);
+        builder.finish(entry, None);
+        builder.finished_data().to_vec()
+    }
+
+    /// Rebuilds `metadata`, whose columns these are, with a table per tensor.
+    #[cfg(any(feature = "async", feature = "mmap"))]
+    pub fn expand(&self, metadata: &TensorBuffersMetadata) -> Vec<u8> {
+        let mut builder = FlatBufferBuilder::new();
+        let tensors =
+            (0..self.len()).map(|index| self.build_entry(&mut builder, index)).collect::<Vec<_>>();
+        let tensors = builder.create_vector(&tensors);
+        let operations = metadata.operations().map(|operations| {
+            let operations = operations
+                .iter()
+                .map(|op| {
+                    TensorOperation::build_table(&mut builder, TensorOperation::with_metadata(&op))
+                })
+                .collect::<Vec<_>>();
+            builder.create_vector(&operations)
+        });
+        let version = builder.create_string(metadata.version());
+        let model = metadata.model().map(|model| builder.create_string(model));
+        let features = metadata.features().map(|features| {
+            let features =
+                features.iter().map(|feature| builder.create_string(feature)).collect::<Vec<_>>();
+            builder.create_vector(&features)
+        });
+        let compression_dictionary = metadata
+            .compression_dictionary()
+            .map(|dictionary| builder.create_vector(dictionary.bytes()));
+        let user_metadata =
+            metadata.user_metadata().map(|bytes| builder.create_vector(bytes.bytes()));
+        let model_card = metadata.model_card().map(|card| builder.create_string(card));
+        let provenance = metadata
+            .provenance()
+            .map(|provenance| Provenance::from_metadata(&provenance).build_table(&mut builder));
+        // Constraints this version can't read are left out, as they can't be checked anyway.
+        let constraints = metadata.constraints().map(|constraints| {
+            let constraints = constraints
+                .iter()
+                .filter_map(|constraint| TensorConstraint::from_metadata(&constraint).ok())
+                .map(|constraint| constraint.build_table(&mut builder))
+                .collect::<Vec<_>>();
+            builder.create_vector(&constraints)
+        });
+        let root = TensorBuffersMetadata::create(&mut builder, &TensorBuffersMetadataArgs {
+            version: Some(version),
+            model,
+            tensors: Some(tensors),
+            operations,
+            features,
+            compression_dictionary,
+            user_metadata,
+            tensor_columns: None,
+            model_card,
+            provenance,
+            constraints,
+        });
+        builder.finish(root, None);
+        builder.finished_data().to_vec()
+    }
+
+    fn data_type(&self, index: usize) -> DataType {
+        self.columns.data_types().map_or(DataType::None, |column| column.get(index))
+    }
+
+    fn compression(&self, index: usize) -> Compression {
+        self.columns.compressions().map_or(Compression::None, |column| column.get(index))
+    }
+}
+
+/// Returns whether `ends` never decrease and stay within `len`, each passing `valid`.
+fn ends_within(ends: Vector<'_, u32>, len: usize, valid: impl Fn(usize) -> bool) -> bool {
+    let mut previous = 0;
+    ends.iter().all(|end| {
+        let end = end as usize;
+        let within = previous <= end && end <= len && valid(end);
+        previous = end;
+        within
+    })
+}
+
+/// Rebuilds metadata that stores its tensors as columns with a table per tensor, for readers
+/// that visit every tensor's table.
+///
+/// # Returns
+/// The rebuilt metadata, or `None` if `metadata` has no columns. An error if its columns are
+/// invalid.
+#[cfg(any(feature = "async", feature = "mmap"))]
+pub(crate) fn expand_columns(metadata: &TensorBuffersMetadata) -> Result<Option<Vec<u8>>> {
+    Ok(Columns::of(metadata)?.map(|columns| columns.expand(metadata)))
+}
+
+#[cfg(all(test, feature = "async"))]
+mod tests {
+    use std::io::Cursor;
+
+    use futures::{stream, SinkExt, StreamExt};
+    use tempfile::NamedTempFile;
+    use tokio::fs::File;
+
+    use super::*;
+    use crate::{
+        parse_slice, slice_file::locate_metadata, Tensor, TensorAny, TensorBuffers,
+        TensorBuffersSink, TensorBuffersWrite, TensorBuffersWriter, WriterOptions,
+    };
+
+    fn tensors(names: &[String]) -> Vec<Tensor<'static, f32>> {
+        let tensor = |(i, name): (usize, &String)| {
+            Tensor::from_vec(name, vec![i as f32; 4 * (i % 3)], vec![2, 2 * (i % 3)])
+        };
+        names.iter().enumerate().map(tensor).collect()
+    }
+
+    async fn write(names: &[String], options: WriterOptions) -> NamedTempFile {
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        let mut writer = TensorBuffersWriter::with_options(&mut file, options);
+        writer.write(tensors(names), vec![]).await.unwrap();
+        tmp
+    }
+
+    fn metadata_size(tmp: &NamedTempFile) -> usize {
+        locate_metadata(&std::fs::read(tmp.path()).unwrap()).unwrap().1.len()
+    }
+
+    #[tokio::test]
+    async fn test_columnar_metadata() {
+        let names = (0..5000).map(|i| format!("layers.{}.weight", i)).collect::<Vec<_>>();
+        let options = WriterOptions::new()
+            .with_compression(Compression::Lz4, 0)
+            .with_checksum(ChecksumAlgorithm::Crc32c);
+        let tables = write(&names, options.clone()).await;
+        let columnar = write(&names, options.with_columnar_metadata(true)).await;
+        let (tables_size, columnar_size) = (metadata_size(&tables), metadata_size(&columnar));
+        assert!(columnar_size < tables_size, "{} vs {}", columnar_size, tables_size);
+
+        let url = format!("file://{}", columnar.path().display());
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        assert_eq!(tensor_buffers.tensor_count().await.unwrap(), 5000);
+        assert!(tensor_buffers.features().await.unwrap().contains(&"columnar_metadata"));
+        let tensor = tensor_buffers.get_tensor_data_by_name::<f32>("layers.517.weight").await;
+        let tensor = tensor.unwrap();
+        assert_eq!((tensor.shape(), tensor.data()), (&[2, 2][..], &[517.0; 4][..]));
+        let info = tensor_buffers.get_tensor_metadata_by_name("layers.3.weight").await.unwrap();
+        assert_eq!((info.name.as_str(), info.shape.as_slice()), ("layers.3.weight", &[2, 0][..]));
+        let missing = tensor_buffers.get_tensor_metadata_by_name("missing").await.unwrap_err();
+        assert_eq!(missing.to_string(), "Tensor ID not found in metadata");
+
+        let loaded = tensor_buffers.load_all::<f32>(8).await.unwrap();
+        assert_eq!(loaded.len(), 5000);
+        let url = format!("file://{}", tables.path().display());
+        let expected = TensorBuffers::open(&url).await.unwrap().load_all::<f32>(8).await.unwrap();
+        assert!(loaded
+            .iter()
+            .zip(&expected)
+            .all(|(a, b)| a.name() == b.name() && a.data() == b.data()));
+
+        let bytes = std::fs::read(columnar.path()).unwrap();
+        assert_eq!(
+            parse_slice(&bytes).unwrap_err().to_string(),
+            "Columnar metadata is not supported for files parsed from a slice"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_columnar_metadata_limits() {
+        let options = WriterOptions::new().with_columnar_metadata(true);
+        let mut writer =
+            TensorBuffersWriter::with_options(Cursor::new(Vec::new()), options.clone());
+        writer.add_alias("a", "b");
+        let tensors = vec![Tensor::new("a", &[1.0f32], vec![1])];
+        let error = writer.write(tensors, vec![]).await.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Columnar metadata can't describe an alias, needed by tensor b"
+        );
+
+        // Sinks write columns too, and wide shapes keep 64-bit dimensions.
+        let tmp = NamedTempFile::new().unwrap();
+        let file = File::create(tmp.path()).await.unwrap();
+        let mut sink = TensorBuffersSink::new(file, options).unwrap();
+        let tensors = vec![
+            TensorAny::from(Tensor::from_vec("wide", Vec::<f32>::new(), vec![0, 1 << 33])),
+            TensorAny::from(Tensor::from_vec("x", vec![1u8, 2], vec![2])),
+        ];
+        stream::iter(tensors).map(Ok).forward(&mut sink).await.unwrap();
+        sink.close().await.unwrap();
+        let bytes = std::fs::read(tmp.path()).unwrap();
+        let metadata =
+            flatbuffers::root::<TensorBuffersMetadata>(&bytes[locate_metadata(&bytes).unwrap().1])
+                .unwrap();
+        let columns = Columns::of(&metadata).unwrap().unwrap();
+        assert_eq!(columns.len(), 2);
+        assert!(
+            metadata.tensors().is_none() && metadata.tensor_columns().unwrap().dims().is_none()
+        );
+        let expanded = expand_columns(&metadata).unwrap().unwrap();
+        let expanded = flatbuffers::root::<TensorBuffersMetadata>(&expanded).unwrap();
+        let tables = expanded.tensors().unwrap();
+        let wide = tables.iter().find(|tensor| tensor.name() == "wide").unwrap();
+        assert_eq!(wide.shape64().unwrap().iter().collect::<Vec<_>>(), [0, 1 << 33]);
+
+        let url = format!("file://{}", tmp.path().display());
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        let x = tensor_buffers.get_tensor_data_by_name::<u8>("x").await.unwrap();
+        assert_eq!(x.data(), &[1, 2]);
+    }
+}
@@ -0,0 +1,201 @@
+use std::fmt::{self, Debug, Display};
+
+use crate::{num_trait::CastFrom, Num, Result, Tensor, TensorValue};
+
+/// An element-wise comparison of two tensors of the same shape.
+///
+/// Elements `a` and `b` match when `|a - b| <= atol + rtol * |b|`, as in numpy's `allclose`.
+/// NaN never matches.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TensorDiff {
+    len: usize,
+    max_abs_error: f64,
+    max_abs_error_index: Option<usize>,
+    first_mismatch: Option<usize>,
+    mismatches: usize,
+}
+
+impl TensorDiff {
+    fn compare<T>(lhs: &[T], rhs: &[T], rtol: f64, atol: f64) -> Self
+    where
+        T: Copy,
+        f64: CastFrom<T>,
+    {
+        let mut diff = TensorDiff {
+            len: lhs.len(),
+            max_abs_error: 0.0,
+            max_abs_error_index: None,
+            first_mismatch: None,
+            mismatches: 0,
+        };
+        for (i, (&a, &b)) in lhs.iter().zip(rhs).enumerate() {
+            let (a, b) = (f64::cast_from(a), f64::cast_from(b));
+            // Equal infinities have no error; everything else involving NaN or infinity does.
+            let error = if a == b { 0.0 } else { (a - b).abs() };
+            if error > diff.max_abs_error || (error.is_nan() && !diff.max_abs_error.is_nan()) {
+                diff.max_abs_error = error;
+                diff.max_abs_error_index = Some(i);
+            }
+            if a != b && !(error <= atol + rtol * b.abs()) {
+                diff.mismatches += 1;
+                diff.first_mismatch.get_or_insert(i);
+            }
+        }
+        diff
+    }
+
+    /// Returns the number of compared elements.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the largest absolute difference between corresponding elements. NaN if any pair
+    /// involves NaN.
+    pub fn max_abs_error(&self) -> f64 {
+        self.max_abs_error
+    }
+
+    /// Returns the flat index of the first element with the largest absolute difference, or
+    /// `None` if all elements are equal.
+    pub fn max_abs_error_index(&self) -> Option<usize> {
+        self.max_abs_error_index
+    }
+
+    /// Returns the flat, row-major index of the first element outside the tolerance.
+    pub fn first_mismatch(&self) -> Option<usize> {
+        self.first_mismatch
+    }
+
+    /// Returns the number of elements outside the tolerance.
+    pub fn mismatches(&self) -> usize {
+        self.mismatches
+    }
+
+    /// Returns whether every element is within the tolerance.
+    pub fn is_close(&self) -> bool {
+        self.mismatches == 0
+    }
+}
+
+impl Display for TensorDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} of {} elements mismatched", self.mismatches, self.len)?;
+        if let Some(index) = self.first_mismatch {
+            write!(f, " (first at index {})", index)?;
+        }
+        write!(f, ", max abs error {}", self.max_abs_error)?;
+        if let Some(index) = self.max_abs_error_index {
+            write!(f, " at index {}", index)?;
+        }
+        Ok(())
+    }
+}
+
+fn diff<T>(
+    lhs: &[T],
+    lhs_shape: &[usize],
+    rhs: &[T],
+    rhs_shape: &[usize],
+    rtol: f64,
+    atol: f64,
+) -> Result<TensorDiff>
+where
+    T: Copy,
+    f64: CastFrom<T>,
+{
+    if lhs_shape != rhs_shape {
+        return Err(format!("Cannot compare shapes {:?} and {:?}", lhs_shape, rhs_shape).into());
+    }
+    Ok(TensorDiff::compare(lhs, rhs, rtol, atol))
+}
+
+impl<T> Tensor<'_, T>
+where
+    T: Num + Debug,
+    f64: CastFrom<T>,
+{
+    /// Compares the tensor element-wise with `other`.
+    ///
+    /// # Arguments
+    /// * `other` - The expected tensor. Tolerances are relative to its elements.
+    /// * `rtol` - Relative tolerance.
+    /// * `atol` - Absolute tolerance.
+    ///
+    /// # Returns
+    /// A report of the differences, or an error if the shapes differ.
+    pub fn diff(&self, other: &Tensor<'_, T>, rtol: f64, atol: f64) -> Result<TensorDiff> {
+        diff(self.data(), self.shape(), other.data(), other.shape(), rtol, atol)
+    }
+
+    /// Returns whether both tensors have the same shape and every pair of elements satisfies
+    /// `|a - b| <= atol + rtol * |b|`.
+    pub fn allclose(&self, other: &Tensor<'_, T>, rtol: f64, atol: f64) -> bool {
+        self.diff(other, rtol, atol).is_ok_and(|diff| diff.is_close())
+    }
+}
+
+impl<T> TensorValue<T>
+where
+    T: Copy,
+    f64: CastFrom<T>,
+{
+    /// Compares the value element-wise with `other`. See [`Tensor::diff`].
+    pub fn diff(&self, other: &TensorValue<T>, rtol: f64, atol: f64) -> Result<TensorDiff> {
+        diff(self.data(), self.shape(), other.data(), other.shape(), rtol, atol)
+    }
+
+    /// Returns whether both values have the same shape and all elements are close.
+    /// See [`Tensor::allclose`].
+    pub fn allclose(&self, other: &TensorValue<T>, rtol: f64, atol: f64) -> bool {
+        self.diff(other, rtol, atol).is_ok_and(|diff| diff.is_close())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allclose() {
+        let a = Tensor::new("a", &[1.0f32, 2.0, 3.0], vec![3]);
+        let b = Tensor::new("b", &[1.0f32, 2.001, 3.0], vec![3]);
+        assert!(a.allclose(&b, 1e-3, 0.0));
+        assert!(!a.allclose(&b, 1e-5, 1e-8));
+        assert!(!a.allclose(&Tensor::new("c", &[1.0f32, 2.0, 3.0], vec![3, 1]), 0.0, 0.0));
+
+        let nan = Tensor::new("nan", &[f64::NAN], vec![1]);
+        assert!(!nan.allclose(&nan, 1.0, 1.0));
+        let diff = Tensor::new("x", &[f64::NAN, 5.0], vec![2])
+            .diff(&Tensor::new("y", &[1.0, 1.0], vec![2]), 0.0, 0.0)
+            .unwrap();
+        assert!(diff.max_abs_error().is_nan());
+        assert_eq!(diff.max_abs_error_index(), Some(0));
+        let inf = Tensor::new("inf", &[f64::INFINITY], vec![1]);
+        assert!(inf.allclose(&inf, 0.0, 0.0));
+
+        let ints = TensorValue::new(vec![1i32, 5], vec![2]).unwrap();
+        assert!(ints.allclose(&TensorValue::new(vec![1, 6], vec![2]).unwrap(), 0.0, 1.0));
+    }
+
+    #[test]
+    fn test_diff_report() {
+        let a = Tensor::new("a", &[1.0f64, 2.0, 3.0, 4.0], vec![2, 2]);
+        let b = Tensor::new("b", &[1.0f64, 2.5, 3.0, 3.0], vec![2, 2]);
+        let diff = a.diff(&b, 0.0, 0.1).unwrap();
+        assert_eq!(diff.len(), 4);
+        assert_eq!(diff.mismatches(), 2);
+        assert_eq!(diff.first_mismatch(), Some(1));
+        assert_eq!(diff.max_abs_error(), 1.0);
+        assert_eq!(diff.max_abs_error_index(), Some(3));
+        assert!(!diff.is_close());
+        assert_eq!(
+            diff.to_string(),
+            "2 of 4 elements mismatched (first at index 1), max abs error 1 at index 3"
+        );
+        assert!(a.diff(&Tensor::new("c", &[1.0f64], vec![1]), 0.0, 0.0).is_err());
+    }
+}
@@ -0,0 +1,128 @@
+use std::fmt::Debug;
+
+use crate::{Num, Result, Tensor};
+
+impl<T> Tensor<'_, T>
+where
+    T: Num + Debug,
+{
+    /// Joins tensors along an existing axis, e.g. to merge sharded weights into one matrix.
+    ///
+    /// # Arguments
+    /// * `name` - The name of the new tensor.
+    /// * `tensors` - The tensors to join, in order. They must have the same rank, and the same
+    ///   size along every axis except `axis`.
+    /// * `axis` - The axis to join along.
+    ///
+    /// # Returns
+    /// A new owned tensor, or an error if there are no tensors or their shapes are incompatible.
+    pub fn concat(
+        name: &str,
+        tensors: &[Tensor<'_, T>],
+        axis: usize,
+    ) -> Result<Tensor<'static, T>> {
+        let Some(first) = tensors.first() else {
+            return Err("Cannot concatenate an empty list of tensors".into());
+        };
+        let rank = first.shape().len();
+        if axis >= rank {
+            return Err(format!("Axis {} is out of bounds for rank {}", axis, rank).into());
+        }
+        for tensor in tensors {
+            let shape = tensor.shape();
+            let compatible =
+                shape.len() == rank && (0..rank).all(|i| i == axis || shape[i] == first.shape()[i]);
+            if !compatible {
+                return Err(format!(
+                    "Cannot concatenate '{}' of shape {:?} with '{}' of shape {:?} along axis {}",
+                    tensor.name(),
+                    shape,
+                    first.name(),
+                    first.shape(),
+                    axis
+                )
+                .into());
+            }
+        }
+
+        let outer = first.shape()[..axis].iter().product::<usize>();
+        let inner = first.shape()[axis + 1..].iter().product::<usize>();
+        let mut shape = first.shape().to_vec();
+        shape[axis] = tensors.iter().map(|tensor| tensor.shape()[axis]).sum();
+        let mut data = Vec::with_capacity(shape.iter().product());
+        for i in 0..outer {
+            for tensor in tensors {
+                let chunk = tensor.shape()[axis] * inner;
+                data.extend_from_slice(&tensor.data()[i * chunk..(i + 1) * chunk]);
+            }
+        }
+        Ok(Tensor::from_vec(name, data, shape))
+    }
+
+    /// Joins tensors of the same shape along a new leading axis.
+    ///
+    /// # Arguments
+    /// * `name` - The name of the new tensor.
+    /// * `tensors` - The tensors to stack, in order.
+    ///
+    /// # Returns
+    /// A new owned tensor of shape `[tensors.len(), ...shape]`, or an error if there are no
+    /// tensors or their shapes differ.
+    pub fn stack(name: &str, tensors: &[Tensor<'_, T>]) -> Result<Tensor<'static, T>> {
+        let Some(first) = tensors.first() else {
+            return Err("Cannot stack an empty list of tensors".into());
+        };
+        if let Some(tensor) = tensors.iter().find(|tensor| tensor.shape() != first.shape()) {
+            return Err(format!(
+                "Cannot stack '{}' of shape {:?} with '{}' of shape {:?}",
+                tensor.name(),
+                tensor.shape(),
+                first.name(),
+                first.shape()
+            )
+            .into());
+        }
+        let mut shape = vec![tensors.len()];
+        shape.extend_from_slice(first.shape());
+        let data = tensors.iter().flat_map(|tensor| tensor.data().iter().copied()).collect();
+        Ok(Tensor::from_vec(name, data, shape))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_concat() {
+        let a = Tensor::new("a", &[1, 2, 3, 4], vec![2, 2]);
+        let b = Tensor::new("b", &[5, 6], vec![2, 1]);
+        let c = Tensor::new("c", &[7, 8], vec![1, 2]);
+
+        let columns = Tensor::concat("ab", &[a.clone(), b.clone()], 1).unwrap();
+        assert_eq!(columns.name(), "ab");
+        assert_eq!(columns.shape(), &[2, 3]);
+        assert_eq!(columns.data(), &[1, 2, 5, 3, 4, 6]);
+
+        let rows = Tensor::concat("ac", &[a.clone(), c], 0).unwrap();
+        assert_eq!(rows.shape(), &[3, 2]);
+        assert_eq!(rows.data(), &[1, 2, 3, 4, 7, 8]);
+
+        assert!(Tensor::concat("ab", &[a.clone(), b], 0).is_err());
+        assert!(Tensor::concat("a", &[a], 2).is_err());
+        assert!(Tensor::<i32>::concat("none", &[], 0).is_err());
+    }
+
+    #[test]
+    fn test_stack() {
+        let a = Tensor::new("a", &[1.0f32, 2.0], vec![2]);
+        let b = Tensor::new("b", &[3.0f32, 4.0], vec![2]);
+        let stacked = Tensor::stack("ab", &[a.clone(), b]).unwrap();
+        assert_eq!(stacked.shape(), &[2, 2]);
+        assert_eq!(stacked.data(), &[1.0, 2.0, 3.0, 4.0]);
+
+        let c = Tensor::new("c", &[5.0f32], vec![1]);
+        assert!(Tensor::stack("ac", &[a, c]).is_err());
+        assert!(Tensor::<f32>::stack("none", &[]).is_err());
+    }
+}
@@ -0,0 +1,157 @@
+use std::fmt::{self, Debug, Display};
+
+use crate::{num_trait::CastFrom, Num, Tensor, TensorValue, TensorView};
+
+/// Number of elements shown at each end of an axis longer than `2 * EDGE_ITEMS`, as in numpy.
+const EDGE_ITEMS: usize = 3;
+
+fn fmt_element<T: Debug>(f: &mut fmt::Formatter<'_>, value: &T) -> fmt::Result {
+    match f.precision() {
+        Some(precision) => write!(f, "{:.*?}", precision, value),
+        None => write!(f, "{:?}", value),
+    }
+}
+
+/// Writes the elements as nested lists, eliding the middle of long axes with `...`.
+fn fmt_elements<T: Debug>(
+    f: &mut fmt::Formatter<'_>,
+    view: &TensorView<'_, T>,
+    index: &mut Vec<usize>,
+) -> fmt::Result {
+    let axis = index.len();
+    if axis == view.shape().len() {
+        return match view.get(index) {
+            Some(value) => fmt_element(f, value),
+            None => Ok(()),
+        };
+    }
+    let len = view.shape()[axis];
+    f.write_str("[")?;
+    for i in 0..len {
+        if len > 2 * EDGE_ITEMS && i == EDGE_ITEMS {
+            f.write_str("..., ")?;
+        }
+        if len > 2 * EDGE_ITEMS && (EDGE_ITEMS..len - EDGE_ITEMS).contains(&i) {
+            continue;
+        }
+        index.push(i);
+        fmt_elements(f, view, index)?;
+        index.pop();
+        if i + 1 < len {
+            f.write_str(", ")?;
+        }
+    }
+    f.write_str("]")
+}
+
+/// Writes the minimum, maximum and mean of the non-NaN elements, and the number of NaNs if any.
+fn fmt_stats<T>(f: &mut fmt::Formatter<'_>, data: &[T]) -> fmt::Result
+where
+    T: Copy,
+    f64: CastFrom<T>,
+{
+    let mut min = f64::INFINITY;
+    let mut max = f64::NEG_INFINITY;
+    let mut sum = 0.0;
+    let mut nans = 0;
+    for &value in data {
+        let value = f64::cast_from(value);
+        if value.is_nan() {
+            nans += 1;
+            continue;
+        }
+        min = min.min(value);
+        max = max.max(value);
+        sum += value;
+    }
+    let count = data.len() - nans;
+    let mut stats = Vec::new();
+    if count > 0 {
+        stats.push(format!("min {}, max {}, mean {}", min, max, sum / count as f64));
+    }
+    if nans > 0 {
+        stats.push(format!("{} NaN", nans));
+    }
+    write!(f, "({})", stats.join(", "))
+}
+
+fn fmt_summary<T>(f: &mut fmt::Formatter<'_>, view: &TensorView<'_, T>, data: &[T]) -> fmt::Result
+where
+    T: Num,
+    f64: CastFrom<T>,
+{
+    write!(f, "{:?} {:?} ", T::data_type(), view.shape())?;
+    fmt_elements(f, view, &mut Vec::new())?;
+    if !data.is_empty() {
+        f.write_str(" ")?;
+        fmt_stats(f, data)?;
+    }
+    Ok(())
+}
+
+/// Summarizes the tensor on one line: name, data type, shape, elements and basic statistics.
+/// Axes longer than six elements only show their first and last three, so large tensors stay
+/// readable in logs. A precision, as in `{:.3}`, applies to the elements.
+impl<T> Display for Tensor<'_, T>
+where
+    T: Num,
+    f64: CastFrom<T>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}: ", self.name())?;
+        fmt_summary(f, &self.view(), self.data())
+    }
+}
+
+/// Summarizes the value like [`Tensor`]'s `Display`, without a name.
+impl<T> Display for TensorValue<T>
+where
+    T: Num,
+    f64: CastFrom<T>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let view = TensorView::new(self.data(), self.shape().to_vec()).map_err(|_| fmt::Error)?;
+        fmt_summary(f, &view, self.data())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let data = [1.0f32, 2.0, 3.0, 4.0, 5.0, 6.0];
+        let tensor = Tensor::new("w", &data, vec![2, 3]);
+        assert_eq!(
+            tensor.to_string(),
+            "\"w\": Float32 [2, 3] [[1.0, 2.0, 3.0], [4.0, 5.0, 6.0]] (min 1, max 6, mean 3.5)"
+        );
+
+        let scalar = TensorValue::new(vec![f64::NAN], vec![]).unwrap();
+        assert_eq!(scalar.to_string(), "Float64 [] NaN (1 NaN)");
+
+        let empty = Tensor::new("empty", &[] as &[u8], vec![0, 4]);
+        assert_eq!(empty.to_string(), "\"empty\": UInt8 [0, 4] []");
+    }
+
+    #[test]
+    fn test_display_truncates_long_axes() {
+        let data = (0..1_000_000).collect::<Vec<i32>>();
+        let tensor = Tensor::new("big", &data, vec![1000, 1000]);
+        let summary = tensor.to_string();
+        assert!(summary.starts_with(
+            "\"big\": Int32 [1000, 1000] [[0, 1, 2, ..., 997, 998, 999], [1000, 1001, 1002, ..., 1997, 1998, 1999], [2000, "
+        ));
+        assert!(
+            summary.ends_with("..., 999997, 999998, 999999]] (min 0, max 999999, mean 499999.5)")
+        );
+        assert!(summary.len() < 1000);
+
+        let value = TensorValue::new(vec![0.12345f64, 1.0], vec![2]).unwrap();
+        assert_eq!(
+            format!("{:.2}", value),
+            "Float64 [2] [0.12, 1.00] (min 0.12345, max 1, mean 0.561725)"
+        );
+    }
+}
@@ -0,0 +1,95 @@
+use crate::{read_mode::tensor_problem, Result, TensorBuffers, TensorInfo};
+
+impl TensorBuffers {
+    /// Returns the tensors for which `predicate` holds, judged from the metadata alone, e.g. to
+    /// find every f32 tensor over 100 MB that should have been quantized. Tensors the reader
+    /// can't describe, such as those of unknown data types in lenient mode, are left out.
+    ///
+    /// # Arguments
+    /// * `predicate` - Called with each tensor's descriptor in id order.
+    ///
+    /// # Returns
+    /// The descriptors of the matching tensors, in id order.
+    pub async fn filter_tensors<F>(&self, mut predicate: F) -> Result<Vec<TensorInfo>>
+    where
+        F: FnMut(&TensorInfo) -> bool,
+    {
+        let mut matches = Vec::new();
+        for tensor in self.tensor_tables().await?.into_iter().flatten() {
+            if tensor_problem(&tensor).is_some() {
+                continue;
+            }
+            let descriptor = TensorInfo::from_metadata(&tensor)?;
+            if predicate(&descriptor) {
+                matches.push(descriptor);
+            }
+        }
+        Ok(matches)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+    use tokio::fs::File;
+
+    use super::*;
+    use crate::{
+        Compression, DataType, Tensor, TensorBuffersWrite, TensorBuffersWriter, WriterOptions,
+    };
+
+    #[tokio::test]
+    async fn test_filter_tensors() {
+        let dir = TempDir::new().unwrap();
+        let big = vec![0.5f32; 4096];
+        let small = vec![0.5f32; 16];
+        let ints = vec![1i32; 4096];
+        let mut file = File::create(dir.path().join("model.tb")).await.unwrap();
+        let options = WriterOptions::new().with_compression(Compression::Zstd, 3);
+        let mut writer = TensorBuffersWriter::with_options(&mut file, options);
+        writer.add_alias("big", "big_tied");
+        writer
+            .write(
+                vec![
+                    Tensor::new("big", &big, vec![64, 64]),
+                    Tensor::new("small", &small, vec![16]),
+                ],
+                vec![],
+            )
+            .await
+            .unwrap();
+        let mut file = File::create(dir.path().join("ints.tb")).await.unwrap();
+        let mut writer = TensorBuffersWriter::new(&mut file);
+        writer.write(vec![Tensor::new("ints", &ints, vec![4096])], vec![]).await.unwrap();
+
+        let url = format!("file://{}", dir.path().join("model.tb").display());
+        let buffers = TensorBuffers::open(&url).await.unwrap();
+        let large = buffers
+            .filter_tensors(|tensor| {
+                tensor.data_type == DataType::Float32 && tensor.data_size > 1024
+            })
+            .await
+            .unwrap();
+        let mut names = large.iter().map(|tensor| tensor.name.as_str()).collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, ["big", "big_tied"]);
+        let big = large.iter().find(|tensor| tensor.name == "big").unwrap();
+        assert_eq!(big.shape, [64, 64]);
+        assert_eq!(big.elements(), 4096);
+        assert_eq!(big.data_size, 4096 * 4);
+        assert!(big.stored_size < big.data_size);
+        assert_eq!(big.compression, Compression::Zstd);
+        assert_eq!(big.alias_of, None);
+        let tied = large.iter().find(|tensor| tensor.name == "big_tied").unwrap();
+        assert_eq!(tied.alias_of.as_deref(), Some("big"));
+
+        let all = buffers.filter_tensors(|_| true).await.unwrap();
+        assert_eq!(all.len(), 3);
+        assert!(all.windows(2).all(|pair| pair[0].id < pair[1].id));
+
+        let url = format!("file://{}", dir.path().join("ints.tb").display());
+        let buffers = TensorBuffers::open(&url).await.unwrap();
+        let floats = buffers.filter_tensors(|tensor| tensor.data_type == DataType::Float32);
+        assert!(floats.await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,185 @@
+use std::collections::VecDeque;
+
+use fnv::FnvHashMap;
+
+use crate::{Result, TensorOperation, TensorOperationId};
+
+/// An in-memory view of the operation graph stored in a TensorBuffers file.
+/// Operations are nodes and `input_operations` are the edges pointing at their dependencies.
+///
+/// With the `serde` feature, a graph is serialized as its list of operations.
+#[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(from = "Vec<TensorOperation>", into = "Vec<TensorOperation>")
+)]
+pub struct TensorGraph {
+    operations: Vec<TensorOperation>,
+    index: FnvHashMap<TensorOperationId, usize>,
+}
+
+impl TensorGraph {
+    /// Creates a graph from a list of operations.
+    ///
+    /// # Arguments
+    /// * `operations` - The operations of the graph, in any order.
+    pub fn new(operations: Vec<TensorOperation>) -> Self {
+        let index = operations.iter().enumerate().map(|(i, op)| (op.id(), i)).collect();
+        TensorGraph { operations, index }
+    }
+
+    pub fn operations(&self) -> &[TensorOperation] {
+        &self.operations
+    }
+
+    pub fn get_operation(&self, operation_id: TensorOperationId) -> Option<&TensorOperation> {
+        self.index.get(&operation_id).map(|&i| &self.operations[i])
+    }
+
+    pub fn into_operations(self) -> Vec<TensorOperation> {
+        self.operations
+    }
+
+    fn require_operation(&self, operation_id: TensorOperationId) -> Result<&TensorOperation> {
+        self.get_operation(operation_id)
+            .ok_or_else(|| format!("Operation {} not found in graph", operation_id).into())
+    }
+
+    /// Returns the operations that the given operation directly takes as inputs.
+    pub fn dependencies_of(
+        &self,
+        operation_id: TensorOperationId,
+    ) -> Result<Vec<&TensorOperation>> {
+        let operation = self.require_operation(operation_id)?;
+        operation
+            .input_operations()
+            .iter()
+            .map(|&input_id| {
+                self.get_operation(input_id).ok_or_else(|| {
+                    format!("Operation {} depends on unknown operation {}", operation_id, input_id)
+                        .into()
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the operations that directly take the given operation as an input.
+    pub fn dependents_of(&self, operation_id: TensorOperationId) -> Result<Vec<&TensorOperation>> {
+        self.require_operation(operation_id)?;
+        Ok(self
+            .operations
+            .iter()
+            .filter(|op| op.input_operations().contains(&operation_id))
+            .collect())
+    }
+
+    /// Returns all operations ordered so that every operation comes after its dependencies.
+    /// Independent operations keep the order in which they appear in the graph.
+    ///
+    /// # Returns
+    /// Returns an error if an operation depends on an unknown operation or the graph has a cycle.
+    pub fn operations_in_topological_order(&self) -> Result<Vec<&TensorOperation>> {
+        // Kahn's algorithm: count unresolved inputs per operation and release operations
+        // once all of their inputs have been emitted.
+        let mut pending = vec![0usize; self.operations.len()];
+        let mut dependents = vec![Vec::new(); self.operations.len()];
+        for (i, op) in self.operations.iter().enumerate() {
+            for &input_id in op.input_operations() {
+                let input = self.index.get(&input_id).ok_or_else(|| {
+                    format!("Operation {} depends on unknown operation {}", op.id(), input_id)
+                })?;
+                pending[i] += 1;
+                dependents[*input].push(i);
+            }
+        }
+
+        let mut ready: VecDeque<usize> = (0..pending.len()).filter(|&i| pending[i] == 0).collect();
+        let mut ordered = Vec::with_capacity(self.operations.len());
+        while let Some(i) = ready.pop_front() {
+            ordered.push(&self.operations[i]);
+            for &dependent in &dependents[i] {
+                pending[dependent] -= 1;
+                if pending[dependent] == 0 {
+                    ready.push_back(dependent);
+                }
+            }
+        }
+
+        if ordered.len() != self.operations.len() {
+            return Err("Operation graph contains a cycle".into());
+        }
+        Ok(ordered)
+    }
+}
+
+impl From<Vec<TensorOperation>> for TensorGraph {
+    fn from(operations: Vec<TensorOperation>) -> Self {
+        TensorGraph::new(operations)
+    }
+}
+
+impl From<TensorGraph> for Vec<TensorOperation> {
+    fn from(graph: TensorGraph) -> Self {
+        graph.into_operations()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Operation;
+
+    fn diamond() -> TensorGraph {
+        // 1 -> {2, 3} -> 4, listed out of order on purpose.
+        TensorGraph::new(vec![
+            TensorOperation::new(4, Operation::Add, vec![2, 3], 40),
+            TensorOperation::new(2, Operation::Sqr, vec![1], 20),
+            TensorOperation::new(3, Operation::Exp, vec![1], 30),
+            TensorOperation::new(1, Operation::None, vec![], 10),
+        ])
+    }
+
+    fn ids(operations: Vec<&TensorOperation>) -> Vec<TensorOperationId> {
+        operations.iter().map(|op| op.id()).collect()
+    }
+
+    #[test]
+    fn test_topological_order() {
+        let graph = diamond();
+        let order = ids(graph.operations_in_topological_order().unwrap());
+        assert_eq!(order, vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_dependencies_and_dependents() {
+        let graph = diamond();
+        assert_eq!(ids(graph.dependencies_of(4).unwrap()), vec![2, 3]);
+        assert!(graph.dependencies_of(1).unwrap().is_empty());
+        assert_eq!(ids(graph.dependents_of(1).unwrap()), vec![2, 3]);
+        assert!(graph.dependents_of(4).unwrap().is_empty());
+        assert!(graph.dependents_of(99).is_err());
+    }
+
+    #[test]
+    fn test_invalid_graphs() {
+        let cyclic = TensorGraph::new(vec![
+            TensorOperation::new(1, Operation::Add, vec![2], 10),
+            TensorOperation::new(2, Operation::Add, vec![1], 20),
+        ]);
+        assert!(cyclic.operations_in_topological_order().is_err());
+
+        let dangling = TensorGraph::new(vec![TensorOperation::new(1, Operation::Add, vec![7], 10)]);
+        assert!(dangling.operations_in_topological_order().is_err());
+        assert!(dangling.dependencies_of(1).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let json = serde_json::to_string(&diamond()).unwrap();
+        let graph: TensorGraph = serde_json::from_str(&json).unwrap();
+        assert_eq!(graph.operations().len(), 4);
+        assert_eq!(graph.dependencies_of(4).unwrap().len(), 2);
+    }
+}
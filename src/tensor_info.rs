@@ -0,0 +1,90 @@
+use crate::{
+    codec,
+    extension_type::data_type_of,
+    generated::tensor_buffers::{Compression, TensorMetadata},
+    tensor::stored_shape,
+    DataType, Result, TensorId,
+};
+
+/// What the metadata says about a tensor, without its data. Owned, so it doesn't depend on the
+/// generated FlatBuffers types or the lifetime of the file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TensorInfo {
+    /// The tensor's id, the hash of its name.
+    pub id: TensorId,
+    /// The tensor's name.
+    pub name: String,
+    /// The type of the tensor's elements.
+    pub data_type: DataType,
+    /// The tensor's shape.
+    pub shape: Vec<usize>,
+    /// The size of the tensor's data in bytes, before compression.
+    pub data_size: u64,
+    /// How many bytes of the file hold the tensor's data.
+    pub stored_size: u64,
+    /// How the tensor's data is compressed.
+    pub compression: Compression,
+    /// The id of the custom codec the data is encoded with, for `Compression::Custom`.
+    pub codec: Option<String>,
+    /// The tensor whose data an alias shares, or `None` if the tensor is not an alias.
+    pub alias_of: Option<String>,
+    /// Where the tensor's data is stored if it is in another file, or `None` if it is in this one.
+    pub location: Option<String>,
+}
+
+impl TensorInfo {
+    /// Returns the number of elements in the tensor.
+    pub fn elements(&self) -> usize {
+        self.shape.iter().product()
+    }
+
+    pub(crate) fn from_metadata(metadata: &TensorMetadata) -> Result<Self> {
+        Ok(Self {
+            id: metadata.id(),
+            name: metadata.name().to_string(),
+            data_type: data_type_of(&metadata)?,
+            shape: stored_shape(metadata)?,
+            data_size: metadata.data_size() as u64,
+            stored_size: codec::stored_size(metadata) as u64,
+            compression: metadata.compression(),
+            codec: metadata.codec().map(str::to_string),
+            alias_of: metadata.alias_of().map(str::to_string),
+            location: metadata.external().map(|external| external.location().to_string()),
+        })
+    }
+}
+
+#[cfg(all(test, feature = "async"))]
+mod tests {
+    use tempfile::NamedTempFile;
+    use tokio::fs::File;
+
+    use super::*;
+    use crate::{
+        utils::hash_key, Tensor, TensorBuffers, TensorBuffersWrite, TensorBuffersWriter,
+        WriterOptions,
+    };
+
+    #[tokio::test]
+    async fn test_get_tensor_metadata() {
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        let options = WriterOptions::new().with_compression(Compression::Zstd, 3);
+        let mut writer = TensorBuffersWriter::with_options(&mut file, options);
+        writer.add_alias("w", "w_tied");
+        let tensor = Tensor::new("w", &[0i32; 256], vec![16, 16]);
+        writer.write(vec![tensor], vec![]).await.unwrap();
+
+        let url = format!("file://{}", tmp.path().display());
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        let info = tensor_buffers.get_tensor_metadata(hash_key("w")).await.unwrap();
+        assert_eq!((info.id, info.name.as_str()), (hash_key("w"), "w"));
+        assert_eq!((info.data_type, info.shape.as_slice()), (DataType::Int32, &[16, 16][..]));
+        assert_eq!((info.data_size, info.compression), (1024, Compression::Zstd));
+        assert!(info.stored_size < info.data_size);
+        assert_eq!((info.codec, info.alias_of, info.location), (None, None, None));
+        let tied = tensor_buffers.get_tensor_metadata(hash_key("w_tied")).await.unwrap();
+        assert_eq!(tied.alias_of.as_deref(), Some("w"));
+        assert!(tensor_buffers.get_tensor_metadata(hash_key("missing")).await.is_err());
+    }
+}
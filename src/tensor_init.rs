@@ -0,0 +1,110 @@
+use std::fmt::Debug;
+
+use crate::{Num, Tensor};
+
+impl<T> Tensor<'_, T>
+where
+    T: Num + Debug,
+{
+    /// Creates an owned scalar tensor, with an empty shape and a single element.
+    pub fn scalar(name: &str, value: T) -> Tensor<'static, T> {
+        Tensor::from_vec(name, vec![value], vec![])
+    }
+
+    /// Creates an owned tensor with every element set to `value`.
+    pub fn full(name: &str, shape: Vec<usize>, value: T) -> Tensor<'static, T> {
+        let elements = shape.iter().product();
+        Tensor::from_vec(name, vec![value; elements], shape)
+    }
+
+    /// Creates an owned tensor of zeros.
+    pub fn zeros(name: &str, shape: Vec<usize>) -> Tensor<'static, T> {
+        Tensor::full(name, shape, T::zero())
+    }
+
+    /// Creates an owned tensor of ones.
+    pub fn ones(name: &str, shape: Vec<usize>) -> Tensor<'static, T> {
+        Tensor::full(name, shape, T::one())
+    }
+
+    /// Creates an owned tensor by calling `f` with the multi-dimensional index of every element,
+    /// in row-major order.
+    ///
+    /// # Arguments
+    /// * `name` - The name of the tensor.
+    /// * `shape` - The shape of the tensor.
+    /// * `f` - Returns the element at the given index.
+    pub fn from_fn<F>(name: &str, shape: Vec<usize>, mut f: F) -> Tensor<'static, T>
+    where
+        F: FnMut(&[usize]) -> T,
+    {
+        let elements = shape.iter().product();
+        let mut data = Vec::with_capacity(elements);
+        let mut index = vec![0; shape.len()];
+        for _ in 0..elements {
+            data.push(f(&index));
+            for axis in (0..shape.len()).rev() {
+                index[axis] += 1;
+                if index[axis] < shape[axis] {
+                    break;
+                }
+                index[axis] = 0;
+            }
+        }
+        Tensor::from_vec(name, data, shape)
+    }
+
+    /// Creates an owned tensor of random elements drawn from rand's `StandardUniform`
+    /// distribution: `[0, 1)` for floats and the whole range for integers.
+    #[cfg(feature = "rand")]
+    pub fn random<R>(name: &str, shape: Vec<usize>, rng: &mut R) -> Tensor<'static, T>
+    where
+        R: rand::Rng + ?Sized,
+        rand::distr::StandardUniform: rand::distr::Distribution<T>,
+    {
+        let elements = shape.iter().product();
+        let data = (0..elements).map(|_| rng.random()).collect();
+        Tensor::from_vec(name, data, shape)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DataType;
+
+    #[test]
+    fn test_constant_constructors() {
+        let zeros = Tensor::<f32>::zeros("z", vec![2, 3]);
+        assert_eq!(zeros.name(), "z");
+        assert_eq!(zeros.shape(), &[2, 3]);
+        assert_eq!(zeros.data(), &[0.0; 6]);
+        assert_eq!(Tensor::<u8>::ones("o", vec![2]).data(), &[1, 1]);
+        let full = Tensor::full("f", vec![], 7i64);
+        assert_eq!(full.data(), &[7]);
+        assert_eq!(full.data_type(), DataType::Int64);
+        assert!(Tensor::<f64>::zeros("e", vec![0, 4]).data().is_empty());
+    }
+
+    #[test]
+    fn test_from_fn() {
+        let tensor = Tensor::from_fn("t", vec![2, 3], |index| (index[0] * 10 + index[1]) as i32);
+        assert_eq!(tensor.data(), &[0, 1, 2, 10, 11, 12]);
+        let eye = |index: &[usize]| if index[0] == index[1] { 1.0 } else { 0.0 };
+        let identity = Tensor::from_fn("eye", vec![2, 2], eye);
+        assert_eq!(identity.data(), &[1.0f32, 0.0, 0.0, 1.0]);
+    }
+
+    #[cfg(feature = "rand")]
+    #[test]
+    fn test_random() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let mut rng = StdRng::seed_from_u64(7);
+        let tensor = Tensor::<f32>::random("r", vec![4, 4], &mut rng);
+        assert_eq!(tensor.data().len(), 16);
+        assert!(tensor.data().iter().all(|value| (0.0..1.0).contains(value)));
+        let again = Tensor::<f32>::random("r", vec![4, 4], &mut StdRng::seed_from_u64(7));
+        assert_eq!(again.data(), tensor.data());
+    }
+}
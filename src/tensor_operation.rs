@@ -1,16 +1,54 @@
+#[cfg(any(feature = "async", feature = "mmap"))]
 use flatbuffers::{FlatBufferBuilder, WIPOffset};
 
+#[cfg(any(feature = "async", feature = "mmap"))]
+use crate::generated::tensor_buffers::OperationMetadataArgs;
 use crate::{
-    generated::tensor_buffers::{Operation, OperationMetadata, OperationMetadataArgs},
-    TensorId, TensorOperationId,
+    generated::tensor_buffers::{Operation, OperationMetadata},
+    OperationProfile, TensorId, TensorOperationId,
 };
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TensorOperation {
     id: TensorOperationId,
+    #[cfg_attr(feature = "serde", serde(with = "operation_name"))]
     operation: Operation,
     input_operations: Vec<TensorOperationId>,
     output: TensorId,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+    name: Option<String>,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+    profile: Option<OperationProfile>,
+}
+
+/// Serializes operations by their schema name, e.g. `"MatMul"`, rather than their discriminant.
+#[cfg(feature = "serde")]
+mod operation_name {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    use crate::Operation;
+
+    pub(super) fn serialize<S: Serializer>(
+        operation: &Operation,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match operation.variant_name() {
+            Some(name) => serializer.serialize_str(name),
+            None => Err(serde::ser::Error::custom(format!("Unknown operation {:?}", operation))),
+        }
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Operation, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Operation::ENUM_VALUES
+            .iter()
+            .find(|operation| operation.variant_name() == Some(name.as_str()))
+            .copied()
+            .ok_or_else(|| D::Error::custom(format!("Unknown operation {}", name)))
+    }
 }
 
 impl TensorOperation {
@@ -20,7 +58,7 @@ impl TensorOperation {
         input_operations: Vec<TensorOperationId>,
         output: TensorId,
     ) -> Self {
-        TensorOperation { id, operation, input_operations, output }
+        TensorOperation { id, operation, input_operations, output, name: None, profile: None }
     }
 
     pub fn id(&self) -> TensorOperationId {
@@ -38,10 +76,49 @@ impl TensorOperation {
     pub fn output(&self) -> &TensorId {
         &self.output
     }
+
+    /// Sets a human-readable name, stored alongside the operation to make graphs easier to debug.
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Attaches the execution stats a profiling run measured, stored alongside the operation.
+    pub fn with_profile(mut self, profile: OperationProfile) -> Self {
+        self.profile = Some(profile);
+        self
+    }
+
+    pub fn profile(&self) -> Option<&OperationProfile> {
+        self.profile.as_ref()
+    }
+
+    /// Returns an operation with the same id and name that computes `output` differently. Its
+    /// profile, measured for the old computation, is dropped.
+    #[cfg(feature = "async")]
+    pub(crate) fn rewritten(
+        &self,
+        operation: Operation,
+        input_operations: Vec<TensorOperationId>,
+        output: TensorId,
+    ) -> TensorOperation {
+        TensorOperation {
+            id: self.id,
+            operation,
+            input_operations,
+            output,
+            name: self.name.clone(),
+            profile: None,
+        }
+    }
 }
 
 impl TensorOperation {
-    pub fn with_metadata(metadata: &OperationMetadata) -> Self {
+    pub(crate) fn with_metadata(metadata: &OperationMetadata) -> Self {
         let id = metadata.id();
         let operation = metadata.operation();
         let input_operations = match metadata.input_operations() {
@@ -49,21 +126,28 @@ impl TensorOperation {
             None => Vec::new(),
         };
         let output = metadata.output();
-        TensorOperation::new(id, operation, input_operations, output)
+        let name = metadata.name().map(|name| name.to_string());
+        let profile = metadata.profile().map(|profile| OperationProfile::from_metadata(&profile));
+        TensorOperation { id, operation, input_operations, output, name, profile }
     }
 
-    pub fn build_table<'a>(
+    #[cfg(any(feature = "async", feature = "mmap"))]
+    pub(crate) fn build_table<'a>(
         builder: &mut FlatBufferBuilder<'a>,
         tensor_operation: TensorOperation,
     ) -> WIPOffset<OperationMetadata<'a>> {
         let operation = *tensor_operation.operation();
         let output = *tensor_operation.output();
         let input_operations = builder.create_vector(&tensor_operation.input_operations);
+        let name = tensor_operation.name().map(|name| builder.create_string(name));
+        let profile = tensor_operation.profile().map(|profile| profile.build_table(builder));
         OperationMetadata::create(builder, &OperationMetadataArgs {
             id: tensor_operation.id(),
             operation: operation,
             input_operations: Some(input_operations),
             output: output,
+            name,
+            profile,
         })
     }
 }
@@ -87,5 +171,29 @@ mod tests {
         assert_eq!(tensor_operation.operation(), &operation);
         assert_eq!(tensor_operation.input_operations(), &input_operations);
         assert_eq!(tensor_operation.output(), &output);
+        assert_eq!(tensor_operation.name(), None);
+        assert_eq!(tensor_operation.with_name("add").name(), Some("add"));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let operation = TensorOperation::new(1, Operation::MatMul, vec![2, 3], 4).with_name("mm");
+        let json = serde_json::to_string(&operation).unwrap();
+        assert_eq!(
+            json,
+            r#"{"id":1,"operation":"MatMul","input_operations":[2,3],"output":4,"name":"mm"}"#
+        );
+        let decoded: TensorOperation = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.operation(), &Operation::MatMul);
+        assert_eq!(decoded.name(), Some("mm"));
+
+        let unnamed = r#"{"id":1,"operation":"Exp","input_operations":[],"output":4}"#;
+        let decoded: TensorOperation = serde_json::from_str(unnamed).unwrap();
+        assert_eq!(decoded.name(), None);
+        assert!(serde_json::from_str::<TensorOperation>(
+            r#"{"id":1,"operation":"Nope","input_operations":[],"output":4}"#
+        )
+        .is_err());
     }
 }
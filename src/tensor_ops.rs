@@ -0,0 +1,145 @@
+//! Eager element-wise arithmetic with the broadcasting rules of the executor's `Add`, `Sub`,
+//! `Mul` and `Div` operations: equal shapes, a single-element operand, or a suffix shape.
+//!
+//! The operators panic when shapes cannot be broadcast; the `checked_*` methods return an error
+//! instead.
+
+use std::{
+    fmt::Debug,
+    ops::{Add, Div, Mul, Sub},
+};
+
+use bytemuck::Pod;
+
+use crate::{
+    kernels::{self, ArithmeticOp},
+    num_trait::Num,
+    Result, Tensor, TensorValue,
+};
+
+fn arithmetic<T: Num + Pod>(
+    op: ArithmeticOp,
+    lhs: &[T],
+    lhs_shape: &[usize],
+    rhs: &[T],
+    rhs_shape: &[usize],
+) -> Result<TensorValue<T>> {
+    let (data, shape) = kernels::arithmetic(op, lhs, lhs_shape, rhs, rhs_shape)?;
+    TensorValue::new(data, shape)
+}
+
+macro_rules! impl_checked {
+    ($($name:ident => $op:expr),*) => {
+        impl<'a, T> Tensor<'a, T>
+        where
+            T: Num + Pod + Debug,
+        {
+            $(
+                pub fn $name(&self, rhs: &Tensor<'_, T>) -> Result<TensorValue<T>> {
+                    arithmetic($op, self.data(), self.shape(), rhs.data(), rhs.shape())
+                }
+            )*
+        }
+
+        impl<T> TensorValue<T>
+        where
+            T: Num + Pod,
+        {
+            $(
+                pub fn $name(&self, rhs: &TensorValue<T>) -> Result<TensorValue<T>> {
+                    arithmetic($op, self.data(), self.shape(), rhs.data(), rhs.shape())
+                }
+            )*
+        }
+    };
+}
+
+impl_checked!(
+    checked_add => ArithmeticOp::Add,
+    checked_sub => ArithmeticOp::Sub,
+    checked_mul => ArithmeticOp::Mul,
+    checked_div => ArithmeticOp::Div
+);
+
+macro_rules! impl_operator {
+    ($trait:ident, $method:ident, $op:expr) => {
+        impl_operator!(@tensors $trait, $method, $op, Tensor<'_, T>, Tensor<'_, T>);
+        impl_operator!(@tensors $trait, $method, $op, Tensor<'_, T>, TensorValue<T>);
+        impl_operator!(@tensors $trait, $method, $op, TensorValue<T>, Tensor<'_, T>);
+        impl_operator!(@tensors $trait, $method, $op, TensorValue<T>, TensorValue<T>);
+        impl_operator!(@scalar $trait, $method, $op, Tensor<'_, T>);
+        impl_operator!(@scalar $trait, $method, $op, TensorValue<T>);
+    };
+    (@tensors $trait:ident, $method:ident, $op:expr, $lhs:ty, $rhs:ty) => {
+        impl<T> $trait<&$rhs> for &$lhs
+        where
+            T: Num + Pod + Debug,
+        {
+            type Output = TensorValue<T>;
+
+            fn $method(self, rhs: &$rhs) -> TensorValue<T> {
+                arithmetic($op, self.data(), self.shape(), rhs.data(), rhs.shape())
+                    .unwrap_or_else(|e| panic!("{}", e))
+            }
+        }
+    };
+    (@scalar $trait:ident, $method:ident, $op:expr, $lhs:ty) => {
+        impl<T> $trait<T> for &$lhs
+        where
+            T: Num + Pod + Debug,
+        {
+            type Output = TensorValue<T>;
+
+            fn $method(self, rhs: T) -> TensorValue<T> {
+                arithmetic($op, self.data(), self.shape(), &[rhs], &[])
+                    .unwrap_or_else(|e| panic!("{}", e))
+            }
+        }
+    };
+}
+
+impl_operator!(Add, add, ArithmeticOp::Add);
+impl_operator!(Sub, sub, ArithmeticOp::Sub);
+impl_operator!(Mul, mul, ArithmeticOp::Mul);
+impl_operator!(Div, div, ArithmeticOp::Div);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tensor_operators() {
+        let a = Tensor::new("a", &[1.0f32, 2.0, 3.0, 4.0], vec![2, 2]);
+        let b = Tensor::new("b", &[10.0f32, 20.0], vec![2]);
+
+        let sum = &a + &b;
+        assert_eq!(sum.shape(), &[2, 2]);
+        assert_eq!(sum.data(), &[11.0, 22.0, 13.0, 24.0]);
+        assert_eq!((&a * 2.0).data(), &[2.0, 4.0, 6.0, 8.0]);
+        assert_eq!((&(&a - &a) + &b).data(), &[10.0, 20.0, 10.0, 20.0]);
+        assert_eq!((&sum / &sum).data(), &[1.0, 1.0, 1.0, 1.0]);
+
+        let ints = Tensor::new("ints", &[6i32, 9], vec![2]);
+        assert_eq!((&ints / 3).data(), &[2, 3]);
+    }
+
+    #[test]
+    fn test_checked_shapes() {
+        let a = Tensor::new("a", &[1.0f64, 2.0, 3.0, 4.0, 5.0, 6.0], vec![2, 3]);
+        let b = Tensor::new("b", &[1.0f64, 2.0], vec![2]);
+        assert!(a.checked_add(&b).is_err());
+        assert!(a
+            .checked_mul(&a)
+            .unwrap()
+            .checked_sub(&TensorValue::new(vec![1.0], vec![1]).unwrap())
+            .is_ok());
+    }
+
+    #[test]
+    #[should_panic(expected = "broadcast")]
+    fn test_operator_panics_on_shape_mismatch() {
+        let a = Tensor::new("a", &[1.0f32, 2.0, 3.0], vec![3]);
+        let b = Tensor::new("b", &[1.0f32, 2.0], vec![2]);
+        let _ = &a + &b;
+    }
+}
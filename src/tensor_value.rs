@@ -0,0 +1,84 @@
+use std::fmt::Debug;
+
+use crate::{
+    num_trait::{CastFrom, Num},
+    Result, Tensor,
+};
+
+/// An owned tensor produced by the executor.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "SerializedTensorValue<T>")
+)]
+pub struct TensorValue<T> {
+    pub(crate) shape: Vec<usize>,
+    pub(crate) data: Vec<T>,
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct SerializedTensorValue<T> {
+    shape: Vec<usize>,
+    data: Vec<T>,
+}
+
+#[cfg(feature = "serde")]
+impl<T> TryFrom<SerializedTensorValue<T>> for TensorValue<T> {
+    type Error = String;
+
+    fn try_from(value: SerializedTensorValue<T>) -> std::result::Result<Self, Self::Error> {
+        TensorValue::new(value.data, value.shape).map_err(|e| e.to_string())
+    }
+}
+
+impl<T> TensorValue<T> {
+    /// Creates a value, checking that the number of elements matches the shape.
+    pub fn new(data: Vec<T>, shape: Vec<usize>) -> Result<Self> {
+        let elements = shape.iter().product::<usize>();
+        if elements != data.len() {
+            return Err(format!(
+                "Shape {:?} expects {} elements, found {}",
+                shape,
+                elements,
+                data.len()
+            )
+            .into());
+        }
+        Ok(TensorValue { shape, data })
+    }
+
+    pub fn shape(&self) -> &[usize] {
+        &self.shape
+    }
+
+    pub fn data(&self) -> &[T] {
+        &self.data
+    }
+
+    pub fn into_data(self) -> Vec<T> {
+        self.data
+    }
+
+    /// Converts every element to `U`. Conversions follow the rules of [`CastFrom`].
+    pub fn astype<U>(&self) -> TensorValue<U>
+    where
+        T: Copy,
+        U: CastFrom<T>,
+    {
+        let data = self.data.iter().map(|&value| U::cast_from(value)).collect();
+        TensorValue { shape: self.shape.clone(), data }
+    }
+}
+
+impl<T> From<Tensor<'_, T>> for TensorValue<T>
+where
+    T: Num + Debug,
+{
+    /// Takes the tensor's data, copying it only if it is borrowed.
+    fn from(tensor: Tensor<'_, T>) -> Self {
+        let shape = tensor.shape().to_vec();
+        TensorValue { shape, data: tensor.into_data() }
+    }
+}
@@ -0,0 +1,205 @@
+use std::{fmt::Debug, ops::Range};
+
+use crate::{num_trait::Num, Result, Tensor};
+
+/// A borrowed, possibly strided window onto tensor data.
+///
+/// Views never copy: `reshape` and `slice` only change the shape, strides and offset used to
+/// index the underlying data, which is stored in row-major order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TensorView<'t, T> {
+    data: &'t [T],
+    offset: usize,
+    shape: Vec<usize>,
+    strides: Vec<usize>,
+}
+
+fn contiguous_strides(shape: &[usize]) -> Vec<usize> {
+    let mut strides = vec![1; shape.len()];
+    for i in (0..shape.len().saturating_sub(1)).rev() {
+        strides[i] = strides[i + 1] * shape[i + 1];
+    }
+    strides
+}
+
+impl<'t, T> TensorView<'t, T> {
+    /// Creates a view of row-major `data` with the given shape.
+    pub fn new(data: &'t [T], shape: Vec<usize>) -> Result<Self> {
+        let elements = shape.iter().product::<usize>();
+        if elements != data.len() {
+            return Err(format!(
+                "Shape {:?} expects {} elements, found {}",
+                shape,
+                elements,
+                data.len()
+            )
+            .into());
+        }
+        let strides = contiguous_strides(&shape);
+        Ok(TensorView { data, offset: 0, shape, strides })
+    }
+
+    pub fn shape(&self) -> &[usize] {
+        &self.shape
+    }
+
+    pub fn strides(&self) -> &[usize] {
+        &self.strides
+    }
+
+    /// Returns the number of elements in the view.
+    pub fn len(&self) -> usize {
+        self.shape.iter().product()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns whether the elements of the view are adjacent and in row-major order.
+    pub fn is_contiguous(&self) -> bool {
+        self.is_empty() || self.strides == contiguous_strides(&self.shape)
+    }
+
+    /// Returns the elements as a slice when the view is contiguous.
+    pub fn as_slice(&self) -> Option<&'t [T]> {
+        if !self.is_contiguous() {
+            return None;
+        }
+        Some(&self.data[self.offset..self.offset + self.len()])
+    }
+
+    /// Returns the element at a multi-dimensional index, or `None` if it is out of bounds.
+    pub fn get(&self, index: &[usize]) -> Option<&'t T> {
+        if index.len() != self.shape.len() || index.iter().zip(&self.shape).any(|(i, n)| i >= n) {
+            return None;
+        }
+        let position = index.iter().zip(&self.strides).map(|(i, stride)| i * stride).sum::<usize>();
+        self.data.get(self.offset + position)
+    }
+
+    /// Iterates over the elements in row-major order.
+    pub fn iter(&self) -> impl Iterator<Item = &'t T> + '_ {
+        (0..self.len()).map(move |mut linear| {
+            let mut position = self.offset;
+            for (&dim, &stride) in self.shape.iter().zip(&self.strides).rev() {
+                position += linear % dim * stride;
+                linear /= dim;
+            }
+            &self.data[position]
+        })
+    }
+
+    /// Gives the same elements a new shape. The view must be contiguous.
+    pub fn reshape(&self, shape: Vec<usize>) -> Result<TensorView<'t, T>> {
+        if shape.iter().product::<usize>() != self.len() {
+            return Err(format!("Cannot reshape {:?} into {:?}", self.shape, shape).into());
+        }
+        if !self.is_contiguous() {
+            return Err(
+                format!("Cannot reshape a non-contiguous view of shape {:?}", self.shape).into()
+            );
+        }
+        let strides = contiguous_strides(&shape);
+        Ok(TensorView { data: self.data, offset: self.offset, shape, strides })
+    }
+
+    /// Restricts the view to `ranges` along its leading dimensions. Dimensions without a range
+    /// are kept whole.
+    pub fn slice(&self, ranges: &[Range<usize>]) -> Result<TensorView<'t, T>> {
+        if ranges.len() > self.shape.len() {
+            return Err(format!(
+                "Cannot slice {} dimensions of shape {:?}",
+                ranges.len(),
+                self.shape
+            )
+            .into());
+        }
+        let mut offset = self.offset;
+        let mut shape = self.shape.clone();
+        for (axis, range) in ranges.iter().enumerate() {
+            if range.start > range.end || range.end > self.shape[axis] {
+                return Err(format!(
+                    "Range {:?} is out of bounds for axis {} of shape {:?}",
+                    range, axis, self.shape
+                )
+                .into());
+            }
+            offset += range.start * self.strides[axis];
+            shape[axis] = range.len();
+        }
+        Ok(TensorView { data: self.data, offset, shape, strides: self.strides.clone() })
+    }
+}
+
+impl<T: Copy> TensorView<'_, T> {
+    /// Copies the elements into a new row-major vector.
+    pub fn to_vec(&self) -> Vec<T> {
+        match self.as_slice() {
+            Some(slice) => slice.to_vec(),
+            None => self.iter().copied().collect(),
+        }
+    }
+}
+
+impl<T> Tensor<'_, T>
+where
+    T: Num + Debug,
+{
+    /// Returns a view of the whole tensor.
+    pub fn view(&self) -> TensorView<'_, T> {
+        let strides = contiguous_strides(self.shape());
+        TensorView { data: self.data(), offset: 0, shape: self.shape().to_vec(), strides }
+    }
+
+    /// Returns a view of the tensor's data with a different shape of the same size.
+    pub fn reshape(&self, shape: Vec<usize>) -> Result<TensorView<'_, T>> {
+        self.view().reshape(shape)
+    }
+
+    /// Returns a view restricted to `ranges` along the leading dimensions.
+    pub fn slice(&self, ranges: &[Range<usize>]) -> Result<TensorView<'_, T>> {
+        self.view().slice(ranges)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reshape() {
+        let data = (0..6).collect::<Vec<i32>>();
+        let tensor = Tensor::new("x", &data, vec![2, 3]);
+        let view = tensor.reshape(vec![3, 2]).unwrap();
+        assert_eq!(view.shape(), &[3, 2]);
+        assert_eq!(view.strides(), &[2, 1]);
+        assert_eq!(view.get(&[2, 1]), Some(&5));
+        assert_eq!(view.as_slice(), Some(&data[..]));
+        assert!(tensor.reshape(vec![4, 2]).is_err());
+    }
+
+    #[test]
+    fn test_slice() {
+        let data = (0..12).collect::<Vec<i32>>();
+        let tensor = Tensor::new("x", &data, vec![3, 4]);
+
+        let rows = tensor.slice(&[1..3, 0..4]).unwrap();
+        assert_eq!(rows.shape(), &[2, 4]);
+        assert_eq!(rows.as_slice(), Some(&data[4..12]));
+
+        let block = tensor.slice(&[0..2, 1..3]).unwrap();
+        assert_eq!(block.shape(), &[2, 2]);
+        assert!(!block.is_contiguous());
+        assert_eq!(block.as_slice(), None);
+        assert_eq!(block.to_vec(), vec![1, 2, 5, 6]);
+        assert_eq!(block.get(&[1, 0]), Some(&5));
+        assert_eq!(block.get(&[2, 0]), None);
+        assert!(block.reshape(vec![4]).is_err());
+
+        let column = block.slice(&[0..2, 1..2]).unwrap();
+        assert_eq!(column.to_vec(), vec![2, 6]);
+        assert!(tensor.slice(&[0..4, 0..4]).is_err());
+        assert!(tensor.slice(&[0..1, 0..1, 0..1]).is_err());
+    }
+}
@@ -0,0 +1,66 @@
+use std::{future::Future, time::Duration};
+
+use crate::Result;
+
+/// Time limits for reading a TensorBuffers file, so hung remote endpoints fail fast instead of
+/// stalling indefinitely. Every limit is off by default.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Timeouts {
+    /// Limit for each HTTP request to a remote file, including reading its body. Retries get a
+    /// fresh limit.
+    pub request: Option<Duration>,
+    /// Limit for opening a file, including fetching the size of a remote file.
+    pub open: Option<Duration>,
+    /// Limit for loading one tensor, including reading the metadata on first use and every
+    /// request and retry this takes.
+    pub load: Option<Duration>,
+}
+
+impl Timeouts {
+    /// Sets the limit for each HTTP request to a remote file.
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.request = Some(timeout);
+        self
+    }
+
+    /// Sets the deadline for opening a file.
+    pub fn with_open_deadline(mut self, deadline: Duration) -> Self {
+        self.open = Some(deadline);
+        self
+    }
+
+    /// Sets the deadline for loading one tensor.
+    pub fn with_load_deadline(mut self, deadline: Duration) -> Self {
+        self.load = Some(deadline);
+        self
+    }
+}
+
+/// Runs `future` to completion, or fails with an error naming `what` once `limit` has passed.
+pub(crate) async fn with_deadline<T, F>(limit: Option<Duration>, what: &str, future: F) -> Result<T>
+where
+    F: Future<Output = Result<T>>,
+{
+    match limit {
+        Some(limit) => tokio::time::timeout(limit, future)
+            .await
+            .map_err(|_| format!("Timed out {} after {:?}", what, limit))?,
+        None => future.await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_with_deadline() {
+        let limit = Some(Duration::from_millis(10));
+        let quick = with_deadline(limit, "waiting", async { Ok(1) }).await;
+        assert_eq!(quick.unwrap(), 1);
+
+        let hung = with_deadline(limit, "waiting", std::future::pending::<Result<()>>()).await;
+        assert_eq!(hung.unwrap_err().to_string(), "Timed out waiting after 10ms");
+        assert!(with_deadline(None, "waiting", async { Ok(()) }).await.is_ok());
+    }
+}
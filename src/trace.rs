@@ -0,0 +1,236 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Debug,
+};
+
+use bytemuck::Pod;
+
+use crate::{
+    executor::compute, num_trait::Float, tensor_buffers_writer::check_name, utils::hash_key,
+    Operation, Result, Tensor, TensorBuffersWrite, TensorOperation, TensorOperationId, TensorValue,
+};
+
+/// A tensor produced or taken in by a [`TraceRecorder`], by the id of the operation that recorded
+/// it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TracedTensor(TensorOperationId);
+
+/// Evaluates tensor operations eagerly, with the executor's semantics, while recording them as
+/// `TensorOperation`s, so the computation that produced results can be written alongside them.
+/// Running the written graph with the [`Executor`](crate::Executor) computes the results again.
+///
+/// ```
+/// use tensorbuffers::{Operation, Tensor, TraceRecorder};
+///
+/// let mut trace = TraceRecorder::new();
+/// let x = trace.input(Tensor::new("x", &[1.0f32, 4.0], vec![2])).unwrap();
+/// let y = trace.apply("y", Operation::Sqrt, &[x]).unwrap();
+/// assert_eq!(trace.value(y).data(), [1.0, 2.0]);
+/// assert_eq!(trace.operations().len(), 2);
+/// ```
+#[derive(Debug)]
+pub struct TraceRecorder<T> {
+    operations: Vec<TensorOperation>,
+    /// The name and value of each traced tensor, by the id of the operation that recorded it.
+    values: HashMap<TensorOperationId, (String, TensorValue<T>)>,
+    names: HashSet<String>,
+}
+
+impl<T> Default for TraceRecorder<T> {
+    fn default() -> Self {
+        TraceRecorder { operations: Vec::new(), values: HashMap::new(), names: HashSet::new() }
+    }
+}
+
+impl<T> TraceRecorder<T>
+where
+    T: Float + Pod + Debug,
+{
+    /// Creates a recorder that has recorded nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records `tensor` as an input of the computation, a source operation whose tensor is
+    /// written along with the results.
+    ///
+    /// # Returns
+    /// An error if the tensor's name is invalid or already traced, or its data doesn't match its
+    /// shape.
+    pub fn input(&mut self, tensor: Tensor<'_, T>) -> Result<TracedTensor> {
+        check_name(tensor.name())?;
+        let (name, shape) = (tensor.name().to_string(), tensor.shape().to_vec());
+        let value = TensorValue::new(TensorValue::from(tensor).into_data(), shape)?;
+        self.record(name, Operation::None, vec![], value)
+    }
+
+    /// Evaluates `operation` on traced tensors and records it.
+    ///
+    /// # Arguments
+    /// * `name` - The name of the result, which is written under it if kept.
+    /// * `operation` - The operation, evaluated as the executor evaluates it.
+    /// * `inputs` - The operation's inputs, in order.
+    ///
+    /// # Returns
+    /// The result, or an error if the name is invalid or already traced, an input wasn't
+    /// recorded by this recorder, or the operation fails on its inputs.
+    pub fn apply(
+        &mut self,
+        name: &str,
+        operation: Operation,
+        inputs: &[TracedTensor],
+    ) -> Result<TracedTensor> {
+        if operation == Operation::None {
+            return Err("Inputs are recorded with TraceRecorder::input".into());
+        }
+        check_name(name)?;
+        let values = inputs.iter().map(|input| self.traced(*input)).collect::<Result<Vec<_>>>()?;
+        let value = compute(operation, &values)
+            .map_err(|e| format!("Operation {} ({:?}) failed: {}", name, operation, e))?;
+        let inputs = inputs.iter().map(|input| input.0).collect();
+        self.record(name.to_string(), operation, inputs, value)
+    }
+
+    /// Returns the value of a traced tensor.
+    ///
+    /// # Panics
+    /// If the tensor wasn't recorded by this recorder.
+    pub fn value(&self, tensor: TracedTensor) -> &TensorValue<T> {
+        self.traced(tensor).expect("traced by another recorder")
+    }
+
+    /// Returns the operations recorded so far, in the order they were recorded.
+    pub fn operations(&self) -> &[TensorOperation] {
+        &self.operations
+    }
+
+    /// Returns the inputs and the `outputs`, named as they were traced, with every recorded
+    /// operation. Results not in `outputs` aren't kept, though their operations are, so readers
+    /// can compute them again.
+    ///
+    /// # Returns
+    /// An error if an output wasn't recorded by this recorder.
+    pub fn finish(
+        mut self,
+        outputs: &[TracedTensor],
+    ) -> Result<(Vec<Tensor<'static, T>>, Vec<TensorOperation>)> {
+        let mut kept = self
+            .operations
+            .iter()
+            .filter(|op| *op.operation() == Operation::None)
+            .map(|op| TracedTensor(op.id()))
+            .collect::<Vec<_>>();
+        for output in outputs {
+            self.traced(*output)?;
+            if !kept.contains(output) {
+                kept.push(*output);
+            }
+        }
+        let tensors = kept
+            .into_iter()
+            .map(|tensor| {
+                let (name, value) = self.values.remove(&tensor.0).unwrap();
+                let shape = value.shape().to_vec();
+                Tensor::from_vec(&name, value.into_data(), shape)
+            })
+            .collect();
+        Ok((tensors, self.operations))
+    }
+
+    /// Writes the inputs and `outputs` with the recorded operations, see `finish`, so the
+    /// results are stored with the computation that produced them.
+    pub async fn write<W>(self, writer: &mut W, outputs: &[TracedTensor]) -> Result<()>
+    where
+        W: TensorBuffersWrite,
+    {
+        let (tensors, operations) = self.finish(outputs)?;
+        writer.write(tensors, operations).await?;
+        Ok(())
+    }
+
+    fn traced(&self, tensor: TracedTensor) -> Result<&TensorValue<T>> {
+        match self.values.get(&tensor.0) {
+            Some((_, value)) => Ok(value),
+            None => Err(format!("No operation {} was recorded", tensor.0).into()),
+        }
+    }
+
+    /// Records the operation computing `value`, with the next id, and names its result `name`.
+    fn record(
+        &mut self,
+        name: String,
+        operation: Operation,
+        inputs: Vec<TensorOperationId>,
+        value: TensorValue<T>,
+    ) -> Result<TracedTensor> {
+        if !self.names.insert(name.clone()) {
+            return Err(format!("A tensor named {} was already traced", name).into());
+        }
+        let id = self.operations.len() as TensorOperationId + 1;
+        let op = TensorOperation::new(id, operation, inputs, hash_key(&name));
+        self.operations.push(op.with_name(&name));
+        self.values.insert(id, (name, value));
+        Ok(TracedTensor(id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::NamedTempFile;
+    use tokio::fs::File;
+
+    use super::*;
+    use crate::{Executor, TensorBuffers, TensorBuffersWriter};
+
+    #[tokio::test]
+    async fn test_trace_recorder() {
+        let mut trace = TraceRecorder::<f32>::new();
+        let x = trace.input(Tensor::new("x", &[1.0, 2.0, 3.0, 4.0], vec![2, 2])).unwrap();
+        let w = trace.input(Tensor::from_vec("w", vec![1.0, 0.0, 0.0, 1.0], vec![2, 2])).unwrap();
+        let b = trace.input(Tensor::new("b", &[0.5, -0.5], vec![2])).unwrap();
+        let xw = trace.apply("xw", Operation::MatMul, &[x, w]).unwrap();
+        let logits = trace.apply("logits", Operation::Add, &[xw, b]).unwrap();
+        let probs = trace.apply("probs", Operation::Softmax, &[logits]).unwrap();
+        assert_eq!(trace.value(logits).data(), [1.5, 1.5, 3.5, 3.5]);
+        assert_eq!(trace.value(probs).data(), [0.5, 0.5, 0.5, 0.5]);
+        let recorded = trace.operations().iter().map(|op| *op.operation()).collect::<Vec<_>>();
+        assert_eq!(recorded, [
+            Operation::None,
+            Operation::None,
+            Operation::None,
+            Operation::MatMul,
+            Operation::Add,
+            Operation::Softmax,
+        ]);
+        assert_eq!(trace.operations()[4].input_operations(), [4, 3]);
+
+        // Failed operations aren't recorded.
+        let error = trace.apply("bad", Operation::MatMul, &[x, b]).unwrap_err();
+        assert!(error.to_string().starts_with("Operation bad (MatMul) failed"));
+        let error = trace.apply("xw", Operation::Exp, &[x]).unwrap_err();
+        assert_eq!(error.to_string(), "A tensor named xw was already traced");
+        assert!(trace.apply("x2", Operation::None, &[x]).is_err());
+        assert!(trace.apply("x2", Operation::Exp, &[TracedTensor(40)]).is_err());
+        assert!(trace.input(Tensor::new("short", &[1.0], vec![2])).is_err());
+        assert_eq!(trace.operations().len(), 6);
+
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        let mut writer = TensorBuffersWriter::new(&mut file);
+        trace.write(&mut writer, &[probs, x]).await.unwrap();
+
+        let url = format!("file://{}", tmp.path().display());
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        // Inputs and the kept outputs are stored, not the other results.
+        assert!(tensor_buffers.get_tensor_data_by_name::<f32>("w").await.is_ok());
+        assert!(tensor_buffers.get_tensor_data_by_name::<f32>("xw").await.is_err());
+        let stored = tensor_buffers.get_tensor_data_by_name::<f32>("probs").await.unwrap();
+        assert_eq!(stored.data(), [0.5, 0.5, 0.5, 0.5]);
+        // The stored graph computes the traced results again.
+        let outputs = Executor::new(&tensor_buffers).run::<f32>(&["logits"]).await.unwrap();
+        assert_eq!(outputs["logits"].data(), [1.5, 1.5, 3.5, 3.5]);
+        let graph = tensor_buffers.graph().await.unwrap();
+        let add = graph.operations().iter().find(|op| op.name() == Some("logits")).unwrap();
+        assert_eq!(add.operation(), &Operation::Add);
+    }
+}
@@ -0,0 +1,522 @@
+//! An interactive terminal browser for a TensorBuffers file, enabled with the `tui` feature.
+//!
+//! Keys:
+//! * `Up`/`Down` (or `k`/`j`), `PageUp`/`PageDown`, `Home`/`End` - Select a tensor.
+//! * `/` - Search tensor names; `Enter` keeps the search, `Esc` clears it.
+//! * `Tab` - Switch the right pane between the tensor's metadata and the operation graph.
+//! * `q` or `Esc` - Quit.
+
+use std::{mem::size_of, pin::pin};
+
+use bytemuck::{pod_read_unaligned, Pod};
+use futures::StreamExt;
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind},
+    layout::{Constraint, Layout, Rect},
+    style::{Style, Stylize},
+    text::Line,
+    widgets::{Block, List, ListState, Paragraph, Wrap},
+    Frame,
+};
+
+use crate::{
+    f16, generated::tensor_buffers::Compression, DataType, Result, TensorBuffers, TensorInfo,
+};
+
+/// Number of elements the value preview shows.
+const PREVIEW_ELEMENTS: usize = 64;
+
+/// Rows a `PageUp` or `PageDown` moves the selection by.
+const PAGE_ROWS: usize = 20;
+
+/// What the right pane shows above the value preview.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Pane {
+    Metadata,
+    Graph,
+}
+
+/// What the event loop does after a key.
+#[derive(Debug, PartialEq, Eq)]
+enum Action {
+    Continue,
+    Quit,
+}
+
+/// An operation of the graph view, as the line describing it and the tensors it touches.
+struct GraphLine {
+    text: String,
+    tensors: Vec<String>,
+}
+
+/// Browses the tensors of a file in the terminal: a searchable tensor list, the selected
+/// tensor's metadata, a preview of its first values and the operation graph. Tensor data is only
+/// read for the preview, and only the first values when the tensor is stored uncompressed, so
+/// large remote files can be browsed over SSH.
+///
+/// ```no_run
+/// # async fn example() -> tensorbuffers::Result<()> {
+/// use tensorbuffers::{Inspector, TensorBuffers};
+///
+/// let tensor_buffers = TensorBuffers::open("https://example.com/model.tb").await?;
+/// Inspector::load(&tensor_buffers).await?.run().await
+/// # }
+/// ```
+pub struct Inspector<'a> {
+    tensor_buffers: &'a TensorBuffers,
+    title: String,
+    /// Every tensor, by name.
+    tensors: Vec<TensorInfo>,
+    graph: Vec<GraphLine>,
+    search: String,
+    searching: bool,
+    /// Indices into `tensors` of those matching the search.
+    matches: Vec<usize>,
+    list: ListState,
+    pane: Pane,
+    /// The tensor the preview was loaded for and its text.
+    preview: Option<(usize, String)>,
+}
+
+impl<'a> Inspector<'a> {
+    /// Reads the metadata and operations of the file, without any tensor data.
+    ///
+    /// # Returns
+    /// An error if the metadata can't be read. Tensors that can't be described are left out.
+    pub async fn load(tensor_buffers: &'a TensorBuffers) -> Result<Self> {
+        let tables = tensor_buffers.tensor_tables().await?.into_iter().flatten();
+        let mut tensors =
+            tables.filter_map(|tensor| TensorInfo::from_metadata(&tensor).ok()).collect::<Vec<_>>();
+        tensors.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let graph = tensor_buffers.graph().await?;
+        let operations = graph.operations_in_topological_order()?;
+        let names = tensor_buffers.graph_tensor_names(&graph).await;
+        let shapes = tensor_buffers.infer_shapes().await.unwrap_or_default();
+        let tensor_of = |id| names.get(&id).cloned().unwrap_or_else(|| format!("tensor {}", id));
+        let graph_lines = operations
+            .iter()
+            .map(|op| {
+                let output = tensor_of(*op.output());
+                let mut text = match op.name() {
+                    Some(name) => {
+                        format!("#{} {} ({:?}) -> {}", op.id(), name, op.operation(), output)
+                    }
+                    None => format!("#{} {:?} -> {}", op.id(), op.operation(), output),
+                };
+                if let Some(shape) = shapes.iter().find(|shape| shape.operation_id() == op.id()) {
+                    text.push_str(&format!(" {:?}", shape.shape()));
+                }
+                let mut tensors = vec![output];
+                if !op.input_operations().is_empty() {
+                    let inputs = op.input_operations().iter().map(|id| format!("#{}", id));
+                    text.push_str(&format!(" <- {}", inputs.collect::<Vec<_>>().join(", ")));
+                    let inputs =
+                        op.input_operations().iter().filter_map(|id| graph.get_operation(*id));
+                    tensors.extend(inputs.map(|input| tensor_of(*input.output())));
+                }
+                GraphLine { text, tensors }
+            })
+            .collect();
+
+        let size = tensor_buffers.file_size().await?;
+        let title =
+            format!(" {} tensors, {} operations, {} bytes ", tensors.len(), operations.len(), size);
+        let mut inspector = Inspector {
+            tensor_buffers,
+            title,
+            matches: (0..tensors.len()).collect(),
+            tensors,
+            graph: graph_lines,
+            search: String::new(),
+            searching: false,
+            list: ListState::default(),
+            pane: Pane::Metadata,
+            preview: None,
+        };
+        inspector.list.select((!inspector.matches.is_empty()).then_some(0));
+        Ok(inspector)
+    }
+
+    /// Runs the browser in the terminal until it is quit, restoring the terminal afterwards.
+    /// Waiting for keys blocks a worker thread, so it needs a multi-threaded Tokio runtime.
+    pub async fn run(mut self) -> Result<()> {
+        let mut terminal = ratatui::init();
+        let result = async {
+            loop {
+                terminal.draw(|frame| self.draw(frame))?;
+                if self.preview_stale() {
+                    self.load_preview().await;
+                    continue;
+                }
+                let event = tokio::task::block_in_place(event::read)?;
+                if let Event::Key(key) = event {
+                    if key.kind == KeyEventKind::Press && self.handle_key(key) == Action::Quit {
+                        return Ok(());
+                    }
+                }
+            }
+        }
+        .await;
+        ratatui::restore();
+        result
+    }
+
+    /// Returns the index into `tensors` of the selected tensor.
+    fn selected(&self) -> Option<usize> {
+        self.list.selected().and_then(|i| self.matches.get(i).copied())
+    }
+
+    fn preview_stale(&self) -> bool {
+        let loaded = self.preview.as_ref().map(|(index, _)| *index);
+        self.selected().is_some() && loaded != self.selected()
+    }
+
+    /// Loads the preview of the selected tensor, or the error that kept it from loading.
+    async fn load_preview(&mut self) {
+        let Some(index) = self.selected() else {
+            return;
+        };
+        let text = match preview(self.tensor_buffers, &self.tensors[index]).await {
+            Ok(text) => text,
+            Err(error) => format!("Can't preview: {}", error),
+        };
+        self.preview = Some((index, text));
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Action {
+        if self.searching {
+            match key.code {
+                KeyCode::Enter => self.searching = false,
+                KeyCode::Esc => {
+                    self.searching = false;
+                    self.set_search(String::new());
+                }
+                KeyCode::Backspace => {
+                    let mut search = self.search.clone();
+                    search.pop();
+                    self.set_search(search);
+                }
+                KeyCode::Char(c) => self.set_search(format!("{}{}", self.search, c)),
+                _ => {}
+            }
+            return Action::Continue;
+        }
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Action::Quit,
+            KeyCode::Char('/') => self.searching = true,
+            KeyCode::Tab => {
+                self.pane = match self.pane {
+                    Pane::Metadata => Pane::Graph,
+                    Pane::Graph => Pane::Metadata,
+                }
+            }
+            KeyCode::Down | KeyCode::Char('j') => self.move_selection(1),
+            KeyCode::Up | KeyCode::Char('k') => self.move_selection(-1),
+            KeyCode::PageDown => self.move_selection(PAGE_ROWS as isize),
+            KeyCode::PageUp => self.move_selection(-(PAGE_ROWS as isize)),
+            KeyCode::Home => self.move_selection(isize::MIN),
+            KeyCode::End => self.move_selection(isize::MAX),
+            _ => {}
+        }
+        Action::Continue
+    }
+
+    /// Moves the selection by `rows`, stopping at either end of the list.
+    fn move_selection(&mut self, rows: isize) {
+        let Some(last) = self.matches.len().checked_sub(1) else {
+            return;
+        };
+        let current = self.list.selected().unwrap_or(0);
+        let target = current.saturating_add_signed(rows).min(last);
+        self.list.select(Some(target));
+    }
+
+    /// Keeps the tensors whose names contain `search`, ignoring case, selecting the first.
+    fn set_search(&mut self, search: String) {
+        let needle = search.to_lowercase();
+        self.matches = (0..self.tensors.len())
+            .filter(|&i| self.tensors[i].name.to_lowercase().contains(&needle))
+            .collect();
+        self.search = search;
+        self.list.select((!self.matches.is_empty()).then_some(0));
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, help] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+        let [left, right] =
+            Layout::horizontal([Constraint::Percentage(35), Constraint::Min(0)]).areas(main);
+        self.draw_list(frame, left);
+        let [details, preview] =
+            Layout::vertical([Constraint::Percentage(60), Constraint::Min(0)]).areas(right);
+        match self.pane {
+            Pane::Metadata => self.draw_metadata(frame, details),
+            Pane::Graph => self.draw_graph(frame, details),
+        }
+        let text = match (&self.preview, self.preview_stale()) {
+            (Some((_, text)), false) => text.as_str(),
+            _ if self.selected().is_some() => "Loading...",
+            _ => "",
+        };
+        let block = Block::bordered().title(" Values ");
+        frame.render_widget(Paragraph::new(text).wrap(Wrap { trim: false }).block(block), preview);
+        let keys = match self.searching {
+            true => " Type to search  Enter keep  Esc clear",
+            false => " Up/Down select  / search  Tab metadata/graph  q quit",
+        };
+        frame.render_widget(Line::from(keys).reversed(), help);
+    }
+
+    fn draw_list(&mut self, frame: &mut Frame, area: Rect) {
+        let title = match (self.searching, self.search.is_empty()) {
+            (true, _) => format!(" /{}_ ", self.search),
+            (false, false) => format!(" /{} ({}) ", self.search, self.matches.len()),
+            (false, true) => " Tensors ".to_string(),
+        };
+        let names = self.matches.iter().map(|&i| self.tensors[i].name.as_str());
+        let list = List::new(names)
+            .block(Block::bordered().title(self.title.as_str()).title_bottom(title))
+            .highlight_style(Style::new().reversed());
+        frame.render_stateful_widget(list, area, &mut self.list);
+    }
+
+    fn draw_metadata(&self, frame: &mut Frame, area: Rect) {
+        let lines = match self.selected() {
+            Some(index) => describe(&self.tensors[index]),
+            None => vec!["No tensor matches".to_string()],
+        };
+        let lines = lines.into_iter().map(Line::from).collect::<Vec<_>>();
+        let block = Block::bordered().title(" Metadata ");
+        frame.render_widget(Paragraph::new(lines).wrap(Wrap { trim: false }).block(block), area);
+    }
+
+    /// Draws the operations in topological order, highlighting those that read or write the
+    /// selected tensor and scrolling to the first of them.
+    fn draw_graph(&self, frame: &mut Frame, area: Rect) {
+        let selected = self.selected().map(|index| self.tensors[index].name.as_str());
+        let touches =
+            |line: &GraphLine| selected.is_some_and(|name| line.tensors.iter().any(|t| t == name));
+        let first = self.graph.iter().position(touches).unwrap_or(0);
+        let lines = self
+            .graph
+            .iter()
+            .map(|line| match touches(line) {
+                true => Line::from(line.text.as_str()).bold(),
+                false => Line::from(line.text.as_str()),
+            })
+            .collect::<Vec<_>>();
+        let text = match lines.is_empty() {
+            true => vec![Line::from("The file has no operations")],
+            false => lines,
+        };
+        let block = Block::bordered().title(" Graph ");
+        let scroll = first.saturating_sub(1) as u16;
+        frame.render_widget(Paragraph::new(text).scroll((scroll, 0)).block(block), area);
+    }
+}
+
+/// Returns the lines describing a tensor in the metadata pane.
+fn describe(tensor: &TensorInfo) -> Vec<String> {
+    let mut lines = vec![
+        format!("Name:        {}", tensor.name),
+        format!("Id:          {}", tensor.id),
+        format!("Data type:   {:?}", tensor.data_type),
+        format!("Shape:       {:?}", tensor.shape),
+        format!("Elements:    {}", tensor.elements()),
+        format!("Data size:   {} bytes", tensor.data_size),
+        format!("Stored size: {} bytes", tensor.stored_size),
+        format!("Compression: {:?}", tensor.compression),
+    ];
+    if let Some(codec) = &tensor.codec {
+        lines.push(format!("Codec:       {}", codec));
+    }
+    if let Some(target) = &tensor.alias_of {
+        lines.push(format!("Alias of:    {}", target));
+    }
+    if let Some(location) = &tensor.location {
+        lines.push(format!("Stored in:   {}", location));
+    }
+    lines
+}
+
+/// Reads the first values of a tensor as stored, only reading them when the tensor is stored
+/// uncompressed.
+async fn preview(tensor_buffers: &TensorBuffers, tensor: &TensorInfo) -> Result<String> {
+    let raw = !matches!(tensor.data_type, DataType::Extension(_) | DataType::Q8_0 | DataType::Q4K);
+    // Types stored as raw bytes are shown as the bytes.
+    let wanted = match raw {
+        true => PREVIEW_ELEMENTS * tensor.data_type.size(),
+        false => PREVIEW_ELEMENTS,
+    };
+    let bytes = match tensor.compression {
+        Compression::None => {
+            let chunks = tensor_buffers.stream_tensor_bytes(&tensor.name, wanted).await?;
+            match pin!(chunks).next().await {
+                Some(chunk) => chunk?.to_vec(),
+                None => Vec::new(),
+            }
+        }
+        _ => {
+            let metadata = tensor_buffers.stored_metadata(tensor.id).await?;
+            let mut bytes = tensor_buffers.read_tensor_any(metadata).await?.data_bytes().to_vec();
+            bytes.truncate(wanted);
+            bytes
+        }
+    };
+    let values = match raw {
+        true => format_values(tensor.data_type, &bytes),
+        false => bytes.iter().map(|byte| format!("{:02x}", byte)).collect(),
+    };
+    let shown = values.len();
+    let mut text = values.join(", ");
+    if shown < tensor.elements() {
+        text.push_str(&format!(", ... ({} of {} shown)", shown, tensor.elements()));
+    }
+    Ok(text)
+}
+
+/// Formats the little-endian elements of `data_type` in `bytes`.
+fn format_values(data_type: DataType, bytes: &[u8]) -> Vec<String> {
+    fn values<T: Pod + std::fmt::Debug>(bytes: &[u8]) -> Vec<String> {
+        let elements = bytes.chunks_exact(size_of::<T>());
+        elements.map(|element| format!("{:?}", pod_read_unaligned::<T>(element))).collect()
+    }
+    match data_type {
+        DataType::Int8 => values::<i8>(bytes),
+        DataType::Int16 => values::<i16>(bytes),
+        DataType::Int32 => values::<i32>(bytes),
+        DataType::Int64 => values::<i64>(bytes),
+        DataType::UInt8 => values::<u8>(bytes),
+        DataType::UInt16 => values::<u16>(bytes),
+        DataType::UInt32 => values::<u32>(bytes),
+        DataType::UInt64 => values::<u64>(bytes),
+        DataType::Float16 => values::<f16>(bytes),
+        DataType::Float32 => values::<f32>(bytes),
+        DataType::Float64 => values::<f64>(bytes),
+        _ => bytes.iter().map(|byte| format!("{:02x}", byte)).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ratatui::{backend::TestBackend, crossterm::event::KeyModifiers, Terminal};
+    use tempfile::NamedTempFile;
+    use tokio::fs::File;
+
+    use super::*;
+    use crate::{
+        utils::hash_key, Operation, Tensor, TensorBuffersWrite, TensorBuffersWriter,
+        TensorOperation, WriterOptions,
+    };
+
+    fn press(inspector: &mut Inspector<'_>, code: KeyCode) -> Action {
+        inspector.handle_key(KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    fn type_text(inspector: &mut Inspector<'_>, text: &str) {
+        text.chars().for_each(|c| assert_eq!(press(inspector, KeyCode::Char(c)), Action::Continue));
+    }
+
+    fn render(inspector: &mut Inspector<'_>) -> String {
+        let mut terminal = Terminal::new(TestBackend::new(120, 30)).unwrap();
+        terminal.draw(|frame| inspector.draw(frame)).unwrap();
+        let buffer = terminal.backend().buffer();
+        let rows = buffer.content().chunks(buffer.area.width as usize);
+        rows.map(|row| row.iter().map(|cell| cell.symbol()).collect::<String>() + "\n").collect()
+    }
+
+    #[tokio::test]
+    async fn test_inspector() {
+        let embed = (0..100).map(|i| i as f32 * 0.5).collect::<Vec<_>>();
+        let tensors = vec![
+            Tensor::new("model.embed.weight", &embed, vec![10, 10]),
+            Tensor::new("model.norm.weight", &embed[..10], vec![10]),
+            Tensor::new("lm_head.weight", &embed, vec![10, 10]),
+        ];
+        let operations = vec![
+            TensorOperation::new(1, Operation::None, vec![], hash_key("model.embed.weight")),
+            TensorOperation::new(2, Operation::Transpose, vec![1], hash_key("transposed"))
+                .with_name("flip"),
+        ];
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        let options = WriterOptions::new().with_compression(Compression::Zstd, 3);
+        let mut writer = TensorBuffersWriter::with_options(&mut file, options);
+        writer.write(tensors, operations).await.unwrap();
+
+        let url = format!("file://{}", tmp.path().display());
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        let mut inspector = Inspector::load(&tensor_buffers).await.unwrap();
+        let names = inspector.tensors.iter().map(|tensor| tensor.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["lm_head.weight", "model.embed.weight", "model.norm.weight"]);
+        assert!(inspector.preview_stale());
+        let screen = render(&mut inspector);
+        assert!(screen.contains("3 tensors, 2 operations"));
+        assert!(screen.contains("Name:        lm_head.weight"));
+        assert!(screen.contains("Loading..."));
+
+        // Compressed tensors are read whole and cut short.
+        inspector.load_preview().await;
+        let screen = render(&mut inspector);
+        assert!(screen.contains("0.0, 0.5, 1.0, 1.5"));
+        assert!(screen.contains("(64 of 100 shown)"));
+
+        // Searching narrows the list and selects the first match.
+        assert_eq!(press(&mut inspector, KeyCode::Char('/')), Action::Continue);
+        type_text(&mut inspector, "MODEL");
+        assert_eq!(inspector.matches, [1, 2]);
+        assert!(render(&mut inspector).contains(" /MODEL_ "));
+        // Keys are search text while searching.
+        type_text(&mut inspector, "q");
+        assert!(inspector.matches.is_empty());
+        assert!(render(&mut inspector).contains("No tensor matches"));
+        press(&mut inspector, KeyCode::Backspace);
+        press(&mut inspector, KeyCode::Enter);
+        press(&mut inspector, KeyCode::End);
+        assert_eq!(inspector.selected(), Some(2));
+        press(&mut inspector, KeyCode::Down);
+        assert_eq!(inspector.selected(), Some(2));
+        press(&mut inspector, KeyCode::Up);
+        assert_eq!(inspector.selected(), Some(1));
+        inspector.load_preview().await;
+        assert!(!inspector.preview_stale());
+
+        // The graph view highlights the operations touching the selected tensor.
+        press(&mut inspector, KeyCode::Tab);
+        let screen = render(&mut inspector);
+        assert!(screen.contains("#1 None -> model.embed.weight [10, 10]"));
+        assert!(screen.contains("#2 flip (Transpose) -> tensor "));
+        assert!(screen.contains("[10, 10] <- #1"));
+
+        assert_eq!(inspector.search, "MODEL");
+        // Esc while searching clears the search rather than quitting.
+        press(&mut inspector, KeyCode::Char('/'));
+        assert_eq!(press(&mut inspector, KeyCode::Esc), Action::Continue);
+        assert_eq!((inspector.search.as_str(), inspector.matches.len()), ("", 3));
+        assert_eq!(press(&mut inspector, KeyCode::Char('q')), Action::Quit);
+        assert_eq!(press(&mut inspector, KeyCode::Esc), Action::Quit);
+    }
+
+    #[tokio::test]
+    async fn test_preview_reads_the_first_values() {
+        let ids = (0..1000).collect::<Vec<i64>>();
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        let mut writer = TensorBuffersWriter::new(&mut file);
+        writer.write(vec![Tensor::new("ids", &ids, vec![1000])], vec![]).await.unwrap();
+
+        let url = format!("file://{}", tmp.path().display());
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        let info = tensor_buffers.get_tensor_metadata(hash_key("ids")).await.unwrap();
+        let before = tensor_buffers.stats().bytes_read;
+        let text = preview(&tensor_buffers, &info).await.unwrap();
+        assert!(text.starts_with("0, 1, 2, "));
+        assert!(text.ends_with("62, 63, ... (64 of 1000 shown)"));
+        // Only the previewed values were read.
+        assert_eq!(tensor_buffers.stats().bytes_read - before, 64 * 8);
+
+        let values = format_values(DataType::Float16, &f16::from_f32(1.5).to_le_bytes());
+        assert_eq!(values, ["1.5"]);
+    }
+}
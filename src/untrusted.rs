@@ -0,0 +1,249 @@
+use bytemuck::Pod;
+use flatbuffers::VerifierOptions;
+
+use crate::{
+    codec,
+    constants::SUPPORTED_VERSIONS,
+    format_features::check_features,
+    generated::tensor_buffers::{TensorBuffersMetadata, TensorMetadata},
+    read_mode::{check_metadata, find_tensor},
+    slice_file::{check_data_layout, decode_stored, locate_metadata},
+    utils::hash_key,
+    Num, Result, Tensor, TensorOperation,
+};
+
+/// Caps on what [`parse_untrusted`] accepts, so a hostile file can't make it allocate or work
+/// without bound.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UntrustedLimits {
+    /// Largest metadata section in bytes.
+    pub max_metadata_size: usize,
+    /// Most tensors in the file.
+    pub max_tensors: usize,
+    /// Most operations in the file.
+    pub max_operations: usize,
+    /// Largest data of one tensor in bytes, both stored and decompressed. Bounds the allocation
+    /// of each `UntrustedFile::tensor` call.
+    pub max_tensor_size: usize,
+    /// Largest decompressed data of all tensors together in bytes.
+    pub max_total_size: u64,
+}
+
+impl Default for UntrustedLimits {
+    fn default() -> Self {
+        UntrustedLimits {
+            max_metadata_size: 16 << 20,
+            max_tensors: 100_000,
+            max_operations: 100_000,
+            max_tensor_size: 1 << 30,
+            max_total_size: 16 << 30,
+        }
+    }
+}
+
+/// A TensorBuffers file parsed from untrusted bytes. Its metadata is fully validated, so reading
+/// tensors from it only fails on corrupt tensor data.
+#[derive(Clone, Copy, Debug)]
+pub struct UntrustedFile<'a> {
+    bytes: &'a [u8],
+    metadata: TensorBuffersMetadata<'a>,
+    limits: UntrustedLimits,
+}
+
+/// Parses a TensorBuffers file held in memory that may have been crafted to attack the parser,
+/// e.g. a user upload. Never panics, and every allocation is bounded by `limits`. Meant as the
+/// entry point for fuzzing.
+///
+/// Checks the magic bytes, footer and metadata checksum, verifies the FlatBuffers metadata within
+/// the limits, and rejects everything that strict reads reject as well as unsupported versions,
+/// tensor data outside the data section and tensors over the size limits. Files with compressed
+/// or columnar metadata are rejected too, since the parsed file borrows its metadata from `bytes`,
+/// and so are files with tensors stored in other files, which could point the reader anywhere.
+///
+/// # Arguments
+/// * `bytes` - The whole file.
+/// * `limits` - The caps to enforce.
+///
+/// # Returns
+/// The parsed file, which borrows `bytes`.
+pub fn parse_untrusted(bytes: &[u8], limits: UntrustedLimits) -> Result<UntrustedFile<'_>> {
+    let (footer, range) = locate_metadata(bytes)?;
+    if footer.metadata_size > limits.max_metadata_size {
+        return Err(format!(
+            "Metadata of {} bytes exceeds the limit of {} bytes",
+            footer.metadata_size, limits.max_metadata_size
+        )
+        .into());
+    }
+    if footer.compressed_metadata {
+        return Err("Compressed metadata is not supported for untrusted files".into());
+    }
+    let metadata_start = range.start;
+    let metadata_bytes = &bytes[range];
+
+    let verifier_options = VerifierOptions {
+        max_depth: 16,
+        max_tables: limits.max_tensors + limits.max_operations + 1,
+        max_apparent_size: limits.max_metadata_size,
+        ..Default::default()
+    };
+    let metadata =
+        flatbuffers::root_with_opts::<TensorBuffersMetadata>(&verifier_options, metadata_bytes)
+            .map_err(|error| format!("Invalid metadata: {}", error))?;
+    if !SUPPORTED_VERSIONS.contains(&metadata.version()) {
+        return Err(format!("Unsupported format version {}", metadata.version()).into());
+    }
+    check_features(&metadata)?;
+    if metadata.tensor_columns().is_some() {
+        return Err("Columnar metadata is not supported for untrusted files".into());
+    }
+
+    let tensors = metadata.tensors().map_or(0, |tensors| tensors.len());
+    let operations = metadata.operations().map_or(0, |operations| operations.len());
+    if tensors > limits.max_tensors || operations > limits.max_operations {
+        return Err(format!(
+            "{} tensors and {} operations exceed the limits of {} and {}",
+            tensors, operations, limits.max_tensors, limits.max_operations
+        )
+        .into());
+    }
+    if let Some(problem) = check_metadata(&metadata).into_iter().next() {
+        return Err(problem.into());
+    }
+
+    let mut total_size = 0u64;
+    for tensor in metadata.tensors().into_iter().flatten() {
+        // Untrusted files must not make the reader open other files or URLs.
+        if tensor.external().is_some() {
+            return Err(format!("Tensor {} is stored in another file", tensor.name()).into());
+        }
+        check_data_layout(&tensor, metadata_start as u64)?;
+        let size = tensor.data_size().max(tensor.stored_size()) as usize;
+        if size > limits.max_tensor_size {
+            return Err(format!(
+                "Tensor {} of {} bytes exceeds the limit of {} bytes",
+                tensor.name(),
+                size,
+                limits.max_tensor_size
+            )
+            .into());
+        }
+        total_size += tensor.data_size() as u64;
+    }
+    if total_size > limits.max_total_size {
+        return Err(format!(
+            "Tensors of {} bytes exceed the limit of {} bytes",
+            total_size, limits.max_total_size
+        )
+        .into());
+    }
+    Ok(UntrustedFile { bytes, metadata, limits })
+}
+
+impl<'a> UntrustedFile<'a> {
+    /// Returns the format version of the file.
+    pub fn version(&self) -> &'a str {
+        self.metadata.version()
+    }
+
+    /// Returns the names of the tensors in the file, in id order.
+    pub fn tensor_names(&self) -> Vec<&'a str> {
+        self.metadata.tensors().into_iter().flatten().map(|tensor| tensor.name()).collect()
+    }
+
+    /// Returns the operations stored in the file.
+    pub fn operations(&self) -> Vec<TensorOperation> {
+        let operations = self.metadata.operations().into_iter().flatten();
+        operations.map(|op| TensorOperation::with_metadata(&op)).collect()
+    }
+
+    /// Decodes a tensor, checking its data against its checksum.
+    ///
+    /// # Arguments
+    /// * `name` - The name of the tensor.
+    pub fn tensor<T>(&self, name: &str) -> Result<Tensor<'static, T>>
+    where
+        T: Pod + Num,
+    {
+        let metadata = find_tensor(&self.metadata, hash_key(name))
+            .ok_or_else(|| format!("Tensor {} not found", name))?;
+        self.decode(metadata)
+    }
+
+    fn decode<T>(&self, metadata: TensorMetadata<'a>) -> Result<Tensor<'static, T>>
+    where
+        T: Pod + Num,
+    {
+        // `parse_untrusted` checked that the size matches the shape and is within the limits, and
+        // that the stored data lies within the data section.
+        let size = metadata.data_size() as usize;
+        if size > self.limits.max_tensor_size {
+            return Err(format!("Tensor {} exceeds the size limit", metadata.name()).into());
+        }
+        let offset = metadata.data_offset() as usize;
+        let stored = &self.bytes[offset..offset + codec::stored_size(&metadata)];
+        decode_stored(self.metadata, metadata, stored)
+    }
+}
+
+#[cfg(all(test, feature = "async"))]
+mod tests {
+    use super::*;
+    use crate::{Compression, TensorBuffersWrite, TensorBuffersWriter, WriterOptions};
+
+    async fn sample_file() -> Vec<u8> {
+        let values = (0..256).map(|i| (i % 8) as f32).collect::<Vec<_>>();
+        let tensors = vec![
+            Tensor::new("w", values.as_slice(), vec![16, 16]),
+            Tensor::new("b", &[1.0f32, 2.0], vec![2]),
+        ];
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        let options = WriterOptions::new().with_compression(Compression::Lz4, 0);
+        let mut writer = TensorBuffersWriter::with_options(&mut bytes, options);
+        writer.write(tensors, vec![]).await.unwrap();
+        bytes.into_inner()
+    }
+
+    #[tokio::test]
+    async fn test_parse_untrusted() {
+        let bytes = sample_file().await;
+        let file = parse_untrusted(&bytes, UntrustedLimits::default()).unwrap();
+        assert_eq!(file.version(), crate::constants::VERSION);
+        assert_eq!(file.tensor_names().len(), 2);
+        let w = file.tensor::<f32>("w").unwrap();
+        assert_eq!(w.shape(), [16, 16]);
+        assert_eq!(w.data()[9], 1.0);
+        assert!(file.tensor::<f64>("w").is_err());
+        assert!(file.tensor::<f32>("missing").is_err());
+
+        let tight = UntrustedLimits { max_tensor_size: 512, ..Default::default() };
+        let error = parse_untrusted(&bytes, tight).unwrap_err();
+        assert_eq!(error.to_string(), "Tensor w of 1024 bytes exceeds the limit of 512 bytes");
+        let tight = UntrustedLimits { max_metadata_size: 64, ..Default::default() };
+        assert!(parse_untrusted(&bytes, tight).is_err());
+        assert!(parse_untrusted(&bytes[1..], UntrustedLimits::default()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_parse_untrusted_never_panics() {
+        let bytes = sample_file().await;
+        // Deterministic corruptions: flipped bytes throughout the file, and every truncation.
+        let mut state = 0x2545f4914f6cdd1du64;
+        for _ in 0..2000 {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            let mut corrupted = bytes.clone();
+            let i = (state % bytes.len() as u64) as usize;
+            corrupted[i] = (state >> 32) as u8;
+            if let Ok(file) = parse_untrusted(&corrupted, UntrustedLimits::default()) {
+                for name in file.tensor_names() {
+                    let _ = file.tensor::<f32>(name);
+                }
+            }
+        }
+        for len in 0..bytes.len() {
+            assert!(parse_untrusted(&bytes[..len], UntrustedLimits::default()).is_err());
+        }
+    }
+}
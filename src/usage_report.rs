@@ -0,0 +1,145 @@
+use std::collections::{BTreeMap, HashSet};
+
+use crate::{codec, Result, TensorBuffers};
+
+/// Sizes of the tensors in one namespace of a file, see [`TensorBuffers::usage_report`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NamespaceUsage {
+    /// The namespace, a prefix of tensor names ending before a `.`, or `""` for the whole file.
+    pub namespace: String,
+    /// Number of tensors in the namespace, including aliases.
+    pub tensors: usize,
+    /// Bytes of tensor data in the namespace before compression, counting each tensor.
+    pub data_size: u64,
+    /// Bytes of the file holding the namespace's data, counting data that tensors share once and
+    /// leaving out data stored in other files.
+    pub stored_size: u64,
+}
+
+/// What [`TensorBuffers::usage_report`] found.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UsageReport {
+    /// The totals of the whole file.
+    pub total: NamespaceUsage,
+    /// The totals of every namespace, in name order.
+    pub namespaces: Vec<NamespaceUsage>,
+}
+
+/// A namespace's totals and the stored regions already counted for it.
+#[derive(Default)]
+struct Totals {
+    usage: NamespaceUsage,
+    regions: HashSet<(u64, usize)>,
+}
+
+impl Totals {
+    fn add(&mut self, data_size: u64, region: Option<(u64, usize)>) {
+        self.usage.tensors += 1;
+        self.usage.data_size += data_size;
+        if let Some((offset, size)) = region {
+            if self.regions.insert((offset, size)) {
+                self.usage.stored_size += size as u64;
+            }
+        }
+    }
+}
+
+impl TensorBuffers {
+    /// Sums tensor sizes per namespace from the metadata alone, like `du` does for directories,
+    /// so dashboards can summarize files without transferring their contents. Namespaces are the
+    /// prefixes of tensor names that end before a `.`, so `encoder.layers.0.weight` counts
+    /// towards `encoder`, `encoder.layers` and `encoder.layers.0`. Tensors the reader can't
+    /// describe, such as those of unknown data types in lenient mode, are counted too.
+    ///
+    /// # Returns
+    /// The totals of the file and of each namespace. Works on files opened with
+    /// `OpenOptions::with_metadata_only`.
+    pub async fn usage_report(&self) -> Result<UsageReport> {
+        let mut total = Totals::default();
+        let mut namespaces = BTreeMap::<&str, Totals>::new();
+        for tensor in self.tensor_tables().await?.into_iter().flatten() {
+            let data_size = tensor.data_size() as u64;
+            let region = match tensor.external() {
+                Some(_) => None,
+                None => Some((tensor.data_offset() as u64, codec::stored_size(&tensor))),
+            };
+            total.add(data_size, region);
+            let name = tensor.name();
+            for (end, _) in name.match_indices('.') {
+                namespaces.entry(&name[..end]).or_default().add(data_size, region);
+            }
+        }
+        let namespaces = namespaces
+            .into_iter()
+            .map(|(namespace, totals)| NamespaceUsage {
+                namespace: namespace.to_string(),
+                ..totals.usage
+            })
+            .collect();
+        Ok(UsageReport { total: total.usage, namespaces })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+    use tokio::fs::File;
+
+    use super::*;
+    use crate::{OpenOptions, Tensor, TensorBuffersWrite, TensorBuffersWriter, WriterOptions};
+
+    #[tokio::test]
+    async fn test_usage_report() {
+        let dir = TempDir::new().unwrap();
+        let data = vec![1.0f32; 256];
+        let other = vec![2.0f32; 64];
+        let mut file = File::create(dir.path().join("model.tb")).await.unwrap();
+        let mut writer =
+            TensorBuffersWriter::with_options(&mut file, WriterOptions::new().with_dedup(true));
+        writer.add_alias("encoder.layers.0.weight", "decoder.embed");
+        writer
+            .write(
+                vec![
+                    Tensor::new("encoder.layers.0.weight", &data, vec![16, 16]),
+                    Tensor::new("encoder.layers.1.weight", &data, vec![16, 16]),
+                    Tensor::new("encoder.norm", &other, vec![64]),
+                    Tensor::new("scale", &other[..1], vec![1]),
+                ],
+                vec![],
+            )
+            .await
+            .unwrap();
+
+        let url = format!("file://{}", dir.path().join("model.tb").display());
+        let options = OpenOptions::new().with_metadata_only(true);
+        let buffers = TensorBuffers::open_with(&url, options).await.unwrap();
+        let report = buffers.usage_report().await.unwrap();
+        assert_eq!(report.total.namespace, "");
+        assert_eq!(report.total.tensors, 5);
+        assert_eq!(report.total.data_size, 3 * 1024 + 256 + 4);
+        assert_eq!(report.total.stored_size, 1024 + 256 + 4);
+        let namespaces = report
+            .namespaces
+            .iter()
+            .map(|usage| {
+                (usage.namespace.as_str(), usage.tensors, usage.data_size, usage.stored_size)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(namespaces, [
+            ("decoder", 1, 1024, 1024),
+            ("encoder", 3, 2 * 1024 + 256, 1024 + 256),
+            ("encoder.layers", 2, 2 * 1024, 1024),
+            ("encoder.layers.0", 1, 1024, 1024),
+            ("encoder.layers.1", 1, 1024, 1024),
+        ]);
+
+        // No tensor data is read from a file opened for its metadata only.
+        assert!(buffers.get_tensor_data_by_name::<f32>("scale").await.is_err());
+        assert!(buffers.get_tensors_data_by_name::<f32>(&["scale"]).await.is_err());
+        assert!(buffers.stream_tensor_bytes("scale", 4).await.is_err());
+        assert!(buffers.verify_data(None).await.is_err());
+        assert_eq!(buffers.filter_tensors(|_| true).await.unwrap().len(), 5);
+    }
+}
@@ -0,0 +1,96 @@
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{Result, TensorBuffers};
+
+/// Encodes `value` as a FlexBuffer, for `WriterOptions::with_user_metadata` or
+/// `TensorBuffersWriter::add_user_metadata`, so organizations can attach structured fields
+/// without extending the schema.
+///
+/// ```
+/// use tensorbuffers::{from_user_metadata, to_user_metadata};
+///
+/// #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+/// struct Provenance {
+///     team: String,
+///     run: u32,
+/// }
+///
+/// let provenance = Provenance { team: "search".to_string(), run: 17 };
+/// let bytes = to_user_metadata(&provenance).unwrap();
+/// assert_eq!(from_user_metadata::<Provenance>(&bytes).unwrap(), provenance);
+/// ```
+pub fn to_user_metadata<T: Serialize>(value: &T) -> Result<Vec<u8>> {
+    flexbuffers::to_vec(value)
+        .map_err(|error| format!("Failed to encode user metadata: {}", error).into())
+}
+
+/// Decodes user metadata encoded by [`to_user_metadata`].
+pub fn from_user_metadata<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+    flexbuffers::from_slice(bytes)
+        .map_err(|error| format!("Failed to decode user metadata: {}", error).into())
+}
+
+impl TensorBuffers {
+    /// Returns the file's user metadata decoded as `T`, see [`to_user_metadata`].
+    ///
+    /// # Returns
+    /// `None` if the file has none, or an error if it isn't a FlexBuffer of a `T`.
+    pub async fn user_metadata_as<T: DeserializeOwned>(&self) -> Result<Option<T>> {
+        self.user_metadata().await?.map(from_user_metadata).transpose()
+    }
+
+    /// Returns the user metadata of a tensor decoded as `T`, see [`to_user_metadata`].
+    ///
+    /// # Returns
+    /// `None` if the tensor has none, or an error if the file has no such tensor or its user
+    /// metadata isn't a FlexBuffer of a `T`.
+    pub async fn tensor_user_metadata_as<T: DeserializeOwned>(
+        &self,
+        tensor_name: &str,
+    ) -> Result<Option<T>> {
+        self.tensor_user_metadata(tensor_name).await?.map(from_user_metadata).transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use tempfile::NamedTempFile;
+    use tokio::fs::File;
+
+    use super::*;
+    use crate::{Tensor, TensorBuffersWrite, TensorBuffersWriter, WriterOptions};
+
+    #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+    struct Review {
+        approved_by: String,
+        tags: BTreeMap<String, f64>,
+    }
+
+    #[tokio::test]
+    async fn test_typed_user_metadata() {
+        let review = Review {
+            approved_by: "ml-platform".to_string(),
+            tags: BTreeMap::from([("sparsity".to_string(), 0.5)]),
+        };
+        let options = WriterOptions::new()
+            .with_user_metadata(to_user_metadata(&vec!["internal", "v3"]).unwrap());
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        let mut writer = TensorBuffersWriter::with_options(&mut file, options);
+        writer.add_user_metadata("w", to_user_metadata(&review).unwrap());
+        let tensors =
+            vec![Tensor::new("w", &[1.0f32, 2.0], vec![2]), Tensor::new("b", &[0.0], vec![1])];
+        writer.write(tensors, vec![]).await.unwrap();
+
+        let url = format!("file://{}", tmp.path().display());
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        let labels = tensor_buffers.user_metadata_as::<Vec<String>>().await.unwrap();
+        assert_eq!(labels.unwrap(), ["internal", "v3"]);
+        let read = tensor_buffers.tensor_user_metadata_as::<Review>("w").await.unwrap();
+        assert_eq!(read, Some(review));
+        assert!(tensor_buffers.tensor_user_metadata_as::<Review>("b").await.unwrap().is_none());
+        assert!(tensor_buffers.user_metadata_as::<u32>().await.is_err());
+    }
+}
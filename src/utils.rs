@@ -1,4 +1,6 @@
 use std::hash::{Hash, Hasher};
+#[cfg(feature = "async")]
+use std::time::Instant;
 
 use fnv::FnvHasher;
 
@@ -7,3 +9,42 @@ pub(crate) fn hash_key(key: &str) -> u64 {
     key.hash(&mut hasher);
     hasher.finish()
 }
+
+/// Returns the URL without its query string, which may carry credentials such as presigned
+/// signatures, so it can be logged.
+#[cfg(feature = "async")]
+pub(crate) fn loggable_url(url: &str) -> &str {
+    url.split_once('?').map_or(url, |(base, _)| base)
+}
+
+/// Returns the milliseconds elapsed since `start`, for recording in tracing spans.
+#[cfg(feature = "async")]
+pub(crate) fn elapsed_ms(start: Instant) -> f64 {
+    start.elapsed().as_secs_f64() * 1000.0
+}
+
+/// Returns `buffer` with a `'static` lifetime, so tables parsed from it can be cached next to it
+/// instead of leaking it.
+///
+/// # Safety
+/// The buffer's allocation must be kept, unchanged, for as long as anything borrows from the
+/// returned slice, and tables parsed from it must only be handed out for the borrow of their owner.
+#[cfg(feature = "async")]
+pub(crate) unsafe fn unbound_lifetime(buffer: &[u8]) -> &'static [u8] {
+    // SAFETY: The caller keeps the allocation alive and unchanged.
+    unsafe { &*(buffer as *const [u8]) }
+}
+
+#[cfg(all(test, feature = "async"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_loggable_url() {
+        assert_eq!(
+            loggable_url("https://host/model.tb?X-Amz-Signature=secret"),
+            "https://host/model.tb"
+        );
+        assert_eq!(loggable_url("model.tb"), "model.tb");
+    }
+}
@@ -0,0 +1,147 @@
+use std::{
+    any::{Any, TypeId},
+    collections::BTreeMap,
+    mem::size_of,
+};
+
+use fnv::FnvHashMap;
+
+use crate::{metrics, TensorOperationId, TensorValue};
+
+/// Results are cached per element type, since the same operation can be evaluated as f32 and f64.
+type CacheKey = (TypeId, TensorOperationId);
+
+struct CacheEntry {
+    value: Box<dyn Any + Send + Sync>,
+    bytes: usize,
+    last_used: u64,
+}
+
+/// A least-recently-used cache of operation results bounded by the total size of their data.
+pub(crate) struct ValueCache {
+    budget: usize,
+    used: usize,
+    tick: u64,
+    entries: FnvHashMap<CacheKey, CacheEntry>,
+    recency: BTreeMap<u64, CacheKey>,
+}
+
+impl ValueCache {
+    pub(crate) fn new(budget: usize) -> Self {
+        ValueCache {
+            budget,
+            used: 0,
+            tick: 0,
+            entries: FnvHashMap::default(),
+            recency: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the number of bytes of tensor data currently cached.
+    pub(crate) fn used(&self) -> usize {
+        self.used
+    }
+
+    fn touch(&mut self, key: CacheKey) -> u64 {
+        self.tick += 1;
+        self.recency.insert(self.tick, key);
+        self.tick
+    }
+
+    /// Returns a copy of the cached result of `operation_id` and marks it as recently used.
+    pub(crate) fn get<T>(&mut self, operation_id: TensorOperationId) -> Option<TensorValue<T>>
+    where
+        T: Clone + 'static,
+    {
+        let key = (TypeId::of::<T>(), operation_id);
+        let entry = self.entries.get(&key);
+        metrics::record_cache_lookup(entry.is_some());
+        let last_used = entry?.last_used;
+        self.recency.remove(&last_used);
+        let tick = self.touch(key);
+        let entry = self.entries.get_mut(&key)?;
+        entry.last_used = tick;
+        entry.value.downcast_ref::<TensorValue<T>>().cloned()
+    }
+
+    /// Caches the result of `operation_id`, evicting the least recently used results until it
+    /// fits. Results larger than the whole budget are not cached.
+    pub(crate) fn insert<T>(&mut self, operation_id: TensorOperationId, value: TensorValue<T>)
+    where
+        T: Send + Sync + 'static,
+    {
+        let key = (TypeId::of::<T>(), operation_id);
+        let bytes = value.data().len() * size_of::<T>();
+        self.remove(key);
+        if bytes > self.budget {
+            return;
+        }
+        while self.used + bytes > self.budget {
+            let Some((_, oldest)) = self.recency.pop_first() else {
+                break;
+            };
+            self.remove(oldest);
+        }
+        let last_used = self.touch(key);
+        self.entries.insert(key, CacheEntry { value: Box::new(value), bytes, last_used });
+        self.used += bytes;
+    }
+
+    fn remove(&mut self, key: CacheKey) {
+        if let Some(entry) = self.entries.remove(&key) {
+            self.recency.remove(&entry.last_used);
+            self.used -= entry.bytes;
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+        self.recency.clear();
+        self.used = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value(len: usize) -> TensorValue<f32> {
+        TensorValue::new(vec![1.0; len], vec![len]).unwrap()
+    }
+
+    #[test]
+    fn test_lru_eviction() {
+        // Room for three 4-element f32 values.
+        let mut cache = ValueCache::new(48);
+        cache.insert(1, value(4));
+        cache.insert(2, value(4));
+        cache.insert(3, value(4));
+        assert_eq!(cache.used(), 48);
+
+        // Using 1 makes 2 the least recently used entry.
+        assert!(cache.get::<f32>(1).is_some());
+        cache.insert(4, value(4));
+        assert!(cache.get::<f32>(2).is_none());
+        assert!(cache.get::<f32>(1).is_some());
+        assert!(cache.get::<f32>(3).is_some());
+        assert!(cache.get::<f32>(4).is_some());
+        assert_eq!(cache.used(), 48);
+
+        // Values larger than the budget are never cached.
+        cache.insert(5, value(13));
+        assert!(cache.get::<f32>(5).is_none());
+        cache.clear();
+        assert_eq!(cache.used(), 0);
+    }
+
+    #[test]
+    fn test_types_are_cached_separately() {
+        let mut cache = ValueCache::new(1024);
+        cache.insert(1, value(2));
+        assert!(cache.get::<f64>(1).is_none());
+        cache.insert(1, TensorValue::new(vec![2.0f64], vec![1]).unwrap());
+        assert_eq!(cache.get::<f32>(1).unwrap().data(), &[1.0, 1.0]);
+        assert_eq!(cache.get::<f64>(1).unwrap().data(), &[2.0]);
+        assert_eq!(cache.used(), 16);
+    }
+}
@@ -0,0 +1,206 @@
+use std::{
+    collections::HashSet,
+    time::{Duration, Instant},
+};
+
+use tracing::{info, instrument, warn};
+
+use crate::{
+    codec::{self, Checksummer},
+    generated::tensor_buffers::{ChecksumAlgorithm, TensorMetadata},
+    read_mode::tensor_problem,
+    reader_pool::ReaderPool,
+    utils::hash_key,
+    Result, TensorBuffers,
+};
+
+/// Size of the chunks `verify_data` reads tensor data in, the only data it holds at once.
+const VERIFY_CHUNK_SIZE: usize = 4 << 20;
+
+/// What [`TensorBuffers::verify_data`] found.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct VerifyReport {
+    /// Number of tensors whose stored data matched their checksum.
+    pub verified: usize,
+    /// Tensors stored without a checksum, which can't be verified.
+    pub unchecked: Vec<String>,
+    /// Tensors whose stored data is corrupt or out of place, with the problem found.
+    pub failed: Vec<(String, String)>,
+    /// Bytes of stored data read, counting data shared by deduplicated tensors once.
+    pub bytes: u64,
+    /// How long the pass took.
+    pub elapsed: Duration,
+}
+
+impl VerifyReport {
+    /// Returns whether no tensor failed verification.
+    pub fn is_ok(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+impl TensorBuffers {
+    /// Reads tensors' stored data through their checksums without keeping it, so a file of any
+    /// size can be checked, e.g. in CI, with memory for one 4 MiB chunk. Compressed data is
+    /// checked as stored, without decompressing it, and data stored in other files is checked
+    /// where it is.
+    ///
+    /// # Arguments
+    /// * `tensor_names` - The tensors to verify, or `None` for every tensor in the file.
+    ///
+    /// # Returns
+    /// Which tensors were verified and which failed. Errors are returned for unknown tensor names
+    /// and files that can't be read, not for corrupt data.
+    #[instrument(skip_all)]
+    pub async fn verify_data(&self, tensor_names: Option<&[&str]>) -> Result<VerifyReport> {
+        self.check_data_reads()?;
+        let start = Instant::now();
+        let tensors = match tensor_names {
+            Some(names) => {
+                let mut tensors = Vec::with_capacity(names.len());
+                for name in names {
+                    tensors.push(self.stored_metadata(hash_key(name)).await?);
+                }
+                tensors
+            }
+            // Unreadable tensors only remain in lenient mode, where loads skip them too.
+            None => {
+                let tensors = self.tensor_tables().await?.into_iter().flatten();
+                tensors.filter(|tensor| tensor_problem(tensor).is_none()).collect()
+            }
+        };
+
+        let mut report = VerifyReport::default();
+        // Deduplicated tensors share their stored data, which is checked once.
+        let mut regions = HashSet::new();
+        for tensor in tensors {
+            let name = tensor.name().to_string();
+            if tensor.checksum_algorithm() == ChecksumAlgorithm::None {
+                report.unchecked.push(name);
+                continue;
+            }
+            let (readers, offset) = match self.locate_data(&tensor).await {
+                Ok(located) => located,
+                Err(error) => {
+                    report.failed.push((name, error.to_string()));
+                    continue;
+                }
+            };
+            let stored_size = codec::stored_size(&tensor);
+            let location = tensor.external().map(|external| external.location());
+            if !regions.insert((location, offset, stored_size)) {
+                report.verified += 1;
+                continue;
+            }
+            match self.verify_tensor(&tensor, &readers, offset).await? {
+                None => report.verified += 1,
+                Some(problem) => {
+                    warn!(
+                        tensor = name.as_str(),
+                        problem = problem.as_str(),
+                        "Corrupt tensor data"
+                    );
+                    report.failed.push((name, problem));
+                }
+            }
+            report.bytes += stored_size as u64;
+        }
+        report.elapsed = start.elapsed();
+        info!(
+            verified = report.verified,
+            unchecked = report.unchecked.len(),
+            failed = report.failed.len(),
+            bytes = report.bytes,
+            "Verified file data"
+        );
+        Ok(report)
+    }
+
+    /// Reads a tensor's stored data, which starts at `offset` of the file `readers` read, through
+    /// its checksum a chunk at a time.
+    ///
+    /// # Returns
+    /// The checksum mismatch, if the data doesn't match.
+    async fn verify_tensor(
+        &self,
+        tensor: &TensorMetadata<'_>,
+        readers: &ReaderPool,
+        offset: u64,
+    ) -> Result<Option<String>> {
+        let stored_size = codec::stored_size(tensor);
+        let mut checksummer = Checksummer::new(tensor.checksum_algorithm())?;
+        let mut read = 0;
+        while read < stored_size {
+            let len = (stored_size - read).min(VERIFY_CHUNK_SIZE);
+            checksummer.update(&self.read_bytes_from(readers, offset + read as u64, len).await?);
+            read += len;
+        }
+        Ok(codec::check_checksum(tensor, checksummer.finish()).err().map(|error| error.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::NamedTempFile;
+    use tokio::fs::File;
+
+    use super::*;
+    use crate::{
+        ChecksumAlgorithm, MemoryBudget, OpenOptions, Tensor, TensorBuffersWrite,
+        TensorBuffersWriter, WriterOptions,
+    };
+
+    #[tokio::test]
+    async fn test_verify_data() {
+        let large = (0..3 << 20).map(|i| (i % 1000) as f32).collect::<Vec<_>>();
+        let tensors = vec![
+            Tensor::new("small", &[1.0f32, 2.0], vec![2]),
+            Tensor::new("large", &large, vec![large.len()]),
+            Tensor::new("copy", &[1.0f32, 2.0], vec![2]),
+        ];
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        let options =
+            WriterOptions::new().with_checksum(ChecksumAlgorithm::XxHash64).with_dedup(true);
+        let mut writer = TensorBuffersWriter::with_options(&mut file, options);
+        writer.write(tensors, vec![]).await.unwrap();
+
+        // Verifying takes a chunk of memory, not the tensor.
+        let url = format!("file://{}", tmp.path().display());
+        let budget = MemoryBudget::default().with_max_tensor_bytes(VERIFY_CHUNK_SIZE);
+        let options = OpenOptions::new().with_memory_budget(budget);
+        let tensor_buffers = TensorBuffers::open_with(&url, options).await.unwrap();
+        let large = tensor_buffers.stored_metadata(hash_key("large")).await.unwrap();
+        assert!(codec::stored_size(&large) > 2 * VERIFY_CHUNK_SIZE);
+        let report = tensor_buffers.verify_data(None).await.unwrap();
+        assert!(report.is_ok());
+        assert_eq!(report.verified, 3);
+        assert_eq!(report.bytes, 8 + codec::stored_size(&large) as u64);
+
+        // Corruption anywhere in the data is reported per tensor.
+        let mut bytes = std::fs::read(tmp.path()).unwrap();
+        let end = large.data_offset() as usize + codec::stored_size(&large);
+        bytes[end - 1] ^= 1;
+        std::fs::write(tmp.path(), &bytes).unwrap();
+        let report = tensor_buffers.verify_data(None).await.unwrap();
+        assert_eq!(report.verified, 2);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].0, "large");
+        assert!(report.failed[0].1.contains("checksum mismatch for tensor large"));
+        let report = tensor_buffers.verify_data(Some(&["small"])).await.unwrap();
+        assert!(report.is_ok() && report.verified == 1);
+        assert!(tensor_buffers.verify_data(Some(&["missing"])).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_verify_data_without_checksums() {
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        let tensor = Tensor::new("x", &[1.0f32], vec![1]);
+        TensorBuffersWriter::new(&mut file).write(vec![tensor], vec![]).await.unwrap();
+        let url = format!("file://{}", tmp.path().display());
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        let report = tensor_buffers.verify_data(None).await.unwrap();
+        assert_eq!((report.verified, report.unchecked), (0, vec!["x".to_string()]));
+    }
+}
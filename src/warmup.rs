@@ -0,0 +1,165 @@
+use std::{
+    collections::HashSet,
+    time::{Duration, Instant},
+};
+
+use tracing::{info, instrument};
+
+use crate::{codec, read_mode::tensor_problem, Result, TensorBuffers};
+
+/// How many bytes at the start of each tensor `WarmupOptions::touch_tensors` reads.
+const TOUCH_SIZE: usize = 4096;
+
+/// What [`TensorBuffers::warmup`] does beyond loading and validating the metadata.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct WarmupOptions {
+    /// Reads the first bytes of every tensor's data, which checks that all of it is reachable and
+    /// warms caches along the way.
+    pub touch_tensors: bool,
+    /// Fully loads tensors of at most this many bytes, checking their checksums and decompressing
+    /// them. Larger tensors are only touched, if `touch_tensors` is set.
+    pub load_tensors_up_to: Option<usize>,
+}
+
+impl WarmupOptions {
+    /// Sets whether the first bytes of every tensor are read.
+    pub fn with_touch_tensors(mut self, touch_tensors: bool) -> Self {
+        self.touch_tensors = touch_tensors;
+        self
+    }
+
+    /// Sets the size up to which tensors are fully loaded.
+    pub fn with_load_tensors_up_to(mut self, bytes: usize) -> Self {
+        self.load_tensors_up_to = Some(bytes);
+        self
+    }
+}
+
+/// What [`TensorBuffers::warmup`] found.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct WarmupReport {
+    /// Number of readable tensors.
+    pub tensors: usize,
+    /// Number of operations.
+    pub operations: usize,
+    /// Bytes needed to load every tensor.
+    pub total_bytes: u64,
+    /// Bytes of tensor data stored in the file and the files it refers to, after compression and
+    /// deduplication.
+    pub stored_bytes: u64,
+    /// Number of tensors whose first bytes were read.
+    pub touched: usize,
+    /// Number of tensors that were fully loaded.
+    pub loaded: usize,
+    /// How long the warmup took.
+    pub elapsed: Duration,
+}
+
+impl TensorBuffers {
+    /// Gets the file ready to serve and reports what loading it will take, e.g. for a readiness
+    /// probe before a model is declared loaded.
+    ///
+    /// Loads the metadata, which validates the footer, checks that every tensor's data lies within
+    /// the file, and then touches or loads tensors as `options` asks. Fails on the first problem.
+    ///
+    /// # Arguments
+    /// * `options` - Which tensors to read.
+    ///
+    /// # Returns
+    /// The number of tensors and operations, the bytes they need and what was read.
+    #[instrument(skip_all)]
+    pub async fn warmup(&self, options: WarmupOptions) -> Result<WarmupReport> {
+        let start = Instant::now();
+        let metadata = self.get_metadata_root().await?;
+        let mut report = WarmupReport {
+            operations: metadata.operations().map_or(0, |operations| operations.len()),
+            ..Default::default()
+        };
+        // Deduplicated tensors share their stored data, which is counted and read once.
+        let mut regions = HashSet::new();
+        // Unreadable tensors only remain in lenient mode, where loads skip them too.
+        let tensors = self.tensor_tables().await?.into_iter().flatten();
+        for tensor in tensors.filter(|t| tensor_problem(t).is_none()) {
+            let (readers, offset) = self.locate_data(&tensor).await?;
+            let stored_size = codec::stored_size(&tensor);
+            let size = tensor.data_size() as usize;
+            report.tensors += 1;
+            report.total_bytes += size as u64;
+            let location = tensor.external().map(|external| external.location());
+            if !regions.insert((location, offset, stored_size)) {
+                continue;
+            }
+            report.stored_bytes += stored_size as u64;
+
+            if options.load_tensors_up_to.is_some_and(|max| size <= max) {
+                let stored = self.read_bytes_from(&readers, offset, stored_size).await?;
+                if self.options().verify_checksums() {
+                    codec::verify_checksum(&tensor, &stored)?;
+                }
+                let dictionary = self.compression_dictionary(&tensor).await?;
+                let out = &mut vec![0; size];
+                codec::decompress(&stored, tensor.compression(), dictionary, tensor.codec(), out)?;
+                report.loaded += 1;
+            } else if options.touch_tensors && stored_size > 0 {
+                self.read_bytes_from(&readers, offset, stored_size.min(TOUCH_SIZE)).await?;
+                report.touched += 1;
+            }
+        }
+        report.elapsed = start.elapsed();
+        info!(
+            tensors = report.tensors,
+            total_bytes = report.total_bytes,
+            loaded = report.loaded,
+            touched = report.touched,
+            "Warmed up file"
+        );
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::NamedTempFile;
+    use tokio::fs::File;
+
+    use super::*;
+    use crate::{
+        ChecksumAlgorithm, Tensor, TensorBuffersWrite, TensorBuffersWriter, WriterOptions,
+    };
+
+    #[tokio::test]
+    async fn test_warmup() {
+        let large = vec![0.5f32; 2048];
+        let tensors = vec![
+            Tensor::new("small", &[1.0f32, 2.0], vec![2]),
+            Tensor::new("large", &large, vec![2048]),
+            Tensor::new("copy", &[1.0f32, 2.0], vec![2]),
+        ];
+        let tmp = NamedTempFile::new().unwrap();
+        let mut file = File::create(tmp.path()).await.unwrap();
+        let options =
+            WriterOptions::new().with_checksum(ChecksumAlgorithm::Crc32c).with_dedup(true);
+        let mut writer = TensorBuffersWriter::with_options(&mut file, options);
+        writer.write(tensors, vec![]).await.unwrap();
+
+        let url = format!("file://{}", tmp.path().display());
+        let tensor_buffers = TensorBuffers::open(&url).await.unwrap();
+        let report = tensor_buffers.warmup(WarmupOptions::default()).await.unwrap();
+        assert_eq!((report.tensors, report.operations), (3, 0));
+        assert_eq!(report.total_bytes, 16 + 8192);
+        assert_eq!(report.stored_bytes, 8 + 8192);
+        assert_eq!((report.touched, report.loaded), (0, 0));
+
+        let options = WarmupOptions::default().with_touch_tensors(true).with_load_tensors_up_to(64);
+        let report = tensor_buffers.warmup(options).await.unwrap();
+        assert_eq!((report.touched, report.loaded), (1, 1));
+
+        // A corrupt small tensor fails the warmup instead of the first request.
+        let mut bytes = std::fs::read(tmp.path()).unwrap();
+        let small = tensor_buffers.stored_metadata(crate::utils::hash_key("small")).await;
+        bytes[small.unwrap().data_offset() as usize] ^= 1;
+        std::fs::write(tmp.path(), &bytes).unwrap();
+        let error = tensor_buffers.warmup(options).await.unwrap_err();
+        assert!(error.to_string().contains("checksum mismatch for tensor"));
+    }
+}
@@ -0,0 +1,501 @@
+#[cfg(feature = "async")]
+use crate::{
+    codec_registry::registered_codec,
+    constants::{
+        MIN_VERSION_FOR_BLOOM_FILTER, MIN_VERSION_FOR_COMPRESSED_METADATA,
+        MIN_VERSION_FOR_COMPRESSION, MIN_VERSION_FOR_FEATURES, SUPPORTED_VERSIONS,
+    },
+    DataType, Result,
+};
+use crate::{
+    compression_policy::CompressionPolicy,
+    constants::{FormatVersion, VERSION},
+    generated::tensor_buffers::{ChecksumAlgorithm, Compression},
+    quantization::QuantizationPolicy,
+};
+
+/// Default Zstandard compression level.
+const DEFAULT_COMPRESSION_LEVEL: i32 = 3;
+
+/// Settings for [`crate::TensorBuffersWriter::with_options`], built up with the `with_*` methods.
+///
+/// The defaults write the same files as [`crate::TensorBuffersWriter::new`]: tensor data packed
+/// back to back in the given order, uncompressed and without checksums.
+///
+/// ```
+/// use tensorbuffers::{ChecksumAlgorithm, Compression, WriterOptions};
+///
+/// let options = WriterOptions::new()
+///     .with_alignment(64)
+///     .with_compression(Compression::Zstd, 9)
+///     .with_checksum(ChecksumAlgorithm::Crc32c);
+/// assert_eq!(options.alignment(), 64);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WriterOptions {
+    alignment: usize,
+    compression: Compression,
+    compression_level: i32,
+    checksum: ChecksumAlgorithm,
+    format_version: String,
+    parsed_version: Option<FormatVersion>,
+    sort_by_name: bool,
+    dedup: bool,
+    name_index: bool,
+    bloom_filter: bool,
+    leading_metadata: bool,
+    compressed_metadata: bool,
+    columnar_metadata: bool,
+    parallel_compression: bool,
+    compression_dictionary: usize,
+    compression_policy: CompressionPolicy,
+    custom_codec: Option<String>,
+    user_metadata: Option<Vec<u8>>,
+    quantization: Option<QuantizationPolicy>,
+}
+
+impl WriterOptions {
+    /// Creates options with the defaults.
+    pub fn new() -> Self {
+        WriterOptions {
+            alignment: 1,
+            compression: Compression::None,
+            compression_level: DEFAULT_COMPRESSION_LEVEL,
+            checksum: ChecksumAlgorithm::None,
+            format_version: VERSION.to_string(),
+            parsed_version: parse_version(VERSION),
+            sort_by_name: false,
+            dedup: false,
+            name_index: false,
+            bloom_filter: false,
+            leading_metadata: false,
+            compressed_metadata: false,
+            columnar_metadata: false,
+            parallel_compression: true,
+            compression_dictionary: 0,
+            compression_policy: CompressionPolicy::default(),
+            custom_codec: None,
+            user_metadata: None,
+            quantization: None,
+        }
+    }
+
+    /// Starts each tensor's data at a multiple of `alignment` bytes, padding with zeros, so it
+    /// can be used in place by SIMD code or memory-mapped readers. Must be a power of two.
+    pub fn with_alignment(mut self, alignment: usize) -> Self {
+        self.alignment = alignment;
+        self
+    }
+
+    /// Compresses each tensor's data with `compression`. Tensors that don't get smaller are
+    /// stored uncompressed.
+    ///
+    /// # Arguments
+    /// * `compression` - The codec.
+    /// * `level` - The compression level. Only used by Zstandard, where it ranges from 1 to 22.
+    pub fn with_compression(mut self, compression: Compression, level: i32) -> Self {
+        self.compression = compression;
+        self.compression_level = level;
+        self
+    }
+
+    /// Stores a checksum of each tensor's stored data, which readers verify on load.
+    pub fn with_checksum(mut self, algorithm: ChecksumAlgorithm) -> Self {
+        self.checksum = algorithm;
+        self
+    }
+
+    /// Writes the given format version, e.g. "1.0.0" for readers that predate compression and
+    /// checksums, or "1.1.0" for readers that predate the metadata checksum. Options the version
+    /// doesn't support are rejected when writing.
+    pub fn with_format_version(mut self, version: &str) -> Self {
+        self.format_version = version.to_string();
+        self.parsed_version = parse_version(version);
+        self
+    }
+
+    /// Writes tensor data in name order instead of the given order, so the same tensors always
+    /// produce the same file.
+    pub fn with_sort_by_name(mut self, sort_by_name: bool) -> Self {
+        self.sort_by_name = sort_by_name;
+        self
+    }
+
+    /// Stores the data of tensors with identical contents once, with every such tensor pointing
+    /// at the same bytes.
+    pub fn with_dedup(mut self, dedup: bool) -> Self {
+        self.dedup = dedup;
+        self
+    }
+
+    /// Writes a name index before the metadata, so readers opened with
+    /// `OpenOptions::with_name_index` can find one tensor without loading all of the metadata.
+    /// Readers that don't use it ignore it.
+    pub fn with_name_index(mut self, name_index: bool) -> Self {
+        self.name_index = name_index;
+        self
+    }
+
+    /// Stores a bloom filter over tensor ids in the footer, so `TensorBuffers::contains_tensor`
+    /// can usually rule out missing tensors without loading the metadata. Needs format version
+    /// 1.3.0, and readers of earlier versions can't read the file.
+    pub fn with_bloom_filter(mut self, bloom_filter: bool) -> Self {
+        self.bloom_filter = bloom_filter;
+        self
+    }
+
+    /// Also writes a copy of the metadata right after the leading magic bytes, so consumers reading
+    /// the file front to back, e.g. from a pipe, can find the tensors with a
+    /// `TensorBuffersStreamReader` before reaching them. Readers that don't use it ignore it. The
+    /// data is encoded in memory before any of it is written.
+    pub fn with_leading_metadata(mut self, leading_metadata: bool) -> Self {
+        self.leading_metadata = leading_metadata;
+        self
+    }
+
+    /// Compresses the metadata with Zstandard, which shrinks the metadata of files with very many
+    /// tensors several times over and speeds up opening them over slow links. Needs format version
+    /// 1.4.0. The leading copy of the metadata and the name index stay uncompressed.
+    pub fn with_compressed_metadata(mut self, compressed_metadata: bool) -> Self {
+        self.compressed_metadata = compressed_metadata;
+        self
+    }
+
+    /// Stores the tensors' metadata as parallel vectors of ids, names, shapes, data types, offsets,
+    /// sizes and checksums instead of a table per tensor, for files with extreme tensor counts.
+    /// The metadata is a fraction of the size, and `TensorBuffers` opens such files without
+    /// decoding an entry per tensor. Needs format version 1.4.0, and can't describe tensors stored
+    /// in other files, aliases, custom codecs, extension types or per-tensor user metadata.
+    pub fn with_columnar_metadata(mut self, columnar_metadata: bool) -> Self {
+        self.columnar_metadata = columnar_metadata;
+        self
+    }
+
+    /// Compresses tensor data on the rayon pool, several tensors at a time, while the writer
+    /// writes the tensors already compressed in order. On by default; turning it off compresses
+    /// each tensor on the writing task. Either way the file is the same.
+    pub fn with_parallel_compression(mut self, parallel_compression: bool) -> Self {
+        self.parallel_compression = parallel_compression;
+        self
+    }
+
+    /// Trains a Zstandard dictionary of at most `max_size` bytes on samples of the tensors' data
+    /// and compresses every tensor with it, which shrinks files of many small tensors that barely
+    /// compress on their own, e.g. norms and biases. The dictionary is stored once in the metadata.
+    /// Needs Zstandard compression and format version 1.4.0. 0, the default, trains none; if too
+    /// little data is written to train one, tensors are compressed without it.
+    pub fn with_compression_dictionary(mut self, max_size: usize) -> Self {
+        self.compression_dictionary = max_size;
+        self
+    }
+
+    /// Decides per tensor whether and how to compress it, e.g. leaving small or fp16 tensors
+    /// uncompressed or compressing some tensors harder. Tensors the policy leaves to the defaults
+    /// are compressed as `with_compression` says.
+    pub fn with_compression_policy(mut self, policy: CompressionPolicy) -> Self {
+        self.compression_policy = policy;
+        self
+    }
+
+    /// Encodes each tensor's data with the custom codec registered as `id`, see
+    /// [`crate::CustomCodec`], storing it as `Compression::Custom` even if it doesn't get smaller,
+    /// since the codec may encrypt it. Policy overrides to `Compression::Custom` use the codec
+    /// too. Needs format version 1.4.0.
+    pub fn with_custom_codec(mut self, id: &str) -> Self {
+        self.compression = Compression::Custom;
+        self.custom_codec = Some(id.to_string());
+        self
+    }
+
+    /// Stores `bytes` as the file's user metadata, e.g. a FlexBuffer of an organization's internal
+    /// fields, which readers return unchanged from `TensorBuffers::user_metadata`.
+    pub fn with_user_metadata(mut self, bytes: Vec<u8>) -> Self {
+        self.user_metadata = Some(bytes);
+        self
+    }
+
+    /// Quantizes the Float32 and Float16 tensors `policy` applies to as they are written, storing
+    /// 8-bit or 4-bit integers with scales computed from the data instead of the floating point
+    /// values. Needs format version 1.4.0, and can't be combined with columnar metadata once a
+    /// tensor is quantized.
+    pub fn with_quantization(mut self, policy: QuantizationPolicy) -> Self {
+        self.quantization = Some(policy);
+        self
+    }
+
+    /// Returns the alignment of tensor data in bytes.
+    pub fn alignment(&self) -> usize {
+        self.alignment
+    }
+
+    /// Returns the codec tensor data is compressed with.
+    pub fn compression(&self) -> Compression {
+        self.compression
+    }
+
+    /// Returns the compression level.
+    pub fn compression_level(&self) -> i32 {
+        self.compression_level
+    }
+
+    /// Returns the checksum algorithm.
+    pub fn checksum(&self) -> ChecksumAlgorithm {
+        self.checksum
+    }
+
+    /// Returns the format version written to the file.
+    pub fn format_version(&self) -> &str {
+        &self.format_version
+    }
+
+    /// Returns whether tensor data is written in name order.
+    pub fn sort_by_name(&self) -> bool {
+        self.sort_by_name
+    }
+
+    /// Returns whether identical tensor data is stored once.
+    pub fn dedup(&self) -> bool {
+        self.dedup
+    }
+
+    /// Returns whether a name index is written.
+    pub fn name_index(&self) -> bool {
+        self.name_index
+    }
+
+    /// Returns whether a bloom filter is stored in the footer.
+    pub fn bloom_filter(&self) -> bool {
+        self.bloom_filter
+    }
+
+    /// Returns whether a copy of the metadata precedes the tensor data.
+    pub fn leading_metadata(&self) -> bool {
+        self.leading_metadata
+    }
+
+    /// Returns whether the metadata is compressed.
+    pub fn compressed_metadata(&self) -> bool {
+        self.compressed_metadata
+    }
+
+    /// Returns whether the tensors' metadata is stored as columns.
+    pub fn columnar_metadata(&self) -> bool {
+        self.columnar_metadata
+    }
+
+    /// Returns whether tensor data is compressed on the rayon pool.
+    pub fn parallel_compression(&self) -> bool {
+        self.parallel_compression
+    }
+
+    /// Returns the largest compression dictionary to train, or 0 if none is trained.
+    pub fn compression_dictionary(&self) -> usize {
+        self.compression_dictionary
+    }
+
+    /// Returns the per-tensor compression policy.
+    pub fn compression_policy(&self) -> &CompressionPolicy {
+        &self.compression_policy
+    }
+
+    /// Returns the id of the custom codec, if one was set.
+    pub fn custom_codec(&self) -> Option<&str> {
+        self.custom_codec.as_deref()
+    }
+
+    /// Returns the file's user metadata, if any was set.
+    pub fn user_metadata(&self) -> Option<&[u8]> {
+        self.user_metadata.as_deref()
+    }
+
+    /// Returns which tensors are quantized, if any.
+    pub fn quantization(&self) -> Option<&QuantizationPolicy> {
+        self.quantization.as_ref()
+    }
+
+    /// Returns the codec and level to compress a tensor with, as the policy chooses.
+    #[cfg(feature = "async")]
+    pub(crate) fn compression_for(
+        &self,
+        name: &str,
+        data_type: DataType,
+        size: usize,
+    ) -> (Compression, i32) {
+        let default = (self.compression, self.compression_level);
+        self.compression_policy.choose(name, data_type, size, default)
+    }
+
+    /// Returns whether the format version is at least `minimum`.
+    #[cfg(feature = "async")]
+    pub(crate) fn supports(&self, minimum: FormatVersion) -> bool {
+        self.parsed_version.is_some_and(|version| version >= minimum)
+    }
+
+    /// Checks that the options are consistent and supported by the format version.
+    #[cfg(feature = "async")]
+    pub(crate) fn validate(&self) -> Result<()> {
+        if !self.alignment.is_power_of_two() {
+            return Err(format!("Alignment {} is not a power of two", self.alignment).into());
+        }
+        if !SUPPORTED_VERSIONS.contains(&self.format_version.as_str()) {
+            return Err(format!("Unsupported format version {}", self.format_version).into());
+        }
+        if !self.supports(MIN_VERSION_FOR_COMPRESSION)
+            && (self.compression != Compression::None
+                || self.compression_policy.overrides_compress()
+                || self.checksum != ChecksumAlgorithm::None)
+        {
+            return Err(format!(
+                "Format version {} does not support compression or checksums",
+                self.format_version
+            )
+            .into());
+        }
+        if self.bloom_filter && !self.supports(MIN_VERSION_FOR_BLOOM_FILTER) {
+            return Err(format!(
+                "Format version {} does not support bloom filters",
+                self.format_version
+            )
+            .into());
+        }
+        if self.compressed_metadata && !self.supports(MIN_VERSION_FOR_COMPRESSED_METADATA) {
+            return Err(format!(
+                "Format version {} does not support compressed metadata",
+                self.format_version
+            )
+            .into());
+        }
+        if self.columnar_metadata && !self.supports(MIN_VERSION_FOR_FEATURES) {
+            return Err(format!(
+                "Format version {} does not support columnar metadata",
+                self.format_version
+            )
+            .into());
+        }
+        if self.compression == Compression::ZstdDictionary {
+            return Err(
+                "Compression::ZstdDictionary is set with with_compression_dictionary".into()
+            );
+        }
+        if self.compression_dictionary > 0 {
+            if self.compression != Compression::Zstd {
+                return Err("A compression dictionary needs Zstandard compression".into());
+            }
+            if !self.supports(MIN_VERSION_FOR_FEATURES) {
+                return Err(format!(
+                    "Format version {} does not support compression dictionaries",
+                    self.format_version
+                )
+                .into());
+            }
+        }
+        self.compression_policy.validate()?;
+        if self.compression == Compression::Custom
+            || self.compression_policy.overrides_with(Compression::Custom)
+        {
+            let id = self
+                .custom_codec
+                .as_deref()
+                .ok_or("Compression::Custom needs a codec set with with_custom_codec")?;
+            registered_codec(id)?;
+            if !self.supports(MIN_VERSION_FOR_FEATURES) {
+                return Err(format!(
+                    "Format version {} does not support custom codecs",
+                    self.format_version
+                )
+                .into());
+            }
+        }
+        if let Some(policy) = &self.quantization {
+            policy.validate()?;
+            if !self.supports(MIN_VERSION_FOR_FEATURES) {
+                return Err(format!(
+                    "Format version {} does not support quantization",
+                    self.format_version
+                )
+                .into());
+            }
+        }
+        if self.compression.variant_name().is_none() {
+            return Err(format!("Unsupported compression {:?}", self.compression).into());
+        }
+        if self.checksum.variant_name().is_none() {
+            return Err(format!("Unsupported checksum algorithm {:?}", self.checksum).into());
+        }
+        if self.compression == Compression::Zstd
+            && !zstd::compression_level_range().contains(&self.compression_level)
+        {
+            return Err(format!("Invalid zstd compression level {}", self.compression_level).into());
+        }
+        Ok(())
+    }
+}
+
+/// Parses a format version such as "1.4.0" into its ordered (major, minor, patch) parts.
+fn parse_version(version: &str) -> Option<FormatVersion> {
+    let mut parts = version.split('.').map(|part| part.parse().ok());
+    let parsed = (parts.next()??, parts.next()??, parts.next()??);
+    parts.next().is_none().then_some(parsed)
+}
+
+impl Default for WriterOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(all(test, feature = "async"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        assert!(WriterOptions::new().validate().is_ok());
+        assert!(WriterOptions::new().with_alignment(64).validate().is_ok());
+        assert!(WriterOptions::new().with_alignment(48).validate().is_err());
+        assert!(WriterOptions::new().with_alignment(0).validate().is_err());
+        assert!(WriterOptions::new().with_format_version("0.9.0").validate().is_err());
+        assert!(WriterOptions::new().with_format_version("1.4").validate().is_err());
+        assert_eq!(parse_version("1.4.0"), Some((1, 4, 0)));
+        assert!(parse_version("1.10.0") > parse_version("1.4.0"));
+        assert_eq!(parse_version("1.4.0.1"), None);
+        assert_eq!(parse_version("1.x.0"), None);
+        let old = WriterOptions::new().with_format_version("1.0.0");
+        assert!(old.validate().is_ok());
+        assert!(old.with_checksum(ChecksumAlgorithm::Crc32c).validate().is_err());
+        let zstd = WriterOptions::new().with_compression(Compression::Zstd, 100);
+        assert!(zstd.validate().is_err());
+        let bloom = WriterOptions::new().with_bloom_filter(true);
+        assert!(bloom.validate().is_ok());
+        assert!(bloom.with_format_version("1.2.0").validate().is_err());
+        let compressed = WriterOptions::new().with_compressed_metadata(true);
+        assert!(compressed.validate().is_ok());
+        assert!(compressed.with_format_version("1.3.0").validate().is_err());
+        let columnar = WriterOptions::new().with_columnar_metadata(true);
+        assert!(columnar.validate().is_ok());
+        assert!(columnar.with_format_version("1.3.0").validate().is_err());
+        let quantized = WriterOptions::new().with_quantization(QuantizationPolicy::int4());
+        assert!(quantized.validate().is_ok());
+        assert!(quantized.clone().with_format_version("1.3.0").validate().is_err());
+        let policy = QuantizationPolicy { bits: 2, ..QuantizationPolicy::int8() };
+        assert!(quantized.with_quantization(policy).validate().is_err());
+        let dictionary = WriterOptions::new().with_compression_dictionary(16 << 10);
+        assert!(dictionary.validate().is_err());
+        let dictionary = dictionary.with_compression(Compression::Zstd, 3);
+        assert!(dictionary.validate().is_ok());
+        assert!(dictionary.with_format_version("1.3.0").validate().is_err());
+        let direct = WriterOptions::new().with_compression(Compression::ZstdDictionary, 3);
+        assert!(direct.validate().is_err());
+        let policy = CompressionPolicy::default().with_override("w", Compression::Lz4, 0);
+        let policy = WriterOptions::new().with_compression_policy(policy);
+        assert!(policy.validate().is_ok());
+        assert!(policy.with_format_version("1.0.0").validate().is_err());
+        let custom = WriterOptions::new().with_compression(Compression::Custom, 0);
+        assert!(custom.validate().is_err());
+        let custom = WriterOptions::new().with_custom_codec("test.unregistered");
+        assert_eq!(
+            custom.validate().unwrap_err().to_string(),
+            "Codec test.unregistered isn't registered"
+        );
+    }
+}